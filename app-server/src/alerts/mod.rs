@@ -0,0 +1,270 @@
+//! Alerting rules on score thresholds, e.g. "average `relevance` in group X drops below 0.7".
+//!
+//! Evaluation rules are checked against the average of the score in an evaluation after each
+//! run that adds results. Online rules are checked periodically against the average of the span
+//! scores of online evaluators over a window. A rule fires while its value breaches the threshold
+//! and resolves once it doesn't, and its channels are notified only when the state changes.
+//! Each time a rule starts firing an incident is recorded, which gets a summary of the likely
+//! root cause, see `root_cause`.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    db::{
+        self,
+        activity::{ActivityType, NewActivity},
+        alerts::{AlertChannelType, AlertComparison, AlertRule, AlertState},
+        DB,
+    },
+    provider_api_keys,
+};
+
+pub mod notifiers;
+pub mod root_cause;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const ONLINE_EVALUATION_INTERVAL_SECONDS: i64 = 60;
+
+/// Targets are encrypted with the rule and channel type as associated data, so that they can't
+/// be moved to another rule
+fn target_name(rule_id: &Uuid, channel_type: AlertChannelType) -> String {
+    format!("alert_channel:{}:{:?}", rule_id, channel_type)
+}
+
+pub fn encode_target(
+    rule_id: &Uuid,
+    channel_type: AlertChannelType,
+    target: &String,
+) -> provider_api_keys::ValueAndNonceHex {
+    provider_api_keys::encode_api_key(&target_name(rule_id, channel_type), target)
+}
+
+pub fn is_breached(comparison: AlertComparison, threshold: f64, value: f64) -> bool {
+    match comparison {
+        AlertComparison::BELOW => value < threshold,
+        AlertComparison::ABOVE => value > threshold,
+    }
+}
+
+/// Where the value of the rule came from, included in the notification
+pub enum AlertContext {
+    Evaluation {
+        id: Uuid,
+        name: String,
+    },
+    Window {
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    },
+}
+
+/// Records the value of the rule and notifies its channels if its state changed
+pub async fn apply_value(
+    db: Arc<DB>,
+    rule: &AlertRule,
+    value: f64,
+    context: AlertContext,
+) -> Result<()> {
+    let state = if is_breached(rule.comparison, rule.threshold, value) {
+        AlertState::FIRING
+    } else {
+        AlertState::RESOLVED
+    };
+    if !db::alerts::record_alert_evaluation(&db.pool, &rule.id, value, state).await? {
+        return Ok(());
+    }
+
+    let comparison = match rule.comparison {
+        AlertComparison::BELOW => "below",
+        AlertComparison::ABOVE => "above",
+    };
+    let (source, evaluation_id, window) = match &context {
+        AlertContext::Evaluation { id, name } => (format!("in evaluation {name}"), Some(*id), None),
+        AlertContext::Window {
+            start_time,
+            end_time,
+        } => (
+            format!("over the last {} minutes", rule.window_minutes),
+            None,
+            Some((*start_time, *end_time)),
+        ),
+    };
+    let incident_id = if state == AlertState::FIRING {
+        match db::alerts::create_alert_incident(&db.pool, &rule.id, value, evaluation_id, window)
+            .await
+        {
+            Ok(id) => Some(id),
+            Err(e) => {
+                log::error!("Failed to record incident of alert {}: {:?}", rule.id, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let subject = match state {
+        AlertState::FIRING => format!("Alert firing: {}", rule.name),
+        AlertState::RESOLVED => format!("Alert resolved: {}", rule.name),
+    };
+    let text = format!(
+        "Average {} is {:.4} {}, the alert fires {} {}",
+        rule.score_name, value, source, comparison, rule.threshold
+    );
+    let payload = json!({
+        "ruleId": rule.id,
+        "ruleName": rule.name,
+        "projectId": rule.project_id,
+        "state": state,
+        "scoreName": rule.score_name,
+        "value": value,
+        "comparison": rule.comparison,
+        "threshold": rule.threshold,
+        "evaluationId": evaluation_id,
+        "incidentId": incident_id,
+        "timestamp": Utc::now(),
+    });
+
+    if state == AlertState::FIRING {
+        let activity = NewActivity {
+            project_id: rule.project_id,
+            activity_type: ActivityType::ALERT_FIRED,
+            actor_id: None,
+            resource_id: Some(rule.id),
+            summary: subject.clone(),
+            details: payload.clone(),
+        };
+        if let Err(e) = db::activity::record_activity(&db.pool, &activity).await {
+            log::error!("Failed to record activity of alert {}: {:?}", rule.id, e);
+        }
+    }
+
+    for channel in db::alerts::get_alert_channels(&db.pool, &rule.id).await? {
+        let target = match provider_api_keys::decode_api_key(
+            &target_name(&rule.id, channel.channel_type),
+            &channel.target_nonce,
+            &channel.target_value,
+        ) {
+            Ok(target) => target,
+            Err(e) => {
+                log::error!("Failed to decode target of alert {}: {:?}", rule.id, e);
+                continue;
+            }
+        };
+        if let Err(e) =
+            notifiers::send(channel.channel_type, &target, &subject, &text, &payload).await
+        {
+            log::error!(
+                "Failed to send alert {} to {:?}: {:?}",
+                rule.id,
+                channel.channel_type,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the evaluation rules of the group against the averages of the evaluation's scores.
+/// Rules on scores the evaluation doesn't have are left as they are.
+pub async fn check_evaluation_alerts(
+    db: Arc<DB>,
+    project_id: Uuid,
+    evaluation_id: Uuid,
+    evaluation_name: String,
+    group_id: String,
+) -> Result<()> {
+    let rules = db::alerts::get_evaluation_alert_rules(&db.pool, &project_id, &group_id).await?;
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let (_, averages) =
+        db::evaluations::get_evaluation_score_averages(&db.pool, evaluation_id).await?;
+
+    for rule in rules {
+        let Some(average) = averages
+            .iter()
+            .find(|average| average.name == rule.score_name)
+        else {
+            continue;
+        };
+        let context = AlertContext::Evaluation {
+            id: evaluation_id,
+            name: evaluation_name.clone(),
+        };
+        if let Err(e) = apply_value(db.clone(), &rule, average.average, context).await {
+            log::error!("Failed to check alert {}: {:?}", rule.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the online rules against the average span score over their windows. Rules without
+/// span scores in their window are left as they are.
+pub async fn run_online_alerts_periodically(db: Arc<DB>, analytics_store: Arc<dyn AnalyticsStore>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        let rules = match db::alerts::claim_online_alert_rules(
+            &db.pool,
+            ONLINE_EVALUATION_INTERVAL_SECONDS,
+        )
+        .await
+        {
+            Ok(rules) => rules,
+            Err(e) => {
+                log::error!("Failed to claim online alert rules: {:?}", e);
+                continue;
+            }
+        };
+
+        for rule in rules {
+            let end_time = Utc::now();
+            let start_time = end_time - chrono::Duration::minutes(rule.window_minutes as i64);
+            let average = analytics_store
+                .get_average_span_score(
+                    rule.project_id,
+                    rule.score_name.clone(),
+                    start_time,
+                    end_time,
+                )
+                .await;
+            let result = match average {
+                // the average of no scores is NaN
+                Ok(average) if average.is_finite() => {
+                    let context = AlertContext::Window {
+                        start_time,
+                        end_time,
+                    };
+                    apply_value(db.clone(), &rule, average, context).await
+                }
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                log::error!("Failed to check alert {}: {:?}", rule.id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_breached() {
+        assert!(is_breached(AlertComparison::BELOW, 0.7, 0.69));
+        assert!(!is_breached(AlertComparison::BELOW, 0.7, 0.7));
+        assert!(is_breached(AlertComparison::ABOVE, 2.0, 2.5));
+        assert!(!is_breached(AlertComparison::ABOVE, 2.0, 1.0));
+    }
+}
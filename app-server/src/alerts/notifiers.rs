@@ -0,0 +1,44 @@
+use anyhow::Result;
+use serde_json::json;
+
+use crate::{db::alerts::AlertChannelType, network::egress};
+
+const RESEND_API_URL: &str = "https://api.resend.com/emails";
+const DEFAULT_EMAIL_FROM: &str = "Laminar <alerts@lmnr.ai>";
+
+/// Sends the alert to a webhook as JSON, to a Slack incoming webhook as a message, or to an
+/// email address through Resend. Emails are skipped if `RESEND_API_KEY` is not set.
+pub async fn send(
+    channel_type: AlertChannelType,
+    target: &str,
+    subject: &str,
+    text: &str,
+    payload: &serde_json::Value,
+) -> Result<()> {
+    let client = egress::http_client();
+    let request = match channel_type {
+        AlertChannelType::WEBHOOK => client.post(target).json(payload),
+        AlertChannelType::SLACK => client
+            .post(target)
+            .json(&json!({ "text": format!("*{subject}*\n{text}") })),
+        AlertChannelType::EMAIL => {
+            let Ok(api_key) = std::env::var("RESEND_API_KEY") else {
+                log::warn!("RESEND_API_KEY is not set, alert email is not sent");
+                return Ok(());
+            };
+            let from = std::env::var("ALERT_EMAIL_FROM").unwrap_or(DEFAULT_EMAIL_FROM.to_string());
+            client
+                .post(RESEND_API_URL)
+                .bearer_auth(api_key)
+                .json(&json!({
+                    "from": from,
+                    "to": [target],
+                    "subject": subject,
+                    "text": text,
+                }))
+        }
+    };
+    request.send().await?.error_for_status()?;
+
+    Ok(())
+}
@@ -0,0 +1,375 @@
+//! Root-cause summaries of alert incidents. When a rule starts firing, the traces behind the
+//! scores that breach it are sampled and counted by model, error category, prompt version and
+//! user cohort, and a language model describes what these breakdowns have in common. The
+//! breakdowns are stored on the incident even if the model can't be called.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    cache::Cache,
+    db::{
+        self,
+        alerts::{AlertComparison, AlertIncident, AlertRule, BreachingScore, TraceCharacteristics},
+        DB,
+    },
+    language_model::{
+        providers::utils::missing_env_vars_for_model, ChatMessage, ChatMessageContent,
+        LanguageModelRunner, NodeInfo,
+    },
+    traces::evaluators::get_stored_env,
+};
+
+use super::is_breached;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// Incidents whose summary wasn't stored this long after being claimed are claimed again
+const RETRY_AFTER_SECONDS: i64 = 600;
+const INCIDENTS_PER_POLL: i64 = 10;
+const DEFAULT_MODEL: &str = "openai:gpt-4o-mini";
+/// Number of most recent span scores the breaching ones are sampled from
+const MAX_CANDIDATES: u64 = 1000;
+const SAMPLE_SIZE: usize = 50;
+
+const SYSTEM_PROMPT: &str = r#"You investigate alerts of LLM applications. An alert fired because the average of a score breached its threshold. You are given the traces with the worst scores, with the model, error category, prompt version and user cohort of each trace, and how often each value occurs in the sample.
+
+Describe in at most 4 sentences what the failing traces have in common and which of these characteristics most likely explains the alert. Say so if nothing stands out. Answer with plain text only."#;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GroupCount {
+    /// None for traces without the characteristic
+    pub value: Option<String>,
+    pub count: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RootCauseSummary {
+    /// Traces with the worst scores, the worst first
+    pub trace_ids: Vec<Uuid>,
+    pub models: Vec<GroupCount>,
+    pub error_categories: Vec<GroupCount>,
+    pub prompt_versions: Vec<GroupCount>,
+    pub user_cohorts: Vec<GroupCount>,
+    /// Written by the language model, None if it isn't configured for the project or failed
+    pub text: Option<String>,
+}
+
+/// Model in the format of "provider:model_name", set with `ALERT_SUMMARY_MODEL`
+fn model() -> String {
+    std::env::var("ALERT_SUMMARY_MODEL").unwrap_or(DEFAULT_MODEL.to_string())
+}
+
+/// Counts of the values, the most frequent first
+fn group_by<'a>(values: impl Iterator<Item = &'a Option<String>>) -> Vec<GroupCount> {
+    let mut counts = BTreeMap::<Option<String>, usize>::new();
+    for value in values {
+        *counts.entry(value.clone()).or_default() += 1;
+    }
+    let mut groups = counts
+        .into_iter()
+        .map(|(value, count)| GroupCount { value, count })
+        .collect::<Vec<_>>();
+    // stable, so that ties keep the order of the values
+    groups.sort_by(|a, b| b.count.cmp(&a.count));
+    groups
+}
+
+/// Scores that breach the rule in the evaluation or window of the incident, the furthest first
+async fn sample_breaching_scores(
+    db: &DB,
+    analytics_store: &Arc<dyn AnalyticsStore>,
+    rule: &AlertRule,
+    incident: &AlertIncident,
+) -> Result<Vec<BreachingScore>> {
+    if let Some(evaluation_id) = incident.evaluation_id {
+        return db::alerts::get_breaching_evaluation_scores(
+            &db.pool,
+            &evaluation_id,
+            &rule.score_name,
+            rule.comparison,
+            rule.threshold,
+            SAMPLE_SIZE as i64,
+        )
+        .await;
+    }
+    let (Some(start_time), Some(end_time)) = (incident.window_start, incident.window_end) else {
+        return Ok(vec![]);
+    };
+
+    let mut scores = analytics_store
+        .get_span_score_scatter(
+            rule.project_id,
+            rule.score_name.clone(),
+            start_time,
+            end_time,
+            MAX_CANDIDATES,
+        )
+        .await?
+        .into_iter()
+        .filter(|point| is_breached(rule.comparison, rule.threshold, point.score))
+        .map(|point| BreachingScore {
+            trace_id: point.trace_id,
+            score: point.score,
+        })
+        .collect::<Vec<_>>();
+    match rule.comparison {
+        AlertComparison::BELOW => scores.sort_by(|a, b| a.score.total_cmp(&b.score)),
+        AlertComparison::ABOVE => scores.sort_by(|a, b| b.score.total_cmp(&a.score)),
+    }
+    scores.truncate(SAMPLE_SIZE);
+    Ok(scores)
+}
+
+fn user_message(
+    rule: &AlertRule,
+    incident: &AlertIncident,
+    scores: &[BreachingScore],
+    characteristics: &HashMap<Uuid, TraceCharacteristics>,
+    summary: &RootCauseSummary,
+) -> String {
+    let comparison = match rule.comparison {
+        AlertComparison::BELOW => "below",
+        AlertComparison::ABOVE => "above",
+    };
+    let or_unknown = |value: &Option<String>| value.clone().unwrap_or("unknown".to_string());
+    let mut message = format!(
+        "Alert: average {} was {:.4}, the alert fires {} {}\n\nTraces with the worst scores:",
+        rule.score_name, incident.value, comparison, rule.threshold
+    );
+    for (i, score) in scores.iter().enumerate() {
+        let Some(trace) = characteristics.get(&score.trace_id) else {
+            continue;
+        };
+        message.push_str(&format!(
+            "\n#{} score {:.4}, model: {}, error category: {}, prompt version: {}, user cohort: {}",
+            i + 1,
+            score.score,
+            or_unknown(&trace.model),
+            trace.error_category.as_deref().unwrap_or("none"),
+            or_unknown(&trace.prompt_version),
+            or_unknown(&trace.user_cohort),
+        ));
+    }
+    for (name, groups, missing) in [
+        ("Models", &summary.models, "unknown"),
+        ("Error categories", &summary.error_categories, "none"),
+        ("Prompt versions", &summary.prompt_versions, "unknown"),
+        ("User cohorts", &summary.user_cohorts, "unknown"),
+    ] {
+        let counts = groups
+            .iter()
+            .map(|group| {
+                let value = group.value.as_deref().unwrap_or(missing);
+                format!("{} ({})", value, group.count)
+            })
+            .collect::<Vec<_>>();
+        message.push_str(&format!("\n\n{}: {}", name, counts.join(", ")));
+    }
+    message
+}
+
+async fn describe(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    env: &HashMap<String, String>,
+    message: String,
+) -> Result<String> {
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: ChatMessageContent::Text(SYSTEM_PROMPT.to_string()),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: ChatMessageContent::Text(message),
+        },
+    ];
+    let node_info = NodeInfo {
+        id: Uuid::new_v4(),
+        node_id: Uuid::nil(),
+        node_name: "alert_root_cause_summary".to_string(),
+        node_type: "LLM".to_string(),
+    };
+
+    let completion = language_model
+        .chat_completion(
+            &model(),
+            &messages,
+            &json!({ "temperature": 0 }),
+            env,
+            None,
+            &node_info,
+            db,
+            cache,
+        )
+        .await?;
+
+    Ok(completion.text_message().trim().to_string())
+}
+
+pub async fn summarize_incident(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    language_model: Arc<LanguageModelRunner>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    incident: &AlertIncident,
+) -> Result<RootCauseSummary> {
+    let rule = db::alerts::get_alert_rule(&db.pool, &incident.rule_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Alert rule {} not found", incident.rule_id))?;
+
+    let scores = sample_breaching_scores(&db, &analytics_store, &rule, incident).await?;
+    let mut seen = HashSet::new();
+    let trace_ids = scores
+        .iter()
+        .map(|score| score.trace_id)
+        .filter(|trace_id| seen.insert(*trace_id))
+        .collect::<Vec<_>>();
+    let characteristics =
+        db::alerts::get_trace_characteristics(&db.pool, &rule.project_id, &trace_ids)
+            .await?
+            .into_iter()
+            .map(|trace| (trace.trace_id, trace))
+            .collect::<HashMap<_, _>>();
+    let sampled = trace_ids
+        .iter()
+        .filter_map(|trace_id| characteristics.get(trace_id))
+        .collect::<Vec<_>>();
+
+    let mut summary = RootCauseSummary {
+        models: group_by(sampled.iter().map(|trace| &trace.model)),
+        error_categories: group_by(sampled.iter().map(|trace| &trace.error_category)),
+        prompt_versions: group_by(sampled.iter().map(|trace| &trace.prompt_version)),
+        user_cohorts: group_by(sampled.iter().map(|trace| &trace.user_cohort)),
+        trace_ids,
+        text: None,
+    };
+    if sampled.is_empty() {
+        return Ok(summary);
+    }
+
+    let env = get_stored_env(db.clone(), rule.project_id).await?;
+    let missing = missing_env_vars_for_model(&model(), &env);
+    if !missing.is_empty() {
+        log::info!(
+            "Skipping the root-cause text of alert incident {}, missing: {}",
+            incident.id,
+            missing.join(", ")
+        );
+        return Ok(summary);
+    }
+    let message = user_message(&rule, incident, &scores, &characteristics, &summary);
+    match describe(language_model, db, cache, &env, message).await {
+        Ok(text) => summary.text = Some(text),
+        Err(e) => log::warn!(
+            "Failed to write the root-cause text of alert incident {}: {:?}",
+            incident.id,
+            e
+        ),
+    }
+
+    Ok(summary)
+}
+
+async fn summarize_and_store(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    language_model: Arc<LanguageModelRunner>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    incident: &AlertIncident,
+) -> Result<()> {
+    let summary =
+        summarize_incident(db.clone(), cache, language_model, analytics_store, incident).await?;
+    db::alerts::set_alert_incident_summary(&db.pool, &incident.id, &serde_json::to_value(summary)?)
+        .await
+}
+
+/// Summarizes new incidents. Incidents that fail are retried after `RETRY_AFTER_SECONDS`.
+pub async fn run_root_cause_summaries_periodically(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    language_model: Arc<LanguageModelRunner>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        let incidents = match db::alerts::claim_unsummarized_alert_incidents(
+            &db.pool,
+            RETRY_AFTER_SECONDS,
+            INCIDENTS_PER_POLL,
+        )
+        .await
+        {
+            Ok(incidents) => incidents,
+            Err(e) => {
+                log::error!("Failed to claim alert incidents: {:?}", e);
+                continue;
+            }
+        };
+
+        for incident in incidents {
+            if let Err(e) = summarize_and_store(
+                db.clone(),
+                cache.clone(),
+                language_model.clone(),
+                analytics_store.clone(),
+                &incident,
+            )
+            .await
+            {
+                log::error!(
+                    "Failed to summarize alert incident {}: {:?}",
+                    incident.id,
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by() {
+        let values = vec![
+            Some("gpt-4o".to_string()),
+            None,
+            Some("gpt-4o-mini".to_string()),
+            Some("gpt-4o-mini".to_string()),
+            None,
+            Some("gpt-4o-mini".to_string()),
+        ];
+        assert_eq!(
+            group_by(values.iter()),
+            vec![
+                GroupCount {
+                    value: Some("gpt-4o-mini".to_string()),
+                    count: 3
+                },
+                GroupCount {
+                    value: None,
+                    count: 2
+                },
+                GroupCount {
+                    value: Some("gpt-4o".to_string()),
+                    count: 1
+                },
+            ]
+        );
+    }
+}
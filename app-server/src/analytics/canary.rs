@@ -0,0 +1,322 @@
+//! Canary analysis: compares the traces of a canary release with a baseline release in the same
+//! time window, and recommends to promote or roll back the canary.
+//!
+//! Error rates are compared with a two-proportion z-test, latency, cost and online scores with
+//! Welch's t-test. Both use the normal approximation, which holds because each release needs at
+//! least `MIN_TRACES` traces for a recommendation.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::{
+    canary::{self, VariantScoreStats, VariantStats},
+    DB,
+};
+
+/// Significance level of the comparisons
+const ALPHA: f64 = 0.05;
+const MIN_TRACES: i64 = 30;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum CanaryRecommendation {
+    Promote,
+    Rollback,
+    /// Not enough traces to decide
+    Inconclusive,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricComparison {
+    /// "errorRate", "latency", "cost" or "score:<name>"
+    pub metric: String,
+    pub baseline: f64,
+    pub canary: f64,
+    /// (canary - baseline) / baseline, None if the baseline is 0
+    pub relative_change: Option<f64>,
+    /// Two-sided p-value of the difference, None if it can't be computed
+    pub p_value: Option<f64>,
+    /// The canary is worse and the difference is significant
+    pub regression: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CanaryAnalysis {
+    pub baseline: VariantStats,
+    pub canary: VariantStats,
+    pub comparisons: Vec<MetricComparison>,
+    pub recommendation: CanaryRecommendation,
+    pub reason: String,
+}
+
+/// Standard normal CDF, with the Abramowitz and Stegun approximation of erf
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+fn two_sided_p_value(z: f64) -> f64 {
+    2.0 * (1.0 - normal_cdf(z.abs()))
+}
+
+fn two_proportion_p_value(baseline: (i64, i64), canary: (i64, i64)) -> Option<f64> {
+    let (baseline_hits, baseline_total) = baseline;
+    let (canary_hits, canary_total) = canary;
+    if baseline_total == 0 || canary_total == 0 {
+        return None;
+    }
+    let pooled = (baseline_hits + canary_hits) as f64 / (baseline_total + canary_total) as f64;
+    let se = (pooled * (1.0 - pooled) * (1.0 / baseline_total as f64 + 1.0 / canary_total as f64))
+        .sqrt();
+    if se == 0.0 {
+        return None;
+    }
+    let difference =
+        canary_hits as f64 / canary_total as f64 - baseline_hits as f64 / baseline_total as f64;
+    Some(two_sided_p_value(difference / se))
+}
+
+/// (mean, stddev, count) of both samples
+fn welch_p_value(baseline: (f64, f64, i64), canary: (f64, f64, i64)) -> Option<f64> {
+    let (baseline_mean, baseline_stddev, baseline_count) = baseline;
+    let (canary_mean, canary_stddev, canary_count) = canary;
+    if baseline_count < 2 || canary_count < 2 {
+        return None;
+    }
+    let se = (baseline_stddev.powi(2) / baseline_count as f64
+        + canary_stddev.powi(2) / canary_count as f64)
+        .sqrt();
+    if se == 0.0 {
+        return None;
+    }
+    Some(two_sided_p_value((canary_mean - baseline_mean) / se))
+}
+
+fn comparison(
+    metric: String,
+    baseline: f64,
+    canary: f64,
+    p_value: Option<f64>,
+    higher_is_better: bool,
+) -> MetricComparison {
+    let worse = if higher_is_better {
+        canary < baseline
+    } else {
+        canary > baseline
+    };
+    MetricComparison {
+        metric,
+        baseline,
+        canary,
+        relative_change: (baseline != 0.0).then(|| (canary - baseline) / baseline),
+        p_value,
+        regression: worse && p_value.is_some_and(|p| p < ALPHA),
+    }
+}
+
+fn error_rate(stats: &VariantStats) -> f64 {
+    if stats.trace_count == 0 {
+        return 0.0;
+    }
+    stats.error_count as f64 / stats.trace_count as f64
+}
+
+fn analyze(
+    baseline: VariantStats,
+    canary: VariantStats,
+    baseline_scores: Vec<VariantScoreStats>,
+    canary_scores: Vec<VariantScoreStats>,
+) -> CanaryAnalysis {
+    let mut comparisons = vec![
+        comparison(
+            "errorRate".to_string(),
+            error_rate(&baseline),
+            error_rate(&canary),
+            two_proportion_p_value(
+                (baseline.error_count, baseline.trace_count),
+                (canary.error_count, canary.trace_count),
+            ),
+            false,
+        ),
+        comparison(
+            "latency".to_string(),
+            baseline.latency_mean,
+            canary.latency_mean,
+            welch_p_value(
+                (
+                    baseline.latency_mean,
+                    baseline.latency_stddev,
+                    baseline.trace_count,
+                ),
+                (
+                    canary.latency_mean,
+                    canary.latency_stddev,
+                    canary.trace_count,
+                ),
+            ),
+            false,
+        ),
+        comparison(
+            "cost".to_string(),
+            baseline.cost_mean,
+            canary.cost_mean,
+            welch_p_value(
+                (
+                    baseline.cost_mean,
+                    baseline.cost_stddev,
+                    baseline.trace_count,
+                ),
+                (canary.cost_mean, canary.cost_stddev, canary.trace_count),
+            ),
+            false,
+        ),
+    ];
+
+    // online scores are compared if both releases have them, higher values are better
+    let baseline_scores = baseline_scores
+        .into_iter()
+        .map(|score| (score.name.clone(), score))
+        .collect::<HashMap<_, _>>();
+    let mut canary_scores = canary_scores;
+    canary_scores.sort_by(|a, b| a.name.cmp(&b.name));
+    for canary_score in canary_scores {
+        let Some(baseline_score) = baseline_scores.get(&canary_score.name) else {
+            continue;
+        };
+        comparisons.push(comparison(
+            format!("score:{}", canary_score.name),
+            baseline_score.mean,
+            canary_score.mean,
+            welch_p_value(
+                (
+                    baseline_score.mean,
+                    baseline_score.stddev,
+                    baseline_score.count,
+                ),
+                (canary_score.mean, canary_score.stddev, canary_score.count),
+            ),
+            true,
+        ));
+    }
+
+    let regressions = comparisons
+        .iter()
+        .filter(|comparison| comparison.regression)
+        .map(|comparison| comparison.metric.as_str())
+        .collect::<Vec<_>>();
+    let (recommendation, reason) =
+        if baseline.trace_count < MIN_TRACES || canary.trace_count < MIN_TRACES {
+            (
+                CanaryRecommendation::Inconclusive,
+                format!(
+                    "Both releases need at least {} traces, baseline has {} and canary has {}",
+                    MIN_TRACES, baseline.trace_count, canary.trace_count
+                ),
+            )
+        } else if !regressions.is_empty() {
+            (
+                CanaryRecommendation::Rollback,
+                format!("Significant regression in {}", regressions.join(", ")),
+            )
+        } else {
+            (
+                CanaryRecommendation::Promote,
+                "No significant regression".to_string(),
+            )
+        };
+
+    CanaryAnalysis {
+        baseline,
+        canary,
+        comparisons,
+        recommendation,
+        reason,
+    }
+}
+
+/// Compares the traces of the `canary` release with the `baseline` release
+pub async fn analyze_canary(
+    db: &DB,
+    project_id: &Uuid,
+    baseline_release: &str,
+    canary_release: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<CanaryAnalysis> {
+    let pool = &db.pool;
+    let (baseline, canary) = tokio::try_join!(
+        canary::get_variant_stats(pool, project_id, baseline_release, start_time, end_time),
+        canary::get_variant_stats(pool, project_id, canary_release, start_time, end_time),
+    )?;
+    let (baseline_scores, canary_scores) = tokio::try_join!(
+        canary::get_variant_score_stats(pool, project_id, baseline_release, start_time, end_time),
+        canary::get_variant_score_stats(pool, project_id, canary_release, start_time, end_time),
+    )?;
+
+    Ok(analyze(baseline, canary, baseline_scores, canary_scores))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(trace_count: i64, error_count: i64, latency_mean: f64) -> VariantStats {
+        VariantStats {
+            trace_count,
+            error_count,
+            latency_mean,
+            latency_stddev: 0.5,
+            cost_mean: 0.01,
+            cost_stddev: 0.001,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normal_cdf() {
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-6);
+        assert!((normal_cdf(1.96) - 0.975).abs() < 1e-3);
+        assert!((two_sided_p_value(-1.96) - 0.05).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let analysis = analyze(stats(1000, 10, 1.0), stats(1000, 40, 1.0), vec![], vec![]);
+        assert_eq!(analysis.recommendation, CanaryRecommendation::Rollback);
+        assert!(analysis.comparisons[0].regression);
+        assert!(!analysis.comparisons[1].regression);
+
+        let score = |mean| VariantScoreStats {
+            name: "helpfulness".to_string(),
+            count: 500,
+            mean,
+            stddev: 0.3,
+        };
+        let analysis = analyze(
+            stats(1000, 10, 1.0),
+            stats(1000, 9, 0.95),
+            vec![score(0.8)],
+            vec![score(0.82)],
+        );
+        assert_eq!(analysis.recommendation, CanaryRecommendation::Promote);
+        assert_eq!(analysis.comparisons[3].metric, "score:helpfulness");
+
+        let analysis = analyze(stats(1000, 10, 1.0), stats(10, 0, 1.0), vec![], vec![]);
+        assert_eq!(analysis.recommendation, CanaryRecommendation::Inconclusive);
+    }
+}
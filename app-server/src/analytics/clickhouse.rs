@@ -0,0 +1,858 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::ch::{
+    self,
+    browser_events::BrowserEvent,
+    browser_snapshots::BrowserSnapshot,
+    evaluation_scores::{
+        ComparedEvaluationScoresBounds, EvaluationScore, EvaluationScoreBucket,
+        EvaluationScoreDiff, EvaluationScoreHistogramBucket, EvaluationScoreLabelFrequency,
+        EvaluationScorePassRate, EvaluationScorePercentile, EvaluationScoreStats,
+        EvaluationScoreTrendPoint, FilteredEvaluationScore,
+    },
+    events::CHEvent,
+    filter_expression::CompiledFilter,
+    modifiers::GroupByInterval,
+    query::{AnalyticsQuery, QueryContext, QueryResultRow},
+    score_writer::ScoreWriter,
+    span_scores::{AnnotatorSpanScore, ScoreScatterPoint, SpanScore, SpanScoresBounds},
+    span_search::{CHSpanSearchEntry, SpanSearchField, SpanSearchResult},
+    spans::{
+        AgentActionStats, AgentFailureSelector, CHSpan, FilteredSpan, FilteredTrace, HeatmapCell,
+        HeatmapMetric, LatencyLevel, LatencyPercentilesPoint, LatencySpanFilter,
+        PipelineLatencyPercentiles, ShadowDiffReport, TraceLatencyAndCost,
+    },
+    table_stats::TableStats,
+    Aggregation, MetricTimeValue,
+};
+
+use super::{custom_metrics::MetricExpression, AnalyticsStore};
+
+#[derive(Clone)]
+pub struct ClickHouseStore {
+    client: clickhouse::Client,
+    score_writer: ScoreWriter,
+}
+
+impl ClickHouseStore {
+    pub fn new(client: clickhouse::Client, score_writer: ScoreWriter) -> Self {
+        Self {
+            client,
+            score_writer,
+        }
+    }
+}
+
+#[async_trait]
+impl AnalyticsStore for ClickHouseStore {
+    async fn insert_span(&self, span: &CHSpan) -> Result<()> {
+        ch::spans::insert_span(self.client.clone(), span).await
+    }
+
+    async fn insert_shadow_span(&self, span: &CHSpan) -> Result<()> {
+        ch::spans::insert_shadow_span(self.client.clone(), span).await
+    }
+
+    async fn insert_span_search_entry(&self, entry: &CHSpanSearchEntry) -> Result<()> {
+        ch::span_search::insert_span_search_entry(self.client.clone(), entry).await
+    }
+
+    async fn insert_events(&self, events: Vec<CHEvent>) -> Result<()> {
+        ch::events::insert_events(self.client.clone(), events).await
+    }
+
+    async fn insert_evaluation_scores(
+        &self,
+        evaluation_scores: Vec<EvaluationScore>,
+    ) -> Result<()> {
+        self.score_writer.write(evaluation_scores).await
+    }
+
+    async fn insert_span_scores(&self, span_scores: Vec<SpanScore>) -> Result<()> {
+        ch::span_scores::insert_span_scores(self.client.clone(), span_scores).await
+    }
+
+    async fn insert_browser_events(&self, events: Vec<BrowserEvent>) -> Result<()> {
+        ch::browser_events::insert_browser_events(self.client.clone(), events).await
+    }
+
+    async fn insert_browser_snapshots(&self, snapshots: Vec<BrowserSnapshot>) -> Result<()> {
+        ch::browser_snapshots::insert_browser_snapshots(self.client.clone(), snapshots).await
+    }
+
+    async fn get_bounds(
+        &self,
+        project_id: &Uuid,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+        ch::utils::get_bounds(&self.client, project_id, table_name, column_name).await
+    }
+
+    async fn get_project_table_stats(&self, project_id: Uuid) -> Result<Vec<TableStats>> {
+        ch::table_stats::get_project_table_stats(self.client.clone(), project_id).await
+    }
+
+    async fn get_total_trace_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        ch::spans::get_total_trace_count_metrics_relative(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            past_hours,
+        )
+        .await
+    }
+
+    async fn get_total_trace_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        ch::spans::get_total_trace_count_metrics_absolute(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_trace_latency_seconds_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        ch::spans::get_trace_latency_seconds_metrics_relative(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            past_hours,
+            aggregation,
+        )
+        .await
+    }
+
+    async fn get_trace_latency_seconds_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        ch::spans::get_trace_latency_seconds_metrics_absolute(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            start_time,
+            end_time,
+            aggregation,
+        )
+        .await
+    }
+
+    async fn get_total_token_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        ch::spans::get_total_token_count_metrics_relative(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            past_hours,
+            aggregation,
+        )
+        .await
+    }
+
+    async fn get_total_token_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        ch::spans::get_total_token_count_metrics_absolute(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            start_time,
+            end_time,
+            aggregation,
+        )
+        .await
+    }
+
+    async fn get_cost_usd_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        ch::spans::get_cost_usd_metrics_relative(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            past_hours,
+            aggregation,
+        )
+        .await
+    }
+
+    async fn get_cost_usd_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        ch::spans::get_cost_usd_metrics_absolute(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            start_time,
+            end_time,
+            aggregation,
+        )
+        .await
+    }
+
+    async fn get_total_event_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        template_id: Uuid,
+        past_hours: i64,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        ch::events::get_total_event_count_metrics_relative(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            template_id,
+            past_hours,
+        )
+        .await
+    }
+
+    async fn get_total_event_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        template_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        ch::events::get_total_event_count_metrics_absolute(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            template_id,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_average_evaluation_score(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<f64> {
+        ch::evaluation_scores::get_average_evaluation_score(
+            self.client.clone(),
+            project_id,
+            evaluation_id,
+            name,
+        )
+        .await
+    }
+
+    async fn get_evaluation_score_stats(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<EvaluationScoreStats> {
+        ch::evaluation_scores::get_evaluation_score_stats(
+            self.client.clone(),
+            project_id,
+            evaluation_id,
+            name,
+        )
+        .await
+    }
+
+    async fn get_evaluation_score_pass_rate(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<EvaluationScorePassRate> {
+        ch::evaluation_scores::get_evaluation_score_pass_rate(
+            self.client.clone(),
+            project_id,
+            evaluation_id,
+            name,
+        )
+        .await
+    }
+
+    async fn get_evaluation_score_label_frequencies(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<Vec<EvaluationScoreLabelFrequency>> {
+        ch::evaluation_scores::get_evaluation_score_label_frequencies(
+            self.client.clone(),
+            project_id,
+            evaluation_id,
+            name,
+        )
+        .await
+    }
+
+    async fn get_evaluation_score_trend(
+        &self,
+        project_id: Uuid,
+        group_id: String,
+        name: String,
+    ) -> Result<Vec<EvaluationScoreTrendPoint>> {
+        ch::evaluation_scores::get_evaluation_score_trend(
+            self.client.clone(),
+            project_id,
+            group_id,
+            name,
+        )
+        .await
+    }
+
+    async fn get_numeric_evaluation_score_names(&self, project_id: Uuid) -> Result<Vec<String>> {
+        ch::evaluation_scores::get_numeric_evaluation_score_names(self.client.clone(), project_id)
+            .await
+    }
+
+    async fn get_evaluation_score_time_series(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        ch::evaluation_scores::get_evaluation_score_time_series(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            name,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_evaluation_score_buckets_based_on_bounds(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+        lower_bound: f64,
+        upper_bound: f64,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        ch::evaluation_scores::get_evaluation_score_buckets_based_on_bounds(
+            self.client.clone(),
+            project_id,
+            evaluation_id,
+            name,
+            lower_bound,
+            upper_bound,
+            bucket_count,
+        )
+        .await
+    }
+
+    async fn get_global_evaluation_scores_bounds(
+        &self,
+        project_id: Uuid,
+        evaluation_ids: &Vec<Uuid>,
+        name: String,
+    ) -> Result<ComparedEvaluationScoresBounds> {
+        ch::evaluation_scores::get_global_evaluation_scores_bounds(
+            self.client.clone(),
+            project_id,
+            evaluation_ids,
+            name,
+        )
+        .await
+    }
+
+    async fn get_evaluation_score_histogram(
+        &self,
+        project_id: Uuid,
+        evaluation_ids: &Vec<Uuid>,
+        name: String,
+        max_bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreHistogramBucket>> {
+        ch::evaluation_scores::get_evaluation_score_histogram(
+            self.client.clone(),
+            project_id,
+            evaluation_ids,
+            name,
+            max_bucket_count,
+        )
+        .await
+    }
+
+    async fn get_evaluation_score_percentiles(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        baseline_evaluation_id: Uuid,
+    ) -> Result<Vec<EvaluationScorePercentile>> {
+        ch::evaluation_scores::get_evaluation_score_percentiles(
+            self.client.clone(),
+            project_id,
+            evaluation_id,
+            baseline_evaluation_id,
+        )
+        .await
+    }
+
+    async fn get_evaluation_score_diffs(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        baseline_evaluation_id: Uuid,
+    ) -> Result<Vec<EvaluationScoreDiff>> {
+        ch::evaluation_scores::get_evaluation_score_diffs(
+            self.client.clone(),
+            project_id,
+            evaluation_id,
+            baseline_evaluation_id,
+        )
+        .await
+    }
+
+    async fn get_average_span_score(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<f64> {
+        ch::span_scores::get_average_span_score(
+            self.client.clone(),
+            project_id,
+            name,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_span_score_buckets_based_on_bounds(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        lower_bound: f64,
+        upper_bound: f64,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        ch::span_scores::get_span_score_buckets_based_on_bounds(
+            self.client.clone(),
+            project_id,
+            name,
+            start_time,
+            end_time,
+            lower_bound,
+            upper_bound,
+            bucket_count,
+        )
+        .await
+    }
+
+    async fn get_span_scores_bounds(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<SpanScoresBounds> {
+        ch::span_scores::get_span_scores_bounds(
+            self.client.clone(),
+            project_id,
+            name,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_span_score_trend(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        ch::span_scores::get_span_score_trend(
+            self.client.clone(),
+            group_by_interval,
+            project_id,
+            name,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_span_score_names(&self, project_id: Uuid) -> Result<Vec<String>> {
+        ch::span_scores::get_span_score_names(self.client.clone(), project_id).await
+    }
+
+    async fn get_span_score_scatter(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<ScoreScatterPoint>> {
+        ch::span_scores::get_span_score_scatter(
+            self.client.clone(),
+            project_id,
+            name,
+            start_time,
+            end_time,
+            limit,
+        )
+        .await
+    }
+
+    async fn get_annotator_span_scores(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<AnnotatorSpanScore>> {
+        ch::span_scores::get_annotator_span_scores(
+            self.client.clone(),
+            project_id,
+            start_time,
+            end_time,
+            limit,
+        )
+        .await
+    }
+
+    async fn get_traces_latency_and_cost(
+        &self,
+        project_id: Uuid,
+        trace_ids: &[Uuid],
+    ) -> Result<Vec<TraceLatencyAndCost>> {
+        ch::spans::get_traces_latency_and_cost(self.client.clone(), project_id, trace_ids).await
+    }
+
+    async fn get_shadow_diff_report(
+        &self,
+        project_id: Option<Uuid>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<ShadowDiffReport> {
+        ch::spans::get_shadow_diff_report(self.client.clone(), project_id, start_time, end_time)
+            .await
+    }
+
+    async fn get_custom_metric_buckets(
+        &self,
+        project_id: Uuid,
+        expression: &MetricExpression,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        ch::spans::get_custom_metric_buckets(
+            self.client.clone(),
+            project_id,
+            expression,
+            start_time,
+            end_time,
+            bucket_count,
+        )
+        .await
+    }
+
+    async fn run_query(
+        &self,
+        project_id: Uuid,
+        query: &AnalyticsQuery,
+        context: &QueryContext,
+    ) -> Result<Vec<QueryResultRow>> {
+        ch::query::run_query(self.client.clone(), project_id, query, context).await
+    }
+
+    async fn get_pipeline_latency_percentiles(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<PipelineLatencyPercentiles>> {
+        ch::spans::get_pipeline_latency_percentiles(
+            self.client.clone(),
+            project_id,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_latency_percentiles_over_time(
+        &self,
+        project_id: Uuid,
+        level: LatencyLevel,
+        filter: &LatencySpanFilter,
+        group_by_interval: GroupByInterval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<LatencyPercentilesPoint>> {
+        ch::spans::get_latency_percentiles_over_time(
+            self.client.clone(),
+            project_id,
+            level,
+            filter,
+            group_by_interval,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_hour_of_week_heatmap(
+        &self,
+        project_id: Uuid,
+        pipeline: Option<&str>,
+        metric: HeatmapMetric,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<HeatmapCell>> {
+        ch::spans::get_hour_of_week_heatmap(
+            self.client.clone(),
+            project_id,
+            pipeline,
+            metric,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn get_agent_action_stats(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<AgentActionStats>> {
+        ch::spans::get_agent_action_stats(self.client.clone(), project_id, start_time, end_time)
+            .await
+    }
+
+    async fn get_agent_failure_selectors(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<AgentFailureSelector>> {
+        ch::spans::get_agent_failure_selectors(
+            self.client.clone(),
+            project_id,
+            start_time,
+            end_time,
+            limit,
+        )
+        .await
+    }
+
+    async fn search_spans(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<SpanSearchResult>> {
+        ch::span_search::search_spans(
+            self.client.clone(),
+            project_id,
+            query,
+            field,
+            start_time,
+            end_time,
+            limit,
+            offset,
+        )
+        .await
+    }
+
+    async fn count_span_search_results(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<u64> {
+        ch::span_search::count_span_search_results(
+            self.client.clone(),
+            project_id,
+            query,
+            field,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn query_spans(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredSpan>> {
+        ch::spans::query_spans(
+            self.client.clone(),
+            project_id,
+            filter,
+            start_time,
+            end_time,
+            limit,
+            offset,
+        )
+        .await
+    }
+
+    async fn query_traces(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredTrace>> {
+        ch::spans::query_traces(
+            self.client.clone(),
+            project_id,
+            filter,
+            start_time,
+            end_time,
+            limit,
+            offset,
+        )
+        .await
+    }
+
+    async fn query_evaluation_scores(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredEvaluationScore>> {
+        ch::evaluation_scores::query_evaluation_scores(
+            self.client.clone(),
+            project_id,
+            filter,
+            start_time,
+            end_time,
+            limit,
+            offset,
+        )
+        .await
+    }
+
+    async fn update_promoted_attribute_values(
+        &self,
+        project_id: Uuid,
+        slot: usize,
+        values: Vec<(Uuid, String)>,
+    ) -> Result<()> {
+        ch::spans::update_promoted_attribute_values(self.client.clone(), project_id, slot, values)
+            .await
+    }
+
+    async fn clear_promoted_attribute(&self, project_id: Uuid, slot: usize) -> Result<()> {
+        ch::spans::clear_promoted_attribute(self.client.clone(), project_id, slot).await
+    }
+
+    async fn get_trace_browser_events(
+        &self,
+        project_id: Uuid,
+        trace_id: Uuid,
+    ) -> Result<Vec<BrowserEvent>> {
+        ch::browser_events::get_trace_browser_events(self.client.clone(), project_id, trace_id)
+            .await
+    }
+
+    async fn get_nearest_browser_snapshot(
+        &self,
+        project_id: Uuid,
+        session_id: String,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<BrowserSnapshot>> {
+        ch::browser_snapshots::get_nearest_browser_snapshot(
+            self.client.clone(),
+            project_id,
+            session_id,
+            timestamp,
+        )
+        .await
+    }
+
+    async fn get_browser_snapshots_at_step(
+        &self,
+        project_id: Uuid,
+        session_id: String,
+        step: u32,
+    ) -> Result<Vec<BrowserSnapshot>> {
+        ch::browser_snapshots::get_browser_snapshots_at_step(
+            self.client.clone(),
+            project_id,
+            session_id,
+            step,
+        )
+        .await
+    }
+}
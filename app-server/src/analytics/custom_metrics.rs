@@ -0,0 +1,322 @@
+//! Custom metrics: per-project metrics defined as arithmetic expressions over span fields, e.g.
+//! `completion_tokens / duration_seconds`, that can be used wherever a built-in span metric can.
+//!
+//! Expressions are parsed into a `MetricExpression`, which is compiled to ClickHouse SQL. Only
+//! the fields in `SpanField`, numbers, `+ - * /` and parentheses are accepted, so a stored
+//! expression can't inject SQL. Division by zero yields NULL, which aggregations skip.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::{ch::spans::CHSpan, db::custom_metrics::CustomMetric};
+
+pub const MAX_EXPRESSION_LENGTH: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpanField {
+    DurationSeconds,
+    InputTokens,
+    OutputTokens,
+    TotalTokens,
+    InputCost,
+    OutputCost,
+    TotalCost,
+}
+
+impl SpanField {
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name {
+            "duration_seconds" => SpanField::DurationSeconds,
+            "input_tokens" | "prompt_tokens" => SpanField::InputTokens,
+            "output_tokens" | "completion_tokens" => SpanField::OutputTokens,
+            "total_tokens" => SpanField::TotalTokens,
+            "input_cost" => SpanField::InputCost,
+            "output_cost" => SpanField::OutputCost,
+            "total_cost" => SpanField::TotalCost,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn to_sql(&self) -> &'static str {
+        match self {
+            SpanField::DurationSeconds => {
+                "((toUnixTimestamp64Nano(end_time) - toUnixTimestamp64Nano(start_time)) / 1e9)"
+            }
+            SpanField::InputTokens => "input_tokens",
+            SpanField::OutputTokens => "output_tokens",
+            SpanField::TotalTokens => "total_tokens",
+            SpanField::InputCost => "input_cost",
+            SpanField::OutputCost => "output_cost",
+            SpanField::TotalCost => "total_cost",
+        }
+    }
+
+    fn value(&self, span: &CHSpan) -> f64 {
+        match self {
+            SpanField::DurationSeconds => (span.end_time - span.start_time) as f64 / 1e9,
+            SpanField::InputTokens => span.input_tokens as f64,
+            SpanField::OutputTokens => span.output_tokens as f64,
+            SpanField::TotalTokens => span.total_tokens as f64,
+            SpanField::InputCost => span.input_cost,
+            SpanField::OutputCost => span.output_cost,
+            SpanField::TotalCost => span.total_cost,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetricExpression {
+    Number(f64),
+    Field(SpanField),
+    Negate(Box<MetricExpression>),
+    Binary(Box<MetricExpression>, Operator, Box<MetricExpression>),
+}
+
+impl MetricExpression {
+    pub fn parse(expression: &str) -> Result<Self> {
+        if expression.len() > MAX_EXPRESSION_LENGTH {
+            return Err(anyhow::anyhow!(
+                "Expression must be at most {} characters",
+                MAX_EXPRESSION_LENGTH
+            ));
+        }
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let parsed = parser.parse_sum()?;
+        if let Some(token) = parser.peek() {
+            return Err(anyhow::anyhow!("Unexpected {:?}", token));
+        }
+        Ok(parsed)
+    }
+
+    pub fn to_sql(&self) -> String {
+        match self {
+            // {:?} renders 1.0 as 1.0 instead of 1, so that divisions aren't integer divisions
+            MetricExpression::Number(number) => format!("{:?}", number),
+            MetricExpression::Field(field) => field.to_sql().to_string(),
+            MetricExpression::Negate(inner) => format!("(-{})", inner.to_sql()),
+            MetricExpression::Binary(left, Operator::Divide, right) => {
+                format!("({} / nullIf({}, 0))", left.to_sql(), right.to_sql())
+            }
+            MetricExpression::Binary(left, operator, right) => {
+                let operator = match operator {
+                    Operator::Add => "+",
+                    Operator::Subtract => "-",
+                    _ => "*",
+                };
+                format!("({} {} {})", left.to_sql(), operator, right.to_sql())
+            }
+        }
+    }
+
+    /// Value of the metric for the span, None on division by zero
+    pub fn evaluate(&self, span: &CHSpan) -> Option<f64> {
+        match self {
+            MetricExpression::Number(number) => Some(*number),
+            MetricExpression::Field(field) => Some(field.value(span)),
+            MetricExpression::Negate(inner) => Some(-inner.evaluate(span)?),
+            MetricExpression::Binary(left, operator, right) => {
+                let (left, right) = (left.evaluate(span)?, right.evaluate(span)?);
+                match operator {
+                    Operator::Add => Some(left + right),
+                    Operator::Subtract => Some(left - right),
+                    Operator::Multiply => Some(left * right),
+                    Operator::Divide if right == 0.0 => None,
+                    Operator::Divide => Some(left / right),
+                }
+            }
+        }
+    }
+}
+
+/// Parsed expressions of the project's custom metrics, by name. Metrics whose expression no
+/// longer parses are skipped.
+pub fn custom_metric_expressions(metrics: &[CustomMetric]) -> HashMap<String, MetricExpression> {
+    metrics
+        .iter()
+        .filter_map(|metric| match MetricExpression::parse(&metric.expression) {
+            Ok(expression) => Some((metric.name.clone(), expression)),
+            Err(e) => {
+                log::warn!(
+                    "Invalid expression of custom metric {} ({}): {}",
+                    metric.name,
+                    metric.id,
+                    e
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Field(SpanField),
+    Operator(Operator),
+    OpenParen,
+    CloseParen,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let chars = expression.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Subtract),
+            '*' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number = chars[start..i].iter().collect::<String>();
+                tokens.push(Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("Invalid number: {}", number))?,
+                ));
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let name = chars[start..i].iter().collect::<String>();
+                let field = SpanField::from_name(&name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown span field: {}", name))?;
+                tokens.push(Token::Field(field));
+                continue;
+            }
+            c => return Err(anyhow::anyhow!("Unexpected character: {}", c)),
+        };
+        tokens.push(token);
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_sum(&mut self) -> Result<MetricExpression> {
+        let mut left = self.parse_product()?;
+        while let Some(Token::Operator(operator @ (Operator::Add | Operator::Subtract))) =
+            self.peek().cloned()
+        {
+            self.position += 1;
+            let right = self.parse_product()?;
+            left = MetricExpression::Binary(Box::new(left), operator, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_product(&mut self) -> Result<MetricExpression> {
+        let mut left = self.parse_factor()?;
+        while let Some(Token::Operator(operator @ (Operator::Multiply | Operator::Divide))) =
+            self.peek().cloned()
+        {
+            self.position += 1;
+            let right = self.parse_factor()?;
+            left = MetricExpression::Binary(Box::new(left), operator, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_factor(&mut self) -> Result<MetricExpression> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(MetricExpression::Number(number)),
+            Some(Token::Field(field)) => Ok(MetricExpression::Field(field)),
+            Some(Token::Operator(Operator::Subtract)) => {
+                Ok(MetricExpression::Negate(Box::new(self.parse_factor()?)))
+            }
+            Some(Token::OpenParen) => {
+                let inner = self.parse_sum()?;
+                match self.next() {
+                    Some(Token::CloseParen) => Ok(inner),
+                    _ => Err(anyhow::anyhow!("Missing closing parenthesis")),
+                }
+            }
+            Some(token) => Err(anyhow::anyhow!("Unexpected {:?}", token)),
+            None => Err(anyhow::anyhow!("Unexpected end of expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_to_sql() {
+        let expression = MetricExpression::parse("completion_tokens / duration_seconds").unwrap();
+        assert_eq!(
+            expression.to_sql(),
+            "(output_tokens / nullIf(((toUnixTimestamp64Nano(end_time) - \
+            toUnixTimestamp64Nano(start_time)) / 1e9), 0))"
+        );
+
+        let expression = MetricExpression::parse("(input_cost + output_cost) * 1000 - 1").unwrap();
+        assert_eq!(
+            expression.to_sql(),
+            "(((input_cost + output_cost) * 1000.0) - 1.0)"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_expressions() {
+        for expression in [
+            "",
+            "total_tokens +",
+            "(total_tokens",
+            "total_tokens)",
+            "attributes['x']",
+            "total_tokens; DROP TABLE spans",
+            "sleep(10)",
+            "1..2",
+        ] {
+            assert!(
+                MetricExpression::parse(expression).is_err(),
+                "{}",
+                expression
+            );
+        }
+    }
+}
@@ -0,0 +1,578 @@
+//! Declarative file of a project's analytics definitions: custom metrics, judge evaluators and
+//! online evaluation rules. Definitions are identified by name, without ids or timestamps, and
+//! sorted, so that the exported file can be versioned in git, reviewed as a diff and applied to
+//! the same or another project.
+
+use std::collections::HashSet;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self, custom_metrics::CustomMetric, judge_evaluators::JudgeEvaluator,
+        judge_evaluators::NewJudgeEvaluator, online_evaluation_rules::NewOnlineEvaluationRule,
+        online_evaluation_rules::OnlineEvaluationRule,
+    },
+    evaluations::{judge, utils::ScoreType},
+    language_model::providers::utils::get_required_env_vars_for_model,
+    traces::online_evaluations::{self, OnlineEvaluator},
+};
+
+use super::custom_metrics::MetricExpression;
+
+/// Bumped on incompatible changes of the file format
+pub const DEFINITIONS_VERSION: u32 = 1;
+const MAX_NAME_LENGTH: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomMetricDefinition {
+    pub name: String,
+    pub expression: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JudgeEvaluatorDefinition {
+    pub name: String,
+    pub model: String,
+    pub prompt: String,
+    pub score_type: ScoreType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OnlineEvaluationRuleDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span_name: Option<String>,
+    #[serde(default = "empty_object")]
+    pub attribute_filters: Value,
+    #[serde(default = "default_sample_percentage")]
+    pub sample_percentage: f64,
+    /// See `traces::online_evaluations::OnlineEvaluator`
+    pub evaluator: Value,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn empty_object() -> Value {
+    Value::Object(serde_json::Map::new())
+}
+
+fn default_sample_percentage() -> f64 {
+    100.0
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsDefinitions {
+    pub version: u32,
+    #[serde(default)]
+    pub custom_metrics: Vec<CustomMetricDefinition>,
+    #[serde(default)]
+    pub judge_evaluators: Vec<JudgeEvaluatorDefinition>,
+    #[serde(default)]
+    pub online_evaluation_rules: Vec<OnlineEvaluationRuleDefinition>,
+}
+
+impl AnalyticsDefinitions {
+    pub fn from_project(
+        metrics: Vec<CustomMetric>,
+        judges: Vec<JudgeEvaluator>,
+        rules: Vec<OnlineEvaluationRule>,
+    ) -> Self {
+        let mut definitions = Self {
+            version: DEFINITIONS_VERSION,
+            custom_metrics: metrics
+                .into_iter()
+                .map(|metric| CustomMetricDefinition {
+                    name: metric.name,
+                    expression: metric.expression,
+                    description: metric.description,
+                })
+                .collect(),
+            judge_evaluators: judges
+                .into_iter()
+                .map(|judge| JudgeEvaluatorDefinition {
+                    name: judge.name,
+                    model: judge.model,
+                    prompt: judge.prompt,
+                    score_type: judge.score_type,
+                    labels: judge.labels,
+                })
+                .collect(),
+            online_evaluation_rules: rules
+                .into_iter()
+                .map(|rule| OnlineEvaluationRuleDefinition {
+                    name: rule.name,
+                    span_path: rule.span_path,
+                    span_name: rule.span_name,
+                    attribute_filters: rule.attribute_filters,
+                    sample_percentage: rule.sample_percentage,
+                    evaluator: rule.evaluator,
+                    enabled: rule.enabled,
+                })
+                .collect(),
+        };
+        definitions.sort();
+        definitions
+    }
+
+    fn sort(&mut self) {
+        self.custom_metrics.sort_by(|a, b| a.name.cmp(&b.name));
+        self.judge_evaluators.sort_by(|a, b| a.name.cmp(&b.name));
+        self.online_evaluation_rules
+            .sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    /// Checks the definitions like the routes that create them do, and brings them to the form
+    /// they are stored in, so that applying an exported file changes nothing
+    pub fn normalize(mut self) -> Result<Self> {
+        if self.version != DEFINITIONS_VERSION {
+            bail!(
+                "Unsupported definitions version {}, expected {}",
+                self.version,
+                DEFINITIONS_VERSION
+            );
+        }
+
+        for metric in &mut self.custom_metrics {
+            metric.name = metric.name.trim().to_string();
+            if metric.name.is_empty()
+                || metric.name.len() > MAX_NAME_LENGTH
+                || !metric
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                bail!(
+                    "Custom metric name {:?} must be between 1 and {} letters, digits, underscores or dashes",
+                    metric.name,
+                    MAX_NAME_LENGTH
+                );
+            }
+            metric.expression = metric.expression.trim().to_string();
+            if let Err(e) = MetricExpression::parse(&metric.expression) {
+                bail!("Invalid expression of custom metric {}: {}", metric.name, e);
+            }
+        }
+
+        for judge in &mut self.judge_evaluators {
+            judge.name = judge.name.trim().to_string();
+            check_name_length("Judge evaluator", &judge.name)?;
+            judge.model = judge.model.trim().to_string();
+            if !judge.model.contains(':')
+                || get_required_env_vars_for_model(&judge.model).is_empty()
+            {
+                bail!(
+                    "Model of judge evaluator {} must be in the format of provider:model_name with a supported provider",
+                    judge.name
+                );
+            }
+            if let Err(e) = judge::validate_prompt(&judge.prompt) {
+                bail!(
+                    "Invalid prompt template of judge evaluator {}: {}",
+                    judge.name,
+                    e
+                );
+            }
+            judge.labels = judge
+                .labels
+                .iter()
+                .map(|label| label.trim().to_string())
+                .filter(|label| !label.is_empty())
+                .collect();
+            match judge.score_type {
+                ScoreType::CATEGORICAL if judge.labels.is_empty() => {
+                    bail!(
+                        "Categorical judge evaluator {} requires at least one label",
+                        judge.name
+                    );
+                }
+                ScoreType::NUMERIC | ScoreType::BOOLEAN if !judge.labels.is_empty() => {
+                    bail!(
+                        "Only categorical judges have labels, judge evaluator {} is not",
+                        judge.name
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        for rule in &mut self.online_evaluation_rules {
+            rule.name = rule.name.trim().to_string();
+            check_name_length("Online evaluation rule", &rule.name)?;
+            if !(rule.sample_percentage > 0.0 && rule.sample_percentage <= 100.0) {
+                bail!(
+                    "Sample percentage of online evaluation rule {} must be greater than 0 and at most 100",
+                    rule.name
+                );
+            }
+            if !rule.attribute_filters.is_object() {
+                bail!(
+                    "Attribute filters of online evaluation rule {} must be an object",
+                    rule.name
+                );
+            }
+            rule.span_path = rule.span_path.take().filter(|path| !path.is_empty());
+            rule.span_name = rule.span_name.take().filter(|name| !name.is_empty());
+            let evaluator = online_evaluations::parse_evaluator(&rule.evaluator).map_err(|e| {
+                anyhow::anyhow!(
+                    "Invalid evaluator of online evaluation rule {}: {}",
+                    rule.name,
+                    e
+                )
+            })?;
+            rule.evaluator = serde_json::to_value(&evaluator)?;
+        }
+
+        check_unique_names(
+            "custom metric",
+            self.custom_metrics.iter().map(|metric| &metric.name),
+        )?;
+        check_unique_names(
+            "judge evaluator",
+            self.judge_evaluators.iter().map(|judge| &judge.name),
+        )?;
+        check_unique_names(
+            "online evaluation rule",
+            self.online_evaluation_rules.iter().map(|rule| &rule.name),
+        )?;
+
+        self.sort();
+        Ok(self)
+    }
+
+    /// Names of the judges that the online evaluation rules run
+    pub fn referenced_judges(&self) -> Vec<String> {
+        let mut names = self
+            .online_evaluation_rules
+            .iter()
+            .filter_map(
+                |rule| match online_evaluations::parse_evaluator(&rule.evaluator) {
+                    Ok(OnlineEvaluator::Judge { judge }) => Some(judge),
+                    _ => None,
+                },
+            )
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+fn check_name_length(kind: &str, name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        bail!(
+            "{} name {:?} must be between 1 and {} characters",
+            kind,
+            name,
+            MAX_NAME_LENGTH
+        );
+    }
+    Ok(())
+}
+
+fn check_unique_names<'a>(kind: &str, names: impl Iterator<Item = &'a String>) -> Result<()> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name) {
+            bail!("Duplicate {} {}", kind, name);
+        }
+    }
+    Ok(())
+}
+
+/// Names of the definitions of a kind that applying the file creates, updates and deletes
+#[derive(Serialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DefinitionChanges {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    /// Only with `prune`, definitions that are not in the file are kept otherwise
+    pub deleted: Vec<String>,
+}
+
+impl DefinitionChanges {
+    fn between<T: PartialEq>(
+        current: &[T],
+        desired: &[T],
+        name: impl Fn(&T) -> &String,
+        prune: bool,
+    ) -> Self {
+        let mut changes = Self::default();
+        for definition in desired {
+            match current.iter().find(|c| name(c) == name(definition)) {
+                None => changes.created.push(name(definition).clone()),
+                Some(c) if c != definition => changes.updated.push(name(definition).clone()),
+                Some(_) => {}
+            }
+        }
+        if prune {
+            changes.deleted = current
+                .iter()
+                .filter(|c| !desired.iter().any(|d| name(d) == name(c)))
+                .map(|c| name(c).clone())
+                .collect();
+        }
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.updated.is_empty() && self.deleted.is_empty()
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsDefinitionsPlan {
+    pub custom_metrics: DefinitionChanges,
+    pub judge_evaluators: DefinitionChanges,
+    pub online_evaluation_rules: DefinitionChanges,
+}
+
+impl AnalyticsDefinitionsPlan {
+    /// Changes that bring the current definitions to the desired ones, both normalized
+    pub fn between(
+        current: &AnalyticsDefinitions,
+        desired: &AnalyticsDefinitions,
+        prune: bool,
+    ) -> Self {
+        Self {
+            custom_metrics: DefinitionChanges::between(
+                &current.custom_metrics,
+                &desired.custom_metrics,
+                |metric| &metric.name,
+                prune,
+            ),
+            judge_evaluators: DefinitionChanges::between(
+                &current.judge_evaluators,
+                &desired.judge_evaluators,
+                |judge| &judge.name,
+                prune,
+            ),
+            online_evaluation_rules: DefinitionChanges::between(
+                &current.online_evaluation_rules,
+                &desired.online_evaluation_rules,
+                |rule| &rule.name,
+                prune,
+            ),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.custom_metrics.is_empty()
+            && self.judge_evaluators.is_empty()
+            && self.online_evaluation_rules.is_empty()
+    }
+
+    /// Judges the project has once the plan is applied
+    pub fn judges_after(
+        &self,
+        current: &AnalyticsDefinitions,
+        desired: &AnalyticsDefinitions,
+    ) -> Vec<String> {
+        let mut names = current
+            .judge_evaluators
+            .iter()
+            .map(|judge| judge.name.clone())
+            .filter(|name| !self.judge_evaluators.deleted.contains(name))
+            .chain(
+                desired
+                    .judge_evaluators
+                    .iter()
+                    .map(|judge| judge.name.clone()),
+            )
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+pub async fn get_project_definitions(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<AnalyticsDefinitions> {
+    let metrics = db::custom_metrics::get_custom_metrics(pool, project_id).await?;
+    let judges = db::judge_evaluators::get_judge_evaluators(pool, project_id).await?;
+    let rules = db::online_evaluation_rules::get_online_evaluation_rules(pool, project_id).await?;
+
+    Ok(AnalyticsDefinitions::from_project(metrics, judges, rules))
+}
+
+/// Applies the plan in a single transaction, so that the project never has a part of the file
+pub async fn apply_definitions(
+    pool: &PgPool,
+    project_id: &Uuid,
+    desired: &AnalyticsDefinitions,
+    plan: &AnalyticsDefinitionsPlan,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    for metric in &desired.custom_metrics {
+        if plan.custom_metrics.created.contains(&metric.name)
+            || plan.custom_metrics.updated.contains(&metric.name)
+        {
+            db::custom_metrics::upsert_custom_metric(
+                &mut *tx,
+                project_id,
+                &metric.name,
+                &metric.expression,
+                metric.description.as_deref(),
+            )
+            .await?;
+        }
+    }
+    for name in &plan.custom_metrics.deleted {
+        db::custom_metrics::delete_custom_metric_by_name(&mut *tx, project_id, name).await?;
+    }
+
+    for judge in &desired.judge_evaluators {
+        if plan.judge_evaluators.created.contains(&judge.name)
+            || plan.judge_evaluators.updated.contains(&judge.name)
+        {
+            let judge = NewJudgeEvaluator {
+                name: judge.name.clone(),
+                model: judge.model.clone(),
+                prompt: judge.prompt.clone(),
+                score_type: judge.score_type,
+                labels: judge.labels.clone(),
+            };
+            db::judge_evaluators::upsert_judge_evaluator(&mut *tx, project_id, &judge).await?;
+        }
+    }
+    for name in &plan.judge_evaluators.deleted {
+        db::judge_evaluators::delete_judge_evaluator_by_name(&mut *tx, project_id, name).await?;
+    }
+
+    for rule in &desired.online_evaluation_rules {
+        if plan.online_evaluation_rules.created.contains(&rule.name)
+            || plan.online_evaluation_rules.updated.contains(&rule.name)
+        {
+            let new_rule = NewOnlineEvaluationRule {
+                name: rule.name.clone(),
+                span_path: rule.span_path.clone(),
+                span_name: rule.span_name.clone(),
+                attribute_filters: rule.attribute_filters.clone(),
+                sample_percentage: rule.sample_percentage,
+                evaluator: rule.evaluator.clone(),
+            };
+            db::online_evaluation_rules::upsert_online_evaluation_rule(
+                &mut *tx,
+                project_id,
+                &new_rule,
+                rule.enabled,
+            )
+            .await?;
+        }
+    }
+    for name in &plan.online_evaluation_rules.deleted {
+        db::online_evaluation_rules::delete_online_evaluation_rule_by_name(
+            &mut *tx, project_id, name,
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn definitions() -> AnalyticsDefinitions {
+        serde_json::from_value(json!({
+            "version": 1,
+            "customMetrics": [
+                {"name": " tokens_per_second ", "expression": "completion_tokens / duration_seconds"}
+            ],
+            "judgeEvaluators": [{
+                "name": "relevance",
+                "model": "openai:gpt-4o-mini",
+                "prompt": "Is {{output}} relevant to {{input}}?",
+                "scoreType": "CATEGORICAL",
+                "labels": ["relevant", " ", "off-topic"]
+            }],
+            "onlineEvaluationRules": [
+                {"name": "relevance", "evaluator": {"type": "judge", "judge": "relevance"}},
+                {"name": "not_empty", "spanName": "", "evaluator": {"type": "notEmpty"}}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_normalize() {
+        let definitions = definitions().normalize().unwrap();
+        assert_eq!(definitions.custom_metrics[0].name, "tokens_per_second");
+        assert_eq!(
+            definitions.judge_evaluators[0].labels,
+            vec!["relevant", "off-topic"]
+        );
+        assert_eq!(definitions.online_evaluation_rules[0].name, "not_empty");
+        assert_eq!(definitions.online_evaluation_rules[0].span_name, None);
+        assert_eq!(definitions.referenced_judges(), vec!["relevance"]);
+
+        // normalizing is idempotent, so that exported files apply without changes
+        assert_eq!(definitions.clone().normalize().unwrap(), definitions);
+    }
+
+    #[test]
+    fn test_normalize_rejects_invalid_definitions() {
+        let mut unsupported_version = definitions();
+        unsupported_version.version = 2;
+        assert!(unsupported_version.normalize().is_err());
+
+        let mut duplicate = definitions();
+        duplicate
+            .custom_metrics
+            .push(duplicate.custom_metrics[0].clone());
+        assert!(duplicate.normalize().is_err());
+
+        let mut unlabeled = definitions();
+        unlabeled.judge_evaluators[0].labels.clear();
+        assert!(unlabeled.normalize().is_err());
+    }
+
+    #[test]
+    fn test_plan() {
+        let current = definitions().normalize().unwrap();
+        let mut desired = current.clone();
+        desired.custom_metrics[0].description = Some("Output speed".to_string());
+        desired.online_evaluation_rules.remove(0);
+        desired.judge_evaluators.clear();
+
+        let plan = AnalyticsDefinitionsPlan::between(&current, &desired, false);
+        assert_eq!(plan.custom_metrics.updated, vec!["tokens_per_second"]);
+        assert!(plan.judge_evaluators.is_empty());
+        assert!(plan.online_evaluation_rules.is_empty());
+        assert_eq!(plan.judges_after(&current, &desired), vec!["relevance"]);
+
+        let plan = AnalyticsDefinitionsPlan::between(&current, &desired, true);
+        assert_eq!(plan.judge_evaluators.deleted, vec!["relevance"]);
+        assert_eq!(plan.online_evaluation_rules.deleted, vec!["not_empty"]);
+        assert!(plan.judges_after(&current, &desired).is_empty());
+
+        assert!(AnalyticsDefinitionsPlan::between(&current, &current, true).is_empty());
+    }
+}
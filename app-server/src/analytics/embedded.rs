@@ -0,0 +1,920 @@
+//! Analytics store of the embedded mode, see `crate::embedded`. Rows are kept in a SQLite
+//! database, so that they survive restarts, and loaded into an [`InMemoryAnalyticsStore`] on
+//! start, which answers the queries. It is meant for the data of a laptop or a CI run.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use uuid::Uuid;
+
+use crate::ch::{
+    browser_events::BrowserEvent,
+    browser_snapshots::BrowserSnapshot,
+    evaluation_scores::{
+        ComparedEvaluationScoresBounds, EvaluationScore, EvaluationScoreBucket,
+        EvaluationScoreDiff, EvaluationScoreHistogramBucket, EvaluationScoreLabelFrequency,
+        EvaluationScorePassRate, EvaluationScorePercentile, EvaluationScoreStats,
+        EvaluationScoreTrendPoint, FilteredEvaluationScore,
+    },
+    events::CHEvent,
+    filter_expression::CompiledFilter,
+    modifiers::GroupByInterval,
+    query::{AnalyticsQuery, QueryContext, QueryResultRow},
+    span_scores::{AnnotatorSpanScore, ScoreScatterPoint, SpanScore, SpanScoresBounds},
+    span_search::{CHSpanSearchEntry, SpanSearchField, SpanSearchResult},
+    spans::{
+        AgentActionStats, AgentFailureSelector, CHSpan, FilteredSpan, FilteredTrace, HeatmapCell,
+        HeatmapMetric, LatencyLevel, LatencyPercentilesPoint, LatencySpanFilter,
+        PipelineLatencyPercentiles, ShadowDiffReport, TraceLatencyAndCost,
+    },
+    table_stats::TableStats,
+    Aggregation, MetricTimeValue,
+};
+
+use super::{custom_metrics::MetricExpression, in_memory::InMemoryAnalyticsStore, AnalyticsStore};
+
+const SPANS: &str = "spans";
+const SHADOW_SPANS: &str = "shadow_spans";
+const SPAN_SEARCH: &str = "span_search";
+const EVENTS: &str = "events";
+const EVALUATION_SCORES: &str = "evaluation_scores";
+const SPAN_SCORES: &str = "span_scores";
+const BROWSER_EVENTS: &str = "browser_events";
+const BROWSER_SNAPSHOTS: &str = "browser_snapshots";
+
+/// Rows of all tables as JSON. `row_id` is set for the rows that are updated, i.e. spans.
+const CREATE_ROWS_TABLE: &str = "CREATE TABLE IF NOT EXISTS analytics_rows (
+    table_name TEXT NOT NULL,
+    row_id TEXT,
+    row TEXT NOT NULL
+)";
+const CREATE_ROWS_INDEX: &str = "CREATE INDEX IF NOT EXISTS analytics_rows_table_name_row_id_idx
+    ON analytics_rows (table_name, row_id)";
+
+pub struct EmbeddedAnalyticsStore {
+    pool: SqlitePool,
+    rows: InMemoryAnalyticsStore,
+}
+
+async fn load<T: DeserializeOwned>(pool: &SqlitePool, table_name: &str) -> Result<Vec<T>> {
+    let rows = sqlx::query_scalar::<_, String>(
+        "SELECT row FROM analytics_rows WHERE table_name = ? ORDER BY rowid",
+    )
+    .bind(table_name)
+    .fetch_all(pool)
+    .await?;
+    rows.iter()
+        .map(|row| Ok(serde_json::from_str(row)?))
+        .collect()
+}
+
+impl EmbeddedAnalyticsStore {
+    /// Opens the database, creating it if it doesn't exist, and loads its rows
+    pub async fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let pool = SqlitePoolOptions::new()
+            // SQLite has a single writer
+            .max_connections(1)
+            .connect_with(
+                SqliteConnectOptions::new()
+                    .filename(path)
+                    .create_if_missing(true),
+            )
+            .await?;
+        sqlx::query(CREATE_ROWS_TABLE).execute(&pool).await?;
+        sqlx::query(CREATE_ROWS_INDEX).execute(&pool).await?;
+
+        let rows = InMemoryAnalyticsStore::default();
+        *rows.spans.lock().unwrap() = load(&pool, SPANS).await?;
+        *rows.shadow_spans.lock().unwrap() = load(&pool, SHADOW_SPANS).await?;
+        *rows.span_search.lock().unwrap() = load(&pool, SPAN_SEARCH).await?;
+        *rows.events.lock().unwrap() = load(&pool, EVENTS).await?;
+        *rows.evaluation_scores.lock().unwrap() = load(&pool, EVALUATION_SCORES).await?;
+        *rows.span_scores.lock().unwrap() = load(&pool, SPAN_SCORES).await?;
+        *rows.browser_events.lock().unwrap() = load(&pool, BROWSER_EVENTS).await?;
+        *rows.browser_snapshots.lock().unwrap() = load(&pool, BROWSER_SNAPSHOTS).await?;
+        log::info!(
+            "Embedded analytics store loaded {} spans from {}",
+            rows.spans.lock().unwrap().len(),
+            path.display()
+        );
+
+        Ok(Self { pool, rows })
+    }
+
+    /// Rows are persisted before they are added to the in-memory store, so that queries never
+    /// return rows that would be lost on restart
+    async fn persist<T: Serialize + Sync>(
+        &self,
+        table_name: &str,
+        rows: &[T],
+        row_id: fn(&T) -> Option<String>,
+    ) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        for row in rows {
+            sqlx::query("INSERT INTO analytics_rows (table_name, row_id, row) VALUES (?, ?, ?)")
+                .bind(table_name)
+                .bind(row_id(row))
+                .bind(serde_json::to_string(row)?)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn persist_updated_spans(&self, spans: &[CHSpan]) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        for span in spans {
+            sqlx::query("UPDATE analytics_rows SET row = ? WHERE table_name = ? AND row_id = ?")
+                .bind(serde_json::to_string(span)?)
+                .bind(SPANS)
+                .bind(span.span_id.to_string())
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Copies of the project's spans with the new value of the promoted attribute, the spans
+    /// without a value are left out
+    fn spans_with_promoted_attribute(
+        &self,
+        project_id: Uuid,
+        slot: usize,
+        value: impl Fn(&CHSpan) -> Option<String>,
+    ) -> Vec<CHSpan> {
+        self.rows
+            .spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|span| span.project_id == project_id)
+            .filter_map(|span| {
+                let mut span = span.clone();
+                span.set_promoted_attribute(slot, value(&span)?);
+                Some(span)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl AnalyticsStore for EmbeddedAnalyticsStore {
+    async fn insert_span(&self, span: &CHSpan) -> Result<()> {
+        self.persist(SPANS, std::slice::from_ref(span), |span| {
+            Some(span.span_id.to_string())
+        })
+        .await?;
+        self.rows.insert_span(span).await
+    }
+
+    async fn insert_shadow_span(&self, span: &CHSpan) -> Result<()> {
+        self.persist(SHADOW_SPANS, std::slice::from_ref(span), |_| None)
+            .await?;
+        self.rows.insert_shadow_span(span).await
+    }
+
+    async fn insert_span_search_entry(&self, entry: &CHSpanSearchEntry) -> Result<()> {
+        self.persist(SPAN_SEARCH, std::slice::from_ref(entry), |_| None)
+            .await?;
+        self.rows.insert_span_search_entry(entry).await
+    }
+
+    async fn insert_events(&self, events: Vec<CHEvent>) -> Result<()> {
+        self.persist(EVENTS, &events, |_| None).await?;
+        self.rows.insert_events(events).await
+    }
+
+    async fn insert_evaluation_scores(
+        &self,
+        evaluation_scores: Vec<EvaluationScore>,
+    ) -> Result<()> {
+        self.persist(EVALUATION_SCORES, &evaluation_scores, |_| None)
+            .await?;
+        self.rows.insert_evaluation_scores(evaluation_scores).await
+    }
+
+    async fn insert_span_scores(&self, span_scores: Vec<SpanScore>) -> Result<()> {
+        self.persist(SPAN_SCORES, &span_scores, |_| None).await?;
+        self.rows.insert_span_scores(span_scores).await
+    }
+
+    async fn insert_browser_events(&self, events: Vec<BrowserEvent>) -> Result<()> {
+        self.persist(BROWSER_EVENTS, &events, |_| None).await?;
+        self.rows.insert_browser_events(events).await
+    }
+
+    async fn insert_browser_snapshots(&self, snapshots: Vec<BrowserSnapshot>) -> Result<()> {
+        self.persist(BROWSER_SNAPSHOTS, &snapshots, |_| None)
+            .await?;
+        self.rows.insert_browser_snapshots(snapshots).await
+    }
+
+    async fn update_promoted_attribute_values(
+        &self,
+        project_id: Uuid,
+        slot: usize,
+        values: Vec<(Uuid, String)>,
+    ) -> Result<()> {
+        let new_values = values.iter().cloned().collect::<HashMap<_, _>>();
+        let spans = self.spans_with_promoted_attribute(project_id, slot, |span| {
+            new_values.get(&span.span_id).cloned()
+        });
+        self.persist_updated_spans(&spans).await?;
+        self.rows
+            .update_promoted_attribute_values(project_id, slot, values)
+            .await
+    }
+
+    async fn clear_promoted_attribute(&self, project_id: Uuid, slot: usize) -> Result<()> {
+        let spans =
+            self.spans_with_promoted_attribute(project_id, slot, |_| Some(String::from("<null>")));
+        self.persist_updated_spans(&spans).await?;
+        self.rows.clear_promoted_attribute(project_id, slot).await
+    }
+
+    async fn get_bounds(
+        &self,
+        project_id: &Uuid,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+        self.rows
+            .get_bounds(project_id, table_name, column_name)
+            .await
+    }
+
+    async fn get_project_table_stats(&self, project_id: Uuid) -> Result<Vec<TableStats>> {
+        self.rows.get_project_table_stats(project_id).await
+    }
+
+    async fn get_total_trace_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        self.rows
+            .get_total_trace_count_metrics_relative(group_by_interval, project_id, past_hours)
+            .await
+    }
+
+    async fn get_total_trace_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        self.rows
+            .get_total_trace_count_metrics_absolute(
+                group_by_interval,
+                project_id,
+                start_time,
+                end_time,
+            )
+            .await
+    }
+
+    async fn get_trace_latency_seconds_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        self.rows
+            .get_trace_latency_seconds_metrics_relative(
+                group_by_interval,
+                project_id,
+                past_hours,
+                aggregation,
+            )
+            .await
+    }
+
+    async fn get_trace_latency_seconds_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        self.rows
+            .get_trace_latency_seconds_metrics_absolute(
+                group_by_interval,
+                project_id,
+                start_time,
+                end_time,
+                aggregation,
+            )
+            .await
+    }
+
+    async fn get_total_token_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        self.rows
+            .get_total_token_count_metrics_relative(
+                group_by_interval,
+                project_id,
+                past_hours,
+                aggregation,
+            )
+            .await
+    }
+
+    async fn get_total_token_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        self.rows
+            .get_total_token_count_metrics_absolute(
+                group_by_interval,
+                project_id,
+                start_time,
+                end_time,
+                aggregation,
+            )
+            .await
+    }
+
+    async fn get_cost_usd_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        self.rows
+            .get_cost_usd_metrics_relative(group_by_interval, project_id, past_hours, aggregation)
+            .await
+    }
+
+    async fn get_cost_usd_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        self.rows
+            .get_cost_usd_metrics_absolute(
+                group_by_interval,
+                project_id,
+                start_time,
+                end_time,
+                aggregation,
+            )
+            .await
+    }
+
+    async fn get_total_event_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        template_id: Uuid,
+        past_hours: i64,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        self.rows
+            .get_total_event_count_metrics_relative(
+                group_by_interval,
+                project_id,
+                template_id,
+                past_hours,
+            )
+            .await
+    }
+
+    async fn get_total_event_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        template_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        self.rows
+            .get_total_event_count_metrics_absolute(
+                group_by_interval,
+                project_id,
+                template_id,
+                start_time,
+                end_time,
+            )
+            .await
+    }
+
+    async fn get_average_evaluation_score(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<f64> {
+        self.rows
+            .get_average_evaluation_score(project_id, evaluation_id, name)
+            .await
+    }
+
+    async fn get_evaluation_score_stats(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<EvaluationScoreStats> {
+        self.rows
+            .get_evaluation_score_stats(project_id, evaluation_id, name)
+            .await
+    }
+
+    async fn get_evaluation_score_pass_rate(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<EvaluationScorePassRate> {
+        self.rows
+            .get_evaluation_score_pass_rate(project_id, evaluation_id, name)
+            .await
+    }
+
+    async fn get_evaluation_score_label_frequencies(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<Vec<EvaluationScoreLabelFrequency>> {
+        self.rows
+            .get_evaluation_score_label_frequencies(project_id, evaluation_id, name)
+            .await
+    }
+
+    async fn get_evaluation_score_trend(
+        &self,
+        project_id: Uuid,
+        group_id: String,
+        name: String,
+    ) -> Result<Vec<EvaluationScoreTrendPoint>> {
+        self.rows
+            .get_evaluation_score_trend(project_id, group_id, name)
+            .await
+    }
+
+    async fn get_numeric_evaluation_score_names(&self, project_id: Uuid) -> Result<Vec<String>> {
+        self.rows
+            .get_numeric_evaluation_score_names(project_id)
+            .await
+    }
+
+    async fn get_evaluation_score_time_series(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        self.rows
+            .get_evaluation_score_time_series(
+                group_by_interval,
+                project_id,
+                name,
+                start_time,
+                end_time,
+            )
+            .await
+    }
+
+    async fn get_evaluation_score_buckets_based_on_bounds(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+        lower_bound: f64,
+        upper_bound: f64,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        self.rows
+            .get_evaluation_score_buckets_based_on_bounds(
+                project_id,
+                evaluation_id,
+                name,
+                lower_bound,
+                upper_bound,
+                bucket_count,
+            )
+            .await
+    }
+
+    async fn get_global_evaluation_scores_bounds(
+        &self,
+        project_id: Uuid,
+        evaluation_ids: &Vec<Uuid>,
+        name: String,
+    ) -> Result<ComparedEvaluationScoresBounds> {
+        self.rows
+            .get_global_evaluation_scores_bounds(project_id, evaluation_ids, name)
+            .await
+    }
+
+    async fn get_evaluation_score_histogram(
+        &self,
+        project_id: Uuid,
+        evaluation_ids: &Vec<Uuid>,
+        name: String,
+        max_bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreHistogramBucket>> {
+        self.rows
+            .get_evaluation_score_histogram(project_id, evaluation_ids, name, max_bucket_count)
+            .await
+    }
+
+    async fn get_evaluation_score_percentiles(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        baseline_evaluation_id: Uuid,
+    ) -> Result<Vec<EvaluationScorePercentile>> {
+        self.rows
+            .get_evaluation_score_percentiles(project_id, evaluation_id, baseline_evaluation_id)
+            .await
+    }
+
+    async fn get_evaluation_score_diffs(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        baseline_evaluation_id: Uuid,
+    ) -> Result<Vec<EvaluationScoreDiff>> {
+        self.rows
+            .get_evaluation_score_diffs(project_id, evaluation_id, baseline_evaluation_id)
+            .await
+    }
+
+    async fn get_average_span_score(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<f64> {
+        self.rows
+            .get_average_span_score(project_id, name, start_time, end_time)
+            .await
+    }
+
+    async fn get_span_score_buckets_based_on_bounds(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        lower_bound: f64,
+        upper_bound: f64,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        self.rows
+            .get_span_score_buckets_based_on_bounds(
+                project_id,
+                name,
+                start_time,
+                end_time,
+                lower_bound,
+                upper_bound,
+                bucket_count,
+            )
+            .await
+    }
+
+    async fn get_span_scores_bounds(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<SpanScoresBounds> {
+        self.rows
+            .get_span_scores_bounds(project_id, name, start_time, end_time)
+            .await
+    }
+
+    async fn get_span_score_trend(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        self.rows
+            .get_span_score_trend(group_by_interval, project_id, name, start_time, end_time)
+            .await
+    }
+
+    async fn get_span_score_names(&self, project_id: Uuid) -> Result<Vec<String>> {
+        self.rows.get_span_score_names(project_id).await
+    }
+
+    async fn get_span_score_scatter(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<ScoreScatterPoint>> {
+        self.rows
+            .get_span_score_scatter(project_id, name, start_time, end_time, limit)
+            .await
+    }
+
+    async fn get_annotator_span_scores(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<AnnotatorSpanScore>> {
+        self.rows
+            .get_annotator_span_scores(project_id, start_time, end_time, limit)
+            .await
+    }
+
+    async fn get_traces_latency_and_cost(
+        &self,
+        project_id: Uuid,
+        trace_ids: &[Uuid],
+    ) -> Result<Vec<TraceLatencyAndCost>> {
+        self.rows
+            .get_traces_latency_and_cost(project_id, trace_ids)
+            .await
+    }
+
+    async fn get_shadow_diff_report(
+        &self,
+        project_id: Option<Uuid>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<ShadowDiffReport> {
+        self.rows
+            .get_shadow_diff_report(project_id, start_time, end_time)
+            .await
+    }
+
+    async fn get_custom_metric_buckets(
+        &self,
+        project_id: Uuid,
+        expression: &MetricExpression,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        self.rows
+            .get_custom_metric_buckets(project_id, expression, start_time, end_time, bucket_count)
+            .await
+    }
+
+    async fn run_query(
+        &self,
+        project_id: Uuid,
+        query: &AnalyticsQuery,
+        context: &QueryContext,
+    ) -> Result<Vec<QueryResultRow>> {
+        self.rows.run_query(project_id, query, context).await
+    }
+
+    async fn get_pipeline_latency_percentiles(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<PipelineLatencyPercentiles>> {
+        self.rows
+            .get_pipeline_latency_percentiles(project_id, start_time, end_time)
+            .await
+    }
+
+    async fn get_latency_percentiles_over_time(
+        &self,
+        project_id: Uuid,
+        level: LatencyLevel,
+        filter: &LatencySpanFilter,
+        group_by_interval: GroupByInterval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<LatencyPercentilesPoint>> {
+        self.rows
+            .get_latency_percentiles_over_time(
+                project_id,
+                level,
+                filter,
+                group_by_interval,
+                start_time,
+                end_time,
+            )
+            .await
+    }
+
+    async fn get_hour_of_week_heatmap(
+        &self,
+        project_id: Uuid,
+        pipeline: Option<&str>,
+        metric: HeatmapMetric,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<HeatmapCell>> {
+        self.rows
+            .get_hour_of_week_heatmap(project_id, pipeline, metric, start_time, end_time)
+            .await
+    }
+
+    async fn get_agent_action_stats(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<AgentActionStats>> {
+        self.rows
+            .get_agent_action_stats(project_id, start_time, end_time)
+            .await
+    }
+
+    async fn get_agent_failure_selectors(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<AgentFailureSelector>> {
+        self.rows
+            .get_agent_failure_selectors(project_id, start_time, end_time, limit)
+            .await
+    }
+
+    async fn search_spans(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<SpanSearchResult>> {
+        self.rows
+            .search_spans(
+                project_id, query, field, start_time, end_time, limit, offset,
+            )
+            .await
+    }
+
+    async fn count_span_search_results(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<u64> {
+        self.rows
+            .count_span_search_results(project_id, query, field, start_time, end_time)
+            .await
+    }
+
+    async fn query_spans(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredSpan>> {
+        self.rows
+            .query_spans(project_id, filter, start_time, end_time, limit, offset)
+            .await
+    }
+
+    async fn query_traces(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredTrace>> {
+        self.rows
+            .query_traces(project_id, filter, start_time, end_time, limit, offset)
+            .await
+    }
+
+    async fn query_evaluation_scores(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredEvaluationScore>> {
+        self.rows
+            .query_evaluation_scores(project_id, filter, start_time, end_time, limit, offset)
+            .await
+    }
+
+    async fn get_trace_browser_events(
+        &self,
+        project_id: Uuid,
+        trace_id: Uuid,
+    ) -> Result<Vec<BrowserEvent>> {
+        self.rows
+            .get_trace_browser_events(project_id, trace_id)
+            .await
+    }
+
+    async fn get_nearest_browser_snapshot(
+        &self,
+        project_id: Uuid,
+        session_id: String,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<BrowserSnapshot>> {
+        self.rows
+            .get_nearest_browser_snapshot(project_id, session_id, timestamp)
+            .await
+    }
+
+    async fn get_browser_snapshots_at_step(
+        &self,
+        project_id: Uuid,
+        session_id: String,
+        step: u32,
+    ) -> Result<Vec<BrowserSnapshot>> {
+        self.rows
+            .get_browser_snapshots_at_step(project_id, session_id, step)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ch::evaluation_scores::{ScoreSource, ScoreType};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rows_survive_reopening() {
+        let path =
+            std::env::temp_dir().join(format!("laminar-analytics-{}.sqlite", Uuid::new_v4()));
+        let project_id = Uuid::new_v4();
+        let evaluation_id = Uuid::new_v4();
+        let score = |value: f64| EvaluationScore {
+            project_id,
+            group_id: "default".to_string(),
+            evaluation_id,
+            result_id: Uuid::new_v4(),
+            name: "accuracy".to_string(),
+            value,
+            timestamp: Utc::now(),
+            datapoint_key: String::new(),
+            score_type: ScoreType::NUMERIC,
+            label: String::new(),
+            source: ScoreSource::AUTO,
+        };
+
+        let store = EmbeddedAnalyticsStore::open(&path).await.unwrap();
+        store
+            .insert_evaluation_scores(vec![score(0.5), score(1.0)])
+            .await
+            .unwrap();
+        store.pool.close().await;
+
+        let store = EmbeddedAnalyticsStore::open(&path).await.unwrap();
+        let average = store
+            .get_average_evaluation_score(project_id, evaluation_id, "accuracy".to_string())
+            .await
+            .unwrap();
+        assert_eq!(average, 0.75);
+
+        store.pool.close().await;
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+//! Hour-of-week heatmaps of traffic, error rate and cost, e.g. for capacity planning.
+
+use serde::Serialize;
+
+use crate::ch::spans::HeatmapCell;
+
+/// Matrix of 7 days, Monday first, by 24 hours in UTC. Traffic values are trace counts and cost
+/// values are USD over the whole time window, error rate values are between 0 and 1.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HourOfWeekHeatmap {
+    pub values: Vec<Vec<f64>>,
+    pub max_value: f64,
+}
+
+impl HourOfWeekHeatmap {
+    /// Hours without spans are 0 for every metric
+    pub fn from_cells(cells: Vec<HeatmapCell>) -> Self {
+        let mut values = vec![vec![0.0; 24]; 7];
+        for cell in cells {
+            let (day, hour) = (cell.day_of_week as usize, cell.hour as usize);
+            if (1..=7).contains(&day) && hour < 24 {
+                values[day - 1][hour] = cell.value;
+            }
+        }
+        let max_value = values.iter().flatten().copied().fold(0.0, f64::max);
+
+        Self { values, max_value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_cells() {
+        let heatmap = HourOfWeekHeatmap::from_cells(vec![
+            HeatmapCell {
+                day_of_week: 1,
+                hour: 0,
+                value: 3.0,
+            },
+            HeatmapCell {
+                day_of_week: 7,
+                hour: 23,
+                value: 5.0,
+            },
+        ]);
+        assert_eq!(heatmap.values.len(), 7);
+        assert_eq!(heatmap.values[0][0], 3.0);
+        assert_eq!(heatmap.values[6][23], 5.0);
+        assert_eq!(heatmap.values[3][12], 0.0);
+        assert_eq!(heatmap.max_value, 5.0);
+    }
+}
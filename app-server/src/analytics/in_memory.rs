@@ -0,0 +1,2215 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use uuid::Uuid;
+
+use crate::{
+    ch::{
+        browser_events::BrowserEvent,
+        browser_snapshots::BrowserSnapshot,
+        evaluation_scores::{
+            label_frequencies, ComparedEvaluationScoresBounds, EvaluationScore,
+            EvaluationScoreBucket, EvaluationScoreDiff, EvaluationScoreHistogramBucket,
+            EvaluationScoreLabelFrequency, EvaluationScorePassRate, EvaluationScorePercentile,
+            EvaluationScoreStats, EvaluationScoreTrendPoint, FilteredEvaluationScore,
+            HistogramBounds, ScoreType,
+        },
+        events::CHEvent,
+        filter_expression::CompiledFilter,
+        modifiers::GroupByInterval,
+        query::{AnalyticsQuery, QueryContext, QueryResultRow},
+        span_scores::{AnnotatorSpanScore, ScoreScatterPoint, SpanScore, SpanScoresBounds},
+        span_search::{matching_snippet, CHSpanSearchEntry, SpanSearchField, SpanSearchResult},
+        spans::{
+            AgentActionStats, AgentFailureSelector, CHSpan, FilteredSpan, FilteredTrace,
+            HeatmapCell, HeatmapMetric, LatencyLevel, LatencyPercentilesPoint, LatencySpanFilter,
+            PipelineLatencyPercentiles, ShadowDiffReport, TraceLatencyAndCost,
+        },
+        table_stats::TableStats,
+        utils::{chrono_to_nanoseconds, nanoseconds_to_chrono},
+        Aggregation, MetricTimeValue,
+    },
+    db::spans::SpanType,
+};
+
+use super::{custom_metrics::MetricExpression, AnalyticsStore, UnsupportedInDeploymentMode};
+
+/// Keeps inserted rows in memory, so that tests can inspect them. Metrics, time series,
+/// statistics and span searches are computed from the rows. Aggregate queries and filter
+/// expression queries are compiled to ClickHouse SQL, so they fail with
+/// `UnsupportedInDeploymentMode`.
+#[derive(Default)]
+pub struct InMemoryAnalyticsStore {
+    pub spans: Mutex<Vec<CHSpan>>,
+    pub shadow_spans: Mutex<Vec<CHSpan>>,
+    pub span_search: Mutex<Vec<CHSpanSearchEntry>>,
+    pub events: Mutex<Vec<CHEvent>>,
+    pub evaluation_scores: Mutex<Vec<EvaluationScore>>,
+    pub span_scores: Mutex<Vec<SpanScore>>,
+    pub browser_events: Mutex<Vec<BrowserEvent>>,
+    pub browser_snapshots: Mutex<Vec<BrowserSnapshot>>,
+}
+
+fn score_buckets(
+    values: &[f64],
+    lower_bound: f64,
+    upper_bound: f64,
+    bucket_count: u64,
+) -> Vec<EvaluationScoreBucket> {
+    let step_size = (upper_bound - lower_bound) / bucket_count as f64;
+    (1..=bucket_count)
+        .map(|interval_num| {
+            let bucket_lower_bound = lower_bound + (interval_num - 1) as f64 * step_size;
+            let is_last = interval_num == bucket_count;
+            let bucket_upper_bound = if is_last {
+                upper_bound
+            } else {
+                lower_bound + interval_num as f64 * step_size
+            };
+            // the last bucket includes the upper bound
+            let height = values
+                .iter()
+                .filter(|value| {
+                    **value >= bucket_lower_bound
+                        && (**value < bucket_upper_bound
+                            || (is_last && **value <= bucket_upper_bound))
+                })
+                .count() as u64;
+            EvaluationScoreBucket {
+                lower_bound: bucket_lower_bound,
+                upper_bound: bucket_upper_bound,
+                height,
+            }
+        })
+        .collect()
+}
+
+fn average(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Nearest-rank percentile of sorted values, 0 if there are none
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted
+        .get(rank.saturating_sub(1))
+        .copied()
+        .unwrap_or_default()
+}
+
+fn aggregate(values: &[f64], aggregation: &Aggregation) -> f64 {
+    match aggregation {
+        Aggregation::Total => values.iter().sum(),
+        Aggregation::Average => average(values),
+    }
+}
+
+fn to_integer_series(series: Vec<MetricTimeValue<f64>>) -> Vec<MetricTimeValue<i64>> {
+    series
+        .into_iter()
+        .map(|point| MetricTimeValue {
+            time: point.time,
+            value: point.value.round() as i64,
+        })
+        .collect()
+}
+
+fn nanoseconds_to_seconds(nanoseconds: i64) -> i64 {
+    nanoseconds.div_euclid(1_000_000_000)
+}
+
+fn matches_latency_filter(filter: &LatencySpanFilter, span: &CHSpan) -> bool {
+    [
+        (&filter.name, &span.name),
+        (&filter.path, &span.path),
+        (&filter.model, &span.model),
+    ]
+    .into_iter()
+    .all(|(expected, value)| expected.as_ref().map_or(true, |expected| expected == value))
+}
+
+/// Time range of a time series, and the intervals that are filled when they have no values, like
+/// `group_by_time_absolute_statement` and `group_by_time_relative_statement` do
+struct TimeSeriesRange {
+    group_by_interval: GroupByInterval,
+    /// Unix timestamps, inclusive
+    start: i64,
+    end: i64,
+    /// Start of the first filled interval and the end of the last one
+    fill_from: i64,
+    fill_to: i64,
+}
+
+impl TimeSeriesRange {
+    fn absolute(
+        group_by_interval: GroupByInterval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Self {
+        let mut range = Self {
+            group_by_interval,
+            start: start_time.timestamp(),
+            end: end_time.timestamp(),
+            fill_from: 0,
+            fill_to: 0,
+        };
+        range.fill_from = range.interval_start(range.start);
+        range.fill_to = range.interval_start(range.end + group_by_interval.seconds());
+        range
+    }
+
+    fn relative(group_by_interval: GroupByInterval, past_hours: i64) -> Self {
+        let now = Utc::now().timestamp();
+        let mut range = Self {
+            group_by_interval,
+            start: now - past_hours * 60 * 60,
+            end: i64::MAX,
+            fill_from: 0,
+            fill_to: 0,
+        };
+        range.fill_from = range.interval_start(range.start + group_by_interval.seconds());
+        range.fill_to = range.interval_start(now + group_by_interval.seconds());
+        range
+    }
+
+    fn contains(&self, timestamp: i64) -> bool {
+        self.start <= timestamp && timestamp <= self.end
+    }
+
+    /// Start of the interval that contains the unix timestamp, like `toStartOf<Interval>`
+    fn interval_start(&self, timestamp: i64) -> i64 {
+        let seconds = self.group_by_interval.seconds();
+        timestamp.div_euclid(seconds) * seconds
+    }
+
+    /// Values by interval start, ordered by time, with the default value in the empty intervals
+    fn fill<T: Default>(&self, mut values: BTreeMap<i64, T>) -> Vec<MetricTimeValue<T>> {
+        let mut time = self.fill_from;
+        while time < self.fill_to {
+            values.entry(time).or_default();
+            time += self.group_by_interval.seconds();
+        }
+        values
+            .into_iter()
+            .map(|(time, value)| MetricTimeValue {
+                time: time as u32,
+                value,
+            })
+            .collect()
+    }
+
+    /// Aggregate of the values in each interval. The values are keyed by unix timestamp and only
+    /// the ones in the range are included.
+    fn aggregate(
+        &self,
+        values: impl IntoIterator<Item = (i64, f64)>,
+        aggregation: &Aggregation,
+    ) -> Vec<MetricTimeValue<f64>> {
+        let mut intervals = BTreeMap::<i64, Vec<f64>>::new();
+        for (timestamp, value) in values {
+            if self.contains(timestamp) {
+                intervals
+                    .entry(self.interval_start(timestamp))
+                    .or_default()
+                    .push(value);
+            }
+        }
+        self.fill(
+            intervals
+                .into_iter()
+                .map(|(time, values)| (time, aggregate(&values, aggregation)))
+                .collect(),
+        )
+    }
+}
+
+/// Start time and end time, in nanoseconds, total tokens and total cost of a trace
+#[derive(Default)]
+struct TraceTotals {
+    start_time: i64,
+    end_time: i64,
+    total_tokens: i64,
+    total_cost: f64,
+}
+
+impl TraceTotals {
+    fn latency(&self) -> f64 {
+        (self.end_time - self.start_time) as f64 / 1e9
+    }
+}
+
+impl InMemoryAnalyticsStore {
+    /// Type, value and label of the evaluation's scores of the name
+    fn evaluation_scores_of(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: &str,
+    ) -> Vec<(ScoreType, f64, String)> {
+        self.evaluation_scores
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|score| {
+                score.project_id == project_id
+                    && score.evaluation_id == evaluation_id
+                    && score.name == name
+            })
+            .map(|score| (score.score_type, score.value, score.label.clone()))
+            .collect()
+    }
+
+    /// Values of the numeric and boolean scores, categorical scores have no value
+    fn evaluation_score_values(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: &str,
+    ) -> Vec<f64> {
+        self.evaluation_scores_of(project_id, evaluation_id, name)
+            .into_iter()
+            .filter(|(score_type, _, _)| *score_type != ScoreType::CATEGORICAL)
+            .map(|(_, value, _)| value)
+            .collect()
+    }
+
+    fn traces_of<'a>(spans: impl IntoIterator<Item = &'a CHSpan>) -> HashMap<Uuid, TraceTotals> {
+        let mut traces = HashMap::<Uuid, TraceTotals>::new();
+        for span in spans {
+            let trace = traces.entry(span.trace_id).or_insert(TraceTotals {
+                start_time: span.start_time,
+                end_time: span.end_time,
+                ..Default::default()
+            });
+            trace.start_time = trace.start_time.min(span.start_time);
+            trace.end_time = trace.end_time.max(span.end_time);
+            trace.total_tokens += span.total_tokens;
+            trace.total_cost += span.total_cost;
+        }
+        traces
+    }
+
+    /// Aggregate of a metric of the project's traces, grouped by the interval in which they
+    /// started, like `span_metric_query_relative` and `span_metric_query_absolute`
+    fn trace_metric_series(
+        &self,
+        project_id: Uuid,
+        range: TimeSeriesRange,
+        aggregation: &Aggregation,
+        metric: impl Fn(&TraceTotals) -> f64,
+    ) -> Vec<MetricTimeValue<f64>> {
+        let spans = self.spans.lock().unwrap();
+        let traces = Self::traces_of(spans.iter().filter(|span| span.project_id == project_id));
+        range.aggregate(
+            traces.values().map(|trace| {
+                let start_time = nanoseconds_to_seconds(trace.start_time);
+                (range.interval_start(start_time), metric(trace))
+            }),
+            aggregation,
+        )
+    }
+
+    fn event_count_series(
+        &self,
+        project_id: Uuid,
+        template_id: Uuid,
+        range: TimeSeriesRange,
+    ) -> Vec<MetricTimeValue<i64>> {
+        let events = self.events.lock().unwrap();
+        let mut ids = HashSet::new();
+        let counts = range.aggregate(
+            events
+                .iter()
+                .filter(|event| {
+                    event.project_id == project_id
+                        && event.template_id == template_id
+                        && ids.insert(event.id)
+                })
+                .map(|event| (nanoseconds_to_seconds(event.timestamp), 1.0)),
+            &Aggregation::Total,
+        );
+        to_integer_series(counts)
+    }
+
+    fn span_score_values(
+        &self,
+        project_id: Uuid,
+        name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Vec<f64> {
+        self.span_scores
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|score| {
+                score.project_id == project_id
+                    && score.name == name
+                    && score.timestamp >= start_time
+                    && score.timestamp <= end_time
+            })
+            .map(|score| score.value)
+            .collect()
+    }
+
+    /// Matching entries of the search index, the newest first
+    fn span_search_results(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Vec<SpanSearchResult> {
+        let start_time = chrono_to_nanoseconds(start_time);
+        let end_time = chrono_to_nanoseconds(end_time);
+        let mut results = self
+            .span_search
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| {
+                entry.project_id == project_id
+                    && entry.start_time >= start_time
+                    && entry.start_time <= end_time
+            })
+            .filter_map(|entry| {
+                let input_snippet = field
+                    .searches_input()
+                    .then(|| matching_snippet(&entry.input, query))
+                    .flatten();
+                let output_snippet = field
+                    .searches_output()
+                    .then(|| matching_snippet(&entry.output, query))
+                    .flatten();
+                if input_snippet.is_none() && output_snippet.is_none() {
+                    return None;
+                }
+                Some(SpanSearchResult {
+                    span_id: entry.span_id,
+                    trace_id: entry.trace_id,
+                    start_time: nanoseconds_to_chrono(entry.start_time),
+                    name: entry.name.clone(),
+                    path: Some(entry.path.clone()).filter(|path| path != "<null>"),
+                    input_snippet,
+                    output_snippet,
+                })
+            })
+            .collect::<Vec<_>>();
+        results.sort_by(|a, b| b.start_time.cmp(&a.start_time));
+        results
+    }
+}
+
+#[async_trait]
+impl AnalyticsStore for InMemoryAnalyticsStore {
+    async fn insert_span(&self, span: &CHSpan) -> Result<()> {
+        self.spans.lock().unwrap().push(span.clone());
+        Ok(())
+    }
+
+    async fn insert_shadow_span(&self, span: &CHSpan) -> Result<()> {
+        self.shadow_spans.lock().unwrap().push(span.clone());
+        Ok(())
+    }
+
+    async fn insert_span_search_entry(&self, entry: &CHSpanSearchEntry) -> Result<()> {
+        self.span_search.lock().unwrap().push(entry.clone());
+        Ok(())
+    }
+
+    async fn insert_events(&self, events: Vec<CHEvent>) -> Result<()> {
+        self.events.lock().unwrap().extend(events);
+        Ok(())
+    }
+
+    async fn insert_evaluation_scores(
+        &self,
+        evaluation_scores: Vec<EvaluationScore>,
+    ) -> Result<()> {
+        self.evaluation_scores
+            .lock()
+            .unwrap()
+            .extend(evaluation_scores);
+        Ok(())
+    }
+
+    async fn insert_span_scores(&self, span_scores: Vec<SpanScore>) -> Result<()> {
+        self.span_scores.lock().unwrap().extend(span_scores);
+        Ok(())
+    }
+
+    async fn insert_browser_events(&self, events: Vec<BrowserEvent>) -> Result<()> {
+        self.browser_events.lock().unwrap().extend(events);
+        Ok(())
+    }
+
+    async fn insert_browser_snapshots(&self, snapshots: Vec<BrowserSnapshot>) -> Result<()> {
+        self.browser_snapshots.lock().unwrap().extend(snapshots);
+        Ok(())
+    }
+
+    async fn get_bounds(
+        &self,
+        project_id: &Uuid,
+        table_name: &str,
+        _column_name: &str,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+        let times = match table_name {
+            "spans" => self
+                .spans
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|span| span.project_id == *project_id)
+                .map(|span| span.start_time)
+                .collect::<Vec<_>>(),
+            "events" => self
+                .events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| event.project_id == *project_id)
+                .map(|event| event.timestamp)
+                .collect::<Vec<_>>(),
+            "span_scores" => self
+                .span_scores
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|score| score.project_id == *project_id)
+                .map(|score| chrono_to_nanoseconds(score.timestamp))
+                .collect::<Vec<_>>(),
+            _ => return Err(anyhow::anyhow!("Invalid table name: {}", table_name)),
+        };
+        let now = chrono_to_nanoseconds(Utc::now());
+        Ok((
+            nanoseconds_to_chrono(times.iter().copied().min().unwrap_or(now)),
+            nanoseconds_to_chrono(times.iter().copied().max().unwrap_or(now)),
+        ))
+    }
+
+    async fn get_project_table_stats(&self, project_id: Uuid) -> Result<Vec<TableStats>> {
+        let tables = [
+            (
+                "spans",
+                self.spans
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|span| span.project_id == project_id)
+                    .map(|span| span.start_time)
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "events",
+                self.events
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|event| event.project_id == project_id)
+                    .map(|event| event.timestamp)
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "span_scores",
+                self.span_scores
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|score| score.project_id == project_id)
+                    .map(|score| chrono_to_nanoseconds(score.timestamp))
+                    .collect::<Vec<_>>(),
+            ),
+        ];
+        Ok(tables
+            .into_iter()
+            .map(|(table, times)| TableStats {
+                table: table.to_string(),
+                rows: times.len() as u64,
+                min_time: times.iter().copied().min().map(nanoseconds_to_chrono),
+                max_time: times.iter().copied().max().map(nanoseconds_to_chrono),
+            })
+            .collect())
+    }
+
+    async fn get_total_trace_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        let range = TimeSeriesRange::relative(group_by_interval, past_hours);
+        let counts = self.trace_metric_series(project_id, range, &Aggregation::Total, |_| 1.0);
+        Ok(to_integer_series(counts))
+    }
+
+    async fn get_total_trace_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        let range = TimeSeriesRange::absolute(group_by_interval, start_time, end_time);
+        let counts = self.trace_metric_series(project_id, range, &Aggregation::Total, |_| 1.0);
+        Ok(to_integer_series(counts))
+    }
+
+    async fn get_trace_latency_seconds_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        let range = TimeSeriesRange::relative(group_by_interval, past_hours);
+        Ok(self.trace_metric_series(project_id, range, &aggregation, TraceTotals::latency))
+    }
+
+    async fn get_trace_latency_seconds_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        let range = TimeSeriesRange::absolute(group_by_interval, start_time, end_time);
+        Ok(self.trace_metric_series(project_id, range, &aggregation, TraceTotals::latency))
+    }
+
+    async fn get_total_token_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        let range = TimeSeriesRange::relative(group_by_interval, past_hours);
+        let tokens = self.trace_metric_series(project_id, range, &aggregation, |trace| {
+            trace.total_tokens as f64
+        });
+        Ok(to_integer_series(tokens))
+    }
+
+    async fn get_total_token_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        let range = TimeSeriesRange::absolute(group_by_interval, start_time, end_time);
+        let tokens = self.trace_metric_series(project_id, range, &aggregation, |trace| {
+            trace.total_tokens as f64
+        });
+        Ok(to_integer_series(tokens))
+    }
+
+    async fn get_cost_usd_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        let range = TimeSeriesRange::relative(group_by_interval, past_hours);
+        Ok(self.trace_metric_series(project_id, range, &aggregation, |trace| trace.total_cost))
+    }
+
+    async fn get_cost_usd_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        let range = TimeSeriesRange::absolute(group_by_interval, start_time, end_time);
+        Ok(self.trace_metric_series(project_id, range, &aggregation, |trace| trace.total_cost))
+    }
+
+    async fn get_total_event_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        template_id: Uuid,
+        past_hours: i64,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        let range = TimeSeriesRange::relative(group_by_interval, past_hours);
+        Ok(self.event_count_series(project_id, template_id, range))
+    }
+
+    async fn get_total_event_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        template_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<i64>>> {
+        let range = TimeSeriesRange::absolute(group_by_interval, start_time, end_time);
+        Ok(self.event_count_series(project_id, template_id, range))
+    }
+
+    async fn get_average_evaluation_score(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<f64> {
+        let values = self.evaluation_score_values(project_id, evaluation_id, &name);
+        Ok(average(&values))
+    }
+
+    async fn get_evaluation_score_stats(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<EvaluationScoreStats> {
+        let mut values = self.evaluation_score_values(project_id, evaluation_id, &name);
+        values.sort_by(|a, b| a.total_cmp(b));
+        let average_value = average(&values);
+        let variance = values
+            .iter()
+            .map(|value| (value - average_value).powi(2))
+            .sum::<f64>()
+            / values.len().max(1) as f64;
+        Ok(EvaluationScoreStats {
+            count: values.len() as u64,
+            average_value,
+            min_value: values.first().copied().unwrap_or_default(),
+            max_value: values.last().copied().unwrap_or_default(),
+            median: percentile(&values, 0.5),
+            p90: percentile(&values, 0.9),
+            p95: percentile(&values, 0.95),
+            p99: percentile(&values, 0.99),
+            stddev: variance.sqrt(),
+        })
+    }
+
+    async fn get_evaluation_score_pass_rate(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<EvaluationScorePassRate> {
+        let values = self
+            .evaluation_scores_of(project_id, evaluation_id, &name)
+            .into_iter()
+            .filter(|(score_type, _, _)| *score_type == ScoreType::BOOLEAN)
+            .map(|(_, value, _)| value)
+            .collect::<Vec<_>>();
+        let passed_count = values.iter().filter(|value| **value == 1.0).count() as u64;
+        Ok(EvaluationScorePassRate {
+            count: values.len() as u64,
+            passed_count,
+            pass_rate: average(&values),
+        })
+    }
+
+    async fn get_evaluation_score_label_frequencies(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<Vec<EvaluationScoreLabelFrequency>> {
+        let scores = self.evaluation_scores_of(project_id, evaluation_id, &name);
+        Ok(label_frequencies(
+            scores
+                .iter()
+                .filter(|(score_type, _, _)| *score_type == ScoreType::CATEGORICAL)
+                .map(|(_, _, label)| label.as_str()),
+        ))
+    }
+
+    async fn get_evaluation_score_trend(
+        &self,
+        project_id: Uuid,
+        group_id: String,
+        name: String,
+    ) -> Result<Vec<EvaluationScoreTrendPoint>> {
+        let scores = self.evaluation_scores.lock().unwrap();
+        // (first timestamp, values) by evaluation
+        let mut evaluations = HashMap::<Uuid, (DateTime<Utc>, Vec<f64>)>::new();
+        for score in scores.iter().filter(|score| {
+            score.project_id == project_id
+                && score.group_id == group_id
+                && score.name == name
+                && score.score_type != ScoreType::CATEGORICAL
+        }) {
+            let entry = evaluations
+                .entry(score.evaluation_id)
+                .or_insert((score.timestamp, Vec::new()));
+            entry.0 = entry.0.min(score.timestamp);
+            entry.1.push(score.value);
+        }
+
+        let mut trend = evaluations
+            .into_iter()
+            .map(|(evaluation_id, (timestamp, mut values))| {
+                values.sort_by(|a, b| a.total_cmp(b));
+                EvaluationScoreTrendPoint {
+                    evaluation_id,
+                    timestamp,
+                    count: values.len() as u64,
+                    average_value: average(&values),
+                    p10: percentile(&values, 0.1),
+                    median: percentile(&values, 0.5),
+                    p90: percentile(&values, 0.9),
+                }
+            })
+            .collect::<Vec<_>>();
+        trend.sort_by(|a, b| (a.timestamp, a.evaluation_id).cmp(&(b.timestamp, b.evaluation_id)));
+
+        Ok(trend)
+    }
+
+    async fn get_numeric_evaluation_score_names(&self, project_id: Uuid) -> Result<Vec<String>> {
+        let mut names = self
+            .evaluation_scores
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|score| {
+                score.project_id == project_id && score.score_type != ScoreType::CATEGORICAL
+            })
+            .map(|score| score.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    async fn get_evaluation_score_time_series(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        let range = TimeSeriesRange::absolute(group_by_interval, start_time, end_time);
+        let scores = self.evaluation_scores.lock().unwrap();
+        Ok(range.aggregate(
+            scores
+                .iter()
+                .filter(|score| {
+                    score.project_id == project_id
+                        && score.name == name
+                        && score.score_type != ScoreType::CATEGORICAL
+                })
+                .map(|score| (score.timestamp.timestamp(), score.value)),
+            &Aggregation::Average,
+        ))
+    }
+
+    async fn get_evaluation_score_buckets_based_on_bounds(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+        lower_bound: f64,
+        upper_bound: f64,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        let values = self.evaluation_score_values(project_id, evaluation_id, &name);
+        Ok(score_buckets(
+            &values,
+            lower_bound,
+            upper_bound,
+            bucket_count,
+        ))
+    }
+
+    async fn get_global_evaluation_scores_bounds(
+        &self,
+        project_id: Uuid,
+        evaluation_ids: &Vec<Uuid>,
+        name: String,
+    ) -> Result<ComparedEvaluationScoresBounds> {
+        let upper_bound = self
+            .evaluation_scores
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|score| {
+                score.project_id == project_id
+                    && evaluation_ids.contains(&score.evaluation_id)
+                    && score.name == name
+                    && score.score_type != ScoreType::CATEGORICAL
+            })
+            .map(|score| score.value)
+            .fold(0.0, f64::max);
+        Ok(ComparedEvaluationScoresBounds { upper_bound })
+    }
+
+    async fn get_evaluation_score_histogram(
+        &self,
+        project_id: Uuid,
+        evaluation_ids: &Vec<Uuid>,
+        name: String,
+        max_bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreHistogramBucket>> {
+        let scores = self.evaluation_scores.lock().unwrap();
+        let scores = scores
+            .iter()
+            .filter(|score| {
+                score.project_id == project_id
+                    && evaluation_ids.contains(&score.evaluation_id)
+                    && score.name == name
+                    && score.score_type != ScoreType::CATEGORICAL
+            })
+            .collect::<Vec<_>>();
+        if scores.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut values = scores.iter().map(|score| score.value).collect::<Vec<_>>();
+        values.sort_by(|a, b| a.total_cmp(b));
+        let bounds = HistogramBounds::new(
+            values[0],
+            values[values.len() - 1],
+            percentile(&values, 0.75) - percentile(&values, 0.25),
+            values.len() as u64,
+            max_bucket_count,
+        );
+        Ok(bounds.histogram(
+            evaluation_ids,
+            scores
+                .iter()
+                .map(|score| (score.evaluation_id, bounds.bucket_index(score.value), 1)),
+        ))
+    }
+
+    async fn get_evaluation_score_percentiles(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        baseline_evaluation_id: Uuid,
+    ) -> Result<Vec<EvaluationScorePercentile>> {
+        let scores = self.evaluation_scores.lock().unwrap();
+        let mut baseline = HashMap::<&str, Vec<f64>>::new();
+        for score in scores.iter().filter(|score| {
+            score.project_id == project_id
+                && score.evaluation_id == baseline_evaluation_id
+                && score.score_type != ScoreType::CATEGORICAL
+        }) {
+            baseline.entry(&score.name).or_default().push(score.value);
+        }
+
+        let mut percentiles = scores
+            .iter()
+            .filter(|score| {
+                score.project_id == project_id
+                    && score.evaluation_id == evaluation_id
+                    && score.score_type != ScoreType::CATEGORICAL
+            })
+            .filter_map(|score| {
+                let baseline_values = baseline.get(score.name.as_str())?;
+                let lower_or_equal = baseline_values
+                    .iter()
+                    .filter(|value| **value <= score.value)
+                    .count();
+                Some(EvaluationScorePercentile {
+                    result_id: score.result_id,
+                    name: score.name.clone(),
+                    value: score.value,
+                    percentile: lower_or_equal as f64 * 100.0 / baseline_values.len() as f64,
+                })
+            })
+            .collect::<Vec<_>>();
+        percentiles.sort_by(|a, b| (&a.name, a.result_id).cmp(&(&b.name, b.result_id)));
+
+        Ok(percentiles)
+    }
+
+    async fn get_evaluation_score_diffs(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        baseline_evaluation_id: Uuid,
+    ) -> Result<Vec<EvaluationScoreDiff>> {
+        let scores = self.evaluation_scores.lock().unwrap();
+        // (result id of the first score, sum, count) by name and datapoint key
+        let scores_per_datapoint = |evaluation_id: Uuid| {
+            let mut datapoints = BTreeMap::<(&str, &str), (Uuid, f64, usize)>::new();
+            for score in scores.iter().filter(|score| {
+                score.project_id == project_id
+                    && score.evaluation_id == evaluation_id
+                    && !score.datapoint_key.is_empty()
+                    && score.score_type != ScoreType::CATEGORICAL
+            }) {
+                let entry = datapoints
+                    .entry((&score.name, &score.datapoint_key))
+                    .or_insert((score.result_id, 0.0, 0));
+                entry.1 += score.value;
+                entry.2 += 1;
+            }
+            datapoints
+        };
+        let baseline = scores_per_datapoint(baseline_evaluation_id);
+
+        Ok(scores_per_datapoint(evaluation_id)
+            .into_iter()
+            .filter_map(|((name, datapoint_key), (result_id, sum, count))| {
+                let (baseline_result_id, baseline_sum, baseline_count) =
+                    baseline.get(&(name, datapoint_key))?;
+                Some(EvaluationScoreDiff::new(
+                    name.to_string(),
+                    datapoint_key.to_string(),
+                    result_id,
+                    *baseline_result_id,
+                    sum / count as f64,
+                    baseline_sum / *baseline_count as f64,
+                ))
+            })
+            .collect())
+    }
+
+    async fn get_average_span_score(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<f64> {
+        let values = self.span_score_values(project_id, &name, start_time, end_time);
+        Ok(average(&values))
+    }
+
+    async fn get_span_score_buckets_based_on_bounds(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        lower_bound: f64,
+        upper_bound: f64,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        let values = self.span_score_values(project_id, &name, start_time, end_time);
+        Ok(score_buckets(
+            &values,
+            lower_bound,
+            upper_bound,
+            bucket_count,
+        ))
+    }
+
+    async fn get_span_scores_bounds(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<SpanScoresBounds> {
+        let upper_bound = self
+            .span_score_values(project_id, &name, start_time, end_time)
+            .into_iter()
+            .fold(0.0, f64::max);
+        Ok(SpanScoresBounds { upper_bound })
+    }
+
+    async fn get_span_score_trend(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<f64>>> {
+        let range = TimeSeriesRange::absolute(group_by_interval, start_time, end_time);
+        let scores = self.span_scores.lock().unwrap();
+        Ok(range.aggregate(
+            scores
+                .iter()
+                .filter(|score| score.project_id == project_id && score.name == name)
+                .map(|score| (score.timestamp.timestamp(), score.value)),
+            &Aggregation::Average,
+        ))
+    }
+
+    async fn get_span_score_names(&self, project_id: Uuid) -> Result<Vec<String>> {
+        let mut names = self
+            .span_scores
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|score| score.project_id == project_id)
+            .map(|score| score.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    async fn get_span_score_scatter(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<ScoreScatterPoint>> {
+        let spans = self.spans.lock().unwrap();
+        let mut scores = self
+            .span_scores
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|score| {
+                score.project_id == project_id
+                    && score.name == name
+                    && score.timestamp >= start_time
+                    && score.timestamp <= end_time
+            })
+            .filter_map(|score| {
+                let span = spans
+                    .iter()
+                    .find(|span| span.project_id == project_id && span.span_id == score.span_id)?;
+                Some((
+                    score.timestamp,
+                    ScoreScatterPoint {
+                        id: score.span_id,
+                        trace_id: score.trace_id,
+                        score: score.value,
+                        latency: (span.end_time - span.start_time) as f64 / 1e9,
+                        cost: span.total_cost,
+                    },
+                ))
+            })
+            .collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.0.cmp(&a.0));
+
+        Ok(scores
+            .into_iter()
+            .take(limit as usize)
+            .map(|(_, point)| point)
+            .collect())
+    }
+
+    async fn get_annotator_span_scores(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<AnnotatorSpanScore>> {
+        let mut latest: HashMap<(String, Uuid, Uuid), (DateTime<Utc>, f64)> = HashMap::new();
+        for score in self.span_scores.lock().unwrap().iter().filter(|score| {
+            score.project_id == project_id
+                && !score.user_id.is_nil()
+                && score.timestamp >= start_time
+                && score.timestamp <= end_time
+        }) {
+            let key = (score.name.clone(), score.span_id, score.user_id);
+            if latest
+                .get(&key)
+                .map_or(true, |(time, _)| *time <= score.timestamp)
+            {
+                latest.insert(key, (score.timestamp, score.value));
+            }
+        }
+
+        Ok(latest
+            .into_iter()
+            .take(limit as usize)
+            .map(
+                |((name, span_id, user_id), (_, value))| AnnotatorSpanScore {
+                    span_id,
+                    user_id,
+                    name,
+                    value,
+                },
+            )
+            .collect())
+    }
+
+    async fn get_traces_latency_and_cost(
+        &self,
+        project_id: Uuid,
+        trace_ids: &[Uuid],
+    ) -> Result<Vec<TraceLatencyAndCost>> {
+        let spans = self.spans.lock().unwrap();
+        Ok(trace_ids
+            .iter()
+            .filter_map(|trace_id| {
+                let trace_spans = spans
+                    .iter()
+                    .filter(|span| span.project_id == project_id && span.trace_id == *trace_id)
+                    .collect::<Vec<_>>();
+                let start_time = trace_spans.iter().map(|span| span.start_time).min()?;
+                let end_time = trace_spans.iter().map(|span| span.end_time).max()?;
+                Some(TraceLatencyAndCost {
+                    trace_id: *trace_id,
+                    latency: (end_time - start_time) as f64 / 1e9,
+                    cost: trace_spans.iter().map(|span| span.total_cost).sum(),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_shadow_diff_report(
+        &self,
+        project_id: Option<Uuid>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<ShadowDiffReport> {
+        let start_time = chrono_to_nanoseconds(start_time);
+        let end_time = chrono_to_nanoseconds(end_time);
+        let spans = self.spans.lock().unwrap();
+        let mut report = ShadowDiffReport::default();
+
+        for shadow in self.shadow_spans.lock().unwrap().iter().filter(|span| {
+            project_id.map_or(true, |project_id| span.project_id == project_id)
+                && span.start_time >= start_time
+                && span.start_time <= end_time
+        }) {
+            report.shadowed_spans += 1;
+            let Some(primary) = spans.iter().find(|span| {
+                span.project_id == shadow.project_id && span.span_id == shadow.span_id
+            }) else {
+                report.missing_in_primary += 1;
+                continue;
+            };
+            report.span_type_mismatches += (primary.span_type != shadow.span_type) as u64;
+            report.model_mismatches += (primary.model != shadow.model) as u64;
+            report.provider_mismatches += (primary.provider != shadow.provider) as u64;
+            report.token_mismatches += (primary.input_tokens != shadow.input_tokens
+                || primary.output_tokens != shadow.output_tokens
+                || primary.total_tokens != shadow.total_tokens)
+                as u64;
+            report.cost_mismatches +=
+                ((primary.total_cost - shadow.total_cost).abs() > 1e-9) as u64;
+            report.path_mismatches += (primary.path != shadow.path) as u64;
+        }
+
+        Ok(report)
+    }
+
+    async fn get_custom_metric_buckets(
+        &self,
+        project_id: Uuid,
+        expression: &MetricExpression,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>> {
+        let start_time = chrono_to_nanoseconds(start_time);
+        let end_time = chrono_to_nanoseconds(end_time);
+        let values = self
+            .spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|span| {
+                span.project_id == project_id
+                    && span.start_time >= start_time
+                    && span.start_time < end_time
+            })
+            .filter_map(|span| expression.evaluate(span))
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return Ok(Vec::new());
+        }
+        let lower_bound = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let upper_bound = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        Ok(score_buckets(
+            &values,
+            lower_bound,
+            upper_bound,
+            bucket_count,
+        ))
+    }
+
+    async fn run_query(
+        &self,
+        project_id: Uuid,
+        query: &AnalyticsQuery,
+        context: &QueryContext,
+    ) -> Result<Vec<QueryResultRow>> {
+        query.to_sql(project_id, context)?;
+        Err(UnsupportedInDeploymentMode("Aggregate queries").into())
+    }
+
+    async fn get_pipeline_latency_percentiles(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<PipelineLatencyPercentiles>> {
+        let pipeline_span_type: u8 = SpanType::PIPELINE.into();
+        let start_time = chrono_to_nanoseconds(start_time);
+        let end_time = chrono_to_nanoseconds(end_time);
+        let mut latencies = BTreeMap::<String, Vec<f64>>::new();
+        for span in self.spans.lock().unwrap().iter().filter(|span| {
+            span.project_id == project_id
+                && span.span_type == pipeline_span_type
+                && span.start_time >= start_time
+                && span.start_time < end_time
+        }) {
+            latencies
+                .entry(span.name.clone())
+                .or_default()
+                .push((span.end_time - span.start_time) as f64 / 1e9);
+        }
+
+        Ok(latencies
+            .into_iter()
+            .map(|(pipeline, mut latencies)| {
+                latencies.sort_by(|a, b| a.total_cmp(b));
+                PipelineLatencyPercentiles {
+                    run_count: latencies.len() as u64,
+                    p50: percentile(&latencies, 0.5),
+                    p95: percentile(&latencies, 0.95),
+                    p99: percentile(&latencies, 0.99),
+                    pipeline,
+                }
+            })
+            .collect())
+    }
+
+    async fn get_latency_percentiles_over_time(
+        &self,
+        project_id: Uuid,
+        level: LatencyLevel,
+        filter: &LatencySpanFilter,
+        group_by_interval: GroupByInterval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<LatencyPercentilesPoint>> {
+        let range = TimeSeriesRange::absolute(group_by_interval, start_time, end_time);
+        let start_time = chrono_to_nanoseconds(start_time);
+        let end_time = chrono_to_nanoseconds(end_time);
+        let spans = self.spans.lock().unwrap();
+        let spans = spans
+            .iter()
+            .filter(|span| {
+                span.project_id == project_id
+                    && span.start_time >= start_time
+                    && span.start_time <= end_time
+            })
+            .collect::<Vec<_>>();
+        // (start time in seconds, latency) of each span or trace
+        let latencies = match level {
+            LatencyLevel::Span => spans
+                .iter()
+                .filter(|span| matches_latency_filter(filter, span))
+                .map(|span| {
+                    (
+                        nanoseconds_to_seconds(span.start_time),
+                        (span.end_time - span.start_time) as f64 / 1e9,
+                    )
+                })
+                .collect::<Vec<_>>(),
+            LatencyLevel::Trace => {
+                let matching_traces = spans
+                    .iter()
+                    .filter(|span| matches_latency_filter(filter, span))
+                    .map(|span| span.trace_id)
+                    .collect::<HashSet<_>>();
+                Self::traces_of(spans.iter().copied())
+                    .into_iter()
+                    .filter(|(trace_id, _)| matching_traces.contains(trace_id))
+                    .map(|(_, trace)| (nanoseconds_to_seconds(trace.start_time), trace.latency()))
+                    .collect()
+            }
+        };
+
+        let mut intervals = BTreeMap::<i64, Vec<f64>>::new();
+        for (start_time, latency) in latencies {
+            intervals
+                .entry(range.interval_start(start_time))
+                .or_default()
+                .push(latency);
+        }
+        Ok(range
+            .fill(intervals)
+            .into_iter()
+            .map(|point| {
+                let mut latencies = point.value;
+                latencies.sort_by(|a, b| a.total_cmp(b));
+                LatencyPercentilesPoint {
+                    time: point.time,
+                    count: latencies.len() as u64,
+                    p50: percentile(&latencies, 0.5),
+                    p90: percentile(&latencies, 0.9),
+                    p95: percentile(&latencies, 0.95),
+                    p99: percentile(&latencies, 0.99),
+                }
+            })
+            .collect())
+    }
+
+    async fn get_hour_of_week_heatmap(
+        &self,
+        project_id: Uuid,
+        pipeline: Option<&str>,
+        metric: HeatmapMetric,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<HeatmapCell>> {
+        let pipeline_span_type: u8 = SpanType::PIPELINE.into();
+        let start_time = chrono_to_nanoseconds(start_time);
+        let end_time = chrono_to_nanoseconds(end_time);
+        let spans = self.spans.lock().unwrap();
+        let spans = spans
+            .iter()
+            .filter(|span| {
+                span.project_id == project_id
+                    && span.start_time >= start_time
+                    && span.start_time < end_time
+            })
+            .collect::<Vec<_>>();
+        let pipeline_traces = pipeline.map(|pipeline| {
+            spans
+                .iter()
+                .filter(|span| span.span_type == pipeline_span_type && span.name == pipeline)
+                .map(|span| span.trace_id)
+                .collect::<HashSet<_>>()
+        });
+
+        // (traces, failed traces, cost) by day of the week and hour
+        let mut cells = BTreeMap::<(u8, u8), (HashSet<Uuid>, HashSet<Uuid>, f64)>::new();
+        for span in spans.iter().filter(|span| {
+            pipeline_traces
+                .as_ref()
+                .map_or(true, |traces| traces.contains(&span.trace_id))
+        }) {
+            let start_time = nanoseconds_to_chrono(span.start_time);
+            let cell = cells
+                .entry((
+                    start_time.weekday().number_from_monday() as u8,
+                    start_time.hour() as u8,
+                ))
+                .or_default();
+            cell.0.insert(span.trace_id);
+            if span.is_error {
+                cell.1.insert(span.trace_id);
+            }
+            cell.2 += span.total_cost;
+        }
+
+        Ok(cells
+            .into_iter()
+            .map(
+                |((day_of_week, hour), (traces, failed_traces, cost))| HeatmapCell {
+                    day_of_week,
+                    hour,
+                    value: match metric {
+                        HeatmapMetric::Traffic => traces.len() as f64,
+                        HeatmapMetric::ErrorRate => {
+                            failed_traces.len() as f64 / traces.len() as f64
+                        }
+                        HeatmapMetric::Cost => cost,
+                    },
+                },
+            )
+            .collect())
+    }
+
+    async fn get_agent_action_stats(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<AgentActionStats>> {
+        let start_time = chrono_to_nanoseconds(start_time);
+        let end_time = chrono_to_nanoseconds(end_time);
+        // count, successes and total retries of each action
+        let mut actions = BTreeMap::<String, (u64, u64, u64)>::new();
+        for span in self.spans.lock().unwrap().iter().filter(|span| {
+            span.project_id == project_id
+                && span.agent_action != "<null>"
+                && span.start_time >= start_time
+                && span.start_time < end_time
+        }) {
+            let action = actions.entry(span.agent_action.clone()).or_default();
+            action.0 += 1;
+            action.1 += !span.is_error as u64;
+            action.2 += span.agent_action_retries as u64;
+        }
+
+        let mut stats = actions
+            .into_iter()
+            .map(
+                |(action, (count, success_count, retries))| AgentActionStats {
+                    action,
+                    count,
+                    success_count,
+                    success_rate: success_count as f64 / count as f64,
+                    average_retries: retries as f64 / count as f64,
+                },
+            )
+            .collect::<Vec<_>>();
+        // stable, so that ties stay ordered by action
+        stats.sort_by(|a, b| b.count.cmp(&a.count));
+        Ok(stats)
+    }
+
+    async fn get_agent_failure_selectors(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<AgentFailureSelector>> {
+        let start_time = chrono_to_nanoseconds(start_time);
+        let end_time = chrono_to_nanoseconds(end_time);
+        let mut failures = BTreeMap::<(String, String), u64>::new();
+        for span in self.spans.lock().unwrap().iter().filter(|span| {
+            span.project_id == project_id
+                && span.agent_action != "<null>"
+                && span.agent_action_selector != "<null>"
+                && span.is_error
+                && span.start_time >= start_time
+                && span.start_time < end_time
+        }) {
+            *failures
+                .entry((
+                    span.agent_action.clone(),
+                    span.agent_action_selector.clone(),
+                ))
+                .or_default() += 1;
+        }
+
+        let mut selectors = failures
+            .into_iter()
+            .map(|((action, selector), failure_count)| AgentFailureSelector {
+                action,
+                selector,
+                failure_count,
+            })
+            .collect::<Vec<_>>();
+        selectors.sort_by(|a, b| b.failure_count.cmp(&a.failure_count));
+        selectors.truncate(limit as usize);
+        Ok(selectors)
+    }
+
+    async fn search_spans(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<SpanSearchResult>> {
+        let results = self.span_search_results(project_id, query, field, start_time, end_time);
+        Ok(results
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn count_span_search_results(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<u64> {
+        let results = self.span_search_results(project_id, query, field, start_time, end_time);
+        Ok(results.len() as u64)
+    }
+
+    async fn query_spans(
+        &self,
+        _project_id: Uuid,
+        _filter: &CompiledFilter,
+        _start_time: DateTime<Utc>,
+        _end_time: DateTime<Utc>,
+        _limit: u64,
+        _offset: u64,
+    ) -> Result<Vec<FilteredSpan>> {
+        Err(UnsupportedInDeploymentMode("Filter expression queries").into())
+    }
+
+    async fn query_traces(
+        &self,
+        _project_id: Uuid,
+        _filter: &CompiledFilter,
+        _start_time: DateTime<Utc>,
+        _end_time: DateTime<Utc>,
+        _limit: u64,
+        _offset: u64,
+    ) -> Result<Vec<FilteredTrace>> {
+        Err(UnsupportedInDeploymentMode("Filter expression queries").into())
+    }
+
+    async fn query_evaluation_scores(
+        &self,
+        _project_id: Uuid,
+        _filter: &CompiledFilter,
+        _start_time: DateTime<Utc>,
+        _end_time: DateTime<Utc>,
+        _limit: u64,
+        _offset: u64,
+    ) -> Result<Vec<FilteredEvaluationScore>> {
+        Err(UnsupportedInDeploymentMode("Filter expression queries").into())
+    }
+
+    async fn update_promoted_attribute_values(
+        &self,
+        project_id: Uuid,
+        slot: usize,
+        values: Vec<(Uuid, String)>,
+    ) -> Result<()> {
+        let values = values.into_iter().collect::<HashMap<_, _>>();
+        for span in self.spans.lock().unwrap().iter_mut() {
+            if span.project_id != project_id {
+                continue;
+            }
+            if let Some(value) = values.get(&span.span_id) {
+                span.set_promoted_attribute(slot, value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    async fn clear_promoted_attribute(&self, project_id: Uuid, slot: usize) -> Result<()> {
+        for span in self.spans.lock().unwrap().iter_mut() {
+            if span.project_id == project_id {
+                span.set_promoted_attribute(slot, String::from("<null>"));
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_trace_browser_events(
+        &self,
+        project_id: Uuid,
+        trace_id: Uuid,
+    ) -> Result<Vec<BrowserEvent>> {
+        let mut events = self
+            .browser_events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.project_id == project_id && event.trace_id == trace_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        events.sort_by_key(|event| event.timestamp);
+        Ok(events)
+    }
+
+    async fn get_nearest_browser_snapshot(
+        &self,
+        project_id: Uuid,
+        session_id: String,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<BrowserSnapshot>> {
+        Ok(self
+            .browser_snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|snapshot| {
+                snapshot.project_id == project_id && snapshot.session_id == session_id
+            })
+            .min_by_key(|snapshot| ((snapshot.timestamp - timestamp).abs(), snapshot.step))
+            .cloned())
+    }
+
+    async fn get_browser_snapshots_at_step(
+        &self,
+        project_id: Uuid,
+        session_id: String,
+        step: u32,
+    ) -> Result<Vec<BrowserSnapshot>> {
+        // last snapshot of each step up to the step
+        let mut last_snapshots = BTreeMap::<u32, BrowserSnapshot>::new();
+        for snapshot in self
+            .browser_snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|snapshot| {
+                snapshot.project_id == project_id
+                    && snapshot.session_id == session_id
+                    && snapshot.step <= step
+            })
+        {
+            let is_later = last_snapshots
+                .get(&snapshot.step)
+                .map_or(true, |last| last.timestamp <= snapshot.timestamp);
+            if is_later {
+                last_snapshots.insert(snapshot.step, snapshot.clone());
+            }
+        }
+        if !last_snapshots.contains_key(&step) {
+            return Ok(Vec::new());
+        }
+
+        Ok(last_snapshots.into_values().rev().take(2).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        ch::evaluation_scores::{summarize_score_diffs, ScoreSource},
+        db::spans::Span,
+        evaluations::utils::ScoreValue,
+    };
+
+    use super::*;
+
+    fn score(project_id: Uuid, evaluation_id: Uuid, value: f64) -> EvaluationScore {
+        EvaluationScore {
+            project_id,
+            group_id: "default".to_string(),
+            evaluation_id,
+            result_id: Uuid::new_v4(),
+            name: "accuracy".to_string(),
+            value,
+            timestamp: Utc::now(),
+            datapoint_key: String::new(),
+            score_type: ScoreType::NUMERIC,
+            label: String::new(),
+            source: ScoreSource::AUTO,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_score_stats() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let evaluation_id = Uuid::new_v4();
+        store
+            .insert_evaluation_scores(vec![
+                score(project_id, evaluation_id, 0.0),
+                score(project_id, evaluation_id, 0.5),
+                score(project_id, evaluation_id, 1.0),
+                score(project_id, Uuid::new_v4(), 5.0),
+            ])
+            .await
+            .unwrap();
+
+        let average = store
+            .get_average_evaluation_score(project_id, evaluation_id, "accuracy".to_string())
+            .await
+            .unwrap();
+        assert_eq!(average, 0.5);
+
+        let stats = store
+            .get_evaluation_score_stats(project_id, evaluation_id, "accuracy".to_string())
+            .await
+            .unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(
+            (stats.min_value, stats.median, stats.max_value),
+            (0.0, 0.5, 1.0)
+        );
+        assert_eq!(stats.p99, 1.0);
+        assert!((stats.stddev - (1.0f64 / 6.0).sqrt()).abs() < 1e-9);
+
+        let bounds = store
+            .get_global_evaluation_scores_bounds(
+                project_id,
+                &vec![evaluation_id],
+                "accuracy".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(bounds.upper_bound, 1.0);
+
+        let buckets = store
+            .get_evaluation_score_buckets_based_on_bounds(
+                project_id,
+                evaluation_id,
+                "accuracy".to_string(),
+                0.0,
+                1.0,
+                2,
+            )
+            .await
+            .unwrap();
+        let heights = buckets.iter().map(|b| b.height).collect::<Vec<_>>();
+        assert_eq!(heights, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_score_histogram() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let evaluation_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+        let mut scores = (0..10)
+            .map(|value| score(project_id, evaluation_ids[0], value as f64))
+            .collect::<Vec<_>>();
+        scores.push(score(project_id, evaluation_ids[1], 9.0));
+        store.insert_evaluation_scores(scores).await.unwrap();
+
+        let buckets = store
+            .get_evaluation_score_histogram(project_id, &evaluation_ids, "accuracy".to_string(), 50)
+            .await
+            .unwrap();
+        let heights = buckets
+            .iter()
+            .map(|b| b.heights.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(heights, vec![vec![5, 0], vec![5, 1]]);
+        assert_eq!((buckets[0].lower_bound, buckets[1].upper_bound), (0.0, 9.0));
+
+        // binary scores have no IQR
+        assert_eq!(HistogramBounds::new(0.0, 1.0, 0.0, 100, 50).bucket_count, 8);
+        assert_eq!(HistogramBounds::new(0.0, 1.0, 0.0, 100, 5).bucket_count, 5);
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_score_percentiles() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let baseline_id = Uuid::new_v4();
+        let evaluation_id = Uuid::new_v4();
+        store
+            .insert_evaluation_scores(vec![
+                score(project_id, baseline_id, 0.2),
+                score(project_id, baseline_id, 0.4),
+                score(project_id, baseline_id, 0.6),
+                score(project_id, baseline_id, 0.8),
+                score(project_id, evaluation_id, 0.5),
+            ])
+            .await
+            .unwrap();
+
+        let percentiles = store
+            .get_evaluation_score_percentiles(project_id, evaluation_id, baseline_id)
+            .await
+            .unwrap();
+        assert_eq!(percentiles.len(), 1);
+        assert_eq!(percentiles[0].percentile, 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_score_diffs() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let baseline_id = Uuid::new_v4();
+        let evaluation_id = Uuid::new_v4();
+        let datapoint_score = |evaluation_id: Uuid, key: &str, value: f64| EvaluationScore {
+            datapoint_key: key.to_string(),
+            ..score(project_id, evaluation_id, value)
+        };
+        store
+            .insert_evaluation_scores(vec![
+                datapoint_score(baseline_id, "a", 0.5),
+                datapoint_score(baseline_id, "b", 1.0),
+                datapoint_score(baseline_id, "c", 0.5),
+                datapoint_score(evaluation_id, "a", 1.0),
+                datapoint_score(evaluation_id, "b", 0.0),
+                datapoint_score(evaluation_id, "c", 0.5),
+                // not in the baseline
+                datapoint_score(evaluation_id, "d", 1.0),
+            ])
+            .await
+            .unwrap();
+
+        let diffs = store
+            .get_evaluation_score_diffs(project_id, evaluation_id, baseline_id)
+            .await
+            .unwrap();
+        let deltas = diffs.iter().map(|diff| diff.delta).collect::<Vec<_>>();
+        assert_eq!(deltas, vec![0.5, -1.0, 0.0]);
+
+        let summaries = summarize_score_diffs(&diffs);
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(
+            (
+                summaries[0].improved_count,
+                summaries[0].regressed_count,
+                summaries[0].unchanged_count
+            ),
+            (1, 1, 1)
+        );
+        assert!((summaries[0].average_delta - (-0.5 / 3.0)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_score_trend() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let (first_id, second_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let started_at = Utc::now();
+        let timed_score = |evaluation_id: Uuid, value: f64, seconds: i64| EvaluationScore {
+            timestamp: started_at + chrono::Duration::seconds(seconds),
+            ..score(project_id, evaluation_id, value)
+        };
+        store
+            .insert_evaluation_scores(vec![
+                timed_score(second_id, 0.2, 60),
+                timed_score(first_id, 0.4, 1),
+                timed_score(first_id, 0.8, 0),
+                // other group
+                EvaluationScore {
+                    group_id: "other".to_string(),
+                    ..timed_score(Uuid::new_v4(), 1.0, 30)
+                },
+            ])
+            .await
+            .unwrap();
+
+        let trend = store
+            .get_evaluation_score_trend(project_id, "default".to_string(), "accuracy".to_string())
+            .await
+            .unwrap();
+        let evaluation_ids = trend
+            .iter()
+            .map(|point| point.evaluation_id)
+            .collect::<Vec<_>>();
+        assert_eq!(evaluation_ids, vec![first_id, second_id]);
+        assert_eq!(trend[0].timestamp, started_at);
+        assert!((trend[0].average_value - 0.6).abs() < 1e-9);
+        assert_eq!((trend[0].p10, trend[0].p90), (0.4, 0.8));
+        assert_eq!((trend[1].count, trend[1].median), (1, 0.2));
+    }
+
+    #[tokio::test]
+    async fn test_boolean_and_categorical_scores() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let evaluation_id = Uuid::new_v4();
+        let typed_score = |name: &str, value: ScoreValue| EvaluationScore {
+            name: name.to_string(),
+            value: value.as_f64().unwrap_or_default(),
+            score_type: value.score_type().into(),
+            label: value.label().unwrap_or_default().to_string(),
+            ..score(project_id, evaluation_id, 0.0)
+        };
+        let label = |label: &str| ScoreValue::Label(label.to_string());
+        store
+            .insert_evaluation_scores(vec![
+                typed_score("correct", ScoreValue::Boolean(true)),
+                typed_score("correct", ScoreValue::Boolean(true)),
+                typed_score("correct", ScoreValue::Boolean(false)),
+                typed_score("correct", ScoreValue::Boolean(true)),
+                typed_score("tone", label("neutral")),
+                typed_score("tone", label("rude")),
+                typed_score("tone", label("neutral")),
+            ])
+            .await
+            .unwrap();
+
+        let pass_rate = store
+            .get_evaluation_score_pass_rate(project_id, evaluation_id, "correct".to_string())
+            .await
+            .unwrap();
+        assert_eq!((pass_rate.count, pass_rate.passed_count), (4, 3));
+        assert_eq!(pass_rate.pass_rate, 0.75);
+
+        let frequencies = store
+            .get_evaluation_score_label_frequencies(project_id, evaluation_id, "tone".to_string())
+            .await
+            .unwrap();
+        let frequencies = frequencies
+            .iter()
+            .map(|frequency| (frequency.label.as_str(), frequency.count))
+            .collect::<Vec<_>>();
+        assert_eq!(frequencies, vec![("neutral", 2), ("rude", 1)]);
+
+        // Labels have no numeric stats
+        let stats = store
+            .get_evaluation_score_stats(project_id, evaluation_id, "tone".to_string())
+            .await
+            .unwrap();
+        assert_eq!(stats.count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_span_score_stats() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let now = Utc::now();
+        let span_score = |value: f64, timestamp: DateTime<Utc>| SpanScore {
+            project_id,
+            timestamp,
+            span_id: Uuid::new_v4(),
+            trace_id: Uuid::new_v4(),
+            name: "helpfulness".to_string(),
+            value,
+            user_id: Uuid::nil(),
+        };
+        store
+            .insert_span_scores(vec![
+                span_score(0.0, now),
+                span_score(1.0, now),
+                span_score(5.0, now - chrono::Duration::days(2)),
+            ])
+            .await
+            .unwrap();
+        let start_time = now - chrono::Duration::days(1);
+
+        let average = store
+            .get_average_span_score(project_id, "helpfulness".to_string(), start_time, now)
+            .await
+            .unwrap();
+        assert_eq!(average, 0.5);
+
+        let bounds = store
+            .get_span_scores_bounds(project_id, "helpfulness".to_string(), start_time, now)
+            .await
+            .unwrap();
+        assert_eq!(bounds.upper_bound, 1.0);
+
+        let buckets = store
+            .get_span_score_buckets_based_on_bounds(
+                project_id,
+                "helpfulness".to_string(),
+                start_time,
+                now,
+                0.0,
+                1.0,
+                2,
+            )
+            .await
+            .unwrap();
+        let heights = buckets.iter().map(|b| b.height).collect::<Vec<_>>();
+        assert_eq!(heights, vec![1, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_time_series_metrics() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let start_time = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let span = |trace_id: Uuid, minutes: i64, seconds: i64, cost: f64| {
+            let mut span = CHSpan::from_db_span(&Span::default(), Default::default(), project_id);
+            span.trace_id = trace_id;
+            span.start_time =
+                chrono_to_nanoseconds(start_time + chrono::Duration::minutes(minutes));
+            span.end_time = span.start_time + seconds * 1_000_000_000;
+            span.total_cost = cost;
+            span
+        };
+        let trace_ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        for span in [
+            span(trace_ids[0], 5, 2, 1.0),
+            span(trace_ids[0], 6, 1, 0.5),
+            span(trace_ids[1], 10, 4, 0.5),
+            span(trace_ids[2], 130, 1, 2.0),
+        ] {
+            store.insert_span(&span).await.unwrap();
+        }
+        let end_time = start_time + chrono::Duration::minutes(179);
+        let hour = |hours: u32| start_time.timestamp() as u32 + hours * 60 * 60;
+
+        let counts = store
+            .get_total_trace_count_metrics_absolute(
+                GroupByInterval::Hour,
+                project_id,
+                start_time,
+                end_time,
+            )
+            .await
+            .unwrap();
+        let counts = counts
+            .iter()
+            .map(|point| (point.time, point.value))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![(hour(0), 2), (hour(1), 0), (hour(2), 1)]);
+
+        let costs = store
+            .get_cost_usd_metrics_absolute(
+                GroupByInterval::Hour,
+                project_id,
+                start_time,
+                end_time,
+                Aggregation::Total,
+            )
+            .await
+            .unwrap();
+        let costs = costs.iter().map(|point| point.value).collect::<Vec<_>>();
+        assert_eq!(costs, vec![2.0, 0.0, 2.0]);
+
+        let latencies = store
+            .get_trace_latency_seconds_metrics_absolute(
+                GroupByInterval::Hour,
+                project_id,
+                start_time,
+                end_time,
+                Aggregation::Average,
+            )
+            .await
+            .unwrap();
+        let latencies = latencies
+            .iter()
+            .map(|point| point.value)
+            .collect::<Vec<_>>();
+        assert_eq!(latencies, vec![32.5, 0.0, 1.0]);
+
+        let percentiles = store
+            .get_latency_percentiles_over_time(
+                project_id,
+                LatencyLevel::Span,
+                &LatencySpanFilter::default(),
+                GroupByInterval::Hour,
+                start_time,
+                end_time,
+            )
+            .await
+            .unwrap();
+        let percentiles = percentiles
+            .iter()
+            .map(|point| (point.count, point.p50, point.p99))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            percentiles,
+            vec![(3, 2.0, 4.0), (0, 0.0, 0.0), (1, 1.0, 1.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_action_stats() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let span = |attributes| {
+            let span = Span {
+                attributes,
+                start_time: Utc::now(),
+                ..Default::default()
+            };
+            CHSpan::from_db_span(&span, Default::default(), project_id)
+        };
+        let step = |action: &str, selector: &str, retries: u32, is_error: bool| {
+            span(json!({
+                "lmnr.agent.action.type": action,
+                "lmnr.agent.action.selector": selector,
+                "lmnr.agent.action.retries": retries,
+                "lmnr.span.status": if is_error { "error" } else { "ok" },
+            }))
+        };
+        for span in [
+            step("click", "#submit", 0, false),
+            step("Click", "#submit", 2, true),
+            step("click", "#cart", 1, true),
+            step("click", "#submit", 1, true),
+            step("navigate", "", 0, false),
+            span(json!({ "gen_ai.operation.name": "execute_tool" })),
+            // not an agent step
+            span(json!({ "lmnr.span.status": "error" })),
+        ] {
+            store.insert_span(&span).await.unwrap();
+        }
+
+        let start_time = Utc::now() - chrono::Duration::hours(1);
+        let end_time = Utc::now() + chrono::Duration::hours(1);
+        let stats = store
+            .get_agent_action_stats(project_id, start_time, end_time)
+            .await
+            .unwrap();
+        let actions = stats
+            .iter()
+            .map(|stats| (stats.action.as_str(), stats.count, stats.success_count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![("click", 4, 1), ("navigate", 1, 1), ("tool_call", 1, 1)]
+        );
+        assert_eq!(stats[0].success_rate, 0.25);
+        assert_eq!(stats[0].average_retries, 1.0);
+
+        let selectors = store
+            .get_agent_failure_selectors(project_id, start_time, end_time, 10)
+            .await
+            .unwrap();
+        let selectors = selectors
+            .iter()
+            .map(|selector| (selector.selector.as_str(), selector.failure_count))
+            .collect::<Vec<_>>();
+        assert_eq!(selectors, vec![("#submit", 2), ("#cart", 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_search_spans() {
+        let store = InMemoryAnalyticsStore::default();
+        let project_id = Uuid::new_v4();
+        let entry = |minutes_ago: i64, input: &str, output: &str| CHSpanSearchEntry {
+            project_id,
+            start_time: chrono_to_nanoseconds(Utc::now() - chrono::Duration::minutes(minutes_ago)),
+            span_id: Uuid::new_v4(),
+            trace_id: Uuid::new_v4(),
+            name: "chat".to_string(),
+            path: "<null>".to_string(),
+            input: input.to_string(),
+            output: output.to_string(),
+        };
+        for entry in [
+            entry(3, "Where is my REFUND?", "It was sent yesterday"),
+            entry(2, "Cancel my order", "The refund takes a week"),
+            entry(1, "Hello", "Hi"),
+        ] {
+            store.insert_span_search_entry(&entry).await.unwrap();
+        }
+
+        let start_time = Utc::now() - chrono::Duration::hours(1);
+        let end_time = Utc::now();
+        let results = store
+            .search_spans(
+                project_id,
+                "refund",
+                SpanSearchField::All,
+                start_time,
+                end_time,
+                10,
+                0,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        // the newest first
+        assert_eq!(results[0].input_snippet, None);
+        assert_eq!(
+            results[0].output_snippet.as_deref(),
+            Some("The refund takes a week")
+        );
+        assert_eq!(
+            results[1].input_snippet.as_deref(),
+            Some("Where is my REFUND?")
+        );
+
+        let count = store
+            .count_span_search_results(
+                project_id,
+                "refund",
+                SpanSearchField::Input,
+                start_time,
+                end_time,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        let page = store
+            .search_spans(
+                project_id,
+                "refund",
+                SpanSearchField::All,
+                start_time,
+                end_time,
+                1,
+                1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(page[0].span_id, results[1].span_id);
+    }
+}
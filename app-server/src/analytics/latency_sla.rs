@@ -0,0 +1,207 @@
+//! Latency SLA tracking: compares the latency percentiles of each pipeline's recent runs with a
+//! baseline computed over the previous weeks.
+//!
+//! Baseline weeks are rolling, i.e. the `BASELINE_WEEKS` seven-day windows right before the
+//! current window. The baseline of a percentile is the median of its weekly values, so that a
+//! single unusual week doesn't shift it.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::try_join_all;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::ch::spans::PipelineLatencyPercentiles;
+
+use super::AnalyticsStore;
+
+pub const BASELINE_WEEKS: i64 = 4;
+
+/// Latencies in seconds
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct LatencyPercentiles {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+impl From<&PipelineLatencyPercentiles> for LatencyPercentiles {
+    fn from(percentiles: &PipelineLatencyPercentiles) -> Self {
+        Self {
+            p50: percentiles.p50,
+            p95: percentiles.p95,
+            p99: percentiles.p99,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyLatency {
+    pub week_start: DateTime<Utc>,
+    pub run_count: u64,
+    #[serde(flatten)]
+    pub percentiles: LatencyPercentiles,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineLatencyDelta {
+    pub pipeline: String,
+    pub run_count: u64,
+    pub current: LatencyPercentiles,
+    /// None if the pipeline has no runs in the baseline weeks
+    pub baseline: Option<LatencyPercentiles>,
+    /// (current - baseline) / baseline for each percentile, positive values are slower
+    pub p50_change: Option<f64>,
+    pub p95_change: Option<f64>,
+    pub p99_change: Option<f64>,
+    /// Baseline weeks with runs of the pipeline, oldest first
+    pub weeks: Vec<WeeklyLatency>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineLatencyReport {
+    pub current_start: DateTime<Utc>,
+    pub current_end: DateTime<Utc>,
+    pub baseline_start: DateTime<Utc>,
+    pub pipelines: Vec<PipelineLatencyDelta>,
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+fn relative_change(current: f64, baseline: f64) -> Option<f64> {
+    (baseline != 0.0).then(|| (current - baseline) / baseline)
+}
+
+/// `weeks` are the start of each baseline week with the percentiles of its runs, oldest first
+fn latency_deltas(
+    current: Vec<PipelineLatencyPercentiles>,
+    weeks: Vec<(DateTime<Utc>, Vec<PipelineLatencyPercentiles>)>,
+) -> Vec<PipelineLatencyDelta> {
+    let mut weekly_by_pipeline = HashMap::<String, Vec<WeeklyLatency>>::new();
+    for (week_start, percentiles) in weeks {
+        for percentiles in percentiles {
+            weekly_by_pipeline
+                .entry(percentiles.pipeline.clone())
+                .or_default()
+                .push(WeeklyLatency {
+                    week_start,
+                    run_count: percentiles.run_count,
+                    percentiles: (&percentiles).into(),
+                });
+        }
+    }
+
+    current
+        .into_iter()
+        .map(|percentiles| {
+            let weeks = weekly_by_pipeline
+                .remove(&percentiles.pipeline)
+                .unwrap_or_default();
+            let baseline = (!weeks.is_empty()).then(|| LatencyPercentiles {
+                p50: median(weeks.iter().map(|week| week.percentiles.p50).collect()),
+                p95: median(weeks.iter().map(|week| week.percentiles.p95).collect()),
+                p99: median(weeks.iter().map(|week| week.percentiles.p99).collect()),
+            });
+            let current = LatencyPercentiles::from(&percentiles);
+            PipelineLatencyDelta {
+                run_count: percentiles.run_count,
+                p50_change: baseline.and_then(|b| relative_change(current.p50, b.p50)),
+                p95_change: baseline.and_then(|b| relative_change(current.p95, b.p95)),
+                p99_change: baseline.and_then(|b| relative_change(current.p99, b.p99)),
+                pipeline: percentiles.pipeline,
+                current,
+                baseline,
+                weeks,
+            }
+        })
+        .collect()
+}
+
+/// Latency percentiles of the pipelines that ran between `current_start` and `current_end`,
+/// compared with their weekly baselines
+pub async fn get_pipeline_latency_report(
+    analytics_store: Arc<dyn AnalyticsStore>,
+    project_id: Uuid,
+    current_start: DateTime<Utc>,
+    current_end: DateTime<Utc>,
+) -> Result<PipelineLatencyReport> {
+    let week_starts = (1..=BASELINE_WEEKS)
+        .rev()
+        .map(|week| current_start - Duration::weeks(week))
+        .collect::<Vec<_>>();
+
+    let current = analytics_store
+        .get_pipeline_latency_percentiles(project_id, current_start, current_end)
+        .await?;
+    let weekly = try_join_all(week_starts.iter().map(|week_start| {
+        analytics_store.get_pipeline_latency_percentiles(
+            project_id,
+            *week_start,
+            *week_start + Duration::weeks(1),
+        )
+    }))
+    .await?;
+
+    Ok(PipelineLatencyReport {
+        current_start,
+        current_end,
+        baseline_start: week_starts[0],
+        pipelines: latency_deltas(current, week_starts.into_iter().zip(weekly).collect()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percentiles(pipeline: &str, p50: f64, p95: f64) -> PipelineLatencyPercentiles {
+        PipelineLatencyPercentiles {
+            pipeline: pipeline.to_string(),
+            run_count: 100,
+            p50,
+            p95,
+            p99: p95 * 2.0,
+        }
+    }
+
+    #[test]
+    fn test_latency_deltas() {
+        let week = |days: i64| Utc::now() - Duration::days(days);
+        let deltas = latency_deltas(
+            vec![
+                percentiles("chat", 1.2, 4.0),
+                percentiles("summary", 2.0, 3.0),
+            ],
+            vec![
+                (week(28), vec![percentiles("chat", 1.0, 2.0)]),
+                (week(21), vec![percentiles("chat", 1.0, 2.0)]),
+                (week(14), vec![percentiles("chat", 3.0, 9.0)]),
+            ],
+        );
+
+        assert_eq!(deltas.len(), 2);
+        let chat = &deltas[0];
+        assert_eq!(chat.weeks.len(), 3);
+        // the slow week doesn't shift the baseline
+        assert_eq!(chat.baseline.unwrap().p50, 1.0);
+        assert!((chat.p50_change.unwrap() - 0.2).abs() < 1e-9);
+        assert!((chat.p95_change.unwrap() - 1.0).abs() < 1e-9);
+
+        let summary = &deltas[1];
+        assert!(summary.baseline.is_none());
+        assert!(summary.p95_change.is_none());
+    }
+}
@@ -0,0 +1,499 @@
+//! Storage for analytical data: spans, events, evaluation and span scores, and the aggregations
+//! over them. ClickHouse is the primary implementation, the in-memory one is used as a test
+//! double and, persisted in SQLite, by the embedded mode.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::ch::{
+    browser_events::BrowserEvent,
+    browser_snapshots::BrowserSnapshot,
+    evaluation_scores::{
+        ComparedEvaluationScoresBounds, EvaluationScore, EvaluationScoreBucket,
+        EvaluationScoreDiff, EvaluationScoreHistogramBucket, EvaluationScoreLabelFrequency,
+        EvaluationScorePassRate, EvaluationScorePercentile, EvaluationScoreStats,
+        EvaluationScoreTrendPoint, FilteredEvaluationScore,
+    },
+    events::CHEvent,
+    filter_expression::CompiledFilter,
+    modifiers::GroupByInterval,
+    query::{AnalyticsQuery, QueryContext, QueryResultRow},
+    span_scores::{AnnotatorSpanScore, ScoreScatterPoint, SpanScore, SpanScoresBounds},
+    span_search::{CHSpanSearchEntry, SpanSearchField, SpanSearchResult},
+    spans::{
+        AgentActionStats, AgentFailureSelector, CHSpan, FilteredSpan, FilteredTrace, HeatmapCell,
+        HeatmapMetric, LatencyLevel, LatencyPercentilesPoint, LatencySpanFilter,
+        PipelineLatencyPercentiles, ShadowDiffReport, TraceLatencyAndCost,
+    },
+    table_stats::TableStats,
+    Aggregation, MetricTimeValue,
+};
+
+use self::custom_metrics::MetricExpression;
+
+pub mod canary;
+pub mod clickhouse;
+pub mod custom_metrics;
+pub mod definitions;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+pub mod heatmap;
+pub mod in_memory;
+pub mod latency_sla;
+pub mod natural_language;
+
+/// The query is compiled to ClickHouse SQL, so stores without ClickHouse, i.e. the lite and
+/// embedded modes, can't run it
+#[derive(thiserror::Error, Debug)]
+#[error("{0} are only supported by ClickHouse")]
+pub struct UnsupportedInDeploymentMode(pub &'static str);
+
+#[async_trait]
+pub trait AnalyticsStore: Sync + Send {
+    async fn insert_span(&self, span: &CHSpan) -> Result<()>;
+
+    /// Insert a span processed by the candidate ingestion logic, see `traces::shadow`
+    async fn insert_shadow_span(&self, span: &CHSpan) -> Result<()>;
+
+    /// Searchable text of a span's input and output
+    async fn insert_span_search_entry(&self, entry: &CHSpanSearchEntry) -> Result<()>;
+
+    async fn insert_events(&self, events: Vec<CHEvent>) -> Result<()>;
+
+    /// Scores may be buffered, so they can be queried only after a short delay
+    async fn insert_evaluation_scores(&self, evaluation_scores: Vec<EvaluationScore>)
+        -> Result<()>;
+
+    /// Scores of online evaluators and manual feedback
+    async fn insert_span_scores(&self, span_scores: Vec<SpanScore>) -> Result<()>;
+
+    /// Events recorded in the browsers of browser agents
+    async fn insert_browser_events(&self, events: Vec<BrowserEvent>) -> Result<()>;
+
+    /// Screenshots and DOM snapshots taken by browser agents after their steps
+    async fn insert_browser_snapshots(&self, snapshots: Vec<BrowserSnapshot>) -> Result<()>;
+
+    /// Earliest and latest value of a time column of a project's rows
+    async fn get_bounds(
+        &self,
+        project_id: &Uuid,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>)>;
+
+    /// Row count and time range of the project's rows in each table, for backup manifests
+    async fn get_project_table_stats(&self, project_id: Uuid) -> Result<Vec<TableStats>>;
+
+    async fn get_total_trace_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+    ) -> Result<Vec<MetricTimeValue<i64>>>;
+
+    async fn get_total_trace_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<i64>>>;
+
+    async fn get_trace_latency_seconds_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>>;
+
+    async fn get_trace_latency_seconds_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>>;
+
+    async fn get_total_token_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<i64>>>;
+
+    async fn get_total_token_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<i64>>>;
+
+    async fn get_cost_usd_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        past_hours: i64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>>;
+
+    async fn get_cost_usd_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Vec<MetricTimeValue<f64>>>;
+
+    async fn get_total_event_count_metrics_relative(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        template_id: Uuid,
+        past_hours: i64,
+    ) -> Result<Vec<MetricTimeValue<i64>>>;
+
+    async fn get_total_event_count_metrics_absolute(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        template_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<i64>>>;
+
+    /// Average of a numeric score, the pass rate of a boolean one
+    async fn get_average_evaluation_score(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<f64>;
+
+    /// Count, mean, extremes, percentiles and standard deviation of the score
+    async fn get_evaluation_score_stats(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<EvaluationScoreStats>;
+
+    /// Pass rate of a boolean score
+    async fn get_evaluation_score_pass_rate(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<EvaluationScorePassRate>;
+
+    /// Frequency of each label of a categorical score, the most frequent first
+    async fn get_evaluation_score_label_frequencies(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+    ) -> Result<Vec<EvaluationScoreLabelFrequency>>;
+
+    /// Average and percentile band of the score in each evaluation of the group, oldest first
+    async fn get_evaluation_score_trend(
+        &self,
+        project_id: Uuid,
+        group_id: String,
+        name: String,
+    ) -> Result<Vec<EvaluationScoreTrendPoint>>;
+
+    /// Distinct names of the project's numeric and boolean evaluation scores
+    async fn get_numeric_evaluation_score_names(&self, project_id: Uuid) -> Result<Vec<String>>;
+
+    /// Average evaluation score per time interval across the project's evaluations
+    async fn get_evaluation_score_time_series(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<f64>>>;
+
+    async fn get_evaluation_score_buckets_based_on_bounds(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        name: String,
+        lower_bound: f64,
+        upper_bound: f64,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>>;
+
+    async fn get_global_evaluation_scores_bounds(
+        &self,
+        project_id: Uuid,
+        evaluation_ids: &Vec<Uuid>,
+        name: String,
+    ) -> Result<ComparedEvaluationScoresBounds>;
+
+    /// Histogram of the evaluations' scores with buckets fitted to the data, shared by all
+    /// evaluations
+    async fn get_evaluation_score_histogram(
+        &self,
+        project_id: Uuid,
+        evaluation_ids: &Vec<Uuid>,
+        name: String,
+        max_bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreHistogramBucket>>;
+
+    /// Scores of the evaluation as percentiles of the baseline evaluation's score distributions
+    async fn get_evaluation_score_percentiles(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        baseline_evaluation_id: Uuid,
+    ) -> Result<Vec<EvaluationScorePercentile>>;
+
+    /// Per-datapoint score deltas between the evaluation and the baseline evaluation, for the
+    /// datapoints scored in both
+    async fn get_evaluation_score_diffs(
+        &self,
+        project_id: Uuid,
+        evaluation_id: Uuid,
+        baseline_evaluation_id: Uuid,
+    ) -> Result<Vec<EvaluationScoreDiff>>;
+
+    async fn get_average_span_score(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<f64>;
+
+    async fn get_span_score_buckets_based_on_bounds(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        lower_bound: f64,
+        upper_bound: f64,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>>;
+
+    async fn get_span_scores_bounds(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<SpanScoresBounds>;
+
+    /// Average span score per time interval
+    async fn get_span_score_trend(
+        &self,
+        group_by_interval: GroupByInterval,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MetricTimeValue<f64>>>;
+
+    /// Distinct names of the project's span scores
+    async fn get_span_score_names(&self, project_id: Uuid) -> Result<Vec<String>>;
+
+    /// Most recent span scores in the period with the latency and cost of the scored spans, for
+    /// score vs. latency and cost scatter plots
+    async fn get_span_score_scatter(
+        &self,
+        project_id: Uuid,
+        name: String,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<ScoreScatterPoint>>;
+
+    /// Latest manual score of each annotator for each scored span in the period, for agreement
+    /// between annotators
+    async fn get_annotator_span_scores(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<AnnotatorSpanScore>>;
+
+    /// Latency and total cost of each trace
+    async fn get_traces_latency_and_cost(
+        &self,
+        project_id: Uuid,
+        trace_ids: &[Uuid],
+    ) -> Result<Vec<TraceLatencyAndCost>>;
+
+    /// Compare spans written by the shadow pipeline with the ones written by the primary pipeline
+    async fn get_shadow_diff_report(
+        &self,
+        project_id: Option<Uuid>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<ShadowDiffReport>;
+
+    /// Distribution of a custom metric over the spans that started in the time window, in
+    /// buckets between the metric's minimum and maximum
+    async fn get_custom_metric_buckets(
+        &self,
+        project_id: Uuid,
+        expression: &MetricExpression,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        bucket_count: u64,
+    ) -> Result<Vec<EvaluationScoreBucket>>;
+
+    /// Runs an aggregate query, see `ch::query`
+    async fn run_query(
+        &self,
+        project_id: Uuid,
+        query: &AnalyticsQuery,
+        context: &QueryContext,
+    ) -> Result<Vec<QueryResultRow>>;
+
+    /// Latency percentiles of the pipeline runs that started in the time window
+    async fn get_pipeline_latency_percentiles(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<PipelineLatencyPercentiles>>;
+
+    /// Span or trace latency percentiles of each time bucket of the time window
+    async fn get_latency_percentiles_over_time(
+        &self,
+        project_id: Uuid,
+        level: LatencyLevel,
+        filter: &LatencySpanFilter,
+        group_by_interval: GroupByInterval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<LatencyPercentilesPoint>>;
+
+    /// Metric of the spans grouped by day of the week and hour of the day
+    async fn get_hour_of_week_heatmap(
+        &self,
+        project_id: Uuid,
+        pipeline: Option<&str>,
+        metric: HeatmapMetric,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<HeatmapCell>>;
+
+    /// Success rate and retries of each action type of agent steps in the time window
+    async fn get_agent_action_stats(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<AgentActionStats>>;
+
+    /// Selectors with the most failed agent steps in the time window
+    async fn get_agent_failure_selectors(
+        &self,
+        project_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<AgentFailureSelector>>;
+
+    /// Spans of the time window whose input or output contains the query, ignoring case, the
+    /// newest first
+    async fn search_spans(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<SpanSearchResult>>;
+
+    async fn count_span_search_results(
+        &self,
+        project_id: Uuid,
+        query: &str,
+        field: SpanSearchField,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<u64>;
+
+    /// Spans of the period that match the filter, the newest first
+    async fn query_spans(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredSpan>>;
+
+    /// Traces that started in the period whose aggregates match the filter, the newest first
+    async fn query_traces(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredTrace>>;
+
+    /// Evaluation scores of the period that match the filter, the newest first
+    async fn query_evaluation_scores(
+        &self,
+        project_id: Uuid,
+        filter: &CompiledFilter,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<FilteredEvaluationScore>>;
+
+    /// Sets the values of a promoted attribute on existing spans, by span id
+    async fn update_promoted_attribute_values(
+        &self,
+        project_id: Uuid,
+        slot: usize,
+        values: Vec<(Uuid, String)>,
+    ) -> Result<()>;
+
+    /// Resets the values of a promoted attribute slot on the project's spans
+    async fn clear_promoted_attribute(&self, project_id: Uuid, slot: usize) -> Result<()>;
+
+    /// Browser events recorded during the trace, oldest first
+    async fn get_trace_browser_events(
+        &self,
+        project_id: Uuid,
+        trace_id: Uuid,
+    ) -> Result<Vec<BrowserEvent>>;
+
+    /// Snapshot of the browser session taken closest to the timestamp
+    async fn get_nearest_browser_snapshot(
+        &self,
+        project_id: Uuid,
+        session_id: String,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<BrowserSnapshot>>;
+
+    /// Last snapshot of the step followed by the last snapshot of the previous step, if any.
+    /// Empty if the step has no snapshot.
+    async fn get_browser_snapshots_at_step(
+        &self,
+        project_id: Uuid,
+        session_id: String,
+        step: u32,
+    ) -> Result<Vec<BrowserSnapshot>>;
+}
@@ -0,0 +1,161 @@
+//! Translates natural language questions, e.g. "what was my average faithfulness score last week
+//! per evaluation group?", into `AnalyticsQuery`s with a language model. The model only writes the
+//! query description, the SQL is always compiled by `ch::query`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    ch::query::AnalyticsQuery,
+    db::DB,
+    language_model::{
+        providers::utils::missing_env_vars_for_model, ChatMessage, ChatMessageContent,
+        LanguageModelRunner, NodeInfo,
+    },
+};
+
+const DEFAULT_MODEL: &str = "openai:gpt-4o-mini";
+
+const SYSTEM_PROMPT: &str = r#"You translate questions about LLM observability data into a JSON query. Answer with the JSON only.
+
+Query format:
+{
+  "source": "spans" | "evaluationScores",
+  "metric": "count" | "latency" | "inputTokens" | "outputTokens" | "totalTokens" | "cost" | "score" | "custom",
+  "customMetric": string,
+  "aggregation": "count" | "sum" | "avg" | "min" | "max" | "p50" | "p90" | "p99",
+  "groupBy": [dimension, ...],
+  "filters": [{"dimension": dimension, "operator": "eq" | "ne", "value": string}],
+  "pastHours": integer,
+  "limit": integer,
+  "compareTo": "previousPeriod" | "previousWeek"
+}
+
+Source "spans" has metrics count, latency (seconds), inputTokens, outputTokens, totalTokens, cost (USD)
+and dimensions model, provider, spanName, spanType, userId, sessionId, sdkLanguage, sdkVersion,
+environment, region (country code), hour, day, week.
+Source "evaluationScores" has metrics count, score and dimensions evaluationId, evaluationGroup,
+scoreName, hour, day, week. Filter by scoreName to query a single score.
+
+"Last week" is pastHours 168, "today" is pastHours 24. Set compareTo only for questions comparing
+periods, e.g. "compared to last week", and never together with hour, day or week dimensions. If the question can't be answered with these
+sources, metrics and dimensions, answer {"error": "<why>"} instead."#;
+
+/// The question can't be expressed as a query
+#[derive(thiserror::Error, Debug)]
+#[error("{0}")]
+pub struct UntranslatableQuestion(pub String);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Translation {
+    Query(AnalyticsQuery),
+    Error { error: String },
+}
+
+/// Model in the format of "provider:model_name", set with `ANALYTICS_QUERY_MODEL`
+fn model() -> String {
+    std::env::var("ANALYTICS_QUERY_MODEL").unwrap_or(DEFAULT_MODEL.to_string())
+}
+
+/// Env variables, e.g. provider api keys, that the model needs but are not in `env`
+pub fn missing_env_vars(env: &HashMap<String, String>) -> Vec<String> {
+    missing_env_vars_for_model(&model(), env)
+}
+
+fn parse_translation(text: &str) -> Result<AnalyticsQuery> {
+    let text = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    match serde_json::from_str::<Translation>(text) {
+        Ok(Translation::Query(query)) => Ok(query),
+        Ok(Translation::Error { error }) => Err(UntranslatableQuestion(error).into()),
+        Err(e) => {
+            Err(UntranslatableQuestion(format!("The generated query is invalid: {}", e)).into())
+        }
+    }
+}
+
+fn system_prompt(custom_metrics: &[String]) -> String {
+    if custom_metrics.is_empty() {
+        return SYSTEM_PROMPT.to_string();
+    }
+    format!(
+        "{SYSTEM_PROMPT}\n\nSource \"spans\" also has the project's custom metrics {}. Query them \
+        with metric \"custom\" and their name in \"customMetric\".",
+        custom_metrics.join(", ")
+    )
+}
+
+/// `env` holds the api keys of the language model provider, e.g. the project's stored keys.
+/// `custom_metrics` are the names of the project's custom metrics.
+pub async fn translate_question(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    env: &HashMap<String, String>,
+    custom_metrics: &[String],
+    question: &str,
+) -> Result<AnalyticsQuery> {
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: ChatMessageContent::Text(system_prompt(custom_metrics)),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: ChatMessageContent::Text(question.to_string()),
+        },
+    ];
+    let node_info = NodeInfo {
+        id: Uuid::new_v4(),
+        node_id: Uuid::nil(),
+        node_name: "analytics_query".to_string(),
+        node_type: "LLM".to_string(),
+    };
+
+    let completion = language_model
+        .chat_completion(
+            &model(),
+            &messages,
+            &json!({ "temperature": 0 }),
+            env,
+            None,
+            &node_info,
+            db,
+            cache,
+        )
+        .await?;
+
+    parse_translation(&completion.text_message())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ch::query::{QueryDimension, QuerySource};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_translation() {
+        let query = parse_translation(
+            "```json\n{\"source\": \"evaluationScores\", \"metric\": \"score\", \
+            \"aggregation\": \"avg\", \"groupBy\": [\"evaluationGroup\"], \"pastHours\": 168}\n```",
+        )
+        .unwrap();
+        assert_eq!(query.source, QuerySource::EvaluationScores);
+        assert_eq!(query.group_by, vec![QueryDimension::EvaluationGroup]);
+
+        let error = parse_translation("{\"error\": \"Prompts are not tracked\"}").unwrap_err();
+        assert!(error.downcast_ref::<UntranslatableQuestion>().is_some());
+    }
+}
@@ -0,0 +1,81 @@
+//! `Deprecation` and `Sunset` headers (RFC 9745, RFC 8594) on endpoints of old API versions.
+//!
+//! SDKs can surface the headers as warnings. A `Link` header with `rel="successor-version"`
+//! points to the same endpoint under the next version.
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error,
+};
+use chrono::{DateTime, TimeZone, Utc};
+
+/// Date from which `v1` is deprecated in favor of `v2`
+fn v1_deprecated_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap()
+}
+
+/// Date after which `v1` endpoints may be removed
+fn v1_sunset_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2027, 5, 1, 0, 0, 0).unwrap()
+}
+
+/// Adds deprecation headers to every response of the `v1` scope
+pub async fn deprecate_v1(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let successor = req.path().replacen("/v1/", "/v2/", 1);
+    let mut res = next.call(req).await?;
+
+    let headers = res.headers_mut();
+    let deprecation = format!("@{}", v1_deprecated_at().timestamp());
+    let sunset = v1_sunset_at()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+    let link = format!("<{successor}>; rel=\"successor-version\"");
+    for (name, value) in [
+        ("deprecation", deprecation),
+        ("sunset", sunset),
+        ("link", link),
+    ] {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{get, middleware::from_fn, test, App, HttpResponse, Responder};
+
+    use super::*;
+
+    #[get("/v1/healthcheck")]
+    async fn healthcheck() -> impl Responder {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_deprecation_headers() {
+        let app =
+            test::init_service(App::new().wrap(from_fn(deprecate_v1)).service(healthcheck)).await;
+        let req = test::TestRequest::get().uri("/v1/healthcheck").to_request();
+        let res = test::call_service(&app, req).await;
+
+        let headers = res.headers();
+        assert_eq!(headers.get("deprecation").unwrap(), "@1793491200");
+        assert_eq!(
+            headers.get("sunset").unwrap(),
+            "Sat, 01 May 2027 00:00:00 GMT"
+        );
+        assert_eq!(
+            headers.get("link").unwrap(),
+            "</v2/healthcheck>; rel=\"successor-version\""
+        );
+    }
+}
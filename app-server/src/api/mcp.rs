@@ -0,0 +1,47 @@
+use actix_web::{post, web, HttpResponse};
+use bytes::Bytes;
+use serde_json::Value;
+
+use crate::{
+    db::{project_api_keys::ProjectApiKey, DB},
+    mcp::{self, JsonRpcResponse},
+    routes::types::ResponseResult,
+};
+
+/// MCP endpoint, authenticated with a project api key. Accepts a single message or a batch.
+#[post("")]
+pub async fn handle_mcp(
+    body: Bytes,
+    db: web::Data<DB>,
+    project_api_key: ProjectApiKey,
+) -> ResponseResult {
+    let project_id = project_api_key.project_id;
+    let db = db.into_inner();
+
+    let message = match serde_json::from_slice::<Value>(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(JsonRpcResponse::parse_error(e.to_string())))
+        }
+    };
+
+    match message {
+        Value::Array(messages) => {
+            let mut responses = Vec::new();
+            for message in messages {
+                if let Some(response) = mcp::handle_message(db.clone(), project_id, message).await {
+                    responses.push(response);
+                }
+            }
+            if responses.is_empty() {
+                return Ok(HttpResponse::Accepted().finish());
+            }
+            Ok(HttpResponse::Ok().json(responses))
+        }
+        message => match mcp::handle_message(db, project_id, message).await {
+            Some(response) => Ok(HttpResponse::Ok().json(response)),
+            // notifications are not answered
+            None => Ok(HttpResponse::Accepted().finish()),
+        },
+    }
+}
@@ -1,2 +1,7 @@
+pub mod deprecation;
+pub mod mcp;
+pub mod openapi;
 pub mod utils;
+pub mod validation;
 pub mod v1;
+pub mod v2;
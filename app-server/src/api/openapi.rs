@@ -0,0 +1,124 @@
+//! OpenAPI document of the public API, served at `/api-docs/openapi.json` with Swagger UI at
+//! `/swagger-ui/`. Client SDKs are generated from it, so new public routes should be added here.
+//!
+//! Endpoints shared by `/v1` and `/v2` are documented under `/v2`, of `/v1` only the endpoints
+//! with a different payload are kept, as deprecated.
+
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::{
+    db::{
+        agent_checkpoints::AgentCheckpoint, evaluations::Evaluation, events::EventObservation,
+        machines::MachineStatus, trace::CurrentTraceAndSpan,
+    },
+    evaluations::{
+        regression_gate::{RegressionGateResult, RegressionThresholds, ScoreGateResult},
+        utils::{EvaluationDatapointResult, HumanEvaluator, ScoreValue},
+    },
+    pipeline::RunBudget,
+    traces::ingestion_lag::IngestionStatus,
+};
+
+use super::{v1, v2};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Laminar API"),
+    paths(
+        v1::traces::process_traces,
+        v1::traces::get_events_for_session,
+        v2::traces::get_ingestion_status,
+        v2::browser_sessions::record_browser_events,
+        v2::browser_sessions::record_browser_snapshots,
+        v1::metrics::process_metrics,
+        v2::machines::create_machine,
+        v2::machines::get_machine,
+        v2::machines::terminate_machine,
+        v2::agent_runs::create_agent_checkpoint,
+        v2::agent_runs::resume_agent_run,
+        v1::evaluations::create_evaluation,
+        v2::evaluations::create_evaluation,
+        v2::evaluations::add_evaluation_datapoints,
+        v2::evaluations::stream_evaluation_progress,
+        v2::evaluations::check_regression_gate,
+        v2::evaluations::export_evaluation,
+        v2::traces::export_project_spans,
+        v2::grafana::grafana_health,
+        v2::grafana::grafana_search,
+        v2::grafana::grafana_metrics,
+        v2::grafana::grafana_query,
+        v1::datasets::get_datapoints,
+        v2::datasets::get_dataset_changes,
+        v1::pipelines::run_pipeline_graph,
+        v1::pipelines::ping_healthcheck,
+    ),
+    components(schemas(
+        v1::evaluations::CreateEvaluationRequest,
+        v2::evaluations::CreateEvaluationRequest,
+        v2::evaluations::EvaluationDatapoint,
+        v2::evaluations::AddEvaluationDatapointsRequest,
+        v2::evaluations::RegressionGateRequest,
+        RegressionThresholds,
+        RegressionGateResult,
+        ScoreGateResult,
+        v1::pipelines::GraphRequest,
+        RunBudget,
+        v2::browser_sessions::BrowserEventsRequest,
+        v2::browser_sessions::RecordedBrowserEvent,
+        v2::browser_sessions::BrowserSnapshotsRequest,
+        v2::browser_sessions::RecordedBrowserSnapshot,
+        v2::machines::MachineResponse,
+        v2::agent_runs::AgentCheckpointRequest,
+        v2::agent_runs::AgentCheckpointResponse,
+        v2::agent_runs::ResumeAgentRunResponse,
+        v2::grafana::GrafanaSearchRequest,
+        v2::grafana::GrafanaQueryRequest,
+        v2::grafana::GrafanaRange,
+        v2::grafana::GrafanaTarget,
+        AgentCheckpoint,
+        MachineStatus,
+        CurrentTraceAndSpan,
+        EvaluationDatapointResult,
+        HumanEvaluator,
+        ScoreValue,
+        Evaluation,
+        EventObservation,
+        IngestionStatus,
+    )),
+    modifiers(&ProjectApiKeyAuth)
+)]
+pub struct ApiDoc;
+
+struct ProjectApiKeyAuth;
+
+impl Modify for ProjectApiKeyAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "project_api_key",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Project api key"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_contains_paths() {
+        let openapi = ApiDoc::openapi();
+        assert!(openapi.paths.paths.contains_key("/v2/traces"));
+        assert!(openapi.paths.paths.contains_key("/v1/evaluations"));
+        assert!(openapi.paths.paths.contains_key("/v2/evaluations"));
+        assert!(openapi.to_json().is_ok());
+    }
+}
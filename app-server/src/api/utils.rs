@@ -3,8 +3,11 @@ use std::sync::Arc;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::db::pipelines::pipeline_deployments::PRODUCTION_TARGET;
 use crate::db::pipelines::PipelineVersion;
-use crate::pipeline::utils::get_target_pipeline_version_cache_key;
+use crate::pipeline::utils::{
+    get_pipeline_deployment_cache_key, get_target_pipeline_version_cache_key,
+};
 use crate::routes::api_keys::hash_api_key;
 use crate::routes::error;
 use crate::{
@@ -40,6 +43,40 @@ pub async fn query_target_pipeline_version(
     }
 }
 
+/// Version the deployment target of the pipeline points at
+pub async fn query_deployed_pipeline_version(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+    pipeline_name: String,
+    target: &str,
+) -> Result<Option<PipelineVersion>, error::Error> {
+    // Production deployments are mirrored to the target pipeline versions
+    if target == PRODUCTION_TARGET {
+        return query_target_pipeline_version(db, cache, project_id, pipeline_name).await;
+    }
+
+    let cache_key =
+        get_pipeline_deployment_cache_key(&project_id.to_string(), &pipeline_name, target);
+    if let Ok(Some(pipeline_version)) = cache.get::<PipelineVersion>(&cache_key).await {
+        return Ok(Some(pipeline_version));
+    }
+    let pipeline_version =
+        db::pipelines::pipeline_deployments::get_deployed_pipeline_version_by_pipeline_name(
+            &db.pool,
+            project_id,
+            &pipeline_name,
+            target,
+        )
+        .await?;
+    if let Some(pipeline_version) = &pipeline_version {
+        let _ = cache
+            .insert::<PipelineVersion>(cache_key, pipeline_version)
+            .await;
+    }
+    Ok(pipeline_version)
+}
+
 pub async fn get_api_key_from_raw_value(
     pool: &PgPool,
     cache: Arc<Cache>,
@@ -1,18 +1,54 @@
 use actix_web::{get, web, HttpResponse};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+use uuid::Uuid;
 
 use crate::{
-    db::{datapoints, datasets, project_api_keys::ProjectApiKey, DB},
-    routes::{types::ResponseResult, PaginatedResponse},
+    db::{
+        datapoints::{self, DatapointView},
+        dataset_versions, datasets,
+        project_api_keys::ProjectApiKey,
+        DB,
+    },
+    routes::{
+        error::{Error, ErrorCode},
+        types::ResponseResult,
+        PaginatedResponse,
+    },
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct GetDatapointsRequestParams {
+    /// Dataset name
     name: String,
     limit: i64,
     offset: i64,
+    /// Version of the dataset to read, defaults to the current version
+    #[serde(default)]
+    version: Option<i32>,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DatapointsResponse {
+    #[serde(flatten)]
+    page: PaginatedResponse<DatapointView>,
+    dataset_id: Uuid,
+    /// Version the datapoints were read at, to be recorded with the evaluation that uses them
+    dataset_version: i32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v2/datasets/datapoints",
+    tag = "datasets",
+    params(GetDatapointsRequestParams),
+    responses(
+        (status = 200, description = "Paginated datapoints of the dataset, with the dataset's id and version"),
+        (status = 404, description = "Dataset not found"),
+    ),
+    security(("project_api_key" = []))
+)]
 #[get("/datasets/datapoints")]
 async fn get_datapoints(
     params: web::Query<GetDatapointsRequestParams>,
@@ -26,18 +62,46 @@ async fn get_datapoints(
     let dataset = datasets::get_dataset_by_name(&db.pool, &query.name, project_id).await?;
 
     let Some(dataset) = dataset else {
-        return Ok(HttpResponse::NotFound().body(format!("dataset {} not found", &query.name)));
+        return Err(Error::api(
+            ErrorCode::DatasetNotFound,
+            format!("dataset {} not found", &query.name),
+        ));
     };
 
-    let datapoints =
-        datapoints::get_datapoints(&db.pool, dataset.id, query.limit, query.offset).await?;
-
-    let total_count = datapoints::count_datapoints(&db.pool, dataset.id).await?;
+    let (datapoints, total_count, dataset_version) = match query.version {
+        Some(version) if !(0..=dataset.version).contains(&version) => {
+            return Err(Error::invalid_request(Some(&format!(
+                "Version must be between 0 and {}",
+                dataset.version
+            ))));
+        }
+        Some(version) => (
+            dataset_versions::get_datapoints_at_version(
+                &db.pool,
+                &dataset.id,
+                version,
+                query.limit,
+                query.offset,
+            )
+            .await?,
+            dataset_versions::count_datapoints_at_version(&db.pool, &dataset.id, version).await?,
+            version,
+        ),
+        None => (
+            datapoints::get_datapoints(&db.pool, dataset.id, query.limit, query.offset).await?,
+            datapoints::count_datapoints(&db.pool, dataset.id).await?,
+            dataset.version,
+        ),
+    };
 
-    let response = PaginatedResponse {
-        total_count,
-        items: datapoints,
-        any_in_project: total_count > 0,
+    let response = DatapointsResponse {
+        page: PaginatedResponse {
+            total_count,
+            items: datapoints,
+            any_in_project: total_count > 0,
+        },
+        dataset_id: dataset.id,
+        dataset_version,
     };
 
     Ok(HttpResponse::Ok().json(response))
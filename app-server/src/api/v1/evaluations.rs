@@ -1,38 +1,51 @@
 use actix_web::{post, web, HttpResponse};
-use chrono::Utc;
 use serde::Deserialize;
 use std::sync::Arc;
-use uuid::Uuid;
+use utoipa::ToSchema;
 
 use crate::{
-    ch::evaluation_scores::{insert_evaluation_scores, EvaluationScore},
-    db::{self, project_api_keys::ProjectApiKey, DB},
-    evaluations::utils::{
-        datapoints_to_labeling_queues, get_columns_from_points, EvaluationDatapointResult,
-    },
+    analytics::AnalyticsStore,
+    api::validation::ValidatedJson,
+    db::{evaluations::Evaluation, project_api_keys::ProjectApiKey, DB},
+    evaluations::{self, progress::EvaluationProgressHub, utils::EvaluationDatapointResult},
     names::NameGenerator,
-    routes::types::ResponseResult,
+    routes::{error::Error, types::ResponseResult},
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct CreateEvaluationRequest {
+pub struct CreateEvaluationRequest {
+    /// If not set, a random name is generated
     name: Option<String>,
+    /// Defaults to "default"
     group_id: Option<String>,
     points: Vec<EvaluationDatapointResult>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/evaluations",
+    tag = "evaluations",
+    request_body = CreateEvaluationRequest,
+    responses(
+        (status = 200, description = "Created evaluation", body = Evaluation),
+        (status = 400, description = "Evaluation has no datapoint results"),
+    ),
+    security(("project_api_key" = [])),
+    deprecated
+)]
 #[post("evaluations")]
 async fn create_evaluation(
-    req: web::Json<CreateEvaluationRequest>,
+    req: ValidatedJson<CreateEvaluationRequest>,
     db: web::Data<DB>,
-    clickhouse: web::Data<clickhouse::Client>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
     project_api_key: ProjectApiKey,
     name_generator: web::Data<Arc<NameGenerator>>,
+    progress_hub: web::Data<Arc<EvaluationProgressHub>>,
 ) -> ResponseResult {
     let project_id = project_api_key.project_id;
     let req = req.into_inner();
-    let clickhouse = clickhouse.into_inner().as_ref().clone();
+    let analytics_store = analytics_store.as_ref().clone();
     let db = db.into_inner();
 
     let name = if let Some(name) = req.name {
@@ -44,59 +57,23 @@ async fn create_evaluation(
     let points = req.points;
 
     if points.is_empty() {
-        return Ok(
-            HttpResponse::BadRequest().json("Evaluation must have at least one datapoint result")
-        );
+        return Err(Error::invalid_request(Some(
+            "Evaluation must have at least one datapoint result",
+        )));
     }
 
-    let evaluation =
-        db::evaluations::create_evaluation(&db.pool, &name, project_id, &group_id).await?;
-
-    let columns = get_columns_from_points(&points);
-    let ids = points.iter().map(|_| Uuid::new_v4()).collect::<Vec<_>>();
-    let labeling_queues =
-        datapoints_to_labeling_queues(db.clone(), &points, &ids, &project_id).await?;
-
-    for (queue_id, entries) in labeling_queues.iter() {
-        db::labeling_queues::push_to_labeling_queue(&db.pool, queue_id, &entries).await?;
-    }
-
-    let ids_clone = ids.clone();
-    let db_task = tokio::spawn(async move {
-        db::evaluations::set_evaluation_results(
-            db.clone(),
-            evaluation.id,
-            &ids_clone,
-            &columns.scores,
-            &columns.datas,
-            &columns.targets,
-            &columns.executor_outputs,
-            &columns.trace_ids,
-        )
-        .await
-    });
-
-    // Flattened scores from all evaluators to be recorded to Clickhouse
-    // Its length can be longer than the amount of evaluation datapoints
-    // since each datapoint can have multiple evaluators
-    let ch_evaluation_scores = EvaluationScore::from_evaluation_datapoint_results(
-        &points,
-        &ids,
+    let evaluation = evaluations::create_evaluation(
+        db,
+        analytics_store,
+        progress_hub.as_ref().clone(),
         project_id,
+        name,
         group_id,
-        evaluation.id,
-        Utc::now(),
-    );
-
-    let ch_task = tokio::spawn(insert_evaluation_scores(
-        clickhouse.clone(),
-        ch_evaluation_scores,
-    ));
-
-    let (db_result, ch_result) = tokio::join!(db_task, ch_task);
-
-    db_result.map_err(|e| anyhow::anyhow!("Database task failed: {}", e))??;
-    ch_result.map_err(|e| anyhow::anyhow!("Clickhouse task failed: {}", e))??;
+        None,
+        None,
+        points,
+    )
+    .await?;
 
     Ok(HttpResponse::Ok().json(evaluation))
 }
@@ -2,6 +2,18 @@ use actix_web::{post, HttpRequest, HttpResponse};
 
 use crate::routes::types::ResponseResult;
 
+#[utoipa::path(
+    post,
+    path = "/v2/metrics",
+    tag = "traces",
+    request_body(
+        content = Vec<u8>,
+        description = "OTLP ExportMetricsServiceRequest",
+        content_type = "application/x-protobuf"
+    ),
+    responses((status = 200, description = "Metrics are accepted and ignored")),
+    security(("project_api_key" = []))
+)]
 #[post("metrics")]
 pub async fn process_metrics(req: HttpRequest) -> ResponseResult {
     // This is a placeholder that just returns ok, so that client otel exporters
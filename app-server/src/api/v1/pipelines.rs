@@ -2,28 +2,62 @@ use std::{collections::HashMap, sync::Arc};
 
 use actix_web::{get, post, web, HttpResponse};
 use serde::Deserialize;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
-    api::utils::query_target_pipeline_version,
+    api::utils::{query_deployed_pipeline_version, query_target_pipeline_version},
     cache::Cache,
-    db::{project_api_keys::ProjectApiKey, trace::CurrentTraceAndSpan, DB},
+    db::{
+        pipelines::pipeline_usage, project_api_keys::ProjectApiKey, trace::CurrentTraceAndSpan, DB,
+    },
+    engine::engine::EngineOutput,
+    logging,
     pipeline::{
         nodes::{GraphOutput, GraphRunOutput, NodeInput, RunEndpointEventError, StreamChunk},
         runner::{PipelineRunner, PipelineRunnerError},
-        Graph, RunType,
+        trace::RunTraceStats,
+        Graph, RunBudget, RunType,
     },
     routes::{
-        error::{self, pipeline_runner_to_http_error},
+        error::{self, pipeline_runner_to_http_error, ErrorCode},
         types::ResponseResult,
     },
 };
 
-#[derive(Deserialize)]
+/// Adds the nodes' executions, tokens and cost to the pipeline's monthly usage in the background
+pub fn record_node_usage(
+    db: Arc<DB>,
+    pipeline_id: Uuid,
+    run_result: &Result<EngineOutput, PipelineRunnerError>,
+) {
+    let Some(trace) = PipelineRunner::get_trace_from_result(run_result) else {
+        return;
+    };
+    let node_stats = RunTraceStats::from_messages(&trace.messages).node_stats;
+    logging::spawn(async move {
+        if let Err(e) =
+            pipeline_usage::record_pipeline_node_usage(&db.pool, &pipeline_id, &node_stats).await
+        {
+            log::error!(
+                "Failed to record pipeline node usage. pipeline_id [{}]: {:?}",
+                pipeline_id,
+                e
+            );
+        }
+    });
+}
+
+#[derive(Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct GraphRequest {
+pub struct GraphRequest {
     /// Name of the pipeline to run
     pipeline: String,
+    /// Deployment target to run, e.g. `staging`. If not set, the pipeline's target version, which
+    /// is also its `production` deployment, is run.
+    #[serde(default)]
+    target: Option<String>,
+    #[schema(value_type = HashMap<String, Object>)]
     inputs: HashMap<String, NodeInput>,
     /// If None, new trace will be generated
     #[serde(default, flatten)]
@@ -33,8 +67,22 @@ struct GraphRequest {
     metadata: HashMap<String, String>,
     #[serde(default)]
     stream: bool,
+    /// Limits of the run, overrides the budget saved with the pipeline version
+    #[serde(default)]
+    budget: Option<RunBudget>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/v2/pipeline/run",
+    tag = "pipelines",
+    request_body = GraphRequest,
+    responses((
+        status = 200,
+        description = "Pipeline outputs, or a stream of server-sent events if `stream` is set"
+    )),
+    security(("project_api_key" = []))
+)]
 #[post("pipeline/run")]
 async fn run_pipeline_graph(
     pipeline_runner: web::Data<Arc<PipelineRunner>>,
@@ -55,13 +103,35 @@ async fn run_pipeline_graph(
 
     env.insert("collection_name".to_string(), project_id.to_string());
 
-    let pipeline_version =
-        query_target_pipeline_version(db.clone(), cache.clone(), project_id, req.pipeline.clone())
-            .await?;
-
-    let Some(pipeline_version) = pipeline_version else {
-        return Err(error::Error::no_target_pipeline(&req.pipeline));
+    let pipeline_version = match &req.target {
+        Some(target) => query_deployed_pipeline_version(
+            db.clone(),
+            cache.clone(),
+            project_id,
+            req.pipeline.clone(),
+            target,
+        )
+        .await?
+        .ok_or_else(|| error::Error::no_pipeline_deployment(&req.pipeline, target))?,
+        None => query_target_pipeline_version(
+            db.clone(),
+            cache.clone(),
+            project_id,
+            req.pipeline.clone(),
+        )
+        .await?
+        .ok_or_else(|| error::Error::no_target_pipeline(&req.pipeline))?,
     };
+    let pipeline_id = pipeline_version.pipeline_id;
+    if !pipeline_usage::reserve_pipeline_execution(&db.pool, &pipeline_id).await? {
+        return Err(error::Error::api(
+            ErrorCode::QuotaExceeded,
+            format!(
+                "Pipeline {} has used up its monthly execution quota",
+                req.pipeline
+            ),
+        ));
+    }
     let pipeline_version_name = format!("{}.{}", req.pipeline, pipeline_version.name);
 
     let run_id = Uuid::new_v4(); // used to uniquely identify the related log or run trace
@@ -71,13 +141,22 @@ async fn run_pipeline_graph(
     graph
         .setup(&inputs, &env, &metadata, &run_type)
         .map_err(error::graph_error_to_http_error)?;
+    graph.project_id = Some(project_id);
+    if let Some(budget) = req.budget {
+        if !budget.is_valid() {
+            return Err(error::Error::invalid_request(Some(
+                "Budget limits must be positive",
+            )));
+        }
+        graph.budget = Some(budget);
+    }
 
     if req.stream {
         let stream = async_stream::stream! {
 
             let (tx, mut rx) = tokio::sync::mpsc::channel::<StreamChunk>(8);
 
-            tokio::spawn(async move {
+            logging::spawn(async move {
                 let run_result = pipeline_runner.run(graph, Some(tx.clone())).await;
                 // write the trace
                 pipeline_runner.record_observations(
@@ -89,6 +168,7 @@ async fn run_pipeline_graph(
                 )
                 .await
                 .expect("Failed to record observations from pipeline output");
+                record_node_usage(db, pipeline_id, &run_result);
 
 
                 // communicate the end result to the client
@@ -151,6 +231,7 @@ async fn run_pipeline_graph(
                 None,
             )
             .await?;
+        record_node_usage(db, pipeline_id, &run_result);
 
         let run_result = run_result.map_err(|e| pipeline_runner_to_http_error(e, run_id))?;
         let outputs = run_result
@@ -164,6 +245,13 @@ async fn run_pipeline_graph(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/v2/healthcheck",
+    tag = "pipelines",
+    responses((status = 200, description = "Project api key is valid")),
+    security(("project_api_key" = []))
+)]
 #[get("healthcheck")]
 async fn ping_healthcheck() -> ResponseResult {
     Ok(HttpResponse::Ok().finish())
@@ -2,11 +2,14 @@ use std::sync::Arc;
 
 use actix_web::{get, post, web, HttpRequest, HttpResponse};
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use lapin::Connection;
 use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
 use uuid::Uuid;
 
 use crate::{
+    api::validation::protobuf_field_error,
     db::{
         events::{self, EventObservation},
         project_api_keys::ProjectApiKey,
@@ -15,8 +18,23 @@ use crate::{
     },
     features::{is_feature_enabled, Feature},
     opentelemetry::opentelemetry::proto::collector::trace::v1::ExportTraceServiceRequest,
-    routes::types::ResponseResult,
-    traces::{limits::get_workspace_limit_exceeded_by_project_id, producer::push_spans_to_queue},
+    routes::{
+        error::{Error, ErrorCode},
+        types::ResponseResult,
+    },
+    traces::{
+        archive::{archive_in_background, PayloadArchive},
+        client_metadata::{client_region_header, parse_region},
+        ingestion_lag::insert_ingestion_headers,
+        limits::get_workspace_limit_exceeded_by_project_id,
+        producer::push_spans_to_queue,
+        protocol::{
+            adapt_request, negotiate_protocol_version, CURRENT_PROTOCOL_VERSION,
+            PROTOCOL_VERSION_HEADER,
+        },
+        sampling::DEBUG_FLAG_HEADER,
+        sdk_versions::track_sdk_version,
+    },
 };
 use prost::Message;
 
@@ -25,8 +43,26 @@ pub struct RabbitMqSpanMessage {
     pub project_id: Uuid,
     pub span: Span,
     pub events: Vec<EventObservation>,
+    /// Set for the spans accepted by the ingestion endpoints, see `traces::ingestion_lag`
+    #[serde(default)]
+    pub accepted_at: Option<DateTime<Utc>>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/v2/traces",
+    tag = "traces",
+    request_body(
+        content = Vec<u8>,
+        description = "OTLP ExportTraceServiceRequest",
+        content_type = "application/x-protobuf"
+    ),
+    responses(
+        (status = 200, description = "Spans are accepted"),
+        (status = 403, description = "Workspace span limit exceeded"),
+    ),
+    security(("project_api_key" = []))
+)]
 #[post("traces")]
 pub async fn process_traces(
     req: HttpRequest,
@@ -35,12 +71,30 @@ pub async fn process_traces(
     rabbitmq_connection: web::Data<Option<Arc<Connection>>>,
     db: web::Data<DB>,
     cache: web::Data<crate::cache::Cache>,
+    payload_archive: web::Data<Option<Arc<PayloadArchive>>>,
 ) -> ResponseResult {
     let db = db.into_inner();
     let cache = cache.into_inner();
-    let request = ExportTraceServiceRequest::decode(body).map_err(|e| {
-        anyhow::anyhow!("Failed to decode ExportTraceServiceRequest from bytes. {e}")
-    })?;
+    let protocol_version = negotiate_protocol_version(
+        req.headers()
+            .get(PROTOCOL_VERSION_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    )
+    .map_err(|e| Error::unsupported_protocol_version(&e))?;
+    // OTLP exporters can also send JSON, which is not supported
+    if req
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"))
+    {
+        return Err(Error::invalid_request(Some(
+            "OTLP/HTTP JSON encoding is not supported, export traces with the protobuf encoding",
+        )));
+    }
+    let raw_payload = body.clone();
+    let mut request = ExportTraceServiceRequest::decode(body).map_err(protobuf_field_error)?;
+    adapt_request(&mut request, protocol_version);
     let rabbitmq_connection = rabbitmq_connection.as_ref().clone();
 
     if is_feature_enabled(Feature::UsageLimit) {
@@ -53,13 +107,45 @@ pub async fn process_traces(
 
         // TODO: do the same for events
         if limits_exceeded.spans {
-            return Ok(HttpResponse::Forbidden().json("Workspace span limit exceeded"));
+            return Err(Error::api(
+                ErrorCode::QuotaExceeded,
+                "Workspace span limit exceeded",
+            ));
         }
     }
 
+    if payload_archive.is_some() {
+        // archived payloads are replayed without the header, so they are stored upgraded
+        let payload = if protocol_version < CURRENT_PROTOCOL_VERSION {
+            request.encode_to_vec()
+        } else {
+            raw_payload.to_vec()
+        };
+        archive_in_background(
+            payload_archive.as_ref().clone(),
+            project_api_key.project_id,
+            payload,
+        );
+    }
+
+    let client_region = client_region_header()
+        .and_then(|header| req.headers().get(header))
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_region);
+
+    let debug_flag = req
+        .headers()
+        .get(DEBUG_FLAG_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    let sdk_warning = track_sdk_version(&request);
+
     let response = push_spans_to_queue(
         request,
         project_api_key.project_id,
+        client_region,
+        debug_flag,
         rabbitmq_connection,
         db,
         cache,
@@ -72,19 +158,35 @@ pub async fn process_traces(
     let keep_alive = req.headers().get("connection").map_or(false, |v| {
         v.to_str().unwrap_or_default().trim().to_lowercase() == "keep-alive"
     });
+    let mut res = HttpResponse::Ok();
+    res.insert_header((
+        PROTOCOL_VERSION_HEADER,
+        CURRENT_PROTOCOL_VERSION.to_string(),
+    ));
     if keep_alive {
-        Ok(HttpResponse::Ok().keep_alive().finish())
-    } else {
-        Ok(HttpResponse::Ok().finish())
+        res.keep_alive();
+    }
+    if let Some(warning) = sdk_warning {
+        res.insert_header(("warning", warning));
     }
+    Ok(res.finish())
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 #[serde(rename_all = "camelCase")]
-struct GetEventsForSessionRequest {
+#[into_params(rename_all = "camelCase")]
+pub struct GetEventsForSessionRequest {
     session_id: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/v2/session-events",
+    tag = "traces",
+    params(GetEventsForSessionRequest),
+    responses((status = 200, description = "Events of the session", body = Vec<EventObservation>)),
+    security(("project_api_key" = []))
+)]
 #[get("session-events")]
 pub async fn get_events_for_session(
     request: web::Query<GetEventsForSessionRequest>,
@@ -96,5 +198,7 @@ pub async fn get_events_for_session(
     let events = events::get_events_for_session(&db.pool, &session_id, &project_id)
         .await
         .map_err(|e| anyhow::anyhow!("Failed to get events for session: {}", e))?;
-    Ok(HttpResponse::Ok().json(events))
+    let mut res = HttpResponse::Ok();
+    insert_ingestion_headers(&mut res, &project_id);
+    Ok(res.json(events))
 }
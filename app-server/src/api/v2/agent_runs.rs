@@ -0,0 +1,146 @@
+use actix_web::{post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self,
+        agent_checkpoints::{AgentCheckpoint, NewAgentCheckpoint},
+        project_api_keys::ProjectApiKey,
+        trace::CurrentTraceAndSpan,
+        DB,
+    },
+    routes::{
+        error::{Error, ErrorCode},
+        types::ResponseResult,
+    },
+};
+
+/// Older checkpoints of a run are deleted, runs are resumed from the last one
+const MAX_CHECKPOINTS_PER_RUN: i64 = 10;
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCheckpointRequest {
+    /// Trace of the agent run
+    trace_id: Uuid,
+    /// Span active when the checkpoint is taken, resumed runs continue under it
+    span_id: Uuid,
+    #[serde(default)]
+    span_path: Option<String>,
+    /// Steps the agent has taken
+    step: i32,
+    /// Conversation and memory of the agent, returned as is when the run is resumed
+    #[schema(value_type = Object)]
+    state: Value,
+    /// Browser session the agent controls, see `/v2/browser-sessions/events`
+    #[serde(default)]
+    browser_session_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCheckpointResponse {
+    id: Uuid,
+    /// Step at which the agent should take its next checkpoint, per the project's interval
+    next_checkpoint_step: i32,
+}
+
+/// Save the state of an agent run, so that it can be resumed if the run fails
+#[utoipa::path(
+    post,
+    path = "/v2/agent-runs/checkpoints",
+    tag = "agent-runs",
+    request_body = AgentCheckpointRequest,
+    responses((status = 200, description = "Checkpoint is saved", body = AgentCheckpointResponse)),
+    security(("project_api_key" = []))
+)]
+#[post("agent-runs/checkpoints")]
+pub async fn create_agent_checkpoint(
+    req: web::Json<AgentCheckpointRequest>,
+    project_api_key: ProjectApiKey,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let req = req.into_inner();
+    let project_id = project_api_key.project_id;
+    if req.step < 0 {
+        return Err(Error::invalid_request(Some("Step must not be negative")));
+    }
+
+    let checkpoint = NewAgentCheckpoint {
+        trace_id: req.trace_id,
+        span_id: req.span_id,
+        span_path: req.span_path,
+        step: req.step,
+        state: req.state,
+        browser_session_id: req.browser_session_id,
+    };
+    let id = db::agent_checkpoints::create_agent_checkpoint(
+        &db.pool,
+        &project_id,
+        &checkpoint,
+        MAX_CHECKPOINTS_PER_RUN,
+    )
+    .await?;
+    let interval = db::projects::get_agent_checkpoint_interval(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(AgentCheckpointResponse {
+        id,
+        next_checkpoint_step: checkpoint.step.saturating_add(interval),
+    }))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeAgentRunResponse {
+    checkpoint: AgentCheckpoint,
+    /// Trace context to record the resumed run in, as a continuation of the original trace
+    current_trace_and_span: CurrentTraceAndSpan,
+    next_checkpoint_step: i32,
+}
+
+/// Resume a failed agent run from its last checkpoint. The agent restores the checkpoint's state
+/// and browser session, and records its spans under the checkpoint's span.
+#[utoipa::path(
+    post,
+    path = "/v2/agent-runs/{trace_id}/resume",
+    tag = "agent-runs",
+    params(("trace_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Last checkpoint of the run", body = ResumeAgentRunResponse),
+        (status = 404, description = "Run has no checkpoints"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[post("agent-runs/{trace_id}/resume")]
+pub async fn resume_agent_run(
+    path: web::Path<Uuid>,
+    project_api_key: ProjectApiKey,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let trace_id = path.into_inner();
+    let project_id = project_api_key.project_id;
+
+    let checkpoint =
+        db::agent_checkpoints::resume_from_last_agent_checkpoint(&db.pool, &project_id, &trace_id)
+            .await?
+            .ok_or_else(|| {
+                Error::api(
+                    ErrorCode::CheckpointNotFound,
+                    format!("run {} has no checkpoints", trace_id),
+                )
+            })?;
+    let interval = db::projects::get_agent_checkpoint_interval(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(ResumeAgentRunResponse {
+        current_trace_and_span: CurrentTraceAndSpan {
+            trace_id: checkpoint.trace_id,
+            parent_span_id: checkpoint.span_id,
+            parent_span_path: checkpoint.span_path.clone(),
+        },
+        next_checkpoint_step: checkpoint.step.saturating_add(interval),
+        checkpoint,
+    }))
+}
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use actix_web::{post, web, HttpResponse};
+use chrono::DateTime;
+use serde::Deserialize;
+use serde_json::Value;
+use sha3::{Digest, Sha3_256};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    ch::{browser_events::BrowserEvent, browser_snapshots::BrowserSnapshot},
+    db::project_api_keys::ProjectApiKey,
+    routes::{error::Error, types::ResponseResult},
+    storage::{base64_to_bytes, create_key, Storage},
+};
+
+const MAX_EVENTS_PER_REQUEST: usize = 1000;
+const MAX_SNAPSHOTS_PER_REQUEST: usize = 100;
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedBrowserEvent {
+    /// E.g. `mutation`, `navigation`, `click` or `screenshot`
+    #[serde(rename = "type")]
+    event_type: String,
+    /// Milliseconds since the Unix epoch
+    timestamp: i64,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    data: Value,
+    /// Base64 encoded PNG of the page
+    #[serde(default)]
+    screenshot: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserEventsRequest {
+    session_id: String,
+    /// Trace of the agent run that controls the browser
+    trace_id: Uuid,
+    events: Vec<RecordedBrowserEvent>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/v2/browser-sessions/events",
+    tag = "traces",
+    request_body = BrowserEventsRequest,
+    responses((status = 200, description = "Events are recorded")),
+    security(("project_api_key" = []))
+)]
+#[post("browser-sessions/events")]
+pub async fn record_browser_events(
+    req: web::Json<BrowserEventsRequest>,
+    project_api_key: ProjectApiKey,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> ResponseResult {
+    let req = req.into_inner();
+    let project_id = project_api_key.project_id;
+    if req.events.len() > MAX_EVENTS_PER_REQUEST {
+        return Err(Error::invalid_request(Some(&format!(
+            "At most {} events can be recorded per request",
+            MAX_EVENTS_PER_REQUEST
+        ))));
+    }
+
+    let mut events = Vec::with_capacity(req.events.len());
+    for event in req.events {
+        let timestamp = DateTime::from_timestamp_millis(event.timestamp)
+            .ok_or_else(|| Error::invalid_request(Some("Invalid event timestamp")))?;
+        let screenshot_url = match event.screenshot {
+            Some(screenshot) => {
+                let data = base64_to_bytes(&screenshot)
+                    .map_err(|_| Error::invalid_request(Some("Screenshot must be base64")))?;
+                let key = create_key(&project_id, &Some("png".to_string()));
+                storage.store(data, &key).await?
+            }
+            None => String::new(),
+        };
+        events.push(BrowserEvent {
+            id: Uuid::new_v4(),
+            project_id,
+            session_id: req.session_id.clone(),
+            trace_id: req.trace_id,
+            timestamp,
+            event_type: event.event_type,
+            data: event.data.to_string(),
+            screenshot_url,
+        });
+    }
+    analytics_store.insert_browser_events(events).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedBrowserSnapshot {
+    /// Step of the agent after which the snapshot was taken
+    step: u32,
+    /// Milliseconds since the Unix epoch
+    timestamp: i64,
+    /// URL of the page
+    #[serde(default)]
+    url: String,
+    /// Base64 encoded PNG of the page
+    #[serde(default)]
+    screenshot: Option<String>,
+    /// Serialized DOM of the page
+    #[serde(default)]
+    dom: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserSnapshotsRequest {
+    session_id: String,
+    /// Trace of the agent run that controls the browser
+    trace_id: Uuid,
+    snapshots: Vec<RecordedBrowserSnapshot>,
+}
+
+fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha3_256::digest(data))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v2/browser-sessions/snapshots",
+    tag = "traces",
+    request_body = BrowserSnapshotsRequest,
+    responses((status = 200, description = "Snapshots are recorded")),
+    security(("project_api_key" = []))
+)]
+#[post("browser-sessions/snapshots")]
+pub async fn record_browser_snapshots(
+    req: web::Json<BrowserSnapshotsRequest>,
+    project_api_key: ProjectApiKey,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> ResponseResult {
+    let req = req.into_inner();
+    let project_id = project_api_key.project_id;
+    if req.snapshots.len() > MAX_SNAPSHOTS_PER_REQUEST {
+        return Err(Error::invalid_request(Some(&format!(
+            "At most {} snapshots can be recorded per request",
+            MAX_SNAPSHOTS_PER_REQUEST
+        ))));
+    }
+
+    let mut snapshots = Vec::with_capacity(req.snapshots.len());
+    for snapshot in req.snapshots {
+        let timestamp = DateTime::from_timestamp_millis(snapshot.timestamp)
+            .ok_or_else(|| Error::invalid_request(Some("Invalid snapshot timestamp")))?;
+        let (screenshot_url, screenshot_hash) = match snapshot.screenshot {
+            Some(screenshot) => {
+                let data = base64_to_bytes(&screenshot)
+                    .map_err(|_| Error::invalid_request(Some("Screenshot must be base64")))?;
+                let hash = content_hash(&data);
+                let key = create_key(&project_id, &Some("png".to_string()));
+                (storage.store(data, &key).await?, hash)
+            }
+            None => (String::new(), String::new()),
+        };
+        let (dom_url, dom_hash) = match snapshot.dom {
+            Some(dom) => {
+                let data = dom.into_bytes();
+                let hash = content_hash(&data);
+                let key = create_key(&project_id, &Some("html".to_string()));
+                (storage.store(data, &key).await?, hash)
+            }
+            None => (String::new(), String::new()),
+        };
+        snapshots.push(BrowserSnapshot {
+            id: Uuid::new_v4(),
+            project_id,
+            session_id: req.session_id.clone(),
+            trace_id: req.trace_id,
+            step: snapshot.step,
+            timestamp,
+            page_url: snapshot.url,
+            screenshot_url,
+            screenshot_hash,
+            dom_url,
+            dom_hash,
+        });
+    }
+    analytics_store.insert_browser_snapshots(snapshots).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
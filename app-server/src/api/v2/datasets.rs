@@ -0,0 +1,121 @@
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::{
+    datasets::changes::ChangeCursor,
+    db::{
+        dataset_versions::{self, DatapointFeedChange},
+        datasets,
+        project_api_keys::ProjectApiKey,
+        DB,
+    },
+    routes::{
+        error::{Error, ErrorCode},
+        types::ResponseResult,
+    },
+};
+
+const DEFAULT_CHANGES_LIMIT: i64 = 1000;
+const MAX_CHANGES_LIMIT: i64 = 10000;
+
+#[derive(Deserialize, IntoParams)]
+pub struct GetDatasetChangesRequestParams {
+    /// Dataset name
+    name: String,
+    /// `nextCursor` of the previous page. To mirror a dataset, read its datapoints and follow
+    /// the feed from their `datasetVersion`. Without a cursor, the feed starts from the
+    /// dataset's creation.
+    #[serde(default)]
+    cursor: Option<String>,
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DatasetChangesResponse {
+    items: Vec<DatapointFeedChange>,
+    /// Cursor to read the following changes from, also once there are none yet
+    next_cursor: String,
+    has_more: bool,
+    dataset_id: Uuid,
+    dataset_version: i32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v2/datasets/changes",
+    tag = "datasets",
+    params(GetDatasetChangesRequestParams),
+    responses(
+        (status = 200, description = "Changes of the datapoints after the cursor, ordered by version, with the cursor of the next page"),
+        (status = 400, description = "Invalid cursor or limit"),
+        (status = 404, description = "Dataset not found"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[get("/datasets/changes")]
+async fn get_dataset_changes(
+    params: web::Query<GetDatasetChangesRequestParams>,
+    db: web::Data<DB>,
+    project_api_key: ProjectApiKey,
+) -> ResponseResult {
+    let project_id = project_api_key.project_id;
+    let query = params.into_inner();
+
+    let Some(dataset) = datasets::get_dataset_by_name(&db.pool, &query.name, project_id).await?
+    else {
+        return Err(Error::api(
+            ErrorCode::DatasetNotFound,
+            format!("dataset {} not found", &query.name),
+        ));
+    };
+
+    let cursor = match &query.cursor {
+        Some(cursor) => {
+            ChangeCursor::parse(cursor).map_err(|e| Error::invalid_request(Some(&e.to_string())))?
+        }
+        None => ChangeCursor::start(),
+    };
+    if cursor.version > dataset.version {
+        return Err(Error::invalid_request(Some(&format!(
+            "Cursor is ahead of the dataset's version {}",
+            dataset.version
+        ))));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_CHANGES_LIMIT);
+    if !(1..=MAX_CHANGES_LIMIT).contains(&limit) {
+        return Err(Error::invalid_request(Some(&format!(
+            "Limit must be between 1 and {}",
+            MAX_CHANGES_LIMIT
+        ))));
+    }
+
+    let changes = dataset_versions::get_datapoint_changes_after(
+        &db.pool,
+        &dataset.id,
+        cursor.version,
+        cursor.datapoint_id,
+        limit,
+    )
+    .await?;
+    let next_cursor = changes
+        .last()
+        .map(|change| ChangeCursor {
+            version: change.version,
+            datapoint_id: Some(change.datapoint_id),
+        })
+        .unwrap_or(cursor);
+
+    let response = DatasetChangesResponse {
+        has_more: changes.len() as i64 == limit,
+        items: changes,
+        next_cursor: next_cursor.to_string(),
+        dataset_id: dataset.id,
+        dataset_version: dataset.version,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
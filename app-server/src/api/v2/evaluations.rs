@@ -0,0 +1,500 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use actix_web::{get, post, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    api::validation::ValidatedJson,
+    cache::Cache,
+    db::{self, evaluations::Evaluation, project_api_keys::ProjectApiKey, DB},
+    evaluations::{
+        self,
+        export::{export_evaluation_results, ExportFormat},
+        judge,
+        progress::{EvaluationProgressEvent, EvaluationProgressHub},
+        regression_gate::{self, RegressionGateResult, RegressionThresholds},
+        utils::{EvaluationDatapointResult, HumanEvaluator, ScoreValue},
+    },
+    language_model::LanguageModelRunner,
+    names::NameGenerator,
+    routes::{
+        error::{Error, ErrorCode},
+        types::ResponseResult,
+    },
+    traces::evaluators::get_stored_env,
+};
+
+/// Interval of the comments sent on an idle progress stream, so that proxies keep it open
+const PROGRESS_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Result of evaluating a single datapoint, `executorOutput` and `executorSpanId` from v1 are
+/// renamed to `output` and `spanId`
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationDatapoint {
+    data: Value,
+    target: Value,
+    output: Option<Value>,
+    #[serde(default)]
+    trace_id: Uuid,
+    /// Span of the executor run within the trace
+    #[serde(default)]
+    span_id: Uuid,
+    /// Numbers, booleans for pass or fail checks, or strings for categorical labels. May be
+    /// empty if the datapoint is scored by judges.
+    #[serde(default)]
+    scores: HashMap<String, ScoreValue>,
+    #[serde(default)]
+    human_evaluators: Vec<HumanEvaluator>,
+    /// When the datapoint was evaluated, defaults to when the server received it
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl From<EvaluationDatapoint> for EvaluationDatapointResult {
+    fn from(datapoint: EvaluationDatapoint) -> Self {
+        Self {
+            data: datapoint.data,
+            target: datapoint.target,
+            executor_output: datapoint.output,
+            trace_id: datapoint.trace_id,
+            scores: datapoint.scores,
+            human_evaluators: datapoint.human_evaluators,
+            executor_span_id: datapoint.span_id,
+            timestamp: datapoint.timestamp,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateEvaluationRequest {
+    /// If not set, a random name is generated
+    name: Option<String>,
+    /// Defaults to "default"
+    group_id: Option<String>,
+    /// May be empty, if the datapoints are added as they are evaluated
+    #[serde(default)]
+    datapoints: Vec<EvaluationDatapoint>,
+    /// Dataset the evaluation runs on, recorded with its version for reproducible comparisons
+    #[serde(default)]
+    dataset_id: Option<Uuid>,
+    /// Version of the dataset the datapoints were read at, defaults to its current version
+    #[serde(default)]
+    dataset_version: Option<i32>,
+    /// Names of the project's judge evaluators that score the datapoints on the server
+    #[serde(default)]
+    judges: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/v2/evaluations",
+    tag = "evaluations",
+    request_body = CreateEvaluationRequest,
+    responses(
+        (status = 200, description = "Created evaluation", body = Evaluation),
+    ),
+    security(("project_api_key" = []))
+)]
+#[post("evaluations")]
+async fn create_evaluation(
+    req: ValidatedJson<CreateEvaluationRequest>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    project_api_key: ProjectApiKey,
+    name_generator: web::Data<Arc<NameGenerator>>,
+    progress_hub: web::Data<Arc<EvaluationProgressHub>>,
+    cache: web::Data<Cache>,
+    language_model: web::Data<Arc<LanguageModelRunner>>,
+) -> ResponseResult {
+    let project_id = project_api_key.project_id;
+    let req = req.into_inner();
+    let analytics_store = analytics_store.as_ref().clone();
+    let db = db.into_inner();
+
+    let name = match req.name {
+        Some(name) => name,
+        None => name_generator.next().await,
+    };
+    let group_id = req.group_id.unwrap_or("default".to_string());
+    let mut points: Vec<EvaluationDatapointResult> =
+        req.datapoints.into_iter().map(Into::into).collect();
+
+    let dataset_version = match req.dataset_id {
+        Some(dataset_id) => {
+            let current_version =
+                db::datasets::get_dataset_version(&db.pool, &project_id, &dataset_id)
+                    .await?
+                    .ok_or_else(|| {
+                        Error::api(
+                            ErrorCode::DatasetNotFound,
+                            format!("dataset {} not found", dataset_id),
+                        )
+                    })?;
+            match req.dataset_version {
+                Some(version) if !(0..=current_version).contains(&version) => {
+                    return Err(Error::invalid_request(Some(&format!(
+                        "Dataset version must be between 0 and {}",
+                        current_version
+                    ))));
+                }
+                Some(version) => Some(version),
+                None => Some(current_version),
+            }
+        }
+        None if req.dataset_version.is_some() => {
+            return Err(Error::invalid_request(Some(
+                "Dataset version requires a dataset id",
+            )));
+        }
+        None => None,
+    };
+
+    score_with_judges(
+        db.clone(),
+        cache.into_inner(),
+        language_model.as_ref().clone(),
+        project_id,
+        &req.judges,
+        &mut points,
+    )
+    .await?;
+
+    let evaluation = evaluations::create_evaluation(
+        db,
+        analytics_store,
+        progress_hub.as_ref().clone(),
+        project_id,
+        name,
+        group_id,
+        req.dataset_id,
+        dataset_version,
+        points,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(evaluation))
+}
+
+async fn get_project_evaluation(
+    db: Arc<DB>,
+    project_id: Uuid,
+    evaluation_id: Uuid,
+) -> Result<Evaluation, Error> {
+    db::evaluations::get_evaluation(db, project_id, evaluation_id)
+        .await
+        .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => {
+                Error::api(ErrorCode::EvaluationNotFound, "Evaluation not found")
+            }
+            _ => e.into(),
+        })
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AddEvaluationDatapointsRequest {
+    datapoints: Vec<EvaluationDatapoint>,
+    /// Names of the project's judge evaluators that score the datapoints on the server
+    #[serde(default)]
+    judges: Vec<String>,
+}
+
+/// Adds the scores of the named judges to the datapoints
+async fn score_with_judges(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    language_model: Arc<LanguageModelRunner>,
+    project_id: Uuid,
+    names: &[String],
+    points: &mut [EvaluationDatapointResult],
+) -> Result<(), Error> {
+    if names.is_empty() || points.is_empty() {
+        return Ok(());
+    }
+    let judges =
+        db::judge_evaluators::get_judge_evaluators_by_names(&db.pool, &project_id, names).await?;
+    let mut unknown = names
+        .iter()
+        .filter(|name| !judges.iter().any(|judge| &judge.name == *name))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        unknown.sort();
+        unknown.dedup();
+        return Err(Error::invalid_request(Some(&format!(
+            "Judge evaluators not found: {}",
+            unknown.join(", ")
+        ))));
+    }
+
+    let env = get_stored_env(db.clone(), project_id).await?;
+    let missing = judge::missing_env_vars(&judges, &env);
+    if !missing.is_empty() {
+        return Err(Error::invalid_request(Some(&format!(
+            "The project has no provider api keys for the judges: {}",
+            missing.join(", ")
+        ))));
+    }
+
+    judge::score_with_judges(db, cache, language_model, &env, &judges, points).await;
+    Ok(())
+}
+
+/// Adds datapoints to an evaluation as they are evaluated, which are sent to the watchers of
+/// its progress stream
+#[utoipa::path(
+    post,
+    path = "/v2/evaluations/{evaluation_id}/datapoints",
+    tag = "evaluations",
+    params(("evaluation_id" = Uuid, Path, description = "Evaluation id")),
+    request_body = AddEvaluationDatapointsRequest,
+    responses(
+        (status = 200, description = "Datapoints have been added"),
+        (status = 404, description = "Evaluation not found"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[post("evaluations/{evaluation_id}/datapoints")]
+async fn add_evaluation_datapoints(
+    path: web::Path<Uuid>,
+    req: ValidatedJson<AddEvaluationDatapointsRequest>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    project_api_key: ProjectApiKey,
+    progress_hub: web::Data<Arc<EvaluationProgressHub>>,
+    cache: web::Data<Cache>,
+    language_model: web::Data<Arc<LanguageModelRunner>>,
+) -> ResponseResult {
+    let evaluation_id = path.into_inner();
+    let req = req.into_inner();
+    let db = db.into_inner();
+    let evaluation =
+        get_project_evaluation(db.clone(), project_api_key.project_id, evaluation_id).await?;
+
+    if !req.datapoints.is_empty() {
+        let mut points: Vec<EvaluationDatapointResult> =
+            req.datapoints.into_iter().map(Into::into).collect();
+        score_with_judges(
+            db.clone(),
+            cache.into_inner(),
+            language_model.as_ref().clone(),
+            project_api_key.project_id,
+            &req.judges,
+            &mut points,
+        )
+        .await?;
+        evaluations::add_evaluation_results(
+            db,
+            analytics_store.as_ref().clone(),
+            progress_hub.as_ref().clone(),
+            &evaluation,
+            points,
+        )
+        .await?;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn progress_event_to_sse(event: &EvaluationProgressEvent) -> web::Bytes {
+    let name = match event {
+        EvaluationProgressEvent::Snapshot(_) => "snapshot",
+        EvaluationProgressEvent::Datapoint(_) => "datapoint",
+    };
+    let data = serde_json::to_string(event).unwrap();
+    web::Bytes::from(format!("event: {name}\ndata: {data}\n\n"))
+}
+
+/// Server-sent events with the progress of an evaluation: a `snapshot` event with the
+/// datapoint count and average scores so far, then a `datapoint` event with the updated
+/// progress for each datapoint added to the evaluation. The stream is open until the client
+/// closes it.
+#[utoipa::path(
+    get,
+    path = "/v2/evaluations/{evaluation_id}/progress",
+    tag = "evaluations",
+    params(("evaluation_id" = Uuid, Path, description = "Evaluation id")),
+    responses(
+        (status = 200, description = "Stream of server-sent events"),
+        (status = 404, description = "Evaluation not found"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[get("evaluations/{evaluation_id}/progress")]
+async fn stream_evaluation_progress(
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    project_api_key: ProjectApiKey,
+    progress_hub: web::Data<Arc<EvaluationProgressHub>>,
+) -> ResponseResult {
+    let evaluation_id = path.into_inner();
+    let db = db.into_inner();
+    get_project_evaluation(db.clone(), project_api_key.project_id, evaluation_id).await?;
+
+    let progress_hub = progress_hub.as_ref().clone();
+    let (progress, mut receiver) = progress_hub.subscribe(&db.pool, evaluation_id).await?;
+
+    let stream = async_stream::stream! {
+        yield Ok::<_, actix_web::Error>(progress_event_to_sse(
+            &EvaluationProgressEvent::Snapshot(progress),
+        ));
+
+        let mut keepalive = tokio::time::interval(PROGRESS_KEEPALIVE_INTERVAL);
+        keepalive.tick().await;
+        loop {
+            tokio::select! {
+                event = receiver.recv() => match event {
+                    Ok(event) => yield Ok(progress_event_to_sse(&event)),
+                    // The missed datapoints are included in the current progress
+                    Err(RecvError::Lagged(_)) => {
+                        if let Some(progress) = progress_hub.progress(&evaluation_id) {
+                            yield Ok(progress_event_to_sse(
+                                &EvaluationProgressEvent::Snapshot(progress),
+                            ));
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = keepalive.tick() => yield Ok(web::Bytes::from_static(b": keepalive\n\n")),
+            }
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .streaming(stream))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RegressionGateRequest {
+    /// Defaults to the baseline pinned for the evaluation's group
+    #[serde(default)]
+    baseline_evaluation_id: Option<Uuid>,
+    /// Scores to compare, all numeric and boolean scores of the baseline if empty
+    #[serde(default)]
+    score_names: Vec<String>,
+    #[serde(default)]
+    thresholds: RegressionThresholds,
+}
+
+/// Compares the evaluation's scores with a baseline evaluation of the same group. `passed` is
+/// false if a score regressed beyond the thresholds, so that CI jobs can fail on it.
+#[utoipa::path(
+    post,
+    path = "/v2/evaluations/{evaluation_id}/regression-gate",
+    tag = "evaluations",
+    params(("evaluation_id" = Uuid, Path, description = "Evaluation id")),
+    request_body = RegressionGateRequest,
+    responses(
+        (status = 200, description = "Result of the gate", body = RegressionGateResult),
+        (status = 400, description = "No baseline, or the baseline is of another group"),
+        (status = 404, description = "Evaluation not found"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[post("evaluations/{evaluation_id}/regression-gate")]
+async fn check_regression_gate(
+    path: web::Path<Uuid>,
+    req: ValidatedJson<RegressionGateRequest>,
+    db: web::Data<DB>,
+    project_api_key: ProjectApiKey,
+) -> ResponseResult {
+    let evaluation_id = path.into_inner();
+    let project_id = project_api_key.project_id;
+    let req = req.into_inner();
+    let db = db.into_inner();
+    let thresholds = &req.thresholds;
+    if thresholds.max_absolute_drop.is_some_and(|drop| drop < 0.0)
+        || thresholds
+            .max_relative_drop_percent
+            .is_some_and(|drop| drop < 0.0)
+    {
+        return Err(Error::invalid_request(Some(
+            "Thresholds must not be negative",
+        )));
+    }
+
+    let evaluation = get_project_evaluation(db.clone(), project_id, evaluation_id).await?;
+    let baseline_evaluation_id = match req.baseline_evaluation_id {
+        Some(id) => id,
+        None => db::evaluations::get_group_baseline(&db.pool, &project_id, &evaluation.group_id)
+            .await?
+            .ok_or_else(|| {
+                Error::invalid_request(Some(&format!(
+                    "No baseline evaluation is pinned for group {}",
+                    evaluation.group_id
+                )))
+            })?,
+    };
+    let baseline = get_project_evaluation(db.clone(), project_id, baseline_evaluation_id).await?;
+    if baseline.group_id != evaluation.group_id {
+        return Err(Error::invalid_request(Some(
+            "Only evaluations of the same group can be compared",
+        )));
+    }
+
+    let result = regression_gate::run_regression_gate(
+        &db.pool,
+        &evaluation,
+        &baseline,
+        thresholds,
+        req.score_names,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportEvaluationResultsQuery {
+    /// `csv`, `jsonl`, `parquet` or `arrow` for an Arrow IPC stream
+    #[serde(default)]
+    #[param(value_type = Option<String>)]
+    format: ExportFormat,
+}
+
+/// Results of the evaluation with their scores, in the order they were added. Scores are typed
+/// columns in the Arrow and Parquet formats.
+#[utoipa::path(
+    get,
+    path = "/v2/evaluations/{evaluation_id}/results",
+    tag = "evaluations",
+    params(
+        ("evaluation_id" = Uuid, Path, description = "Evaluation id"),
+        ExportEvaluationResultsQuery,
+    ),
+    responses(
+        (status = 200, description = "Results in the requested format"),
+        (status = 404, description = "Evaluation not found"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[get("evaluations/{evaluation_id}/results")]
+async fn export_evaluation(
+    path: web::Path<Uuid>,
+    query: web::Query<ExportEvaluationResultsQuery>,
+    db: web::Data<DB>,
+    project_api_key: ProjectApiKey,
+) -> ResponseResult {
+    let evaluation_id = path.into_inner();
+    let format = query.into_inner().format;
+    let db = db.into_inner();
+    get_project_evaluation(db.clone(), project_api_key.project_id, evaluation_id).await?;
+    let score_names = db::evaluations::get_evaluation_score_names(&db.pool, evaluation_id).await?;
+
+    let stream = export_evaluation_results(db.pool.clone(), evaluation_id, score_names, format);
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .streaming(stream))
+}
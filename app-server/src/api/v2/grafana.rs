@@ -0,0 +1,419 @@
+//! Data source for Grafana's JSON API plugins (`simpod-json-datasource`, and the older
+//! SimpleJSON), so that project metrics and scores can be charted in Grafana. The data source
+//! URL is `<api url>/v2/grafana`, with the project api key sent as a bearer token in the
+//! `Authorization` header.
+//!
+//! Metrics are `trace_count`, `trace_latency_seconds`, `total_tokens` and `cost_usd`, and the
+//! average of each score as `span_score:<name>` and `evaluation_score:<name>`.
+
+use std::sync::Arc;
+
+use actix_web::{get, post, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    ch::{
+        downsampling::{downsample, MAX_CHART_POINTS},
+        modifiers::GroupByInterval,
+        Aggregation, MetricTimeValue,
+    },
+    db::project_api_keys::ProjectApiKey,
+    routes::{error::Error, types::ResponseResult},
+};
+
+const SPAN_SCORE_PREFIX: &str = "span_score:";
+const EVALUATION_SCORE_PREFIX: &str = "evaluation_score:";
+
+#[derive(Debug, PartialEq)]
+enum GrafanaMetric {
+    TraceCount,
+    TraceLatencySeconds,
+    TotalTokens,
+    CostUsd,
+    SpanScore(String),
+    EvaluationScore(String),
+}
+
+impl GrafanaMetric {
+    const TRACE_METRICS: [&'static str; 4] = [
+        "trace_count",
+        "trace_latency_seconds",
+        "total_tokens",
+        "cost_usd",
+    ];
+
+    fn parse(target: &str) -> Option<Self> {
+        let target = target.trim();
+        if let Some(name) = target.strip_prefix(SPAN_SCORE_PREFIX) {
+            return Some(GrafanaMetric::SpanScore(name.to_string()));
+        }
+        if let Some(name) = target.strip_prefix(EVALUATION_SCORE_PREFIX) {
+            return Some(GrafanaMetric::EvaluationScore(name.to_string()));
+        }
+        match target {
+            "trace_count" => Some(GrafanaMetric::TraceCount),
+            "trace_latency_seconds" => Some(GrafanaMetric::TraceLatencySeconds),
+            "total_tokens" => Some(GrafanaMetric::TotalTokens),
+            "cost_usd" => Some(GrafanaMetric::CostUsd),
+            _ => None,
+        }
+    }
+}
+
+/// The finest supported interval that is at least Grafana's interval, coarsened so that the
+/// range has at most `max_points` buckets
+fn group_by_interval(interval_ms: i64, range_seconds: i64, max_points: usize) -> GroupByInterval {
+    let requested = match interval_ms / 1000 {
+        seconds if seconds <= 60 => GroupByInterval::Minute,
+        seconds if seconds <= 60 * 60 => GroupByInterval::Hour,
+        _ => GroupByInterval::Day,
+    };
+    requested.coarsen_for_range(range_seconds, max_points)
+}
+
+fn to_f64_series(values: Vec<MetricTimeValue<i64>>) -> Vec<MetricTimeValue<f64>> {
+    values
+        .into_iter()
+        .map(|point| MetricTimeValue {
+            time: point.time,
+            value: point.value as f64,
+        })
+        .collect()
+}
+
+async fn get_metric_series(
+    analytics_store: &Arc<dyn AnalyticsStore>,
+    metric: GrafanaMetric,
+    project_id: Uuid,
+    group_by_interval: GroupByInterval,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<MetricTimeValue<f64>>> {
+    let values = match metric {
+        GrafanaMetric::TraceCount => to_f64_series(
+            analytics_store
+                .get_total_trace_count_metrics_absolute(
+                    group_by_interval,
+                    project_id,
+                    start_time,
+                    end_time,
+                )
+                .await?,
+        ),
+        GrafanaMetric::TraceLatencySeconds => {
+            analytics_store
+                .get_trace_latency_seconds_metrics_absolute(
+                    group_by_interval,
+                    project_id,
+                    start_time,
+                    end_time,
+                    Aggregation::Average,
+                )
+                .await?
+        }
+        GrafanaMetric::TotalTokens => to_f64_series(
+            analytics_store
+                .get_total_token_count_metrics_absolute(
+                    group_by_interval,
+                    project_id,
+                    start_time,
+                    end_time,
+                    Aggregation::Total,
+                )
+                .await?,
+        ),
+        GrafanaMetric::CostUsd => {
+            analytics_store
+                .get_cost_usd_metrics_absolute(
+                    group_by_interval,
+                    project_id,
+                    start_time,
+                    end_time,
+                    Aggregation::Total,
+                )
+                .await?
+        }
+        GrafanaMetric::SpanScore(name) => {
+            analytics_store
+                .get_span_score_trend(group_by_interval, project_id, name, start_time, end_time)
+                .await?
+        }
+        GrafanaMetric::EvaluationScore(name) => {
+            analytics_store
+                .get_evaluation_score_time_series(
+                    group_by_interval,
+                    project_id,
+                    name,
+                    start_time,
+                    end_time,
+                )
+                .await?
+        }
+    };
+    Ok(values)
+}
+
+/// Connection test of the data source
+#[utoipa::path(
+    get,
+    path = "/v2/grafana",
+    tag = "grafana",
+    responses((status = 200, description = "The api key is valid")),
+    security(("project_api_key" = []))
+)]
+#[get("grafana")]
+pub async fn grafana_health(_project_api_key: ProjectApiKey) -> ResponseResult {
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GrafanaSearchRequest {
+    /// Part of the metric name, SimpleJSON's `target` or the JSON plugin's `metric`
+    #[serde(default, alias = "metric")]
+    target: String,
+}
+
+#[derive(Serialize)]
+struct GrafanaMetricOption {
+    label: String,
+    value: String,
+}
+
+async fn get_metric_names(
+    analytics_store: &Arc<dyn AnalyticsStore>,
+    project_id: Uuid,
+    filter: &str,
+) -> anyhow::Result<Vec<String>> {
+    let span_scores = analytics_store.get_span_score_names(project_id).await?;
+    let evaluation_scores = analytics_store
+        .get_numeric_evaluation_score_names(project_id)
+        .await?;
+
+    let filter = filter.trim().to_lowercase();
+    let names = GrafanaMetric::TRACE_METRICS
+        .iter()
+        .map(|name| name.to_string())
+        .chain(
+            span_scores
+                .into_iter()
+                .map(|name| format!("{SPAN_SCORE_PREFIX}{name}")),
+        )
+        .chain(
+            evaluation_scores
+                .into_iter()
+                .map(|name| format!("{EVALUATION_SCORE_PREFIX}{name}")),
+        )
+        .filter(|name| name.to_lowercase().contains(&filter))
+        .collect();
+    Ok(names)
+}
+
+/// Metric names, for SimpleJSON
+#[utoipa::path(
+    post,
+    path = "/v2/grafana/search",
+    tag = "grafana",
+    request_body = GrafanaSearchRequest,
+    responses(
+        (status = 200, description = "Names of the metrics that contain the target", body = Vec<String>),
+    ),
+    security(("project_api_key" = []))
+)]
+#[post("grafana/search")]
+pub async fn grafana_search(
+    req: web::Json<GrafanaSearchRequest>,
+    project_api_key: ProjectApiKey,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let names = get_metric_names(
+        analytics_store.get_ref(),
+        project_api_key.project_id,
+        &req.target,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(names))
+}
+
+/// Metric names with labels, for the JSON plugin
+#[utoipa::path(
+    post,
+    path = "/v2/grafana/metrics",
+    tag = "grafana",
+    request_body = GrafanaSearchRequest,
+    responses((status = 200, description = "Metrics that contain the metric filter")),
+    security(("project_api_key" = []))
+)]
+#[post("grafana/metrics")]
+pub async fn grafana_metrics(
+    req: web::Json<GrafanaSearchRequest>,
+    project_api_key: ProjectApiKey,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let options = get_metric_names(
+        analytics_store.get_ref(),
+        project_api_key.project_id,
+        &req.target,
+    )
+    .await?
+    .into_iter()
+    .map(|name| GrafanaMetricOption {
+        label: name.clone(),
+        value: name,
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(options))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GrafanaRange {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GrafanaTarget {
+    /// Metric name, see `grafana_search`
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default, rename = "refId")]
+    ref_id: Option<String>,
+    #[serde(default)]
+    hide: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GrafanaQueryRequest {
+    range: GrafanaRange,
+    #[serde(default)]
+    interval_ms: i64,
+    #[serde(default)]
+    max_data_points: Option<usize>,
+    targets: Vec<GrafanaTarget>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GrafanaTimeSeries {
+    target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ref_id: Option<String>,
+    /// Pairs of value and unix time in milliseconds
+    datapoints: Vec<(f64, i64)>,
+}
+
+/// Time series of the targets in the range
+#[utoipa::path(
+    post,
+    path = "/v2/grafana/query",
+    tag = "grafana",
+    request_body = GrafanaQueryRequest,
+    responses(
+        (status = 200, description = "One time series per target, with [value, unix ms] datapoints"),
+        (status = 400, description = "Unknown metric or invalid range"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[post("grafana/query")]
+pub async fn grafana_query(
+    req: web::Json<GrafanaQueryRequest>,
+    project_api_key: ProjectApiKey,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let req = req.into_inner();
+    let (start_time, end_time) = (req.range.from, req.range.to);
+    if start_time >= end_time {
+        return Err(Error::invalid_request(Some(
+            "range.from must be before range.to",
+        )));
+    }
+    let max_points = req
+        .max_data_points
+        .unwrap_or(MAX_CHART_POINTS)
+        .clamp(1, MAX_CHART_POINTS);
+    let group_by_interval = group_by_interval(
+        req.interval_ms,
+        (end_time - start_time).num_seconds(),
+        max_points,
+    );
+
+    let mut series = Vec::with_capacity(req.targets.len());
+    for target in req.targets {
+        let Some(name) = target
+            .target
+            .filter(|name| !target.hide && !name.is_empty())
+        else {
+            continue;
+        };
+        let Some(metric) = GrafanaMetric::parse(&name) else {
+            return Err(Error::invalid_request(Some(&format!(
+                "Unknown metric: {}",
+                name
+            ))));
+        };
+        let values = get_metric_series(
+            analytics_store.get_ref(),
+            metric,
+            project_api_key.project_id,
+            group_by_interval,
+            start_time,
+            end_time,
+        )
+        .await?;
+        let datapoints = downsample(values, max_points)
+            .into_iter()
+            .map(|point| (point.value, point.time as i64 * 1000))
+            .collect();
+        series.push(GrafanaTimeSeries {
+            target: name,
+            ref_id: target.ref_id,
+            datapoints,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(series))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metric() {
+        assert_eq!(
+            GrafanaMetric::parse("cost_usd"),
+            Some(GrafanaMetric::CostUsd)
+        );
+        assert_eq!(
+            GrafanaMetric::parse("span_score:helpfulness"),
+            Some(GrafanaMetric::SpanScore("helpfulness".to_string()))
+        );
+        assert_eq!(
+            GrafanaMetric::parse("evaluation_score:accuracy"),
+            Some(GrafanaMetric::EvaluationScore("accuracy".to_string()))
+        );
+        assert_eq!(GrafanaMetric::parse("latency"), None);
+    }
+
+    #[test]
+    fn test_group_by_interval() {
+        assert!(matches!(
+            group_by_interval(30_000, 6 * 60 * 60, MAX_CHART_POINTS),
+            GroupByInterval::Minute
+        ));
+        assert!(matches!(
+            group_by_interval(30_000, 14 * 24 * 60 * 60, 500),
+            GroupByInterval::Hour
+        ));
+        assert!(matches!(
+            group_by_interval(6 * 60 * 60 * 1000, 60 * 60, MAX_CHART_POINTS),
+            GroupByInterval::Day
+        ));
+    }
+}
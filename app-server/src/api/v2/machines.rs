@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        machines::{self, Machine, MachineStatus},
+        project_api_keys::ProjectApiKey,
+        DB,
+    },
+    machine_manager::{self, MachineManager},
+    routes::{
+        error::{Error, ErrorCode},
+        types::ResponseResult,
+    },
+};
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MachineResponse {
+    id: Uuid,
+    status: MachineStatus,
+    /// DevTools endpoint of the machine's browser, set once the machine is running
+    url: Option<String>,
+    /// Why the machine failed
+    error: Option<String>,
+    /// Position among all queued machines, set while the machine is queued
+    queue_position: Option<i64>,
+}
+
+async fn machine_response(db: &DB, machine: Machine) -> anyhow::Result<MachineResponse> {
+    let queue_position = match machine.status {
+        MachineStatus::QUEUED => machines::get_queue_position(&db.pool, &machine.id).await?,
+        _ => None,
+    };
+
+    Ok(MachineResponse {
+        id: machine.id,
+        status: machine.status,
+        url: machine.url,
+        error: machine.error,
+        queue_position,
+    })
+}
+
+fn machine_not_found(id: &Uuid) -> Error {
+    Error::api(
+        ErrorCode::MachineNotFound,
+        format!("machine {} not found", id),
+    )
+}
+
+/// Request a browser machine. The machine is queued until the pool has capacity and the project
+/// is under its machine limit, poll it until it's running.
+#[utoipa::path(
+    post,
+    path = "/v2/machines",
+    tag = "machines",
+    responses((status = 202, description = "Machine is queued", body = MachineResponse)),
+    security(("project_api_key" = []))
+)]
+#[post("machines")]
+pub async fn create_machine(project_api_key: ProjectApiKey, db: web::Data<DB>) -> ResponseResult {
+    let machine = machines::create_machine(&db.pool, &project_api_key.project_id).await?;
+    let response = machine_response(&db, machine).await?;
+
+    Ok(HttpResponse::Accepted().json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v2/machines/{machine_id}",
+    tag = "machines",
+    params(("machine_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Machine", body = MachineResponse),
+        (status = 404, description = "Machine not found"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[get("machines/{machine_id}")]
+pub async fn get_machine(
+    path: web::Path<Uuid>,
+    project_api_key: ProjectApiKey,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let machine_id = path.into_inner();
+    let machine = machines::get_machine(&db.pool, &project_api_key.project_id, &machine_id)
+        .await?
+        .ok_or_else(|| machine_not_found(&machine_id))?;
+    let response = machine_response(&db, machine).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Release the machine, or leave the queue if it's still queued
+#[utoipa::path(
+    delete,
+    path = "/v2/machines/{machine_id}",
+    tag = "machines",
+    params(("machine_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Machine is terminated", body = MachineResponse),
+        (status = 404, description = "Machine not found"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[delete("machines/{machine_id}")]
+pub async fn terminate_machine(
+    path: web::Path<Uuid>,
+    project_api_key: ProjectApiKey,
+    db: web::Data<DB>,
+    machine_manager: web::Data<Arc<dyn MachineManager>>,
+) -> ResponseResult {
+    let machine_id = path.into_inner();
+    let machine = machine_manager::terminate_machine(
+        &db,
+        machine_manager.as_ref().as_ref(),
+        &project_api_key.project_id,
+        &machine_id,
+    )
+    .await?
+    .ok_or_else(|| machine_not_found(&machine_id))?;
+    let response = machine_response(&db, machine).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
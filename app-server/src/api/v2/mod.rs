@@ -0,0 +1,14 @@
+//! Second version of the public API.
+//!
+//! Endpoints whose payloads did not change are registered from `v1` under both prefixes. Handlers
+//! here cover the changed payloads and convert them to the shapes used internally, so that `v1`
+//! keeps working unchanged until its sunset, see [`super::deprecation`], and the endpoints that
+//! only exist in `v2`.
+
+pub mod agent_runs;
+pub mod browser_sessions;
+pub mod datasets;
+pub mod evaluations;
+pub mod grafana;
+pub mod machines;
+pub mod traces;
@@ -0,0 +1,88 @@
+use actix_web::{get, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    db::{field_visibility::FieldVisibility, project_api_keys::ProjectApiKey, DB},
+    evaluations::export::ExportFormat,
+    routes::{error::Error, types::ResponseResult},
+    traces::{
+        export::export_spans,
+        ingestion_lag::{self, IngestionStatus},
+    },
+};
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(rename_all = "camelCase")]
+pub struct ExportSpansQuery {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    /// `csv`, `jsonl`, `parquet` or `arrow` for an Arrow IPC stream, which can be read with
+    /// `pyarrow.ipc.open_stream` or `polars.read_ipc_stream`
+    #[serde(default)]
+    #[param(value_type = Option<String>)]
+    format: ExportFormat,
+}
+
+/// Spans that started in the period, oldest first. The response is streamed, so that large
+/// periods can be read in one request.
+#[utoipa::path(
+    get,
+    path = "/v2/spans",
+    tag = "traces",
+    params(ExportSpansQuery),
+    responses(
+        (status = 200, description = "Spans in the requested format"),
+        (status = 400, description = "Invalid period"),
+    ),
+    security(("project_api_key" = []))
+)]
+#[get("spans")]
+pub async fn export_project_spans(
+    query: web::Query<ExportSpansQuery>,
+    project_api_key: ProjectApiKey,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let query = query.into_inner();
+    if query.start_time >= query.end_time {
+        return Err(Error::invalid_request(Some(
+            "startTime must be before endTime",
+        )));
+    }
+
+    let stream = export_spans(
+        db.pool.clone(),
+        project_api_key.project_id,
+        query.start_time,
+        query.end_time,
+        FieldVisibility::ALL,
+        query.format,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(query.format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"spans.{}\"",
+                query.format.extension()
+            ),
+        ))
+        .streaming(stream))
+}
+
+/// Spans accepted for the project that are not yet queryable, and how long the last spans took
+/// to become queryable. Explains why a trace that was just sent isn't visible yet.
+#[utoipa::path(
+    get,
+    path = "/v2/ingestion-status",
+    tag = "traces",
+    responses((status = 200, description = "Ingestion lag of the project", body = IngestionStatus)),
+    security(("project_api_key" = []))
+)]
+#[get("ingestion-status")]
+pub async fn get_ingestion_status(project_api_key: ProjectApiKey) -> ResponseResult {
+    Ok(HttpResponse::Ok().json(ingestion_lag::ingestion_status(&project_api_key.project_id)))
+}
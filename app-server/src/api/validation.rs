@@ -0,0 +1,203 @@
+//! Field-level errors for malformed ingestion payloads.
+//!
+//! Instead of an opaque deserialization failure, the response says where the payload is wrong,
+//! as a JSON pointer, and what was expected there:
+//!
+//! ```json
+//! {
+//!     "error_code": "INVALID_PAYLOAD",
+//!     "error_message": {
+//!         "pointer": "/points/0/humanEvaluators",
+//!         "expected": "a sequence",
+//!         "message": "invalid type: string \"high\", expected a sequence"
+//!     },
+//!     "retryable": false
+//! }
+//! ```
+
+use std::ops::Deref;
+
+use actix_web::{dev::Payload, web::Bytes, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_path_to_error::{Path, Segment};
+
+use crate::routes::error::Error;
+
+const PROTOBUF_ERROR_PREFIX: &str = "failed to decode Protobuf message: ";
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PayloadFieldError {
+    pub pointer: String,
+    pub expected: Option<String>,
+    pub message: String,
+}
+
+impl From<PayloadFieldError> for Error {
+    fn from(error: PayloadFieldError) -> Self {
+        Error::invalid_payload(serde_json::to_value(error).unwrap_or_default())
+    }
+}
+
+/// Same as `web::Json`, but deserialization errors are reported as [`PayloadFieldError`]
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for ValidatedJson<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        // Respects the `PayloadConfig` limit of the scope
+        let body = Bytes::from_request(req, payload);
+        Box::pin(async move {
+            let body = body.await?;
+            let value = parse_json(&body).map_err(Error::from)?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
+
+pub fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, PayloadFieldError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(body);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let pointer = json_pointer(e.path());
+        json_field_error(pointer, e.into_inner())
+    })
+}
+
+fn json_field_error(mut pointer: String, error: serde_json::Error) -> PayloadFieldError {
+    let message = error.to_string();
+    // Line and column are not useful for SDK payloads, the pointer is
+    let message = match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    };
+    // The path of a missing field ends at the object that should contain it
+    if let Some(field) = message
+        .strip_prefix("missing field `")
+        .and_then(|field| field.strip_suffix('`'))
+    {
+        pointer = format!("{}/{}", pointer, escape_pointer_token(field));
+    }
+    let expected = message
+        .split_once(", expected ")
+        .map(|(_, expected)| expected.to_string());
+
+    PayloadFieldError {
+        pointer,
+        expected,
+        message,
+    }
+}
+
+fn json_pointer(path: &Path) -> String {
+    path.iter()
+        .filter_map(|segment| match segment {
+            Segment::Seq { index } => Some(index.to_string()),
+            Segment::Map { key } => Some(escape_pointer_token(key)),
+            Segment::Enum { .. } | Segment::Unknown => None,
+        })
+        .map(|token| format!("/{token}"))
+        .collect()
+}
+
+fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Converts a protobuf decoding error, e.g. of an OTLP export request, to a field error.
+///
+/// prost only reports the message and field names on the way to the invalid field, so the
+/// pointer has no repeated field indices.
+pub fn protobuf_field_error(error: prost::DecodeError) -> PayloadFieldError {
+    let message = error.to_string();
+    let stack = message
+        .strip_prefix(PROTOBUF_ERROR_PREFIX)
+        .unwrap_or(&message);
+    // The stack is a list of `Message.field: ` prefixes, the description may contain ": " too
+    let mut pointer = String::new();
+    let mut description = stack;
+    while let Some((segment, rest)) = description.split_once(": ") {
+        match segment.split_once('.') {
+            Some((_, field)) if !segment.contains(' ') => {
+                pointer.push('/');
+                pointer.push_str(&escape_pointer_token(field));
+                description = rest;
+            }
+            _ => break,
+        }
+    }
+
+    PayloadFieldError {
+        pointer,
+        expected: None,
+        message: description.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use prost::Message;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::opentelemetry::opentelemetry::proto::collector::trace::v1::ExportTraceServiceRequest;
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Point {
+        name: String,
+        scores: HashMap<String, f64>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Request {
+        points: Vec<Point>,
+    }
+
+    #[test]
+    fn test_invalid_type() {
+        let body = br#"{"points": [{"name": "a", "scores": {"accuracy": "high"}}]}"#;
+        let error = parse_json::<Request>(body).unwrap_err();
+        assert_eq!(error.pointer, "/points/0/scores/accuracy");
+        assert_eq!(error.expected.as_deref(), Some("f64"));
+        assert_eq!(error.message, "invalid type: string \"high\", expected f64");
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let body = br#"{"points": [{"name": "a", "scores": {}}, {"scores": {}}]}"#;
+        let error = parse_json::<Request>(body).unwrap_err();
+        assert_eq!(error.pointer, "/points/1/name");
+        assert_eq!(error.expected, None);
+        assert_eq!(error.message, "missing field `name`");
+    }
+
+    #[test]
+    fn test_protobuf_error() {
+        // `resource_spans` holding a `resource` that is longer than the payload
+        let body: &[u8] = &[0x0a, 0x02, 0x0a, 0x05];
+        let error = ExportTraceServiceRequest::decode(body).unwrap_err();
+        let error = protobuf_field_error(error);
+        assert!(error.pointer.starts_with("/resource_spans"));
+        assert!(!error.message.is_empty());
+    }
+}
@@ -1,3 +1,6 @@
+pub mod session_tokens;
+pub mod sso;
+
 use anyhow::Result;
 use std::env;
 use std::future::{ready, Ready};
@@ -13,9 +16,23 @@ use actix_web_httpauth::extractors::AuthenticationError;
 
 use crate::api::utils::get_api_key_from_raw_value;
 use crate::cache::Cache;
+use crate::db::personal_access_tokens::{use_token, PersonalAccessToken, TokenScope};
 use crate::db::project_api_keys::ProjectApiKey;
-use crate::db::user::{get_user_from_api_key, User};
+use crate::db::scim::get_organization_id_by_token_hash;
+use crate::db::ui_sessions::get_active_session_expiry;
+use crate::db::user::{get_user_from_api_key, get_user_with_projects, User};
 use crate::db::DB;
+use crate::network::ip_allowlist::{client_ip, is_ip_allowed_for_project};
+use crate::routes::api_keys::hash_api_key;
+use crate::routes::personal_access_tokens::PERSONAL_ACCESS_TOKEN_PREFIX;
+use session_tokens::{SessionTokenClaims, SESSION_TOKEN_PREFIX};
+use uuid::Uuid;
+
+/// Organization that the SCIM bearer token of the request belongs to
+#[derive(Clone)]
+pub struct ScimOrganization {
+    pub id: Uuid,
+}
 
 impl FromRequest for User {
     type Error = Error;
@@ -41,6 +58,18 @@ impl FromRequest for ProjectApiKey {
     }
 }
 
+impl FromRequest for ScimOrganization {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        match req.extensions().get::<Self>().cloned() {
+            Some(organization) => return ready(Ok(organization)),
+            None => return ready(Err(actix_web::error::ParseError::Incomplete.into())),
+        };
+    }
+}
+
 pub async fn validator(
     req: ServiceRequest,
     credentials: BearerAuth,
@@ -62,7 +91,22 @@ pub async fn validator(
         .into_inner();
 
     match validate_token(&db, cache.clone(), credentials.token().to_string()).await {
-        Ok(user) => {
+        Ok((user, credential)) => {
+            match credential {
+                UserCredential::PersonalAccessToken(token) => {
+                    if token.scope == TokenScope::Read && !req.method().is_safe() {
+                        return Err((
+                            actix_web::error::ErrorForbidden("Token has read-only scope"),
+                            req,
+                        ));
+                    }
+                    req.extensions_mut().insert(token);
+                }
+                UserCredential::SessionToken(claims) => {
+                    req.extensions_mut().insert(claims);
+                }
+                UserCredential::ApiKey => {}
+            }
             req.extensions_mut().insert(user);
             Ok(req)
         }
@@ -73,8 +117,43 @@ pub async fn validator(
     }
 }
 
-async fn validate_token(db: &DB, cache: Arc<Cache>, token: String) -> Result<User> {
-    get_user_from_api_key(&db.pool, token, cache).await
+/// True if the user authenticated with their long-lived API key, rather than with
+/// a derived credential, such as a personal access token or a session token
+pub fn authenticated_with_api_key(req: &HttpRequest) -> bool {
+    let extensions = req.extensions();
+    extensions.get::<PersonalAccessToken>().is_none()
+        && extensions.get::<SessionTokenClaims>().is_none()
+}
+
+/// The kind of credential a user authenticated with
+enum UserCredential {
+    ApiKey,
+    PersonalAccessToken(PersonalAccessToken),
+    SessionToken(SessionTokenClaims),
+}
+
+async fn validate_token(
+    db: &DB,
+    cache: Arc<Cache>,
+    token: String,
+) -> Result<(User, UserCredential)> {
+    if token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX) {
+        let personal_access_token = use_token(&db.pool, &hash_api_key(&token))
+            .await?
+            .ok_or(anyhow::anyhow!("Personal access token is invalid or expired"))?;
+        let user = get_user_with_projects(&db.pool, &personal_access_token.user_id).await?;
+        return Ok((user, UserCredential::PersonalAccessToken(personal_access_token)));
+    }
+    if token.starts_with(SESSION_TOKEN_PREFIX) {
+        let claims = session_tokens::verify_token(&token)?;
+        get_active_session_expiry(&db.pool, &claims.session_id, &claims.user_id)
+            .await?
+            .ok_or(anyhow::anyhow!("Session is revoked or expired"))?;
+        let user = get_user_with_projects(&db.pool, &claims.user_id).await?;
+        return Ok((user, UserCredential::SessionToken(claims)));
+    }
+    let user = get_user_from_api_key(&db.pool, token, cache).await?;
+    Ok((user, UserCredential::ApiKey))
 }
 
 pub async fn project_validator(
@@ -97,8 +176,29 @@ pub async fn project_validator(
         .unwrap()
         .into_inner();
 
-    match get_api_key_from_raw_value(&db.pool, cache, credentials.token().to_string()).await {
+    match get_api_key_from_raw_value(&db.pool, cache.clone(), credentials.token().to_string())
+        .await
+    {
         Ok(api_key) => {
+            let ip = client_ip(&req.connection_info());
+            match is_ip_allowed_for_project(db, cache, api_key.project_id, ip).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    log::warn!(
+                        "Rejected request from {:?} not in IP allowlist of project {}",
+                        ip,
+                        api_key.project_id
+                    );
+                    return Err((
+                        actix_web::error::ErrorForbidden("IP address is not allowed"),
+                        req,
+                    ));
+                }
+                Err(e) => {
+                    log::error!("Error checking IP allowlist: {}", e);
+                    return Err((actix_web::error::ErrorInternalServerError(e), req));
+                }
+            }
             req.extensions_mut().insert(api_key);
             Ok(req)
         }
@@ -126,3 +226,31 @@ pub async fn shared_secret_validator(
         Err((AuthenticationError::from(config).into(), req))
     }
 }
+
+pub async fn scim_validator(
+    req: ServiceRequest,
+    credentials: BearerAuth,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let config = req
+        .app_data::<Config>()
+        .map(|data| data.clone())
+        .unwrap_or_else(Default::default);
+
+    let db = req
+        .app_data::<web::Data<DB>>()
+        .cloned()
+        .unwrap()
+        .into_inner();
+
+    let hash = hash_api_key(credentials.token());
+    match get_organization_id_by_token_hash(&db.pool, &hash).await {
+        Ok(id) => {
+            req.extensions_mut().insert(ScimOrganization { id });
+            Ok(req)
+        }
+        Err(e) => {
+            log::error!("Error validating SCIM token: {}", e);
+            Err((AuthenticationError::from(config).into(), req))
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Short-lived signed tokens for direct calls from the browser.
+//!
+//! A token is minted per UI session and carries the session id, user id and expiry, signed with
+//! HMAC-SHA256. Signature and expiry are checked without a lookup; the session itself is still
+//! checked in Postgres, so that logging out revokes all tokens of the session immediately.
+
+use anyhow::Result;
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sodiumoxide::{
+    crypto::auth::hmacsha256::{authenticate, verify, Key, Tag},
+    hex,
+};
+use uuid::Uuid;
+
+pub const SESSION_TOKEN_PREFIX: &str = "lmnr_st_";
+/// Lifetime of a single token. The frontend refreshes it before it expires.
+pub const SESSION_TOKEN_TTL_MINUTES: i64 = 15;
+/// Lifetime of the session, after which tokens cannot be refreshed anymore
+pub const SESSION_TTL_HOURS: i64 = 12;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionTokenClaims {
+    #[serde(rename = "sid")]
+    pub session_id: Uuid,
+    #[serde(rename = "uid")]
+    pub user_id: Uuid,
+    /// Expiration, seconds since epoch
+    pub exp: i64,
+}
+
+fn signing_key() -> Result<Key> {
+    let key_hex = std::env::var("SESSION_TOKEN_SECRET")
+        .map_err(|_| anyhow::anyhow!("SESSION_TOKEN_SECRET must be set to use session tokens"))?;
+    let key_bytes =
+        hex::decode(key_hex).map_err(|_| anyhow::anyhow!("SESSION_TOKEN_SECRET must be hex"))?;
+    Key::from_slice(&key_bytes).ok_or(anyhow::anyhow!(
+        "SESSION_TOKEN_SECRET must be 32 bytes long"
+    ))
+}
+
+/// Returns the token and its expiration time. The token never outlives the session.
+pub fn mint_token(
+    session_id: Uuid,
+    user_id: Uuid,
+    session_expires_at: DateTime<Utc>,
+) -> Result<(String, DateTime<Utc>)> {
+    let expires_at =
+        std::cmp::min(Utc::now() + Duration::minutes(SESSION_TOKEN_TTL_MINUTES), session_expires_at);
+    let claims = SessionTokenClaims {
+        session_id,
+        user_id,
+        exp: expires_at.timestamp(),
+    };
+    Ok((sign(&claims, &signing_key()?)?, expires_at))
+}
+
+fn sign(claims: &SessionTokenClaims, key: &Key) -> Result<String> {
+    let payload = BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+    let tag = authenticate(payload.as_bytes(), key);
+    Ok(format!(
+        "{SESSION_TOKEN_PREFIX}{payload}.{}",
+        BASE64_URL_SAFE_NO_PAD.encode(tag.0)
+    ))
+}
+
+/// Verifies the signature and expiry of the token. Does not check whether the session was revoked.
+pub fn verify_token(token: &str) -> Result<SessionTokenClaims> {
+    verify_with_key(token, &signing_key()?, Utc::now())
+}
+
+fn verify_with_key(token: &str, key: &Key, now: DateTime<Utc>) -> Result<SessionTokenClaims> {
+    let (payload, signature) = token
+        .strip_prefix(SESSION_TOKEN_PREFIX)
+        .and_then(|token| token.split_once('.'))
+        .ok_or(anyhow::anyhow!("Malformed session token"))?;
+
+    let signature = BASE64_URL_SAFE_NO_PAD.decode(signature)?;
+    let tag = Tag::from_slice(&signature).ok_or(anyhow::anyhow!("Malformed session token"))?;
+    if !verify(&tag, payload.as_bytes(), key) {
+        return Err(anyhow::anyhow!("Invalid session token signature"));
+    }
+
+    let claims: SessionTokenClaims =
+        serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(payload)?)?;
+    if claims.exp <= now.timestamp() {
+        return Err(anyhow::anyhow!("Session token expired"));
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sodiumoxide::crypto::auth::hmacsha256::gen_key;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = gen_key();
+        let now = Utc::now();
+        let claims = SessionTokenClaims {
+            session_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            exp: (now + Duration::minutes(5)).timestamp(),
+        };
+        let token = sign(&claims, &key).unwrap();
+
+        assert_eq!(verify_with_key(&token, &key, now).unwrap(), claims);
+        // expired
+        assert!(verify_with_key(&token, &key, now + Duration::minutes(10)).is_err());
+        // signed with another key
+        assert!(verify_with_key(&token, &gen_key(), now).is_err());
+        // tampered payload
+        let other = sign(
+            &SessionTokenClaims {
+                user_id: Uuid::new_v4(),
+                ..claims.clone()
+            },
+            &key,
+        )
+        .unwrap();
+        let (other_payload, _) = other.split_once('.').unwrap();
+        let (_, signature) = token.split_once('.').unwrap();
+        assert!(verify_with_key(&format!("{other_payload}.{signature}"), &key, now).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! Checks sign-ins of members of organizations that require SSO. The frontend forwards the token
+//! it got from the identity provider, and the provider is only trusted if it confirms that the
+//! token belongs to a verified identity with the user's email.
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::network::egress;
+
+const GITHUB_EMAILS_URL: &str = "https://api.github.com/user/emails";
+const GOOGLE_TOKEN_INFO_URL: &str = "https://oauth2.googleapis.com/tokeninfo";
+
+/// Token of the next-auth provider the user signed in with: the access token for GitHub and the
+/// id token for Google
+#[derive(Deserialize)]
+pub struct IdentityToken {
+    provider: String,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct GithubEmail {
+    email: String,
+    verified: bool,
+}
+
+#[derive(Deserialize)]
+struct GoogleTokenInfo {
+    aud: String,
+    email: String,
+    email_verified: String,
+}
+
+async fn github_identity_matches(access_token: &str, email: &str) -> Result<bool> {
+    let response = egress::http_client()
+        .get(GITHUB_EMAILS_URL)
+        .bearer_auth(access_token)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "laminar-app-server")
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Ok(false);
+    }
+    let emails = response.json::<Vec<GithubEmail>>().await?;
+
+    Ok(emails.iter().any(|github_email| {
+        github_email.verified && github_email.email.eq_ignore_ascii_case(email)
+    }))
+}
+
+/// Id tokens are only accepted for the OAuth client of the frontend, `AUTH_GOOGLE_ID`
+async fn google_identity_matches(id_token: &str, email: &str) -> Result<bool> {
+    let Ok(client_id) = std::env::var("AUTH_GOOGLE_ID") else {
+        log::warn!("AUTH_GOOGLE_ID is not set, Google sign-ins can't satisfy SSO requirements");
+        return Ok(false);
+    };
+    let response = egress::http_client()
+        .get(GOOGLE_TOKEN_INFO_URL)
+        .query(&[("id_token", id_token)])
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Ok(false);
+    }
+    let info = response.json::<GoogleTokenInfo>().await?;
+
+    Ok(info.aud == client_id
+        && info.email_verified == "true"
+        && info.email.eq_ignore_ascii_case(email))
+}
+
+/// Whether an identity provider verified that the token belongs to the user with `email`.
+/// Providers that can't be reached count as not verified.
+pub async fn is_verified_by_identity_provider(token: &IdentityToken, email: &str) -> bool {
+    let result = match token.provider.as_str() {
+        "github" => github_identity_matches(&token.token, email).await,
+        "google" => google_identity_matches(&token.token, email).await,
+        _ => Ok(false),
+    };
+    result.unwrap_or_else(|e| {
+        log::error!(
+            "Failed to verify the {} identity of a sign-in: {:?}",
+            token.provider,
+            e
+        );
+        false
+    })
+}
@@ -0,0 +1,303 @@
+//! Encrypted backups of a workspace, so that self-hosted deployments can be recovered without
+//! database tooling.
+//!
+//! A backup is a consistent snapshot of the workspace's rows in Postgres: its projects, api keys,
+//! datasets, pipelines, evaluations and project settings, see `db::backups::BACKUP_TABLES`.
+//! Spans, events and scores in ClickHouse and the files in object storage are too large to go
+//! through the API, so the backup has a manifest of them instead: the rows and time range of each
+//! project in each ClickHouse table and the object storage prefix of each project. They are
+//! backed up with ClickHouse's `BACKUP` and the bucket's versioning or replication, and can be
+//! checked against the manifest after a restore.
+//!
+//! The snapshot is encrypted with XChaCha20-Poly1305 under a key derived from a passphrase with
+//! Argon2id, so that backup files can be stored anywhere. Provider api keys, alert channel
+//! targets and export credentials in the rows stay encrypted with the server's `AEAD_SECRET_KEY`,
+//! which the restoring server needs to read them.
+//!
+//! Restoring inserts the rows that don't exist with their original ids, so a backup can be
+//! restored on a new server or to recover deleted rows, and restoring it twice changes nothing.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::{anyhow, bail, Result};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sodiumoxide::{
+    crypto::{
+        aead::xchacha20poly1305_ietf::{self, Key, Nonce, KEYBYTES},
+        pwhash::argon2id13::{self, MemLimit, OpsLimit, Salt},
+    },
+    hex,
+};
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    ch::table_stats::TableStats,
+    db::{
+        self,
+        backups::{BackupMember, RestoreReport},
+        DB,
+    },
+    storage,
+};
+
+const BACKUP_FORMAT: &str = "laminar-workspace-backup";
+const BACKUP_VERSION: u32 = 1;
+const KEY_DERIVATION_ALGORITHM: &str = "argon2id13";
+pub const MIN_PASSPHRASE_LENGTH: usize = 12;
+/// Backup files can be larger than most request bodies
+pub const MAX_BACKUP_BYTES: usize = 512 * 1024 * 1024;
+
+/// Data of a project outside of Postgres, which is backed up separately
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectManifest {
+    pub project_id: Uuid,
+    /// Prefix of the project's files in the `S3_IMGS_BUCKET` bucket
+    pub storage_prefix: String,
+    pub clickhouse_tables: Vec<TableStats>,
+}
+
+/// Decrypted contents of a backup
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceSnapshot {
+    pub workspace_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    /// Rows as JSON objects by table name
+    pub tables: BTreeMap<String, Vec<Value>>,
+    pub members: Vec<BackupMember>,
+    pub projects: Vec<ProjectManifest>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyDerivation {
+    pub algorithm: String,
+    /// Hex
+    pub salt: String,
+    pub ops_limit: usize,
+    pub mem_limit: usize,
+}
+
+/// Backup file. The header is in the clear, so that backups can be told apart without the
+/// passphrase, and is authenticated with the snapshot.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedBackup {
+    pub format: String,
+    pub version: u32,
+    pub workspace_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub key_derivation: KeyDerivation,
+    /// Hex
+    pub nonce: String,
+    /// Base64 of the encrypted snapshot
+    pub ciphertext: String,
+}
+
+impl EncryptedBackup {
+    /// The snapshot is bound to the header, so that it can't be passed off as another
+    /// workspace's backup
+    fn associated_data(&self) -> String {
+        format!("{}:{}:{}", self.format, self.version, self.workspace_id)
+    }
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &Salt,
+    ops_limit: OpsLimit,
+    mem_limit: MemLimit,
+) -> Result<Key> {
+    let mut key = Key([0; KEYBYTES]);
+    argon2id13::derive_key(
+        &mut key.0,
+        passphrase.as_bytes(),
+        salt,
+        ops_limit,
+        mem_limit,
+    )
+    .map_err(|_| anyhow!("Failed to derive the backup key"))?;
+    Ok(key)
+}
+
+/// Derives the key with Argon2id, which takes a moment and 64 MiB of memory, so callers run it on
+/// a blocking thread
+pub fn encrypt(snapshot: &WorkspaceSnapshot, passphrase: &str) -> Result<EncryptedBackup> {
+    let salt = argon2id13::gen_salt();
+    let (ops_limit, mem_limit) = (
+        argon2id13::OPSLIMIT_INTERACTIVE,
+        argon2id13::MEMLIMIT_INTERACTIVE,
+    );
+    let key = derive_key(passphrase, &salt, ops_limit, mem_limit)?;
+    let nonce = xchacha20poly1305_ietf::gen_nonce();
+
+    let mut backup = EncryptedBackup {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        workspace_id: snapshot.workspace_id,
+        created_at: snapshot.created_at,
+        key_derivation: KeyDerivation {
+            algorithm: KEY_DERIVATION_ALGORITHM.to_string(),
+            salt: hex::encode(salt),
+            ops_limit: ops_limit.0,
+            mem_limit: mem_limit.0,
+        },
+        nonce: hex::encode(nonce),
+        ciphertext: String::new(),
+    };
+    let encrypted = xchacha20poly1305_ietf::seal(
+        &serde_json::to_vec(snapshot)?,
+        Some(backup.associated_data().as_bytes()),
+        &nonce,
+        &key,
+    );
+    backup.ciphertext = BASE64_STANDARD.encode(encrypted);
+
+    Ok(backup)
+}
+
+/// Fails if the passphrase is wrong or the backup was modified
+pub fn decrypt(backup: &EncryptedBackup, passphrase: &str) -> Result<WorkspaceSnapshot> {
+    if backup.format != BACKUP_FORMAT {
+        bail!("Not a workspace backup");
+    }
+    if backup.version != BACKUP_VERSION {
+        bail!("Unsupported backup version {}", backup.version);
+    }
+    let kdf = &backup.key_derivation;
+    if kdf.algorithm != KEY_DERIVATION_ALGORITHM {
+        bail!("Unsupported key derivation {}", kdf.algorithm);
+    }
+    // the limits are read from the file, so they are capped to what this server would use
+    if kdf.ops_limit > argon2id13::OPSLIMIT_SENSITIVE.0
+        || kdf.mem_limit > argon2id13::MEMLIMIT_MODERATE.0
+    {
+        bail!("Key derivation limits of the backup are too high");
+    }
+    let salt = hex::decode(&kdf.salt)
+        .ok()
+        .and_then(|salt| Salt::from_slice(&salt))
+        .ok_or(anyhow!("Invalid salt"))?;
+    let nonce = hex::decode(&backup.nonce)
+        .ok()
+        .and_then(|nonce| Nonce::from_slice(&nonce))
+        .ok_or(anyhow!("Invalid nonce"))?;
+    let encrypted = BASE64_STANDARD
+        .decode(&backup.ciphertext)
+        .map_err(|_| anyhow!("Invalid ciphertext"))?;
+
+    let key = derive_key(
+        passphrase,
+        &salt,
+        OpsLimit(kdf.ops_limit),
+        MemLimit(kdf.mem_limit),
+    )?;
+    let decrypted = xchacha20poly1305_ietf::open(
+        &encrypted,
+        Some(backup.associated_data().as_bytes()),
+        &nonce,
+        &key,
+    )
+    .map_err(|_| anyhow!("Wrong passphrase or the backup was modified"))?;
+    let snapshot = serde_json::from_slice::<WorkspaceSnapshot>(&decrypted)?;
+    if snapshot.workspace_id != backup.workspace_id {
+        bail!("Backup is of another workspace");
+    }
+
+    Ok(snapshot)
+}
+
+/// Snapshot of the workspace, encrypted with the passphrase. None if the workspace doesn't
+/// exist.
+pub async fn create_backup(
+    db: Arc<DB>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    workspace_id: Uuid,
+    passphrase: String,
+) -> Result<Option<EncryptedBackup>> {
+    let created_at = Utc::now();
+    let Some(rows) = db::backups::get_workspace_rows(&db.pool, &workspace_id).await? else {
+        return Ok(None);
+    };
+
+    let mut projects = Vec::new();
+    for project_id in rows.project_ids {
+        projects.push(ProjectManifest {
+            project_id,
+            storage_prefix: storage::project_prefix(&project_id),
+            clickhouse_tables: analytics_store.get_project_table_stats(project_id).await?,
+        });
+    }
+    let snapshot = WorkspaceSnapshot {
+        workspace_id,
+        created_at,
+        tables: rows.tables,
+        members: rows.members,
+        projects,
+    };
+
+    let backup = tokio::task::spawn_blocking(move || encrypt(&snapshot, &passphrase)).await??;
+    Ok(Some(backup))
+}
+
+pub async fn restore_snapshot(db: Arc<DB>, snapshot: &WorkspaceSnapshot) -> Result<RestoreReport> {
+    db::backups::restore_workspace_rows(
+        &db.pool,
+        &snapshot.workspace_id,
+        &snapshot.tables,
+        &snapshot.members,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::db::field_visibility::WorkspaceRole;
+
+    use super::*;
+
+    fn snapshot() -> WorkspaceSnapshot {
+        let workspace_id = Uuid::new_v4();
+        WorkspaceSnapshot {
+            workspace_id,
+            created_at: Utc::now(),
+            tables: BTreeMap::from([(
+                "workspaces".to_string(),
+                vec![json!({"id": workspace_id, "name": "Acme"})],
+            )]),
+            members: vec![BackupMember {
+                email: "owner@acme.com".to_string(),
+                member_role: WorkspaceRole::Owner,
+            }],
+            projects: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        sodiumoxide::init().unwrap();
+        let snapshot = snapshot();
+        let backup = encrypt(&snapshot, "correct horse battery").unwrap();
+        assert_eq!(backup.workspace_id, snapshot.workspace_id);
+        assert!(!backup.ciphertext.contains("Acme"));
+
+        let decrypted = decrypt(&backup, "correct horse battery").unwrap();
+        assert_eq!(decrypted, snapshot);
+        assert!(decrypt(&backup, "wrong horse battery").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_modified_header() {
+        sodiumoxide::init().unwrap();
+        let mut backup = encrypt(&snapshot(), "correct horse battery").unwrap();
+        backup.workspace_id = Uuid::new_v4();
+        assert!(decrypt(&backup, "correct horse battery").is_err());
+    }
+}
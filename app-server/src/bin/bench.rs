@@ -0,0 +1,355 @@
+//! Load generator for the ingestion path. Sends traces, and optionally evaluations, to a running
+//! app-server for a fixed duration and reports throughput, request latency and ClickHouse lag.
+//!
+//! Configured with env variables:
+//! - `BENCH_URL`: base url of the app-server, `http://localhost:8000` by default
+//! - `BENCH_PROJECT_API_KEY`: project api key, required
+//! - `BENCH_DURATION_SECS`, `BENCH_CONCURRENCY`, `BENCH_SPANS_PER_TRACE`
+//! - `BENCH_EVALUATION_RATE`: fraction of requests that create an evaluation instead of a trace
+//! - `BENCH_PROJECT_ID`, `CLICKHOUSE_URL`, `CLICKHOUSE_USER`, `CLICKHOUSE_PASSWORD`: if set, the
+//!   time until all sent spans are visible in ClickHouse is measured after the load ends
+//!
+//! Run with `cargo run --release --bin bench`.
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use prost::Message;
+use rand::Rng;
+use serde_json::json;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+#[path = "../opentelemetry/mod.rs"]
+#[allow(dead_code)]
+mod opentelemetry;
+
+use opentelemetry::{
+    opentelemetry::proto::collector::trace::v1::ExportTraceServiceRequest,
+    opentelemetry_proto_common_v1::{any_value, AnyValue, KeyValue},
+    opentelemetry_proto_trace_v1::{ResourceSpans, ScopeSpans, Span},
+};
+
+const CLICKHOUSE_LAG_TIMEOUT: Duration = Duration::from_secs(300);
+
+struct Config {
+    url: String,
+    api_key: String,
+    duration: Duration,
+    concurrency: usize,
+    spans_per_trace: usize,
+    evaluation_rate: f64,
+    project_id: Option<Uuid>,
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            url: env::var("BENCH_URL").unwrap_or("http://localhost:8000".to_string()),
+            api_key: env::var("BENCH_PROJECT_API_KEY").expect("BENCH_PROJECT_API_KEY must be set"),
+            duration: Duration::from_secs(env_or("BENCH_DURATION_SECS", 60)),
+            concurrency: env_or("BENCH_CONCURRENCY", 16),
+            spans_per_trace: env_or::<usize>("BENCH_SPANS_PER_TRACE", 5).max(1),
+            evaluation_rate: env_or("BENCH_EVALUATION_RATE", 0.0),
+            project_id: env::var("BENCH_PROJECT_ID")
+                .ok()
+                .and_then(|id| Uuid::parse_str(&id).ok()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    spans: AtomicU64,
+    evaluations: AtomicU64,
+    latencies_us: Mutex<Vec<u64>>,
+}
+
+fn attribute(key: &str, value: any_value::Value) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue { value: Some(value) }),
+    }
+}
+
+fn string_attribute(key: &str, value: &str) -> KeyValue {
+    attribute(key, any_value::Value::StringValue(value.to_string()))
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64
+}
+
+/// A trace shaped like an instrumented LLM app: a root span with a chain of children,
+/// where every other child is an LLM call with prompt, completion and usage
+fn generate_trace(spans_per_trace: usize) -> Vec<Span> {
+    let mut rng = rand::thread_rng();
+    let trace_id = Uuid::new_v4().as_bytes().to_vec();
+    let start = now_nanos();
+    let mut spans = Vec::with_capacity(spans_per_trace);
+    let root_span_id = rng.gen::<[u8; 8]>().to_vec();
+
+    spans.push(Span {
+        trace_id: trace_id.clone(),
+        span_id: root_span_id.clone(),
+        name: "bench.workflow".to_string(),
+        start_time_unix_nano: start,
+        end_time_unix_nano: start + 2_000_000_000,
+        attributes: vec![
+            string_attribute("lmnr.span.path", "bench.workflow"),
+            string_attribute("lmnr.association.properties.session_id", "bench-session"),
+        ],
+        ..Default::default()
+    });
+
+    for i in 1..spans_per_trace {
+        let span_start = start + i as u64 * 1_000_000;
+        let mut attributes = vec![string_attribute(
+            "lmnr.span.path",
+            &format!("bench.workflow.step_{i}"),
+        )];
+        if i % 2 == 1 {
+            let input_tokens = rng.gen_range(100..2000);
+            let output_tokens = rng.gen_range(10..500);
+            attributes.extend([
+                string_attribute("lmnr.span.type", "LLM"),
+                string_attribute("gen_ai.system", "openai"),
+                string_attribute("gen_ai.request.model", "gpt-4o-mini"),
+                string_attribute("gen_ai.response.model", "gpt-4o-mini"),
+                attribute(
+                    "gen_ai.usage.input_tokens",
+                    any_value::Value::IntValue(input_tokens),
+                ),
+                attribute(
+                    "gen_ai.usage.output_tokens",
+                    any_value::Value::IntValue(output_tokens),
+                ),
+                string_attribute("gen_ai.prompt.0.role", "user"),
+                string_attribute(
+                    "gen_ai.prompt.0.content",
+                    &"Summarize the text. ".repeat(20),
+                ),
+                string_attribute("gen_ai.completion.0.role", "assistant"),
+                string_attribute("gen_ai.completion.0.content", &"A summary. ".repeat(10)),
+            ]);
+        }
+        spans.push(Span {
+            trace_id: trace_id.clone(),
+            span_id: rng.gen::<[u8; 8]>().to_vec(),
+            parent_span_id: root_span_id.clone(),
+            name: format!("step_{i}"),
+            start_time_unix_nano: span_start,
+            end_time_unix_nano: span_start + rng.gen_range(50_000_000..1_500_000_000),
+            attributes,
+            ..Default::default()
+        });
+    }
+
+    spans
+}
+
+async fn send_trace(client: &reqwest::Client, config: &Config) -> anyhow::Result<usize> {
+    let spans = generate_trace(config.spans_per_trace);
+    let count = spans.len();
+    let request = ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            scope_spans: vec![ScopeSpans {
+                spans,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    };
+    client
+        .post(format!("{}/v1/traces", config.url))
+        .bearer_auth(&config.api_key)
+        .header("Content-Type", "application/x-protobuf")
+        .body(request.encode_to_vec())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(count)
+}
+
+async fn send_evaluation(client: &reqwest::Client, config: &Config) -> anyhow::Result<()> {
+    let points = (0..10)
+        .map(|i| {
+            json!({
+                "data": {"question": format!("question {i}")},
+                "target": {"answer": format!("answer {i}")},
+                "executorOutput": format!("output {i}"),
+                "scores": {"accuracy": rand::thread_rng().gen_range(0.0..1.0)},
+            })
+        })
+        .collect::<Vec<_>>();
+    client
+        .post(format!("{}/v1/evaluations", config.url))
+        .bearer_auth(&config.api_key)
+        .json(&json!({"groupId": "bench", "points": points}))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn worker(
+    client: reqwest::Client,
+    config: Arc<Config>,
+    stats: Arc<Stats>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut latencies = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        let is_evaluation = rand::thread_rng().gen_bool(config.evaluation_rate.clamp(0.0, 1.0));
+        let start = Instant::now();
+        let res = if is_evaluation {
+            send_evaluation(&client, &config).await.map(|_| 0)
+        } else {
+            send_trace(&client, &config).await
+        };
+        latencies.push(start.elapsed().as_micros() as u64);
+        stats.requests.fetch_add(1, Ordering::Relaxed);
+        match res {
+            Ok(_) if is_evaluation => {
+                stats.evaluations.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(spans) => {
+                stats.spans.fetch_add(spans as u64, Ordering::Relaxed);
+            }
+            Err(e) => {
+                if stats.errors.fetch_add(1, Ordering::Relaxed) == 0 {
+                    eprintln!("First request error: {e}");
+                }
+            }
+        }
+    }
+    stats.latencies_us.lock().await.extend(latencies);
+}
+
+fn percentile(sorted: &[u64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() as f64 - 1.0) * p).round() as usize;
+    sorted[index] as f64 / 1000.0
+}
+
+/// Time from the end of the load until all spans sent during the run are in ClickHouse
+async fn measure_clickhouse_lag(
+    project_id: Uuid,
+    run_start_nanos: u64,
+    expected_spans: u64,
+) -> anyhow::Result<Duration> {
+    let mut clickhouse = clickhouse::Client::default()
+        .with_url(env::var("CLICKHOUSE_URL")?)
+        .with_user(env::var("CLICKHOUSE_USER").unwrap_or("default".to_string()))
+        .with_database("default");
+    if let Ok(password) = env::var("CLICKHOUSE_PASSWORD") {
+        clickhouse = clickhouse.with_password(password);
+    }
+
+    let start = Instant::now();
+    loop {
+        let count = clickhouse
+            .query(
+                "SELECT count() FROM spans
+                WHERE project_id = ? AND start_time >= fromUnixTimestamp64Nano(?)",
+            )
+            .bind(project_id)
+            .bind(run_start_nanos as i64)
+            .fetch_one::<u64>()
+            .await?;
+        if count >= expected_spans {
+            return Ok(start.elapsed());
+        }
+        if start.elapsed() > CLICKHOUSE_LAG_TIMEOUT {
+            return Err(anyhow::anyhow!(
+                "only {count} of {expected_spans} spans in ClickHouse after {}s",
+                CLICKHOUSE_LAG_TIMEOUT.as_secs()
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    let config = Arc::new(Config::from_env());
+    let stats = Arc::new(Stats::default());
+    let stop = Arc::new(AtomicBool::new(false));
+    let client = reqwest::Client::new();
+
+    println!(
+        "Sending load to {} for {}s with {} workers",
+        config.url,
+        config.duration.as_secs(),
+        config.concurrency
+    );
+
+    let run_start_nanos = now_nanos();
+    let start = Instant::now();
+    let workers = (0..config.concurrency)
+        .map(|_| {
+            tokio::spawn(worker(
+                client.clone(),
+                config.clone(),
+                stats.clone(),
+                stop.clone(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    tokio::time::sleep(config.duration).await;
+    stop.store(true, Ordering::Relaxed);
+    for worker in workers {
+        worker.await?;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let mut latencies = std::mem::take(&mut *stats.latencies_us.lock().await);
+    latencies.sort_unstable();
+    let requests = stats.requests.load(Ordering::Relaxed);
+    let spans = stats.spans.load(Ordering::Relaxed);
+
+    println!("requests:        {}", requests);
+    println!("errors:          {}", stats.errors.load(Ordering::Relaxed));
+    println!("requests/s:      {:.1}", requests as f64 / elapsed);
+    println!("spans/s:         {:.1}", spans as f64 / elapsed);
+    println!(
+        "evaluations/s:   {:.1}",
+        stats.evaluations.load(Ordering::Relaxed) as f64 / elapsed
+    );
+    println!("latency p50 ms:  {:.2}", percentile(&latencies, 0.5));
+    println!("latency p99 ms:  {:.2}", percentile(&latencies, 0.99));
+    println!("latency max ms:  {:.2}", percentile(&latencies, 1.0));
+
+    match config.project_id {
+        Some(project_id) if env::var("CLICKHOUSE_URL").is_ok() => {
+            match measure_clickhouse_lag(project_id, run_start_nanos, spans).await {
+                Ok(lag) => println!("clickhouse lag:  {:.2}s", lag.as_secs_f64()),
+                Err(e) => println!("clickhouse lag:  failed to measure, {e}"),
+            }
+        }
+        _ => println!("clickhouse lag:  not measured, set BENCH_PROJECT_ID and CLICKHOUSE_URL"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::features::{is_feature_enabled, Feature};
+
+use super::utils::{
+    chrono_to_nanoseconds, deserialize_timestamp, execute_query, insert_rows, nanoseconds_to_chrono,
+};
+
+fn serialize_timestamp<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_i64(chrono_to_nanoseconds(timestamp.clone()))
+}
+
+/// Event recorded in the browser controlled by an agent, e.g. a DOM mutation, a navigation or a
+/// screenshot
+#[derive(Row, Serialize, Deserialize, Clone, Debug)]
+pub struct BrowserEvent {
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub project_id: Uuid,
+    pub session_id: String,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub trace_id: Uuid,
+    #[serde(
+        serialize_with = "serialize_timestamp",
+        deserialize_with = "deserialize_timestamp"
+    )]
+    pub timestamp: DateTime<Utc>,
+    pub event_type: String,
+    /// JSON payload of the event as recorded by the SDK
+    pub data: String,
+    /// Empty if the event has no screenshot
+    pub screenshot_url: String,
+}
+
+pub async fn insert_browser_events(
+    clickhouse: clickhouse::Client,
+    events: Vec<BrowserEvent>,
+) -> Result<()> {
+    if events.is_empty() || !is_feature_enabled(Feature::FullBuild) {
+        return Ok(());
+    }
+
+    insert_rows(&clickhouse, "browser_session_events", &events).await
+}
+
+#[derive(Row, Deserialize)]
+struct BrowserEventRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    id: Uuid,
+    session_id: String,
+    timestamp: i64,
+    event_type: String,
+    data: String,
+    screenshot_url: String,
+}
+
+/// Browser events recorded during the trace, oldest first
+pub async fn get_trace_browser_events(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    trace_id: Uuid,
+) -> Result<Vec<BrowserEvent>> {
+    let query = format!(
+        "SELECT
+            id,
+            session_id,
+            toUnixTimestamp64Nano(timestamp) AS timestamp,
+            event_type,
+            data,
+            screenshot_url
+        FROM browser_session_events
+        WHERE project_id = '{project_id}' AND trace_id = '{trace_id}'
+        ORDER BY timestamp ASC"
+    );
+
+    let rows: Vec<BrowserEventRow> = execute_query(&clickhouse, &query).await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| BrowserEvent {
+            id: row.id,
+            project_id,
+            session_id: row.session_id,
+            trace_id,
+            timestamp: nanoseconds_to_chrono(row.timestamp),
+            event_type: row.event_type,
+            data: row.data,
+            screenshot_url: row.screenshot_url,
+        })
+        .collect())
+}
@@ -0,0 +1,149 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::features::{is_feature_enabled, Feature};
+
+use super::utils::{
+    chrono_to_nanoseconds, deserialize_timestamp, execute_query, insert_rows,
+    nanoseconds_to_chrono, validate_string_against_injection,
+};
+
+fn serialize_timestamp<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_i64(chrono_to_nanoseconds(timestamp.clone()))
+}
+
+/// Screenshot and DOM of the page in the browser of an agent after a step. The contents are in
+/// object storage, the hashes tell whether they changed between steps without fetching them.
+#[derive(Row, Serialize, Deserialize, Clone, Debug)]
+pub struct BrowserSnapshot {
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub project_id: Uuid,
+    pub session_id: String,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub trace_id: Uuid,
+    pub step: u32,
+    #[serde(
+        serialize_with = "serialize_timestamp",
+        deserialize_with = "deserialize_timestamp"
+    )]
+    pub timestamp: DateTime<Utc>,
+    /// URL of the page, not of the snapshot
+    pub page_url: String,
+    /// Empty if the snapshot has no screenshot
+    pub screenshot_url: String,
+    pub screenshot_hash: String,
+    /// Empty if the snapshot has no DOM
+    pub dom_url: String,
+    pub dom_hash: String,
+}
+
+pub async fn insert_browser_snapshots(
+    clickhouse: clickhouse::Client,
+    snapshots: Vec<BrowserSnapshot>,
+) -> Result<()> {
+    if snapshots.is_empty() || !is_feature_enabled(Feature::FullBuild) {
+        return Ok(());
+    }
+
+    insert_rows(&clickhouse, "browser_snapshots", &snapshots).await
+}
+
+#[derive(Row, Deserialize)]
+struct BrowserSnapshotRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    trace_id: Uuid,
+    step: u32,
+    timestamp: i64,
+    page_url: String,
+    screenshot_url: String,
+    screenshot_hash: String,
+    dom_url: String,
+    dom_hash: String,
+}
+
+const SNAPSHOT_COLUMNS: &str =
+    "id, trace_id, step, toUnixTimestamp64Nano(timestamp) AS timestamp, \
+    page_url, screenshot_url, screenshot_hash, dom_url, dom_hash";
+
+async fn query_browser_snapshots(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    session_id: String,
+    query: String,
+) -> Result<Vec<BrowserSnapshot>> {
+    let rows: Vec<BrowserSnapshotRow> = execute_query(&clickhouse, &query).await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| BrowserSnapshot {
+            id: row.id,
+            project_id,
+            session_id: session_id.clone(),
+            trace_id: row.trace_id,
+            step: row.step,
+            timestamp: nanoseconds_to_chrono(row.timestamp),
+            page_url: row.page_url,
+            screenshot_url: row.screenshot_url,
+            screenshot_hash: row.screenshot_hash,
+            dom_url: row.dom_url,
+            dom_hash: row.dom_hash,
+        })
+        .collect())
+}
+
+/// Snapshot of the session taken closest to the timestamp, before or after it
+pub async fn get_nearest_browser_snapshot(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    session_id: String,
+    timestamp: DateTime<Utc>,
+) -> Result<Option<BrowserSnapshot>> {
+    validate_string_against_injection(&session_id)?;
+
+    let query = format!(
+        "SELECT {SNAPSHOT_COLUMNS}
+        FROM browser_snapshots
+        WHERE project_id = '{project_id}' AND session_id = '{session_id}'
+        ORDER BY abs(toUnixTimestamp64Nano(timestamp) - {}) ASC, step ASC
+        LIMIT 1",
+        chrono_to_nanoseconds(timestamp)
+    );
+
+    let mut snapshots = query_browser_snapshots(clickhouse, project_id, session_id, query).await?;
+    Ok(snapshots.pop())
+}
+
+/// Last snapshot of the step and the last snapshot of the session's previous step, the step's
+/// snapshot first. Empty if the step has no snapshot.
+pub async fn get_browser_snapshots_at_step(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    session_id: String,
+    step: u32,
+) -> Result<Vec<BrowserSnapshot>> {
+    validate_string_against_injection(&session_id)?;
+
+    let query = format!(
+        "SELECT {SNAPSHOT_COLUMNS}
+        FROM browser_snapshots
+        WHERE project_id = '{project_id}' AND session_id = '{session_id}' AND step <= {step}
+        ORDER BY step DESC, timestamp DESC
+        LIMIT 1 BY step
+        LIMIT 2"
+    );
+
+    let snapshots = query_browser_snapshots(clickhouse, project_id, session_id, query).await?;
+    if snapshots.first().map(|snapshot| snapshot.step) != Some(step) {
+        return Ok(Vec::new());
+    }
+    Ok(snapshots)
+}
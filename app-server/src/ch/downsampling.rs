@@ -0,0 +1,125 @@
+//! Reduction of long-range time series, so that charts get at most a few thousand points
+//! regardless of the range
+
+use super::MetricTimeValue;
+
+/// Maximum number of points of a time series chart
+pub const MAX_CHART_POINTS: usize = 2000;
+
+pub trait ChartValue {
+    fn to_f64(&self) -> f64;
+}
+
+impl ChartValue for i64 {
+    fn to_f64(&self) -> f64 {
+        *self as f64
+    }
+}
+
+impl ChartValue for f64 {
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+}
+
+/// Reduce the series to `max_points` with Largest-Triangle-Three-Buckets, which keeps the
+/// first and last points and the peaks of the series, unlike averaging
+pub fn downsample<T: ChartValue>(
+    points: Vec<MetricTimeValue<T>>,
+    max_points: usize,
+) -> Vec<MetricTimeValue<T>> {
+    let len = points.len();
+    if max_points < 3 || len <= max_points {
+        return points;
+    }
+
+    let x = |i: usize| points[i].time as f64;
+    let y = |i: usize| points[i].value.to_f64();
+    // The first and last points are always kept, the others are split into buckets
+    let bucket_size = (len - 2) as f64 / (max_points - 2) as f64;
+    let mut selected = Vec::with_capacity(max_points);
+    selected.push(0);
+    let mut previous = 0;
+
+    for bucket in 0..max_points - 2 {
+        let start = (bucket as f64 * bucket_size) as usize + 1;
+        let end = ((bucket + 1) as f64 * bucket_size) as usize + 1;
+
+        // Average of the next bucket, or the last point for the last bucket
+        let next_start = end;
+        let next_end = (((bucket + 2) as f64 * bucket_size) as usize + 1).min(len);
+        let next_count = (next_end - next_start).max(1) as f64;
+        let (next_x, next_y) = if next_start >= next_end {
+            (x(len - 1), y(len - 1))
+        } else {
+            (next_start..next_end).fold((0.0, 0.0), |(sum_x, sum_y), i| {
+                (sum_x + x(i) / next_count, sum_y + y(i) / next_count)
+            })
+        };
+
+        let (previous_x, previous_y) = (x(previous), y(previous));
+        let mut max_area = -1.0;
+        let mut max_index = start;
+        for i in start..end.min(len - 1) {
+            let area = ((previous_x - next_x) * (y(i) - previous_y)
+                - (previous_x - x(i)) * (next_y - previous_y))
+                .abs();
+            if area > max_area {
+                max_area = area;
+                max_index = i;
+            }
+        }
+        selected.push(max_index);
+        previous = max_index;
+    }
+    selected.push(len - 1);
+
+    let mut selected = selected.into_iter().peekable();
+    points
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, point)| {
+            if selected.peek() == Some(&i) {
+                selected.next();
+                Some(point)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(values: &[i64]) -> Vec<MetricTimeValue<i64>> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| MetricTimeValue {
+                time: i as u32 * 60,
+                value: *value,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_downsample() {
+        let mut values = vec![1; 1000];
+        values[500] = 100;
+        let points = downsample(series(&values), 50);
+
+        assert_eq!(points.len(), 50);
+        assert_eq!(points.first().unwrap().time, 0);
+        assert_eq!(points.last().unwrap().time, 999 * 60);
+        assert!(points.iter().any(|point| point.value == 100));
+        assert!(points.windows(2).all(|pair| pair[0].time < pair[1].time));
+    }
+
+    #[test]
+    fn test_downsample_short_series() {
+        let points = downsample(series(&[1, 2, 3]), 50);
+        assert_eq!(points.len(), 3);
+    }
+}
@@ -1,12 +1,30 @@
+use std::collections::BTreeMap;
+
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use clickhouse::Row;
 use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sha3::{Digest, Sha3_256};
 use uuid::Uuid;
 
-use crate::evaluations::utils::EvaluationDatapointResult;
+use crate::{
+    chaos,
+    evaluations::utils::{self, EvaluationDatapointResult},
+    features::{is_feature_enabled, Feature},
+};
 
-use super::utils::{chrono_to_nanoseconds, execute_query, validate_string_against_injection};
+use super::{
+    filter_expression::CompiledFilter,
+    modifiers::GroupByInterval,
+    utils::{
+        chrono_to_nanoseconds, deserialize_timestamp, execute_query,
+        group_by_time_absolute_statement, insert_rows, nanoseconds_to_chrono,
+        validate_string_against_injection,
+    },
+    MetricTimeValue,
+};
 
 fn serialize_timestamp<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -15,8 +33,34 @@ where
     serializer.serialize_i64(chrono_to_nanoseconds(timestamp.clone()))
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum ScoreType {
+    NUMERIC = 0,
+    BOOLEAN = 1,
+    CATEGORICAL = 2,
+}
+
+impl From<utils::ScoreType> for ScoreType {
+    fn from(score_type: utils::ScoreType) -> Self {
+        match score_type {
+            utils::ScoreType::NUMERIC => ScoreType::NUMERIC,
+            utils::ScoreType::BOOLEAN => ScoreType::BOOLEAN,
+            utils::ScoreType::CATEGORICAL => ScoreType::CATEGORICAL,
+        }
+    }
+}
+
+/// Whether the score was reported by an evaluator or submitted by a user
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum ScoreSource {
+    AUTO = 0,
+    HUMAN = 1,
+}
+
 /// Evaluation score
-#[derive(Row, Serialize)]
+#[derive(Row, Serialize, Deserialize)]
 pub struct EvaluationScore {
     /// Project id, its purpose is to validate user accesses evaluations only from projects they belong to
     #[serde(with = "clickhouse::serde::uuid")]
@@ -28,9 +72,30 @@ pub struct EvaluationScore {
     pub result_id: Uuid,
     // Note that one evaluator can produce multiple scores
     pub name: String,
+    /// 1 or 0 for boolean scores and 0 for categorical ones, see `ScoreValue::as_f64`
     pub value: f64,
-    #[serde(serialize_with = "serialize_timestamp")]
+    #[serde(
+        serialize_with = "serialize_timestamp",
+        deserialize_with = "deserialize_timestamp"
+    )]
     pub timestamp: DateTime<Utc>,
+    /// See `datapoint_key`, empty for the scores inserted before it was added
+    pub datapoint_key: String,
+    pub score_type: ScoreType,
+    /// Label of categorical scores, empty for the others
+    pub label: String,
+    pub source: ScoreSource,
+}
+
+/// Identity of a datapoint across evaluations, so that the scores of the same datapoint in
+/// different evaluations can be compared. Result ids are unique per evaluation, so the key is
+/// the hash of the datapoint's data and target instead.
+pub fn datapoint_key(data: &Value, target: &Value) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(data.to_string().as_bytes());
+    hasher.update([0]);
+    hasher.update(target.to_string().as_bytes());
+    format!("{:x}", hasher.finalize())
 }
 
 impl EvaluationScore {
@@ -40,26 +105,29 @@ impl EvaluationScore {
         project_id: Uuid,
         group_id: String,
         evaluation_id: Uuid,
-        // TODO: timestamp must be set in each point. This needs to be sent from
-        // client libraries. For now the same timestamp is used for all scores,
-        // which is fine.
-        timestamp: DateTime<Utc>,
+        // Used for the points without a timestamp from the client library
+        default_timestamp: DateTime<Utc>,
     ) -> Vec<EvaluationScore> {
         points
             .iter()
             .zip(result_ids.iter())
             .flat_map(|(point, result_id)| {
+                let timestamp = point.timestamp.unwrap_or(default_timestamp);
+                let datapoint_key = datapoint_key(&point.data, &point.target);
                 point.scores.iter().map(|(name, value)| {
                     let name = name.to_string();
-                    let value = value.clone();
                     EvaluationScore {
                         project_id,
                         group_id: group_id.clone(),
                         evaluation_id,
                         result_id: *result_id,
                         name: name.to_string(),
-                        value: value.clone(),
+                        value: value.as_f64().unwrap_or_default(),
                         timestamp,
+                        datapoint_key: datapoint_key.clone(),
+                        score_type: value.score_type().into(),
+                        label: value.label().unwrap_or_default().to_string(),
+                        source: ScoreSource::AUTO,
                     }
                 })
             })
@@ -67,36 +135,12 @@ impl EvaluationScore {
     }
 }
 
-pub async fn insert_evaluation_scores(
-    clickhouse: clickhouse::Client,
-    evaluation_scores: Vec<EvaluationScore>,
+/// Insert the scores in a single ClickHouse insert, see `score_writer` for buffered writes
+pub async fn write_evaluation_scores(
+    clickhouse: &clickhouse::Client,
+    evaluation_scores: &[EvaluationScore],
 ) -> Result<()> {
-    if evaluation_scores.is_empty() {
-        return Ok(());
-    }
-
-    let ch_insert = clickhouse.insert("evaluation_scores");
-    match ch_insert {
-        Ok(mut ch_insert) => {
-            for evaluation_score in evaluation_scores {
-                ch_insert.write(&evaluation_score).await?;
-            }
-            let ch_insert_end_res = ch_insert.end().await;
-            match ch_insert_end_res {
-                Ok(_) => Ok(()),
-                Err(e) => Err(anyhow::anyhow!(
-                    "Clickhouse evaluation scores insertion failed: {:?}",
-                    e
-                )),
-            }
-        }
-        Err(e) => {
-            return Err(anyhow::anyhow!(
-                "Failed to insert evaluation scores into Clickhouse: {:?}",
-                e
-            ));
-        }
-    }
+    insert_rows(clickhouse, "evaluation_scores", evaluation_scores).await
 }
 
 #[derive(Row, Deserialize)]
@@ -104,6 +148,7 @@ struct AverageEvaluationScore {
     average_value: f64,
 }
 
+/// Average of the numeric scores, the pass rate of the boolean ones
 pub async fn get_average_evaluation_score(
     clickhouse: clickhouse::Client,
     project_id: Uuid,
@@ -117,13 +162,283 @@ pub async fn get_average_evaluation_score(
         FROM evaluation_scores
         WHERE project_id = '{project_id}'
             AND evaluation_id = '{evaluation_id}'
-            AND name = '{name}'",
+            AND name = '{name}'
+            AND score_type != 'CATEGORICAL'",
     );
 
     let rows: Vec<AverageEvaluationScore> = execute_query(&clickhouse, &query).await?;
     Ok(rows[0].average_value)
 }
 
+/// Summary of the distribution of an evaluation's score
+#[derive(Row, Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScoreStats {
+    pub count: u64,
+    pub average_value: f64,
+    pub min_value: f64,
+    pub max_value: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    /// Population standard deviation
+    pub stddev: f64,
+}
+
+pub async fn get_evaluation_score_stats(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    evaluation_id: Uuid,
+    name: String,
+) -> Result<EvaluationScoreStats> {
+    validate_string_against_injection(&name)?;
+
+    let query = format!(
+        "SELECT
+            count() AS count,
+            avg(value) AS average_value,
+            min(value) AS min_value,
+            max(value) AS max_value,
+            quantileExact(0.5)(value) AS median,
+            quantileExact(0.9)(value) AS p90,
+            quantileExact(0.95)(value) AS p95,
+            quantileExact(0.99)(value) AS p99,
+            stddevPop(value) AS stddev
+        FROM evaluation_scores
+        WHERE project_id = '{project_id}'
+            AND evaluation_id = '{evaluation_id}'
+            AND name = '{name}'
+            AND score_type != 'CATEGORICAL'",
+    );
+
+    let mut rows: Vec<EvaluationScoreStats> = execute_query(&clickhouse, &query).await?;
+    rows.pop()
+        .ok_or_else(|| anyhow::anyhow!("Evaluation score stats query returned no rows"))
+}
+
+/// Share of the boolean scores of an evaluation that passed
+#[derive(Row, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScorePassRate {
+    pub count: u64,
+    pub passed_count: u64,
+    /// 0 to 1, 0 if there are no scores
+    pub pass_rate: f64,
+}
+
+pub async fn get_evaluation_score_pass_rate(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    evaluation_id: Uuid,
+    name: String,
+) -> Result<EvaluationScorePassRate> {
+    validate_string_against_injection(&name)?;
+
+    let query = format!(
+        "SELECT
+            count() AS count,
+            countIf(value = 1) AS passed_count,
+            if(count = 0, 0, passed_count / count) AS pass_rate
+        FROM evaluation_scores
+        WHERE project_id = '{project_id}'
+            AND evaluation_id = '{evaluation_id}'
+            AND name = '{name}'
+            AND score_type = 'BOOLEAN'",
+    );
+
+    let mut rows: Vec<EvaluationScorePassRate> = execute_query(&clickhouse, &query).await?;
+    rows.pop()
+        .ok_or_else(|| anyhow::anyhow!("Evaluation score pass rate query returned no rows"))
+}
+
+/// Number of the categorical scores of an evaluation with the label
+#[derive(Row, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScoreLabelFrequency {
+    pub label: String,
+    pub count: u64,
+    /// Share of the evaluation's scores of the name, 0 to 1
+    pub frequency: f64,
+}
+
+/// Label frequencies of the categorical scores, the most frequent label first
+pub async fn get_evaluation_score_label_frequencies(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    evaluation_id: Uuid,
+    name: String,
+) -> Result<Vec<EvaluationScoreLabelFrequency>> {
+    validate_string_against_injection(&name)?;
+
+    let query = format!(
+        "SELECT
+            label,
+            count() AS count,
+            count / sum(count) OVER () AS frequency
+        FROM evaluation_scores
+        WHERE project_id = '{project_id}'
+            AND evaluation_id = '{evaluation_id}'
+            AND name = '{name}'
+            AND score_type = 'CATEGORICAL'
+        GROUP BY label
+        ORDER BY count DESC, label ASC",
+    );
+
+    let rows: Vec<EvaluationScoreLabelFrequency> = execute_query(&clickhouse, &query).await?;
+    Ok(rows)
+}
+
+/// Label frequencies from the labels of the scores, in the order of
+/// `get_evaluation_score_label_frequencies`
+pub fn label_frequencies<'a>(
+    labels: impl IntoIterator<Item = &'a str>,
+) -> Vec<EvaluationScoreLabelFrequency> {
+    let mut counts = BTreeMap::<&str, u64>::new();
+    for label in labels {
+        *counts.entry(label).or_default() += 1;
+    }
+    let total = counts.values().sum::<u64>();
+
+    let mut frequencies = counts
+        .into_iter()
+        .map(|(label, count)| EvaluationScoreLabelFrequency {
+            label: label.to_string(),
+            count,
+            frequency: count as f64 / total as f64,
+        })
+        .collect::<Vec<_>>();
+    // Stable, so labels of the same count stay in alphabetical order
+    frequencies.sort_by(|a, b| b.count.cmp(&a.count));
+    frequencies
+}
+
+#[derive(Row, Deserialize)]
+struct EvaluationScoreTrendRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    evaluation_id: Uuid,
+    timestamp: i64,
+    count: u64,
+    average_value: f64,
+    p10: f64,
+    median: f64,
+    p90: f64,
+}
+
+/// Score of one evaluation of a group
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScoreTrendPoint {
+    pub evaluation_id: Uuid,
+    /// Time of the evaluation's first score
+    pub timestamp: DateTime<Utc>,
+    pub count: u64,
+    pub average_value: f64,
+    /// The band around the average, between the 10th and 90th percentiles
+    pub p10: f64,
+    pub median: f64,
+    pub p90: f64,
+}
+
+/// Average and percentiles of the score in each evaluation of the group, the oldest evaluation
+/// first. Evaluations without scores of the name are left out.
+pub async fn get_evaluation_score_trend(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    group_id: String,
+    name: String,
+) -> Result<Vec<EvaluationScoreTrendPoint>> {
+    validate_string_against_injection(&group_id)?;
+    validate_string_against_injection(&name)?;
+
+    let query = format!(
+        "SELECT
+            evaluation_id,
+            toUnixTimestamp64Nano(min(timestamp)) AS timestamp,
+            count() AS count,
+            avg(value) AS average_value,
+            quantileExact(0.1)(value) AS p10,
+            quantileExact(0.5)(value) AS median,
+            quantileExact(0.9)(value) AS p90
+        FROM evaluation_scores
+        WHERE project_id = '{project_id}'
+            AND group_id = '{group_id}'
+            AND name = '{name}'
+            AND score_type != 'CATEGORICAL'
+        GROUP BY evaluation_id
+        ORDER BY timestamp ASC, evaluation_id ASC",
+    );
+
+    let rows: Vec<EvaluationScoreTrendRow> = execute_query(&clickhouse, &query).await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| EvaluationScoreTrendPoint {
+            evaluation_id: row.evaluation_id,
+            timestamp: nanoseconds_to_chrono(row.timestamp),
+            count: row.count,
+            average_value: row.average_value,
+            p10: row.p10,
+            median: row.median,
+            p90: row.p90,
+        })
+        .collect())
+}
+
+#[derive(Row, Deserialize)]
+struct EvaluationScoreNameRow {
+    name: String,
+}
+
+/// Distinct names of the project's numeric and boolean evaluation scores
+pub async fn get_numeric_evaluation_score_names(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+) -> Result<Vec<String>> {
+    let query = format!(
+        "SELECT DISTINCT name
+        FROM evaluation_scores
+        WHERE project_id = '{project_id}'
+            AND score_type != 'CATEGORICAL'
+        ORDER BY name",
+    );
+
+    let rows: Vec<EvaluationScoreNameRow> = execute_query(&clickhouse, &query).await?;
+    Ok(rows.into_iter().map(|row| row.name).collect())
+}
+
+/// Average score per time interval across the project's evaluations, pass rate for booleans
+pub async fn get_evaluation_score_time_series(
+    clickhouse: clickhouse::Client,
+    group_by_interval: GroupByInterval,
+    project_id: Uuid,
+    name: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<MetricTimeValue<f64>>> {
+    validate_string_against_injection(&name)?;
+    let ch_round_time = group_by_interval.to_ch_truncate_time();
+    let ch_start_time = start_time.timestamp();
+    let ch_end_time = end_time.timestamp();
+
+    let query = format!(
+        "
+    SELECT
+        {ch_round_time}(timestamp) AS time,
+        AVG(value) AS value
+    FROM evaluation_scores
+    WHERE
+        project_id = '{project_id}'
+        AND name = '{name}'
+        AND score_type != 'CATEGORICAL'
+        AND timestamp >= fromUnixTimestamp({ch_start_time})
+        AND timestamp <= fromUnixTimestamp({ch_end_time})
+    {}",
+        group_by_time_absolute_statement(start_time, end_time, group_by_interval)
+    );
+
+    execute_query(&clickhouse, &query).await
+}
+
 #[derive(Row, Deserialize)]
 pub struct EvaluationScoreBucket {
     pub lower_bound: f64,
@@ -175,6 +490,7 @@ JOIN intervals ON 1 = 1
 WHERE project_id = '{project_id}'
 AND evaluation_id = '{evaluation_id}'
 AND name = '{name}'
+AND score_type != 'CATEGORICAL'
 GROUP BY intervals.lower_bound, intervals.upper_bound, intervals.interval_num
 ORDER BY intervals.interval_num",
         lower_bound, step_size, upper_bound, lower_bound, step_size
@@ -185,6 +501,175 @@ ORDER BY intervals.interval_num",
     Ok(rows)
 }
 
+/// Bucket of a histogram shared by several evaluations
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScoreHistogramBucket {
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+    /// Heights in the same order as the evaluation ids
+    pub heights: Vec<u64>,
+}
+
+/// Equal-width buckets fitted to the data, starting at the minimum value
+#[derive(Debug, PartialEq)]
+pub struct HistogramBounds {
+    pub lower_bound: f64,
+    pub bucket_width: f64,
+    pub bucket_count: u64,
+}
+
+impl HistogramBounds {
+    /// Freedman–Diaconis bucket width `2 * IQR / cbrt(n)`, with at most `max_bucket_count`
+    /// buckets. Falls back to Sturges' rule `log2(n) + 1` buckets when the IQR is 0, e.g. for
+    /// mostly binary scores. Must match the computation in `get_evaluation_score_histogram`.
+    pub fn new(
+        min_value: f64,
+        max_value: f64,
+        iqr: f64,
+        count: u64,
+        max_bucket_count: u64,
+    ) -> Self {
+        if max_value <= min_value {
+            return Self {
+                lower_bound: min_value,
+                bucket_width: 1.0,
+                bucket_count: 1,
+            };
+        }
+        let range = max_value - min_value;
+        let bucket_count = if iqr > 0.0 {
+            (range / (2.0 * iqr / (count as f64).cbrt())).ceil()
+        } else {
+            (count as f64).log2().ceil() + 1.0
+        };
+        let bucket_count = bucket_count.min(max_bucket_count as f64).max(1.0) as u64;
+        Self {
+            lower_bound: min_value,
+            bucket_width: range / bucket_count as f64,
+            bucket_count,
+        }
+    }
+
+    /// The maximum value falls into the last bucket
+    pub fn bucket_index(&self, value: f64) -> u64 {
+        (((value - self.lower_bound) / self.bucket_width).floor() as u64).min(self.bucket_count - 1)
+    }
+
+    /// All buckets, including the empty ones, from the heights by evaluation id and bucket index
+    pub fn histogram(
+        &self,
+        evaluation_ids: &[Uuid],
+        heights: impl IntoIterator<Item = (Uuid, u64, u64)>,
+    ) -> Vec<EvaluationScoreHistogramBucket> {
+        let mut buckets = (0..self.bucket_count)
+            .map(|index| EvaluationScoreHistogramBucket {
+                lower_bound: self.lower_bound + index as f64 * self.bucket_width,
+                upper_bound: self.lower_bound + (index + 1) as f64 * self.bucket_width,
+                heights: vec![0; evaluation_ids.len()],
+            })
+            .collect::<Vec<_>>();
+        for (evaluation_id, bucket_index, height) in heights {
+            let Some(position) = evaluation_ids.iter().position(|id| *id == evaluation_id) else {
+                continue;
+            };
+            if let Some(bucket) = buckets.get_mut(bucket_index as usize) {
+                bucket.heights[position] += height;
+            }
+        }
+        buckets
+    }
+}
+
+#[derive(Row, Deserialize)]
+struct EvaluationScoreHistogramRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    evaluation_id: Uuid,
+    lower_bound: f64,
+    bucket_width: f64,
+    bucket_count: u64,
+    bucket_index: u64,
+    height: u64,
+}
+
+/// Histogram of the evaluations' scores with bounds and bucket width computed from the data in
+/// the same query, see `HistogramBounds::new`. Empty if there are no scores.
+pub async fn get_evaluation_score_histogram(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    evaluation_ids: &Vec<Uuid>,
+    name: String,
+    max_bucket_count: u64,
+) -> Result<Vec<EvaluationScoreHistogramBucket>> {
+    validate_string_against_injection(&name)?;
+
+    let evaluation_ids_str = evaluation_ids
+        .iter()
+        .map(|id| format!("'{}'", id))
+        .collect::<Vec<String>>()
+        .join(",");
+    let filter = format!(
+        "project_id = '{project_id}'
+        AND evaluation_id IN ({evaluation_ids_str})
+        AND name = '{name}'
+        AND score_type != 'CATEGORICAL'"
+    );
+
+    let query = format!(
+        "
+WITH
+    (
+        SELECT tuple(
+            min(value),
+            max(value),
+            quantileExact(0.75)(value) - quantileExact(0.25)(value),
+            count()
+        )
+        FROM evaluation_scores
+        WHERE {filter}
+    ) AS stats,
+    tupleElement(stats, 1) AS min_value,
+    tupleElement(stats, 2) AS max_value,
+    tupleElement(stats, 3) AS iqr,
+    tupleElement(stats, 4) AS total_count,
+    toUInt64(if(
+        max_value > min_value,
+        greatest(1, least({max_bucket_count}, if(
+            iqr > 0,
+            ceil((max_value - min_value) / (2 * iqr / cbrt(total_count))),
+            ceil(log2(total_count)) + 1
+        ))),
+        1
+    )) AS buckets,
+    if(max_value > min_value, (max_value - min_value) / buckets, 1) AS width
+SELECT
+    evaluation_id,
+    min_value AS lower_bound,
+    width AS bucket_width,
+    buckets AS bucket_count,
+    least(toUInt64(floor((value - min_value) / width)), buckets - 1) AS bucket_index,
+    count() AS height
+FROM evaluation_scores
+WHERE {filter}
+GROUP BY evaluation_id, bucket_index"
+    );
+
+    let rows: Vec<EvaluationScoreHistogramRow> = execute_query(&clickhouse, &query).await?;
+    let Some(first) = rows.first() else {
+        return Ok(Vec::new());
+    };
+    let bounds = HistogramBounds {
+        lower_bound: first.lower_bound,
+        bucket_width: first.bucket_width,
+        bucket_count: first.bucket_count,
+    };
+    Ok(bounds.histogram(
+        evaluation_ids,
+        rows.iter()
+            .map(|row| (row.evaluation_id, row.bucket_index, row.height)),
+    ))
+}
+
 #[derive(Row, Deserialize, Clone)]
 pub struct ComparedEvaluationScoresBounds {
     pub upper_bound: f64,
@@ -211,9 +696,312 @@ SELECT
 FROM evaluation_scores
 WHERE project_id = '{project_id}'
     AND evaluation_id IN ({evaluation_ids_str})
-    AND name = '{name}'",
+    AND name = '{name}'
+    AND score_type != 'CATEGORICAL'",
     );
 
     let rows: Vec<ComparedEvaluationScoresBounds> = execute_query(&clickhouse, &query).await?;
     Ok(rows[0].clone())
 }
+
+#[derive(Row, Deserialize)]
+struct EvaluationScorePercentileRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    result_id: Uuid,
+    name: String,
+    value: f64,
+    percentile: f64,
+}
+
+/// Score of an evaluation datapoint as the percentage of the baseline evaluation's scores of the
+/// same name that are lower or equal
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScorePercentile {
+    pub result_id: Uuid,
+    pub name: String,
+    pub value: f64,
+    /// 0 to 100
+    pub percentile: f64,
+}
+
+/// Scores whose name the baseline evaluation doesn't have are not returned
+pub async fn get_evaluation_score_percentiles(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    evaluation_id: Uuid,
+    baseline_evaluation_id: Uuid,
+) -> Result<Vec<EvaluationScorePercentile>> {
+    let query = format!(
+        "
+SELECT
+    evaluation_scores.result_id AS result_id,
+    evaluation_scores.name AS name,
+    evaluation_scores.value AS value,
+    arrayCount(x -> x <= evaluation_scores.value, baseline.values) * 100 / length(baseline.values)
+        AS percentile
+FROM evaluation_scores
+JOIN (
+    SELECT name, groupArray(value) AS values
+    FROM evaluation_scores
+    WHERE project_id = '{project_id}'
+        AND evaluation_id = '{baseline_evaluation_id}'
+        AND score_type != 'CATEGORICAL'
+    GROUP BY name
+) AS baseline ON baseline.name = evaluation_scores.name
+WHERE evaluation_scores.project_id = '{project_id}'
+    AND evaluation_scores.evaluation_id = '{evaluation_id}'
+    AND evaluation_scores.score_type != 'CATEGORICAL'
+ORDER BY evaluation_scores.name, evaluation_scores.result_id",
+    );
+
+    let rows: Vec<EvaluationScorePercentileRow> = execute_query(&clickhouse, &query).await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| EvaluationScorePercentile {
+            result_id: row.result_id,
+            name: row.name,
+            value: row.value,
+            percentile: row.percentile,
+        })
+        .collect())
+}
+
+#[derive(Row, Deserialize)]
+struct EvaluationScoreDiffRow {
+    name: String,
+    datapoint_key: String,
+    #[serde(with = "clickhouse::serde::uuid")]
+    result_id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    baseline_result_id: Uuid,
+    value: f64,
+    baseline_value: f64,
+}
+
+/// Score of a datapoint in an evaluation next to the score of the same datapoint in the
+/// baseline evaluation
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScoreDiff {
+    pub name: String,
+    pub datapoint_key: String,
+    pub result_id: Uuid,
+    pub baseline_result_id: Uuid,
+    pub value: f64,
+    pub baseline_value: f64,
+    /// `value - baseline_value`
+    pub delta: f64,
+}
+
+impl EvaluationScoreDiff {
+    pub fn new(
+        name: String,
+        datapoint_key: String,
+        result_id: Uuid,
+        baseline_result_id: Uuid,
+        value: f64,
+        baseline_value: f64,
+    ) -> Self {
+        Self {
+            name,
+            datapoint_key,
+            result_id,
+            baseline_result_id,
+            value,
+            baseline_value,
+            delta: value - baseline_value,
+        }
+    }
+}
+
+/// Per-datapoint score deltas between the evaluation and the baseline evaluation. Only the
+/// datapoints scored in both evaluations are returned. A datapoint repeated within an evaluation
+/// counts once, with its average score.
+pub async fn get_evaluation_score_diffs(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    evaluation_id: Uuid,
+    baseline_evaluation_id: Uuid,
+) -> Result<Vec<EvaluationScoreDiff>> {
+    let scores_per_datapoint = |evaluation_id: Uuid| {
+        format!(
+            "SELECT name, datapoint_key, any(result_id) AS result_id, avg(value) AS value
+    FROM evaluation_scores
+    WHERE project_id = '{project_id}'
+        AND evaluation_id = '{evaluation_id}'
+        AND datapoint_key != ''
+        AND score_type != 'CATEGORICAL'
+    GROUP BY name, datapoint_key"
+        )
+    };
+    let query = format!(
+        "
+SELECT
+    current.name AS name,
+    current.datapoint_key AS datapoint_key,
+    current.result_id AS result_id,
+    baseline.result_id AS baseline_result_id,
+    current.value AS value,
+    baseline.value AS baseline_value
+FROM ({}) AS current
+JOIN ({}) AS baseline
+    ON baseline.name = current.name AND baseline.datapoint_key = current.datapoint_key
+ORDER BY name, datapoint_key",
+        scores_per_datapoint(evaluation_id),
+        scores_per_datapoint(baseline_evaluation_id),
+    );
+
+    let rows: Vec<EvaluationScoreDiffRow> = execute_query(&clickhouse, &query).await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            EvaluationScoreDiff::new(
+                row.name,
+                row.datapoint_key,
+                row.result_id,
+                row.baseline_result_id,
+                row.value,
+                row.baseline_value,
+            )
+        })
+        .collect())
+}
+
+/// How the scores of one name changed between the baseline evaluation and the evaluation.
+/// Higher scores are considered better.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScoreChangeSummary {
+    pub name: String,
+    pub compared_count: u64,
+    pub improved_count: u64,
+    pub regressed_count: u64,
+    pub unchanged_count: u64,
+    pub average_delta: f64,
+}
+
+/// Summaries of the diffs, ordered by score name
+pub fn summarize_score_diffs(diffs: &[EvaluationScoreDiff]) -> Vec<EvaluationScoreChangeSummary> {
+    let mut diffs_by_name = BTreeMap::<&str, Vec<f64>>::new();
+    for diff in diffs {
+        diffs_by_name
+            .entry(&diff.name)
+            .or_default()
+            .push(diff.delta);
+    }
+
+    diffs_by_name
+        .into_iter()
+        .map(|(name, deltas)| {
+            let count = |f: fn(&f64) -> bool| deltas.iter().filter(|delta| f(delta)).count() as u64;
+            EvaluationScoreChangeSummary {
+                name: name.to_string(),
+                compared_count: deltas.len() as u64,
+                improved_count: count(|delta| *delta > f64::EPSILON),
+                regressed_count: count(|delta| *delta < -f64::EPSILON),
+                unchanged_count: count(|delta| delta.abs() <= f64::EPSILON),
+                average_delta: deltas.iter().sum::<f64>() / deltas.len() as f64,
+            }
+        })
+        .collect()
+}
+
+#[derive(Row, Deserialize)]
+struct FilteredEvaluationScoreRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    evaluation_id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    result_id: Uuid,
+    group_id: String,
+    name: String,
+    value: f64,
+    label: String,
+    score_type_name: String,
+    source_name: String,
+    timestamp_ns: i64,
+}
+
+/// Evaluation score matching a filter expression
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredEvaluationScore {
+    pub evaluation_id: Uuid,
+    pub result_id: Uuid,
+    pub group_id: String,
+    pub name: String,
+    pub value: f64,
+    /// Label of categorical scores
+    pub label: Option<String>,
+    pub score_type: String,
+    pub source: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<FilteredEvaluationScoreRow> for FilteredEvaluationScore {
+    fn from(row: FilteredEvaluationScoreRow) -> Self {
+        Self {
+            evaluation_id: row.evaluation_id,
+            result_id: row.result_id,
+            group_id: row.group_id,
+            name: row.name,
+            value: row.value,
+            label: Some(row.label).filter(|label| !label.is_empty()),
+            score_type: row.score_type_name,
+            source: row.source_name,
+            timestamp: nanoseconds_to_chrono(row.timestamp_ns),
+        }
+    }
+}
+
+/// Evaluation scores of the period that match the filter, compiled for
+/// `FilterTarget::EvaluationScores`, the newest first
+pub async fn query_evaluation_scores(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    filter: &CompiledFilter,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: u64,
+    offset: u64,
+) -> Result<Vec<FilteredEvaluationScore>> {
+    if !is_feature_enabled(Feature::FullBuild) {
+        return Ok(Vec::new());
+    }
+    // the aliases differ from the column names, which the filter refers to
+    let query_string = format!(
+        "SELECT
+            evaluation_id,
+            result_id,
+            group_id,
+            name,
+            value,
+            label,
+            toString(score_type) AS score_type_name,
+            toString(source) AS source_name,
+            toUnixTimestamp64Nano(timestamp) AS timestamp_ns
+        FROM evaluation_scores
+        WHERE project_id = ?
+            AND timestamp >= fromUnixTimestamp64Nano(?)
+            AND timestamp <= fromUnixTimestamp64Nano(?)
+            AND {}
+        ORDER BY timestamp DESC
+        LIMIT ? OFFSET ?",
+        filter.sql
+    );
+
+    let query = clickhouse
+        .query(&query_string)
+        .bind(project_id.to_string())
+        .bind(chrono_to_nanoseconds(start_time))
+        .bind(chrono_to_nanoseconds(end_time));
+    chaos::clickhouse_latency().await;
+    let rows = filter
+        .bind(query)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all::<FilteredEvaluationScoreRow>()
+        .await?;
+
+    Ok(rows.into_iter().map(Into::into).collect())
+}
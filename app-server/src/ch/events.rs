@@ -1,8 +1,8 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use clickhouse::Row;
-use serde::Serialize;
-use serde_repr::Serialize_repr;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 use uuid::Uuid;
 
 use crate::db::{self, event_templates::EventTemplate};
@@ -11,12 +11,12 @@ use super::{
     modifiers::GroupByInterval,
     utils::{
         chrono_to_nanoseconds, execute_query, group_by_time_absolute_statement,
-        group_by_time_relative_statement,
+        group_by_time_relative_statement, insert_rows,
     },
     MetricTimeValue,
 };
 
-#[derive(Debug, Serialize_repr)]
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
 pub enum EventSource {
     CODE = 0,
@@ -34,7 +34,7 @@ impl From<db::events::EventSource> for EventSource {
     }
 }
 
-#[derive(Debug, Serialize_repr)]
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
 pub enum EventType {
     BOOLEAN = 0,
@@ -52,7 +52,7 @@ impl From<db::event_templates::EventType> for EventType {
     }
 }
 
-#[derive(Row, Serialize)]
+#[derive(Row, Serialize, Deserialize)]
 pub struct CHEvent {
     #[serde(with = "clickhouse::serde::uuid")]
     pub id: Uuid,
@@ -92,28 +92,7 @@ pub async fn insert_events(clickhouse: clickhouse::Client, events: Vec<CHEvent>)
         return Ok(());
     }
 
-    let ch_insert = clickhouse.insert("events");
-    match ch_insert {
-        Ok(mut ch_insert) => {
-            for event in events {
-                ch_insert.write(&event).await?;
-            }
-            let ch_insert_end_res = ch_insert.end().await;
-            match ch_insert_end_res {
-                Ok(_) => Ok(()),
-                Err(e) => Err(anyhow::anyhow!(
-                    "Clickhouse events insertion failed: {:?}",
-                    e
-                )),
-            }
-        }
-        Err(e) => {
-            return Err(anyhow::anyhow!(
-                "Failed to insert events into Clickhouse: {:?}",
-                e
-            ));
-        }
-    }
+    insert_rows(&clickhouse, "events", &events).await
 }
 
 pub async fn get_total_event_count_metrics_relative(
@@ -0,0 +1,711 @@
+//! Filter expressions over spans, traces and evaluation scores, e.g.
+//! `model = "gpt-4o" AND (latency > 2.5 OR attributes.customer_tier IN ("enterprise", "pro"))`.
+//!
+//! Conditions compare a field with `=`, `!=`, `<`, `<=`, `>`, `>=`, `IN (...)`, `NOT IN (...)`
+//! or `CONTAINS`, and are combined with `AND`, `OR`, `NOT` and parentheses. Values are strings in
+//! single or double quotes, numbers, `true` or `false`. Expressions are compiled against the
+//! fields of a target only, and their values are bound as query arguments, never inlined.
+
+use std::{collections::HashMap, fmt};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+const MAX_EXPRESSION_LENGTH: usize = 4096;
+const MAX_CONDITIONS: usize = 32;
+const MAX_DEPTH: usize = 8;
+const RESERVED_WORDS: [&str; 7] = ["AND", "OR", "NOT", "IN", "CONTAINS", "TRUE", "FALSE"];
+const ATTRIBUTE_PREFIX: &str = "attributes.";
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum FilterValue {
+    String(String),
+    Number(f64),
+    Bool(bool),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterOperator {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    In,
+    NotIn,
+    /// Case-insensitive substring match
+    Contains,
+}
+
+impl fmt::Display for FilterOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            FilterOperator::Eq => "=",
+            FilterOperator::Ne => "!=",
+            FilterOperator::Lt => "<",
+            FilterOperator::Lte => "<=",
+            FilterOperator::Gt => ">",
+            FilterOperator::Gte => ">=",
+            FilterOperator::In => "IN",
+            FilterOperator::NotIn => "NOT IN",
+            FilterOperator::Contains => "CONTAINS",
+        };
+        write!(f, "{}", operator)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterCondition {
+    pub field: String,
+    pub operator: FilterOperator,
+    /// One value, except for `IN` and `NOT IN`
+    pub values: Vec<FilterValue>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterExpression {
+    Condition(FilterCondition),
+    And(Vec<FilterExpression>),
+    Or(Vec<FilterExpression>),
+    Not(Box<FilterExpression>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    String(String),
+    Number(f64),
+    Operator(FilterOperator),
+    LeftParen,
+    RightParen,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "{}", ident),
+            Token::String(s) => write!(f, "{:?}", s),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Operator(operator) => write!(f, "{}", operator),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        Some(token) => format!("'{}'", token),
+        None => "the end of the filter".to_string(),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' | ')' | ',' | '=' => {
+                tokens.push(match c {
+                    '(' => Token::LeftParen,
+                    ')' => Token::RightParen,
+                    ',' => Token::Comma,
+                    _ => Token::Operator(FilterOperator::Eq),
+                });
+                i += 1;
+            }
+            '!' if next == Some('=') => {
+                tokens.push(Token::Operator(FilterOperator::Ne));
+                i += 2;
+            }
+            '<' | '>' => {
+                let operator = match (c, next == Some('=')) {
+                    ('<', false) => FilterOperator::Lt,
+                    ('<', true) => FilterOperator::Lte,
+                    ('>', false) => FilterOperator::Gt,
+                    _ => FilterOperator::Gte,
+                };
+                tokens.push(Token::Operator(operator));
+                i += if next == Some('=') { 2 } else { 1 };
+            }
+            '"' | '\'' => {
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => bail!("Unterminated string in the filter"),
+                        Some('\\') => {
+                            let escaped = chars
+                                .get(i + 1)
+                                .ok_or_else(|| anyhow!("Unterminated string in the filter"))?;
+                            s.push(*escaped);
+                            i += 2;
+                        }
+                        Some(quote) if *quote == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(other) => {
+                            s.push(*other);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::String(s));
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let start = i;
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric()
+                        || chars[i] == '.'
+                        || (matches!(chars[i], '-' | '+') && matches!(chars[i - 1], 'e' | 'E')))
+                {
+                    i += 1;
+                }
+                let text = chars[start..i].iter().collect::<String>();
+                let number = text
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|number| number.is_finite())
+                    .ok_or_else(|| anyhow!("Invalid number {} in the filter", text))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '_' | '.' | '-'))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            c => bail!("Unexpected character '{}' in the filter", c),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    conditions: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.advance() {
+            Some(token) if token == expected => Ok(()),
+            token => bail!(
+                "Expected '{}', found {}",
+                expected,
+                describe(token.as_ref())
+            ),
+        }
+    }
+
+    fn parse_or(&mut self, depth: usize) -> Result<FilterExpression> {
+        let mut operands = vec![self.parse_and(depth)?];
+        while self.is_keyword("OR") {
+            self.position += 1;
+            operands.push(self.parse_and(depth)?);
+        }
+        Ok(match operands.len() {
+            1 => operands.remove(0),
+            _ => FilterExpression::Or(operands),
+        })
+    }
+
+    fn parse_and(&mut self, depth: usize) -> Result<FilterExpression> {
+        let mut operands = vec![self.parse_unary(depth)?];
+        while self.is_keyword("AND") {
+            self.position += 1;
+            operands.push(self.parse_unary(depth)?);
+        }
+        Ok(match operands.len() {
+            1 => operands.remove(0),
+            _ => FilterExpression::And(operands),
+        })
+    }
+
+    fn parse_unary(&mut self, depth: usize) -> Result<FilterExpression> {
+        if depth > MAX_DEPTH {
+            bail!("Filter is nested more than {} levels deep", MAX_DEPTH);
+        }
+        if self.is_keyword("NOT") {
+            self.position += 1;
+            let operand = self.parse_unary(depth + 1)?;
+            return Ok(FilterExpression::Not(Box::new(operand)));
+        }
+        if self.peek() == Some(&Token::LeftParen) {
+            self.position += 1;
+            let expression = self.parse_or(depth + 1)?;
+            self.expect(Token::RightParen)?;
+            return Ok(expression);
+        }
+        self.parse_condition()
+    }
+
+    fn parse_condition(&mut self) -> Result<FilterExpression> {
+        self.conditions += 1;
+        if self.conditions > MAX_CONDITIONS {
+            bail!("Filter has more than {} conditions", MAX_CONDITIONS);
+        }
+        let field = match self.advance() {
+            Some(Token::Ident(field))
+                if !RESERVED_WORDS
+                    .iter()
+                    .any(|word| word.eq_ignore_ascii_case(&field)) =>
+            {
+                field
+            }
+            token => bail!("Expected a field, found {}", describe(token.as_ref())),
+        };
+        let operator = match self.advance() {
+            Some(Token::Operator(operator)) => operator,
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("IN") => FilterOperator::In,
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("CONTAINS") => {
+                FilterOperator::Contains
+            }
+            Some(Token::Ident(ident))
+                if ident.eq_ignore_ascii_case("NOT") && self.is_keyword("IN") =>
+            {
+                self.position += 1;
+                FilterOperator::NotIn
+            }
+            token => bail!(
+                "Expected an operator after {}, found {}",
+                field,
+                describe(token.as_ref())
+            ),
+        };
+        let values = match operator {
+            FilterOperator::In | FilterOperator::NotIn => {
+                self.expect(Token::LeftParen)?;
+                let mut values = vec![self.parse_value()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.position += 1;
+                    values.push(self.parse_value()?);
+                }
+                self.expect(Token::RightParen)?;
+                values
+            }
+            _ => vec![self.parse_value()?],
+        };
+
+        Ok(FilterExpression::Condition(FilterCondition {
+            field,
+            operator,
+            values,
+        }))
+    }
+
+    fn parse_value(&mut self) -> Result<FilterValue> {
+        match self.advance() {
+            Some(Token::String(s)) => Ok(FilterValue::String(s)),
+            Some(Token::Number(n)) => Ok(FilterValue::Number(n)),
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("true") => {
+                Ok(FilterValue::Bool(true))
+            }
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("false") => {
+                Ok(FilterValue::Bool(false))
+            }
+            token => bail!(
+                "Expected a value, found {}. Strings must be quoted",
+                describe(token.as_ref())
+            ),
+        }
+    }
+}
+
+/// Rows that expressions are compiled for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterTarget {
+    Spans,
+    /// Aggregates of the spans of each trace, compiled into a `HAVING` clause
+    Traces,
+    EvaluationScores,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FieldKind {
+    String,
+    Number,
+    Bool,
+}
+
+const SPAN_LATENCY: &str =
+    "(toUnixTimestamp64Nano(end_time) - toUnixTimestamp64Nano(start_time)) / 1e9";
+const SPAN_TYPE_NAME: &str = "arrayElement(\
+    ['DEFAULT', 'LLM', 'PIPELINE', 'EXECUTOR', 'EVALUATOR', 'EVALUATION'], span_type + 1)";
+const TRACE_LATENCY: &str =
+    "(max(toUnixTimestamp64Nano(end_time)) - min(toUnixTimestamp64Nano(start_time))) / 1e9";
+
+/// Field names with their ClickHouse expressions
+const SPAN_FIELDS: &[(&str, &str, FieldKind)] = &[
+    ("name", "name", FieldKind::String),
+    ("span_type", SPAN_TYPE_NAME, FieldKind::String),
+    ("path", "path", FieldKind::String),
+    ("trace_id", "toString(trace_id)", FieldKind::String),
+    ("model", "model", FieldKind::String),
+    ("provider", "provider", FieldKind::String),
+    ("user_id", "user_id", FieldKind::String),
+    ("session_id", "session_id", FieldKind::String),
+    ("environment", "environment", FieldKind::String),
+    ("region", "region", FieldKind::String),
+    ("sdk_language", "sdk_language", FieldKind::String),
+    ("sdk_version", "sdk_version", FieldKind::String),
+    ("agent_action", "agent_action", FieldKind::String),
+    ("latency", SPAN_LATENCY, FieldKind::Number),
+    ("input_tokens", "input_tokens", FieldKind::Number),
+    ("output_tokens", "output_tokens", FieldKind::Number),
+    ("total_tokens", "total_tokens", FieldKind::Number),
+    ("cost", "total_cost", FieldKind::Number),
+    ("is_error", "is_error", FieldKind::Bool),
+];
+
+const TRACE_FIELDS: &[(&str, &str, FieldKind)] = &[
+    ("trace_id", "toString(trace_id)", FieldKind::String),
+    // name of the first span, which is the top span unless it's still being ingested
+    ("name", "argMin(name, start_time)", FieldKind::String),
+    ("user_id", "any(user_id)", FieldKind::String),
+    ("session_id", "any(session_id)", FieldKind::String),
+    ("environment", "any(environment)", FieldKind::String),
+    ("latency", TRACE_LATENCY, FieldKind::Number),
+    ("total_tokens", "sum(total_tokens)", FieldKind::Number),
+    ("cost", "sum(total_cost)", FieldKind::Number),
+    ("span_count", "count()", FieldKind::Number),
+    ("has_error", "max(is_error)", FieldKind::Bool),
+];
+
+const EVALUATION_SCORE_FIELDS: &[(&str, &str, FieldKind)] = &[
+    ("name", "name", FieldKind::String),
+    ("value", "value", FieldKind::Number),
+    ("label", "label", FieldKind::String),
+    ("score_type", "toString(score_type)", FieldKind::String),
+    ("source", "toString(source)", FieldKind::String),
+    (
+        "evaluation_id",
+        "toString(evaluation_id)",
+        FieldKind::String,
+    ),
+    ("group_id", "group_id", FieldKind::String),
+    ("datapoint_key", "datapoint_key", FieldKind::String),
+];
+
+impl FilterTarget {
+    fn fields(&self) -> &'static [(&'static str, &'static str, FieldKind)] {
+        match self {
+            FilterTarget::Spans => SPAN_FIELDS,
+            FilterTarget::Traces => TRACE_FIELDS,
+            FilterTarget::EvaluationScores => EVALUATION_SCORE_FIELDS,
+        }
+    }
+
+    /// ClickHouse expression of the field. Spans also have the promoted attributes as
+    /// `attributes.<key>`, see `traces::promoted_attributes`.
+    fn field(
+        &self,
+        name: &str,
+        attribute_slots: &HashMap<String, usize>,
+    ) -> Result<(String, FieldKind)> {
+        if let Some(key) = name.strip_prefix(ATTRIBUTE_PREFIX) {
+            if *self != FilterTarget::Spans {
+                bail!("Attributes can only be filtered on for spans");
+            }
+            let Some(slot) = attribute_slots.get(key) else {
+                bail!(
+                    "Attribute {} is not promoted, promote it in the project settings to filter on it",
+                    key
+                );
+            };
+            return Ok((format!("attribute_{slot}"), FieldKind::String));
+        }
+        self.fields()
+            .iter()
+            .find(|(field, _, _)| *field == name)
+            .map(|(_, expression, kind)| (expression.to_string(), *kind))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown field {}, the fields are {}",
+                    name,
+                    self.fields()
+                        .iter()
+                        .map(|(field, _, _)| *field)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// ClickHouse predicate with a `?` placeholder for each of the params
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledFilter {
+    pub sql: String,
+    pub params: Vec<FilterValue>,
+}
+
+impl CompiledFilter {
+    /// Matches every row
+    pub fn all() -> Self {
+        Self {
+            sql: "true".to_string(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Binds the params, in the place of the filter's placeholders in the query
+    pub fn bind(&self, mut query: clickhouse::query::Query) -> clickhouse::query::Query {
+        for param in &self.params {
+            query = query.bind(param);
+        }
+        query
+    }
+}
+
+impl FilterExpression {
+    pub fn parse(input: &str) -> Result<Self> {
+        if input.len() > MAX_EXPRESSION_LENGTH {
+            bail!(
+                "Filter must be at most {} characters",
+                MAX_EXPRESSION_LENGTH
+            );
+        }
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            bail!("Filter is empty");
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            conditions: 0,
+        };
+        let expression = parser.parse_or(0)?;
+        if let Some(token) = parser.peek() {
+            bail!(
+                "Unexpected '{}', conditions must be joined with AND or OR",
+                token
+            );
+        }
+        Ok(expression)
+    }
+
+    /// Names of the fields the expression filters on
+    pub fn fields(&self) -> Vec<&str> {
+        match self {
+            FilterExpression::Condition(condition) => vec![condition.field.as_str()],
+            FilterExpression::And(operands) | FilterExpression::Or(operands) => operands
+                .iter()
+                .flat_map(|operand| operand.fields())
+                .collect(),
+            FilterExpression::Not(operand) => operand.fields(),
+        }
+    }
+
+    pub fn compile(
+        &self,
+        target: FilterTarget,
+        attribute_slots: &HashMap<String, usize>,
+    ) -> Result<CompiledFilter> {
+        let mut params = Vec::new();
+        let sql = self.compile_into(target, attribute_slots, &mut params)?;
+        Ok(CompiledFilter { sql, params })
+    }
+
+    fn compile_into(
+        &self,
+        target: FilterTarget,
+        attribute_slots: &HashMap<String, usize>,
+        params: &mut Vec<FilterValue>,
+    ) -> Result<String> {
+        let condition = match self {
+            FilterExpression::Condition(condition) => condition,
+            FilterExpression::And(operands) | FilterExpression::Or(operands) => {
+                let joiner = match self {
+                    FilterExpression::And(_) => " AND ",
+                    _ => " OR ",
+                };
+                let operands = operands
+                    .iter()
+                    .map(|operand| operand.compile_into(target, attribute_slots, params))
+                    .collect::<Result<Vec<_>>>()?;
+                return Ok(format!("({})", operands.join(joiner)));
+            }
+            FilterExpression::Not(operand) => {
+                return Ok(format!(
+                    "NOT ({})",
+                    operand.compile_into(target, attribute_slots, params)?
+                ));
+            }
+        };
+
+        let (expression, kind) = target.field(&condition.field, attribute_slots)?;
+        let operator_allowed = match kind {
+            FieldKind::String => !matches!(
+                condition.operator,
+                FilterOperator::Lt | FilterOperator::Lte | FilterOperator::Gt | FilterOperator::Gte
+            ),
+            FieldKind::Number => condition.operator != FilterOperator::Contains,
+            FieldKind::Bool => {
+                matches!(condition.operator, FilterOperator::Eq | FilterOperator::Ne)
+            }
+        };
+        if !operator_allowed {
+            bail!(
+                "{} can't be used with {}",
+                condition.operator,
+                condition.field
+            );
+        }
+        for value in &condition.values {
+            let matches_kind = matches!(
+                (kind, value),
+                (FieldKind::String, FilterValue::String(_))
+                    | (FieldKind::Number, FilterValue::Number(_))
+                    | (FieldKind::Bool, FilterValue::Bool(_))
+            );
+            if !matches_kind {
+                bail!(
+                    "{} is compared with {} values",
+                    condition.field,
+                    match kind {
+                        FieldKind::String => "quoted string",
+                        FieldKind::Number => "number",
+                        FieldKind::Bool => "true or false",
+                    }
+                );
+            }
+            params.push(value.clone());
+        }
+
+        let placeholders = vec!["?"; condition.values.len()].join(", ");
+        Ok(match condition.operator {
+            FilterOperator::In => format!("{expression} IN ({placeholders})"),
+            FilterOperator::NotIn => format!("{expression} NOT IN ({placeholders})"),
+            FilterOperator::Contains => format!("positionCaseInsensitiveUTF8({expression}, ?) > 0"),
+            operator => format!("{expression} {operator} ?"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let expression =
+            FilterExpression::parse("model = 'gpt-4o' and (latency > 2.5 OR NOT is_error = true)")
+                .unwrap();
+        assert_eq!(
+            expression,
+            FilterExpression::And(vec![
+                FilterExpression::Condition(FilterCondition {
+                    field: "model".to_string(),
+                    operator: FilterOperator::Eq,
+                    values: vec![FilterValue::String("gpt-4o".to_string())],
+                }),
+                FilterExpression::Or(vec![
+                    FilterExpression::Condition(FilterCondition {
+                        field: "latency".to_string(),
+                        operator: FilterOperator::Gt,
+                        values: vec![FilterValue::Number(2.5)],
+                    }),
+                    FilterExpression::Not(Box::new(FilterExpression::Condition(FilterCondition {
+                        field: "is_error".to_string(),
+                        operator: FilterOperator::Eq,
+                        values: vec![FilterValue::Bool(true)],
+                    }))),
+                ]),
+            ])
+        );
+        assert_eq!(expression.fields(), vec!["model", "latency", "is_error"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(FilterExpression::parse("").is_err());
+        assert!(FilterExpression::parse("model = gpt-4o").is_err());
+        assert!(FilterExpression::parse("model = 'gpt-4o").is_err());
+        assert!(FilterExpression::parse("model 'gpt-4o'").is_err());
+        assert!(FilterExpression::parse("(cost > 1").is_err());
+        assert!(FilterExpression::parse("cost > 1 cost < 2").is_err());
+        assert!(FilterExpression::parse("model IN ()").is_err());
+        assert!(FilterExpression::parse(&"(".repeat(MAX_DEPTH + 2)).is_err());
+        let many_conditions = vec!["cost > 1"; MAX_CONDITIONS + 1].join(" OR ");
+        assert!(FilterExpression::parse(&many_conditions).is_err());
+    }
+
+    #[test]
+    fn test_compile() {
+        let attribute_slots = HashMap::from([("customer_tier".to_string(), 2)]);
+        let compiled = FilterExpression::parse(
+            r#"model CONTAINS "gpt" AND attributes.customer_tier NOT IN ("free", 'trial')"#,
+        )
+        .unwrap()
+        .compile(FilterTarget::Spans, &attribute_slots)
+        .unwrap();
+        assert_eq!(
+            compiled.sql,
+            "(positionCaseInsensitiveUTF8(model, ?) > 0 AND attribute_2 NOT IN (?, ?))"
+        );
+        assert_eq!(
+            compiled.params,
+            vec![
+                FilterValue::String("gpt".to_string()),
+                FilterValue::String("free".to_string()),
+                FilterValue::String("trial".to_string()),
+            ]
+        );
+
+        let compiled = FilterExpression::parse("cost >= 0.5 OR has_error = true")
+            .unwrap()
+            .compile(FilterTarget::Traces, &HashMap::new())
+            .unwrap();
+        assert_eq!(compiled.sql, "(sum(total_cost) >= ? OR max(is_error) = ?)");
+    }
+
+    #[test]
+    fn test_compile_rejects_invalid_conditions() {
+        let no_slots = HashMap::new();
+        let compile = |input: &str, target: FilterTarget| {
+            FilterExpression::parse(input)
+                .unwrap()
+                .compile(target, &no_slots)
+        };
+        // values are bound, so that they can't change the query
+        let injection = compile(r#"name = "x' OR 1=1 --""#, FilterTarget::Spans).unwrap();
+        assert_eq!(injection.sql, "name = ?");
+
+        assert!(compile("input = 'x'", FilterTarget::Spans).is_err());
+        assert!(compile("model > 'a'", FilterTarget::Spans).is_err());
+        assert!(compile("cost = '1'", FilterTarget::Spans).is_err());
+        assert!(compile("is_error < true", FilterTarget::Spans).is_err());
+        assert!(compile("model = 'gpt-4o'", FilterTarget::EvaluationScores).is_err());
+        assert!(compile("attributes.customer_tier = 'pro'", FilterTarget::Spans).is_err());
+        assert!(compile("attributes.customer_tier = 'pro'", FilterTarget::Traces).is_err());
+    }
+}
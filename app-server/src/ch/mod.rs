@@ -1,10 +1,19 @@
 use clickhouse::Row;
 use serde::{Deserialize, Serialize};
 
+pub mod browser_events;
+pub mod browser_snapshots;
+pub mod downsampling;
 pub mod evaluation_scores;
 pub mod events;
+pub mod filter_expression;
 pub mod modifiers;
+pub mod query;
+pub mod score_writer;
+pub mod span_scores;
+pub mod span_search;
 pub mod spans;
+pub mod table_stats;
 pub mod utils;
 
 #[derive(Deserialize, Debug)]
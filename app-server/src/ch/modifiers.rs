@@ -31,6 +31,28 @@ impl GroupByInterval {
         }
     }
 
+    pub fn seconds(&self) -> i64 {
+        match self {
+            GroupByInterval::Minute => 60,
+            GroupByInterval::Hour => 60 * 60,
+            GroupByInterval::Day => 24 * 60 * 60,
+        }
+    }
+
+    /// The interval itself, or the finest coarser interval that splits the range into at
+    /// most `max_buckets` buckets, e.g. hours instead of minutes for a 90 day range
+    pub fn coarsen_for_range(self, range_seconds: i64, max_buckets: usize) -> Self {
+        [
+            GroupByInterval::Minute,
+            GroupByInterval::Hour,
+            GroupByInterval::Day,
+        ]
+        .into_iter()
+        .filter(|interval| interval.seconds() >= self.seconds())
+        .find(|interval| range_seconds / interval.seconds() <= max_buckets as i64)
+        .unwrap_or(GroupByInterval::Day)
+    }
+
     pub fn to_ch_step(&self) -> &str {
         match self {
             GroupByInterval::Minute => "toIntervalMinute(1)",
@@ -0,0 +1,551 @@
+//! Aggregate queries over spans and evaluation scores, described as data instead of SQL, so that
+//! they can be generated, e.g. from a natural language question, and still be safe to run.
+//! Only whitelisted columns are compiled and every query is scoped to one project.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::analytics::custom_metrics::MetricExpression;
+
+use super::utils::{execute_query, validate_string_against_injection};
+
+const DEFAULT_PAST_HOURS: u32 = 24;
+const MAX_PAST_HOURS: u32 = 24 * 365;
+const DEFAULT_LIMIT: u32 = 100;
+const MAX_LIMIT: u32 = 1000;
+const NULL_FLOAT: &str = "CAST(NULL, 'Nullable(Float64)')";
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum QuerySource {
+    Spans,
+    EvaluationScores,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum QueryMetric {
+    /// Number of rows, use with the `count` aggregation
+    Count,
+    /// Span duration in seconds
+    Latency,
+    InputTokens,
+    OutputTokens,
+    TotalTokens,
+    /// Span cost in USD
+    Cost,
+    /// Value of an evaluation score
+    Score,
+    /// Project's custom metric named by `customMetric`, see `analytics::custom_metrics`
+    Custom,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum QueryAggregation {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    P50,
+    P90,
+    P99,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum QueryDimension {
+    Model,
+    Provider,
+    SpanName,
+    SpanType,
+    UserId,
+    SessionId,
+    /// Language of the client's OpenTelemetry SDK
+    SdkLanguage,
+    SdkVersion,
+    /// Deployment environment of the client
+    Environment,
+    /// Country code of the client
+    Region,
+    EvaluationId,
+    /// Group id of the evaluation
+    EvaluationGroup,
+    ScoreName,
+    Hour,
+    Day,
+    Week,
+}
+
+/// Period whose aggregates are returned next to the current ones
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum CompareTo {
+    /// The `pastHours` before the current period
+    PreviousPeriod,
+    /// The current period, one week earlier
+    PreviousWeek,
+}
+
+impl CompareTo {
+    /// Hours between the start of the current period and the start of the compared one
+    fn offset_hours(&self, past_hours: u32) -> u32 {
+        match self {
+            CompareTo::PreviousPeriod => past_hours,
+            CompareTo::PreviousWeek => 24 * 7,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum QueryFilterOperator {
+    Eq,
+    Ne,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryFilter {
+    pub dimension: QueryDimension,
+    pub operator: QueryFilterOperator,
+    pub value: String,
+}
+
+/// Filter on a span attribute. Only promoted attributes can be filtered on, see
+/// `traces::promoted_attributes`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributeFilter {
+    pub key: String,
+    pub operator: QueryFilterOperator,
+    pub value: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsQuery {
+    pub source: QuerySource,
+    pub metric: QueryMetric,
+    /// Name of the custom metric, if `metric` is `custom`
+    #[serde(default)]
+    pub custom_metric: Option<String>,
+    pub aggregation: QueryAggregation,
+    #[serde(default)]
+    pub group_by: Vec<QueryDimension>,
+    #[serde(default)]
+    pub filters: Vec<QueryFilter>,
+    #[serde(default)]
+    pub attribute_filters: Vec<AttributeFilter>,
+    /// Defaults to 24
+    #[serde(default)]
+    pub past_hours: Option<u32>,
+    /// Maximum number of groups, defaults to 100
+    #[serde(default)]
+    pub limit: Option<u32>,
+    /// Also aggregates the compared period, can't be combined with time dimensions
+    #[serde(default)]
+    pub compare_to: Option<CompareTo>,
+}
+
+/// Project settings that queries are compiled against
+#[derive(Default)]
+pub struct QueryContext {
+    /// Promoted attribute keys with the slot of their column
+    pub attribute_slots: HashMap<String, usize>,
+    /// Parsed custom metrics by name
+    pub custom_metrics: HashMap<String, MetricExpression>,
+}
+
+#[derive(Row, Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResultRow {
+    /// Values of the `groupBy` dimensions, in the same order
+    pub dimensions: Vec<String>,
+    pub value: f64,
+    /// Value in the compared period, if the query has `compareTo`
+    pub previous_value: Option<f64>,
+    /// Change from the previous value in percent, None if the previous value is 0
+    pub delta_percent: Option<f64>,
+}
+
+fn time_column(source: QuerySource) -> &'static str {
+    match source {
+        QuerySource::Spans => "start_time",
+        QuerySource::EvaluationScores => "timestamp",
+    }
+}
+
+fn dimension_expression(source: QuerySource, dimension: QueryDimension) -> Result<String> {
+    let time_column = time_column(source);
+    let expression = match (source, dimension) {
+        (_, QueryDimension::Hour) => format!("toString(toStartOfHour({time_column}))"),
+        (_, QueryDimension::Day) => format!("toString(toStartOfDay({time_column}))"),
+        (_, QueryDimension::Week) => format!("toString(toStartOfWeek({time_column}))"),
+        (QuerySource::Spans, QueryDimension::Model) => "model".to_string(),
+        (QuerySource::Spans, QueryDimension::Provider) => "provider".to_string(),
+        (QuerySource::Spans, QueryDimension::SpanName) => "name".to_string(),
+        (QuerySource::Spans, QueryDimension::SpanType) => "toString(span_type)".to_string(),
+        (QuerySource::Spans, QueryDimension::UserId) => "user_id".to_string(),
+        (QuerySource::Spans, QueryDimension::SessionId) => "session_id".to_string(),
+        (QuerySource::Spans, QueryDimension::SdkLanguage) => "sdk_language".to_string(),
+        (QuerySource::Spans, QueryDimension::SdkVersion) => "sdk_version".to_string(),
+        (QuerySource::Spans, QueryDimension::Environment) => "environment".to_string(),
+        (QuerySource::Spans, QueryDimension::Region) => "region".to_string(),
+        (QuerySource::EvaluationScores, QueryDimension::EvaluationId) => {
+            "toString(evaluation_id)".to_string()
+        }
+        (QuerySource::EvaluationScores, QueryDimension::EvaluationGroup) => "group_id".to_string(),
+        (QuerySource::EvaluationScores, QueryDimension::ScoreName) => "name".to_string(),
+        (source, dimension) => {
+            return Err(anyhow::anyhow!(
+                "Dimension {:?} is not available for {:?}",
+                dimension,
+                source
+            ))
+        }
+    };
+    Ok(expression)
+}
+
+fn metric_expression(query: &AnalyticsQuery, context: &QueryContext) -> Result<String> {
+    let (source, metric) = (query.source, query.metric);
+    if (source, metric) == (QuerySource::Spans, QueryMetric::Custom) {
+        let name = query
+            .custom_metric
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("customMetric is required for the custom metric"))?;
+        let expression = context
+            .custom_metrics
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown custom metric: {}", name))?;
+        return Ok(expression.to_sql());
+    }
+    let expression = match (source, metric) {
+        (_, QueryMetric::Count) => "1",
+        (QuerySource::Spans, QueryMetric::Latency) => {
+            "(toUnixTimestamp64Nano(end_time) - toUnixTimestamp64Nano(start_time)) / 1e9"
+        }
+        (QuerySource::Spans, QueryMetric::InputTokens) => "input_tokens",
+        (QuerySource::Spans, QueryMetric::OutputTokens) => "output_tokens",
+        (QuerySource::Spans, QueryMetric::TotalTokens) => "total_tokens",
+        (QuerySource::Spans, QueryMetric::Cost) => "total_cost",
+        (QuerySource::EvaluationScores, QueryMetric::Score) => "value",
+        (source, metric) => {
+            return Err(anyhow::anyhow!(
+                "Metric {:?} is not available for {:?}",
+                metric,
+                source
+            ))
+        }
+    };
+    Ok(expression.to_string())
+}
+
+fn filter_operator(operator: QueryFilterOperator) -> &'static str {
+    match operator {
+        QueryFilterOperator::Eq => "=",
+        QueryFilterOperator::Ne => "!=",
+    }
+}
+
+/// Aggregates the rows matching `condition` only, if set
+fn aggregation_expression(
+    aggregation: QueryAggregation,
+    metric: &str,
+    condition: Option<&str>,
+) -> String {
+    let Some(condition) = condition else {
+        return match aggregation {
+            QueryAggregation::Count => "count()".to_string(),
+            QueryAggregation::Sum => format!("sum({metric})"),
+            QueryAggregation::Avg => format!("avg({metric})"),
+            QueryAggregation::Min => format!("min({metric})"),
+            QueryAggregation::Max => format!("max({metric})"),
+            QueryAggregation::P50 => format!("quantile(0.5)({metric})"),
+            QueryAggregation::P90 => format!("quantile(0.9)({metric})"),
+            QueryAggregation::P99 => format!("quantile(0.99)({metric})"),
+        };
+    };
+    match aggregation {
+        QueryAggregation::Count => format!("countIf({condition})"),
+        QueryAggregation::Sum => format!("sumIf({metric}, {condition})"),
+        QueryAggregation::Avg => format!("avgIf({metric}, {condition})"),
+        QueryAggregation::Min => format!("minIf({metric}, {condition})"),
+        QueryAggregation::Max => format!("maxIf({metric}, {condition})"),
+        QueryAggregation::P50 => format!("quantileIf(0.5)({metric}, {condition})"),
+        QueryAggregation::P90 => format!("quantileIf(0.9)({metric}, {condition})"),
+        QueryAggregation::P99 => format!("quantileIf(0.99)({metric}, {condition})"),
+    }
+}
+
+impl AnalyticsQuery {
+    /// ClickHouse SQL of the query, scoped to the project
+    pub fn to_sql(&self, project_id: Uuid, context: &QueryContext) -> Result<String> {
+        let table = match self.source {
+            QuerySource::Spans => "spans",
+            QuerySource::EvaluationScores => "evaluation_scores",
+        };
+        let metric = metric_expression(self, context)?;
+
+        let group_by = self
+            .group_by
+            .iter()
+            .map(|dimension| dimension_expression(self.source, *dimension))
+            .collect::<Result<Vec<_>>>()?;
+
+        let past_hours = self.past_hours.unwrap_or(DEFAULT_PAST_HOURS);
+        if past_hours == 0 || past_hours > MAX_PAST_HOURS {
+            return Err(anyhow::anyhow!(
+                "pastHours must be between 1 and {}",
+                MAX_PAST_HOURS
+            ));
+        }
+        let time_column = time_column(self.source);
+        let current_period = format!("{time_column} >= now() - INTERVAL {past_hours} HOUR");
+        let (values, start_hours) = match self.compare_to {
+            None => (
+                format!(
+                    "toFloat64({}) AS value, {NULL_FLOAT} AS previous_value, \
+                    {NULL_FLOAT} AS delta_percent",
+                    aggregation_expression(self.aggregation, &metric, None)
+                ),
+                past_hours,
+            ),
+            Some(compare_to) => {
+                let offset_hours = compare_to.offset_hours(past_hours);
+                let previous_period = format!(
+                    "{time_column} >= now() - INTERVAL {} HOUR \
+                    AND {time_column} < now() - INTERVAL {offset_hours} HOUR",
+                    offset_hours + past_hours
+                );
+                (
+                    format!(
+                        "toFloat64({}) AS value, toNullable(toFloat64({})) AS previous_value, \
+                        (value - previous_value) * 100 / nullIf(previous_value, 0) AS delta_percent",
+                        aggregation_expression(self.aggregation, &metric, Some(&current_period)),
+                        aggregation_expression(self.aggregation, &metric, Some(&previous_period)),
+                    ),
+                    offset_hours + past_hours,
+                )
+            }
+        };
+        let mut conditions = vec![
+            format!("project_id = '{project_id}'"),
+            format!("{time_column} >= now() - INTERVAL {start_hours} HOUR"),
+        ];
+        if self.source == QuerySource::EvaluationScores {
+            // Categorical scores have no value to aggregate
+            conditions.push("score_type != 'CATEGORICAL'".to_string());
+        }
+        for filter in &self.filters {
+            validate_string_against_injection(&filter.value)?;
+            conditions.push(format!(
+                "{} {} '{}'",
+                dimension_expression(self.source, filter.dimension)?,
+                filter_operator(filter.operator),
+                filter.value
+            ));
+        }
+        for filter in &self.attribute_filters {
+            if self.source != QuerySource::Spans {
+                return Err(anyhow::anyhow!(
+                    "Attribute filters are only available for spans"
+                ));
+            }
+            let Some(slot) = context.attribute_slots.get(&filter.key) else {
+                return Err(anyhow::anyhow!(
+                    "Attribute {} is not promoted, promote it in the project settings to filter on it",
+                    filter.key
+                ));
+            };
+            validate_string_against_injection(&filter.value)?;
+            conditions.push(format!(
+                "attribute_{slot} {} '{}'",
+                filter_operator(filter.operator),
+                filter.value
+            ));
+        }
+
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+        let is_time_series = self.group_by.iter().any(|dimension| {
+            matches!(
+                dimension,
+                QueryDimension::Hour | QueryDimension::Day | QueryDimension::Week
+            )
+        });
+        if is_time_series && self.compare_to.is_some() {
+            return Err(anyhow::anyhow!(
+                "compareTo can't be combined with hour, day or week dimensions"
+            ));
+        }
+
+        let mut query = format!(
+            "SELECT [{}] AS dimensions, {values} FROM {table} WHERE {}",
+            group_by.join(", "),
+            conditions.join(" AND ")
+        );
+        if !group_by.is_empty() {
+            query.push_str(&format!(" GROUP BY {}", group_by.join(", ")));
+            if is_time_series {
+                query.push_str(" ORDER BY dimensions");
+            } else {
+                query.push_str(" ORDER BY value DESC");
+            }
+        }
+        query.push_str(&format!(" LIMIT {limit}"));
+
+        Ok(query)
+    }
+}
+
+pub async fn run_query(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    query: &AnalyticsQuery,
+    context: &QueryContext,
+) -> Result<Vec<QueryResultRow>> {
+    let query_string = query.to_sql(project_id, context)?;
+    execute_query(&clickhouse, &query_string).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(source: QuerySource, metric: QueryMetric) -> AnalyticsQuery {
+        AnalyticsQuery {
+            source,
+            metric,
+            custom_metric: None,
+            aggregation: QueryAggregation::Avg,
+            group_by: vec![],
+            filters: vec![],
+            attribute_filters: vec![],
+            past_hours: Some(24 * 7),
+            limit: None,
+            compare_to: None,
+        }
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let project_id = Uuid::nil();
+        let mut scores = query(QuerySource::EvaluationScores, QueryMetric::Score);
+        scores.group_by = vec![QueryDimension::EvaluationGroup];
+        scores.filters = vec![QueryFilter {
+            dimension: QueryDimension::ScoreName,
+            operator: QueryFilterOperator::Eq,
+            value: "faithfulness".to_string(),
+        }];
+        assert_eq!(
+            scores.to_sql(project_id, &QueryContext::default()).unwrap(),
+            "SELECT [group_id] AS dimensions, toFloat64(avg(value)) AS value, \
+            CAST(NULL, 'Nullable(Float64)') AS previous_value, \
+            CAST(NULL, 'Nullable(Float64)') AS delta_percent FROM evaluation_scores \
+            WHERE project_id = '00000000-0000-0000-0000-000000000000' \
+            AND timestamp >= now() - INTERVAL 168 HOUR AND score_type != 'CATEGORICAL' \
+            AND name = 'faithfulness' \
+            GROUP BY group_id ORDER BY value DESC LIMIT 100"
+        );
+    }
+
+    #[test]
+    fn test_to_sql_rejects_unsafe_queries() {
+        let project_id = Uuid::nil();
+
+        let mut scores_by_model = query(QuerySource::EvaluationScores, QueryMetric::Score);
+        scores_by_model.group_by = vec![QueryDimension::Model];
+        assert!(scores_by_model
+            .to_sql(project_id, &QueryContext::default())
+            .is_err());
+
+        assert!(query(QuerySource::EvaluationScores, QueryMetric::Cost)
+            .to_sql(project_id, &QueryContext::default())
+            .is_err());
+
+        let mut injection = query(QuerySource::Spans, QueryMetric::Cost);
+        injection.filters = vec![QueryFilter {
+            dimension: QueryDimension::Model,
+            operator: QueryFilterOperator::Eq,
+            value: "gpt-4o' OR 1=1 --".to_string(),
+        }];
+        assert!(injection
+            .to_sql(project_id, &QueryContext::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_to_sql_attribute_filters() {
+        let project_id = Uuid::nil();
+        let mut cost = query(QuerySource::Spans, QueryMetric::Cost);
+        cost.attribute_filters = vec![AttributeFilter {
+            key: "customer_tier".to_string(),
+            operator: QueryFilterOperator::Eq,
+            value: "enterprise".to_string(),
+        }];
+        let context = QueryContext {
+            attribute_slots: HashMap::from([("customer_tier".to_string(), 2)]),
+            ..Default::default()
+        };
+        assert!(cost
+            .to_sql(project_id, &context)
+            .unwrap()
+            .contains("AND attribute_2 = 'enterprise'"));
+        // not promoted
+        assert!(cost.to_sql(project_id, &QueryContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_to_sql_custom_metric() {
+        let project_id = Uuid::nil();
+        let mut throughput = query(QuerySource::Spans, QueryMetric::Custom);
+        throughput.custom_metric = Some("throughput".to_string());
+        let context = QueryContext {
+            custom_metrics: HashMap::from([(
+                "throughput".to_string(),
+                MetricExpression::parse("output_tokens / 2").unwrap(),
+            )]),
+            ..Default::default()
+        };
+        assert!(throughput
+            .to_sql(project_id, &context)
+            .unwrap()
+            .starts_with(
+                "SELECT [] AS dimensions, toFloat64(avg((output_tokens / nullIf(2.0, 0))))"
+            ));
+        // not defined in the project
+        assert!(throughput
+            .to_sql(project_id, &QueryContext::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_to_sql_compare_to() {
+        let project_id = Uuid::nil();
+        let mut cost = query(QuerySource::Spans, QueryMetric::Cost);
+        cost.past_hours = Some(24);
+        cost.group_by = vec![QueryDimension::Model];
+        cost.compare_to = Some(CompareTo::PreviousWeek);
+        let sql = cost.to_sql(project_id, &QueryContext::default()).unwrap();
+        assert!(
+            sql.contains("toFloat64(avgIf(total_cost, start_time >= now() - INTERVAL 24 HOUR))")
+        );
+        assert!(sql.contains(
+            "avgIf(total_cost, start_time >= now() - INTERVAL 192 HOUR \
+            AND start_time < now() - INTERVAL 168 HOUR)"
+        ));
+        assert!(sql.contains(
+            "WHERE project_id = '00000000-0000-0000-0000-000000000000' \
+            AND start_time >= now() - INTERVAL 192 HOUR"
+        ));
+
+        cost.group_by = vec![QueryDimension::Day];
+        assert!(cost.to_sql(project_id, &QueryContext::default()).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+//! Buffered writer of evaluation scores. Scores of all requests are accumulated and inserted
+//! into ClickHouse in batches, rather than one small insert per request.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::{
+    features::{is_feature_enabled, Feature},
+    runtime::wait_stop_signal,
+};
+
+use super::evaluation_scores::{write_evaluation_scores, EvaluationScore};
+
+/// Writers wait for the buffer to be flushed once the channel is full
+const CHANNEL_CAPACITY: usize = 100_000;
+const MAX_BATCH_SIZE: usize = 5000;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub struct ScoreWriter {
+    sender: mpsc::Sender<EvaluationScore>,
+}
+
+impl ScoreWriter {
+    /// Start the background flushing task. The returned handle finishes after the final flush
+    /// on shutdown.
+    pub fn start(clickhouse: clickhouse::Client) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let handle = tokio::spawn(run(clickhouse, receiver));
+        (Self { sender }, handle)
+    }
+
+    /// Queue the scores for insertion. They are visible in ClickHouse after the next flush.
+    pub async fn write(&self, evaluation_scores: Vec<EvaluationScore>) -> Result<()> {
+        if !is_feature_enabled(Feature::FullBuild) {
+            return Err(anyhow::anyhow!(
+                "Evaluation scores can't be stored without ClickHouse, run with ENVIRONMENT=FULL, \
+                or in the embedded mode: built with `--features embedded` and run with \
+                EMBEDDED_POSTGRES=true"
+            ));
+        }
+        for evaluation_score in evaluation_scores {
+            self.sender
+                .send(evaluation_score)
+                .await
+                .map_err(|_| anyhow::anyhow!("Evaluation score writer is stopped"))?;
+        }
+        Ok(())
+    }
+}
+
+async fn run(clickhouse: clickhouse::Client, mut receiver: mpsc::Receiver<EvaluationScore>) {
+    let mut buffer = Vec::with_capacity(MAX_BATCH_SIZE);
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    let stop_signal = wait_stop_signal("evaluation score writer");
+    tokio::pin!(stop_signal);
+
+    loop {
+        tokio::select! {
+            evaluation_score = receiver.recv() => {
+                let Some(evaluation_score) = evaluation_score else {
+                    break;
+                };
+                buffer.push(evaluation_score);
+                if buffer.len() >= MAX_BATCH_SIZE {
+                    flush(&clickhouse, &mut buffer).await;
+                }
+            }
+            _ = interval.tick() => flush(&clickhouse, &mut buffer).await,
+            _ = &mut stop_signal => {
+                // Scores queued before the signal are still written
+                receiver.close();
+                while let Some(evaluation_score) = receiver.recv().await {
+                    buffer.push(evaluation_score);
+                    if buffer.len() >= MAX_BATCH_SIZE {
+                        flush(&clickhouse, &mut buffer).await;
+                    }
+                }
+                break;
+            }
+        }
+    }
+    flush(&clickhouse, &mut buffer).await;
+    log::debug!("Evaluation score writer stopped");
+}
+
+/// Insert the buffered scores, failed inserts are retried by `write_evaluation_scores`. The
+/// buffer is emptied even if all attempts fail, so that a ClickHouse outage doesn't grow it
+/// without bound.
+async fn flush(clickhouse: &clickhouse::Client, buffer: &mut Vec<EvaluationScore>) {
+    if buffer.is_empty() {
+        return;
+    }
+    if let Err(e) = write_evaluation_scores(clickhouse, buffer).await {
+        log::error!("Dropped {} evaluation scores: {:?}", buffer.len(), e);
+    }
+    buffer.clear();
+}
@@ -0,0 +1,341 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::features::{is_feature_enabled, Feature};
+
+use super::{
+    evaluation_scores::EvaluationScoreBucket,
+    modifiers::GroupByInterval,
+    utils::{
+        chrono_to_nanoseconds, deserialize_timestamp, execute_query,
+        group_by_time_absolute_statement, insert_rows, validate_string_against_injection,
+    },
+    MetricTimeValue,
+};
+
+fn serialize_timestamp<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_i64(chrono_to_nanoseconds(timestamp.clone()))
+}
+
+/// Score of a span, produced by an online evaluator or by manual feedback
+#[derive(Row, Serialize, Deserialize)]
+pub struct SpanScore {
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub project_id: Uuid,
+    #[serde(
+        serialize_with = "serialize_timestamp",
+        deserialize_with = "deserialize_timestamp"
+    )]
+    pub timestamp: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub span_id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub trace_id: Uuid,
+    /// Name of the label class
+    pub name: String,
+    pub value: f64,
+    /// Annotator of a manual score, nil for scores of online evaluators
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub user_id: Uuid,
+}
+
+pub async fn insert_span_scores(
+    clickhouse: clickhouse::Client,
+    span_scores: Vec<SpanScore>,
+) -> Result<()> {
+    if span_scores.is_empty() || !is_feature_enabled(Feature::FullBuild) {
+        return Ok(());
+    }
+
+    insert_rows(&clickhouse, "span_scores", &span_scores).await
+}
+
+#[derive(Row, Deserialize)]
+struct AverageSpanScore {
+    average_value: f64,
+}
+
+pub async fn get_average_span_score(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    name: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<f64> {
+    validate_string_against_injection(&name)?;
+    let ch_start_time = start_time.timestamp();
+    let ch_end_time = end_time.timestamp();
+
+    let query = format!(
+        "SELECT avg(value) as average_value
+        FROM span_scores
+        WHERE project_id = '{project_id}'
+            AND name = '{name}'
+            AND timestamp >= fromUnixTimestamp({ch_start_time})
+            AND timestamp <= fromUnixTimestamp({ch_end_time})",
+    );
+
+    let rows: Vec<AverageSpanScore> = execute_query(&clickhouse, &query).await?;
+    Ok(rows[0].average_value)
+}
+
+#[derive(Row, Deserialize, Clone)]
+pub struct SpanScoresBounds {
+    pub upper_bound: f64,
+}
+
+pub async fn get_span_scores_bounds(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    name: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<SpanScoresBounds> {
+    validate_string_against_injection(&name)?;
+    let ch_start_time = start_time.timestamp();
+    let ch_end_time = end_time.timestamp();
+
+    let query = format!(
+        "SELECT MAX(value) AS upper_bound
+        FROM span_scores
+        WHERE project_id = '{project_id}'
+            AND name = '{name}'
+            AND timestamp >= fromUnixTimestamp({ch_start_time})
+            AND timestamp <= fromUnixTimestamp({ch_end_time})",
+    );
+
+    let rows: Vec<SpanScoresBounds> = execute_query(&clickhouse, &query).await?;
+    Ok(rows[0].clone())
+}
+
+pub async fn get_span_score_buckets_based_on_bounds(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    name: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    lower_bound: f64,
+    upper_bound: f64,
+    bucket_count: u64,
+) -> Result<Vec<EvaluationScoreBucket>> {
+    validate_string_against_injection(&name)?;
+    let ch_start_time = start_time.timestamp();
+    let ch_end_time = end_time.timestamp();
+
+    let step_size = (upper_bound - lower_bound) / bucket_count as f64;
+    let interval_nums = (1..=bucket_count)
+        .map(|num| num.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+
+    // Same buckets as for evaluation scores, see `get_evaluation_score_buckets_based_on_bounds`
+    let query = format!(
+        "
+WITH intervals AS (
+    SELECT
+        arrayJoin([{interval_nums}]) AS interval_num,
+        {:?} + ((interval_num - 1) * {:?}) AS lower_bound,
+        CASE
+            WHEN interval_num = {bucket_count} THEN {:?}
+            ELSE {:?} + (interval_num * {:?})
+        END AS upper_bound
+)
+SELECT
+    intervals.lower_bound,
+    intervals.upper_bound,
+    COUNT(CASE
+        WHEN value >= intervals.lower_bound AND value < intervals.upper_bound THEN 1
+        WHEN intervals.interval_num = {bucket_count}
+            AND value >= intervals.lower_bound
+            AND value <= intervals.upper_bound THEN 1
+        ELSE NULL
+    END) AS height
+FROM span_scores
+JOIN intervals ON 1 = 1
+WHERE project_id = '{project_id}'
+AND name = '{name}'
+AND timestamp >= fromUnixTimestamp({ch_start_time})
+AND timestamp <= fromUnixTimestamp({ch_end_time})
+GROUP BY intervals.lower_bound, intervals.upper_bound, intervals.interval_num
+ORDER BY intervals.interval_num",
+        lower_bound, step_size, upper_bound, lower_bound, step_size
+    );
+
+    let rows: Vec<EvaluationScoreBucket> = execute_query(&clickhouse, &query).await?;
+
+    Ok(rows)
+}
+
+/// Average score per time interval
+pub async fn get_span_score_trend(
+    clickhouse: clickhouse::Client,
+    group_by_interval: GroupByInterval,
+    project_id: Uuid,
+    name: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<MetricTimeValue<f64>>> {
+    validate_string_against_injection(&name)?;
+    let ch_round_time = group_by_interval.to_ch_truncate_time();
+    let ch_start_time = start_time.timestamp();
+    let ch_end_time = end_time.timestamp();
+
+    let query = format!(
+        "
+    SELECT
+        {ch_round_time}(timestamp) AS time,
+        AVG(value) AS value
+    FROM span_scores
+    WHERE
+        project_id = '{project_id}'
+        AND name = '{name}'
+        AND timestamp >= fromUnixTimestamp({ch_start_time})
+        AND timestamp <= fromUnixTimestamp({ch_end_time})
+    {}",
+        group_by_time_absolute_statement(start_time, end_time, group_by_interval)
+    );
+
+    execute_query(&clickhouse, &query).await
+}
+
+#[derive(Row, Deserialize)]
+struct SpanScoreName {
+    name: String,
+}
+
+/// Distinct names of the project's span scores
+pub async fn get_span_score_names(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+) -> Result<Vec<String>> {
+    let query = format!(
+        "SELECT DISTINCT name
+        FROM span_scores
+        WHERE project_id = '{project_id}'
+        ORDER BY name",
+    );
+
+    let rows: Vec<SpanScoreName> = execute_query(&clickhouse, &query).await?;
+    Ok(rows.into_iter().map(|row| row.name).collect())
+}
+
+#[derive(Row, Deserialize)]
+struct ScoreScatterRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    trace_id: Uuid,
+    score: f64,
+    latency: f64,
+    cost: f64,
+}
+
+/// Score of a span or an evaluation datapoint next to its latency, in seconds, and cost
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreScatterPoint {
+    /// Span id or evaluation datapoint id
+    pub id: Uuid,
+    pub trace_id: Uuid,
+    pub score: f64,
+    pub latency: f64,
+    pub cost: f64,
+}
+
+/// Most recent span scores in the period with the latency and cost of the scored span
+pub async fn get_span_score_scatter(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    name: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: u64,
+) -> Result<Vec<ScoreScatterPoint>> {
+    validate_string_against_injection(&name)?;
+    let ch_start_time = start_time.timestamp();
+    let ch_end_time = end_time.timestamp();
+
+    let query = format!(
+        "
+    SELECT
+        span_scores.span_id AS id,
+        span_scores.trace_id AS trace_id,
+        span_scores.value AS score,
+        (toUnixTimestamp64Nano(spans.end_time) - toUnixTimestamp64Nano(spans.start_time)) / 1e9
+            AS latency,
+        spans.total_cost AS cost
+    FROM span_scores
+    JOIN (
+        SELECT span_id, start_time, end_time, total_cost
+        FROM spans
+        WHERE project_id = '{project_id}'
+    ) AS spans ON spans.span_id = span_scores.span_id
+    WHERE
+        span_scores.project_id = '{project_id}'
+        AND span_scores.name = '{name}'
+        AND span_scores.timestamp >= fromUnixTimestamp({ch_start_time})
+        AND span_scores.timestamp <= fromUnixTimestamp({ch_end_time})
+    ORDER BY span_scores.timestamp DESC
+    LIMIT {limit}"
+    );
+
+    let rows: Vec<ScoreScatterRow> = execute_query(&clickhouse, &query).await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| ScoreScatterPoint {
+            id: row.id,
+            trace_id: row.trace_id,
+            score: row.score,
+            latency: row.latency,
+            cost: row.cost,
+        })
+        .collect())
+}
+
+/// Latest manual score of a span by one annotator
+#[derive(Row, Deserialize, Clone, Debug)]
+pub struct AnnotatorSpanScore {
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub span_id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub user_id: Uuid,
+    pub name: String,
+    pub value: f64,
+}
+
+/// Manual scores in the period, the latest one of each annotator for each span and score name
+pub async fn get_annotator_span_scores(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: u64,
+) -> Result<Vec<AnnotatorSpanScore>> {
+    let ch_start_time = start_time.timestamp();
+    let ch_end_time = end_time.timestamp();
+
+    let query = format!(
+        "
+    SELECT
+        span_id,
+        user_id,
+        name,
+        argMax(value, timestamp) AS value
+    FROM span_scores
+    WHERE
+        project_id = '{project_id}'
+        AND user_id != toUUID('00000000-0000-0000-0000-000000000000')
+        AND timestamp >= fromUnixTimestamp({ch_start_time})
+        AND timestamp <= fromUnixTimestamp({ch_end_time})
+    GROUP BY name, span_id, user_id
+    LIMIT {limit}"
+    );
+
+    execute_query(&clickhouse, &query).await
+}
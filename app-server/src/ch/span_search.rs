@@ -0,0 +1,324 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    chaos,
+    db::spans::Span,
+    features::{is_feature_enabled, Feature},
+};
+
+use super::utils::{chrono_to_nanoseconds, insert_rows, nanoseconds_to_chrono};
+
+/// Longer inputs and outputs are searched in their first `MAX_INDEXED_BYTES` bytes only
+const MAX_INDEXED_BYTES: usize = 64 * 1024;
+/// Characters of context on each side of the first match in the snippets
+const SNIPPET_CONTEXT_CHARS: usize = 80;
+/// Shorter queries can't be looked up in the ngram indexes, see the span_search table
+pub const MIN_QUERY_LENGTH: usize = 3;
+
+/// Searchable text of a span, written next to the span itself
+#[derive(Row, Serialize, Deserialize, Clone)]
+pub struct CHSpanSearchEntry {
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub project_id: Uuid,
+    /// Start time in nanoseconds
+    pub start_time: i64,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub span_id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub trace_id: Uuid,
+    pub name: String,
+    pub path: String,
+    pub input: String,
+    pub output: String,
+}
+
+/// Text of a span input or output: strings as they are, other values as JSON
+fn searchable_text(value: &Option<Value>) -> String {
+    let mut text = match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    };
+    if text.len() > MAX_INDEXED_BYTES {
+        let mut end = MAX_INDEXED_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    text
+}
+
+impl CHSpanSearchEntry {
+    pub fn from_db_span(span: &Span, project_id: Uuid) -> Self {
+        Self {
+            project_id,
+            start_time: chrono_to_nanoseconds(span.start_time),
+            span_id: span.span_id,
+            trace_id: span.trace_id,
+            name: span.name.clone(),
+            path: span
+                .get_attributes()
+                .path()
+                .unwrap_or(String::from("<null>")),
+            input: searchable_text(&span.input),
+            output: searchable_text(&span.output),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty() && self.output.is_empty()
+    }
+}
+
+pub async fn insert_span_search_entry(
+    clickhouse: clickhouse::Client,
+    entry: &CHSpanSearchEntry,
+) -> Result<()> {
+    insert_rows(&clickhouse, "span_search", std::slice::from_ref(entry)).await
+}
+
+/// Span fields that are searched
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SpanSearchField {
+    Input,
+    Output,
+    #[default]
+    All,
+}
+
+impl SpanSearchField {
+    pub fn searches_input(&self) -> bool {
+        matches!(self, SpanSearchField::Input | SpanSearchField::All)
+    }
+
+    pub fn searches_output(&self) -> bool {
+        matches!(self, SpanSearchField::Output | SpanSearchField::All)
+    }
+}
+
+/// Escapes the `LIKE` wildcards of the query, so that it's matched literally
+fn like_pattern(query: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in query.to_lowercase().chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+fn match_condition(searches_input: bool, searches_output: bool) -> Result<&'static str> {
+    match (searches_input, searches_output) {
+        (true, true) => Ok("(lower(input) LIKE ? OR lower(output) LIKE ?)"),
+        (true, false) => Ok("lower(input) LIKE ?"),
+        (false, true) => Ok("lower(output) LIKE ?"),
+        (false, false) => Err(anyhow::anyhow!("No fields to search")),
+    }
+}
+
+fn snippet_expression(field: &str, searched: bool) -> String {
+    if !searched {
+        return "''".to_string();
+    }
+    format!(
+        "if(positionCaseInsensitiveUTF8({field}, ?) > 0,
+            substringUTF8(
+                {field},
+                greatest(1, toInt64(positionCaseInsensitiveUTF8({field}, ?)) - {SNIPPET_CONTEXT_CHARS}),
+                {}
+            ),
+            '')",
+        2 * SNIPPET_CONTEXT_CHARS + MIN_QUERY_LENGTH
+    )
+}
+
+/// Text around the first match of the query in the text, ignoring case, like the snippets of
+/// `search_spans`. None if the text doesn't contain the query.
+pub fn matching_snippet(text: &str, query: &str) -> Option<String> {
+    let lowercase_text = text.to_lowercase();
+    let position = lowercase_text.find(&query.to_lowercase())?;
+    let position = lowercase_text[..position].chars().count();
+    Some(
+        text.chars()
+            .skip(position.saturating_sub(SNIPPET_CONTEXT_CHARS))
+            .take(2 * SNIPPET_CONTEXT_CHARS + MIN_QUERY_LENGTH)
+            .collect(),
+    )
+}
+
+#[derive(Row, Deserialize)]
+struct SpanSearchRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    span_id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    trace_id: Uuid,
+    start_time_ns: i64,
+    name: String,
+    path: String,
+    input_snippet: String,
+    output_snippet: String,
+}
+
+/// Span whose input or output contains the query, with the text around the first match
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanSearchResult {
+    pub span_id: Uuid,
+    pub trace_id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub name: String,
+    pub path: Option<String>,
+    /// None if the input doesn't contain the query or isn't searched
+    pub input_snippet: Option<String>,
+    pub output_snippet: Option<String>,
+}
+
+impl From<SpanSearchRow> for SpanSearchResult {
+    fn from(row: SpanSearchRow) -> Self {
+        Self {
+            span_id: row.span_id,
+            trace_id: row.trace_id,
+            start_time: nanoseconds_to_chrono(row.start_time_ns),
+            name: row.name,
+            path: Some(row.path).filter(|path| path != "<null>"),
+            input_snippet: Some(row.input_snippet).filter(|snippet| !snippet.is_empty()),
+            output_snippet: Some(row.output_snippet).filter(|snippet| !snippet.is_empty()),
+        }
+    }
+}
+
+/// Spans of the period whose searched fields contain the query, ignoring case, the newest first
+pub async fn search_spans(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    query: &str,
+    field: SpanSearchField,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: u64,
+    offset: u64,
+) -> Result<Vec<SpanSearchResult>> {
+    if !is_feature_enabled(Feature::FullBuild) {
+        return Ok(Vec::new());
+    }
+    let condition = match_condition(field.searches_input(), field.searches_output())?;
+    let query_string = format!(
+        "SELECT
+            span_id,
+            trace_id,
+            toUnixTimestamp64Nano(start_time) AS start_time_ns,
+            name,
+            path,
+            {} AS input_snippet,
+            {} AS output_snippet
+        FROM span_search
+        WHERE project_id = ?
+            AND start_time >= fromUnixTimestamp64Nano(?)
+            AND start_time <= fromUnixTimestamp64Nano(?)
+            AND {condition}
+        ORDER BY start_time DESC
+        LIMIT ? OFFSET ?",
+        snippet_expression("input", field.searches_input()),
+        snippet_expression("output", field.searches_output()),
+    );
+
+    let mut ch_query = clickhouse.query(&query_string);
+    // the snippets take the query twice each
+    for _ in 0..2 * (field.searches_input() as usize + field.searches_output() as usize) {
+        ch_query = ch_query.bind(query);
+    }
+    ch_query = ch_query
+        .bind(project_id.to_string())
+        .bind(chrono_to_nanoseconds(start_time))
+        .bind(chrono_to_nanoseconds(end_time));
+    let pattern = like_pattern(query);
+    for _ in 0..(field.searches_input() as usize + field.searches_output() as usize) {
+        ch_query = ch_query.bind(&pattern);
+    }
+    chaos::clickhouse_latency().await;
+    let rows = ch_query
+        .bind(limit)
+        .bind(offset)
+        .fetch_all::<SpanSearchRow>()
+        .await?;
+
+    Ok(rows.into_iter().map(Into::into).collect())
+}
+
+#[derive(Row, Deserialize)]
+struct CountRow {
+    count: u64,
+}
+
+pub async fn count_span_search_results(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    query: &str,
+    field: SpanSearchField,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<u64> {
+    if !is_feature_enabled(Feature::FullBuild) {
+        return Ok(0);
+    }
+    let condition = match_condition(field.searches_input(), field.searches_output())?;
+    let query_string = format!(
+        "SELECT count() AS count
+        FROM span_search
+        WHERE project_id = ?
+            AND start_time >= fromUnixTimestamp64Nano(?)
+            AND start_time <= fromUnixTimestamp64Nano(?)
+            AND {condition}"
+    );
+
+    let mut ch_query = clickhouse
+        .query(&query_string)
+        .bind(project_id.to_string())
+        .bind(chrono_to_nanoseconds(start_time))
+        .bind(chrono_to_nanoseconds(end_time));
+    let pattern = like_pattern(query);
+    for _ in 0..(field.searches_input() as usize + field.searches_output() as usize) {
+        ch_query = ch_query.bind(&pattern);
+    }
+    chaos::clickhouse_latency().await;
+    let rows = ch_query.fetch_all::<CountRow>().await?;
+
+    Ok(rows.first().map(|row| row.count).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_like_pattern() {
+        assert_eq!(like_pattern("Refund"), "%refund%");
+        assert_eq!(like_pattern("100%_off\\"), "%100\\%\\_off\\\\%");
+    }
+
+    #[test]
+    fn test_searchable_text() {
+        assert_eq!(searchable_text(&None), "");
+        assert_eq!(searchable_text(&Some(json!("plain text"))), "plain text");
+        assert_eq!(
+            searchable_text(&Some(json!({"role": "user"}))),
+            r#"{"role":"user"}"#
+        );
+
+        let long = "é".repeat(MAX_INDEXED_BYTES);
+        let text = searchable_text(&Some(json!(long)));
+        assert!(text.len() <= MAX_INDEXED_BYTES);
+        assert!(text.chars().all(|c| c == 'é'));
+    }
+}
@@ -5,19 +5,28 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    analytics::custom_metrics::MetricExpression,
+    chaos,
     db::spans::{Span, SpanType},
-    traces::spans::SpanUsage,
+    features::{is_feature_enabled, Feature},
+    traces::{client_metadata::ClientMetadata, spans::SpanUsage},
 };
 
 use super::{
+    evaluation_scores::EvaluationScoreBucket,
+    filter_expression::CompiledFilter,
     modifiers::GroupByInterval,
     utils::{
         chrono_to_nanoseconds, execute_query, group_by_time_absolute_statement,
-        group_by_time_relative_statement,
+        group_by_time_relative_statement, insert_rows, nanoseconds_to_chrono,
+        validate_string_against_injection,
     },
     Aggregation, MetricTimeValue,
 };
 
+/// Number of `attribute_<slot>` columns of the spans table
+pub const MAX_PROMOTED_ATTRIBUTES: usize = 5;
+
 /// for inserting into clickhouse
 ///
 /// Don't change the order of the fields or their values
@@ -34,7 +43,7 @@ impl Into<u8> for SpanType {
     }
 }
 
-#[derive(Row, Serialize, Deserialize)]
+#[derive(Row, Serialize, Deserialize, Clone)]
 pub struct CHSpan {
     #[serde(with = "clickhouse::serde::uuid")]
     pub span_id: Uuid,
@@ -60,11 +69,30 @@ pub struct CHSpan {
     pub user_id: String,
     // Default value is <null>  backwards compatibility or if path attribute is not present
     pub path: String,
+    pub is_error: bool,
+    /// Client metadata, `<null>` if not known, see `traces::client_metadata`
+    pub sdk_language: String,
+    pub sdk_version: String,
+    pub environment: String,
+    pub region: String,
+    /// Values of the project's promoted attributes, `<null>` if the slot is free or the span
+    /// doesn't have the attribute, see `traces::promoted_attributes`
+    pub attribute_0: String,
+    pub attribute_1: String,
+    pub attribute_2: String,
+    pub attribute_3: String,
+    pub attribute_4: String,
+    /// Action of an agent step span, `<null>` for other spans
+    pub agent_action: String,
+    /// Element the action targeted, `<null>` if not known
+    pub agent_action_selector: String,
+    pub agent_action_retries: u32,
 }
 
 impl CHSpan {
     pub fn from_db_span(span: &Span, usage: SpanUsage, project_id: Uuid) -> Self {
         let span_attributes = span.get_attributes();
+        let client_metadata = ClientMetadata::from_span_attributes(&span_attributes);
 
         CHSpan {
             span_id: span.span_id,
@@ -90,32 +118,61 @@ impl CHSpan {
             provider: usage.provider_name.unwrap_or(String::from("<null>")),
             user_id: span_attributes.user_id().unwrap_or(String::from("<null>")),
             path: span_attributes.path().unwrap_or(String::from("<null>")),
+            is_error: span_attributes.is_error(),
+            sdk_language: client_metadata
+                .sdk_language
+                .unwrap_or(String::from("<null>")),
+            sdk_version: client_metadata
+                .sdk_version
+                .unwrap_or(String::from("<null>")),
+            environment: client_metadata
+                .environment
+                .unwrap_or(String::from("<null>")),
+            region: client_metadata.region.unwrap_or(String::from("<null>")),
+            attribute_0: String::from("<null>"),
+            attribute_1: String::from("<null>"),
+            attribute_2: String::from("<null>"),
+            attribute_3: String::from("<null>"),
+            attribute_4: String::from("<null>"),
+            agent_action: span_attributes
+                .agent_action()
+                .unwrap_or(String::from("<null>")),
+            agent_action_selector: span_attributes
+                .agent_action_selector()
+                .unwrap_or(String::from("<null>")),
+            agent_action_retries: span_attributes.agent_action_retries(),
         }
     }
-}
 
-pub async fn insert_span(clickhouse: clickhouse::Client, span: &CHSpan) -> Result<()> {
-    let ch_insert = clickhouse.insert("spans");
-    match ch_insert {
-        Ok(mut ch_insert) => {
-            ch_insert.write(span).await?;
-            let ch_insert_end_res = ch_insert.end().await;
-            match ch_insert_end_res {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    return Err(anyhow::anyhow!("Clickhouse span insertion failed: {:?}", e));
-                }
-            }
-        }
-        Err(e) => {
-            return Err(anyhow::anyhow!(
-                "Failed to insert span into Clickhouse: {:?}",
-                e
-            ));
+    pub fn set_promoted_attribute(&mut self, slot: usize, value: String) {
+        match slot {
+            0 => self.attribute_0 = value,
+            1 => self.attribute_1 = value,
+            2 => self.attribute_2 = value,
+            3 => self.attribute_3 = value,
+            4 => self.attribute_4 = value,
+            _ => {}
         }
     }
 }
 
+pub async fn insert_span(clickhouse: clickhouse::Client, span: &CHSpan) -> Result<()> {
+    insert_span_into_table(clickhouse, "spans", span).await
+}
+
+/// Insert a span processed by the candidate ingestion logic, see `traces::shadow`
+pub async fn insert_shadow_span(clickhouse: clickhouse::Client, span: &CHSpan) -> Result<()> {
+    insert_span_into_table(clickhouse, "spans_shadow", span).await
+}
+
+async fn insert_span_into_table(
+    clickhouse: clickhouse::Client,
+    table: &'static str,
+    span: &CHSpan,
+) -> Result<()> {
+    insert_rows(&clickhouse, table, std::slice::from_ref(span)).await
+}
+
 pub async fn get_total_trace_count_metrics_relative(
     clickhouse: clickhouse::Client,
     group_by_interval: GroupByInterval,
@@ -368,3 +425,855 @@ fn span_metric_query_absolute(
         group_by_time_absolute_statement(start_time, end_time, group_by_interval)
     )
 }
+
+#[derive(Deserialize, Row, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowDiffReport {
+    pub shadowed_spans: u64,
+    /// Shadowed spans for which the primary pipeline has not written a span
+    pub missing_in_primary: u64,
+    pub span_type_mismatches: u64,
+    pub model_mismatches: u64,
+    pub provider_mismatches: u64,
+    pub token_mismatches: u64,
+    pub cost_mismatches: u64,
+    pub path_mismatches: u64,
+}
+
+/// Compare spans written by the shadow pipeline with the ones written by the primary pipeline
+pub async fn get_shadow_diff_report(
+    clickhouse: clickhouse::Client,
+    project_id: Option<Uuid>,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<ShadowDiffReport> {
+    let project_filter = |table_alias: &str| {
+        project_id
+            .map(|project_id| format!("AND {table_alias}project_id = '{project_id}'"))
+            .unwrap_or_default()
+    };
+    let ch_start_time = chrono_to_nanoseconds(start_time);
+    let ch_end_time = chrono_to_nanoseconds(end_time);
+    // the primary spans are filtered like the shadow ones, so that the join doesn't read the
+    // whole table, and the mismatches are only counted for the spans that exist in both
+    let query_string = format!(
+        "
+    SELECT
+        COUNT(*) AS shadowed_spans,
+        countIf(NOT p.in_primary) AS missing_in_primary,
+        countIf(p.in_primary AND p.span_type != s.span_type) AS span_type_mismatches,
+        countIf(p.in_primary AND p.model != s.model) AS model_mismatches,
+        countIf(p.in_primary AND p.provider != s.provider) AS provider_mismatches,
+        countIf(p.in_primary AND (p.input_tokens != s.input_tokens OR p.output_tokens != s.output_tokens)) AS token_mismatches,
+        countIf(p.in_primary AND abs(p.total_cost - s.total_cost) > 1e-9) AS cost_mismatches,
+        countIf(p.in_primary AND p.path != s.path) AS path_mismatches
+    FROM spans_shadow s
+    LEFT JOIN (
+        SELECT
+            span_id,
+            project_id,
+            span_type,
+            model,
+            provider,
+            input_tokens,
+            output_tokens,
+            total_cost,
+            path,
+            true AS in_primary
+        FROM spans
+        WHERE
+            start_time >= fromUnixTimestamp64Nano({ch_start_time})
+            AND start_time <= fromUnixTimestamp64Nano({ch_end_time})
+            {}
+    ) p ON s.span_id = p.span_id AND s.project_id = p.project_id
+    WHERE
+        s.start_time >= fromUnixTimestamp64Nano({ch_start_time})
+        AND s.start_time <= fromUnixTimestamp64Nano({ch_end_time})
+        {}",
+        project_filter(""),
+        project_filter("s."),
+    );
+
+    let rows: Vec<ShadowDiffReport> = execute_query(&clickhouse, &query_string).await?;
+    Ok(rows.into_iter().next().unwrap_or(ShadowDiffReport {
+        shadowed_spans: 0,
+        missing_in_primary: 0,
+        span_type_mismatches: 0,
+        model_mismatches: 0,
+        provider_mismatches: 0,
+        token_mismatches: 0,
+        cost_mismatches: 0,
+        path_mismatches: 0,
+    }))
+}
+
+/// Latency percentiles, in seconds, of a pipeline's runs
+#[derive(Deserialize, Row, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineLatencyPercentiles {
+    pub pipeline: String,
+    pub run_count: u64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// Latency percentiles of the pipeline spans, grouped by pipeline name, that started in the
+/// time window
+pub async fn get_pipeline_latency_percentiles(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<PipelineLatencyPercentiles>> {
+    let pipeline_span_type: u8 = SpanType::PIPELINE.into();
+    let query_string = format!(
+        "
+    SELECT
+        name AS pipeline,
+        COUNT(*) AS run_count,
+        quantileTDigest(0.5)(latency) AS p50,
+        quantileTDigest(0.95)(latency) AS p95,
+        quantileTDigest(0.99)(latency) AS p99
+    FROM (
+        SELECT
+            name,
+            (toUnixTimestamp64Nano(end_time) - toUnixTimestamp64Nano(start_time)) / 1e9 AS latency
+        FROM spans
+        WHERE
+            project_id = '{project_id}'
+            AND span_type = {pipeline_span_type}
+            AND start_time >= fromUnixTimestamp64Nano({})
+            AND start_time < fromUnixTimestamp64Nano({})
+    )
+    GROUP BY pipeline
+    ORDER BY pipeline",
+        chrono_to_nanoseconds(start_time),
+        chrono_to_nanoseconds(end_time),
+    );
+
+    execute_query(&clickhouse, &query_string).await
+}
+
+/// Whether the latency of each span is measured, or of each trace, from the start of its first
+/// span to the end of its last
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum LatencyLevel {
+    #[default]
+    Span,
+    Trace,
+}
+
+/// Spans whose latency is measured or, for trace latency, one of which the traces must contain.
+/// Unset fields match every span.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencySpanFilter {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl LatencySpanFilter {
+    /// Predicate with a `?` placeholder for each of the returned values
+    fn to_condition(&self) -> (String, Vec<&str>) {
+        let (columns, values): (Vec<_>, Vec<_>) = [
+            ("name", &self.name),
+            ("path", &self.path),
+            ("model", &self.model),
+        ]
+        .into_iter()
+        .filter_map(|(column, value)| Some((format!("{column} = ?"), value.as_deref()?)))
+        .unzip();
+        if columns.is_empty() {
+            return ("true".to_string(), values);
+        }
+        (columns.join(" AND "), values)
+    }
+}
+
+/// Latency percentiles, in seconds, of the spans or traces that started in a time bucket.
+/// Buckets without any are filled with zeros.
+#[derive(Deserialize, Row, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyPercentilesPoint {
+    pub time: u32,
+    pub count: u64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// Latency percentiles over time of the spans that started in the time window or, for trace
+/// latency, of the traces whose spans started in it
+pub async fn get_latency_percentiles_over_time(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    level: LatencyLevel,
+    filter: &LatencySpanFilter,
+    group_by_interval: GroupByInterval,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<LatencyPercentilesPoint>> {
+    if !is_feature_enabled(Feature::FullBuild) {
+        return Ok(Vec::new());
+    }
+    let ch_round_time = group_by_interval.to_ch_truncate_time();
+    let (condition, values) = filter.to_condition();
+    let latencies = match level {
+        LatencyLevel::Span => format!(
+            "SELECT
+                {ch_round_time}(start_time) AS time,
+                (toUnixTimestamp64Nano(end_time) - toUnixTimestamp64Nano(start_time)) / 1e9 AS latency
+            FROM spans
+            WHERE project_id = ?
+                AND start_time >= fromUnixTimestamp64Nano(?)
+                AND start_time <= fromUnixTimestamp64Nano(?)
+                AND {condition}"
+        ),
+        LatencyLevel::Trace => format!(
+            "SELECT
+                {ch_round_time}(MIN(start_time)) AS time,
+                (toUnixTimestamp64Nano(MAX(end_time)) - toUnixTimestamp64Nano(MIN(start_time))) / 1e9 AS latency
+            FROM spans
+            WHERE project_id = ?
+                AND start_time >= fromUnixTimestamp64Nano(?)
+                AND start_time <= fromUnixTimestamp64Nano(?)
+            GROUP BY trace_id
+            HAVING countIf({condition}) > 0"
+        ),
+    };
+    let query_string = format!(
+        "SELECT
+            time,
+            COUNT(*) AS count,
+            quantileTDigest(0.5)(latency) AS p50,
+            quantileTDigest(0.9)(latency) AS p90,
+            quantileTDigest(0.95)(latency) AS p95,
+            quantileTDigest(0.99)(latency) AS p99
+        FROM ({latencies})
+        {}",
+        group_by_time_absolute_statement(start_time, end_time, group_by_interval)
+    );
+
+    let mut query = clickhouse
+        .query(&query_string)
+        .bind(project_id.to_string())
+        .bind(chrono_to_nanoseconds(start_time))
+        .bind(chrono_to_nanoseconds(end_time));
+    for value in values {
+        query = query.bind(value);
+    }
+    chaos::clickhouse_latency().await;
+    let points = query.fetch_all::<LatencyPercentilesPoint>().await?;
+
+    Ok(points)
+}
+
+/// Success rate and retries of an action type of agent steps
+#[derive(Deserialize, Row, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentActionStats {
+    pub action: String,
+    pub count: u64,
+    pub success_count: u64,
+    pub success_rate: f64,
+    pub average_retries: f64,
+}
+
+/// Element whose actions failed in agent steps
+#[derive(Deserialize, Row, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentFailureSelector {
+    pub action: String,
+    pub selector: String,
+    pub failure_count: u64,
+}
+
+/// Stats of each action type of the agent step spans that started in the time window, the most
+/// frequent action first. A step succeeded if its span has no error.
+pub async fn get_agent_action_stats(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<AgentActionStats>> {
+    let query_string = format!(
+        "
+    SELECT
+        agent_action AS action,
+        COUNT(*) AS count,
+        countIf(NOT is_error) AS success_count,
+        countIf(NOT is_error) / COUNT(*) AS success_rate,
+        AVG(agent_action_retries) AS average_retries
+    FROM spans
+    WHERE
+        project_id = '{project_id}'
+        AND agent_action != '<null>'
+        AND start_time >= fromUnixTimestamp64Nano({})
+        AND start_time < fromUnixTimestamp64Nano({})
+    GROUP BY action
+    ORDER BY count DESC, action",
+        chrono_to_nanoseconds(start_time),
+        chrono_to_nanoseconds(end_time),
+    );
+
+    execute_query(&clickhouse, &query_string).await
+}
+
+/// Selectors of the failed agent steps that started in the time window, the most failures first
+pub async fn get_agent_failure_selectors(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: u64,
+) -> Result<Vec<AgentFailureSelector>> {
+    let query_string = format!(
+        "
+    SELECT
+        agent_action AS action,
+        agent_action_selector AS selector,
+        COUNT(*) AS failure_count
+    FROM spans
+    WHERE
+        project_id = '{project_id}'
+        AND agent_action != '<null>'
+        AND agent_action_selector != '<null>'
+        AND is_error
+        AND start_time >= fromUnixTimestamp64Nano({})
+        AND start_time < fromUnixTimestamp64Nano({})
+    GROUP BY action, selector
+    ORDER BY failure_count DESC, action, selector
+    LIMIT {limit}",
+        chrono_to_nanoseconds(start_time),
+        chrono_to_nanoseconds(end_time),
+    );
+
+    execute_query(&clickhouse, &query_string).await
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum HeatmapMetric {
+    /// Number of traces
+    Traffic,
+    /// Share of traces with at least one failed span
+    ErrorRate,
+    /// Total cost in USD
+    Cost,
+}
+
+impl HeatmapMetric {
+    fn to_ch_expression(&self) -> &str {
+        match self {
+            HeatmapMetric::Traffic => "toFloat64(uniqExact(trace_id))",
+            HeatmapMetric::ErrorRate => "uniqExactIf(trace_id, is_error) / uniqExact(trace_id)",
+            HeatmapMetric::Cost => "SUM(total_cost)",
+        }
+    }
+}
+
+#[derive(Deserialize, Row, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HeatmapCell {
+    /// 1 is Monday, 7 is Sunday
+    pub day_of_week: u8,
+    /// Hour of the day in UTC
+    pub hour: u8,
+    pub value: f64,
+}
+
+/// Metric of the spans that started in the time window, grouped by day of the week and hour of
+/// the day. Hours without spans are not returned. If `pipeline` is set, only the traces of the
+/// pipeline's runs are included.
+pub async fn get_hour_of_week_heatmap(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    pipeline: Option<&str>,
+    metric: HeatmapMetric,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<HeatmapCell>> {
+    let ch_start_time = chrono_to_nanoseconds(start_time);
+    let ch_end_time = chrono_to_nanoseconds(end_time);
+    let pipeline_filter = match pipeline {
+        Some(pipeline) => {
+            validate_string_against_injection(pipeline)?;
+            let pipeline_span_type: u8 = SpanType::PIPELINE.into();
+            format!(
+                "AND trace_id IN (
+            SELECT trace_id
+            FROM spans
+            WHERE
+                project_id = '{project_id}'
+                AND span_type = {pipeline_span_type}
+                AND name = '{pipeline}'
+                AND start_time >= fromUnixTimestamp64Nano({ch_start_time})
+                AND start_time < fromUnixTimestamp64Nano({ch_end_time})
+        )"
+            )
+        }
+        None => String::new(),
+    };
+    let query_string = format!(
+        "
+    SELECT
+        toDayOfWeek(start_time) AS day_of_week,
+        toHour(start_time) AS hour,
+        {} AS value
+    FROM spans
+    WHERE
+        project_id = '{project_id}'
+        AND start_time >= fromUnixTimestamp64Nano({ch_start_time})
+        AND start_time < fromUnixTimestamp64Nano({ch_end_time})
+        {pipeline_filter}
+    GROUP BY day_of_week, hour
+    ORDER BY day_of_week, hour",
+        metric.to_ch_expression(),
+    );
+
+    execute_query(&clickhouse, &query_string).await
+}
+
+#[derive(Row, Deserialize)]
+struct TraceLatencyAndCostRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    trace_id: Uuid,
+    latency: f64,
+    cost: f64,
+}
+
+/// Latency, in seconds, and total cost of a trace
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceLatencyAndCost {
+    pub trace_id: Uuid,
+    pub latency: f64,
+    pub cost: f64,
+}
+
+/// Traces that have no spans in the project are not returned
+pub async fn get_traces_latency_and_cost(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    trace_ids: &[Uuid],
+) -> Result<Vec<TraceLatencyAndCost>> {
+    if trace_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let trace_ids_str = trace_ids
+        .iter()
+        .map(|id| format!("'{}'", id))
+        .collect::<Vec<String>>()
+        .join(",");
+    let query_string = format!(
+        "
+    SELECT
+        trace_id,
+        (MAX(toUnixTimestamp64Nano(end_time)) - MIN(toUnixTimestamp64Nano(start_time))) / 1e9
+            AS latency,
+        SUM(total_cost) AS cost
+    FROM spans
+    WHERE
+        project_id = '{project_id}'
+        AND trace_id IN ({trace_ids_str})
+    GROUP BY trace_id"
+    );
+
+    let rows: Vec<TraceLatencyAndCostRow> = execute_query(&clickhouse, &query_string).await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| TraceLatencyAndCost {
+            trace_id: row.trace_id,
+            latency: row.latency,
+            cost: row.cost,
+        })
+        .collect())
+}
+
+#[derive(Row, Deserialize)]
+struct CustomMetricBounds {
+    lower_bound: Option<f64>,
+    upper_bound: Option<f64>,
+}
+
+/// Empty if no span in the time window has a value for the metric
+pub async fn get_custom_metric_buckets(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    expression: &MetricExpression,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    bucket_count: u64,
+) -> Result<Vec<EvaluationScoreBucket>> {
+    let metric = expression.to_sql();
+    let conditions = format!(
+        "project_id = '{project_id}'
+        AND start_time >= fromUnixTimestamp64Nano({})
+        AND start_time < fromUnixTimestamp64Nano({})",
+        chrono_to_nanoseconds(start_time),
+        chrono_to_nanoseconds(end_time),
+    );
+
+    let bounds_query = format!(
+        "SELECT min({metric}) AS lower_bound, max({metric}) AS upper_bound
+        FROM spans
+        WHERE {conditions}"
+    );
+    let bounds: Vec<CustomMetricBounds> = execute_query(&clickhouse, &bounds_query).await?;
+    let Some(CustomMetricBounds {
+        lower_bound: Some(lower_bound),
+        upper_bound: Some(upper_bound),
+    }) = bounds.into_iter().next()
+    else {
+        return Ok(Vec::new());
+    };
+
+    let step_size = (upper_bound - lower_bound) / bucket_count as f64;
+    let interval_nums = (1..=bucket_count)
+        .map(|num| num.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+    // Same buckets as for evaluation scores, see `get_evaluation_score_buckets_based_on_bounds`
+    let query = format!(
+        "
+WITH intervals AS (
+    SELECT
+        arrayJoin([{interval_nums}]) AS interval_num,
+        {:?} + ((interval_num - 1) * {:?}) AS lower_bound,
+        CASE
+            WHEN interval_num = {bucket_count} THEN {:?}
+            ELSE {:?} + (interval_num * {:?})
+        END AS upper_bound
+)
+SELECT
+    intervals.lower_bound,
+    intervals.upper_bound,
+    COUNT(CASE
+        WHEN value >= intervals.lower_bound AND value < intervals.upper_bound THEN 1
+        WHEN intervals.interval_num = {bucket_count}
+            AND value >= intervals.lower_bound
+            AND value <= intervals.upper_bound THEN 1
+        ELSE NULL
+    END) AS height
+FROM (SELECT {metric} AS value FROM spans WHERE {conditions})
+JOIN intervals ON 1 = 1
+GROUP BY intervals.lower_bound, intervals.upper_bound, intervals.interval_num
+ORDER BY intervals.interval_num",
+        lower_bound, step_size, upper_bound, lower_bound, step_size
+    );
+
+    execute_query(&clickhouse, &query).await
+}
+
+fn promoted_attribute_column(slot: usize) -> Result<String> {
+    if slot >= MAX_PROMOTED_ATTRIBUTES {
+        return Err(anyhow::anyhow!("Invalid promoted attribute slot: {}", slot));
+    }
+    Ok(format!("attribute_{slot}"))
+}
+
+/// Span ids, twice, and values of a backfill mutation are inlined into the query, which must stay
+/// well below ClickHouse's `max_query_size` of 256 KiB
+const MAX_PROMOTED_ATTRIBUTE_MUTATION_BYTES: usize = 64 * 1024;
+
+/// Splits the values, so that each mutation stays below `MAX_PROMOTED_ATTRIBUTE_MUTATION_BYTES`
+fn promoted_attribute_mutation_batches(values: Vec<(Uuid, String)>) -> Vec<Vec<(Uuid, String)>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_bytes = 0;
+    for (span_id, value) in values {
+        // two quoted span ids and a quoted value
+        let bytes = 2 * 40 + value.len() + 4;
+        if !batch.is_empty() && batch_bytes + bytes > MAX_PROMOTED_ATTRIBUTE_MUTATION_BYTES {
+            batches.push(std::mem::take(&mut batch));
+            batch_bytes = 0;
+        }
+        batch.push((span_id, value));
+        batch_bytes += bytes;
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+/// Backfills the values of a promoted attribute on existing spans. Each mutation waits until
+/// ClickHouse has applied it, so that a backfill doesn't queue up mutations faster than they
+/// are merged.
+pub async fn update_promoted_attribute_values(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    slot: usize,
+    values: Vec<(Uuid, String)>,
+) -> Result<()> {
+    let column = promoted_attribute_column(slot)?;
+
+    for batch in promoted_attribute_mutation_batches(values) {
+        let (span_ids, values): (Vec<String>, Vec<String>) = batch
+            .into_iter()
+            .map(|(span_id, value)| (span_id.to_string(), value))
+            .unzip();
+
+        clickhouse
+            .query(&format!(
+                "ALTER TABLE spans
+                UPDATE {column} = transform(toString(span_id), ?, ?, {column})
+                WHERE project_id = ? AND toString(span_id) IN ?
+                SETTINGS mutations_sync = 1"
+            ))
+            .bind(&span_ids)
+            .bind(values)
+            .bind(project_id.to_string())
+            .bind(&span_ids)
+            .execute()
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Resets a promoted attribute column of the project's spans, so that the slot can be reused
+pub async fn clear_promoted_attribute(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    slot: usize,
+) -> Result<()> {
+    let column = promoted_attribute_column(slot)?;
+    clickhouse
+        .query(&format!(
+            "ALTER TABLE spans UPDATE {column} = '<null>' WHERE project_id = ?"
+        ))
+        .bind(project_id.to_string())
+        .execute()
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Row, Deserialize)]
+struct FilteredSpanRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    span_id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    trace_id: Uuid,
+    name: String,
+    span_type_name: String,
+    start_time_ns: i64,
+    end_time_ns: i64,
+    model: String,
+    total_tokens: i64,
+    span_cost: f64,
+    is_error: bool,
+}
+
+/// Span matching a filter expression, without its input and output
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredSpan {
+    pub span_id: Uuid,
+    pub trace_id: Uuid,
+    pub name: String,
+    pub span_type: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub model: Option<String>,
+    pub total_tokens: i64,
+    pub cost: f64,
+    pub is_error: bool,
+}
+
+impl From<FilteredSpanRow> for FilteredSpan {
+    fn from(row: FilteredSpanRow) -> Self {
+        Self {
+            span_id: row.span_id,
+            trace_id: row.trace_id,
+            name: row.name,
+            span_type: row.span_type_name,
+            start_time: nanoseconds_to_chrono(row.start_time_ns),
+            end_time: nanoseconds_to_chrono(row.end_time_ns),
+            model: Some(row.model).filter(|model| model != "<null>" && !model.is_empty()),
+            total_tokens: row.total_tokens,
+            cost: row.span_cost,
+            is_error: row.is_error,
+        }
+    }
+}
+
+/// Spans of the period that match the filter, compiled for `FilterTarget::Spans`, the newest
+/// first
+pub async fn query_spans(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    filter: &CompiledFilter,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: u64,
+    offset: u64,
+) -> Result<Vec<FilteredSpan>> {
+    if !is_feature_enabled(Feature::FullBuild) {
+        return Ok(Vec::new());
+    }
+    // the aliases differ from the column names, which the filter refers to
+    let query_string = format!(
+        "SELECT
+            span_id,
+            trace_id,
+            name,
+            arrayElement(
+                ['DEFAULT', 'LLM', 'PIPELINE', 'EXECUTOR', 'EVALUATOR', 'EVALUATION'],
+                span_type + 1
+            ) AS span_type_name,
+            toUnixTimestamp64Nano(start_time) AS start_time_ns,
+            toUnixTimestamp64Nano(end_time) AS end_time_ns,
+            model,
+            total_tokens,
+            total_cost AS span_cost,
+            is_error
+        FROM spans
+        WHERE project_id = ?
+            AND start_time >= fromUnixTimestamp64Nano(?)
+            AND start_time <= fromUnixTimestamp64Nano(?)
+            AND {}
+        ORDER BY start_time DESC
+        LIMIT ? OFFSET ?",
+        filter.sql
+    );
+
+    let query = clickhouse
+        .query(&query_string)
+        .bind(project_id.to_string())
+        .bind(chrono_to_nanoseconds(start_time))
+        .bind(chrono_to_nanoseconds(end_time));
+    chaos::clickhouse_latency().await;
+    let rows = filter
+        .bind(query)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all::<FilteredSpanRow>()
+        .await?;
+
+    Ok(rows.into_iter().map(Into::into).collect())
+}
+
+#[derive(Row, Deserialize)]
+struct FilteredTraceRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    trace_id: Uuid,
+    start_time_ns: i64,
+    end_time_ns: i64,
+    trace_tokens: i64,
+    trace_cost: f64,
+    span_count: u64,
+    has_error: bool,
+}
+
+/// Trace matching a filter expression, aggregated from its spans in the period
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredTrace {
+    pub trace_id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// In seconds
+    pub latency: f64,
+    pub total_tokens: i64,
+    pub cost: f64,
+    pub span_count: u64,
+    pub has_error: bool,
+}
+
+impl From<FilteredTraceRow> for FilteredTrace {
+    fn from(row: FilteredTraceRow) -> Self {
+        Self {
+            trace_id: row.trace_id,
+            start_time: nanoseconds_to_chrono(row.start_time_ns),
+            end_time: nanoseconds_to_chrono(row.end_time_ns),
+            latency: (row.end_time_ns - row.start_time_ns) as f64 / 1e9,
+            total_tokens: row.trace_tokens,
+            cost: row.trace_cost,
+            span_count: row.span_count,
+            has_error: row.has_error,
+        }
+    }
+}
+
+/// Traces of the spans that started in the period, that match the filter, compiled for
+/// `FilterTarget::Traces`, the newest first
+pub async fn query_traces(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+    filter: &CompiledFilter,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: u64,
+    offset: u64,
+) -> Result<Vec<FilteredTrace>> {
+    if !is_feature_enabled(Feature::FullBuild) {
+        return Ok(Vec::new());
+    }
+    let query_string = format!(
+        "SELECT
+            trace_id,
+            min(toUnixTimestamp64Nano(start_time)) AS start_time_ns,
+            max(toUnixTimestamp64Nano(end_time)) AS end_time_ns,
+            sum(total_tokens) AS trace_tokens,
+            sum(total_cost) AS trace_cost,
+            count() AS span_count,
+            max(is_error) AS has_error
+        FROM spans
+        WHERE project_id = ?
+            AND start_time >= fromUnixTimestamp64Nano(?)
+            AND start_time <= fromUnixTimestamp64Nano(?)
+        GROUP BY trace_id
+        HAVING {}
+        ORDER BY start_time_ns DESC
+        LIMIT ? OFFSET ?",
+        filter.sql
+    );
+
+    let query = clickhouse
+        .query(&query_string)
+        .bind(project_id.to_string())
+        .bind(chrono_to_nanoseconds(start_time))
+        .bind(chrono_to_nanoseconds(end_time));
+    chaos::clickhouse_latency().await;
+    let rows = filter
+        .bind(query)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all::<FilteredTraceRow>()
+        .await?;
+
+    Ok(rows.into_iter().map(Into::into).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_promoted_attribute_mutation_batches() {
+        let values = (0..2_000)
+            .map(|_| (Uuid::new_v4(), "x".repeat(100)))
+            .collect::<Vec<_>>();
+        let batches = promoted_attribute_mutation_batches(values);
+
+        assert!(batches.len() > 1);
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), 2_000);
+        for batch in batches {
+            let bytes = batch
+                .iter()
+                .map(|(span_id, value)| 2 * span_id.to_string().len() + value.len())
+                .sum::<usize>();
+            assert!(bytes <= MAX_PROMOTED_ATTRIBUTE_MUTATION_BYTES);
+        }
+        assert!(promoted_attribute_mutation_batches(Vec::new()).is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    chaos,
+    features::{is_feature_enabled, Feature},
+};
+
+use super::utils::nanoseconds_to_chrono;
+
+/// Tables with a project's rows and their time column
+const PROJECT_TABLES: [(&str, &str); 7] = [
+    ("spans", "start_time"),
+    ("span_search", "start_time"),
+    ("events", "timestamp"),
+    ("evaluation_scores", "timestamp"),
+    ("span_scores", "timestamp"),
+    ("browser_session_events", "timestamp"),
+    ("browser_snapshots", "timestamp"),
+];
+
+#[derive(Row, Deserialize)]
+struct TableStatsRow {
+    table_name: String,
+    row_count: u64,
+    min_time_ns: i64,
+    max_time_ns: i64,
+}
+
+/// Rows of a project in a table, and the time range they cover
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TableStats {
+    pub table: String,
+    pub rows: u64,
+    /// None if the table has no rows of the project
+    pub min_time: Option<DateTime<Utc>>,
+    pub max_time: Option<DateTime<Utc>>,
+}
+
+impl From<TableStatsRow> for TableStats {
+    fn from(row: TableStatsRow) -> Self {
+        let has_rows = row.row_count > 0;
+        Self {
+            table: row.table_name,
+            rows: row.row_count,
+            min_time: Some(nanoseconds_to_chrono(row.min_time_ns)).filter(|_| has_rows),
+            max_time: Some(nanoseconds_to_chrono(row.max_time_ns)).filter(|_| has_rows),
+        }
+    }
+}
+
+/// Row count and time range of the project's rows in each table
+pub async fn get_project_table_stats(
+    clickhouse: clickhouse::Client,
+    project_id: Uuid,
+) -> Result<Vec<TableStats>> {
+    if !is_feature_enabled(Feature::FullBuild) {
+        return Ok(Vec::new());
+    }
+    let query_string = PROJECT_TABLES
+        .iter()
+        .map(|(table, time_column)| {
+            format!(
+                "SELECT
+                    '{table}' AS table_name,
+                    count() AS row_count,
+                    toUnixTimestamp64Nano(min({time_column})) AS min_time_ns,
+                    toUnixTimestamp64Nano(max({time_column})) AS max_time_ns
+                FROM {table}
+                WHERE project_id = ?"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+
+    let mut query = clickhouse.query(&query_string);
+    for _ in PROJECT_TABLES {
+        query = query.bind(project_id.to_string());
+    }
+    chaos::clickhouse_latency().await;
+    let rows = query.fetch_all::<TableStatsRow>().await?;
+
+    // UNION ALL doesn't keep the order of the tables
+    let mut stats = rows.into_iter().map(TableStats::from).collect::<Vec<_>>();
+    stats.sort_by_key(|stats| {
+        PROJECT_TABLES
+            .iter()
+            .position(|(table, _)| *table == stats.table)
+    });
+    Ok(stats)
+}
@@ -1,22 +1,84 @@
+use std::time::{Duration, Instant};
+
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use clickhouse::Row;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    chaos,
     db::utils::validate_sql_string,
     features::{is_feature_enabled, Feature},
+    metrics,
+    traces::self_tracing::record_clickhouse_query_span,
 };
 
 use super::modifiers::GroupByInterval;
 
+/// Inserts are on the ingestion path, so they are only retried briefly before the rows are dropped
+const MAX_INSERT_RETRIES: u32 = 2;
+const INSERT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
 #[derive(Deserialize, Row)]
 pub struct TimeBounds {
     pub min_time: i64,
     pub max_time: i64,
 }
 
+/// Insert the rows in one statement, retrying failed inserts, and record them in the table's
+/// batch writer metrics.
+///
+/// An insert can fail after ClickHouse has written the rows, so all attempts carry the same
+/// `insert_deduplication_token` and ClickHouse skips a retried batch it already wrote. This
+/// needs the table's `non_replicated_deduplication_window`, see
+/// clickhouse/015000-insert-deduplication.sql.
+pub async fn insert_rows<T: Row + Serialize>(
+    clickhouse: &clickhouse::Client,
+    table: &'static str,
+    rows: &[T],
+) -> Result<()> {
+    let writer_metrics = metrics::batch_writer(table);
+    let clickhouse = clickhouse
+        .clone()
+        .with_option("insert_deduplication_token", Uuid::new_v4().to_string());
+    let mut attempt = 0;
+    loop {
+        let start = Instant::now();
+        chaos::clickhouse_latency().await;
+        match try_insert_rows(&clickhouse, table, rows).await {
+            Ok(()) => {
+                writer_metrics.record_flush(rows.len(), start.elapsed());
+                return Ok(());
+            }
+            Err(e) => {
+                if attempt >= MAX_INSERT_RETRIES {
+                    writer_metrics.record_failed_flush();
+                    writer_metrics.record_dropped(rows.len());
+                    return Err(e.context(format!("Failed to insert into Clickhouse {table}")));
+                }
+                attempt += 1;
+                writer_metrics.record_retry();
+                log::warn!("Retrying insert into Clickhouse {table}, attempt {attempt}: {e:?}");
+                tokio::time::sleep(INSERT_RETRY_BACKOFF * attempt).await;
+            }
+        }
+    }
+}
+
+async fn try_insert_rows<T: Row + Serialize>(
+    clickhouse: &clickhouse::Client,
+    table: &str,
+    rows: &[T],
+) -> Result<()> {
+    let mut insert = clickhouse.insert(table)?;
+    for row in rows {
+        insert.write(row).await?;
+    }
+    insert.end().await?;
+    Ok(())
+}
+
 pub fn chrono_to_nanoseconds(chrono_dt: DateTime<Utc>) -> i64 {
     let timestamp = chrono_dt.timestamp(); // seconds since the Unix epoch
     let nanos = chrono_dt.timestamp_subsec_nanos(); // nanoseconds part
@@ -42,6 +104,14 @@ pub fn nanoseconds_to_chrono(timestamp_nanos: i64) -> DateTime<Utc> {
     })
 }
 
+/// Reads a timestamp serialized as nanoseconds, like the row types' `serialize_timestamp` do
+pub fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(nanoseconds_to_chrono(i64::deserialize(deserializer)?))
+}
+
 pub fn group_by_time_absolute_statement(
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
@@ -147,6 +217,23 @@ where
         return Ok(Vec::new());
     }
 
+    let start_time = Utc::now();
+    let res = fetch_all(clickhouse, query_string).await;
+    record_clickhouse_query_span(
+        query_string,
+        start_time,
+        Utc::now(),
+        res.as_ref().ok().map(|rows| rows.len()),
+        res.as_ref().err().map(|e| e.to_string()),
+    );
+    res
+}
+
+async fn fetch_all<'de, T>(clickhouse: &clickhouse::Client, query_string: &str) -> Result<Vec<T>>
+where
+    T: Row + Deserialize<'de>,
+{
+    chaos::clickhouse_latency().await;
     let mut cursor = clickhouse.query(query_string).fetch::<T>()?;
 
     let mut res = Vec::new();
@@ -0,0 +1,147 @@
+//! Fault injection for resilience testing, compiled only with the `chaos` cargo feature.
+//!
+//! With the feature enabled, faults are injected at the rates given by:
+//! - `CHAOS_CLICKHOUSE_LATENCY_RATE` and `CHAOS_CLICKHOUSE_LATENCY_MS`: delay of ClickHouse queries
+//!   and inserts
+//! - `CHAOS_POSTGRES_FAILURE_RATE`: failure of span writes to Postgres, before they reach the DB
+//! - `CHAOS_QUEUE_DROP_RATE`: spans silently not published to RabbitMQ
+//!
+//! Rates are between 0 and 1. Without the feature, all hooks are no-ops.
+
+use anyhow::Result;
+
+#[cfg(feature = "chaos")]
+mod faults {
+    use std::sync::RwLock;
+
+    use lazy_static::lazy_static;
+    use rand::Rng;
+
+    #[derive(Clone, Debug, Default)]
+    pub struct ChaosConfig {
+        pub clickhouse_latency_rate: f64,
+        pub clickhouse_latency_ms: u64,
+        pub postgres_failure_rate: f64,
+        pub queue_drop_rate: f64,
+    }
+
+    fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    }
+
+    impl ChaosConfig {
+        fn from_env() -> Self {
+            Self {
+                clickhouse_latency_rate: env_or("CHAOS_CLICKHOUSE_LATENCY_RATE", 0.0),
+                clickhouse_latency_ms: env_or("CHAOS_CLICKHOUSE_LATENCY_MS", 1000),
+                postgres_failure_rate: env_or("CHAOS_POSTGRES_FAILURE_RATE", 0.0),
+                queue_drop_rate: env_or("CHAOS_QUEUE_DROP_RATE", 0.0),
+            }
+        }
+    }
+
+    lazy_static! {
+        static ref CONFIG: RwLock<ChaosConfig> = RwLock::new(ChaosConfig::from_env());
+    }
+
+    #[cfg(test)]
+    lazy_static! {
+        static ref TEST_CONFIG_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+    }
+
+    /// Held by tests that change the configuration, which is shared by all tests
+    #[cfg(test)]
+    pub async fn lock_config() -> tokio::sync::MutexGuard<'static, ()> {
+        TEST_CONFIG_LOCK.lock().await
+    }
+
+    pub fn config() -> ChaosConfig {
+        CONFIG.read().unwrap().clone()
+    }
+
+    /// Replace the configuration read from env, e.g. in tests
+    pub fn set_config(config: ChaosConfig) {
+        *CONFIG.write().unwrap() = config;
+    }
+
+    pub fn roll(rate: f64) -> bool {
+        rate > 0.0 && rand::thread_rng().gen_bool(rate.min(1.0))
+    }
+}
+
+#[cfg(all(test, feature = "chaos"))]
+pub use faults::lock_config;
+#[cfg(feature = "chaos")]
+pub use faults::{set_config, ChaosConfig};
+
+/// Delays a ClickHouse call
+pub async fn clickhouse_latency() {
+    #[cfg(feature = "chaos")]
+    {
+        let config = faults::config();
+        if faults::roll(config.clickhouse_latency_rate) {
+            log::warn!(
+                "chaos: delaying ClickHouse by {}ms",
+                config.clickhouse_latency_ms
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(
+                config.clickhouse_latency_ms,
+            ))
+            .await;
+        }
+    }
+}
+
+/// Fails a Postgres write
+pub fn postgres_failure() -> Result<()> {
+    #[cfg(feature = "chaos")]
+    if faults::roll(faults::config().postgres_failure_rate) {
+        return Err(anyhow::anyhow!("chaos: injected Postgres failure"));
+    }
+    Ok(())
+}
+
+/// Whether a message should be dropped instead of being published to the queue
+pub fn drop_queue_message() -> bool {
+    #[cfg(feature = "chaos")]
+    if faults::roll(faults::config().queue_drop_rate) {
+        log::warn!("chaos: dropping queue message");
+        return true;
+    }
+    false
+}
+
+#[cfg(all(test, feature = "chaos"))]
+mod tests {
+    use super::*;
+
+    // Retries and dead-lettering of failed span writes are tested in traces::consumer
+    #[tokio::test]
+    async fn test_faults() {
+        let _config_lock = lock_config().await;
+        set_config(ChaosConfig::default());
+        assert!(postgres_failure().is_ok());
+        assert!(!drop_queue_message());
+
+        set_config(ChaosConfig {
+            clickhouse_latency_rate: 1.0,
+            clickhouse_latency_ms: 50,
+            postgres_failure_rate: 1.0,
+            queue_drop_rate: 1.0,
+        });
+        assert!(drop_queue_message());
+
+        let start = std::time::Instant::now();
+        clickhouse_latency().await;
+        assert!(start.elapsed().as_millis() >= 50);
+        assert!(postgres_failure()
+            .unwrap_err()
+            .to_string()
+            .contains("injected Postgres failure"));
+
+        set_config(ChaosConfig::default());
+    }
+}
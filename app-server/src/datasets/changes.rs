@@ -0,0 +1,91 @@
+//! Cursor of a dataset's change feed, see `db::dataset_versions::get_datapoint_changes_after`.
+//!
+//! The cursor is `<version>` after all changes of a version, e.g. one whose datapoints were read
+//! with the datapoints endpoint, or `<version>:<datapoint_id>` after a change within a version.
+//! Clients treat it as opaque and pass back the `nextCursor` of the previous page.
+
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChangeCursor {
+    pub version: i32,
+    /// None after all changes of the version
+    pub datapoint_id: Option<Uuid>,
+}
+
+impl ChangeCursor {
+    /// Before all changes, including the datapoints the dataset was created with
+    pub fn start() -> Self {
+        Self {
+            version: -1,
+            datapoint_id: None,
+        }
+    }
+
+    pub fn after_version(version: i32) -> Self {
+        Self {
+            version,
+            datapoint_id: None,
+        }
+    }
+
+    pub fn parse(cursor: &str) -> Result<Self> {
+        let (version, datapoint_id) = match cursor.split_once(':') {
+            Some((version, datapoint_id)) => (version, Some(datapoint_id)),
+            None => (cursor, None),
+        };
+        let version = version
+            .parse::<i32>()
+            .ok()
+            .filter(|version| *version >= -1)
+            .ok_or_else(|| anyhow!("Invalid cursor {}", cursor))?;
+        let datapoint_id = datapoint_id
+            .map(Uuid::parse_str)
+            .transpose()
+            .map_err(|_| anyhow!("Invalid cursor {}", cursor))?;
+
+        Ok(Self {
+            version,
+            datapoint_id,
+        })
+    }
+}
+
+impl fmt::Display for ChangeCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.datapoint_id {
+            Some(datapoint_id) => write!(f, "{}:{}", self.version, datapoint_id),
+            None => write!(f, "{}", self.version),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursors = [
+            ChangeCursor::start(),
+            ChangeCursor::after_version(7),
+            ChangeCursor {
+                version: 3,
+                datapoint_id: Some(Uuid::new_v4()),
+            },
+        ];
+        for cursor in cursors {
+            assert_eq!(ChangeCursor::parse(&cursor.to_string()).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_cursor() {
+        assert!(ChangeCursor::parse("").is_err());
+        assert!(ChangeCursor::parse("-2").is_err());
+        assert!(ChangeCursor::parse("3:not-a-uuid").is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Import of datasets from the Hugging Face Hub, read page by page from the dataset viewer API.
+//!
+//! Imports run in the background and insert their rows in batches, each batch committed together
+//! with the import's progress. A failed or interrupted import is resumed from the first row that
+//! wasn't imported. Private and gated datasets are read with the project's `HF_TOKEN` key.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self,
+        dataset_imports::{DatasetImport, DatasetImportStatus},
+        DB,
+    },
+    logging,
+    network::egress,
+    semantic_search::SemanticSearch,
+    traces::evaluators::get_stored_env,
+};
+
+use super::import::ImportMapping;
+
+const DATASET_VIEWER_URL: &str = "https://datasets-server.huggingface.co";
+const HF_TOKEN_NAME: &str = "HF_TOKEN";
+/// The most rows the dataset viewer returns per request
+const PAGE_SIZE: i64 = 100;
+/// Pages inserted together as one version of the dataset
+const PAGES_PER_BATCH: i64 = 10;
+
+#[derive(Deserialize)]
+struct SplitsResponse {
+    splits: Vec<SplitEntry>,
+}
+
+#[derive(Deserialize)]
+struct SplitEntry {
+    config: String,
+    split: String,
+}
+
+#[derive(Deserialize)]
+struct RowsResponse {
+    rows: Vec<RowEntry>,
+    num_rows_total: i64,
+}
+
+#[derive(Deserialize)]
+struct RowEntry {
+    row: Map<String, Value>,
+}
+
+pub struct HuggingFaceClient {
+    client: reqwest::Client,
+    token: Option<String>,
+}
+
+impl HuggingFaceClient {
+    pub async fn for_project(db: Arc<DB>, project_id: Uuid) -> Result<Self> {
+        let token = get_stored_env(db, project_id).await?.remove(HF_TOKEN_NAME);
+        Ok(Self {
+            client: egress::http_client(),
+            token,
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+        let mut request = self
+            .client
+            .get(format!("{DATASET_VIEWER_URL}/{path}"))
+            .query(query);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let res = request.send().await?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            return Err(anyhow!("Hugging Face request failed with {status}: {body}"));
+        }
+        Ok(res.json::<T>().await?)
+    }
+
+    /// Config of the split, the first config that has it if `config` is None
+    pub async fn resolve_config(
+        &self,
+        repo_id: &str,
+        config: Option<&str>,
+        split: &str,
+    ) -> Result<String> {
+        let splits = self
+            .get::<SplitsResponse>("splits", &[("dataset", repo_id.to_string())])
+            .await?
+            .splits;
+        splits
+            .into_iter()
+            .find(|entry| entry.split == split && config.map_or(true, |c| entry.config == c))
+            .map(|entry| entry.config)
+            .ok_or_else(|| anyhow!("Dataset {repo_id} has no split {split}"))
+    }
+
+    async fn get_rows(&self, import: &DatasetImport, offset: i64) -> Result<RowsResponse> {
+        self.get(
+            "rows",
+            &[
+                ("dataset", import.repo_id.clone()),
+                ("config", import.config.clone()),
+                ("split", import.split.clone()),
+                ("offset", offset.to_string()),
+                ("length", PAGE_SIZE.to_string()),
+            ],
+        )
+        .await
+    }
+}
+
+pub fn spawn_import(db: Arc<DB>, semantic_search: Arc<dyn SemanticSearch>, import: DatasetImport) {
+    logging::spawn(async move {
+        let id = import.id;
+        if let Err(e) = run_import(db.clone(), semantic_search, &import).await {
+            log::error!("Failed to import dataset. id [{}]: {:?}", id, e);
+            if let Err(e) = db::dataset_imports::update_dataset_import_status(
+                &db.pool,
+                &id,
+                DatasetImportStatus::FAILED,
+                None,
+                Some(&e.to_string()),
+            )
+            .await
+            {
+                log::error!(
+                    "Failed to mark dataset import as failed. id [{}]: {:?}",
+                    id,
+                    e
+                );
+            }
+        }
+    });
+}
+
+async fn run_import(
+    db: Arc<DB>,
+    semantic_search: Arc<dyn SemanticSearch>,
+    import: &DatasetImport,
+) -> Result<()> {
+    let mapping = serde_json::from_value::<ImportMapping>(import.mapping.clone())?;
+    let dataset = db::datasets::get_dataset(&db.pool, import.project_id, import.dataset_id).await?;
+    let client = HuggingFaceClient::for_project(db.clone(), import.project_id).await?;
+    db::dataset_imports::update_dataset_import_status(
+        &db.pool,
+        &import.id,
+        DatasetImportStatus::RUNNING,
+        None,
+        None,
+    )
+    .await?;
+
+    let mut offset = import.imported_rows;
+    loop {
+        let mut datapoints = Vec::new();
+        let (mut read_rows, mut failed_rows) = (0, 0);
+        let mut total_rows = None;
+        let mut finished = false;
+        for _ in 0..PAGES_PER_BATCH {
+            let page = client.get_rows(import, offset + read_rows).await?;
+            let page_rows = page.rows.len() as i64;
+            for entry in page.rows {
+                match mapping.map_row(import.dataset_id, entry.row) {
+                    Ok(datapoint) => datapoints.push(datapoint),
+                    Err(_) => failed_rows += 1,
+                }
+            }
+            read_rows += page_rows;
+            total_rows = Some(page.num_rows_total);
+            if page_rows < PAGE_SIZE || offset + read_rows >= page.num_rows_total {
+                finished = true;
+                break;
+            }
+        }
+
+        let datapoints = db::dataset_imports::insert_dataset_import_batch(
+            &db.pool,
+            import,
+            datapoints,
+            read_rows,
+            failed_rows,
+        )
+        .await?;
+        offset += read_rows;
+        let status = if finished {
+            DatasetImportStatus::DONE
+        } else {
+            DatasetImportStatus::RUNNING
+        };
+        db::dataset_imports::update_dataset_import_status(
+            &db.pool, &import.id, status, total_rows, None,
+        )
+        .await?;
+
+        if dataset.indexed_on.is_some() && !datapoints.is_empty() {
+            dataset
+                .index_new_points(
+                    datapoints,
+                    semantic_search.clone(),
+                    import.project_id.to_string(),
+                    dataset.indexed_on.clone(),
+                )
+                .await?;
+        }
+        if finished {
+            return Ok(());
+        }
+    }
+}
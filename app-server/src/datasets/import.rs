@@ -0,0 +1,312 @@
+//! Import of datapoints from CSV and JSONL files, parsed as the upload streams in.
+//!
+//! Columns of the file are mapped to the keys of the datapoints' `data`, `target` and `metadata`.
+//! Rows that can't be parsed or mapped are reported and skipped, the rest of the file is still
+//! imported.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use super::datapoints::Datapoint;
+
+const MAX_IMPORT_ROWS: usize = 100_000;
+/// Further failed rows are only counted
+const MAX_REPORTED_ERRORS: usize = 100;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    Jsonl,
+}
+
+impl ImportFormat {
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        match filename.rsplit('.').next()?.to_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "jsonl" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// Columns that become the keys of each field of the datapoints, other columns are ignored
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ImportMapping {
+    pub data: Vec<String>,
+    #[serde(default)]
+    pub target: Vec<String>,
+    #[serde(default)]
+    pub metadata: Vec<String>,
+}
+
+impl ImportMapping {
+    pub fn validate(&self) -> Result<()> {
+        if self.data.is_empty() {
+            bail!("Mapping must map at least one column to data");
+        }
+        let mut columns = self.columns().collect::<Vec<_>>();
+        columns.sort();
+        if columns.windows(2).any(|pair| pair[0] == pair[1]) {
+            bail!("Each column can only be mapped once");
+        }
+        Ok(())
+    }
+
+    fn columns(&self) -> impl Iterator<Item = &String> {
+        self.data.iter().chain(&self.target).chain(&self.metadata)
+    }
+
+    /// Datapoint of a row, which must have all the columns mapped to data
+    pub fn map_row(
+        &self,
+        dataset_id: Uuid,
+        mut row: Map<String, Value>,
+    ) -> std::result::Result<Datapoint, String> {
+        let mut take_columns = |columns: &[String], required: bool| {
+            let mut object = Map::new();
+            for column in columns {
+                match row.remove(column) {
+                    Some(value) => {
+                        object.insert(column.clone(), value);
+                    }
+                    None if required => return Err(format!("Row has no column {column}")),
+                    None => {}
+                }
+            }
+            Ok((!object.is_empty()).then_some(Value::Object(object)))
+        };
+        let data = take_columns(&self.data, true)?;
+        let target = take_columns(&self.target, false)?;
+        let metadata = take_columns(&self.metadata, false)?;
+
+        Ok(Datapoint {
+            id: Uuid::new_v4(),
+            dataset_id,
+            data: data.unwrap_or_default(),
+            target,
+            metadata,
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRowError {
+    /// Line of the row in the file, starting at 1. CSV rows spanning several lines are counted
+    /// once, and the header is row 1.
+    pub row: usize,
+    pub error: String,
+}
+
+pub struct DatapointImporter {
+    format: ImportFormat,
+    mapping: ImportMapping,
+    dataset_id: Uuid,
+    /// Start of the record that hasn't ended yet
+    buffer: Vec<u8>,
+    /// Whether the end of the buffer is inside a quoted CSV field, where newlines don't end
+    /// the record
+    in_quotes: bool,
+    headers: Option<Vec<String>>,
+    rows: usize,
+    pub datapoints: Vec<Datapoint>,
+    pub failed_count: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+impl DatapointImporter {
+    pub fn new(format: ImportFormat, mapping: ImportMapping, dataset_id: Uuid) -> Self {
+        Self {
+            format,
+            mapping,
+            dataset_id,
+            buffer: Vec::new(),
+            in_quotes: false,
+            headers: None,
+            rows: 0,
+            datapoints: Vec::new(),
+            failed_count: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Parses the records completed by the chunk. Fails for problems with the whole file, e.g.
+    /// a CSV header without the mapped columns.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<()> {
+        let scanned = self.buffer.len();
+        self.buffer.extend_from_slice(chunk);
+        let buffer = std::mem::take(&mut self.buffer);
+
+        let mut start = 0;
+        for (i, byte) in buffer.iter().enumerate().skip(scanned) {
+            match byte {
+                // Escaped quotes are doubled, so they don't change whether the field is quoted
+                b'"' if self.format == ImportFormat::Csv => self.in_quotes = !self.in_quotes,
+                b'\n' if !self.in_quotes => {
+                    self.process_record(&buffer[start..i])?;
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+
+        self.buffer = buffer[start..].to_vec();
+        Ok(())
+    }
+
+    /// Parses the last record, which may not end with a newline
+    pub fn finish(&mut self) -> Result<()> {
+        let buffer = std::mem::take(&mut self.buffer);
+        if !buffer.is_empty() {
+            self.process_record(&buffer)?;
+        }
+        if self.format == ImportFormat::Csv && self.headers.is_none() {
+            bail!("CSV file must have a header");
+        }
+        Ok(())
+    }
+
+    fn process_record(&mut self, record: &[u8]) -> Result<()> {
+        self.rows += 1;
+        let record = record.strip_suffix(b"\r").unwrap_or(record);
+        if record.iter().all(u8::is_ascii_whitespace) {
+            return Ok(());
+        }
+        if self.datapoints.len() + self.failed_count >= MAX_IMPORT_ROWS {
+            bail!("File must have at most {MAX_IMPORT_ROWS} rows");
+        }
+
+        let row = match self.format {
+            ImportFormat::Csv => match self.parse_csv_record(record)? {
+                Some(row) => row,
+                // The header
+                None => return Ok(()),
+            },
+            ImportFormat::Jsonl => parse_jsonl_record(record),
+        };
+        match row.and_then(|row| self.mapping.map_row(self.dataset_id, row)) {
+            Ok(datapoint) => self.datapoints.push(datapoint),
+            Err(error) => {
+                self.failed_count += 1;
+                if self.errors.len() < MAX_REPORTED_ERRORS {
+                    self.errors.push(ImportRowError {
+                        row: self.rows,
+                        error,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Columns of the row, None for the header
+    fn parse_csv_record(
+        &mut self,
+        record: &[u8],
+    ) -> Result<Option<std::result::Result<Map<String, Value>, String>>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(record);
+        let fields = match reader.records().next() {
+            Some(Ok(fields)) => fields,
+            Some(Err(e)) if self.headers.is_some() => return Ok(Some(Err(e.to_string()))),
+            Some(Err(e)) => bail!("Invalid CSV header: {e}"),
+            None => return Ok(Some(Err("Empty row".to_string()))),
+        };
+
+        let Some(headers) = &self.headers else {
+            let headers = fields
+                .iter()
+                .map(|h| h.trim_start_matches('\u{feff}').trim().to_string())
+                .collect::<Vec<_>>();
+            if let Some(column) = self.mapping.columns().find(|c| !headers.contains(c)) {
+                bail!("Column {column} is not in the CSV header");
+            }
+            self.headers = Some(headers);
+            return Ok(None);
+        };
+        if fields.len() != headers.len() {
+            return Ok(Some(Err(format!(
+                "Row has {} columns, the header has {}",
+                fields.len(),
+                headers.len()
+            ))));
+        }
+
+        Ok(Some(Ok(headers
+            .iter()
+            .zip(fields.iter())
+            .map(|(header, field)| (header.clone(), Value::String(field.to_string())))
+            .collect())))
+    }
+}
+
+fn parse_jsonl_record(record: &[u8]) -> std::result::Result<Map<String, Value>, String> {
+    match serde_json::from_slice::<Value>(record) {
+        Ok(Value::Object(row)) => Ok(row),
+        Ok(_) => Err("Row must be a JSON object".to_string()),
+        Err(e) => Err(format!("Invalid JSON: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn mapping() -> ImportMapping {
+        ImportMapping {
+            data: vec!["question".to_string()],
+            target: vec!["answer".to_string()],
+            metadata: vec![],
+        }
+    }
+
+    #[test]
+    fn test_import_csv() {
+        let mut importer = DatapointImporter::new(ImportFormat::Csv, mapping(), Uuid::nil());
+        importer
+            .push(b"question,answer,notes\r\n\"first\nline")
+            .unwrap();
+        importer
+            .push(b"\",\"a \"\"quoted\"\" answer\",x\nbad,row\n")
+            .unwrap();
+        importer.push(b"second,b,y").unwrap();
+        importer.finish().unwrap();
+
+        assert_eq!(importer.datapoints.len(), 2);
+        assert_eq!(
+            importer.datapoints[0].data,
+            json!({"question": "first\nline"})
+        );
+        assert_eq!(
+            importer.datapoints[0].target,
+            Some(json!({"answer": "a \"quoted\" answer"}))
+        );
+        assert_eq!(importer.failed_count, 1);
+        assert_eq!(importer.errors[0].row, 3);
+
+        let mut importer = DatapointImporter::new(ImportFormat::Csv, mapping(), Uuid::nil());
+        assert!(importer.push(b"question,notes\n").is_err());
+    }
+
+    #[test]
+    fn test_import_jsonl() {
+        let mut importer = DatapointImporter::new(ImportFormat::Jsonl, mapping(), Uuid::nil());
+        importer
+            .push(b"{\"question\": {\"text\": \"q\"}}\n\n[1]\n{\"answer\": \"a\"}\n{\"ques")
+            .unwrap();
+        importer.push(b"tion\": \"q\", \"answer\": 1}").unwrap();
+        importer.finish().unwrap();
+
+        assert_eq!(importer.datapoints.len(), 2);
+        assert_eq!(importer.datapoints[0].target, None);
+        assert_eq!(importer.datapoints[1].target, Some(json!({"answer": 1})));
+        let failed_rows = importer.errors.iter().map(|e| e.row).collect::<Vec<_>>();
+        assert_eq!(failed_rows, vec![3, 4]);
+    }
+}
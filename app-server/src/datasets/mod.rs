@@ -7,7 +7,10 @@ use uuid::Uuid;
 
 use crate::{pipeline::nodes::NodeInput, semantic_search::SemanticSearch};
 
+pub mod changes;
 pub mod datapoints;
+pub mod huggingface;
+pub mod import;
 pub mod utils;
 
 #[derive(Debug, Clone, Deserialize, Serialize, FromRow)]
@@ -19,6 +22,9 @@ pub struct Dataset {
     pub project_id: Uuid,
     #[serde(default)]
     pub indexed_on: Option<String>,
+    /// Incremented on every change of the datapoints, see `db::dataset_versions`
+    #[serde(default)]
+    pub version: i32,
 }
 
 impl Dataset {
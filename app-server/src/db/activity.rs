@@ -0,0 +1,129 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "activity_type")]
+pub enum ActivityType {
+    EVALUATION_RUN,
+    DATASET_MODIFIED,
+    PROMPT_DEPLOYED,
+    ALERT_FIRED,
+    PIPELINE_TRIGGER_FAILED,
+    EVALUATION_SCHEDULE_FAILED,
+}
+
+/// Entry of a project's activity feed
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Activity {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub activity_type: ActivityType,
+    /// None for activity of project API keys and of the server itself
+    pub actor_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    pub resource_id: Option<Uuid>,
+    pub summary: String,
+    pub details: Value,
+}
+
+pub struct NewActivity {
+    pub project_id: Uuid,
+    pub activity_type: ActivityType,
+    pub actor_id: Option<Uuid>,
+    pub resource_id: Option<Uuid>,
+    pub summary: String,
+    pub details: Value,
+}
+
+pub struct ActivityFilter {
+    pub activity_types: Vec<ActivityType>,
+    pub actor_id: Option<Uuid>,
+    pub resource_id: Option<Uuid>,
+}
+
+pub async fn record_activity(pool: &PgPool, activity: &NewActivity) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO project_activity
+            (project_id, activity_type, actor_id, resource_id, summary, details)
+        VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(activity.project_id)
+    .bind(activity.activity_type)
+    .bind(activity.actor_id)
+    .bind(activity.resource_id)
+    .bind(&activity.summary)
+    .bind(&activity.details)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Newest activity first
+pub async fn get_activity(
+    pool: &PgPool,
+    project_id: &Uuid,
+    filter: &ActivityFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Activity>> {
+    let activity = sqlx::query_as::<_, Activity>(
+        "SELECT
+            project_activity.id,
+            project_activity.created_at,
+            project_activity.project_id,
+            project_activity.activity_type,
+            project_activity.actor_id,
+            users.email AS actor_email,
+            project_activity.resource_id,
+            project_activity.summary,
+            project_activity.details
+        FROM project_activity
+        LEFT JOIN users ON users.id = project_activity.actor_id
+        WHERE project_activity.project_id = $1
+            AND (cardinality($2::activity_type[]) = 0 OR project_activity.activity_type = ANY($2))
+            AND ($3::uuid IS NULL OR project_activity.actor_id = $3)
+            AND ($4::uuid IS NULL OR project_activity.resource_id = $4)
+        ORDER BY project_activity.created_at DESC
+        LIMIT $5 OFFSET $6",
+    )
+    .bind(project_id)
+    .bind(&filter.activity_types)
+    .bind(filter.actor_id)
+    .bind(filter.resource_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(activity)
+}
+
+pub async fn count_activity(
+    pool: &PgPool,
+    project_id: &Uuid,
+    filter: &ActivityFilter,
+) -> Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT count(*)
+        FROM project_activity
+        WHERE project_id = $1
+            AND (cardinality($2::activity_type[]) = 0 OR activity_type = ANY($2))
+            AND ($3::uuid IS NULL OR actor_id = $3)
+            AND ($4::uuid IS NULL OR resource_id = $4)",
+    )
+    .bind(project_id)
+    .bind(&filter.activity_types)
+    .bind(filter.actor_id)
+    .bind(filter.resource_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Serialize, FromRow, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCheckpoint {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub trace_id: Uuid,
+    /// Span that was active when the checkpoint was taken, resumed runs continue under it
+    pub span_id: Uuid,
+    pub span_path: Option<String>,
+    pub step: i32,
+    /// Conversation and memory of the agent, as saved by the SDK
+    #[schema(value_type = Object)]
+    pub state: Value,
+    pub browser_session_id: Option<String>,
+    pub resume_count: i32,
+    pub resumed_at: Option<DateTime<Utc>>,
+}
+
+pub struct NewAgentCheckpoint {
+    pub trace_id: Uuid,
+    pub span_id: Uuid,
+    pub span_path: Option<String>,
+    pub step: i32,
+    pub state: Value,
+    pub browser_session_id: Option<String>,
+}
+
+const AGENT_CHECKPOINT_COLUMNS: &str =
+    "id, created_at, trace_id, span_id, span_path, step, state, \
+    browser_session_id, resume_count, resumed_at";
+
+/// Saves the checkpoint and deletes the oldest checkpoints of the run beyond `keep`
+pub async fn create_agent_checkpoint(
+    pool: &PgPool,
+    project_id: &Uuid,
+    checkpoint: &NewAgentCheckpoint,
+    keep: i64,
+) -> Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    let id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO agent_checkpoints
+            (project_id, trace_id, span_id, span_path, step, state, browser_session_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id",
+    )
+    .bind(project_id)
+    .bind(checkpoint.trace_id)
+    .bind(checkpoint.span_id)
+    .bind(&checkpoint.span_path)
+    .bind(checkpoint.step)
+    .bind(&checkpoint.state)
+    .bind(&checkpoint.browser_session_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "DELETE FROM agent_checkpoints
+        WHERE project_id = $1 AND trace_id = $2 AND id NOT IN (
+            SELECT id FROM agent_checkpoints
+            WHERE project_id = $1 AND trace_id = $2
+            ORDER BY step DESC, created_at DESC
+            LIMIT $3
+        )",
+    )
+    .bind(project_id)
+    .bind(checkpoint.trace_id)
+    .bind(keep)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(id)
+}
+
+pub async fn get_agent_checkpoints(
+    pool: &PgPool,
+    project_id: &Uuid,
+    trace_id: &Uuid,
+) -> Result<Vec<AgentCheckpoint>> {
+    let checkpoints = sqlx::query_as::<_, AgentCheckpoint>(&format!(
+        "SELECT {AGENT_CHECKPOINT_COLUMNS} FROM agent_checkpoints
+        WHERE project_id = $1 AND trace_id = $2
+        ORDER BY step ASC, created_at ASC"
+    ))
+    .bind(project_id)
+    .bind(trace_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(checkpoints)
+}
+
+/// Returns the last checkpoint of the run, recording that the run is resumed from it
+pub async fn resume_from_last_agent_checkpoint(
+    pool: &PgPool,
+    project_id: &Uuid,
+    trace_id: &Uuid,
+) -> Result<Option<AgentCheckpoint>> {
+    let checkpoint = sqlx::query_as::<_, AgentCheckpoint>(&format!(
+        "UPDATE agent_checkpoints SET resume_count = resume_count + 1, resumed_at = now()
+        WHERE id = (
+            SELECT id FROM agent_checkpoints
+            WHERE project_id = $1 AND trace_id = $2
+            ORDER BY step DESC, created_at DESC
+            LIMIT 1
+        )
+        RETURNING {AGENT_CHECKPOINT_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(trace_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(checkpoint)
+}
@@ -0,0 +1,492 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "alert_score_source")]
+pub enum AlertScoreSource {
+    /// Average of an evaluation score, checked after each evaluation run
+    EVALUATION,
+    /// Average of the span scores of online evaluators over a window, checked periodically
+    ONLINE,
+}
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "alert_comparison")]
+pub enum AlertComparison {
+    BELOW,
+    ABOVE,
+}
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "alert_state")]
+pub enum AlertState {
+    RESOLVED,
+    FIRING,
+}
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "alert_channel_type")]
+pub enum AlertChannelType {
+    WEBHOOK,
+    SLACK,
+    EMAIL,
+}
+
+#[derive(Serialize, FromRow, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRule {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub name: String,
+    pub source: AlertScoreSource,
+    pub score_name: String,
+    /// Evaluation group the rule applies to, None for all groups. Only for evaluation rules.
+    pub group_id: Option<String>,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    /// Window of the span scores averaged by online rules
+    pub window_minutes: i32,
+    pub state: AlertState,
+    pub last_value: Option<f64>,
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+    pub state_changed_at: Option<DateTime<Utc>>,
+}
+
+/// Channel without its target, as shown to users
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertChannelInfo {
+    pub id: Uuid,
+    pub rule_id: Uuid,
+    pub channel_type: AlertChannelType,
+    /// Host of the webhook URL or the email address
+    pub target_preview: String,
+}
+
+#[derive(FromRow)]
+pub struct AlertChannel {
+    pub channel_type: AlertChannelType,
+    pub target_nonce: String,
+    pub target_value: String,
+}
+
+/// A rule starting to fire. The summary of its likely root cause is filled in by a background job.
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertIncident {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub rule_id: Uuid,
+    /// Value the rule fired at
+    pub value: f64,
+    /// Evaluation that fired an evaluation rule
+    pub evaluation_id: Option<Uuid>,
+    /// Window of the span scores that fired an online rule
+    pub window_start: Option<DateTime<Utc>>,
+    pub window_end: Option<DateTime<Utc>>,
+    pub summary: Option<Value>,
+    pub summarized_at: Option<DateTime<Utc>>,
+}
+
+/// Score of a traced datapoint or span that breaches a rule
+#[derive(FromRow)]
+pub struct BreachingScore {
+    pub trace_id: Uuid,
+    pub score: f64,
+}
+
+/// What a trace's incident summary groups by. Taken from the first LLM span with a model, the
+/// first span with the error status and the first span with a `lmnr.span.prompt_version`
+/// attribute.
+#[derive(FromRow)]
+pub struct TraceCharacteristics {
+    pub trace_id: Uuid,
+    pub model: Option<String>,
+    /// `error.type` of the error span, or "error" without it
+    pub error_category: Option<String>,
+    pub prompt_version: Option<String>,
+    /// `cohort` of the trace's metadata, or its client region without it
+    pub user_cohort: Option<String>,
+}
+
+pub struct NewAlertRule {
+    pub id: Uuid,
+    pub name: String,
+    pub source: AlertScoreSource,
+    pub score_name: String,
+    pub group_id: Option<String>,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    pub window_minutes: i32,
+}
+
+pub struct NewAlertChannel {
+    pub channel_type: AlertChannelType,
+    pub target_preview: String,
+    pub target_nonce: String,
+    pub target_value: String,
+}
+
+const ALERT_INCIDENT_COLUMNS: &str = "id, created_at, rule_id, value, evaluation_id, \
+    window_start, window_end, summary, summarized_at";
+
+const ALERT_RULE_COLUMNS: &str = "id, created_at, project_id, name, source, score_name, group_id, \
+    comparison, threshold, window_minutes, state, last_value, last_evaluated_at, state_changed_at";
+
+pub async fn create_alert_rule(
+    pool: &PgPool,
+    project_id: &Uuid,
+    rule: &NewAlertRule,
+    channels: &[NewAlertChannel],
+) -> Result<AlertRule> {
+    let mut tx = pool.begin().await?;
+    let created = sqlx::query_as::<_, AlertRule>(&format!(
+        "INSERT INTO alert_rules
+            (id, project_id, name, source, score_name, group_id, comparison, threshold,
+            window_minutes)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING {ALERT_RULE_COLUMNS}"
+    ))
+    .bind(rule.id)
+    .bind(project_id)
+    .bind(&rule.name)
+    .bind(rule.source)
+    .bind(&rule.score_name)
+    .bind(&rule.group_id)
+    .bind(rule.comparison)
+    .bind(rule.threshold)
+    .bind(rule.window_minutes)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for channel in channels {
+        sqlx::query(
+            "INSERT INTO alert_channels
+                (rule_id, channel_type, target_preview, target_nonce, target_value)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(created.id)
+        .bind(channel.channel_type)
+        .bind(&channel.target_preview)
+        .bind(&channel.target_nonce)
+        .bind(&channel.target_value)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(created)
+}
+
+pub async fn get_alert_rules(pool: &PgPool, project_id: &Uuid) -> Result<Vec<AlertRule>> {
+    let rules = sqlx::query_as::<_, AlertRule>(&format!(
+        "SELECT {ALERT_RULE_COLUMNS} FROM alert_rules
+        WHERE project_id = $1
+        ORDER BY created_at ASC"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rules)
+}
+
+/// Channels of all rules of the project
+pub async fn get_alert_channel_infos(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<AlertChannelInfo>> {
+    let channels = sqlx::query_as::<_, AlertChannelInfo>(
+        "SELECT alert_channels.id, alert_channels.rule_id, alert_channels.channel_type,
+            alert_channels.target_preview
+        FROM alert_channels
+        JOIN alert_rules ON alert_rules.id = alert_channels.rule_id
+        WHERE alert_rules.project_id = $1
+        ORDER BY alert_channels.created_at ASC",
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(channels)
+}
+
+pub async fn get_alert_channels(pool: &PgPool, rule_id: &Uuid) -> Result<Vec<AlertChannel>> {
+    let channels = sqlx::query_as::<_, AlertChannel>(
+        "SELECT channel_type, target_nonce, target_value
+        FROM alert_channels
+        WHERE rule_id = $1",
+    )
+    .bind(rule_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(channels)
+}
+
+/// Returns false if the rule doesn't exist in the project
+pub async fn delete_alert_rule(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM alert_rules WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Evaluation rules of the project that apply to the group
+pub async fn get_evaluation_alert_rules(
+    pool: &PgPool,
+    project_id: &Uuid,
+    group_id: &str,
+) -> Result<Vec<AlertRule>> {
+    let rules = sqlx::query_as::<_, AlertRule>(&format!(
+        "SELECT {ALERT_RULE_COLUMNS} FROM alert_rules
+        WHERE project_id = $1
+            AND source = 'EVALUATION'
+            AND (group_id IS NULL OR group_id = $2)"
+    ))
+    .bind(project_id)
+    .bind(group_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rules)
+}
+
+/// Online rules that haven't been evaluated for `interval_seconds`. Returned rules count as
+/// evaluated, so that each is evaluated by one instance only.
+pub async fn claim_online_alert_rules(
+    pool: &PgPool,
+    interval_seconds: i64,
+) -> Result<Vec<AlertRule>> {
+    let rules = sqlx::query_as::<_, AlertRule>(&format!(
+        "UPDATE alert_rules SET last_evaluated_at = now()
+        WHERE source = 'ONLINE'
+            AND (last_evaluated_at IS NULL
+                OR last_evaluated_at < now() - make_interval(secs => $1))
+        RETURNING {ALERT_RULE_COLUMNS}"
+    ))
+    .bind(interval_seconds as f64)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rules)
+}
+
+/// Records the value the rule was evaluated to and its resulting state. Returns true if the
+/// state changed.
+pub async fn record_alert_evaluation(
+    pool: &PgPool,
+    id: &Uuid,
+    value: f64,
+    state: AlertState,
+) -> Result<bool> {
+    let changed = sqlx::query_scalar::<_, bool>(
+        "WITH previous AS (
+            SELECT state FROM alert_rules WHERE id = $1 FOR UPDATE
+        )
+        UPDATE alert_rules SET
+            last_value = $2,
+            last_evaluated_at = now(),
+            state_changed_at = CASE WHEN state = $3 THEN state_changed_at ELSE now() END,
+            state = $3
+        WHERE id = $1
+        RETURNING (SELECT state FROM previous) != $3",
+    )
+    .bind(id)
+    .bind(value)
+    .bind(state)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(changed.unwrap_or(false))
+}
+
+pub async fn get_alert_rule(pool: &PgPool, id: &Uuid) -> Result<Option<AlertRule>> {
+    let rule = sqlx::query_as::<_, AlertRule>(&format!(
+        "SELECT {ALERT_RULE_COLUMNS} FROM alert_rules WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(rule)
+}
+
+pub async fn create_alert_incident(
+    pool: &PgPool,
+    rule_id: &Uuid,
+    value: f64,
+    evaluation_id: Option<Uuid>,
+    window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+) -> Result<Uuid> {
+    let id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO alert_incidents (rule_id, value, evaluation_id, window_start, window_end)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id",
+    )
+    .bind(rule_id)
+    .bind(value)
+    .bind(evaluation_id)
+    .bind(window.map(|(start, _)| start))
+    .bind(window.map(|(_, end)| end))
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id)
+}
+
+/// Most recent incidents of the rule, None if the rule doesn't exist in the project
+pub async fn get_alert_incidents(
+    pool: &PgPool,
+    project_id: &Uuid,
+    rule_id: &Uuid,
+    limit: i64,
+) -> Result<Option<Vec<AlertIncident>>> {
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM alert_rules WHERE id = $1 AND project_id = $2)",
+    )
+    .bind(rule_id)
+    .bind(project_id)
+    .fetch_one(pool)
+    .await?;
+    if !exists {
+        return Ok(None);
+    }
+
+    let incidents = sqlx::query_as::<_, AlertIncident>(&format!(
+        "SELECT {ALERT_INCIDENT_COLUMNS} FROM alert_incidents
+        WHERE rule_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2"
+    ))
+    .bind(rule_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(incidents))
+}
+
+/// Incidents without a summary that no instance claimed in the last `retry_after_seconds`.
+/// Returned incidents count as claimed, so that each is summarized by one instance only.
+pub async fn claim_unsummarized_alert_incidents(
+    pool: &PgPool,
+    retry_after_seconds: i64,
+    limit: i64,
+) -> Result<Vec<AlertIncident>> {
+    let incidents = sqlx::query_as::<_, AlertIncident>(&format!(
+        "UPDATE alert_incidents SET summary_claimed_at = now()
+        WHERE id IN (
+            SELECT id FROM alert_incidents
+            WHERE summarized_at IS NULL
+                AND (summary_claimed_at IS NULL
+                    OR summary_claimed_at < now() - make_interval(secs => $1))
+            ORDER BY created_at ASC
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {ALERT_INCIDENT_COLUMNS}"
+    ))
+    .bind(retry_after_seconds as f64)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(incidents)
+}
+
+pub async fn set_alert_incident_summary(pool: &PgPool, id: &Uuid, summary: &Value) -> Result<()> {
+    sqlx::query("UPDATE alert_incidents SET summary = $2, summarized_at = now() WHERE id = $1")
+        .bind(id)
+        .bind(summary)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Scores of the evaluation's datapoints that breach the threshold, the furthest first
+pub async fn get_breaching_evaluation_scores(
+    pool: &PgPool,
+    evaluation_id: &Uuid,
+    score_name: &str,
+    comparison: AlertComparison,
+    threshold: f64,
+    limit: i64,
+) -> Result<Vec<BreachingScore>> {
+    let (condition, order) = match comparison {
+        AlertComparison::BELOW => ("<", "ASC"),
+        AlertComparison::ABOVE => (">", "DESC"),
+    };
+    let scores = sqlx::query_as::<_, BreachingScore>(&format!(
+        "SELECT evaluation_results.trace_id, evaluation_scores.score
+        FROM evaluation_scores
+        JOIN evaluation_results ON evaluation_results.id = evaluation_scores.result_id
+        WHERE evaluation_results.evaluation_id = $1
+            AND evaluation_scores.name = $2
+            AND evaluation_scores.score {condition} $3
+        ORDER BY evaluation_scores.score {order}
+        LIMIT $4"
+    ))
+    .bind(evaluation_id)
+    .bind(score_name)
+    .bind(threshold)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(scores)
+}
+
+pub async fn get_trace_characteristics(
+    pool: &PgPool,
+    project_id: &Uuid,
+    trace_ids: &[Uuid],
+) -> Result<Vec<TraceCharacteristics>> {
+    let characteristics = sqlx::query_as::<_, TraceCharacteristics>(
+        "SELECT
+            traces.id AS trace_id,
+            (SELECT COALESCE(
+                    spans.attributes ->> 'gen_ai.response.model',
+                    spans.attributes ->> 'gen_ai.request.model')
+                FROM spans
+                WHERE spans.project_id = traces.project_id
+                    AND spans.trace_id = traces.id
+                    AND spans.span_type = 'LLM'
+                    AND (spans.attributes ? 'gen_ai.response.model'
+                        OR spans.attributes ? 'gen_ai.request.model')
+                ORDER BY spans.start_time ASC
+                LIMIT 1) AS model,
+            (SELECT COALESCE(spans.attributes ->> 'error.type', 'error')
+                FROM spans
+                WHERE spans.project_id = traces.project_id
+                    AND spans.trace_id = traces.id
+                    AND spans.attributes ->> 'lmnr.span.status' = 'error'
+                ORDER BY spans.start_time ASC
+                LIMIT 1) AS error_category,
+            (SELECT spans.attributes ->> 'lmnr.span.prompt_version'
+                FROM spans
+                WHERE spans.project_id = traces.project_id
+                    AND spans.trace_id = traces.id
+                    AND spans.attributes ? 'lmnr.span.prompt_version'
+                ORDER BY spans.start_time ASC
+                LIMIT 1) AS prompt_version,
+            COALESCE(traces.metadata ->> 'cohort', traces.region) AS user_cohort
+        FROM traces
+        WHERE traces.project_id = $1 AND traces.id = ANY($2)",
+    )
+    .bind(project_id)
+    .bind(trace_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(characteristics)
+}
@@ -0,0 +1,356 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::field_visibility::WorkspaceRole;
+
+/// Rows inserted per statement on restore
+const RESTORE_BATCH_SIZE: usize = 500;
+
+enum BackupScope {
+    /// The workspace itself
+    Workspace,
+    /// Rows with the workspace's `workspace_id`
+    WorkspaceId,
+    /// Rows with the `project_id` of one of the workspace's projects
+    Project,
+    /// Rows whose column references a backed up row of the parent table
+    Parent {
+        column: &'static str,
+        parent: &'static str,
+    },
+}
+
+/// Table of a workspace backup. Trace data is in ClickHouse and object storage, which are backed
+/// up with their own tools, see `backups`.
+pub struct BackupTable {
+    pub name: &'static str,
+    scope: BackupScope,
+    /// Nullable columns referencing rows outside the workspace, e.g. users, that are set to null
+    /// on restore if the row doesn't exist
+    external_references: &'static [(&'static str, &'static str)],
+}
+
+const fn table(name: &'static str, scope: BackupScope) -> BackupTable {
+    BackupTable {
+        name,
+        scope,
+        external_references: &[],
+    }
+}
+
+const fn child(name: &'static str, column: &'static str, parent: &'static str) -> BackupTable {
+    table(name, BackupScope::Parent { column, parent })
+}
+
+/// Ordered so that referenced rows are restored first
+pub const BACKUP_TABLES: &[BackupTable] = &[
+    BackupTable {
+        name: "workspaces",
+        scope: BackupScope::Workspace,
+        external_references: &[("organization_id", "organizations")],
+    },
+    table("projects", BackupScope::WorkspaceId),
+    table("project_api_keys", BackupScope::Project),
+    table("provider_api_keys", BackupScope::Project),
+    table("datasets", BackupScope::Project),
+    child("dataset_datapoints", "dataset_id", "datasets"),
+    child("dataset_versions", "dataset_id", "datasets"),
+    child("dataset_datapoint_revisions", "dataset_id", "datasets"),
+    table("pipelines", BackupScope::Project),
+    child("pipeline_versions", "pipeline_id", "pipelines"),
+    child("target_pipeline_versions", "pipeline_id", "pipelines"),
+    table("evaluations", BackupScope::Project),
+    child("evaluation_results", "evaluation_id", "evaluations"),
+    BackupTable {
+        name: "evaluation_scores",
+        scope: BackupScope::Parent {
+            column: "result_id",
+            parent: "evaluation_results",
+        },
+        external_references: &[("user_id", "users")],
+    },
+    table("evaluation_group_baselines", BackupScope::Project),
+    table("event_templates", BackupScope::Project),
+    table("label_classes", BackupScope::Project),
+    table("label_classes_for_path", BackupScope::Project),
+    table("promoted_attributes", BackupScope::Project),
+    table("field_visibility_rules", BackupScope::Project),
+    table("masking_profiles", BackupScope::Project),
+    table("custom_metrics", BackupScope::Project),
+    table("judge_evaluators", BackupScope::Project),
+    table("online_evaluation_rules", BackupScope::Project),
+    table("evaluation_schedules", BackupScope::Project),
+    table("alert_rules", BackupScope::Project),
+    child("alert_channels", "rule_id", "alert_rules"),
+    table("sampling_exemptions", BackupScope::Project),
+    table("shadow_deployments", BackupScope::Project),
+    table("resource_tags", BackupScope::Project),
+];
+
+fn backup_table(name: &str) -> Option<&'static BackupTable> {
+    BACKUP_TABLES.iter().find(|table| table.name == name)
+}
+
+/// Condition on the rows of the table that belong to the workspace `$1`
+fn scope_condition(table: &BackupTable) -> String {
+    match &table.scope {
+        BackupScope::Workspace => "id = $1".to_string(),
+        BackupScope::WorkspaceId => "workspace_id = $1".to_string(),
+        BackupScope::Project => {
+            "project_id IN (SELECT id FROM projects WHERE workspace_id = $1)".to_string()
+        }
+        BackupScope::Parent { column, parent } => {
+            let parent = backup_table(parent).expect("parent of a backup table is backed up");
+            format!(
+                "{column} IN (SELECT id FROM {} WHERE {})",
+                parent.name,
+                scope_condition(parent)
+            )
+        }
+    }
+}
+
+/// Member of the workspace. Users aren't backed up, they are matched by email on restore.
+#[derive(Serialize, Deserialize, FromRow, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupMember {
+    pub email: String,
+    pub member_role: WorkspaceRole,
+}
+
+pub struct WorkspaceRows {
+    pub project_ids: Vec<Uuid>,
+    /// Rows as JSON objects by table name
+    pub tables: BTreeMap<String, Vec<Value>>,
+    pub members: Vec<BackupMember>,
+}
+
+/// All backed up rows of the workspace, read in one repeatable read transaction, so that they
+/// are consistent with each other. None if the workspace doesn't exist.
+pub async fn get_workspace_rows(
+    pool: &PgPool,
+    workspace_id: &Uuid,
+) -> Result<Option<WorkspaceRows>> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+
+    let mut tables = BTreeMap::new();
+    for table in BACKUP_TABLES {
+        let rows = sqlx::query_scalar::<_, Value>(&format!(
+            "SELECT to_jsonb(t) FROM {} t WHERE {}",
+            table.name,
+            scope_condition(table)
+        ))
+        .bind(workspace_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        tables.insert(table.name.to_string(), rows);
+    }
+    if tables["workspaces"].is_empty() {
+        return Ok(None);
+    }
+
+    let project_ids =
+        sqlx::query_scalar::<_, Uuid>("SELECT id FROM projects WHERE workspace_id = $1")
+            .bind(workspace_id)
+            .fetch_all(&mut *tx)
+            .await?;
+    let members = sqlx::query_as::<_, BackupMember>(
+        "SELECT users.email, members_of_workspaces.member_role
+        FROM members_of_workspaces
+        JOIN users ON users.id = members_of_workspaces.user_id
+        WHERE members_of_workspaces.workspace_id = $1
+        ORDER BY users.email",
+    )
+    .bind(workspace_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some(WorkspaceRows {
+        project_ids,
+        tables,
+        members,
+    }))
+}
+
+/// Rows of a table in the backup and how many of them were missing and restored
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoredTable {
+    pub table: String,
+    pub rows: usize,
+    pub restored: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub tables: Vec<RestoredTable>,
+    pub restored_members: Vec<String>,
+    /// Emails of the members without a user on this server, who can be added again once they
+    /// sign up
+    pub missing_members: Vec<String>,
+}
+
+/// Sets the references to rows that don't exist, e.g. deleted users, to null
+async fn clear_missing_references(
+    conn: &mut PgConnection,
+    rows: &mut [Value],
+    column: &str,
+    referenced_table: &str,
+) -> Result<()> {
+    let ids = rows
+        .iter()
+        .filter_map(|row| row.get(column)?.as_str()?.parse::<Uuid>().ok())
+        .collect::<Vec<_>>();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let existing = sqlx::query_scalar::<_, Uuid>(&format!(
+        "SELECT id FROM {referenced_table} WHERE id = ANY($1)"
+    ))
+    .bind(&ids)
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .collect::<HashSet<_>>();
+
+    for row in rows.iter_mut() {
+        let missing = row
+            .get(column)
+            .and_then(Value::as_str)
+            .and_then(|id| id.parse::<Uuid>().ok())
+            .is_some_and(|id| !existing.contains(&id));
+        if missing {
+            row[column] = Value::Null;
+        }
+    }
+    Ok(())
+}
+
+/// Inserts the rows of the backup that don't exist, in one transaction. Existing rows are kept
+/// as they are, so restoring the same backup again changes nothing.
+pub async fn restore_workspace_rows(
+    pool: &PgPool,
+    workspace_id: &Uuid,
+    tables: &BTreeMap<String, Vec<Value>>,
+    members: &[BackupMember],
+) -> Result<RestoreReport> {
+    if let Some(unknown) = tables.keys().find(|name| backup_table(name).is_none()) {
+        return Err(anyhow!(
+            "Backup has rows of table {}, which this server doesn't restore",
+            unknown
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut restored_tables = Vec::new();
+    for table in BACKUP_TABLES {
+        let mut rows = tables.get(table.name).cloned().unwrap_or_default();
+        for (column, referenced_table) in table.external_references {
+            clear_missing_references(&mut *tx, &mut rows, column, referenced_table).await?;
+        }
+        let mut restored = 0;
+        for batch in rows.chunks(RESTORE_BATCH_SIZE) {
+            restored += sqlx::query(&format!(
+                "INSERT INTO {table}
+                SELECT * FROM jsonb_populate_recordset(NULL::{table}, $1)
+                ON CONFLICT DO NOTHING",
+                table = table.name
+            ))
+            .bind(Value::Array(batch.to_vec()))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        restored_tables.push(RestoredTable {
+            table: table.name.to_string(),
+            rows: rows.len(),
+            restored,
+        });
+    }
+
+    sqlx::query(
+        "INSERT INTO workspace_usage (workspace_id)
+        SELECT $1
+        WHERE NOT EXISTS (SELECT 1 FROM workspace_usage WHERE workspace_id = $1)",
+    )
+    .bind(workspace_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let mut restored_members = Vec::new();
+    let mut missing_members = Vec::new();
+    for member in members {
+        let user_exists =
+            sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE email = $1)")
+                .bind(&member.email)
+                .fetch_one(&mut *tx)
+                .await?;
+        if !user_exists {
+            missing_members.push(member.email.clone());
+            continue;
+        }
+        let inserted = sqlx::query(
+            "INSERT INTO members_of_workspaces (user_id, workspace_id, member_role)
+            SELECT id, $2, $3 FROM users
+            WHERE users.email = $1
+                AND NOT EXISTS (
+                    SELECT 1 FROM members_of_workspaces
+                    WHERE workspace_id = $2 AND user_id = users.id
+                )",
+        )
+        .bind(&member.email)
+        .bind(workspace_id)
+        .bind(member.member_role)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if inserted > 0 {
+            restored_members.push(member.email.clone());
+        }
+    }
+    tx.commit().await?;
+
+    Ok(RestoreReport {
+        tables: restored_tables,
+        restored_members,
+        missing_members,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parents_are_restored_first() {
+        for (index, table) in BACKUP_TABLES.iter().enumerate() {
+            if let BackupScope::Parent { parent, .. } = table.scope {
+                let parent_index = BACKUP_TABLES
+                    .iter()
+                    .position(|table| table.name == parent)
+                    .unwrap();
+                assert!(parent_index < index, "{} before {}", parent, table.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scope_condition() {
+        assert_eq!(
+            scope_condition(backup_table("evaluation_scores").unwrap()),
+            "result_id IN (SELECT id FROM evaluation_results WHERE evaluation_id IN \
+            (SELECT id FROM evaluations WHERE project_id IN \
+            (SELECT id FROM projects WHERE workspace_id = $1)))"
+        );
+    }
+}
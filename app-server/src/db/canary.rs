@@ -0,0 +1,106 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// Trace statistics of a release in a time window
+#[derive(Serialize, FromRow, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantStats {
+    pub trace_count: i64,
+    pub error_count: i64,
+    /// Trace latencies in seconds
+    pub latency_mean: f64,
+    pub latency_stddev: f64,
+    pub latency_p50: f64,
+    pub latency_p95: f64,
+    /// Trace costs in USD
+    pub cost_mean: f64,
+    pub cost_stddev: f64,
+}
+
+/// Statistics of the labels that online evaluators set on the spans of a release
+#[derive(Serialize, FromRow, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantScoreStats {
+    pub name: String,
+    pub count: i64,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+pub async fn get_variant_stats(
+    pool: &PgPool,
+    project_id: &Uuid,
+    release: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<VariantStats> {
+    let stats = sqlx::query_as::<_, VariantStats>(
+        "WITH variant_traces AS (
+            SELECT
+                success,
+                cost,
+                extract(epoch FROM end_time - start_time)::float8 as latency
+            FROM traces
+            WHERE project_id = $1
+                AND release = $2
+                AND start_time >= $3
+                AND start_time < $4
+                AND end_time IS NOT NULL
+        )
+        SELECT
+            count(*) as trace_count,
+            count(*) FILTER (WHERE NOT success) as error_count,
+            coalesce(avg(latency), 0) as latency_mean,
+            coalesce(stddev_samp(latency), 0) as latency_stddev,
+            coalesce(percentile_cont(0.5) WITHIN GROUP (ORDER BY latency), 0) as latency_p50,
+            coalesce(percentile_cont(0.95) WITHIN GROUP (ORDER BY latency), 0) as latency_p95,
+            coalesce(avg(cost), 0) as cost_mean,
+            coalesce(stddev_samp(cost), 0) as cost_stddev
+        FROM variant_traces",
+    )
+    .bind(project_id)
+    .bind(release)
+    .bind(start_time)
+    .bind(end_time)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(stats)
+}
+
+pub async fn get_variant_score_stats(
+    pool: &PgPool,
+    project_id: &Uuid,
+    release: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<VariantScoreStats>> {
+    let stats = sqlx::query_as::<_, VariantScoreStats>(
+        "SELECT
+            label_classes.name,
+            count(*) as count,
+            avg(labels.value) as mean,
+            coalesce(stddev_samp(labels.value), 0) as stddev
+        FROM labels
+        JOIN label_classes ON label_classes.id = labels.class_id
+        JOIN spans ON spans.span_id = labels.span_id
+        JOIN traces ON traces.id = spans.trace_id
+        WHERE traces.project_id = $1
+            AND traces.release = $2
+            AND traces.start_time >= $3
+            AND traces.start_time < $4
+            AND labels.label_source = 'AUTO'
+        GROUP BY label_classes.name",
+    )
+    .bind(project_id)
+    .bind(release)
+    .bind(start_time)
+    .bind(end_time)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(stats)
+}
@@ -0,0 +1,261 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "comment_target_type")]
+#[allow(non_camel_case_types)]
+pub enum CommentTargetType {
+    TRACE,
+    SPAN,
+    EVALUATION_RESULT,
+    DATAPOINT,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub target_type: CommentTargetType,
+    pub target_id: Uuid,
+    /// Comment that this one replies to, None for the first comment of a thread
+    pub parent_id: Option<Uuid>,
+    pub content: String,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub user_email: String,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Mention {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub comment_id: Uuid,
+    pub project_id: Uuid,
+    pub target_type: CommentTargetType,
+    pub target_id: Uuid,
+    pub content: String,
+    pub author_name: String,
+}
+
+const COMMENT_COLUMNS: &str = "
+    comments.id,
+    comments.created_at,
+    comments.updated_at,
+    comments.target_type,
+    comments.target_id,
+    comments.parent_id,
+    comments.content,
+    comments.user_id,
+    users.name as user_name,
+    users.email as user_email";
+
+pub async fn target_exists(
+    pool: &PgPool,
+    project_id: &Uuid,
+    target_type: CommentTargetType,
+    target_id: &Uuid,
+) -> Result<bool> {
+    let query = match target_type {
+        CommentTargetType::TRACE => {
+            "SELECT EXISTS (SELECT 1 FROM traces WHERE id = $2 AND project_id = $1)"
+        }
+        CommentTargetType::SPAN => {
+            "SELECT EXISTS (SELECT 1 FROM spans WHERE span_id = $2 AND project_id = $1)"
+        }
+        CommentTargetType::EVALUATION_RESULT => {
+            "SELECT EXISTS (
+                SELECT 1 FROM evaluation_results
+                JOIN evaluations ON evaluations.id = evaluation_results.evaluation_id
+                WHERE evaluation_results.id = $2 AND evaluations.project_id = $1
+            )"
+        }
+        CommentTargetType::DATAPOINT => {
+            "SELECT EXISTS (
+                SELECT 1 FROM dataset_datapoints
+                JOIN datasets ON datasets.id = dataset_datapoints.dataset_id
+                WHERE dataset_datapoints.id = $2 AND datasets.project_id = $1
+            )"
+        }
+    };
+
+    let exists = sqlx::query_scalar::<_, bool>(query)
+        .bind(project_id)
+        .bind(target_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(exists)
+}
+
+pub async fn get_comments(
+    pool: &PgPool,
+    project_id: &Uuid,
+    target_type: CommentTargetType,
+    target_id: &Uuid,
+) -> Result<Vec<Comment>> {
+    let comments = sqlx::query_as::<_, Comment>(&format!(
+        "SELECT {COMMENT_COLUMNS}
+        FROM comments
+        JOIN users ON users.id = comments.user_id
+        WHERE comments.project_id = $1
+            AND comments.target_type = $2
+            AND comments.target_id = $3
+        ORDER BY comments.created_at"
+    ))
+    .bind(project_id)
+    .bind(target_type)
+    .bind(target_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(comments)
+}
+
+/// Returns None if the parent comment is not on the same target
+pub async fn create_comment(
+    pool: &PgPool,
+    project_id: &Uuid,
+    user_id: &Uuid,
+    target_type: CommentTargetType,
+    target_id: &Uuid,
+    parent_id: Option<Uuid>,
+    content: &str,
+) -> Result<Option<Comment>> {
+    let comment = sqlx::query_as::<_, Comment>(&format!(
+        "WITH inserted AS (
+            INSERT INTO comments (project_id, user_id, target_type, target_id, parent_id, content)
+            SELECT $1, $2, $3, $4, $5, $6
+            WHERE $5::uuid IS NULL OR EXISTS (
+                SELECT 1 FROM comments
+                WHERE id = $5 AND project_id = $1 AND target_type = $3 AND target_id = $4
+            )
+            RETURNING *
+        )
+        SELECT {COMMENT_COLUMNS}
+        FROM inserted comments
+        JOIN users ON users.id = comments.user_id"
+    ))
+    .bind(project_id)
+    .bind(user_id)
+    .bind(target_type)
+    .bind(target_id)
+    .bind(parent_id)
+    .bind(content)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(comment)
+}
+
+/// Only the author can edit a comment, returns None otherwise
+pub async fn update_comment(
+    pool: &PgPool,
+    project_id: &Uuid,
+    comment_id: &Uuid,
+    user_id: &Uuid,
+    content: &str,
+) -> Result<Option<Comment>> {
+    let comment = sqlx::query_as::<_, Comment>(&format!(
+        "WITH updated AS (
+            UPDATE comments SET content = $4, updated_at = now()
+            WHERE id = $2 AND project_id = $1 AND user_id = $3
+            RETURNING *
+        )
+        SELECT {COMMENT_COLUMNS}
+        FROM updated comments
+        JOIN users ON users.id = comments.user_id"
+    ))
+    .bind(project_id)
+    .bind(comment_id)
+    .bind(user_id)
+    .bind(content)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(comment)
+}
+
+/// Only the author can delete a comment, replies are deleted with it
+pub async fn delete_comment(
+    pool: &PgPool,
+    project_id: &Uuid,
+    comment_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<bool> {
+    let res =
+        sqlx::query("DELETE FROM comments WHERE id = $1 AND project_id = $2 AND user_id = $3")
+            .bind(comment_id)
+            .bind(project_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Mentions members of the project's workspace by email, the author is never mentioned
+pub async fn add_mentions(
+    pool: &PgPool,
+    project_id: &Uuid,
+    comment_id: &Uuid,
+    author_id: &Uuid,
+    emails: &Vec<String>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO comment_mentions (comment_id, user_id)
+        SELECT $1, users.id
+        FROM users
+        JOIN members_of_workspaces ON members_of_workspaces.user_id = users.id
+        JOIN projects ON projects.workspace_id = members_of_workspaces.workspace_id
+        WHERE projects.id = $2 AND users.email = ANY($3) AND users.id != $4
+        ON CONFLICT (comment_id, user_id) DO NOTHING",
+    )
+    .bind(comment_id)
+    .bind(project_id)
+    .bind(emails)
+    .bind(author_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_unread_mentions(pool: &PgPool, user_id: &Uuid) -> Result<Vec<Mention>> {
+    let mentions = sqlx::query_as::<_, Mention>(
+        "SELECT
+            comment_mentions.id,
+            comment_mentions.created_at,
+            comment_mentions.comment_id,
+            comments.project_id,
+            comments.target_type,
+            comments.target_id,
+            comments.content,
+            users.name as author_name
+        FROM comment_mentions
+        JOIN comments ON comments.id = comment_mentions.comment_id
+        JOIN users ON users.id = comments.user_id
+        WHERE comment_mentions.user_id = $1 AND comment_mentions.read_at IS NULL
+        ORDER BY comment_mentions.created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(mentions)
+}
+
+pub async fn mark_mention_read(pool: &PgPool, user_id: &Uuid, mention_id: &Uuid) -> Result<()> {
+    sqlx::query("UPDATE comment_mentions SET read_at = now() WHERE id = $1 AND user_id = $2")
+        .bind(mention_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+/// Metric computed from span fields, see `analytics::custom_metrics`
+#[derive(Serialize, FromRow, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomMetric {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub name: String,
+    pub expression: String,
+    pub description: Option<String>,
+}
+
+pub async fn get_custom_metrics(pool: &PgPool, project_id: &Uuid) -> Result<Vec<CustomMetric>> {
+    let metrics = sqlx::query_as::<_, CustomMetric>(
+        "SELECT id, created_at, project_id, name, expression, description
+        FROM custom_metrics
+        WHERE project_id = $1
+        ORDER BY name",
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(metrics)
+}
+
+/// Returns None if the project already has a metric with the name
+pub async fn create_custom_metric(
+    pool: &PgPool,
+    project_id: &Uuid,
+    name: &str,
+    expression: &str,
+    description: Option<&str>,
+) -> Result<Option<CustomMetric>> {
+    let metric = sqlx::query_as::<_, CustomMetric>(
+        "INSERT INTO custom_metrics (project_id, name, expression, description)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (project_id, name) DO NOTHING
+        RETURNING id, created_at, project_id, name, expression, description",
+    )
+    .bind(project_id)
+    .bind(name)
+    .bind(expression)
+    .bind(description)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(metric)
+}
+
+pub async fn delete_custom_metric(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<CustomMetric>> {
+    let metric = sqlx::query_as::<_, CustomMetric>(
+        "DELETE FROM custom_metrics
+        WHERE id = $1 AND project_id = $2
+        RETURNING id, created_at, project_id, name, expression, description",
+    )
+    .bind(id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(metric)
+}
+
+/// Creates the metric, or replaces the expression and description of the project's metric with
+/// the name
+pub async fn upsert_custom_metric(
+    conn: &mut PgConnection,
+    project_id: &Uuid,
+    name: &str,
+    expression: &str,
+    description: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO custom_metrics (project_id, name, expression, description)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (project_id, name)
+        DO UPDATE SET expression = EXCLUDED.expression, description = EXCLUDED.description",
+    )
+    .bind(project_id)
+    .bind(name)
+    .bind(expression)
+    .bind(description)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_custom_metric_by_name(
+    conn: &mut PgConnection,
+    project_id: &Uuid,
+    name: &str,
+) -> Result<()> {
+    sqlx::query("DELETE FROM custom_metrics WHERE project_id = $1 AND name = $2")
+        .bind(project_id)
+        .bind(name)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
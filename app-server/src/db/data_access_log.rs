@@ -0,0 +1,94 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "accessed_resource_type")]
+pub enum AccessedResourceType {
+    TRACE,
+    SPAN,
+    DATAPOINT,
+}
+
+/// Accesses of a user to a resource over the report's period
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessReportRow {
+    pub user_id: Uuid,
+    /// None if the user has been deleted since
+    pub user_email: Option<String>,
+    pub resource_type: AccessedResourceType,
+    pub resource_id: Uuid,
+    pub access_count: i64,
+    pub first_accessed_at: DateTime<Utc>,
+    pub last_accessed_at: DateTime<Utc>,
+}
+
+pub async fn record_data_access(
+    pool: &PgPool,
+    project_id: &Uuid,
+    user_id: &Uuid,
+    resource_type: AccessedResourceType,
+    resource_ids: &[Uuid],
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO data_access_log (project_id, user_id, resource_type, resource_id)
+        SELECT $1, $2, $3, unnest($4::uuid[])",
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .bind(resource_type)
+    .bind(resource_ids)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Accesses in the period grouped by user and resource, most recently accessed first
+pub async fn get_access_report(
+    pool: &PgPool,
+    project_id: &Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    resource_type: Option<AccessedResourceType>,
+    resource_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+) -> Result<Vec<AccessReportRow>> {
+    let rows = sqlx::query_as::<_, AccessReportRow>(
+        "SELECT
+            data_access_log.user_id,
+            users.email AS user_email,
+            data_access_log.resource_type,
+            data_access_log.resource_id,
+            count(*) AS access_count,
+            min(data_access_log.created_at) AS first_accessed_at,
+            max(data_access_log.created_at) AS last_accessed_at
+        FROM data_access_log
+        LEFT JOIN users ON users.id = data_access_log.user_id
+        WHERE data_access_log.project_id = $1
+            AND data_access_log.created_at >= $2
+            AND data_access_log.created_at < $3
+            AND ($4::accessed_resource_type IS NULL OR data_access_log.resource_type = $4)
+            AND ($5::uuid IS NULL OR data_access_log.resource_id = $5)
+            AND ($6::uuid IS NULL OR data_access_log.user_id = $6)
+        GROUP BY
+            data_access_log.user_id,
+            users.email,
+            data_access_log.resource_type,
+            data_access_log.resource_id
+        ORDER BY last_accessed_at DESC",
+    )
+    .bind(project_id)
+    .bind(start_time)
+    .bind(end_time)
+    .bind(resource_type)
+    .bind(resource_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
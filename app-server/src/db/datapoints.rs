@@ -2,15 +2,19 @@ use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 use serde_json::Value;
-use sqlx::{prelude::FromRow, PgPool};
+use sqlx::{prelude::FromRow, PgConnection, PgPool};
 use uuid::Uuid;
 
 use crate::datasets::datapoints::Datapoint;
 
+use super::dataset_versions;
+
+const IMPORT_BATCH_SIZE: usize = 1000;
+
 #[derive(FromRow, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DatapointView {
-    id: Uuid,
+    pub id: Uuid,
     created_at: DateTime<Utc>,
     dataset_id: Uuid,
     data: Value,
@@ -23,7 +27,53 @@ pub async fn insert_datapoints(
     dataset_id: &Uuid,
     datapoints: Vec<Datapoint>,
 ) -> Result<Vec<Datapoint>> {
-    let size = datapoints.len();
+    if datapoints.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut tx = pool.begin().await?;
+    let version =
+        dataset_versions::create_dataset_version(&mut tx, dataset_id, "add_datapoints").await?;
+    let datapoints =
+        insert_datapoints_in_version(&mut tx, dataset_id, datapoints, 0, version).await?;
+    tx.commit().await?;
+
+    Ok(datapoints)
+}
+
+/// Inserts the datapoints of an imported file in batches, as a single version of the dataset
+pub async fn import_datapoints(
+    pool: &PgPool,
+    dataset_id: &Uuid,
+    datapoints: Vec<Datapoint>,
+) -> Result<Vec<Datapoint>> {
+    if datapoints.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut tx = pool.begin().await?;
+    let version = dataset_versions::create_dataset_version(&mut tx, dataset_id, "import").await?;
+    let mut inserted = Vec::with_capacity(datapoints.len());
+    for batch in datapoints.chunks(IMPORT_BATCH_SIZE) {
+        let first_index = inserted.len() as i64;
+        inserted.extend(
+            insert_datapoints_in_version(&mut tx, dataset_id, batch.to_vec(), first_index, version)
+                .await?,
+        );
+    }
+    tx.commit().await?;
+
+    Ok(inserted)
+}
+
+/// Datapoints inserted in one transaction share the creation time, `first_index` orders them
+/// after the ones inserted before in the transaction
+pub(super) async fn insert_datapoints_in_version(
+    conn: &mut PgConnection,
+    dataset_id: &Uuid,
+    datapoints: Vec<Datapoint>,
+    first_index: i64,
+    version: i32,
+) -> Result<Vec<Datapoint>> {
+    let size = datapoints.len() as i64;
     let datapoints = sqlx::query_as::<_, Datapoint>(
         "INSERT INTO dataset_datapoints 
             (dataset_id, id, data, target, metadata, index_in_batch)
@@ -52,10 +102,13 @@ pub async fn insert_datapoints(
             .map(|dp| dp.metadata)
             .collect::<Vec<_>>(),
     )
-    .bind(&Vec::from_iter(0..size as i64))
-    .fetch_all(pool)
+    .bind(&Vec::from_iter(first_index..first_index + size))
+    .fetch_all(&mut *conn)
     .await?;
 
+    let datapoint_ids = datapoints.iter().map(|dp| dp.id).collect::<Vec<_>>();
+    dataset_versions::add_datapoint_revisions(conn, dataset_id, &datapoint_ids, version).await?;
+
     Ok(datapoints)
 }
 
@@ -115,31 +168,68 @@ pub async fn get_datapoints(
 
 pub async fn update_datapoint(
     pool: &PgPool,
+    dataset_id: &Uuid,
     datapoint_id: &Uuid,
     data: &Value,
     target: &Value,
     metadata: &Option<Value>,
 ) -> Result<Datapoint> {
+    let mut tx = pool.begin().await?;
+    let version =
+        dataset_versions::create_dataset_version(&mut tx, dataset_id, "update_datapoint").await?;
     let datapoint = sqlx::query_as::<_, Datapoint>(
-        "UPDATE dataset_datapoints SET data = $2, target = $3, metadata = $4
-        WHERE id = $1
+        "UPDATE dataset_datapoints SET data = $3, target = $4, metadata = $5
+        WHERE id = $1 AND dataset_id = $2
         RETURNING id, dataset_id, data, target, metadata",
     )
     .bind(datapoint_id)
+    .bind(dataset_id)
     .bind(data)
     .bind(target)
     .bind(metadata)
-    .fetch_optional(pool)
+    .fetch_optional(&mut *tx)
+    .await?
+    .context(anyhow::anyhow!("Failed to find datapoint by id"))?;
+
+    let datapoint_ids = [datapoint.id];
+    dataset_versions::remove_datapoint_revisions(
+        &mut tx,
+        dataset_id,
+        Some(&datapoint_ids),
+        version,
+    )
     .await?;
+    dataset_versions::add_datapoint_revisions(&mut tx, dataset_id, &datapoint_ids, version).await?;
+    tx.commit().await?;
 
-    datapoint.context(anyhow::anyhow!("Failed to find datapoint by id"))
+    Ok(datapoint)
 }
 
-pub async fn delete_datapoints(pool: &PgPool, datapoint_ids: &Vec<Uuid>) -> Result<()> {
-    sqlx::query("DELETE FROM dataset_datapoints WHERE id in (SELECT * FROM UNNEST($1::uuid[]))")
-        .bind(datapoint_ids)
-        .execute(pool)
-        .await?;
+pub async fn delete_datapoints(
+    pool: &PgPool,
+    dataset_id: &Uuid,
+    datapoint_ids: &Vec<Uuid>,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let version =
+        dataset_versions::create_dataset_version(&mut tx, dataset_id, "delete_datapoints").await?;
+    sqlx::query(
+        "DELETE FROM dataset_datapoints
+        WHERE dataset_id = $1 AND id in (SELECT * FROM UNNEST($2::uuid[]))",
+    )
+    .bind(dataset_id)
+    .bind(datapoint_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    dataset_versions::remove_datapoint_revisions(
+        &mut tx,
+        dataset_id,
+        Some(datapoint_ids.as_slice()),
+        version,
+    )
+    .await?;
+    tx.commit().await?;
 
     Ok(())
 }
@@ -150,16 +240,23 @@ struct DeletedDatapointId {
 }
 
 pub async fn delete_all_datapoints(pool: &PgPool, dataset_id: &Uuid) -> Result<Vec<Uuid>> {
+    let mut tx = pool.begin().await?;
+    let version =
+        dataset_versions::create_dataset_version(&mut tx, dataset_id, "delete_all_datapoints")
+            .await?;
     let datapoint_ids = sqlx::query_as::<_, DeletedDatapointId>(
         "DELETE FROM dataset_datapoints WHERE dataset_id = $1 RETURNING id",
     )
     .bind(dataset_id)
-    .fetch_all(pool)
+    .fetch_all(&mut *tx)
     .await?
     .iter()
     .map(|row| row.id)
     .collect();
 
+    dataset_versions::remove_datapoint_revisions(&mut tx, dataset_id, None, version).await?;
+    tx.commit().await?;
+
     Ok(datapoint_ids)
 }
 
@@ -0,0 +1,202 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::datasets::datapoints::Datapoint;
+
+use super::{datapoints, dataset_versions};
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "dataset_import_status")]
+pub enum DatasetImportStatus {
+    PENDING,
+    RUNNING,
+    DONE,
+    FAILED,
+}
+
+/// Import of an external dataset into a dataset, see `datasets::huggingface`
+#[derive(Serialize, FromRow, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetImport {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub dataset_id: Uuid,
+    /// `huggingface`
+    pub source: String,
+    pub repo_id: String,
+    pub config: String,
+    pub split: String,
+    /// `datasets::import::ImportMapping` of the source's columns
+    pub mapping: Value,
+    pub status: DatasetImportStatus,
+    /// Rows read from the source, including the failed ones. Resumed imports continue after them.
+    pub imported_rows: i64,
+    pub failed_rows: i64,
+    pub total_rows: Option<i64>,
+    pub error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct NewDatasetImport {
+    pub source: String,
+    pub repo_id: String,
+    pub config: String,
+    pub split: String,
+    pub mapping: Value,
+}
+
+const DATASET_IMPORT_COLUMNS: &str = "id, created_at, project_id, dataset_id, source, repo_id,
+    config, split, mapping, status, imported_rows, failed_rows, total_rows, error, updated_at";
+
+pub async fn create_dataset_import(
+    pool: &PgPool,
+    project_id: &Uuid,
+    dataset_id: &Uuid,
+    import: &NewDatasetImport,
+) -> Result<DatasetImport> {
+    let import = sqlx::query_as::<_, DatasetImport>(&format!(
+        "INSERT INTO dataset_imports (project_id, dataset_id, source, repo_id, config, split, mapping)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {DATASET_IMPORT_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(dataset_id)
+    .bind(&import.source)
+    .bind(&import.repo_id)
+    .bind(&import.config)
+    .bind(&import.split)
+    .bind(&import.mapping)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(import)
+}
+
+pub async fn get_dataset_imports(
+    pool: &PgPool,
+    project_id: &Uuid,
+    dataset_id: &Uuid,
+) -> Result<Vec<DatasetImport>> {
+    let imports = sqlx::query_as::<_, DatasetImport>(&format!(
+        "SELECT {DATASET_IMPORT_COLUMNS}
+        FROM dataset_imports
+        WHERE project_id = $1 AND dataset_id = $2
+        ORDER BY created_at DESC"
+    ))
+    .bind(project_id)
+    .bind(dataset_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(imports)
+}
+
+pub async fn get_dataset_import(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<DatasetImport>> {
+    let import = sqlx::query_as::<_, DatasetImport>(&format!(
+        "SELECT {DATASET_IMPORT_COLUMNS} FROM dataset_imports WHERE id = $1 AND project_id = $2"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(import)
+}
+
+/// Marks a failed or stalled import as running again, so that it is resumed only once. Imports
+/// are stalled if their progress hasn't changed for `stalled_after_minutes`, e.g. because the
+/// server was restarted.
+pub async fn restart_dataset_import(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+    stalled_after_minutes: i32,
+) -> Result<Option<DatasetImport>> {
+    let import = sqlx::query_as::<_, DatasetImport>(&format!(
+        "UPDATE dataset_imports
+        SET status = 'RUNNING', error = NULL, updated_at = now()
+        WHERE id = $1
+            AND project_id = $2
+            AND (status = 'FAILED'
+                OR (status IN ('PENDING', 'RUNNING')
+                    AND updated_at < now() - make_interval(mins => $3)))
+        RETURNING {DATASET_IMPORT_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .bind(stalled_after_minutes)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(import)
+}
+
+pub async fn update_dataset_import_status(
+    pool: &PgPool,
+    id: &Uuid,
+    status: DatasetImportStatus,
+    total_rows: Option<i64>,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE dataset_imports
+        SET status = $2, total_rows = COALESCE($3, total_rows), error = $4, updated_at = now()
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(status)
+    .bind(total_rows)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Inserts a batch of the import's datapoints as a new version of the dataset, together with
+/// the progress, so that a resumed import neither skips nor duplicates rows
+pub async fn insert_dataset_import_batch(
+    pool: &PgPool,
+    import: &DatasetImport,
+    datapoints: Vec<Datapoint>,
+    read_rows: i64,
+    failed_rows: i64,
+) -> Result<Vec<Datapoint>> {
+    let mut tx = pool.begin().await?;
+    let datapoints = if datapoints.is_empty() {
+        datapoints
+    } else {
+        let version =
+            dataset_versions::create_dataset_version(&mut tx, &import.dataset_id, "import").await?;
+        datapoints::insert_datapoints_in_version(
+            &mut tx,
+            &import.dataset_id,
+            datapoints,
+            0,
+            version,
+        )
+        .await?
+    };
+    sqlx::query(
+        "UPDATE dataset_imports
+        SET imported_rows = imported_rows + $2, failed_rows = failed_rows + $3, updated_at = now()
+        WHERE id = $1",
+    )
+    .bind(import.id)
+    .bind(read_rows)
+    .bind(failed_rows)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(datapoints)
+}
@@ -0,0 +1,319 @@
+//! Every change of a dataset's datapoints creates a new version of the dataset. Each revision of
+//! a datapoint is kept with the versions it was added and removed in, so that any version can be
+//! read back as it was.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::datapoints::DatapointView;
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetVersion {
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    /// Change that created the version, e.g. `add_datapoints`
+    pub action: String,
+    pub datapoints_count: i64,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DatapointChange {
+    pub datapoint_id: Uuid,
+    /// `added`, `removed` or `modified`
+    pub change: String,
+    /// Data, target and metadata of the datapoint in the older version
+    pub before: Option<Value>,
+    /// Data, target and metadata of the datapoint in the newer version
+    pub after: Option<Value>,
+}
+
+/// Entry of the dataset's change feed: the datapoint as of the version, or its deletion
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DatapointFeedChange {
+    pub version: i32,
+    pub changed_at: DateTime<Utc>,
+    pub datapoint_id: Uuid,
+    /// `upsert` or `delete`
+    pub operation: String,
+    pub data: Option<Value>,
+    pub target: Option<Value>,
+    pub metadata: Option<Value>,
+    pub datapoint_created_at: DateTime<Utc>,
+}
+
+/// Revisions of the dataset that are part of version `$2`
+const REVISIONS_AT_VERSION: &str = "dataset_id = $1
+    AND added_in_version <= $2
+    AND (removed_in_version IS NULL OR removed_in_version > $2)";
+
+/// Creates the next version of the dataset. Must be called in the transaction that changes the
+/// datapoints, before their revisions are recorded.
+pub async fn create_dataset_version(
+    conn: &mut PgConnection,
+    dataset_id: &Uuid,
+    action: &str,
+) -> Result<i32> {
+    let version = sqlx::query_scalar::<_, i32>(
+        "UPDATE datasets SET version = version + 1 WHERE id = $1 RETURNING version",
+    )
+    .bind(dataset_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query("INSERT INTO dataset_versions (dataset_id, version, action) VALUES ($1, $2, $3)")
+        .bind(dataset_id)
+        .bind(version)
+        .bind(action)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(version)
+}
+
+/// Records the current state of the datapoints as their revisions added in `version`
+pub async fn add_datapoint_revisions(
+    conn: &mut PgConnection,
+    dataset_id: &Uuid,
+    datapoint_ids: &[Uuid],
+    version: i32,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO dataset_datapoint_revisions
+            (dataset_id, datapoint_id, datapoint_created_at, index_in_batch, data, target,
+            metadata, added_in_version)
+        SELECT dataset_id, id, created_at, index_in_batch, data, target, metadata, $3
+        FROM dataset_datapoints
+        WHERE dataset_id = $1 AND id = ANY($2)",
+    )
+    .bind(dataset_id)
+    .bind(datapoint_ids)
+    .bind(version)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Ends the current revisions of the datapoints at `version`, all of the dataset's if
+/// `datapoint_ids` is None
+pub async fn remove_datapoint_revisions(
+    conn: &mut PgConnection,
+    dataset_id: &Uuid,
+    datapoint_ids: Option<&[Uuid]>,
+    version: i32,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE dataset_datapoint_revisions SET removed_in_version = $3
+        WHERE dataset_id = $1
+            AND removed_in_version IS NULL
+            AND ($2::uuid[] IS NULL OR datapoint_id = ANY($2))",
+    )
+    .bind(dataset_id)
+    .bind(datapoint_ids)
+    .bind(version)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_dataset_versions(pool: &PgPool, dataset_id: &Uuid) -> Result<Vec<DatasetVersion>> {
+    let versions = sqlx::query_as::<_, DatasetVersion>(
+        "SELECT
+            dataset_versions.version,
+            dataset_versions.created_at,
+            dataset_versions.action,
+            (
+                SELECT COUNT(*) FROM dataset_datapoint_revisions r
+                WHERE r.dataset_id = dataset_versions.dataset_id
+                    AND r.added_in_version <= dataset_versions.version
+                    AND (r.removed_in_version IS NULL
+                        OR r.removed_in_version > dataset_versions.version)
+            ) AS datapoints_count
+        FROM dataset_versions
+        WHERE dataset_id = $1
+        ORDER BY version DESC",
+    )
+    .bind(dataset_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(versions)
+}
+
+/// Datapoints of the dataset as they were in the version, ordered like the current datapoints
+pub async fn get_datapoints_at_version(
+    pool: &PgPool,
+    dataset_id: &Uuid,
+    version: i32,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<DatapointView>> {
+    let datapoints = sqlx::query_as::<_, DatapointView>(&format!(
+        "SELECT
+            datapoint_id AS id,
+            dataset_id,
+            data,
+            target,
+            metadata,
+            datapoint_created_at AS created_at
+        FROM dataset_datapoint_revisions
+        WHERE {REVISIONS_AT_VERSION}
+        ORDER BY
+            datapoint_created_at DESC,
+            index_in_batch ASC NULLS FIRST
+        LIMIT $3
+        OFFSET $4"
+    ))
+    .bind(dataset_id)
+    .bind(version)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(datapoints)
+}
+
+pub async fn count_datapoints_at_version(
+    pool: &PgPool,
+    dataset_id: &Uuid,
+    version: i32,
+) -> Result<u64> {
+    let count = sqlx::query_scalar::<_, i64>(&format!(
+        "SELECT COUNT(*) FROM dataset_datapoint_revisions WHERE {REVISIONS_AT_VERSION}"
+    ))
+    .bind(dataset_id)
+    .bind(version)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count as u64)
+}
+
+/// Datapoints added, removed or modified from version `from` to version `to`
+pub async fn diff_dataset_versions(
+    pool: &PgPool,
+    dataset_id: &Uuid,
+    from: i32,
+    to: i32,
+) -> Result<Vec<DatapointChange>> {
+    let changes = sqlx::query_as::<_, DatapointChange>(
+        "WITH before AS (
+            SELECT datapoint_id, data, target, metadata FROM dataset_datapoint_revisions
+            WHERE dataset_id = $1
+                AND added_in_version <= $2
+                AND (removed_in_version IS NULL OR removed_in_version > $2)
+        ),
+        after AS (
+            SELECT datapoint_id, data, target, metadata FROM dataset_datapoint_revisions
+            WHERE dataset_id = $1
+                AND added_in_version <= $3
+                AND (removed_in_version IS NULL OR removed_in_version > $3)
+        )
+        SELECT
+            COALESCE(before.datapoint_id, after.datapoint_id) AS datapoint_id,
+            CASE
+                WHEN before.datapoint_id IS NULL THEN 'added'
+                WHEN after.datapoint_id IS NULL THEN 'removed'
+                ELSE 'modified'
+            END AS change,
+            CASE WHEN before.datapoint_id IS NOT NULL THEN jsonb_build_object(
+                'data', before.data, 'target', before.target, 'metadata', before.metadata
+            ) END AS before,
+            CASE WHEN after.datapoint_id IS NOT NULL THEN jsonb_build_object(
+                'data', after.data, 'target', after.target, 'metadata', after.metadata
+            ) END AS after
+        FROM before
+        FULL OUTER JOIN after ON before.datapoint_id = after.datapoint_id
+        WHERE before.datapoint_id IS NULL
+            OR after.datapoint_id IS NULL
+            OR before.data IS DISTINCT FROM after.data
+            OR before.target IS DISTINCT FROM after.target
+            OR before.metadata IS DISTINCT FROM after.metadata
+        ORDER BY change, datapoint_id",
+    )
+    .bind(dataset_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(changes)
+}
+
+/// Changes of the datapoints after the position `($2, $3)` of the feed, which is ordered by
+/// version and then datapoint id. Without `$3`, the changes after all of version `$2`. Modified
+/// datapoints are upserted with their new revision, so that replaying the feed in order onto
+/// the datapoints at a version gives the current datapoints.
+pub async fn get_datapoint_changes_after(
+    pool: &PgPool,
+    dataset_id: &Uuid,
+    version: i32,
+    datapoint_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<DatapointFeedChange>> {
+    let changes = sqlx::query_as::<_, DatapointFeedChange>(
+        "WITH changes AS (
+            SELECT
+                added_in_version AS version,
+                datapoint_id,
+                'upsert' AS operation,
+                data,
+                target,
+                metadata,
+                datapoint_created_at
+            FROM dataset_datapoint_revisions
+            WHERE dataset_id = $1 AND added_in_version >= $2
+            UNION ALL
+            SELECT
+                removed.removed_in_version AS version,
+                removed.datapoint_id,
+                'delete' AS operation,
+                NULL AS data,
+                NULL AS target,
+                NULL AS metadata,
+                removed.datapoint_created_at
+            FROM dataset_datapoint_revisions removed
+            WHERE removed.dataset_id = $1
+                AND removed.removed_in_version >= $2
+                AND NOT EXISTS (
+                    SELECT 1 FROM dataset_datapoint_revisions added
+                    WHERE added.dataset_id = $1
+                        AND added.datapoint_id = removed.datapoint_id
+                        AND added.added_in_version = removed.removed_in_version
+                )
+        )
+        SELECT
+            changes.version,
+            dataset_versions.created_at AS changed_at,
+            changes.datapoint_id,
+            changes.operation,
+            changes.data,
+            changes.target,
+            changes.metadata,
+            changes.datapoint_created_at
+        FROM changes
+        JOIN dataset_versions
+            ON dataset_versions.dataset_id = $1 AND dataset_versions.version = changes.version
+        WHERE changes.version > $2
+            OR ($3::uuid IS NOT NULL AND changes.version = $2 AND changes.datapoint_id > $3)
+        ORDER BY changes.version, changes.datapoint_id
+        LIMIT $4",
+    )
+    .bind(dataset_id)
+    .bind(version)
+    .bind(datapoint_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(changes)
+}
@@ -6,10 +6,16 @@ use crate::datasets::Dataset;
 
 pub async fn create_dataset(pool: &PgPool, name: &String, project_id: Uuid) -> Result<Dataset> {
     let dataset = sqlx::query_as::<_, Dataset>(
-        "INSERT INTO datasets (name, project_id)
-        VALUES ($1, $2)
-        RETURNING id, created_at, name, project_id, indexed_on
-        ",
+        "WITH dataset AS (
+            INSERT INTO datasets (name, project_id)
+            VALUES ($1, $2)
+            RETURNING id, created_at, name, project_id, indexed_on, version
+        ),
+        initial_version AS (
+            INSERT INTO dataset_versions (dataset_id, version, action)
+            SELECT id, version, 'create' FROM dataset
+        )
+        SELECT * FROM dataset",
     )
     .bind(name)
     .bind(project_id)
@@ -31,7 +37,8 @@ pub async fn get_datasets(
             created_at,
             name,
             project_id,
-            indexed_on
+            indexed_on,
+            version
         FROM datasets WHERE project_id = $1
         ORDER BY created_at DESC
         LIMIT $2 OFFSET $3",
@@ -56,7 +63,7 @@ pub async fn count_datasets(pool: &PgPool, project_id: Uuid) -> Result<i64> {
 
 pub async fn get_dataset(pool: &PgPool, project_id: Uuid, dataset_id: Uuid) -> Result<Dataset> {
     let dataset = sqlx::query_as::<_, Dataset>(
-        "SELECT id, created_at, name, project_id, indexed_on FROM datasets WHERE id = $1 AND project_id = $2",
+        "SELECT id, created_at, name, project_id, indexed_on, version FROM datasets WHERE id = $1 AND project_id = $2",
     )
     .bind(dataset_id)
     .bind(project_id)
@@ -74,7 +81,7 @@ pub async fn rename_dataset(
 ) -> Result<Dataset> {
     let dataset = sqlx::query_as::<_, Dataset>(
         "UPDATE datasets SET name = $3 WHERE id = $1 AND project_id = $2
-        RETURNING id, created_at, name, project_id, indexed_on",
+        RETURNING id, created_at, name, project_id, indexed_on, version",
     )
     .bind(id)
     .bind(project_id)
@@ -101,7 +108,7 @@ pub async fn update_index_column(
 ) -> Result<Dataset> {
     let dataset = sqlx::query_as::<_, Dataset>(
         "UPDATE datasets SET indexed_on = $2 WHERE id = $1
-        RETURNING id, created_at, name, project_id, indexed_on",
+        RETURNING id, created_at, name, project_id, indexed_on, version",
     )
     .bind(dataset_id)
     .bind(index_column)
@@ -117,7 +124,7 @@ pub async fn get_dataset_by_name(
     project_id: Uuid,
 ) -> Result<Option<Dataset>> {
     let dataset = sqlx::query_as::<_, Dataset>(
-        "SELECT id, created_at, name, project_id, indexed_on
+        "SELECT id, created_at, name, project_id, indexed_on, version
         FROM datasets
         WHERE name = $1 AND project_id = $2
         ORDER BY created_at DESC
@@ -130,3 +137,20 @@ pub async fn get_dataset_by_name(
 
     Ok(dataset)
 }
+
+/// Current version of the dataset, None if it's not in the project
+pub async fn get_dataset_version(
+    pool: &PgPool,
+    project_id: &Uuid,
+    dataset_id: &Uuid,
+) -> Result<Option<i32>> {
+    let version = sqlx::query_scalar::<_, i32>(
+        "SELECT version FROM datasets WHERE id = $1 AND project_id = $2",
+    )
+    .bind(dataset_id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(version)
+}
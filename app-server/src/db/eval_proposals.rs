@@ -0,0 +1,153 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "eval_proposal_status")]
+pub enum EvalProposalStatus {
+    GENERATING,
+    READY,
+    FAILED,
+    ADOPTED,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct EvalProposal {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub span_path: String,
+    pub status: EvalProposalStatus,
+    /// Proposed dataset datapoints, Vec<ProposedDatapoint>
+    pub datapoints: Value,
+    /// Rubric of the proposed LLM judge evaluator
+    pub rubric: Option<String>,
+    pub error: Option<String>,
+    /// Dataset and label class created on adoption
+    pub dataset_id: Option<Uuid>,
+    pub label_class_id: Option<Uuid>,
+}
+
+const EVAL_PROPOSAL_COLUMNS: &str = "id, created_at, project_id, span_path, status, datapoints, \
+    rubric, error, dataset_id, label_class_id";
+
+pub async fn create_eval_proposal(
+    pool: &PgPool,
+    project_id: &Uuid,
+    span_path: &str,
+) -> Result<EvalProposal> {
+    let proposal = sqlx::query_as::<_, EvalProposal>(&format!(
+        "INSERT INTO eval_proposals (project_id, span_path)
+        VALUES ($1, $2)
+        RETURNING {EVAL_PROPOSAL_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(span_path)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(proposal)
+}
+
+pub async fn set_eval_proposal_ready(
+    pool: &PgPool,
+    id: &Uuid,
+    datapoints: &Value,
+    rubric: &str,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE eval_proposals SET status = 'READY', datapoints = $2, rubric = $3
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(datapoints)
+    .bind(rubric)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_eval_proposal_failed(pool: &PgPool, id: &Uuid, error: &str) -> Result<()> {
+    sqlx::query("UPDATE eval_proposals SET status = 'FAILED', error = $2 WHERE id = $1")
+        .bind(id)
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_eval_proposals(pool: &PgPool, project_id: &Uuid) -> Result<Vec<EvalProposal>> {
+    let proposals = sqlx::query_as::<_, EvalProposal>(&format!(
+        "SELECT {EVAL_PROPOSAL_COLUMNS}
+        FROM eval_proposals
+        WHERE project_id = $1
+        ORDER BY created_at DESC"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(proposals)
+}
+
+/// Moves a ready proposal to ADOPTED, so that it can only be adopted once.
+/// Returns None if the proposal doesn't exist or is not ready.
+pub async fn claim_eval_proposal(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<EvalProposal>> {
+    let proposal = sqlx::query_as::<_, EvalProposal>(&format!(
+        "UPDATE eval_proposals SET status = 'ADOPTED'
+        WHERE id = $1 AND project_id = $2 AND status = 'READY'
+        RETURNING {EVAL_PROPOSAL_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(proposal)
+}
+
+/// Gives a claimed proposal back, e.g. when its adoption failed
+pub async fn release_eval_proposal(pool: &PgPool, id: &Uuid) -> Result<()> {
+    sqlx::query("UPDATE eval_proposals SET status = 'READY' WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_eval_proposal_adopted(
+    pool: &PgPool,
+    id: &Uuid,
+    dataset_id: &Uuid,
+    label_class_id: &Uuid,
+) -> Result<()> {
+    sqlx::query("UPDATE eval_proposals SET dataset_id = $2, label_class_id = $3 WHERE id = $1")
+        .bind(id)
+        .bind(dataset_id)
+        .bind(label_class_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_eval_proposal(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM eval_proposals WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
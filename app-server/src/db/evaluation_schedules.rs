@@ -0,0 +1,324 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "evaluation_schedule_run_status")]
+pub enum EvaluationScheduleRunStatus {
+    QUEUED,
+    RUNNING,
+    SUCCEEDED,
+    FAILED,
+    /// Not run, because the previous run of the schedule was still running when it was due
+    SKIPPED,
+}
+
+/// Evaluation that runs on a cron schedule, see `evaluations::schedules`
+#[derive(Serialize, FromRow, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationSchedule {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub name: String,
+    /// Cron expression in UTC, with or without the seconds field
+    pub cron_schedule: String,
+    /// Dataset whose current datapoints are evaluated
+    pub dataset_id: Uuid,
+    pub pipeline_id: Uuid,
+    /// Deployment target whose version is evaluated, unless `pipeline_version_id` is set
+    pub target: String,
+    /// Pins the evaluated version of the pipeline
+    pub pipeline_version_id: Option<Uuid>,
+    /// Names of the project's judge evaluators that score the outputs
+    pub judges: Vec<String>,
+    /// Group of the created evaluations, so that the runs are compared with each other
+    pub group_id: String,
+    pub enabled: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationScheduleRun {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub schedule_id: Uuid,
+    pub status: EvaluationScheduleRunStatus,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    /// Evaluation created by the run, once it has succeeded
+    pub evaluation_id: Option<Uuid>,
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+pub struct NewEvaluationSchedule {
+    pub project_id: Uuid,
+    pub name: String,
+    pub cron_schedule: String,
+    pub dataset_id: Uuid,
+    pub pipeline_id: Uuid,
+    pub target: String,
+    pub pipeline_version_id: Option<Uuid>,
+    pub judges: Vec<String>,
+    pub group_id: String,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+}
+
+const SCHEDULE_COLUMNS: &str = "id, created_at, project_id, name, cron_schedule, dataset_id, \
+    pipeline_id, target, pipeline_version_id, judges, group_id, enabled, next_run_at, created_by";
+
+const SCHEDULE_RUN_COLUMNS: &str = "id, created_at, schedule_id, status, scheduled_at, \
+    evaluation_id, error, started_at, finished_at";
+
+/// Returns None if the project already has a schedule with the name
+pub async fn create_schedule(
+    pool: &PgPool,
+    schedule: &NewEvaluationSchedule,
+) -> Result<Option<EvaluationSchedule>> {
+    let schedule = sqlx::query_as::<_, EvaluationSchedule>(&format!(
+        "INSERT INTO evaluation_schedules (
+            project_id,
+            name,
+            cron_schedule,
+            dataset_id,
+            pipeline_id,
+            target,
+            pipeline_version_id,
+            judges,
+            group_id,
+            next_run_at,
+            created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        ON CONFLICT (project_id, name) DO NOTHING
+        RETURNING {SCHEDULE_COLUMNS}"
+    ))
+    .bind(schedule.project_id)
+    .bind(&schedule.name)
+    .bind(&schedule.cron_schedule)
+    .bind(schedule.dataset_id)
+    .bind(schedule.pipeline_id)
+    .bind(&schedule.target)
+    .bind(schedule.pipeline_version_id)
+    .bind(&schedule.judges)
+    .bind(&schedule.group_id)
+    .bind(schedule.next_run_at)
+    .bind(schedule.created_by)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(schedule)
+}
+
+pub async fn get_schedule(pool: &PgPool, id: &Uuid) -> Result<Option<EvaluationSchedule>> {
+    let schedule = sqlx::query_as::<_, EvaluationSchedule>(&format!(
+        "SELECT {SCHEDULE_COLUMNS} FROM evaluation_schedules WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(schedule)
+}
+
+pub async fn get_schedules(pool: &PgPool, project_id: &Uuid) -> Result<Vec<EvaluationSchedule>> {
+    let schedules = sqlx::query_as::<_, EvaluationSchedule>(&format!(
+        "SELECT {SCHEDULE_COLUMNS} FROM evaluation_schedules
+        WHERE project_id = $1
+        ORDER BY name"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(schedules)
+}
+
+/// Enables or disables the schedule. Like pipeline trigger schedules, it continues from
+/// `next_run_at`, so that runs missed while it was disabled are not caught up on.
+pub async fn set_schedule_enabled(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+    enabled: bool,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<Option<EvaluationSchedule>> {
+    let schedule = sqlx::query_as::<_, EvaluationSchedule>(&format!(
+        "UPDATE evaluation_schedules SET enabled = $3, next_run_at = $4
+        WHERE id = $1 AND project_id = $2
+        RETURNING {SCHEDULE_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .bind(enabled)
+    .bind(next_run_at)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(schedule)
+}
+
+pub async fn delete_schedule(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM evaluation_schedules WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[derive(FromRow)]
+struct DueSchedule {
+    id: Uuid,
+    cron_schedule: String,
+    next_run_at: Option<DateTime<Utc>>,
+    /// Whether a previous run is queued or has been running for less than the timeout
+    in_progress: bool,
+}
+
+/// Queues a run of each enabled schedule that is due and moves it to its next run, which
+/// `next_run_at` computes from the cron schedule. The run is recorded as skipped if the previous
+/// run of the schedule hasn't finished, unless it has been running for longer than
+/// `run_timeout`, in which case its instance is assumed to have stopped. Schedules are locked
+/// while they are queued, so that every due run is queued by exactly one app-server instance.
+pub async fn enqueue_due_schedule_runs(
+    pool: &PgPool,
+    run_timeout: chrono::Duration,
+    next_run_at: impl Fn(&str) -> Option<DateTime<Utc>>,
+) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    let schedules = sqlx::query_as::<_, DueSchedule>(
+        "SELECT
+            id,
+            cron_schedule,
+            next_run_at,
+            EXISTS (
+                SELECT 1 FROM evaluation_schedule_runs runs
+                WHERE runs.schedule_id = evaluation_schedules.id
+                    AND (
+                        runs.status = 'QUEUED'
+                        OR (runs.status = 'RUNNING' AND runs.started_at > now() - $1)
+                    )
+            ) AS in_progress
+        FROM evaluation_schedules
+        WHERE enabled AND next_run_at <= now()
+        FOR UPDATE SKIP LOCKED",
+    )
+    .bind(run_timeout)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for schedule in &schedules {
+        sqlx::query("UPDATE evaluation_schedules SET next_run_at = $2 WHERE id = $1")
+            .bind(schedule.id)
+            .bind(next_run_at(&schedule.cron_schedule))
+            .execute(&mut *tx)
+            .await?;
+        if schedule.in_progress {
+            sqlx::query(
+                "INSERT INTO evaluation_schedule_runs
+                    (schedule_id, status, scheduled_at, error, finished_at)
+                VALUES ($1, 'SKIPPED', $2, 'The previous run has not finished', now())",
+            )
+            .bind(schedule.id)
+            .bind(schedule.next_run_at)
+            .execute(&mut *tx)
+            .await?;
+        } else {
+            sqlx::query(
+                "INSERT INTO evaluation_schedule_runs (schedule_id, scheduled_at) VALUES ($1, $2)",
+            )
+            .bind(schedule.id)
+            .bind(schedule.next_run_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(schedules.len() as u64)
+}
+
+/// Marks the oldest queued run as running and returns it, if there is one
+pub async fn claim_queued_schedule_run(pool: &PgPool) -> Result<Option<EvaluationScheduleRun>> {
+    let run = sqlx::query_as::<_, EvaluationScheduleRun>(&format!(
+        "UPDATE evaluation_schedule_runs SET status = 'RUNNING', started_at = now()
+        WHERE id = (
+            SELECT id FROM evaluation_schedule_runs
+            WHERE status = 'QUEUED'
+            ORDER BY created_at ASC
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {SCHEDULE_RUN_COLUMNS}"
+    ))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(run)
+}
+
+pub async fn finish_schedule_run(
+    pool: &PgPool,
+    id: &Uuid,
+    status: EvaluationScheduleRunStatus,
+    evaluation_id: Option<Uuid>,
+    error: Option<String>,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE evaluation_schedule_runs SET
+            status = $2,
+            evaluation_id = $3,
+            error = $4,
+            finished_at = now()
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(status)
+    .bind(evaluation_id)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Run history of the schedule, the newest first
+pub async fn get_schedule_runs(
+    pool: &PgPool,
+    project_id: &Uuid,
+    schedule_id: &Uuid,
+    limit: i64,
+) -> Result<Vec<EvaluationScheduleRun>> {
+    let runs = sqlx::query_as::<_, EvaluationScheduleRun>(
+        "SELECT
+            runs.id,
+            runs.created_at,
+            runs.schedule_id,
+            runs.status,
+            runs.scheduled_at,
+            runs.evaluation_id,
+            runs.error,
+            runs.started_at,
+            runs.finished_at
+        FROM evaluation_schedule_runs runs
+        JOIN evaluation_schedules ON evaluation_schedules.id = runs.schedule_id
+        WHERE evaluation_schedules.project_id = $1 AND runs.schedule_id = $2
+        ORDER BY runs.created_at DESC
+        LIMIT $3",
+    )
+    .bind(project_id)
+    .bind(schedule_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(runs)
+}
@@ -2,14 +2,19 @@ use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use futures::stream::BoxStream;
+use lazy_static::lazy_static;
 use serde::Serialize;
 use serde_json::Value;
 use sqlx::{prelude::FromRow, PgPool};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::evaluations::utils::{ScoreType, ScoreValue};
+
 use super::DB;
 
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, FromRow, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Evaluation {
     pub id: Uuid,
@@ -20,6 +25,9 @@ pub struct Evaluation {
     ///
     /// Conceptually, evaluations with different group ids are used to test different features.
     pub group_id: String,
+    /// Dataset the evaluation ran on, and its version at the time
+    pub dataset_id: Option<Uuid>,
+    pub dataset_version: Option<i32>,
 }
 
 #[derive(Serialize, FromRow)]
@@ -30,11 +38,18 @@ pub struct EvaluationDatapoint {
     pub evaluation_id: Uuid,
     pub data: Value,
     pub target: Value,
-    pub scores: Value, // HashMap<String, f64>
+    pub scores: Value, // HashMap<String, ScoreValue>
     pub executor_output: Option<Value>,
     pub trace_id: Uuid,
 }
 
+/// Score of an `evaluation_scores` row as JSON, in the form it was reported in, see `ScoreValue`
+pub const SCORE_VALUE_JSON: &str = "CASE evaluation_scores.score_type
+    WHEN 'BOOLEAN' THEN to_jsonb(evaluation_scores.score = 1)
+    WHEN 'CATEGORICAL' THEN to_jsonb(evaluation_scores.label)
+    ELSE to_jsonb(evaluation_scores.score)
+END";
+
 #[derive(Serialize, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct EvaluationDatapointPreview {
@@ -46,23 +61,31 @@ pub struct EvaluationDatapointPreview {
 
 pub async fn create_evaluation(
     pool: &PgPool,
+    id: Uuid,
     name: &String,
     project_id: Uuid,
     group_id: &str,
+    dataset_id: Option<Uuid>,
+    dataset_version: Option<i32>,
 ) -> Result<Evaluation> {
     let evaluation = sqlx::query_as::<_, Evaluation>(
-        "INSERT INTO evaluations (name, project_id, group_id)
-        VALUES ($1, $2, $3)
+        "INSERT INTO evaluations (id, name, project_id, group_id, dataset_id, dataset_version)
+        VALUES ($1, $2, $3, $4, $5, $6)
         RETURNING
             id,
             created_at,
             name,
             project_id,
-            group_id",
+            group_id,
+            dataset_id,
+            dataset_version",
     )
+    .bind(id)
     .bind(name)
     .bind(project_id)
     .bind(group_id)
+    .bind(dataset_id)
+    .bind(dataset_version)
     .fetch_one(pool)
     .await?;
 
@@ -76,7 +99,7 @@ pub async fn get_evaluation(
 ) -> Result<Evaluation> {
     let evaluation = sqlx::query_as::<_, Evaluation>(
         "SELECT
-            id, name, project_id, created_at, group_id
+            id, name, project_id, created_at, group_id, dataset_id, dataset_version
         FROM evaluations WHERE id = $1 AND project_id = $2",
     )
     .bind(evaluation_id)
@@ -89,7 +112,7 @@ pub async fn get_evaluation(
 
 pub async fn get_evaluations(pool: &PgPool, project_id: Uuid) -> Result<Vec<Evaluation>> {
     let evaluations = sqlx::query_as::<_, Evaluation>(
-        "SELECT id, name, project_id, created_at, group_id
+        "SELECT id, name, project_id, created_at, group_id, dataset_id, dataset_version
         FROM evaluations WHERE project_id = $1
         ORDER BY created_at DESC",
     )
@@ -106,7 +129,7 @@ pub async fn get_evaluations_grouped_by_current_evaluation(
     current_evaluation_id: Uuid,
 ) -> Result<Vec<Evaluation>> {
     let evaluations = sqlx::query_as::<_, Evaluation>(
-        "SELECT id, name, project_id, created_at, group_id
+        "SELECT id, name, project_id, created_at, group_id, dataset_id, dataset_version
         FROM evaluations
         WHERE project_id = $1
           AND group_id = (SELECT group_id FROM evaluations WHERE id = $2)
@@ -127,7 +150,7 @@ pub async fn set_evaluation_results(
     db: Arc<DB>,
     evaluation_id: Uuid,
     ids: &Vec<Uuid>,
-    scores: &Vec<HashMap<String, f64>>,
+    scores: &Vec<HashMap<String, ScoreValue>>,
     datas: &Vec<Value>,
     targets: &Vec<Value>,
     executor_outputs: &Vec<Option<Value>>,
@@ -167,30 +190,38 @@ pub async fn set_evaluation_results(
     .fetch_all(&db.pool)
     .await?;
 
-    // Each datapoint can have multiple scores, so unzip the scores and result ids.
-    let (score_result_ids, (score_names, score_values)): (Vec<Uuid>, (Vec<String>, Vec<f64>)) =
-        scores
-            .iter()
-            .zip(results.iter())
-            .flat_map(|(score, result)| {
-                score
-                    .iter()
-                    .map(|(name, value)| (result.id, (name.clone(), value)))
-            })
-            .unzip();
+    // Each datapoint can have multiple scores, so flatten the scores with their result ids.
+    let mut score_result_ids = Vec::new();
+    let mut score_names = Vec::new();
+    let mut score_values = Vec::new();
+    let mut score_types = Vec::new();
+    let mut score_labels = Vec::new();
+    for (score, result) in scores.iter().zip(results.iter()) {
+        for (name, value) in score {
+            score_result_ids.push(result.id);
+            score_names.push(name.clone());
+            score_values.push(value.as_f64());
+            score_types.push(value.score_type().as_str());
+            score_labels.push(value.label());
+        }
+    }
 
     sqlx::query(
-        "INSERT INTO evaluation_scores (result_id, name, score)
+        "INSERT INTO evaluation_scores (result_id, name, score, score_type, label)
         SELECT
             result_id,
             name,
-            score
-        FROM UNNEST ($1::uuid[], $2::text[], $3::float8[])
-        AS tmp_table(result_id, name, score)",
+            score,
+            score_type::evaluation_score_type,
+            label
+        FROM UNNEST ($1::uuid[], $2::text[], $3::float8[], $4::text[], $5::text[])
+        AS tmp_table(result_id, name, score, score_type, label)",
     )
     .bind(&score_result_ids)
     .bind(&score_names)
     .bind(&score_values)
+    .bind(&score_types)
+    .bind(&score_labels)
     .execute(&db.pool)
     .await?;
 
@@ -201,11 +232,11 @@ pub async fn get_evaluation_results(
     pool: &PgPool,
     evaluation_id: Uuid,
 ) -> Result<Vec<EvaluationDatapoint>> {
-    let results = sqlx::query_as::<_, EvaluationDatapoint>(
+    let results = sqlx::query_as::<_, EvaluationDatapoint>(&format!(
         "WITH scores AS (
             SELECT
                 result_id,
-                jsonb_object_agg(name, score) as scores
+                jsonb_object_agg(name, {SCORE_VALUE_JSON}) as scores
             FROM evaluation_scores
             GROUP BY result_id
         )
@@ -221,8 +252,8 @@ pub async fn get_evaluation_results(
         FROM evaluation_results r
         LEFT JOIN scores s ON r.id = s.result_id
         WHERE evaluation_id = $1
-        ORDER BY created_at ASC, index_in_batch ASC NULLS FIRST",
-    )
+        ORDER BY created_at ASC, index_in_batch ASC NULLS FIRST"
+    ))
     .bind(evaluation_id)
     .fetch_all(pool)
     .await?;
@@ -230,6 +261,104 @@ pub async fn get_evaluation_results(
     Ok(results)
 }
 
+lazy_static! {
+    /// The stream borrows its query, so the query is built once
+    static ref STREAM_EVALUATION_RESULTS_QUERY: String = format!(
+        "SELECT
+            r.id,
+            r.created_at,
+            r.evaluation_id,
+            r.data,
+            r.target,
+            r.executor_output,
+            COALESCE((
+                SELECT jsonb_object_agg(name, {SCORE_VALUE_JSON})
+                FROM evaluation_scores
+                WHERE result_id = r.id
+            ), '{{}}'::jsonb) as scores,
+            r.trace_id
+        FROM evaluation_results r
+        WHERE evaluation_id = $1
+        ORDER BY created_at ASC, index_in_batch ASC NULLS FIRST"
+    );
+}
+
+/// Results of the evaluation with their scores, in the order of `get_evaluation_results`, without
+/// loading them all into memory
+pub fn stream_evaluation_results(
+    pool: &PgPool,
+    evaluation_id: Uuid,
+) -> BoxStream<'_, Result<EvaluationDatapoint, sqlx::Error>> {
+    sqlx::query_as::<_, EvaluationDatapoint>(STREAM_EVALUATION_RESULTS_QUERY.as_str())
+        .bind(evaluation_id)
+        .fetch(pool)
+}
+
+#[derive(FromRow)]
+pub struct EvaluationScoreName {
+    pub name: String,
+    /// If the name has scores of several types, the last of `NUMERIC`, `BOOLEAN` and `CATEGORICAL`
+    pub score_type: ScoreType,
+}
+
+pub async fn get_evaluation_score_names(
+    pool: &PgPool,
+    evaluation_id: Uuid,
+) -> Result<Vec<EvaluationScoreName>> {
+    let names = sqlx::query_as::<_, EvaluationScoreName>(
+        "SELECT evaluation_scores.name, MAX(evaluation_scores.score_type) as score_type
+        FROM evaluation_scores
+        JOIN evaluation_results ON evaluation_results.id = evaluation_scores.result_id
+        WHERE evaluation_results.evaluation_id = $1
+        GROUP BY evaluation_scores.name
+        ORDER BY evaluation_scores.name",
+    )
+    .bind(evaluation_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(names)
+}
+
+#[derive(FromRow)]
+pub struct EvaluationScoreAverage {
+    pub name: String,
+    /// Pass rate for boolean scores
+    pub average: f64,
+    /// Number of datapoints with the score
+    pub count: i64,
+}
+
+/// Number of results of the evaluation and the average of each of its numeric and boolean scores
+pub async fn get_evaluation_score_averages(
+    pool: &PgPool,
+    evaluation_id: Uuid,
+) -> Result<(i64, Vec<EvaluationScoreAverage>)> {
+    let result_count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM evaluation_results WHERE evaluation_id = $1",
+    )
+    .bind(evaluation_id)
+    .fetch_one(pool)
+    .await?;
+
+    let averages = sqlx::query_as::<_, EvaluationScoreAverage>(
+        "SELECT
+            evaluation_scores.name,
+            AVG(evaluation_scores.score) as average,
+            COUNT(*) as count
+        FROM evaluation_scores
+        JOIN evaluation_results ON evaluation_results.id = evaluation_scores.result_id
+        WHERE evaluation_results.evaluation_id = $1
+            AND evaluation_scores.score IS NOT NULL
+        GROUP BY evaluation_scores.name",
+    )
+    .bind(evaluation_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok((result_count, averages))
+}
+
 pub async fn delete_evaluation(pool: &PgPool, evaluation_id: &Uuid) -> Result<()> {
     sqlx::query("DELETE FROM evaluations WHERE id = $1")
         .bind(evaluation_id)
@@ -237,3 +366,143 @@ pub async fn delete_evaluation(pool: &PgPool, evaluation_id: &Uuid) -> Result<()
         .await?;
     Ok(())
 }
+
+/// Pins the evaluation as the baseline of its group, replacing the pinned one
+pub async fn set_group_baseline(
+    pool: &PgPool,
+    project_id: &Uuid,
+    group_id: &str,
+    evaluation_id: &Uuid,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO evaluation_group_baselines (project_id, group_id, evaluation_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (project_id, group_id)
+        DO UPDATE SET evaluation_id = $3, created_at = now()",
+    )
+    .bind(project_id)
+    .bind(group_id)
+    .bind(evaluation_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Pinned baseline evaluation of the group, None if there is none
+pub async fn get_group_baseline(
+    pool: &PgPool,
+    project_id: &Uuid,
+    group_id: &str,
+) -> Result<Option<Uuid>> {
+    let evaluation_id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT evaluation_id FROM evaluation_group_baselines
+        WHERE project_id = $1 AND group_id = $2",
+    )
+    .bind(project_id)
+    .bind(group_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(evaluation_id)
+}
+
+pub async fn delete_group_baseline(pool: &PgPool, project_id: &Uuid, group_id: &str) -> Result<()> {
+    sqlx::query("DELETE FROM evaluation_group_baselines WHERE project_id = $1 AND group_id = $2")
+        .bind(project_id)
+        .bind(group_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Results of the project's evaluations with the given ids, in the order of their creation
+pub async fn get_evaluation_results_by_ids(
+    pool: &PgPool,
+    project_id: Uuid,
+    result_ids: &[Uuid],
+) -> Result<Vec<EvaluationDatapoint>> {
+    let results = sqlx::query_as::<_, EvaluationDatapoint>(&format!(
+        "WITH scores AS (
+            SELECT
+                result_id,
+                jsonb_object_agg(name, {SCORE_VALUE_JSON}) as scores
+            FROM evaluation_scores
+            WHERE result_id = ANY($2)
+            GROUP BY result_id
+        )
+        SELECT
+            r.id,
+            r.created_at,
+            r.evaluation_id,
+            r.data,
+            r.target,
+            r.executor_output,
+            s.scores,
+            r.trace_id
+        FROM evaluation_results r
+        JOIN evaluations e ON e.id = r.evaluation_id
+        LEFT JOIN scores s ON r.id = s.result_id
+        WHERE e.project_id = $1 AND r.id = ANY($2)
+        ORDER BY r.created_at ASC, r.index_in_batch ASC NULLS FIRST"
+    ))
+    .bind(project_id)
+    .bind(result_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(results)
+}
+
+/// Records the scores a user gave to an evaluation result. Fails with no score recorded if the
+/// result already has a score of one of the names, human scores can't replace other scores.
+pub async fn insert_human_evaluation_scores(
+    pool: &PgPool,
+    result_id: Uuid,
+    user_id: Uuid,
+    scores: &HashMap<String, ScoreValue>,
+) -> Result<bool> {
+    let mut names = Vec::with_capacity(scores.len());
+    let mut values = Vec::with_capacity(scores.len());
+    let mut types = Vec::with_capacity(scores.len());
+    let mut labels = Vec::with_capacity(scores.len());
+    for (name, value) in scores {
+        names.push(name.clone());
+        values.push(value.as_f64());
+        types.push(value.score_type().as_str());
+        labels.push(value.label());
+    }
+
+    let mut tx = pool.begin().await?;
+    let inserted = sqlx::query(
+        "INSERT INTO evaluation_scores (result_id, name, score, score_type, label, source, user_id)
+        SELECT
+            $1,
+            name,
+            score,
+            score_type::evaluation_score_type,
+            label,
+            'HUMAN',
+            $2
+        FROM UNNEST ($3::text[], $4::float8[], $5::text[], $6::text[])
+        AS tmp_table(name, score, score_type, label)
+        ON CONFLICT (result_id, name) DO NOTHING",
+    )
+    .bind(result_id)
+    .bind(user_id)
+    .bind(&names)
+    .bind(&values)
+    .bind(&types)
+    .bind(&labels)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if inserted as usize != scores.len() {
+        tx.rollback().await?;
+        return Ok(false);
+    }
+    tx.commit().await?;
+
+    Ok(true)
+}
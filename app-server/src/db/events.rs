@@ -6,6 +6,7 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{PgPool, Postgres, QueryBuilder};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
@@ -28,7 +29,7 @@ pub enum EventSource {
     CODE,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EventObservation {
     pub id: Uuid,
@@ -273,6 +274,42 @@ pub async fn count_all_events_by_template_id_in_project(
     Ok(count.count)
 }
 
+/// Events of the project with a timestamp in [start_time, end_time)
+pub async fn get_events_in_period(
+    pool: &PgPool,
+    project_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<EventWithTemplateName>> {
+    let events = sqlx::query_as::<_, EventWithTemplateName>(
+        "SELECT
+            e.id,
+            e.created_at,
+            e.span_id,
+            e.timestamp,
+            e.template_id,
+            event_templates.name as template_name,
+            event_templates.event_type as template_event_type,
+            e.source,
+            e.metadata,
+            e.value,
+            e.inputs
+        FROM events e
+        JOIN event_templates ON e.template_id = event_templates.id
+        WHERE event_templates.project_id = $1
+            AND e.timestamp >= $2
+            AND e.timestamp < $3
+        ORDER BY e.timestamp",
+    )
+    .bind(project_id)
+    .bind(start_time)
+    .bind(end_time)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
+
 pub async fn get_events_for_session(
     pool: &PgPool,
     session_id: &String,
@@ -0,0 +1,215 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "workspace_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceRole {
+    Member,
+    Owner,
+    /// Can see metrics and latencies, but not raw prompt contents by default
+    Viewer,
+}
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "sensitive_field")]
+pub enum SensitiveField {
+    SPAN_INPUT,
+    SPAN_OUTPUT,
+    USER_ID,
+}
+
+/// Overrides the default visibility of a sensitive field for a role in a project
+#[derive(Serialize, FromRow, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldVisibilityRule {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub role: WorkspaceRole,
+    pub field: SensitiveField,
+    pub visible: bool,
+}
+
+/// Sensitive fields that read queries return to a user. Hidden fields are returned as null
+/// and are not matched by text search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldVisibility {
+    pub span_input: bool,
+    pub span_output: bool,
+    pub user_id: bool,
+}
+
+impl FieldVisibility {
+    /// For project API keys and members with full access
+    pub const ALL: FieldVisibility = FieldVisibility {
+        span_input: true,
+        span_output: true,
+        user_id: true,
+    };
+
+    fn default_for_role(role: WorkspaceRole) -> Self {
+        match role {
+            WorkspaceRole::Owner | WorkspaceRole::Member => Self::ALL,
+            WorkspaceRole::Viewer => FieldVisibility {
+                span_input: false,
+                span_output: false,
+                user_id: true,
+            },
+        }
+    }
+
+    /// Visibility of the role's fields, with the project's rules applied over the defaults
+    pub fn for_role(role: WorkspaceRole, rules: &[FieldVisibilityRule]) -> Self {
+        let mut visibility = Self::default_for_role(role);
+        for rule in rules.iter().filter(|rule| rule.role == role) {
+            match rule.field {
+                SensitiveField::SPAN_INPUT => visibility.span_input = rule.visible,
+                SensitiveField::SPAN_OUTPUT => visibility.span_output = rule.visible,
+                SensitiveField::USER_ID => visibility.user_id = rule.visible,
+            }
+        }
+        visibility
+    }
+}
+
+pub async fn get_field_visibility_rules(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<FieldVisibilityRule>> {
+    let rules = sqlx::query_as::<_, FieldVisibilityRule>(
+        "SELECT id, created_at, project_id, role, field, visible
+        FROM field_visibility_rules
+        WHERE project_id = $1
+        ORDER BY role, field",
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rules)
+}
+
+pub async fn set_field_visibility_rule(
+    pool: &PgPool,
+    project_id: &Uuid,
+    role: WorkspaceRole,
+    field: SensitiveField,
+    visible: bool,
+) -> Result<FieldVisibilityRule> {
+    let rule = sqlx::query_as::<_, FieldVisibilityRule>(
+        "INSERT INTO field_visibility_rules (project_id, role, field, visible)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (project_id, role, field) DO UPDATE SET visible = EXCLUDED.visible
+        RETURNING id, created_at, project_id, role, field, visible",
+    )
+    .bind(project_id)
+    .bind(role)
+    .bind(field)
+    .bind(visible)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(rule)
+}
+
+/// Restores the default visibility of the field for the role. Returns false if there was no rule.
+pub async fn delete_field_visibility_rule(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<bool> {
+    let res = sqlx::query("DELETE FROM field_visibility_rules WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Role of the user in the workspace of the project, None if the user is not a member
+pub async fn get_project_member_role(
+    pool: &PgPool,
+    user_id: &Uuid,
+    project_id: &Uuid,
+) -> Result<Option<WorkspaceRole>> {
+    let role = sqlx::query_scalar::<_, WorkspaceRole>(
+        "SELECT members_of_workspaces.member_role
+        FROM members_of_workspaces
+        JOIN projects ON projects.workspace_id = members_of_workspaces.workspace_id
+        WHERE members_of_workspaces.user_id = $1 AND projects.id = $2",
+    )
+    .bind(user_id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(role)
+}
+
+/// Fields of the project that the user is allowed to see. Users that are not members of the
+/// project's workspace see nothing sensitive.
+pub async fn get_field_visibility(
+    pool: &PgPool,
+    user_id: &Uuid,
+    project_id: &Uuid,
+) -> Result<FieldVisibility> {
+    let Some(role) = get_project_member_role(pool, user_id, project_id).await? else {
+        return Ok(FieldVisibility {
+            span_input: false,
+            span_output: false,
+            user_id: false,
+        });
+    };
+    let rules = get_field_visibility_rules(pool, project_id).await?;
+
+    Ok(FieldVisibility::for_role(role, &rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_visibility_for_role() {
+        let rule = |role, field, visible| FieldVisibilityRule {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            project_id: Uuid::nil(),
+            role,
+            field,
+            visible,
+        };
+        let rules = vec![
+            rule(WorkspaceRole::Viewer, SensitiveField::SPAN_OUTPUT, true),
+            rule(WorkspaceRole::Viewer, SensitiveField::USER_ID, false),
+            rule(WorkspaceRole::Member, SensitiveField::SPAN_INPUT, false),
+        ];
+
+        assert_eq!(
+            FieldVisibility::for_role(WorkspaceRole::Viewer, &[]),
+            FieldVisibility {
+                span_input: false,
+                span_output: false,
+                user_id: true,
+            }
+        );
+        assert_eq!(
+            FieldVisibility::for_role(WorkspaceRole::Viewer, &rules),
+            FieldVisibility {
+                span_input: false,
+                span_output: true,
+                user_id: false,
+            }
+        );
+        assert!(!FieldVisibility::for_role(WorkspaceRole::Member, &rules).span_input);
+        assert_eq!(
+            FieldVisibility::for_role(WorkspaceRole::Owner, &rules),
+            FieldVisibility::ALL
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct ScoreAverage {
+    /// Evaluation group id for evaluation scores, None for online scores
+    pub group_id: Option<String>,
+    pub name: String,
+    pub average: f64,
+}
+
+/// Average of every numeric and boolean score of the evaluations created in the window, per
+/// evaluation group
+pub async fn get_evaluation_score_averages(
+    pool: &PgPool,
+    project_id: &Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<ScoreAverage>> {
+    let averages = sqlx::query_as::<_, ScoreAverage>(
+        "SELECT
+            evaluations.group_id,
+            evaluation_scores.name,
+            avg(evaluation_scores.score) as average
+        FROM evaluation_scores
+        JOIN evaluation_results ON evaluation_results.id = evaluation_scores.result_id
+        JOIN evaluations ON evaluations.id = evaluation_results.evaluation_id
+        WHERE evaluations.project_id = $1
+            AND evaluations.created_at >= $2
+            AND evaluations.created_at < $3
+            AND evaluation_scores.score IS NOT NULL
+        GROUP BY evaluations.group_id, evaluation_scores.name",
+    )
+    .bind(project_id)
+    .bind(start_time)
+    .bind(end_time)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(averages)
+}
+
+/// Average of every label class set by online evaluators in the window
+pub async fn get_online_score_averages(
+    pool: &PgPool,
+    project_id: &Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<ScoreAverage>> {
+    let averages = sqlx::query_as::<_, ScoreAverage>(
+        "SELECT
+            NULL::text as group_id,
+            label_classes.name,
+            avg(labels.value) as average
+        FROM labels
+        JOIN label_classes ON label_classes.id = labels.class_id
+        WHERE label_classes.project_id = $1
+            AND labels.label_source = 'AUTO'
+            AND labels.created_at >= $2
+            AND labels.created_at < $3
+        GROUP BY label_classes.name",
+    )
+    .bind(project_id)
+    .bind(start_time)
+    .bind(end_time)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(averages)
+}
+
+pub async fn count_evaluations(
+    pool: &PgPool,
+    project_id: &Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT count(*) FROM evaluations
+        WHERE project_id = $1 AND created_at >= $2 AND created_at < $3",
+    )
+    .bind(project_id)
+    .bind(start_time)
+    .bind(end_time)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
@@ -0,0 +1,302 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use super::evaluations::SCORE_VALUE_JSON;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "issue_tracker_provider")]
+pub enum IssueTrackerProvider {
+    JIRA,
+    LINEAR,
+}
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "linked_issue_resource_type")]
+#[allow(non_camel_case_types)]
+pub enum LinkedIssueResourceType {
+    TRACE,
+    EVALUATION_RESULT,
+}
+
+#[derive(FromRow)]
+pub struct IssueTrackerIntegration {
+    pub provider: IssueTrackerProvider,
+    pub config: Value,
+    pub token_nonce: String,
+    pub token_value: String,
+}
+
+/// Integration without its token, as shown to users
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueTrackerIntegrationInfo {
+    pub created_at: DateTime<Utc>,
+    pub provider: IssueTrackerProvider,
+    pub config: Value,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedIssue {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub resource_type: LinkedIssueResourceType,
+    pub resource_id: Uuid,
+    pub provider: IssueTrackerProvider,
+    pub external_id: String,
+    pub key: String,
+    pub url: String,
+    pub title: String,
+    /// Status name in the issue tracker, e.g. "In Progress"
+    pub status: Option<String>,
+    pub status_updated_at: Option<DateTime<Utc>>,
+}
+
+pub struct NewLinkedIssue {
+    pub resource_type: LinkedIssueResourceType,
+    pub resource_id: Uuid,
+    pub provider: IssueTrackerProvider,
+    pub external_id: String,
+    pub key: String,
+    pub url: String,
+    pub title: String,
+}
+
+#[derive(FromRow)]
+pub struct TraceSummary {
+    pub id: Uuid,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub success: bool,
+    pub total_token_count: i64,
+    pub cost: f64,
+}
+
+#[derive(FromRow)]
+pub struct EvaluationResultSummary {
+    pub id: Uuid,
+    pub evaluation_id: Uuid,
+    pub evaluation_name: String,
+    pub data: Value,
+    pub target: Value,
+    pub executor_output: Option<Value>,
+    pub scores: Option<Value>,
+    pub trace_id: Uuid,
+}
+
+pub async fn set_integration(
+    pool: &PgPool,
+    project_id: &Uuid,
+    provider: IssueTrackerProvider,
+    config: &Value,
+    token_nonce: &str,
+    token_value: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO issue_tracker_integrations
+            (project_id, provider, config, token_nonce, token_value)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (project_id, provider) DO UPDATE SET
+            config = EXCLUDED.config,
+            token_nonce = EXCLUDED.token_nonce,
+            token_value = EXCLUDED.token_value",
+    )
+    .bind(project_id)
+    .bind(provider)
+    .bind(config)
+    .bind(token_nonce)
+    .bind(token_value)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_integration(
+    pool: &PgPool,
+    project_id: &Uuid,
+    provider: IssueTrackerProvider,
+) -> Result<Option<IssueTrackerIntegration>> {
+    let integration = sqlx::query_as::<_, IssueTrackerIntegration>(
+        "SELECT provider, config, token_nonce, token_value
+        FROM issue_tracker_integrations
+        WHERE project_id = $1 AND provider = $2",
+    )
+    .bind(project_id)
+    .bind(provider)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(integration)
+}
+
+pub async fn get_integrations(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<IssueTrackerIntegrationInfo>> {
+    let integrations = sqlx::query_as::<_, IssueTrackerIntegrationInfo>(
+        "SELECT created_at, provider, config
+        FROM issue_tracker_integrations
+        WHERE project_id = $1
+        ORDER BY created_at",
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(integrations)
+}
+
+pub async fn delete_integration(
+    pool: &PgPool,
+    project_id: &Uuid,
+    provider: IssueTrackerProvider,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE FROM issue_tracker_integrations WHERE project_id = $1 AND provider = $2",
+    )
+    .bind(project_id)
+    .bind(provider)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_trace_summary(
+    pool: &PgPool,
+    project_id: &Uuid,
+    trace_id: &Uuid,
+) -> Result<Option<TraceSummary>> {
+    let trace = sqlx::query_as::<_, TraceSummary>(
+        "SELECT id, start_time, end_time, success, total_token_count, cost
+        FROM traces
+        WHERE id = $2 AND project_id = $1",
+    )
+    .bind(project_id)
+    .bind(trace_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(trace)
+}
+
+pub async fn get_evaluation_result_summary(
+    pool: &PgPool,
+    project_id: &Uuid,
+    result_id: &Uuid,
+) -> Result<Option<EvaluationResultSummary>> {
+    let result = sqlx::query_as::<_, EvaluationResultSummary>(&format!(
+        "SELECT
+            r.id,
+            r.evaluation_id,
+            e.name as evaluation_name,
+            r.data,
+            r.target,
+            r.executor_output,
+            (SELECT jsonb_object_agg(name, {SCORE_VALUE_JSON})
+                FROM evaluation_scores WHERE result_id = r.id) as scores,
+            r.trace_id
+        FROM evaluation_results r
+        JOIN evaluations e ON e.id = r.evaluation_id
+        WHERE r.id = $2 AND e.project_id = $1"
+    ))
+    .bind(project_id)
+    .bind(result_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn create_linked_issue(
+    pool: &PgPool,
+    project_id: &Uuid,
+    issue: &NewLinkedIssue,
+) -> Result<LinkedIssue> {
+    let issue = sqlx::query_as::<_, LinkedIssue>(
+        "INSERT INTO linked_issues
+            (project_id, resource_type, resource_id, provider, external_id, key, url, title)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING
+            id,
+            created_at,
+            resource_type,
+            resource_id,
+            provider,
+            external_id,
+            key,
+            url,
+            title,
+            status,
+            status_updated_at",
+    )
+    .bind(project_id)
+    .bind(issue.resource_type)
+    .bind(issue.resource_id)
+    .bind(issue.provider)
+    .bind(&issue.external_id)
+    .bind(&issue.key)
+    .bind(&issue.url)
+    .bind(&issue.title)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(issue)
+}
+
+pub async fn get_linked_issues(
+    pool: &PgPool,
+    project_id: &Uuid,
+    resource_type: LinkedIssueResourceType,
+    resource_id: &Uuid,
+) -> Result<Vec<LinkedIssue>> {
+    let issues = sqlx::query_as::<_, LinkedIssue>(
+        "SELECT
+            id,
+            created_at,
+            resource_type,
+            resource_id,
+            provider,
+            external_id,
+            key,
+            url,
+            title,
+            status,
+            status_updated_at
+        FROM linked_issues
+        WHERE project_id = $1 AND resource_type = $2 AND resource_id = $3
+        ORDER BY created_at",
+    )
+    .bind(project_id)
+    .bind(resource_type)
+    .bind(resource_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(issues)
+}
+
+pub async fn set_linked_issue_status(pool: &PgPool, id: &Uuid, status: &str) -> Result<()> {
+    sqlx::query("UPDATE linked_issues SET status = $2, status_updated_at = now() WHERE id = $1")
+        .bind(id)
+        .bind(status)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_linked_issue(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM linked_issues WHERE id = $2 AND project_id = $1")
+        .bind(project_id)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
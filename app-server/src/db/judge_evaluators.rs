@@ -0,0 +1,151 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::evaluations::utils::ScoreType;
+
+/// LLM judge that scores evaluation datapoints on the server, see `evaluations::judge`
+#[derive(Serialize, FromRow, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct JudgeEvaluator {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    /// Name of the scores the judge produces
+    pub name: String,
+    /// In the format of "provider:model_name"
+    pub model: String,
+    /// Handlebars template over the datapoint's `input`, `output` and `target`
+    pub prompt: String,
+    pub score_type: ScoreType,
+    /// Allowed labels of categorical scores, empty for the others
+    pub labels: Vec<String>,
+}
+
+pub struct NewJudgeEvaluator {
+    pub name: String,
+    pub model: String,
+    pub prompt: String,
+    pub score_type: ScoreType,
+    pub labels: Vec<String>,
+}
+
+pub async fn get_judge_evaluators(pool: &PgPool, project_id: &Uuid) -> Result<Vec<JudgeEvaluator>> {
+    let judges = sqlx::query_as::<_, JudgeEvaluator>(
+        "SELECT id, created_at, project_id, name, model, prompt, score_type, labels
+        FROM judge_evaluators
+        WHERE project_id = $1
+        ORDER BY name",
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(judges)
+}
+
+/// Judges of the project with the given names, names without a judge are left out
+pub async fn get_judge_evaluators_by_names(
+    pool: &PgPool,
+    project_id: &Uuid,
+    names: &[String],
+) -> Result<Vec<JudgeEvaluator>> {
+    let judges = sqlx::query_as::<_, JudgeEvaluator>(
+        "SELECT id, created_at, project_id, name, model, prompt, score_type, labels
+        FROM judge_evaluators
+        WHERE project_id = $1 AND name = ANY($2)
+        ORDER BY name",
+    )
+    .bind(project_id)
+    .bind(names)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(judges)
+}
+
+/// Returns None if the project already has a judge with the name
+pub async fn create_judge_evaluator(
+    pool: &PgPool,
+    project_id: &Uuid,
+    judge: &NewJudgeEvaluator,
+) -> Result<Option<JudgeEvaluator>> {
+    let judge = sqlx::query_as::<_, JudgeEvaluator>(
+        "INSERT INTO judge_evaluators (project_id, name, model, prompt, score_type, labels)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (project_id, name) DO NOTHING
+        RETURNING id, created_at, project_id, name, model, prompt, score_type, labels",
+    )
+    .bind(project_id)
+    .bind(&judge.name)
+    .bind(&judge.model)
+    .bind(&judge.prompt)
+    .bind(judge.score_type)
+    .bind(&judge.labels)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(judge)
+}
+
+pub async fn delete_judge_evaluator(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<JudgeEvaluator>> {
+    let judge = sqlx::query_as::<_, JudgeEvaluator>(
+        "DELETE FROM judge_evaluators
+        WHERE id = $1 AND project_id = $2
+        RETURNING id, created_at, project_id, name, model, prompt, score_type, labels",
+    )
+    .bind(id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(judge)
+}
+
+/// Creates the judge, or replaces the settings of the project's judge with the name
+pub async fn upsert_judge_evaluator(
+    conn: &mut PgConnection,
+    project_id: &Uuid,
+    judge: &NewJudgeEvaluator,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO judge_evaluators (project_id, name, model, prompt, score_type, labels)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (project_id, name)
+        DO UPDATE SET
+            model = EXCLUDED.model,
+            prompt = EXCLUDED.prompt,
+            score_type = EXCLUDED.score_type,
+            labels = EXCLUDED.labels",
+    )
+    .bind(project_id)
+    .bind(&judge.name)
+    .bind(&judge.model)
+    .bind(&judge.prompt)
+    .bind(judge.score_type)
+    .bind(&judge.labels)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_judge_evaluator_by_name(
+    conn: &mut PgConnection,
+    project_id: &Uuid,
+    name: &str,
+) -> Result<()> {
+    sqlx::query("DELETE FROM judge_evaluators WHERE project_id = $1 AND name = $2")
+        .bind(project_id)
+        .bind(name)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
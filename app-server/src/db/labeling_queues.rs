@@ -1,6 +1,8 @@
 use anyhow::Result;
-use chrono::Utc;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
 use uuid::Uuid;
 
 use crate::evaluations::utils::LabelingQueueEntry;
@@ -63,3 +65,257 @@ pub async fn push_to_labeling_queue(
 
     Ok(())
 }
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelingQueueSummary {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub name: String,
+    pub project_id: Uuid,
+    pub item_count: i64,
+    /// Items that an annotator is working on
+    pub locked_count: i64,
+    pub completed_count: i64,
+}
+
+/// Span to annotate. Items pushed from evaluations have the id of the evaluation result in
+/// `action.resultId`.
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelingQueueItem {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub queue_id: Uuid,
+    pub span_id: Uuid,
+    pub action: Value,
+    pub assigned_to: Option<Uuid>,
+    /// The item can be claimed by another annotator after this time
+    pub locked_until: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub completed_by: Option<Uuid>,
+}
+
+const LABELING_QUEUE_ITEM_COLUMNS: &str = "id, created_at, queue_id, span_id, action,
+    assigned_to, locked_until, completed_at, completed_by";
+
+const LABELING_QUEUE_SUMMARY_QUERY: &str = "SELECT
+        labeling_queues.id,
+        labeling_queues.created_at,
+        labeling_queues.name,
+        labeling_queues.project_id,
+        COUNT(labeling_queue_items.id) AS item_count,
+        COUNT(labeling_queue_items.id) FILTER (
+            WHERE labeling_queue_items.completed_at IS NULL
+                AND labeling_queue_items.locked_until > now()
+        ) AS locked_count,
+        COUNT(labeling_queue_items.completed_at) AS completed_count
+    FROM labeling_queues
+    LEFT JOIN labeling_queue_items ON labeling_queue_items.queue_id = labeling_queues.id";
+
+pub async fn get_labeling_queues(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<LabelingQueueSummary>> {
+    let queues = sqlx::query_as::<_, LabelingQueueSummary>(&format!(
+        "{LABELING_QUEUE_SUMMARY_QUERY}
+        WHERE labeling_queues.project_id = $1
+        GROUP BY labeling_queues.id
+        ORDER BY labeling_queues.created_at DESC"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(queues)
+}
+
+pub async fn get_labeling_queue(
+    pool: &PgPool,
+    project_id: &Uuid,
+    queue_id: &Uuid,
+) -> Result<Option<LabelingQueueSummary>> {
+    let queue = sqlx::query_as::<_, LabelingQueueSummary>(&format!(
+        "{LABELING_QUEUE_SUMMARY_QUERY}
+        WHERE labeling_queues.project_id = $1 AND labeling_queues.id = $2
+        GROUP BY labeling_queues.id"
+    ))
+    .bind(project_id)
+    .bind(queue_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(queue)
+}
+
+pub async fn create_labeling_queue(
+    pool: &PgPool,
+    project_id: &Uuid,
+    name: &str,
+) -> Result<LabelingQueueSummary> {
+    let queue = sqlx::query_as::<_, LabelingQueueSummary>(
+        "INSERT INTO labeling_queues (name, project_id)
+        VALUES ($1, $2)
+        RETURNING
+            id,
+            created_at,
+            name,
+            project_id,
+            0::int8 AS item_count,
+            0::int8 AS locked_count,
+            0::int8 AS completed_count",
+    )
+    .bind(name)
+    .bind(project_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(queue)
+}
+
+pub async fn delete_labeling_queue(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let res = sqlx::query("DELETE FROM labeling_queues WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Ids of the spans that are in the project, in no particular order
+pub async fn get_project_span_ids(
+    pool: &PgPool,
+    project_id: &Uuid,
+    span_ids: &[Uuid],
+) -> Result<Vec<Uuid>> {
+    let span_ids = sqlx::query_scalar::<_, Uuid>(
+        "SELECT DISTINCT span_id FROM spans WHERE project_id = $1 AND span_id = ANY($2)",
+    )
+    .bind(project_id)
+    .bind(span_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(span_ids)
+}
+
+/// Top span of the trace of each evaluation result of the project, which is what the
+/// annotators review
+pub async fn get_evaluation_result_spans(
+    pool: &PgPool,
+    project_id: &Uuid,
+    result_ids: &[Uuid],
+) -> Result<Vec<(Uuid, Uuid)>> {
+    let spans = sqlx::query_as::<_, (Uuid, Uuid)>(
+        "SELECT DISTINCT ON (evaluation_results.id) evaluation_results.id, spans.span_id
+        FROM evaluation_results
+        JOIN evaluations ON evaluations.id = evaluation_results.evaluation_id
+        JOIN spans ON spans.trace_id = evaluation_results.trace_id
+            AND spans.parent_span_id IS NULL
+        WHERE evaluations.project_id = $1 AND evaluation_results.id = ANY($2)
+        ORDER BY evaluation_results.id, spans.start_time",
+    )
+    .bind(project_id)
+    .bind(result_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(spans)
+}
+
+/// Locks the next item of the queue for the user, the one they already hold if any. Items
+/// whose lock expired can be claimed by anyone.
+pub async fn claim_next_item(
+    pool: &PgPool,
+    queue_id: &Uuid,
+    user_id: &Uuid,
+    lock_secs: f64,
+) -> Result<Option<LabelingQueueItem>> {
+    let item = sqlx::query_as::<_, LabelingQueueItem>(&format!(
+        "UPDATE labeling_queue_items
+        SET assigned_to = $2, locked_until = now() + make_interval(secs => $3)
+        WHERE id = (
+            SELECT id FROM labeling_queue_items
+            WHERE queue_id = $1
+                AND completed_at IS NULL
+                AND (locked_until IS NULL OR locked_until < now() OR assigned_to = $2)
+            ORDER BY assigned_to IS NOT DISTINCT FROM $2 DESC, created_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {LABELING_QUEUE_ITEM_COLUMNS}"
+    ))
+    .bind(queue_id)
+    .bind(user_id)
+    .bind(lock_secs)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(item)
+}
+
+pub async fn get_labeling_queue_item(
+    pool: &PgPool,
+    queue_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<LabelingQueueItem>> {
+    let item = sqlx::query_as::<_, LabelingQueueItem>(&format!(
+        "SELECT {LABELING_QUEUE_ITEM_COLUMNS}
+        FROM labeling_queue_items
+        WHERE id = $1 AND queue_id = $2"
+    ))
+    .bind(id)
+    .bind(queue_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(item)
+}
+
+/// Unlocks an item of the user that is not completed, so that others can claim it
+pub async fn release_item(
+    pool: &PgPool,
+    queue_id: &Uuid,
+    id: &Uuid,
+    user_id: &Uuid,
+) -> Result<bool> {
+    let res = sqlx::query(
+        "UPDATE labeling_queue_items
+        SET assigned_to = NULL, locked_until = NULL
+        WHERE id = $1 AND queue_id = $2 AND assigned_to = $3 AND completed_at IS NULL",
+    )
+    .bind(id)
+    .bind(queue_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Completes an item that is not locked by another user. None if it is, or if it was already
+/// completed.
+pub async fn complete_item(
+    pool: &PgPool,
+    queue_id: &Uuid,
+    id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Option<LabelingQueueItem>> {
+    let item = sqlx::query_as::<_, LabelingQueueItem>(&format!(
+        "UPDATE labeling_queue_items
+        SET completed_at = now(), completed_by = $3, locked_until = NULL
+        WHERE id = $1
+            AND queue_id = $2
+            AND completed_at IS NULL
+            AND (locked_until IS NULL OR locked_until < now() OR assigned_to = $3)
+        RETURNING {LABELING_QUEUE_ITEM_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(queue_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(item)
+}
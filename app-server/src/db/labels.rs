@@ -122,7 +122,7 @@ pub async fn create_label_class(
     name: String,
     project_id: Uuid,
     label_type: &LabelType,
-    value_map: Vec<Value>,
+    value_map: Value,
     description: Option<String>,
     evaluator_runnable_graph: Option<Value>,
 ) -> Result<LabelClass> {
@@ -152,7 +152,7 @@ pub async fn create_label_class(
     .bind(name)
     .bind(project_id)
     .bind(label_type)
-    .bind(serde_json::to_value(value_map).unwrap())
+    .bind(value_map)
     .bind(description)
     .bind(evaluator_runnable_graph)
     .fetch_one(pool)
@@ -309,6 +309,57 @@ pub async fn get_span_labels(pool: &PgPool, span_id: Uuid) -> Result<Vec<SpanLab
     Ok(span_labels)
 }
 
+/// Scores of a label class across all spans of a trace
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceScoreSummary {
+    pub class_id: Uuid,
+    pub class_name: String,
+    pub label_type: LabelType,
+    pub value_map: Value, // Vec<Value>
+    pub count: i64,
+    pub average_value: f64,
+    pub min_value: f64,
+    pub max_value: f64,
+    pub auto_count: i64,
+    pub manual_count: i64,
+}
+
+pub async fn get_trace_score_summaries(
+    pool: &PgPool,
+    trace_id: Uuid,
+    project_id: Uuid,
+) -> Result<Vec<TraceScoreSummary>> {
+    let summaries = sqlx::query_as::<_, TraceScoreSummary>(
+        "SELECT
+            label_classes.id AS class_id,
+            label_classes.name AS class_name,
+            label_classes.label_type,
+            label_classes.value_map,
+            count(*) AS count,
+            avg(labels.value) AS average_value,
+            min(labels.value) AS min_value,
+            max(labels.value) AS max_value,
+            count(*) FILTER (WHERE labels.label_source = 'AUTO') AS auto_count,
+            count(*) FILTER (WHERE labels.label_source = 'MANUAL') AS manual_count
+        FROM labels
+        JOIN spans ON spans.span_id = labels.span_id
+        JOIN label_classes ON labels.class_id = label_classes.id
+        WHERE spans.trace_id = $1
+            AND spans.project_id = $2
+            AND label_classes.project_id = $2
+            AND labels.value IS NOT NULL
+        GROUP BY label_classes.id
+        ORDER BY label_classes.name ASC",
+    )
+    .bind(trace_id)
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(summaries)
+}
+
 #[derive(FromRow)]
 pub struct SpanLabelInstance {
     pub input: Option<Value>,
@@ -410,3 +461,49 @@ pub async fn get_registered_label_classes_for_path(
 
     Ok(registered_paths)
 }
+
+/// Label with the name of its class, for exports of the project's scores
+#[derive(FromRow)]
+pub struct ExportedLabel {
+    pub id: Uuid,
+    pub span_id: Uuid,
+    pub class_name: String,
+    pub value: f64,
+    pub label_source: LabelSource,
+    pub user_id: Option<Uuid>,
+    pub reasoning: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Labels of the project last updated in [start_time, end_time)
+pub async fn get_labels_updated_in_period(
+    pool: &PgPool,
+    project_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<ExportedLabel>> {
+    let labels = sqlx::query_as::<_, ExportedLabel>(
+        "SELECT
+            labels.id,
+            labels.span_id,
+            label_classes.name as class_name,
+            labels.value,
+            labels.label_source,
+            labels.user_id,
+            labels.reasoning,
+            labels.updated_at
+        FROM labels
+        JOIN label_classes ON labels.class_id = label_classes.id
+        WHERE label_classes.project_id = $1
+            AND labels.updated_at >= $2
+            AND labels.updated_at < $3
+        ORDER BY labels.updated_at",
+    )
+    .bind(project_id)
+    .bind(start_time)
+    .bind(end_time)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(labels)
+}
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// Hourly export of a project's data to the user's S3 bucket, see `lake_export`
+#[derive(FromRow, Clone)]
+pub struct LakeExport {
+    pub project_id: Uuid,
+    pub bucket: String,
+    pub prefix: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_nonce: String,
+    pub secret_value: String,
+    /// End of the last exported hour
+    pub exported_until: DateTime<Utc>,
+}
+
+/// Export without its secret, as shown to users
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LakeExportInfo {
+    pub created_at: DateTime<Utc>,
+    pub bucket: String,
+    pub prefix: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub enabled: bool,
+    pub exported_until: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+pub struct NewLakeExport {
+    pub bucket: String,
+    pub prefix: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_nonce: String,
+    pub secret_value: String,
+    pub enabled: bool,
+    pub exported_until: DateTime<Utc>,
+}
+
+const LAKE_EXPORT_COLUMNS: &str = "project_id, bucket, prefix, region, access_key_id, secret_nonce,
+    secret_value, exported_until";
+
+/// Creates or replaces the export of the project. The progress of an existing export is kept, so
+/// that changing the credentials doesn't export the same hours again.
+pub async fn set_lake_export(
+    pool: &PgPool,
+    project_id: &Uuid,
+    export: &NewLakeExport,
+) -> Result<LakeExportInfo> {
+    let info = sqlx::query_as::<_, LakeExportInfo>(
+        "INSERT INTO lake_exports (project_id, bucket, prefix, region, access_key_id,
+            secret_nonce, secret_value, enabled, exported_until)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (project_id) DO UPDATE SET
+            bucket = EXCLUDED.bucket,
+            prefix = EXCLUDED.prefix,
+            region = EXCLUDED.region,
+            access_key_id = EXCLUDED.access_key_id,
+            secret_nonce = EXCLUDED.secret_nonce,
+            secret_value = EXCLUDED.secret_value,
+            enabled = EXCLUDED.enabled,
+            last_error = NULL
+        RETURNING created_at, bucket, prefix, region, access_key_id, enabled, exported_until,
+            last_run_at, last_error",
+    )
+    .bind(project_id)
+    .bind(&export.bucket)
+    .bind(&export.prefix)
+    .bind(&export.region)
+    .bind(&export.access_key_id)
+    .bind(&export.secret_nonce)
+    .bind(&export.secret_value)
+    .bind(export.enabled)
+    .bind(export.exported_until)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(info)
+}
+
+pub async fn get_lake_export(pool: &PgPool, project_id: &Uuid) -> Result<Option<LakeExportInfo>> {
+    let info = sqlx::query_as::<_, LakeExportInfo>(
+        "SELECT created_at, bucket, prefix, region, access_key_id, enabled, exported_until,
+            last_run_at, last_error
+        FROM lake_exports
+        WHERE project_id = $1",
+    )
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(info)
+}
+
+pub async fn delete_lake_export(pool: &PgPool, project_id: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM lake_exports WHERE project_id = $1")
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Enabled exports with an hour that ended more than `delay_seconds` ago and that no instance
+/// started within `lease_seconds`. Returned exports count as started, so that each is run by
+/// one instance only.
+pub async fn claim_due_lake_exports(
+    pool: &PgPool,
+    delay_seconds: i64,
+    lease_seconds: i64,
+) -> Result<Vec<LakeExport>> {
+    let exports = sqlx::query_as::<_, LakeExport>(&format!(
+        "UPDATE lake_exports SET last_run_at = now()
+        WHERE enabled
+            AND exported_until + interval '1 hour' + make_interval(secs => $1) <= now()
+            AND (last_run_at IS NULL OR last_run_at < now() - make_interval(secs => $2))
+        RETURNING {LAKE_EXPORT_COLUMNS}"
+    ))
+    .bind(delay_seconds as f64)
+    .bind(lease_seconds as f64)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(exports)
+}
+
+/// Records an exported hour and clears the error of the previous run
+pub async fn update_exported_until(
+    pool: &PgPool,
+    project_id: &Uuid,
+    exported_until: DateTime<Utc>,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE lake_exports SET exported_until = $2, last_error = NULL WHERE project_id = $1",
+    )
+    .bind(project_id)
+    .bind(exported_until)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn record_lake_export_error(pool: &PgPool, project_id: &Uuid, error: &str) -> Result<()> {
+    sqlx::query("UPDATE lake_exports SET last_error = $2 WHERE project_id = $1")
+        .bind(project_id)
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
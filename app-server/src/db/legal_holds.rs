@@ -0,0 +1,146 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// While a project has an active legal hold, its data cannot be deleted
+#[derive(Serialize, FromRow, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LegalHold {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub reason: String,
+    pub placed_by: Uuid,
+    pub released_at: Option<DateTime<Utc>>,
+    pub released_by: Option<Uuid>,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LegalHoldAuditLogEntry {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub action: String,
+    pub details: Value,
+}
+
+const LEGAL_HOLD_COLUMNS: &str =
+    "id, created_at, project_id, reason, placed_by, released_at, released_by";
+
+pub async fn get_active_legal_hold(pool: &PgPool, project_id: &Uuid) -> Result<Option<LegalHold>> {
+    let hold = sqlx::query_as::<_, LegalHold>(&format!(
+        "SELECT {LEGAL_HOLD_COLUMNS}
+        FROM legal_holds
+        WHERE project_id = $1 AND released_at IS NULL"
+    ))
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(hold)
+}
+
+/// Active and released holds of the project, newest first
+pub async fn get_legal_holds(pool: &PgPool, project_id: &Uuid) -> Result<Vec<LegalHold>> {
+    let holds = sqlx::query_as::<_, LegalHold>(&format!(
+        "SELECT {LEGAL_HOLD_COLUMNS}
+        FROM legal_holds
+        WHERE project_id = $1
+        ORDER BY created_at DESC"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(holds)
+}
+
+/// Returns None if the project is already under an active hold
+pub async fn place_legal_hold(
+    pool: &PgPool,
+    project_id: &Uuid,
+    user_id: &Uuid,
+    reason: &str,
+) -> Result<Option<LegalHold>> {
+    let hold = sqlx::query_as::<_, LegalHold>(&format!(
+        "INSERT INTO legal_holds (project_id, placed_by, reason)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (project_id) WHERE released_at IS NULL DO NOTHING
+        RETURNING {LEGAL_HOLD_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(user_id)
+    .bind(reason)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(hold)
+}
+
+/// Returns None if the project has no active hold
+pub async fn release_legal_hold(
+    pool: &PgPool,
+    project_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Option<LegalHold>> {
+    let hold = sqlx::query_as::<_, LegalHold>(&format!(
+        "UPDATE legal_holds
+        SET released_at = now(), released_by = $2
+        WHERE project_id = $1 AND released_at IS NULL
+        RETURNING {LEGAL_HOLD_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(hold)
+}
+
+pub async fn write_audit_log(
+    pool: &PgPool,
+    project_id: &Uuid,
+    user_id: Option<&Uuid>,
+    action: &str,
+    details: &Value,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO legal_hold_audit_log (project_id, user_id, action, details)
+        VALUES ($1, $2, $3, $4)",
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .bind(action)
+    .bind(details)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_audit_log(
+    pool: &PgPool,
+    project_id: &Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<LegalHoldAuditLogEntry>> {
+    let entries = sqlx::query_as::<_, LegalHoldAuditLogEntry>(
+        "SELECT id, created_at, project_id, user_id, action, details
+        FROM legal_hold_audit_log
+        WHERE project_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2 OFFSET $3",
+    )
+    .bind(project_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(entries)
+}
@@ -0,0 +1,286 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Held while queued machines are claimed, so that instances never start more machines than the
+/// pool has capacity for
+const MACHINE_POOL_LOCK_KEY: i64 = 0x6d61_6368_696e_6573;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[sqlx(type_name = "machine_status")]
+pub enum MachineStatus {
+    /// Waiting for capacity in the pool or under the project's limit
+    QUEUED,
+    STARTING,
+    RUNNING,
+    TERMINATED,
+    /// Failed to start, became unhealthy or waited too long in the queue
+    FAILED,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Machine {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub status: MachineStatus,
+    /// Id of the machine at the machine manager, None until it's started
+    pub provider_machine_id: Option<String>,
+    /// URL of the browser's DevTools endpoint, None until the machine is running
+    pub url: Option<String>,
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub last_health_check_at: Option<DateTime<Utc>>,
+    pub terminated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MachinePoolUsage {
+    /// Starting and running machines of all projects
+    pub active_count: i64,
+    pub queued_count: i64,
+    pub project_active_count: i64,
+    pub project_queued_count: i64,
+    /// Concurrent machines the project can have, None if it's only limited by the pool
+    pub project_limit: Option<i32>,
+}
+
+const MACHINE_COLUMNS: &str = "id, created_at, project_id, status, provider_machine_id, url, \
+    error, started_at, last_health_check_at, terminated_at";
+
+/// Queues a machine for the project, it's started by the pool once there's capacity
+pub async fn create_machine(pool: &PgPool, project_id: &Uuid) -> Result<Machine> {
+    let machine = sqlx::query_as::<_, Machine>(&format!(
+        "INSERT INTO machines (project_id) VALUES ($1) RETURNING {MACHINE_COLUMNS}"
+    ))
+    .bind(project_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(machine)
+}
+
+pub async fn get_machine(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<Option<Machine>> {
+    let machine = sqlx::query_as::<_, Machine>(&format!(
+        "SELECT {MACHINE_COLUMNS} FROM machines WHERE id = $1 AND project_id = $2"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(machine)
+}
+
+/// Machines of the project, the newest first. Terminated and failed ones only if `include_ended`.
+pub async fn get_machines(
+    pool: &PgPool,
+    project_id: &Uuid,
+    include_ended: bool,
+    limit: i64,
+) -> Result<Vec<Machine>> {
+    let machines = sqlx::query_as::<_, Machine>(&format!(
+        "SELECT {MACHINE_COLUMNS} FROM machines
+        WHERE project_id = $1 AND ($2 OR status IN ('QUEUED', 'STARTING', 'RUNNING'))
+        ORDER BY created_at DESC
+        LIMIT $3"
+    ))
+    .bind(project_id)
+    .bind(include_ended)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(machines)
+}
+
+/// 1-based position of the queued machine among all queued machines, None if it's not queued
+pub async fn get_queue_position(pool: &PgPool, id: &Uuid) -> Result<Option<i64>> {
+    let position = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM machines queued
+        JOIN machines machine ON machine.id = $1 AND machine.status = 'QUEUED'
+        WHERE queued.status = 'QUEUED' AND queued.created_at <= machine.created_at",
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Some(position).filter(|position| *position > 0))
+}
+
+pub async fn get_pool_usage(pool: &PgPool, project_id: &Uuid) -> Result<MachinePoolUsage> {
+    let usage = sqlx::query_as::<_, MachinePoolUsage>(
+        "SELECT
+            COUNT(*) FILTER (WHERE status IN ('STARTING', 'RUNNING')) as active_count,
+            COUNT(*) FILTER (WHERE status = 'QUEUED') as queued_count,
+            COUNT(*) FILTER (WHERE status IN ('STARTING', 'RUNNING') AND project_id = $1)
+                as project_active_count,
+            COUNT(*) FILTER (WHERE status = 'QUEUED' AND project_id = $1) as project_queued_count,
+            (SELECT machine_limit FROM projects WHERE id = $1) as project_limit
+        FROM machines
+        WHERE status IN ('QUEUED', 'STARTING', 'RUNNING')",
+    )
+    .bind(project_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(usage)
+}
+
+pub async fn set_machine_limit(
+    pool: &PgPool,
+    project_id: &Uuid,
+    machine_limit: Option<i32>,
+) -> Result<()> {
+    sqlx::query("UPDATE projects SET machine_limit = $2 WHERE id = $1")
+        .bind(project_id)
+        .bind(machine_limit)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Marks the oldest queued machines as starting and returns them, as many as fit into the pool's
+/// capacity without exceeding the limits of their projects. Machines of a project at its limit
+/// don't hold up the machines of other projects.
+pub async fn claim_queued_machines(pool: &PgPool, capacity: i64) -> Result<Vec<Machine>> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MACHINE_POOL_LOCK_KEY)
+        .execute(&mut *tx)
+        .await?;
+
+    let machines = sqlx::query_as::<_, Machine>(&format!(
+        "WITH active AS (
+            SELECT project_id, COUNT(*) as count
+            FROM machines
+            WHERE status IN ('STARTING', 'RUNNING')
+            GROUP BY project_id
+        ),
+        queued AS (
+            SELECT
+                machines.id,
+                machines.created_at,
+                projects.machine_limit,
+                COALESCE(active.count, 0) + ROW_NUMBER() OVER (
+                    PARTITION BY machines.project_id ORDER BY machines.created_at
+                ) as project_count
+            FROM machines
+            JOIN projects ON projects.id = machines.project_id
+            LEFT JOIN active ON active.project_id = machines.project_id
+            WHERE machines.status = 'QUEUED'
+        ),
+        claimed AS (
+            SELECT id FROM queued
+            WHERE machine_limit IS NULL OR project_count <= machine_limit
+            ORDER BY created_at ASC
+            LIMIT GREATEST($1 - (SELECT COALESCE(SUM(count), 0) FROM active)::bigint, 0)
+        )
+        UPDATE machines SET status = 'STARTING'
+        WHERE id IN (SELECT id FROM claimed)
+        RETURNING {MACHINE_COLUMNS}"
+    ))
+    .bind(capacity)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(machines)
+}
+
+/// Records the started machine. Returns false if the machine has been terminated while starting,
+/// in which case the started machine must be terminated.
+pub async fn set_machine_running(
+    pool: &PgPool,
+    id: &Uuid,
+    provider_machine_id: &str,
+    url: &str,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE machines SET
+            status = 'RUNNING',
+            provider_machine_id = $2,
+            url = $3,
+            started_at = now(),
+            last_health_check_at = now()
+        WHERE id = $1 AND status = 'STARTING'",
+    )
+    .bind(id)
+    .bind(provider_machine_id)
+    .bind(url)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn set_machine_failed(pool: &PgPool, id: &Uuid, error: &str) -> Result<()> {
+    sqlx::query(
+        "UPDATE machines SET status = 'FAILED', error = $2, terminated_at = now()
+        WHERE id = $1 AND status IN ('QUEUED', 'STARTING', 'RUNNING')",
+    )
+    .bind(id)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Marks the machine as terminated and returns it, None if it has already ended or doesn't exist.
+/// The machine itself is terminated by the caller.
+pub async fn terminate_machine(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<Machine>> {
+    let machine = sqlx::query_as::<_, Machine>(&format!(
+        "UPDATE machines SET status = 'TERMINATED', terminated_at = now()
+        WHERE id = $1 AND project_id = $2 AND status IN ('QUEUED', 'STARTING', 'RUNNING')
+        RETURNING {MACHINE_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(machine)
+}
+
+/// Fails the machines that have been queued for longer than `max_wait_seconds`
+pub async fn expire_queued_machines(pool: &PgPool, max_wait_seconds: i64) -> Result<u64> {
+    let result = sqlx::query(
+        "UPDATE machines SET
+            status = 'FAILED',
+            error = 'No machine became available in time',
+            terminated_at = now()
+        WHERE status = 'QUEUED' AND created_at < now() - make_interval(secs => $1)",
+    )
+    .bind(max_wait_seconds as f64)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Running machines that haven't been checked for `interval_seconds`. Returned machines count as
+/// checked, so that each is checked by one instance only.
+pub async fn claim_health_checks(pool: &PgPool, interval_seconds: i64) -> Result<Vec<Machine>> {
+    let machines = sqlx::query_as::<_, Machine>(&format!(
+        "UPDATE machines SET last_health_check_at = now()
+        WHERE status = 'RUNNING'
+            AND last_health_check_at < now() - make_interval(secs => $1)
+        RETURNING {MACHINE_COLUMNS}"
+    ))
+    .bind(interval_seconds as f64)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(machines)
+}
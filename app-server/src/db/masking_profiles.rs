@@ -0,0 +1,95 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use super::field_visibility::WorkspaceRole;
+
+/// Masking applied to the data served to members of a role, see `traces::masking`
+#[derive(Serialize, FromRow, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MaskingProfile {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub role: WorkspaceRole,
+    pub hash_user_ids: bool,
+    pub redact_emails: bool,
+}
+
+pub async fn get_masking_profiles(pool: &PgPool, project_id: &Uuid) -> Result<Vec<MaskingProfile>> {
+    let profiles = sqlx::query_as::<_, MaskingProfile>(
+        "SELECT id, created_at, project_id, role, hash_user_ids, redact_emails
+        FROM masking_profiles
+        WHERE project_id = $1
+        ORDER BY role",
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(profiles)
+}
+
+/// Profile of the user's role in the project's workspace, if the role has one
+pub async fn get_masking_profile_of_user(
+    pool: &PgPool,
+    user_id: &Uuid,
+    project_id: &Uuid,
+) -> Result<Option<MaskingProfile>> {
+    let profile = sqlx::query_as::<_, MaskingProfile>(
+        "SELECT
+            masking_profiles.id,
+            masking_profiles.created_at,
+            masking_profiles.project_id,
+            masking_profiles.role,
+            masking_profiles.hash_user_ids,
+            masking_profiles.redact_emails
+        FROM masking_profiles
+        JOIN projects ON projects.id = masking_profiles.project_id
+        JOIN members_of_workspaces ON members_of_workspaces.workspace_id = projects.workspace_id
+            AND members_of_workspaces.member_role = masking_profiles.role
+        WHERE masking_profiles.project_id = $1 AND members_of_workspaces.user_id = $2",
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(profile)
+}
+
+pub async fn set_masking_profile(
+    pool: &PgPool,
+    project_id: &Uuid,
+    role: WorkspaceRole,
+    hash_user_ids: bool,
+    redact_emails: bool,
+) -> Result<MaskingProfile> {
+    let profile = sqlx::query_as::<_, MaskingProfile>(
+        "INSERT INTO masking_profiles (project_id, role, hash_user_ids, redact_emails)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (project_id, role) DO UPDATE
+        SET hash_user_ids = EXCLUDED.hash_user_ids, redact_emails = EXCLUDED.redact_emails
+        RETURNING id, created_at, project_id, role, hash_user_ids, redact_emails",
+    )
+    .bind(project_id)
+    .bind(role)
+    .bind(hash_user_ids)
+    .bind(redact_emails)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(profile)
+}
+
+pub async fn delete_masking_profile(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let res = sqlx::query("DELETE FROM masking_profiles WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
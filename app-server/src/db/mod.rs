@@ -1,24 +1,54 @@
 use sqlx::PgPool;
 
+pub mod activity;
+pub mod agent_checkpoints;
+pub mod alerts;
+pub mod backups;
+pub mod canary;
+pub mod comments;
+pub mod custom_metrics;
+pub mod data_access_log;
 pub mod datapoints;
+pub mod dataset_imports;
+pub mod dataset_versions;
 pub mod datasets;
+pub mod eval_proposals;
+pub mod evaluation_schedules;
 pub mod evaluations;
 pub mod event_templates;
 pub mod events;
+pub mod field_visibility;
+pub mod insights;
+pub mod issues;
+pub mod judge_evaluators;
 pub mod labeling_queues;
 pub mod labels;
+pub mod lake_exports;
+pub mod legal_holds;
+pub mod machines;
+pub mod masking_profiles;
 pub mod modifiers;
+pub mod online_evaluation_rules;
+pub mod organizations;
+pub mod personal_access_tokens;
 pub mod pipelines;
 pub mod prices;
 pub mod project_api_keys;
 pub mod projects;
+pub mod promoted_attributes;
 pub mod provider_api_keys;
+pub mod sampling_exemptions;
+pub mod scim;
+pub mod shadow_deployments;
 pub mod spans;
 pub mod stats;
 pub mod subscriptions;
+pub mod tags;
 pub mod trace;
+pub mod ui_sessions;
 pub mod user;
 pub mod utils;
+pub mod warehouse_syncs;
 pub mod workspace;
 
 #[derive(Clone, Debug)]
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+/// Rule that scores a sample of the project's incoming spans, see `traces::online_evaluations`
+#[derive(Serialize, FromRow, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OnlineEvaluationRule {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    /// Name of the scores the rule produces
+    pub name: String,
+    /// Matches spans at the path only, if set
+    pub span_path: Option<String>,
+    /// Matches spans with the name only, if set
+    pub span_name: Option<String>,
+    /// Attributes with the values that matched spans must have, e.g. `{"gen_ai.system": "openai"}`
+    pub attribute_filters: Value,
+    /// Percentage of the matched spans, from 0 to 100, that are scored
+    pub sample_percentage: f64,
+    /// See `traces::online_evaluations::OnlineEvaluator`
+    pub evaluator: Value,
+    pub enabled: bool,
+}
+
+pub struct NewOnlineEvaluationRule {
+    pub name: String,
+    pub span_path: Option<String>,
+    pub span_name: Option<String>,
+    pub attribute_filters: Value,
+    pub sample_percentage: f64,
+    pub evaluator: Value,
+}
+
+const ONLINE_EVALUATION_RULE_COLUMNS: &str = "id, created_at, project_id, name, span_path, \
+    span_name, attribute_filters, sample_percentage, evaluator, enabled";
+
+pub async fn get_online_evaluation_rules(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<OnlineEvaluationRule>> {
+    let rules = sqlx::query_as::<_, OnlineEvaluationRule>(&format!(
+        "SELECT {ONLINE_EVALUATION_RULE_COLUMNS}
+        FROM online_evaluation_rules
+        WHERE project_id = $1
+        ORDER BY name"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rules)
+}
+
+pub async fn get_enabled_online_evaluation_rules(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<OnlineEvaluationRule>> {
+    let rules = sqlx::query_as::<_, OnlineEvaluationRule>(&format!(
+        "SELECT {ONLINE_EVALUATION_RULE_COLUMNS}
+        FROM online_evaluation_rules
+        WHERE project_id = $1 AND enabled"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rules)
+}
+
+/// Returns None if the project already has a rule with the name
+pub async fn create_online_evaluation_rule(
+    pool: &PgPool,
+    project_id: &Uuid,
+    rule: &NewOnlineEvaluationRule,
+) -> Result<Option<OnlineEvaluationRule>> {
+    let rule = sqlx::query_as::<_, OnlineEvaluationRule>(&format!(
+        "INSERT INTO online_evaluation_rules
+            (project_id, name, span_path, span_name, attribute_filters, sample_percentage, evaluator)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (project_id, name) DO NOTHING
+        RETURNING {ONLINE_EVALUATION_RULE_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(&rule.name)
+    .bind(&rule.span_path)
+    .bind(&rule.span_name)
+    .bind(&rule.attribute_filters)
+    .bind(rule.sample_percentage)
+    .bind(&rule.evaluator)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(rule)
+}
+
+pub async fn set_online_evaluation_rule_enabled(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+    enabled: bool,
+) -> Result<Option<OnlineEvaluationRule>> {
+    let rule = sqlx::query_as::<_, OnlineEvaluationRule>(&format!(
+        "UPDATE online_evaluation_rules SET enabled = $3
+        WHERE id = $1 AND project_id = $2
+        RETURNING {ONLINE_EVALUATION_RULE_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .bind(enabled)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(rule)
+}
+
+pub async fn delete_online_evaluation_rule(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<bool> {
+    let result =
+        sqlx::query("DELETE FROM online_evaluation_rules WHERE id = $1 AND project_id = $2")
+            .bind(id)
+            .bind(project_id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Creates the rule, or replaces the settings of the project's rule with the name
+pub async fn upsert_online_evaluation_rule(
+    conn: &mut PgConnection,
+    project_id: &Uuid,
+    rule: &NewOnlineEvaluationRule,
+    enabled: bool,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO online_evaluation_rules (
+            project_id,
+            name,
+            span_path,
+            span_name,
+            attribute_filters,
+            sample_percentage,
+            evaluator,
+            enabled
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (project_id, name)
+        DO UPDATE SET
+            span_path = EXCLUDED.span_path,
+            span_name = EXCLUDED.span_name,
+            attribute_filters = EXCLUDED.attribute_filters,
+            sample_percentage = EXCLUDED.sample_percentage,
+            evaluator = EXCLUDED.evaluator,
+            enabled = EXCLUDED.enabled",
+    )
+    .bind(project_id)
+    .bind(&rule.name)
+    .bind(&rule.span_path)
+    .bind(&rule.span_name)
+    .bind(&rule.attribute_filters)
+    .bind(rule.sample_percentage)
+    .bind(&rule.evaluator)
+    .bind(enabled)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_online_evaluation_rule_by_name(
+    conn: &mut PgConnection,
+    project_id: &Uuid,
+    name: &str,
+) -> Result<()> {
+    sqlx::query("DELETE FROM online_evaluation_rules WHERE project_id = $1 AND name = $2")
+        .bind(project_id)
+        .bind(name)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use super::workspace::Workspace;
+
+#[derive(Deserialize, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub tier_name: String,
+    pub billing_email: Option<String>,
+    /// If true, members of the organization must sign in with SSO, see `routes::auth`
+    pub sso_required: bool,
+    /// Workspaces of the organization and their projects cannot set retention lower than this
+    pub min_retention_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationWithWorkspaces {
+    #[serde(flatten)]
+    pub organization: Organization,
+    pub workspaces: Vec<Workspace>,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationMember {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub role: String,
+}
+
+/// Policies that a workspace inherits from its organization.
+/// Workspaces without an organization have the default, unrestricted, policy.
+#[derive(Serialize, FromRow, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspacePolicy {
+    pub organization_id: Option<Uuid>,
+    pub sso_required: bool,
+    pub min_retention_days: Option<i64>,
+}
+
+const ORGANIZATION_COLUMNS: &str = "
+    organizations.id,
+    organizations.name,
+    subscription_tiers.name as tier_name,
+    organizations.billing_email,
+    organizations.sso_required,
+    organizations.min_retention_days";
+
+pub async fn create_organization(
+    pool: &PgPool,
+    name: &str,
+    billing_email: &Option<String>,
+    owner_id: &Uuid,
+) -> Result<Organization> {
+    let mut tx = pool.begin().await?;
+
+    let organization_id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO organizations (name, billing_email) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(name)
+    .bind(billing_email)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO members_of_organizations (organization_id, user_id, member_role)
+        VALUES ($1, $2, 'owner'::organization_role)",
+    )
+    .bind(organization_id)
+    .bind(owner_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    get_organization(pool, &organization_id).await
+}
+
+pub async fn get_organization(pool: &PgPool, organization_id: &Uuid) -> Result<Organization> {
+    let organization = sqlx::query_as::<_, Organization>(&format!(
+        "SELECT {ORGANIZATION_COLUMNS}
+        FROM organizations
+        JOIN subscription_tiers ON organizations.tier_id = subscription_tiers.id
+        WHERE organizations.id = $1"
+    ))
+    .bind(organization_id)
+    .fetch_optional(pool)
+    .await?;
+
+    organization.ok_or(anyhow::anyhow!("Organization not found"))
+}
+
+pub async fn get_organizations_of_user(
+    pool: &PgPool,
+    user_id: &Uuid,
+) -> Result<Vec<Organization>> {
+    let organizations = sqlx::query_as::<_, Organization>(&format!(
+        "SELECT {ORGANIZATION_COLUMNS}
+        FROM organizations
+        JOIN subscription_tiers ON organizations.tier_id = subscription_tiers.id
+        JOIN members_of_organizations ON organizations.id = members_of_organizations.organization_id
+        WHERE members_of_organizations.user_id = $1
+        ORDER BY organizations.created_at DESC"
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(organizations)
+}
+
+pub async fn get_organization_workspaces(
+    pool: &PgPool,
+    organization_id: &Uuid,
+) -> Result<Vec<Workspace>> {
+    let workspaces = sqlx::query_as::<_, Workspace>(
+        "SELECT
+            workspaces.id,
+            workspaces.name,
+            subscription_tiers.name as tier_name,
+            tier_id = 1 as is_free_tier
+        FROM
+            workspaces
+            JOIN subscription_tiers on workspaces.tier_id = subscription_tiers.id
+        WHERE workspaces.organization_id = $1
+        ORDER BY workspaces.created_at DESC",
+    )
+    .bind(organization_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(workspaces)
+}
+
+pub async fn get_organization_members(
+    pool: &PgPool,
+    organization_id: &Uuid,
+) -> Result<Vec<OrganizationMember>> {
+    let members = sqlx::query_as::<_, OrganizationMember>(
+        "SELECT
+            users.id,
+            users.name,
+            users.email,
+            members_of_organizations.member_role::text as role
+        FROM users
+        JOIN members_of_organizations ON users.id = members_of_organizations.user_id
+        WHERE members_of_organizations.organization_id = $1
+        ORDER BY members_of_organizations.created_at ASC",
+    )
+    .bind(organization_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(members)
+}
+
+/// Returns None if the user is not a member of the organization
+pub async fn get_member_role(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Option<String>> {
+    let role = sqlx::query_scalar::<_, String>(
+        "SELECT member_role::text
+        FROM members_of_organizations
+        WHERE organization_id = $1 AND user_id = $2",
+    )
+    .bind(organization_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(role)
+}
+
+pub async fn add_user_to_organization_by_email(
+    pool: &PgPool,
+    user_email: &str,
+    organization_id: &Uuid,
+    role: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO members_of_organizations (user_id, organization_id, member_role)
+        SELECT id, $2, $3::organization_role FROM users
+        WHERE users.email = $1
+        ON CONFLICT (organization_id, user_id) DO UPDATE SET member_role = EXCLUDED.member_role",
+    )
+    .bind(user_email)
+    .bind(organization_id)
+    .bind(role)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn attach_workspace_to_organization(
+    pool: &PgPool,
+    workspace_id: &Uuid,
+    organization_id: &Uuid,
+) -> Result<()> {
+    sqlx::query("UPDATE workspaces SET organization_id = $2 WHERE id = $1")
+        .bind(workspace_id)
+        .bind(organization_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Workspaces and projects of the organization with a retention lower than the new floor are
+/// raised to it
+pub async fn update_organization_policy(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    sso_required: bool,
+    min_retention_days: Option<i64>,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE organizations
+        SET sso_required = $2, min_retention_days = $3
+        WHERE id = $1",
+    )
+    .bind(organization_id)
+    .bind(sso_required)
+    .bind(min_retention_days)
+    .execute(&mut *tx)
+    .await?;
+
+    if let Some(min_retention_days) = min_retention_days {
+        sqlx::query(
+            "UPDATE workspaces SET retention_days = $2
+            WHERE organization_id = $1 AND retention_days < $2",
+        )
+        .bind(organization_id)
+        .bind(min_retention_days)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE projects SET retention_days = $2
+            FROM workspaces
+            WHERE projects.workspace_id = workspaces.id
+                AND workspaces.organization_id = $1
+                AND projects.retention_days < $2",
+        )
+        .bind(organization_id)
+        .bind(min_retention_days)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn get_workspace_policy(pool: &PgPool, workspace_id: &Uuid) -> Result<WorkspacePolicy> {
+    let policy = sqlx::query_as::<_, WorkspacePolicy>(
+        "SELECT
+            organizations.id as organization_id,
+            organizations.sso_required,
+            organizations.min_retention_days
+        FROM workspaces
+        JOIN organizations ON workspaces.organization_id = organizations.id
+        WHERE workspaces.id = $1",
+    )
+    .bind(workspace_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(policy.unwrap_or_default())
+}
+
+/// Whether the user with the email is a member of an organization that requires SSO
+pub async fn is_sso_required_for_email(pool: &PgPool, email: &str) -> Result<bool> {
+    let sso_required = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (
+            SELECT 1
+            FROM members_of_organizations
+            JOIN organizations ON members_of_organizations.organization_id = organizations.id
+            JOIN users ON members_of_organizations.user_id = users.id
+            WHERE users.email = $1 AND organizations.sso_required
+        )",
+    )
+    .bind(email)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(sso_required)
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "token_scope", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    /// Only safe (GET) requests are allowed
+    Read,
+    Write,
+}
+
+#[derive(Serialize, FromRow, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalAccessToken {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub user_id: Uuid,
+    pub name: String,
+    pub shorthand: String,
+    pub scope: TokenScope,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+const TOKEN_COLUMNS: &str =
+    "id, created_at, user_id, name, shorthand, scope, expires_at, last_used_at";
+
+pub async fn create_token(
+    pool: &PgPool,
+    user_id: &Uuid,
+    name: &str,
+    hash: &str,
+    shorthand: &str,
+    scope: TokenScope,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<PersonalAccessToken> {
+    let token = sqlx::query_as::<_, PersonalAccessToken>(&format!(
+        "INSERT INTO personal_access_tokens (user_id, name, hash, shorthand, scope, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING {TOKEN_COLUMNS}"
+    ))
+    .bind(user_id)
+    .bind(name)
+    .bind(hash)
+    .bind(shorthand)
+    .bind(scope)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(token)
+}
+
+pub async fn get_tokens_of_user(pool: &PgPool, user_id: &Uuid) -> Result<Vec<PersonalAccessToken>> {
+    let tokens = sqlx::query_as::<_, PersonalAccessToken>(&format!(
+        "SELECT {TOKEN_COLUMNS}
+        FROM personal_access_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC"
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tokens)
+}
+
+pub async fn delete_token(pool: &PgPool, user_id: &Uuid, token_id: &Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM personal_access_tokens WHERE user_id = $1 AND id = $2")
+        .bind(user_id)
+        .bind(token_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Look up a non-expired token by hash and record that it was used
+pub async fn use_token(pool: &PgPool, hash: &str) -> Result<Option<PersonalAccessToken>> {
+    let token = sqlx::query_as::<_, PersonalAccessToken>(&format!(
+        "UPDATE personal_access_tokens
+        SET last_used_at = now()
+        WHERE hash = $1 AND (expires_at IS NULL OR expires_at > now())
+        RETURNING {TOKEN_COLUMNS}"
+    ))
+    .bind(hash)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(token)
+}
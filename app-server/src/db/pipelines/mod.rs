@@ -3,5 +3,9 @@ pub use pipeline_templates::*;
 pub use pipeline_version::*;
 
 pub mod pipeline;
+pub mod pipeline_approvals;
+pub mod pipeline_deployments;
 pub mod pipeline_templates;
+pub mod pipeline_triggers;
+pub mod pipeline_usage;
 pub mod pipeline_version;
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "approval_task_status")]
+pub enum ApprovalTaskStatus {
+    PENDING,
+    APPROVED,
+    REJECTED,
+    /// Nobody resolved the task before the approval node timed out
+    EXPIRED,
+}
+
+/// Payload of an approval node's run, waiting for a human to approve or reject it
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalTask {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub node_name: String,
+    pub instructions: String,
+    pub payload: Value,
+    pub status: ApprovalTaskStatus,
+    /// Payload edited by the approver, which the pipeline continues with instead of the original
+    pub resolved_payload: Option<Value>,
+    pub comment: Option<String>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+const APPROVAL_TASK_COLUMNS: &str = "id, created_at, project_id, node_name, instructions, \
+    payload, status, resolved_payload, comment, resolved_by, resolved_at";
+
+pub async fn create_approval_task(
+    pool: &PgPool,
+    project_id: &Uuid,
+    node_name: &str,
+    instructions: &str,
+    payload: &Value,
+) -> Result<ApprovalTask> {
+    let task = sqlx::query_as::<_, ApprovalTask>(&format!(
+        "INSERT INTO pipeline_approval_tasks (project_id, node_name, instructions, payload)
+        VALUES ($1, $2, $3, $4)
+        RETURNING {APPROVAL_TASK_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(node_name)
+    .bind(instructions)
+    .bind(payload)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(task)
+}
+
+pub async fn get_approval_task(pool: &PgPool, id: &Uuid) -> Result<Option<ApprovalTask>> {
+    let task = sqlx::query_as::<_, ApprovalTask>(&format!(
+        "SELECT {APPROVAL_TASK_COLUMNS} FROM pipeline_approval_tasks WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(task)
+}
+
+/// Tasks of the project, the oldest first, so that approvers work through them in order
+pub async fn get_approval_tasks(
+    pool: &PgPool,
+    project_id: &Uuid,
+    status: Option<ApprovalTaskStatus>,
+) -> Result<Vec<ApprovalTask>> {
+    let tasks = sqlx::query_as::<_, ApprovalTask>(&format!(
+        "SELECT {APPROVAL_TASK_COLUMNS}
+        FROM pipeline_approval_tasks
+        WHERE project_id = $1 AND ($2::approval_task_status IS NULL OR status = $2)
+        ORDER BY created_at ASC"
+    ))
+    .bind(project_id)
+    .bind(status)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tasks)
+}
+
+/// Approves or rejects a pending task. Returns None if the task doesn't exist or has already
+/// been resolved.
+pub async fn resolve_approval_task(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+    status: ApprovalTaskStatus,
+    resolved_payload: Option<Value>,
+    comment: Option<String>,
+    resolved_by: Option<Uuid>,
+) -> Result<Option<ApprovalTask>> {
+    let task = sqlx::query_as::<_, ApprovalTask>(&format!(
+        "UPDATE pipeline_approval_tasks SET
+            status = $3,
+            resolved_payload = $4,
+            comment = $5,
+            resolved_by = $6,
+            resolved_at = now()
+        WHERE id = $1 AND project_id = $2 AND status = 'PENDING'
+        RETURNING {APPROVAL_TASK_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .bind(status)
+    .bind(resolved_payload)
+    .bind(comment)
+    .bind(resolved_by)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(task)
+}
+
+/// Expires the task if it is still pending. Returns false if it has been resolved meanwhile.
+pub async fn expire_approval_task(pool: &PgPool, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE pipeline_approval_tasks SET status = 'EXPIRED', resolved_at = now()
+        WHERE id = $1 AND status = 'PENDING'",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
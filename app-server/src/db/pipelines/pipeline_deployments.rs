@@ -0,0 +1,158 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgPool};
+use uuid::Uuid;
+
+use super::PipelineVersion;
+
+/// Target of `target_pipeline_versions`, which runs of the pipeline without a target use
+pub const PRODUCTION_TARGET: &str = "production";
+
+/// Named deployment target, e.g. production or staging, pointing at a commit version
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineDeployment {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub pipeline_id: Uuid,
+    pub target: String,
+    pub pipeline_version_id: Uuid,
+    /// Version the target pointed at before the last deploy, which rollback restores
+    pub previous_pipeline_version_id: Option<Uuid>,
+    pub deployed_by: Option<Uuid>,
+}
+
+pub async fn get_pipeline_deployments(
+    pool: &PgPool,
+    pipeline_id: &Uuid,
+) -> Result<Vec<PipelineDeployment>> {
+    let deployments = sqlx::query_as::<_, PipelineDeployment>(
+        "SELECT
+            id,
+            created_at,
+            updated_at,
+            pipeline_id,
+            target,
+            pipeline_version_id,
+            previous_pipeline_version_id,
+            deployed_by
+        FROM pipeline_deployments
+        WHERE pipeline_id = $1
+        ORDER BY target",
+    )
+    .bind(pipeline_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deployments)
+}
+
+/// Point the target at the version, remembering the version it pointed at before.
+/// Re-deploying the current version keeps the previous version.
+pub async fn deploy_pipeline_version(
+    pool: &PgPool,
+    pipeline_id: &Uuid,
+    target: &str,
+    pipeline_version_id: &Uuid,
+    deployed_by: Option<Uuid>,
+) -> Result<PipelineDeployment> {
+    let deployment = sqlx::query_as::<_, PipelineDeployment>(
+        "INSERT INTO pipeline_deployments (pipeline_id, target, pipeline_version_id, deployed_by)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (pipeline_id, target) DO UPDATE SET
+            previous_pipeline_version_id = CASE
+                WHEN pipeline_deployments.pipeline_version_id = EXCLUDED.pipeline_version_id
+                    THEN pipeline_deployments.previous_pipeline_version_id
+                ELSE pipeline_deployments.pipeline_version_id
+            END,
+            pipeline_version_id = EXCLUDED.pipeline_version_id,
+            deployed_by = EXCLUDED.deployed_by,
+            updated_at = now()
+        RETURNING
+            id,
+            created_at,
+            updated_at,
+            pipeline_id,
+            target,
+            pipeline_version_id,
+            previous_pipeline_version_id,
+            deployed_by",
+    )
+    .bind(pipeline_id)
+    .bind(target)
+    .bind(pipeline_version_id)
+    .bind(deployed_by)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(deployment)
+}
+
+/// Swap the target's version with its previous version, so that a second rollback undoes the
+/// first. None if the target doesn't exist or has never been re-deployed.
+pub async fn rollback_pipeline_deployment(
+    pool: &PgPool,
+    pipeline_id: &Uuid,
+    target: &str,
+    deployed_by: Option<Uuid>,
+) -> Result<Option<PipelineDeployment>> {
+    let deployment = sqlx::query_as::<_, PipelineDeployment>(
+        "UPDATE pipeline_deployments SET
+            pipeline_version_id = previous_pipeline_version_id,
+            previous_pipeline_version_id = pipeline_version_id,
+            deployed_by = $3,
+            updated_at = now()
+        WHERE pipeline_id = $1
+            AND target = $2
+            AND previous_pipeline_version_id IS NOT NULL
+        RETURNING
+            id,
+            created_at,
+            updated_at,
+            pipeline_id,
+            target,
+            pipeline_version_id,
+            previous_pipeline_version_id,
+            deployed_by",
+    )
+    .bind(pipeline_id)
+    .bind(target)
+    .bind(deployed_by)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(deployment)
+}
+
+pub async fn get_deployed_pipeline_version_by_pipeline_name(
+    pool: &PgPool,
+    project_id: Uuid,
+    pipeline_name: &str,
+    target: &str,
+) -> Result<Option<PipelineVersion>> {
+    let version = sqlx::query_as::<_, PipelineVersion>(
+        "SELECT
+            pipeline_versions.id,
+            pipeline_versions.pipeline_id,
+            pipeline_versions.pipeline_type,
+            pipeline_versions.name,
+            pipeline_versions.displayable_graph,
+            pipeline_versions.runnable_graph,
+            pipeline_versions.created_at
+        FROM pipeline_deployments
+        JOIN pipelines ON pipelines.id = pipeline_deployments.pipeline_id
+        JOIN pipeline_versions ON pipeline_versions.id = pipeline_deployments.pipeline_version_id
+        WHERE pipelines.project_id = $1
+            AND pipelines.name = $2
+            AND pipeline_deployments.target = $3",
+    )
+    .bind(project_id)
+    .bind(pipeline_name)
+    .bind(target)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(version)
+}
@@ -0,0 +1,357 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "pipeline_trigger_type")]
+pub enum PipelineTriggerType {
+    /// Runs on the trigger's cron schedule
+    SCHEDULE,
+    /// Runs when datapoints of the trigger's dataset, or of any dataset, are changed
+    DATASET_UPDATED,
+    /// Runs when the trigger's webhook URL is called
+    WEBHOOK,
+}
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "pipeline_trigger_run_status")]
+pub enum PipelineTriggerRunStatus {
+    QUEUED,
+    RUNNING,
+    SUCCEEDED,
+    FAILED,
+}
+
+#[derive(Serialize, FromRow, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineTrigger {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub pipeline_id: Uuid,
+    /// Deployment target whose version is run
+    pub target: String,
+    pub trigger_type: PipelineTriggerType,
+    pub cron_schedule: Option<String>,
+    /// If None, dataset triggers run on changes of any dataset of the project
+    pub dataset_id: Option<Uuid>,
+    pub webhook_token: Option<String>,
+    /// Inputs of the pipeline, the event is passed as the `event` input in addition
+    pub inputs: Value,
+    pub enabled: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineTriggerRun {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub trigger_id: Uuid,
+    pub status: PipelineTriggerRunStatus,
+    /// What caused the run, e.g. the webhook's body
+    pub event: Value,
+    pub outputs: Option<Value>,
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+pub struct NewPipelineTrigger {
+    pub project_id: Uuid,
+    pub pipeline_id: Uuid,
+    pub target: String,
+    pub trigger_type: PipelineTriggerType,
+    pub cron_schedule: Option<String>,
+    pub dataset_id: Option<Uuid>,
+    pub webhook_token: Option<String>,
+    pub inputs: Value,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+}
+
+const TRIGGER_COLUMNS: &str = "id, created_at, project_id, pipeline_id, target, trigger_type, \
+    cron_schedule, dataset_id, webhook_token, inputs, enabled, next_run_at, created_by";
+
+const TRIGGER_RUN_COLUMNS: &str =
+    "id, created_at, trigger_id, status, event, outputs, error, started_at, finished_at";
+
+pub async fn create_trigger(
+    pool: &PgPool,
+    trigger: &NewPipelineTrigger,
+) -> Result<PipelineTrigger> {
+    let trigger = sqlx::query_as::<_, PipelineTrigger>(&format!(
+        "INSERT INTO pipeline_triggers (
+            project_id,
+            pipeline_id,
+            target,
+            trigger_type,
+            cron_schedule,
+            dataset_id,
+            webhook_token,
+            inputs,
+            next_run_at,
+            created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING {TRIGGER_COLUMNS}"
+    ))
+    .bind(trigger.project_id)
+    .bind(trigger.pipeline_id)
+    .bind(&trigger.target)
+    .bind(trigger.trigger_type)
+    .bind(&trigger.cron_schedule)
+    .bind(trigger.dataset_id)
+    .bind(&trigger.webhook_token)
+    .bind(&trigger.inputs)
+    .bind(trigger.next_run_at)
+    .bind(trigger.created_by)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(trigger)
+}
+
+pub async fn get_trigger(pool: &PgPool, id: &Uuid) -> Result<Option<PipelineTrigger>> {
+    let trigger = sqlx::query_as::<_, PipelineTrigger>(&format!(
+        "SELECT {TRIGGER_COLUMNS} FROM pipeline_triggers WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(trigger)
+}
+
+pub async fn get_trigger_by_webhook_token(
+    pool: &PgPool,
+    webhook_token: &str,
+) -> Result<Option<PipelineTrigger>> {
+    let trigger = sqlx::query_as::<_, PipelineTrigger>(&format!(
+        "SELECT {TRIGGER_COLUMNS} FROM pipeline_triggers
+        WHERE webhook_token = $1 AND trigger_type = 'WEBHOOK'"
+    ))
+    .bind(webhook_token)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(trigger)
+}
+
+pub async fn get_pipeline_triggers(
+    pool: &PgPool,
+    project_id: &Uuid,
+    pipeline_id: &Uuid,
+) -> Result<Vec<PipelineTrigger>> {
+    let triggers = sqlx::query_as::<_, PipelineTrigger>(&format!(
+        "SELECT {TRIGGER_COLUMNS} FROM pipeline_triggers
+        WHERE project_id = $1 AND pipeline_id = $2
+        ORDER BY created_at ASC"
+    ))
+    .bind(project_id)
+    .bind(pipeline_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(triggers)
+}
+
+/// Enables or disables the trigger. Schedules continue from `next_run_at`, so that runs missed
+/// while the trigger was disabled are not caught up on.
+pub async fn set_trigger_enabled(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+    enabled: bool,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<Option<PipelineTrigger>> {
+    let trigger = sqlx::query_as::<_, PipelineTrigger>(&format!(
+        "UPDATE pipeline_triggers SET enabled = $3, next_run_at = $4
+        WHERE id = $1 AND project_id = $2
+        RETURNING {TRIGGER_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .bind(enabled)
+    .bind(next_run_at)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(trigger)
+}
+
+pub async fn delete_trigger(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM pipeline_triggers WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn enqueue_trigger_run(
+    pool: &PgPool,
+    trigger_id: &Uuid,
+    event: &Value,
+) -> Result<PipelineTriggerRun> {
+    let run = sqlx::query_as::<_, PipelineTriggerRun>(&format!(
+        "INSERT INTO pipeline_trigger_runs (trigger_id, event)
+        VALUES ($1, $2)
+        RETURNING {TRIGGER_RUN_COLUMNS}"
+    ))
+    .bind(trigger_id)
+    .bind(event)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(run)
+}
+
+/// Queues a run of each enabled dataset trigger of the project that watches the dataset.
+/// Returns the number of queued runs.
+pub async fn enqueue_dataset_trigger_runs(
+    pool: &PgPool,
+    project_id: &Uuid,
+    dataset_id: &Uuid,
+    event: &Value,
+) -> Result<u64> {
+    let result = sqlx::query(
+        "INSERT INTO pipeline_trigger_runs (trigger_id, event)
+        SELECT id, $3
+        FROM pipeline_triggers
+        WHERE project_id = $1
+            AND trigger_type = 'DATASET_UPDATED'
+            AND enabled
+            AND (dataset_id IS NULL OR dataset_id = $2)",
+    )
+    .bind(project_id)
+    .bind(dataset_id)
+    .bind(event)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[derive(FromRow)]
+struct DueSchedule {
+    id: Uuid,
+    cron_schedule: Option<String>,
+    next_run_at: Option<DateTime<Utc>>,
+}
+
+/// Queues a run of each enabled schedule that is due and moves it to its next run, which
+/// `next_run_at` computes from the cron schedule. Schedules are locked while they are queued,
+/// so that every due run is queued by exactly one app-server instance.
+pub async fn enqueue_due_schedule_runs(
+    pool: &PgPool,
+    next_run_at: impl Fn(&str) -> Option<DateTime<Utc>>,
+) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    let schedules = sqlx::query_as::<_, DueSchedule>(
+        "SELECT id, cron_schedule, next_run_at
+        FROM pipeline_triggers
+        WHERE trigger_type = 'SCHEDULE' AND enabled AND next_run_at <= now()
+        FOR UPDATE SKIP LOCKED",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for schedule in &schedules {
+        sqlx::query("UPDATE pipeline_triggers SET next_run_at = $2 WHERE id = $1")
+            .bind(schedule.id)
+            .bind(schedule.cron_schedule.as_deref().and_then(&next_run_at))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO pipeline_trigger_runs (trigger_id, event) VALUES ($1, $2)")
+            .bind(schedule.id)
+            .bind(json!({ "scheduledAt": schedule.next_run_at }))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(schedules.len() as u64)
+}
+
+/// Marks the oldest queued run as running and returns it, if there is one
+pub async fn claim_queued_trigger_run(pool: &PgPool) -> Result<Option<PipelineTriggerRun>> {
+    let run = sqlx::query_as::<_, PipelineTriggerRun>(&format!(
+        "UPDATE pipeline_trigger_runs SET status = 'RUNNING', started_at = now()
+        WHERE id = (
+            SELECT id FROM pipeline_trigger_runs
+            WHERE status = 'QUEUED'
+            ORDER BY created_at ASC
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {TRIGGER_RUN_COLUMNS}"
+    ))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(run)
+}
+
+pub async fn finish_trigger_run(
+    pool: &PgPool,
+    id: &Uuid,
+    status: PipelineTriggerRunStatus,
+    outputs: Option<Value>,
+    error: Option<String>,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE pipeline_trigger_runs SET
+            status = $2,
+            outputs = $3,
+            error = $4,
+            finished_at = now()
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(status)
+    .bind(outputs)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Run history of the trigger, the newest first
+pub async fn get_trigger_runs(
+    pool: &PgPool,
+    project_id: &Uuid,
+    trigger_id: &Uuid,
+    limit: i64,
+) -> Result<Vec<PipelineTriggerRun>> {
+    let runs = sqlx::query_as::<_, PipelineTriggerRun>(
+        "SELECT
+            runs.id,
+            runs.created_at,
+            runs.trigger_id,
+            runs.status,
+            runs.event,
+            runs.outputs,
+            runs.error,
+            runs.started_at,
+            runs.finished_at
+        FROM pipeline_trigger_runs runs
+        JOIN pipeline_triggers ON pipeline_triggers.id = runs.trigger_id
+        WHERE pipeline_triggers.project_id = $1 AND runs.trigger_id = $2
+        ORDER BY runs.created_at DESC
+        LIMIT $3",
+    )
+    .bind(project_id)
+    .bind(trigger_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(runs)
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::pipeline::trace::NodeRunStats;
+
+/// Usage of a pipeline node in the current month, over all endpoint runs of the pipeline
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineNodeUsage {
+    pub node_name: String,
+    pub node_type: String,
+    pub execution_count: i64,
+    pub total_token_count: i64,
+    /// Sum of the known costs
+    pub approximate_cost: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineUsage {
+    /// None if the pipeline has no quota
+    pub monthly_execution_quota: Option<i64>,
+    pub execution_count: i64,
+    /// Most expensive nodes first
+    pub nodes: Vec<PipelineNodeUsage>,
+}
+
+/// Count an execution of the pipeline against its monthly quota. Returns false, without
+/// counting it, if the quota has been used up.
+pub async fn reserve_pipeline_execution(pool: &PgPool, pipeline_id: &Uuid) -> Result<bool> {
+    let execution_count = sqlx::query_scalar::<_, i64>(
+        "WITH quota AS (
+            SELECT monthly_execution_quota FROM pipelines WHERE id = $1
+        )
+        INSERT INTO pipeline_execution_usage (pipeline_id, month_start, execution_count)
+        SELECT $1, date_trunc('month', now()), 1
+        FROM quota
+        WHERE quota.monthly_execution_quota IS NULL OR quota.monthly_execution_quota > 0
+        ON CONFLICT (pipeline_id, month_start) DO UPDATE SET
+            execution_count = pipeline_execution_usage.execution_count + 1
+        WHERE (SELECT monthly_execution_quota FROM quota) IS NULL
+            OR pipeline_execution_usage.execution_count
+                < (SELECT monthly_execution_quota FROM quota)
+        RETURNING execution_count",
+    )
+    .bind(pipeline_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(execution_count.is_some())
+}
+
+pub async fn record_pipeline_node_usage(
+    pool: &PgPool,
+    pipeline_id: &Uuid,
+    node_stats: &[NodeRunStats],
+) -> Result<()> {
+    let node_names = node_stats
+        .iter()
+        .map(|stats| stats.node_name.clone())
+        .collect::<Vec<_>>();
+    let node_types = node_stats
+        .iter()
+        .map(|stats| stats.node_type.clone())
+        .collect::<Vec<_>>();
+    let execution_counts = node_stats
+        .iter()
+        .map(|stats| stats.executions as i64)
+        .collect::<Vec<_>>();
+    let token_counts = node_stats
+        .iter()
+        .map(|stats| stats.total_token_count)
+        .collect::<Vec<_>>();
+    let costs = node_stats
+        .iter()
+        .map(|stats| stats.approximate_cost.unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    sqlx::query(
+        "INSERT INTO pipeline_node_usage (
+            pipeline_id,
+            month_start,
+            node_name,
+            node_type,
+            execution_count,
+            total_token_count,
+            approximate_cost
+        )
+        SELECT $1, date_trunc('month', now()), node_name, node_type, execution_count,
+            total_token_count, approximate_cost
+        FROM UNNEST ($2::text[], $3::text[], $4::int8[], $5::int8[], $6::float8[])
+        AS tmp_table(node_name, node_type, execution_count, total_token_count, approximate_cost)
+        ON CONFLICT (pipeline_id, month_start, node_name) DO UPDATE SET
+            node_type = EXCLUDED.node_type,
+            execution_count = pipeline_node_usage.execution_count + EXCLUDED.execution_count,
+            total_token_count = pipeline_node_usage.total_token_count
+                + EXCLUDED.total_token_count,
+            approximate_cost = pipeline_node_usage.approximate_cost + EXCLUDED.approximate_cost",
+    )
+    .bind(pipeline_id)
+    .bind(&node_names)
+    .bind(&node_types)
+    .bind(&execution_counts)
+    .bind(&token_counts)
+    .bind(&costs)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_pipeline_usage(pool: &PgPool, pipeline_id: &Uuid) -> Result<PipelineUsage> {
+    let (monthly_execution_quota, execution_count) = sqlx::query_as::<_, (Option<i64>, i64)>(
+        "SELECT
+            pipelines.monthly_execution_quota,
+            COALESCE(pipeline_execution_usage.execution_count, 0)
+        FROM pipelines
+        LEFT JOIN pipeline_execution_usage
+            ON pipeline_execution_usage.pipeline_id = pipelines.id
+            AND pipeline_execution_usage.month_start = date_trunc('month', now())
+        WHERE pipelines.id = $1",
+    )
+    .bind(pipeline_id)
+    .fetch_one(pool)
+    .await?;
+
+    let nodes = sqlx::query_as::<_, PipelineNodeUsage>(
+        "SELECT node_name, node_type, execution_count, total_token_count, approximate_cost
+        FROM pipeline_node_usage
+        WHERE pipeline_id = $1 AND month_start = date_trunc('month', now())
+        ORDER BY approximate_cost DESC, node_name",
+    )
+    .bind(pipeline_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(PipelineUsage {
+        monthly_execution_quota,
+        execution_count,
+        nodes,
+    })
+}
+
+pub async fn set_pipeline_execution_quota(
+    pool: &PgPool,
+    pipeline_id: &Uuid,
+    monthly_execution_quota: Option<i64>,
+) -> Result<()> {
+    sqlx::query("UPDATE pipelines SET monthly_execution_quota = $2 WHERE id = $1")
+        .bind(pipeline_id)
+        .bind(monthly_execution_quota)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
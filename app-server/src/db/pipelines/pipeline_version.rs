@@ -20,18 +20,6 @@ pub struct PipelineVersion {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(FromRow)]
-pub struct PipelineVersionWithPipelineName {
-    // pub id: Uuid,
-    // pub pipeline_id: Uuid,
-    pub pipeline_type: String,
-    // pub name: String,
-    // pub displayable_graph: Value,
-    // pub runnable_graph: Value,
-    // pub created_at: DateTime<Utc>,
-    pub pipeline_name: String,
-}
-
 #[derive(Serialize, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct TargetPipelineVersion {
@@ -193,6 +181,8 @@ pub async fn clone_pipeline_version_to_pipeline(
 }
 
 /// Overwrite pipeline version's graph without changing the pipeline name and type
+///
+/// Commit versions are immutable and are left unchanged.
 pub async fn overwrite_graph(
     pool: &PgPool,
     ref_pipeline_version_id: Uuid,
@@ -217,7 +207,8 @@ pub async fn overwrite_graph(
             displayable_graph = ref_version.displayable_graph,
             runnable_graph = ref_version.runnable_graph
         FROM ref_version
-            WHERE pipeline_versions.id = $2",
+            WHERE pipeline_versions.id = $2
+                AND pipeline_versions.pipeline_type = 'WORKSHOP'",
     )
     .bind(ref_pipeline_version_id)
     .bind(workshop_pipeline_version_id)
@@ -249,28 +240,6 @@ pub async fn get_pipeline_version(pool: &PgPool, version_id: &Uuid) -> Result<Pi
     Ok(version)
 }
 
-pub async fn get_pipeline_version_with_pipeline_name(
-    pool: &PgPool,
-    version_id: &Uuid,
-) -> Result<PipelineVersionWithPipelineName> {
-    let version = sqlx::query_as::<_, PipelineVersionWithPipelineName>(
-        "SELECT
-            pipeline_versions.pipeline_type,
-            pipelines.name as pipeline_name
-        FROM
-            pipeline_versions
-        JOIN
-            pipelines ON pipeline_versions.pipeline_id = pipelines.id
-        WHERE
-            pipeline_versions.id = $1",
-    )
-    .bind(version_id)
-    .fetch_one(pool)
-    .await?;
-
-    Ok(version)
-}
-
 pub async fn create_or_update_target_pipeline_version(
     pool: &PgPool,
     pipeline_id: Uuid,
@@ -328,10 +297,11 @@ pub async fn get_target_pipeline_version_by_pipeline_name(
 
 /// Update a pipeline version in the database
 ///
-/// Note: Use only for workshop pipeline versions.
+/// Note: Only workshop pipeline versions are updated, commit versions are immutable.
 pub async fn update_pipeline_version(pool: &PgPool, version: &PipelineVersion) -> Result<()> {
     sqlx::query(
-        "UPDATE pipeline_versions SET name = $1, displayable_graph = $2, runnable_graph = $3 WHERE id = $4",
+        "UPDATE pipeline_versions SET name = $1, displayable_graph = $2, runnable_graph = $3
+        WHERE id = $4 AND pipeline_type = 'WORKSHOP'",
     )
     .bind(&version.name)
     .bind(&version.displayable_graph)
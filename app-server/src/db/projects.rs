@@ -31,11 +31,12 @@ pub async fn get_all_projects_for_user(pool: &PgPool, user_id: &Uuid) -> Result<
 }
 
 pub async fn get_project(pool: &PgPool, project_id: &Uuid) -> Result<Project> {
-    let project =
-        sqlx::query_as::<_, Project>("SELECT id, name, workspace_id FROM projects WHERE id = $1")
-            .bind(project_id)
-            .fetch_one(pool)
-            .await?;
+    let project = sqlx::query_as::<_, Project>(
+        "SELECT id, name, workspace_id, slug FROM projects WHERE id = $1",
+    )
+    .bind(project_id)
+    .fetch_one(pool)
+    .await?;
 
     Ok(project)
 }
@@ -45,29 +46,72 @@ pub async fn create_project(
     user_id: &Uuid,
     name: &str,
     workspace_id: Uuid,
+    slug: Option<&str>,
 ) -> Result<Project> {
     // create project only if user is part of the workspace which owns the project
     let project = sqlx::query_as::<_, Project>(
-        "INSERT INTO projects (name, workspace_id)
+        "INSERT INTO projects (name, workspace_id, slug)
         SELECT
-            $1, $2
+            $1, $2, $4
         FROM
             members_of_workspaces
         WHERE
             workspace_id = $2 and
             user_id = $3
         RETURNING
-            id, name, workspace_id",
+            id, name, workspace_id, slug",
     )
     .bind(name)
     .bind(workspace_id)
     .bind(user_id)
+    .bind(slug)
     .fetch_one(pool)
     .await?;
 
     Ok(project)
 }
 
+pub async fn get_projects_of_workspace(pool: &PgPool, workspace_id: &Uuid) -> Result<Vec<Project>> {
+    let projects = sqlx::query_as::<_, Project>(
+        "SELECT id, name, workspace_id, slug FROM projects WHERE workspace_id = $1",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(projects)
+}
+
+/// Project with the slug in the workspace, if user is part of the workspace
+pub async fn get_project_by_slug(
+    pool: &PgPool,
+    user_id: &Uuid,
+    workspace_id: &Uuid,
+    slug: &str,
+) -> Result<Option<Project>> {
+    let project = sqlx::query_as::<_, Project>(
+        "SELECT
+            projects.id,
+            projects.name,
+            projects.workspace_id,
+            projects.slug
+        FROM
+            projects
+            JOIN members_of_workspaces ON projects.workspace_id = members_of_workspaces.workspace_id
+        WHERE
+            projects.workspace_id = $1
+            AND projects.slug = $2
+            AND members_of_workspaces.user_id = $3",
+    )
+    .bind(workspace_id)
+    .bind(slug)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(project)
+}
+
 pub async fn delete_project(pool: &PgPool, project_id: &Uuid) -> Result<()> {
     sqlx::query("DELETE FROM projects WHERE id = $1")
         .bind(project_id)
@@ -76,3 +120,101 @@ pub async fn delete_project(pool: &PgPool, project_id: &Uuid) -> Result<()> {
 
     Ok(())
 }
+
+pub async fn get_collect_client_region(pool: &PgPool, project_id: &Uuid) -> Result<bool> {
+    let collect_client_region =
+        sqlx::query_scalar::<_, bool>("SELECT collect_client_region FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
+    collect_client_region.ok_or(anyhow::anyhow!("Project not found"))
+}
+
+pub async fn update_collect_client_region(
+    pool: &PgPool,
+    project_id: &Uuid,
+    collect_client_region: bool,
+) -> Result<()> {
+    sqlx::query("UPDATE projects SET collect_client_region = $2 WHERE id = $1")
+        .bind(project_id)
+        .bind(collect_client_region)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_span_sample_rate(pool: &PgPool, project_id: &Uuid) -> Result<f64> {
+    let sample_rate =
+        sqlx::query_scalar::<_, f64>("SELECT span_sample_rate FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
+    sample_rate.ok_or(anyhow::anyhow!("Project not found"))
+}
+
+pub async fn update_span_sample_rate(
+    pool: &PgPool,
+    project_id: &Uuid,
+    sample_rate: f64,
+) -> Result<()> {
+    sqlx::query("UPDATE projects SET span_sample_rate = $2 WHERE id = $1")
+        .bind(project_id)
+        .bind(sample_rate)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_agent_checkpoint_interval(pool: &PgPool, project_id: &Uuid) -> Result<i32> {
+    let interval = sqlx::query_scalar::<_, i32>(
+        "SELECT agent_checkpoint_interval FROM projects WHERE id = $1",
+    )
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    interval.ok_or(anyhow::anyhow!("Project not found"))
+}
+
+pub async fn update_agent_checkpoint_interval(
+    pool: &PgPool,
+    project_id: &Uuid,
+    interval: i32,
+) -> Result<()> {
+    sqlx::query("UPDATE projects SET agent_checkpoint_interval = $2 WHERE id = $1")
+        .bind(project_id)
+        .bind(interval)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// None if the project keeps data for the retention of its workspace
+pub async fn get_retention_days(pool: &PgPool, project_id: &Uuid) -> Result<Option<i64>> {
+    let retention_days =
+        sqlx::query_scalar::<_, Option<i64>>("SELECT retention_days FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
+    retention_days.ok_or(anyhow::anyhow!("Project not found"))
+}
+
+pub async fn update_retention_days(
+    pool: &PgPool,
+    project_id: &Uuid,
+    retention_days: Option<i64>,
+) -> Result<()> {
+    sqlx::query("UPDATE projects SET retention_days = $2 WHERE id = $1")
+        .bind(project_id)
+        .bind(retention_days)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "backfill_status")]
+pub enum BackfillStatus {
+    PENDING,
+    RUNNING,
+    DONE,
+    FAILED,
+}
+
+/// Span attribute that is copied to a dedicated ClickHouse column, see `traces::promoted_attributes`
+#[derive(Serialize, FromRow, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PromotedAttribute {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub attribute_key: String,
+    /// Index of the ClickHouse column, `attribute_<slot>`
+    pub slot: i32,
+    pub backfill_status: BackfillStatus,
+    pub backfilled_spans: i64,
+}
+
+#[derive(FromRow)]
+pub struct SpanAttributeValue {
+    pub span_id: Uuid,
+    pub value: Value,
+}
+
+const PROMOTED_ATTRIBUTE_COLUMNS: &str =
+    "id, created_at, project_id, attribute_key, slot, backfill_status, backfilled_spans";
+
+pub async fn get_promoted_attributes(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<PromotedAttribute>> {
+    let attributes = sqlx::query_as::<_, PromotedAttribute>(&format!(
+        "SELECT {PROMOTED_ATTRIBUTE_COLUMNS}
+        FROM promoted_attributes
+        WHERE project_id = $1
+        ORDER BY slot"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(attributes)
+}
+
+/// Promotes the attribute to the lowest free slot below `max_slots`. Returns None if all slots
+/// are taken or the attribute is already promoted.
+pub async fn create_promoted_attribute(
+    pool: &PgPool,
+    project_id: &Uuid,
+    attribute_key: &str,
+    max_slots: i32,
+) -> Result<Option<PromotedAttribute>> {
+    let attribute = sqlx::query_as::<_, PromotedAttribute>(&format!(
+        "INSERT INTO promoted_attributes (project_id, attribute_key, slot)
+        SELECT $1, $2, min(slot)
+        FROM generate_series(0, $3 - 1) AS slot
+        WHERE slot NOT IN (SELECT slot FROM promoted_attributes WHERE project_id = $1)
+        HAVING min(slot) IS NOT NULL
+        ON CONFLICT DO NOTHING
+        RETURNING {PROMOTED_ATTRIBUTE_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(attribute_key)
+    .bind(max_slots)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(attribute)
+}
+
+pub async fn delete_promoted_attribute(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<PromotedAttribute>> {
+    let attribute = sqlx::query_as::<_, PromotedAttribute>(&format!(
+        "DELETE FROM promoted_attributes
+        WHERE id = $1 AND project_id = $2
+        RETURNING {PROMOTED_ATTRIBUTE_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(attribute)
+}
+
+pub async fn update_backfill_progress(
+    pool: &PgPool,
+    id: &Uuid,
+    status: BackfillStatus,
+    backfilled_spans: i64,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE promoted_attributes
+        SET backfill_status = $2, backfilled_spans = $3
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(status)
+    .bind(backfilled_spans)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Values of the attribute on the project's spans, in span id order after `after_span_id`
+pub async fn get_span_attribute_values(
+    pool: &PgPool,
+    project_id: &Uuid,
+    attribute_key: &str,
+    after_span_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<SpanAttributeValue>> {
+    let values = sqlx::query_as::<_, SpanAttributeValue>(
+        "SELECT span_id, attributes -> $2 as value
+        FROM spans
+        WHERE project_id = $1
+            AND attributes ? $2
+            AND ($3::uuid IS NULL OR span_id > $3)
+        ORDER BY span_id
+        LIMIT $4",
+    )
+    .bind(project_id)
+    .bind(attribute_key)
+    .bind(after_span_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(values)
+}
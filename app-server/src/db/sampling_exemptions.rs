@@ -0,0 +1,79 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "sampling_exemption_kind")]
+pub enum SamplingExemptionKind {
+    /// Spans of the user, by the `user_id` association property
+    USER_ID,
+    /// Spans whose path is the value or starts with it, e.g. of a pipeline under debugging
+    SPAN_PATH,
+    /// Spans exported with the value in the debug flag header, see `traces::sampling`
+    DEBUG_FLAG,
+}
+
+/// Rule for spans that are always kept, whatever the sample rate of the project
+#[derive(Serialize, FromRow, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplingExemption {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub kind: SamplingExemptionKind,
+    pub value: String,
+}
+
+pub async fn get_sampling_exemptions(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<SamplingExemption>> {
+    let exemptions = sqlx::query_as::<_, SamplingExemption>(
+        "SELECT id, created_at, project_id, kind, value
+        FROM sampling_exemptions
+        WHERE project_id = $1
+        ORDER BY created_at",
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(exemptions)
+}
+
+pub async fn create_sampling_exemption(
+    pool: &PgPool,
+    project_id: &Uuid,
+    kind: SamplingExemptionKind,
+    value: &str,
+) -> Result<SamplingExemption> {
+    let exemption = sqlx::query_as::<_, SamplingExemption>(
+        "INSERT INTO sampling_exemptions (project_id, kind, value)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (project_id, kind, value) DO UPDATE SET value = EXCLUDED.value
+        RETURNING id, created_at, project_id, kind, value",
+    )
+    .bind(project_id)
+    .bind(kind)
+    .bind(value)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(exemption)
+}
+
+pub async fn delete_sampling_exemption(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<bool> {
+    let res = sqlx::query("DELETE FROM sampling_exemptions WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
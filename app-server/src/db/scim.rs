@@ -0,0 +1,477 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    /// External id and active state are of the organization membership, since users can belong
+    /// to several organizations, each with its own identity provider
+    pub scim_external_id: Option<String>,
+    pub active: bool,
+}
+
+#[derive(Serialize, FromRow, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroup {
+    pub id: Uuid,
+    pub display_name: String,
+    pub external_id: Option<String>,
+    /// Workspace that members of this group are added to, if mapped
+    pub workspace_id: Option<Uuid>,
+    pub member_role: Option<String>,
+}
+
+#[derive(FromRow)]
+pub struct ScimGroupMember {
+    pub group_id: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+}
+
+pub async fn create_scim_token(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    hash: &str,
+    shorthand: &str,
+) -> Result<()> {
+    sqlx::query("INSERT INTO scim_tokens (organization_id, hash, shorthand) VALUES ($1, $2, $3)")
+        .bind(organization_id)
+        .bind(hash)
+        .bind(shorthand)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_organization_id_by_token_hash(pool: &PgPool, hash: &str) -> Result<Uuid> {
+    let organization_id =
+        sqlx::query_scalar::<_, Uuid>("SELECT organization_id FROM scim_tokens WHERE hash = $1")
+            .bind(hash)
+            .fetch_optional(pool)
+            .await?;
+
+    organization_id.ok_or(anyhow::anyhow!("invalid SCIM token"))
+}
+
+const SCIM_USER_COLUMNS: &str = "users.id, users.name, users.email,
+    members_of_organizations.scim_external_id, members_of_organizations.active";
+
+pub async fn get_users(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    email: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ScimUser>> {
+    let users = sqlx::query_as::<_, ScimUser>(&format!(
+        "SELECT {SCIM_USER_COLUMNS}
+        FROM users
+        JOIN members_of_organizations ON users.id = members_of_organizations.user_id
+        WHERE members_of_organizations.organization_id = $1
+            AND ($2::text IS NULL OR lower(users.email) = lower($2))
+        ORDER BY members_of_organizations.created_at ASC
+        LIMIT $3 OFFSET $4"
+    ))
+    .bind(organization_id)
+    .bind(email)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(users)
+}
+
+pub async fn count_users(pool: &PgPool, organization_id: &Uuid, email: Option<&str>) -> Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*)
+        FROM users
+        JOIN members_of_organizations ON users.id = members_of_organizations.user_id
+        WHERE members_of_organizations.organization_id = $1
+            AND ($2::text IS NULL OR lower(users.email) = lower($2))",
+    )
+    .bind(organization_id)
+    .bind(email)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+pub async fn get_user(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Option<ScimUser>> {
+    let user = sqlx::query_as::<_, ScimUser>(&format!(
+        "SELECT {SCIM_USER_COLUMNS}
+        FROM users
+        JOIN members_of_organizations ON users.id = members_of_organizations.user_id
+        WHERE members_of_organizations.organization_id = $1 AND users.id = $2"
+    ))
+    .bind(organization_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Create a user, or link an existing user with the same email, and add them to the organization.
+///
+/// Users are shared between organizations, so an existing user is only linked: their name is
+/// kept, and the external id and active state are set on the membership of this organization.
+pub async fn provision_user(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    email: &str,
+    name: &str,
+    external_id: &Option<String>,
+    active: bool,
+) -> Result<ScimUser> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("INSERT INTO users (name, email) VALUES ($1, $2) ON CONFLICT (email) DO NOTHING")
+        .bind(name)
+        .bind(email)
+        .execute(&mut *tx)
+        .await?;
+
+    let user = sqlx::query_as::<_, ScimUser>(&format!(
+        "WITH membership AS (
+            INSERT INTO members_of_organizations
+                (organization_id, user_id, member_role, scim_external_id, active)
+            SELECT $1, id, 'member'::organization_role, $3, $4 FROM users WHERE email = $2
+            ON CONFLICT (organization_id, user_id) DO UPDATE SET
+                scim_external_id = EXCLUDED.scim_external_id,
+                active = EXCLUDED.active
+            RETURNING user_id, scim_external_id, active
+        )
+        SELECT {SCIM_USER_COLUMNS}
+        FROM users
+        JOIN membership members_of_organizations ON users.id = members_of_organizations.user_id"
+    ))
+    .bind(organization_id)
+    .bind(email)
+    .bind(external_id)
+    .bind(active)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(user)
+}
+
+/// Updates the user's membership of the organization. The user itself is not changed, since it
+/// may belong to other organizations too.
+pub async fn update_user(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+    external_id: Option<&str>,
+    active: Option<bool>,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE members_of_organizations SET
+            scim_external_id = COALESCE($3, scim_external_id),
+            active = COALESCE($4, active)
+        WHERE organization_id = $1 AND user_id = $2",
+    )
+    .bind(organization_id)
+    .bind(user_id)
+    .bind(external_id)
+    .bind(active)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Remove the user from all workspaces and SCIM groups of the organization.
+/// Organization membership itself is kept, unless `remove_from_organization` is set.
+pub async fn deprovision_user(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+    remove_from_organization: bool,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "DELETE FROM members_of_workspaces
+        WHERE user_id = $2
+            AND workspace_id IN (SELECT id FROM workspaces WHERE organization_id = $1)",
+    )
+    .bind(organization_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "DELETE FROM scim_group_members
+        WHERE user_id = $2
+            AND group_id IN (SELECT id FROM scim_groups WHERE organization_id = $1)",
+    )
+    .bind(organization_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    if remove_from_organization {
+        sqlx::query(
+            "DELETE FROM members_of_organizations WHERE organization_id = $1 AND user_id = $2",
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn get_user_api_keys(pool: &PgPool, user_id: &Uuid) -> Result<Vec<String>> {
+    let api_keys = sqlx::query_scalar::<_, String>("SELECT api_key FROM api_keys WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(api_keys)
+}
+
+const SCIM_GROUP_COLUMNS: &str =
+    "id, display_name, external_id, workspace_id, member_role::text as member_role";
+
+pub async fn create_group(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    display_name: &str,
+    external_id: &Option<String>,
+) -> Result<ScimGroup> {
+    let group = sqlx::query_as::<_, ScimGroup>(&format!(
+        "INSERT INTO scim_groups (organization_id, display_name, external_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (organization_id, display_name) DO UPDATE SET external_id = EXCLUDED.external_id
+        RETURNING {SCIM_GROUP_COLUMNS}"
+    ))
+    .bind(organization_id)
+    .bind(display_name)
+    .bind(external_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(group)
+}
+
+pub async fn get_groups(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    display_name: Option<&str>,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<Vec<ScimGroup>> {
+    let groups = sqlx::query_as::<_, ScimGroup>(&format!(
+        "SELECT {SCIM_GROUP_COLUMNS}
+        FROM scim_groups
+        WHERE organization_id = $1 AND ($2::text IS NULL OR display_name = $2)
+        ORDER BY created_at ASC
+        LIMIT $3 OFFSET $4"
+    ))
+    .bind(organization_id)
+    .bind(display_name)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(groups)
+}
+
+pub async fn count_groups(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    display_name: Option<&str>,
+) -> Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*)
+        FROM scim_groups
+        WHERE organization_id = $1 AND ($2::text IS NULL OR display_name = $2)",
+    )
+    .bind(organization_id)
+    .bind(display_name)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+pub async fn get_group(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Option<ScimGroup>> {
+    let group = sqlx::query_as::<_, ScimGroup>(&format!(
+        "SELECT {SCIM_GROUP_COLUMNS}
+        FROM scim_groups
+        WHERE organization_id = $1 AND id = $2"
+    ))
+    .bind(organization_id)
+    .bind(group_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(group)
+}
+
+pub async fn delete_group(pool: &PgPool, organization_id: &Uuid, group_id: &Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM scim_groups WHERE organization_id = $1 AND id = $2")
+        .bind(organization_id)
+        .bind(group_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_group_members(pool: &PgPool, group_ids: &Vec<Uuid>) -> Result<Vec<ScimGroupMember>> {
+    let members = sqlx::query_as::<_, ScimGroupMember>(
+        "SELECT scim_group_members.group_id, users.id as user_id, users.email
+        FROM scim_group_members
+        JOIN users ON scim_group_members.user_id = users.id
+        WHERE scim_group_members.group_id = ANY($1)
+        ORDER BY scim_group_members.created_at ASC",
+    )
+    .bind(group_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(members)
+}
+
+/// Add members to the group, and to the mapped workspace, if any.
+/// Only users that belong to the organization are added. Workspace memberships that weren't
+/// provisioned by SCIM keep their role, and owners are never changed.
+pub async fn add_group_members(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    group: &ScimGroup,
+    user_ids: &Vec<Uuid>,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO scim_group_members (group_id, user_id)
+        SELECT $1, user_id FROM members_of_organizations
+        WHERE organization_id = $2 AND user_id = ANY($3)
+        ON CONFLICT (group_id, user_id) DO NOTHING",
+    )
+    .bind(group.id)
+    .bind(organization_id)
+    .bind(user_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    if let (Some(workspace_id), Some(member_role)) = (group.workspace_id, &group.member_role) {
+        sqlx::query(
+            "INSERT INTO members_of_workspaces
+                (workspace_id, user_id, member_role, scim_provisioned)
+            SELECT $1, user_id, $2::workspace_role, true FROM scim_group_members
+            WHERE group_id = $3 AND user_id = ANY($4)
+            ON CONFLICT (workspace_id, user_id) DO UPDATE SET member_role = EXCLUDED.member_role
+            WHERE members_of_workspaces.scim_provisioned
+                AND members_of_workspaces.member_role <> 'owner'",
+        )
+        .bind(workspace_id)
+        .bind(member_role)
+        .bind(group.id)
+        .bind(user_ids)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Remove members from the group, and from the mapped workspace, if any. Only workspace
+/// memberships provisioned by SCIM are removed, and only if no other group of the user grants
+/// the workspace.
+pub async fn remove_group_members(
+    pool: &PgPool,
+    group: &ScimGroup,
+    user_ids: &Vec<Uuid>,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM scim_group_members WHERE group_id = $1 AND user_id = ANY($2)")
+        .bind(group.id)
+        .bind(user_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    if let Some(workspace_id) = group.workspace_id {
+        sqlx::query(
+            "DELETE FROM members_of_workspaces
+            WHERE workspace_id = $1 AND user_id = ANY($2) AND scim_provisioned
+                AND NOT EXISTS (
+                    SELECT 1 FROM scim_group_members
+                    JOIN scim_groups ON scim_groups.id = scim_group_members.group_id
+                    WHERE scim_groups.workspace_id = members_of_workspaces.workspace_id
+                        AND scim_group_members.user_id = members_of_workspaces.user_id
+                )",
+        )
+        .bind(workspace_id)
+        .bind(user_ids)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Map the group to a workspace role and add all current group members to the workspace
+pub async fn map_group_to_workspace(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    group_id: &Uuid,
+    workspace_id: &Uuid,
+    member_role: &str,
+) -> Result<ScimGroup> {
+    let group = sqlx::query_as::<_, ScimGroup>(&format!(
+        "UPDATE scim_groups
+        SET workspace_id = $3, member_role = $4::workspace_role
+        WHERE organization_id = $1 AND id = $2
+            AND $3 IN (SELECT id FROM workspaces WHERE organization_id = $1)
+        RETURNING {SCIM_GROUP_COLUMNS}"
+    ))
+    .bind(organization_id)
+    .bind(group_id)
+    .bind(workspace_id)
+    .bind(member_role)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(anyhow::anyhow!(
+        "SCIM group or workspace not found in organization"
+    ))?;
+
+    let user_ids = get_group_members(pool, &vec![group.id])
+        .await?
+        .into_iter()
+        .map(|member| member.user_id)
+        .collect::<Vec<_>>();
+    add_group_members(pool, organization_id, &group, &user_ids).await?;
+
+    Ok(group)
+}
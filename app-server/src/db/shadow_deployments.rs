@@ -0,0 +1,235 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "shadow_verdict")]
+pub enum ShadowVerdict {
+    CANDIDATE,
+    PRODUCTION,
+    TIE,
+}
+
+#[derive(Serialize, FromRow, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowDeployment {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub name: String,
+    /// Path of the production LLM spans that are re-executed
+    pub span_path: String,
+    /// Model in the format of "provider:model_name"
+    pub candidate_model: String,
+    /// Replaces the system message of the production request, if set
+    pub candidate_system_prompt: Option<String>,
+    /// Percentage of spans, from 0 to 100, that are re-executed
+    pub sample_percentage: f64,
+    pub active: bool,
+}
+
+pub struct NewShadowDeployment {
+    pub name: String,
+    pub span_path: String,
+    pub candidate_model: String,
+    pub candidate_system_prompt: Option<String>,
+    pub sample_percentage: f64,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowComparison {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub span_id: Uuid,
+    pub trace_id: Uuid,
+    pub candidate_output: String,
+    pub verdict: ShadowVerdict,
+    pub reasoning: String,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowComparisonStats {
+    pub total: i64,
+    pub candidate_wins: i64,
+    pub production_wins: i64,
+    pub ties: i64,
+}
+
+const SHADOW_DEPLOYMENT_COLUMNS: &str = "id, created_at, project_id, name, span_path, \
+    candidate_model, candidate_system_prompt, sample_percentage, active";
+
+pub async fn create_shadow_deployment(
+    pool: &PgPool,
+    project_id: &Uuid,
+    deployment: &NewShadowDeployment,
+) -> Result<ShadowDeployment> {
+    let deployment = sqlx::query_as::<_, ShadowDeployment>(&format!(
+        "INSERT INTO shadow_deployments
+            (project_id, name, span_path, candidate_model, candidate_system_prompt, sample_percentage)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING {SHADOW_DEPLOYMENT_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(&deployment.name)
+    .bind(&deployment.span_path)
+    .bind(&deployment.candidate_model)
+    .bind(&deployment.candidate_system_prompt)
+    .bind(deployment.sample_percentage)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(deployment)
+}
+
+pub async fn get_shadow_deployments(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<ShadowDeployment>> {
+    let deployments = sqlx::query_as::<_, ShadowDeployment>(&format!(
+        "SELECT {SHADOW_DEPLOYMENT_COLUMNS}
+        FROM shadow_deployments
+        WHERE project_id = $1
+        ORDER BY created_at DESC"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deployments)
+}
+
+pub async fn get_shadow_deployment(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<ShadowDeployment>> {
+    let deployment = sqlx::query_as::<_, ShadowDeployment>(&format!(
+        "SELECT {SHADOW_DEPLOYMENT_COLUMNS}
+        FROM shadow_deployments
+        WHERE id = $1 AND project_id = $2"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(deployment)
+}
+
+pub async fn get_active_shadow_deployments_for_path(
+    pool: &PgPool,
+    project_id: &Uuid,
+    span_path: &str,
+) -> Result<Vec<ShadowDeployment>> {
+    let deployments = sqlx::query_as::<_, ShadowDeployment>(&format!(
+        "SELECT {SHADOW_DEPLOYMENT_COLUMNS}
+        FROM shadow_deployments
+        WHERE project_id = $1 AND span_path = $2 AND active"
+    ))
+    .bind(project_id)
+    .bind(span_path)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deployments)
+}
+
+pub async fn set_shadow_deployment_active(
+    pool: &PgPool,
+    project_id: &Uuid,
+    id: &Uuid,
+    active: bool,
+) -> Result<Option<ShadowDeployment>> {
+    let deployment = sqlx::query_as::<_, ShadowDeployment>(&format!(
+        "UPDATE shadow_deployments SET active = $3
+        WHERE id = $1 AND project_id = $2
+        RETURNING {SHADOW_DEPLOYMENT_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(project_id)
+    .bind(active)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(deployment)
+}
+
+pub async fn delete_shadow_deployment(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM shadow_deployments WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn record_shadow_comparison(
+    pool: &PgPool,
+    deployment_id: &Uuid,
+    span_id: &Uuid,
+    trace_id: &Uuid,
+    candidate_output: &str,
+    verdict: ShadowVerdict,
+    reasoning: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO shadow_comparisons
+            (deployment_id, span_id, trace_id, candidate_output, verdict, reasoning)
+        VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(deployment_id)
+    .bind(span_id)
+    .bind(trace_id)
+    .bind(candidate_output)
+    .bind(verdict)
+    .bind(reasoning)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_shadow_comparison_stats(
+    pool: &PgPool,
+    deployment_id: &Uuid,
+) -> Result<ShadowComparisonStats> {
+    let stats = sqlx::query_as::<_, ShadowComparisonStats>(
+        "SELECT
+            count(*) as total,
+            count(*) FILTER (WHERE verdict = 'CANDIDATE') as candidate_wins,
+            count(*) FILTER (WHERE verdict = 'PRODUCTION') as production_wins,
+            count(*) FILTER (WHERE verdict = 'TIE') as ties
+        FROM shadow_comparisons
+        WHERE deployment_id = $1",
+    )
+    .bind(deployment_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(stats)
+}
+
+pub async fn get_recent_shadow_comparisons(
+    pool: &PgPool,
+    deployment_id: &Uuid,
+    limit: i64,
+) -> Result<Vec<ShadowComparison>> {
+    let comparisons = sqlx::query_as::<_, ShadowComparison>(
+        "SELECT id, created_at, span_id, trace_id, candidate_output, verdict, reasoning
+        FROM shadow_comparisons
+        WHERE deployment_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2",
+    )
+    .bind(deployment_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(comparisons)
+}
@@ -1,10 +1,13 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use futures::stream::BoxStream;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{FromRow, PgPool, Postgres};
 use uuid::Uuid;
 
+use super::field_visibility::FieldVisibility;
+
 const PREVIEW_CHARACTERS: usize = 50;
 
 #[derive(sqlx::Type, Deserialize, Serialize, PartialEq, Clone, Debug, Default)]
@@ -130,6 +133,7 @@ pub async fn get_trace_spans(
     trace_id: Uuid,
     project_id: Uuid,
     search: Option<String>,
+    visibility: &FieldVisibility,
 ) -> Result<Vec<Span>> {
     let mut query = sqlx::QueryBuilder::<Postgres>::new(
         "WITH span_events AS (
@@ -180,8 +184,14 @@ pub async fn get_trace_spans(
                 spans.end_time,
                 spans.version,
                 spans.trace_id,
-                spans.input,
-                spans.output,
+                CASE WHEN ",
+    );
+    query
+        .push_bind(visibility.span_input)
+        .push(" THEN spans.input END AS input, CASE WHEN ")
+        .push_bind(visibility.span_output)
+        .push(
+            " THEN spans.output END AS output,
                 spans.parent_span_id,
                 spans.name,
                 spans.attributes,
@@ -192,7 +202,7 @@ pub async fn get_trace_spans(
             LEFT JOIN span_events ON spans.span_id = span_events.span_id AND span_events.project_id = spans.project_id
             LEFT JOIN span_labels ON spans.span_id = span_labels.span_id AND span_labels.project_id = spans.project_id
             WHERE spans.trace_id = ",
-    );
+        );
     query.push_bind(trace_id);
     query.push(" AND spans.project_id = ");
     query.push_bind(project_id);
@@ -203,6 +213,7 @@ pub async fn get_trace_spans(
     );
 
     if let Some(search) = search {
+        // hidden fields are null in spans_info, so they never match
         query
             .push(" AND (input::TEXT ILIKE ")
             .push_bind(format!("%{search}%"))
@@ -221,7 +232,12 @@ pub async fn get_trace_spans(
     Ok(spans)
 }
 
-pub async fn get_span(pool: &PgPool, id: Uuid, project_id: Uuid) -> Result<Span> {
+pub async fn get_span(
+    pool: &PgPool,
+    id: Uuid,
+    project_id: Uuid,
+    visibility: &FieldVisibility,
+) -> Result<Span> {
     let span = sqlx::query_as::<_, Span>(
         "SELECT
             span_id,
@@ -232,8 +248,8 @@ pub async fn get_span(pool: &PgPool, id: Uuid, project_id: Uuid) -> Result<Span>
             parent_span_id,
             name,
             attributes,
-            input,
-            output,
+            CASE WHEN $3 THEN input END AS input,
+            CASE WHEN $4 THEN output END AS output,
             span_type,
             '[]'::jsonb as events,
             '[]'::jsonb as labels
@@ -242,8 +258,87 @@ pub async fn get_span(pool: &PgPool, id: Uuid, project_id: Uuid) -> Result<Span>
     )
     .bind(id)
     .bind(project_id)
+    .bind(visibility.span_input)
+    .bind(visibility.span_output)
     .fetch_one(pool)
     .await?;
 
     Ok(span)
 }
+
+/// Spans of the project that started in [start_time, end_time), oldest first, without loading
+/// them all into memory
+pub fn stream_spans(
+    pool: &PgPool,
+    project_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    visibility: FieldVisibility,
+) -> BoxStream<'_, Result<Span, sqlx::Error>> {
+    sqlx::query_as::<_, Span>(
+        "SELECT
+            span_id,
+            start_time,
+            end_time,
+            version,
+            trace_id,
+            parent_span_id,
+            name,
+            attributes,
+            CASE WHEN $4 THEN input END AS input,
+            CASE WHEN $5 THEN output END AS output,
+            span_type,
+            '[]'::jsonb as events,
+            '[]'::jsonb as labels
+        FROM spans
+        WHERE project_id = $1 AND start_time >= $2 AND start_time < $3
+        ORDER BY start_time ASC",
+    )
+    .bind(project_id)
+    .bind(start_time)
+    .bind(end_time)
+    .bind(visibility.span_input)
+    .bind(visibility.span_output)
+    .fetch(pool)
+}
+
+/// Most recent spans at `path` with an input, newest first
+pub async fn get_recent_spans_for_path(
+    pool: &PgPool,
+    project_id: Uuid,
+    path: &str,
+    start_time: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Span>> {
+    let spans = sqlx::query_as::<_, Span>(
+        "SELECT
+            span_id,
+            start_time,
+            end_time,
+            version,
+            trace_id,
+            parent_span_id,
+            name,
+            attributes,
+            input,
+            output,
+            span_type,
+            '[]'::jsonb as events,
+            '[]'::jsonb as labels
+        FROM spans
+        WHERE project_id = $1
+            AND attributes ->> 'lmnr.span.path' = $2
+            AND start_time >= $3
+            AND input IS NOT NULL
+        ORDER BY start_time DESC
+        LIMIT $4",
+    )
+    .bind(project_id)
+    .bind(path)
+    .bind(start_time)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(spans)
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "tagged_resource_type")]
+pub enum TaggedResourceType {
+    DATASET,
+    EVALUATION,
+    PIPELINE,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TaggedResource {
+    pub resource_type: TaggedResourceType,
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+/// Taggable resources of the project $1. Tags are not deleted with their resources, so queries
+/// join with this to skip tags of deleted resources.
+const PROJECT_RESOURCES: &str = "
+    SELECT 'DATASET'::tagged_resource_type AS resource_type, id, name, created_at
+    FROM datasets WHERE project_id = $1
+    UNION ALL
+    SELECT 'EVALUATION'::tagged_resource_type AS resource_type, id, name, created_at
+    FROM evaluations WHERE project_id = $1
+    UNION ALL
+    SELECT 'PIPELINE'::tagged_resource_type AS resource_type, id, name, created_at
+    FROM pipelines WHERE project_id = $1";
+
+pub async fn resource_exists(
+    pool: &PgPool,
+    project_id: &Uuid,
+    resource_type: TaggedResourceType,
+    resource_id: &Uuid,
+) -> Result<bool> {
+    let exists = sqlx::query_scalar::<_, bool>(&format!(
+        "SELECT EXISTS (
+            SELECT 1 FROM ({PROJECT_RESOURCES}) resources
+            WHERE resource_type = $2 AND id = $3
+        )"
+    ))
+    .bind(project_id)
+    .bind(resource_type)
+    .bind(resource_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(exists)
+}
+
+/// Replaces the tags of the resource
+pub async fn set_resource_tags(
+    pool: &PgPool,
+    project_id: &Uuid,
+    resource_type: TaggedResourceType,
+    resource_id: &Uuid,
+    tags: &Vec<String>,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM resource_tags WHERE resource_type = $1 AND resource_id = $2")
+        .bind(resource_type)
+        .bind(resource_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO resource_tags (project_id, resource_type, resource_id, tag)
+        SELECT $1, $2, $3, tag FROM UNNEST($4::text[]) AS tag
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(project_id)
+    .bind(resource_type)
+    .bind(resource_id)
+    .bind(tags)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn get_resource_tags(
+    pool: &PgPool,
+    project_id: &Uuid,
+    resource_type: TaggedResourceType,
+    resource_id: &Uuid,
+) -> Result<Vec<String>> {
+    let tags = sqlx::query_scalar::<_, String>(
+        "SELECT tag FROM resource_tags
+        WHERE project_id = $1 AND resource_type = $2 AND resource_id = $3
+        ORDER BY tag",
+    )
+    .bind(project_id)
+    .bind(resource_type)
+    .bind(resource_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tags)
+}
+
+/// All tags in use in the project, with the number of resources tagged with each
+pub async fn get_project_tags(pool: &PgPool, project_id: &Uuid) -> Result<Vec<TagCount>> {
+    let tags = sqlx::query_as::<_, TagCount>(&format!(
+        "SELECT resource_tags.tag, COUNT(*) AS count
+        FROM resource_tags
+        JOIN ({PROJECT_RESOURCES}) resources
+            ON resources.resource_type = resource_tags.resource_type
+            AND resources.id = resource_tags.resource_id
+        WHERE resource_tags.project_id = $1
+        GROUP BY resource_tags.tag
+        ORDER BY resource_tags.tag"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tags)
+}
+
+/// Resources of all types tagged with the tag, newest first
+pub async fn get_tagged_resources(
+    pool: &PgPool,
+    project_id: &Uuid,
+    tag: &str,
+    resource_type: Option<TaggedResourceType>,
+) -> Result<Vec<TaggedResource>> {
+    let resources = sqlx::query_as::<_, TaggedResource>(&format!(
+        "SELECT
+            resources.resource_type,
+            resources.id,
+            resources.name,
+            resources.created_at,
+            ARRAY(
+                SELECT all_tags.tag FROM resource_tags all_tags
+                WHERE all_tags.resource_type = resources.resource_type
+                AND all_tags.resource_id = resources.id
+                ORDER BY all_tags.tag
+            ) AS tags
+        FROM ({PROJECT_RESOURCES}) resources
+        JOIN resource_tags
+            ON resource_tags.resource_type = resources.resource_type
+            AND resource_tags.resource_id = resources.id
+        WHERE
+            resource_tags.project_id = $1
+            AND resource_tags.tag = $2
+            AND ($3::tagged_resource_type IS NULL OR resources.resource_type = $3)
+        ORDER BY resources.created_at DESC"
+    ))
+    .bind(project_id)
+    .bind(tag)
+    .bind(resource_type)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(resources)
+}
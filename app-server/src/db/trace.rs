@@ -3,6 +3,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
@@ -11,6 +12,7 @@ use crate::{
 };
 
 use super::{
+    field_visibility::FieldVisibility,
     modifiers::{Filter, FilterOperator},
     utils::add_date_range_to_query,
 };
@@ -18,7 +20,7 @@ use super::{
 pub const DEFAULT_VERSION: &str = "0.1.0";
 
 /// Helper struct to pass current trace info, if exists, if pipeline is called from remote trace context
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CurrentTraceAndSpan {
     pub trace_id: Uuid,
@@ -50,7 +52,7 @@ pub struct Trace {
     // Laminar customers' release version
     release: Option<String>,
     // User id of Laminar customers' user
-    user_id: Option<String>,
+    pub user_id: Option<String>,
     session_id: Option<String>,
     metadata: Option<Value>,
     input_token_count: i64,
@@ -61,6 +63,11 @@ pub struct Trace {
     cost: f64,
     success: bool,
     project_id: Uuid,
+    sdk_language: Option<String>,
+    sdk_version: Option<String>,
+    environment: Option<String>,
+    /// Country code of the client, if the project collects client regions
+    region: Option<String>,
 }
 
 #[derive(Serialize, sqlx::FromRow)]
@@ -74,7 +81,7 @@ pub struct TraceWithTopSpan {
     // Laminar customers' release version
     release: Option<String>,
     // User id of Laminar customers' user
-    user_id: Option<String>,
+    pub user_id: Option<String>,
     session_id: Option<String>,
     metadata: Option<Value>,
     input_token_count: i64,
@@ -85,9 +92,14 @@ pub struct TraceWithTopSpan {
     cost: f64,
     success: bool,
     project_id: Uuid,
-
-    top_span_input_preview: Option<String>,
-    top_span_output_preview: Option<String>,
+    sdk_language: Option<String>,
+    sdk_version: Option<String>,
+    environment: Option<String>,
+    /// Country code of the client, if the project collects client regions
+    region: Option<String>,
+
+    pub top_span_input_preview: Option<String>,
+    pub top_span_output_preview: Option<String>,
     top_span_name: Option<String>,
     top_span_type: Option<SpanType>,
     top_span_path: Option<String>,
@@ -120,7 +132,12 @@ pub async fn update_trace_attributes(
             version,
             session_id,
             user_id,
-            trace_type
+            trace_type,
+            sdk_language,
+            sdk_version,
+            environment,
+            region,
+            external_id
         )
         VALUES (
             $1,
@@ -137,7 +154,12 @@ pub async fn update_trace_attributes(
             $12,
             $13,
             $14,
-            COALESCE($15, 'DEFAULT'::trace_type)
+            COALESCE($15, 'DEFAULT'::trace_type),
+            $16,
+            $17,
+            $18,
+            $19,
+            $20
         )
         ON CONFLICT(id) DO
         UPDATE
@@ -153,7 +175,12 @@ pub async fn update_trace_attributes(
             end_time = CASE WHEN traces.end_time IS NULL OR traces.end_time < $11 THEN $11 ELSE traces.end_time END,
             session_id = CASE WHEN traces.session_id IS NULL THEN $13 ELSE traces.session_id END,
             user_id = CASE WHEN traces.user_id IS NULL THEN $14 ELSE traces.user_id END,
-            trace_type = CASE WHEN $15 IS NULL THEN traces.trace_type ELSE COALESCE($15, 'DEFAULT'::trace_type) END
+            trace_type = CASE WHEN $15 IS NULL THEN traces.trace_type ELSE COALESCE($15, 'DEFAULT'::trace_type) END,
+            sdk_language = COALESCE(traces.sdk_language, $16),
+            sdk_version = COALESCE(traces.sdk_version, $17),
+            environment = COALESCE(traces.environment, $18),
+            region = COALESCE(traces.region, $19),
+            external_id = COALESCE(traces.external_id, $20)
         "
     )
     .bind(attributes.id)
@@ -171,6 +198,11 @@ pub async fn update_trace_attributes(
     .bind(&attributes.session_id)
     .bind(&attributes.user_id)
     .bind(&attributes.trace_type)
+    .bind(&attributes.client_metadata.sdk_language)
+    .bind(&attributes.client_metadata.sdk_version)
+    .bind(&attributes.client_metadata.environment)
+    .bind(&attributes.client_metadata.region)
+    .bind(&attributes.external_id)
     .execute(pool)
     .await?;
     Ok(())
@@ -201,6 +233,10 @@ fn add_traces_info_expression(
             output_cost,
             cost,
             success,
+            sdk_language,
+            sdk_version,
+            environment,
+            region,
             trace_type,
             top_level_spans.input_preview top_span_input_preview,
             top_level_spans.output_preview top_span_output_preview,
@@ -256,6 +292,7 @@ fn add_text_join(
     query: &mut QueryBuilder<Postgres>,
     date_range: &Option<DateRange>,
     text_search_filter: &String,
+    visibility: &FieldVisibility,
 ) -> Result<()> {
     query.push(
         "
@@ -265,15 +302,21 @@ fn add_text_join(
             WHERE ",
     );
     query
-        .push("(input::TEXT ILIKE ")
-        .push_bind(format!("%{text_search_filter}%"))
-        .push(" OR output::TEXT ILIKE ")
-        .push_bind(format!("%{text_search_filter}%"))
-        .push(" OR name::TEXT ILIKE ")
+        .push("(name::TEXT ILIKE ")
         .push_bind(format!("%{text_search_filter}%"))
         .push(" OR attributes::TEXT ILIKE ")
-        .push_bind(format!("%{text_search_filter}%"))
-        .push(")");
+        .push_bind(format!("%{text_search_filter}%"));
+    if visibility.span_input {
+        query
+            .push(" OR input::TEXT ILIKE ")
+            .push_bind(format!("%{text_search_filter}%"));
+    }
+    if visibility.span_output {
+        query
+            .push(" OR output::TEXT ILIKE ")
+            .push_bind(format!("%{text_search_filter}%"));
+    }
+    query.push(")");
 
     add_date_range_to_query(query, date_range, "start_time", Some("end_time"))?;
 
@@ -281,7 +324,11 @@ fn add_text_join(
     Ok(())
 }
 
-fn add_filters_to_traces_query(query: &mut QueryBuilder<Postgres>, filters: &Option<Vec<Filter>>) {
+fn add_filters_to_traces_query(
+    query: &mut QueryBuilder<Postgres>,
+    filters: &Option<Vec<Filter>>,
+    visibility: &FieldVisibility,
+) {
     if let Some(filters) = filters {
         filters.iter().for_each(|filter| {
             let filter_value_str = match &filter.filter_value {
@@ -292,6 +339,17 @@ fn add_filters_to_traces_query(query: &mut QueryBuilder<Postgres>, filters: &Opt
                 log::warn!("Invalid column name: {}", filter.filter_column);
                 return;
             }
+            // filtering on a hidden field would reveal its values
+            let hidden = match filter.filter_column.as_str() {
+                "user_id" => !visibility.user_id,
+                "top_span_input_preview" => !visibility.span_input,
+                "top_span_output_preview" => !visibility.span_output,
+                _ => false,
+            };
+            if hidden {
+                log::warn!("Filter on hidden column: {}", filter.filter_column);
+                return;
+            }
             if filter.filter_column.starts_with("event.") {
                 let template_name = filter.filter_column.strip_prefix("event.").unwrap();
                 filter_by_event_value(
@@ -395,6 +453,7 @@ pub async fn get_traces(
     filters: &Option<Vec<Filter>>,
     date_range: &Option<DateRange>,
     text_search_filter: Option<String>,
+    visibility: &FieldVisibility,
 ) -> Result<Vec<TraceWithTopSpan>> {
     let mut query = QueryBuilder::<Postgres>::new("WITH ");
     add_traces_info_expression(&mut query, date_range, project_id)?;
@@ -408,7 +467,12 @@ pub async fn get_traces(
             end_time,
             version,
             release,
-            user_id,
+            CASE WHEN ",
+    );
+    query
+        .push_bind(visibility.user_id)
+        .push(
+            " THEN user_id END AS user_id,
             session_id,
             metadata,
             project_id,
@@ -419,21 +483,30 @@ pub async fn get_traces(
             output_cost,
             cost,
             success,
-            top_span_input_preview,
-            top_span_output_preview,
+            sdk_language,
+            sdk_version,
+            environment,
+            region,
+            CASE WHEN ",
+        )
+        .push_bind(visibility.span_input)
+        .push(" THEN top_span_input_preview END AS top_span_input_preview, CASE WHEN ")
+        .push_bind(visibility.span_output)
+        .push(
+            " THEN top_span_output_preview END AS top_span_output_preview,
             top_span_name,
             top_span_type,
             top_span_path,
             status
         FROM traces_info ",
-    );
+        );
     if let Some(search) = text_search_filter {
-        add_text_join(&mut query, date_range, &search)?;
+        add_text_join(&mut query, date_range, &search, visibility)?;
     }
     query.push(" WHERE project_id = ");
     query.push_bind(project_id);
 
-    add_filters_to_traces_query(&mut query, &filters);
+    add_filters_to_traces_query(&mut query, &filters, visibility);
 
     query
         .push(" ORDER BY start_time DESC, id OFFSET ")
@@ -456,6 +529,7 @@ pub async fn count_traces(
     filters: &Option<Vec<Filter>>,
     date_range: &Option<DateRange>,
     text_search_filter: Option<String>,
+    visibility: &FieldVisibility,
 ) -> Result<i64> {
     let mut query = QueryBuilder::<Postgres>::new(
         "WITH traces_info AS (
@@ -476,6 +550,10 @@ pub async fn count_traces(
         output_cost,
         cost,
         success,
+        sdk_language,
+        sdk_version,
+        environment,
+        region,
         trace_type,
         EXTRACT(EPOCH FROM (end_time - start_time)) as latency,
         CASE WHEN success = true THEN 'Success' ELSE 'Failed' END status
@@ -490,13 +568,13 @@ pub async fn count_traces(
         ",
     );
     if let Some(search) = text_search_filter {
-        add_text_join(&mut query, date_range, &search)?;
+        add_text_join(&mut query, date_range, &search, visibility)?;
     }
     query.push(" WHERE project_id = ");
     query.push_bind(project_id);
     add_date_range_to_query(&mut query, date_range, "start_time", Some("end_time"))?;
 
-    add_filters_to_traces_query(&mut query, &filters);
+    add_filters_to_traces_query(&mut query, &filters, visibility);
 
     let count = query
         .build_query_as::<'_, TotalCount>()
@@ -507,7 +585,11 @@ pub async fn count_traces(
     Ok(count)
 }
 
-pub async fn get_single_trace(pool: &PgPool, id: Uuid) -> Result<Trace> {
+pub async fn get_single_trace(
+    pool: &PgPool,
+    id: Uuid,
+    visibility: &FieldVisibility,
+) -> Result<Trace> {
     let trace = sqlx::query_as::<_, Trace>(
         "SELECT
             id,
@@ -515,7 +597,7 @@ pub async fn get_single_trace(pool: &PgPool, id: Uuid) -> Result<Trace> {
             end_time,
             version,
             release,
-            user_id,
+            CASE WHEN $2 THEN user_id END AS user_id,
             session_id,
             metadata,
             project_id,
@@ -525,18 +607,69 @@ pub async fn get_single_trace(pool: &PgPool, id: Uuid) -> Result<Trace> {
             input_cost,
             output_cost,
             cost,
-            success
+            success,
+            sdk_language,
+            sdk_version,
+            environment,
+            region
         FROM traces
         WHERE id = $1
         AND start_time IS NOT NULL AND end_time IS NOT NULL",
     )
     .bind(id)
+    .bind(visibility.user_id)
     .fetch_one(pool)
     .await?;
 
     Ok(trace)
 }
 
+/// Traces of the project with the external id, the most recent first
+pub async fn get_traces_by_external_id(
+    pool: &PgPool,
+    project_id: Uuid,
+    external_id: &str,
+    visibility: &FieldVisibility,
+    limit: i64,
+) -> Result<Vec<Trace>> {
+    let traces = sqlx::query_as::<_, Trace>(
+        "SELECT
+            id,
+            start_time,
+            end_time,
+            version,
+            release,
+            CASE WHEN $3 THEN user_id END AS user_id,
+            session_id,
+            metadata,
+            project_id,
+            input_token_count,
+            output_token_count,
+            total_token_count,
+            input_cost,
+            output_cost,
+            cost,
+            success,
+            sdk_language,
+            sdk_version,
+            environment,
+            region
+        FROM traces
+        WHERE project_id = $1 AND external_id = $2
+        AND start_time IS NOT NULL AND end_time IS NOT NULL
+        ORDER BY start_time DESC
+        LIMIT $4",
+    )
+    .bind(project_id)
+    .bind(external_id)
+    .bind(visibility.user_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(traces)
+}
+
 #[derive(Serialize, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct Session {
@@ -560,6 +693,7 @@ pub async fn get_sessions(
     offset: usize,
     filters: &Option<Vec<Filter>>,
     date_range: &Option<DateRange>,
+    visibility: &FieldVisibility,
 ) -> Result<Vec<Session>> {
     let mut query = sqlx::QueryBuilder::new(
         "SELECT
@@ -581,7 +715,7 @@ pub async fn get_sessions(
 
     add_date_range_to_query(&mut query, date_range, "start_time", Some("end_time"))?;
 
-    add_filters_to_traces_query(&mut query, filters);
+    add_filters_to_traces_query(&mut query, filters, visibility);
 
     query
         .push(" GROUP BY session_id ORDER BY start_time DESC")
@@ -600,6 +734,7 @@ pub async fn count_sessions(
     project_id: Uuid,
     filters: &Option<Vec<Filter>>,
     date_range: &Option<DateRange>,
+    visibility: &FieldVisibility,
 ) -> Result<i64> {
     let mut query = sqlx::QueryBuilder::new(
         "SELECT
@@ -612,7 +747,7 @@ pub async fn count_sessions(
 
     add_date_range_to_query(&mut query, date_range, "start_time", Some("end_time"))?;
 
-    add_filters_to_traces_query(&mut query, filters);
+    add_filters_to_traces_query(&mut query, filters, visibility);
 
     let count = query
         .build_query_as::<'_, TotalCount>()
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn create_session(
+    pool: &PgPool,
+    user_id: &Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<Uuid> {
+    let id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO ui_sessions (user_id, expires_at) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(user_id)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id)
+}
+
+/// Returns the session expiration time, if the session exists and is neither expired nor revoked
+pub async fn get_active_session_expiry(
+    pool: &PgPool,
+    session_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Option<DateTime<Utc>>> {
+    let expires_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+        "SELECT expires_at FROM ui_sessions
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > now()",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(expires_at)
+}
+
+pub async fn revoke_session(pool: &PgPool, session_id: &Uuid, user_id: &Uuid) -> Result<()> {
+    sqlx::query(
+        "UPDATE ui_sessions SET revoked_at = now()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
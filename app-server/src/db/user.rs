@@ -126,6 +126,11 @@ pub async fn get_user_from_api_key(
         FROM
             users u
             left join members_of_workspaces mo on u.id = mo.user_id
+                AND mo.workspace_id NOT IN (
+                    SELECT w.id FROM workspaces w
+                    JOIN members_of_organizations mog ON w.organization_id = mog.organization_id
+                    WHERE mog.user_id = u.id AND NOT mog.active
+                )
             left join projects p on mo.workspace_id = p.workspace_id
             left join api_keys ak on u.id = ak.user_id
         WHERE
@@ -149,6 +154,39 @@ pub async fn get_user_from_api_key(
     }
 }
 
+/// Same as `get_user_from_api_key`, but for requests authenticated with a personal access token.
+/// Not cached, so that revoked and expired tokens stop working immediately.
+pub async fn get_user_with_projects(pool: &PgPool, user_id: &Uuid) -> Result<User> {
+    let user = sqlx::query_as::<_, User>(
+        "SELECT
+            u.id as id,
+            u.name as name,
+            u.email as email,
+            array_remove(array_agg(p.id), null) as project_ids,
+            null::text as api_key
+        FROM
+            users u
+            left join members_of_workspaces mo on u.id = mo.user_id
+                AND mo.workspace_id NOT IN (
+                    SELECT w.id FROM workspaces w
+                    JOIN members_of_organizations mog ON w.organization_id = mog.organization_id
+                    WHERE mog.user_id = u.id AND NOT mog.active
+                )
+            left join projects p on mo.workspace_id = p.workspace_id
+        WHERE
+            u.id = $1
+        GROUP BY
+            u.id,
+            u.name,
+            u.email",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    user.ok_or(anyhow::anyhow!("No user found"))
+}
+
 pub async fn get_api_key_for_user_from_email(pool: &PgPool, email: &String) -> Option<String> {
     match sqlx::query_as::<_, ApiKey>(
         "SELECT api_key, user_id, name
@@ -9,6 +9,17 @@ use crate::opentelemetry::opentelemetry_proto_common_v1;
 
 use super::modifiers::DateRange;
 
+/// Slugs are lowercase alphanumeric words separated by single dashes, at most 63 characters
+pub fn is_valid_slug(slug: &str) -> bool {
+    slug.len() <= 63
+        && slug.split('-').all(|word| {
+            !word.is_empty()
+                && word
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+        })
+}
+
 pub fn generate_random_key() -> String {
     Alphanumeric.sample_string(&mut rand::thread_rng(), 64)
 }
@@ -0,0 +1,181 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "warehouse_destination")]
+pub enum WarehouseDestination {
+    BIGQUERY,
+    SNOWFLAKE,
+}
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "warehouse_sync_mode")]
+pub enum WarehouseSyncMode {
+    /// Hourly span and score metrics
+    AGGREGATED,
+    /// Every span and score
+    RAW,
+}
+
+/// Hourly sync of a project's data to a cloud warehouse, see `warehouse_sync`
+#[derive(FromRow, Clone)]
+pub struct WarehouseSync {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub destination: WarehouseDestination,
+    pub mode: WarehouseSyncMode,
+    /// Destination settings without secrets, e.g. `warehouse_sync::bigquery::BigQueryConfig`
+    pub config: Value,
+    pub credentials_nonce: String,
+    pub credentials_value: String,
+    /// End of the last synced hour
+    pub synced_until: DateTime<Utc>,
+}
+
+/// Sync without its credentials, as shown to users
+#[derive(Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WarehouseSyncInfo {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub destination: WarehouseDestination,
+    pub mode: WarehouseSyncMode,
+    pub config: Value,
+    pub enabled: bool,
+    pub synced_until: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+pub struct NewWarehouseSync {
+    pub destination: WarehouseDestination,
+    pub mode: WarehouseSyncMode,
+    pub config: Value,
+    pub credentials_nonce: String,
+    pub credentials_value: String,
+    pub enabled: bool,
+    pub synced_until: DateTime<Utc>,
+}
+
+const WAREHOUSE_SYNC_COLUMNS: &str = "id, project_id, destination, mode, config,
+    credentials_nonce, credentials_value, synced_until";
+
+const WAREHOUSE_SYNC_INFO_COLUMNS: &str = "id, created_at, destination, mode, config, enabled,
+    synced_until, last_run_at, last_error";
+
+/// Creates or replaces the project's sync to the destination. The progress of an existing sync
+/// is kept, unless its mode changes, as the loaded tables are different then.
+pub async fn set_warehouse_sync(
+    pool: &PgPool,
+    project_id: &Uuid,
+    sync: &NewWarehouseSync,
+) -> Result<WarehouseSyncInfo> {
+    let info = sqlx::query_as::<_, WarehouseSyncInfo>(&format!(
+        "INSERT INTO warehouse_syncs (project_id, destination, mode, config, credentials_nonce,
+            credentials_value, enabled, synced_until)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (project_id, destination) DO UPDATE SET
+            mode = EXCLUDED.mode,
+            config = EXCLUDED.config,
+            credentials_nonce = EXCLUDED.credentials_nonce,
+            credentials_value = EXCLUDED.credentials_value,
+            enabled = EXCLUDED.enabled,
+            synced_until = CASE
+                WHEN warehouse_syncs.mode = EXCLUDED.mode THEN warehouse_syncs.synced_until
+                ELSE EXCLUDED.synced_until
+            END,
+            last_error = NULL
+        RETURNING {WAREHOUSE_SYNC_INFO_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(sync.destination)
+    .bind(sync.mode)
+    .bind(&sync.config)
+    .bind(&sync.credentials_nonce)
+    .bind(&sync.credentials_value)
+    .bind(sync.enabled)
+    .bind(sync.synced_until)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(info)
+}
+
+pub async fn get_warehouse_syncs(
+    pool: &PgPool,
+    project_id: &Uuid,
+) -> Result<Vec<WarehouseSyncInfo>> {
+    let syncs = sqlx::query_as::<_, WarehouseSyncInfo>(&format!(
+        "SELECT {WAREHOUSE_SYNC_INFO_COLUMNS}
+        FROM warehouse_syncs
+        WHERE project_id = $1
+        ORDER BY created_at"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(syncs)
+}
+
+pub async fn delete_warehouse_sync(pool: &PgPool, project_id: &Uuid, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM warehouse_syncs WHERE id = $1 AND project_id = $2")
+        .bind(id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Enabled syncs with an hour that ended more than `delay_seconds` ago and that no instance
+/// started within `lease_seconds`. Returned syncs count as started, so that each is run by one
+/// instance only.
+pub async fn claim_due_warehouse_syncs(
+    pool: &PgPool,
+    delay_seconds: i64,
+    lease_seconds: i64,
+) -> Result<Vec<WarehouseSync>> {
+    let syncs = sqlx::query_as::<_, WarehouseSync>(&format!(
+        "UPDATE warehouse_syncs SET last_run_at = now()
+        WHERE enabled
+            AND synced_until + interval '1 hour' + make_interval(secs => $1) <= now()
+            AND (last_run_at IS NULL OR last_run_at < now() - make_interval(secs => $2))
+        RETURNING {WAREHOUSE_SYNC_COLUMNS}"
+    ))
+    .bind(delay_seconds as f64)
+    .bind(lease_seconds as f64)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(syncs)
+}
+
+/// Records a synced hour and clears the error of the previous run
+pub async fn update_synced_until(
+    pool: &PgPool,
+    id: &Uuid,
+    synced_until: DateTime<Utc>,
+) -> Result<()> {
+    sqlx::query("UPDATE warehouse_syncs SET synced_until = $2, last_error = NULL WHERE id = $1")
+        .bind(id)
+        .bind(synced_until)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn record_warehouse_sync_error(pool: &PgPool, id: &Uuid, error: &str) -> Result<()> {
+    sqlx::query("UPDATE warehouse_syncs SET last_error = $2 WHERE id = $1")
+        .bind(id)
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -4,7 +4,7 @@ use uuid::Uuid;
 
 use crate::projects::Project;
 
-use super::stats::create_usage_stats_for_workspace;
+use super::{field_visibility::WorkspaceRole, stats::create_usage_stats_for_workspace};
 
 #[derive(Deserialize, Serialize, FromRow)]
 #[serde(rename_all = "camelCase")]
@@ -13,6 +13,9 @@ pub struct Workspace {
     pub name: String,
     pub tier_name: String,
     pub is_free_tier: bool,
+    /// Used by automation to provision workspaces idempotently
+    #[sqlx(default)]
+    pub slug: Option<String>,
 }
 
 // create an error type with multiple variants
@@ -70,7 +73,8 @@ pub async fn get_all_workspaces_of_user(
             "select
                 projects.id,
                 projects.name,
-                projects.workspace_id
+                projects.workspace_id,
+                projects.slug
             from
                 projects
             where
@@ -114,17 +118,51 @@ pub async fn get_owned_workspaces(pool: &PgPool, user_id: &Uuid) -> anyhow::Resu
     Ok(workspaces)
 }
 
+/// Workspace with the slug among the workspaces the user is a member of
+pub async fn get_workspace_of_user_by_slug(
+    pool: &PgPool,
+    user_id: &Uuid,
+    slug: &str,
+) -> anyhow::Result<Option<Workspace>> {
+    let workspace = sqlx::query_as::<_, Workspace>(
+        "SELECT
+            workspaces.id,
+            workspaces.name,
+            subscription_tiers.name as tier_name,
+            tier_id = 1 as is_free_tier,
+            workspaces.slug
+        FROM
+            workspaces
+            join members_of_workspaces on workspaces.id = members_of_workspaces.workspace_id
+            join subscription_tiers on workspaces.tier_id = subscription_tiers.id
+        WHERE
+            members_of_workspaces.user_id = $1
+            AND workspaces.slug = $2
+        ORDER BY
+            workspaces.created_at
+        LIMIT 1",
+    )
+    .bind(user_id)
+    .bind(slug)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(workspace)
+}
+
 pub async fn create_new_workspace(
     pool: &PgPool,
     id: Uuid,
     name: String,
+    slug: Option<String>,
 ) -> anyhow::Result<Workspace> {
     let workspace = sqlx::query_as::<_, Workspace>(
-        "INSERT INTO workspaces (id, name) VALUES ($1, $2)
-        RETURNING id, name, 'Free' as tier_name, true as is_free_tier",
+        "INSERT INTO workspaces (id, name, slug) VALUES ($1, $2, $3)
+        RETURNING id, name, 'Free' as tier_name, true as is_free_tier, slug",
     )
     .bind(id)
     .bind(name)
+    .bind(slug)
     .fetch_one(pool)
     .await?;
 
@@ -155,14 +193,16 @@ pub async fn add_user_to_workspace_by_email(
     pool: &PgPool,
     user_email: &str,
     workspace_id: &Uuid,
+    role: WorkspaceRole,
 ) -> anyhow::Result<()> {
     sqlx::query(
         "INSERT INTO members_of_workspaces (user_id, workspace_id, member_role)
-        SELECT id, $2 as workspace_id, 'member'::workspace_role FROM users
+        SELECT id, $2 as workspace_id, $3 FROM users
         WHERE users.email = $1",
     )
     .bind(user_email)
     .bind(workspace_id)
+    .bind(role)
     .execute(pool)
     .await
     .map_err(|e| WorkspaceError::UnhandledError(e.into()))?;
@@ -265,3 +305,52 @@ pub async fn get_user_api_keys_in_workspace(
 
     Ok(records.iter().map(|r| r.api_key.clone()).collect())
 }
+
+pub async fn get_ip_allowlist(pool: &PgPool, workspace_id: &Uuid) -> anyhow::Result<Vec<String>> {
+    let ip_allowlist =
+        sqlx::query_scalar::<_, Vec<String>>("SELECT ip_allowlist FROM workspaces WHERE id = $1")
+            .bind(workspace_id)
+            .fetch_optional(pool)
+            .await?;
+
+    ip_allowlist.ok_or(anyhow::anyhow!("Workspace not found"))
+}
+
+pub async fn update_ip_allowlist(
+    pool: &PgPool,
+    workspace_id: &Uuid,
+    ip_allowlist: &Vec<String>,
+) -> anyhow::Result<()> {
+    sqlx::query("UPDATE workspaces SET ip_allowlist = $2 WHERE id = $1")
+        .bind(workspace_id)
+        .bind(ip_allowlist)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// None if the workspace keeps data for the retention of its subscription tier
+pub async fn get_retention_days(pool: &PgPool, workspace_id: &Uuid) -> anyhow::Result<Option<i64>> {
+    let retention_days =
+        sqlx::query_scalar::<_, Option<i64>>("SELECT retention_days FROM workspaces WHERE id = $1")
+            .bind(workspace_id)
+            .fetch_optional(pool)
+            .await?;
+
+    retention_days.ok_or(anyhow::anyhow!("Workspace not found"))
+}
+
+pub async fn update_retention_days(
+    pool: &PgPool,
+    workspace_id: &Uuid,
+    retention_days: Option<i64>,
+) -> anyhow::Result<()> {
+    sqlx::query("UPDATE workspaces SET retention_days = $2 WHERE id = $1")
+        .bind(workspace_id)
+        .bind(retention_days)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
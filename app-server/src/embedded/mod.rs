@@ -0,0 +1,229 @@
+//! Embedded mode for laptops and CI: the app-server starts its own Postgres, applies the
+//! frontend's migrations and seed data, and creates a local project, so that traces and
+//! evaluations can be sent without docker-compose.
+//!
+//! Compiled with the `embedded` cargo feature and enabled with `EMBEDDED_POSTGRES=true`.
+//! Postgres binaries are downloaded on first start, data is kept in `EMBEDDED_DATA_DIR`.
+//! Run it together with `ENVIRONMENT=LITE`: instead of ClickHouse, analytics are kept in a SQLite
+//! database next to the data directory, see `analytics::embedded`.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use include_dir::{include_dir, Dir};
+use postgresql_embedded::{PostgreSQL, Settings};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    db::{self, utils::generate_random_key},
+    ids,
+    routes::api_keys::hash_api_key,
+};
+
+static MIGRATIONS: Dir = include_dir!("$CARGO_MANIFEST_DIR/../frontend/lib/db/migrations");
+const SEED: &str = include_str!("../../../frontend/lib/db/seed.json");
+
+const DATABASE_NAME: &str = "laminar";
+const STATEMENT_BREAKPOINT: &str = "--> statement-breakpoint";
+
+pub fn is_enabled() -> bool {
+    std::env::var("EMBEDDED_POSTGRES").is_ok_and(|v| v == "true")
+}
+
+pub fn data_dir() -> PathBuf {
+    PathBuf::from(std::env::var("EMBEDDED_DATA_DIR").unwrap_or(".laminar/postgres".to_string()))
+}
+
+/// SQLite database of the embedded analytics store
+pub fn analytics_path() -> PathBuf {
+    data_dir().with_file_name("analytics.sqlite")
+}
+
+/// Starts Postgres and returns it with its connection url. Postgres stops when it is dropped.
+/// Without a data directory, the data is deleted when Postgres stops.
+pub async fn start_postgres(data_dir: Option<PathBuf>) -> Result<(PostgreSQL, String)> {
+    let settings = match data_dir {
+        Some(data_dir) => Settings {
+            data_dir,
+            // the data directory outlives the process, so the password must be stable
+            password: "laminar".to_string(),
+            temporary: false,
+            ..Default::default()
+        },
+        None => Settings::default(),
+    };
+
+    let mut postgres = PostgreSQL::new(settings);
+    postgres.setup().await?;
+    postgres.start().await?;
+    if !postgres.database_exists(DATABASE_NAME).await? {
+        postgres.create_database(DATABASE_NAME).await?;
+    }
+    let url = postgres.settings().url(DATABASE_NAME);
+    log::info!(
+        "Started embedded Postgres on port {}",
+        postgres.settings().port
+    );
+
+    Ok((postgres, url))
+}
+
+#[derive(Deserialize)]
+struct Journal {
+    entries: Vec<JournalEntry>,
+}
+
+#[derive(Deserialize)]
+struct JournalEntry {
+    tag: String,
+}
+
+/// Applies the drizzle migrations in journal order. Applied migrations are tracked in
+/// `embedded_migrations`, separately from drizzle's own bookkeeping.
+pub async fn migrate(pool: &PgPool) -> Result<()> {
+    sqlx::query("CREATE TABLE IF NOT EXISTS embedded_migrations (tag text PRIMARY KEY)")
+        .execute(pool)
+        .await?;
+
+    let journal = MIGRATIONS
+        .get_file("meta/_journal.json")
+        .and_then(|file| file.contents_utf8())
+        .context("Migrations journal not found")?;
+    let journal = serde_json::from_str::<Journal>(journal)?;
+
+    for entry in journal.entries {
+        let applied = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM embedded_migrations WHERE tag = $1)",
+        )
+        .bind(&entry.tag)
+        .fetch_one(pool)
+        .await?;
+        if applied {
+            continue;
+        }
+
+        let sql = MIGRATIONS
+            .get_file(format!("{}.sql", entry.tag))
+            .and_then(|file| file.contents_utf8())
+            .context(format!("Migration {} not found", entry.tag))?;
+
+        let mut transaction = pool.begin().await?;
+        for statement in sql.split(STATEMENT_BREAKPOINT) {
+            if statement.trim().is_empty() {
+                continue;
+            }
+            sqlx::raw_sql(statement)
+                .execute(&mut *transaction)
+                .await
+                .context(format!("Failed to apply migration {}", entry.tag))?;
+        }
+        sqlx::query("INSERT INTO embedded_migrations (tag) VALUES ($1)")
+            .bind(&entry.tag)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        log::info!("Applied migration {}", entry.tag);
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct SeedEntry {
+    table: String,
+    data: Vec<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Inserts the same seed data as the frontend does for a local database
+pub async fn seed(pool: &PgPool) -> Result<()> {
+    let seeded = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM subscription_tiers)")
+        .fetch_one(pool)
+        .await?;
+    if seeded {
+        return Ok(());
+    }
+
+    for entry in serde_json::from_str::<Vec<SeedEntry>>(SEED)? {
+        let Some(first_row) = entry.data.first() else {
+            continue;
+        };
+        let columns = first_row.keys().cloned().collect::<Vec<_>>().join(", ");
+        let query = format!(
+            "INSERT INTO {table} ({columns})
+            SELECT {columns} FROM json_populate_recordset(NULL::{table}, $1)",
+            table = entry.table,
+        );
+        sqlx::query(&query)
+            .bind(serde_json::Value::from(entry.data))
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Creates a local user, workspace and project on first start. The api key of the project is
+/// `EMBEDDED_PROJECT_API_KEY` if set, otherwise a generated key that is logged once.
+pub async fn ensure_local_project(pool: &PgPool) -> Result<()> {
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM projects)")
+        .fetch_one(pool)
+        .await?;
+    if exists {
+        return Ok(());
+    }
+
+    let (value, generated) = match std::env::var("EMBEDDED_PROJECT_API_KEY") {
+        Ok(value) if value.len() >= 16 => (value, false),
+        Ok(_) => {
+            return Err(anyhow::anyhow!(
+                "EMBEDDED_PROJECT_API_KEY must be at least 16 characters"
+            ))
+        }
+        Err(_) => (generate_random_key(), true),
+    };
+    let project_id = create_local_project(pool, &value).await?;
+
+    if generated {
+        log::info!(
+            "Created local project {} with api key {}, it will not be shown again",
+            project_id,
+            value
+        );
+    } else {
+        log::info!("Created local project {}", project_id);
+    }
+
+    Ok(())
+}
+
+/// Creates a user, workspace and project, with the given api key for the project
+pub async fn create_local_project(pool: &PgPool, api_key: &str) -> Result<Uuid> {
+    let user_id = ids::new_id();
+    db::user::write_user(
+        pool,
+        &user_id,
+        &"local@localhost".to_string(),
+        &"Local".to_string(),
+    )
+    .await?;
+    let workspace =
+        db::workspace::create_new_workspace(pool, ids::new_id(), "Local".to_string(), None)
+            .await?;
+    db::workspace::add_owner_to_workspace(pool, &user_id, &workspace.id).await?;
+    let project =
+        db::projects::create_project(pool, &user_id, "Local", workspace.id, None).await?;
+
+    let shorthand = format!("{}...{}", &api_key[..4], &api_key[api_key.len() - 4..]);
+    db::project_api_keys::create_project_api_key(
+        pool,
+        &project.id,
+        &Some("local".to_string()),
+        &hash_api_key(api_key),
+        &shorthand,
+    )
+    .await?;
+
+    Ok(project.id)
+}
@@ -0,0 +1,98 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::pipeline::RunBudget;
+
+/// Steps, cost and time used by a run, shared by its tasks
+pub struct BudgetTracker {
+    budget: RunBudget,
+    start_time: Instant,
+    steps: AtomicU32,
+    cost: Mutex<f64>,
+}
+
+impl BudgetTracker {
+    pub fn new(budget: RunBudget) -> Self {
+        Self {
+            budget,
+            start_time: Instant::now(),
+            steps: AtomicU32::new(0),
+            cost: Mutex::new(0.0),
+        }
+    }
+
+    /// Counts a step about to run. Returns why the run must stop instead, if any of the limits
+    /// is reached.
+    pub fn start_step(&self) -> Option<String> {
+        let steps = self.steps.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(max_steps) = self.budget.max_steps {
+            if steps > max_steps {
+                return Some(format!("Run budget exceeded: more than {max_steps} steps"));
+            }
+        }
+        if let Some(max_cost) = self.budget.max_cost {
+            let cost = *self.cost.lock().unwrap();
+            if cost >= max_cost {
+                return Some(format!(
+                    "Run budget exceeded: cost ${cost:.4} reached the limit of ${max_cost}"
+                ));
+            }
+        }
+        if self.remaining_duration() == Some(Duration::ZERO) {
+            return Some(self.duration_exceeded());
+        }
+        None
+    }
+
+    /// Adds the approximate cost of a finished step, unknown costs count as zero
+    pub fn add_cost(&self, cost: Option<f64>) {
+        *self.cost.lock().unwrap() += cost.unwrap_or(0.0);
+    }
+
+    /// Time left until the wall-clock limit, steps running over it are stopped
+    pub fn remaining_duration(&self) -> Option<Duration> {
+        self.budget
+            .max_duration_seconds
+            .map(|max_duration_seconds| {
+                Duration::from_secs(max_duration_seconds).saturating_sub(self.start_time.elapsed())
+            })
+    }
+
+    pub fn duration_exceeded(&self) -> String {
+        format!(
+            "Run budget exceeded: run took longer than {} seconds",
+            self.budget.max_duration_seconds.unwrap_or_default()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_tracker() {
+        let tracker = BudgetTracker::new(RunBudget {
+            max_steps: Some(2),
+            max_cost: Some(0.1),
+            max_duration_seconds: None,
+        });
+        assert!(tracker.start_step().is_none());
+        tracker.add_cost(Some(0.15));
+        assert!(tracker.start_step().unwrap().contains("cost"));
+
+        let tracker = BudgetTracker::new(RunBudget {
+            max_steps: Some(2),
+            ..Default::default()
+        });
+        assert!(tracker.start_step().is_none());
+        assert!(tracker.start_step().is_none());
+        assert!(tracker.start_step().unwrap().contains("steps"));
+        assert!(tracker.remaining_duration().is_none());
+    }
+}
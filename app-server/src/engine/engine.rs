@@ -1,12 +1,14 @@
 use crate::{
     engine::{
-        task::{State, Task},
+        budget::BudgetTracker,
+        task::{Action, State, Task},
         RunOutput,
     },
     pipeline::{
         context::Context,
         nodes::{BreakpointChunk, Message, NodeInput, NodeStreamChunk, NodeStreamEnd, StreamChunk},
-        trace::MetaLog,
+        trace::{message_usage, MetaLog},
+        NodeExecutionOptions,
     },
     routes::pipelines::GraphInterruptMessage,
 };
@@ -18,12 +20,15 @@ use serde::Serialize;
 use std::{
     collections::{HashMap, HashSet},
     panic::AssertUnwindSafe,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::task::JoinHandle;
 use uuid::Uuid;
 
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
 pub struct Engine {
     /// Store all tasks.
     tasks: Arc<DashMap<Uuid, Arc<Task>>>,
@@ -45,6 +50,10 @@ pub struct Engine {
     control_semaphore: Arc<tokio::sync::Semaphore>,
     /// Tasks which will stop the execution of the graph and wait until continue signal is received.
     breakpoint_task_ids: Arc<DashSet<Uuid>>,
+    /// Limits of the run, None if it's unlimited.
+    budget: Option<Arc<BudgetTracker>>,
+    /// Why the run was stopped before it finished, e.g. its budget was exceeded.
+    termination: Arc<Mutex<Option<String>>>,
 }
 
 #[derive(Debug)]
@@ -59,6 +68,8 @@ enum ScheduledTask {
 pub struct EngineOutput {
     pub output_message_ids: Vec<Uuid>,
     pub messages: HashMap<Uuid, Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub termination: Option<String>,
 }
 
 impl EngineOutput {
@@ -75,6 +86,10 @@ impl EngineOutput {
 
 impl Engine {
     fn new(context: Context) -> Engine {
+        let budget = context
+            .budget
+            .clone()
+            .map(|budget| Arc::new(BudgetTracker::new(budget)));
         Engine {
             tasks: Arc::new(DashMap::new()),
             active_tasks: Arc::new(DashSet::new()),
@@ -86,6 +101,8 @@ impl Engine {
             handles: Arc::new(DashMap::new()),
             control_semaphore: Arc::new(tokio::sync::Semaphore::new(20)),
             breakpoint_task_ids: Arc::new(DashSet::new()),
+            budget,
+            termination: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -246,6 +263,10 @@ impl Engine {
                                 .execute_task(task, task_send.clone(), stream_send.clone())
                                 .await
                             {
+                                Ok(_) if self.termination.lock().unwrap().is_some() => {
+                                    self.handles.iter().for_each(|handle| handle.abort());
+                                    return Err(self.get_outputs());
+                                }
                                 Ok(_) => {
                                     // if all active tasks are done, break
                                     // idle tasks often follow conditional nodes and will never get an input
@@ -312,6 +333,9 @@ impl Engine {
         let node_messages = self.node_messages.clone();
         let control_semaphore = self.control_semaphore.clone();
         let breakpoint_task_ids = self.breakpoint_task_ids.clone();
+        let execution_options = task.execution_options.clone();
+        let budget = self.budget.clone();
+        let termination = self.termination.clone();
 
         tokio::spawn(async move {
             // acquire semaphore to control the number of active tasks
@@ -341,6 +365,47 @@ impl Engine {
                 control_semaphore.forget_permits(control_semaphore.available_permits());
             }
 
+            // stop the graph instead of running the task once the budget is used up
+            if let Some(reason) = budget.as_ref().and_then(|budget| budget.start_step()) {
+                debug!("{}, terminating graph", reason);
+
+                let msg_id = Uuid::new_v4();
+                let now = Utc::now();
+
+                let error = Message {
+                    id: msg_id,
+                    value: reason.clone().into(),
+                    node_id: action.node_id(),
+                    node_name: action.node_name(),
+                    node_type: action.node_type(),
+                    input_message_ids,
+                    meta_log: None,
+                    retries: 0,
+                    start_time: now,
+                    end_time: now,
+                };
+
+                if let Some(stream_send) = stream_send {
+                    let stream_chunk = StreamChunk::NodeEnd(NodeStreamEnd {
+                        message: error.clone(),
+                    });
+
+                    stream_send.send(stream_chunk).await.unwrap();
+                }
+
+                output_ids.insert(msg_id);
+                node_messages.insert(msg_id, error);
+                termination.lock().unwrap().get_or_insert(reason);
+                idle_tasks.remove(&task_id);
+                active_tasks.remove(&task_id);
+
+                task_send.send(ScheduledTask::Err).await.unwrap();
+
+                // release semaphore
+                drop(control_permit);
+                return;
+            }
+
             let start_time = Utc::now();
 
             let mut id = Uuid::new_v4();
@@ -358,10 +423,24 @@ impl Engine {
                 stream_send.send(stream_chunk).await.unwrap();
             }
 
-            match AssertUnwindSafe(action.run(inputs, context))
-                .catch_unwind()
-                .await
-            {
+            let run = run_with_retries(&action, inputs, context, &execution_options);
+            let remaining_duration = budget
+                .as_ref()
+                .and_then(|budget| budget.remaining_duration());
+            let (result, retries) = match (remaining_duration, budget.as_ref()) {
+                (Some(remaining_duration), Some(budget)) => {
+                    match tokio::time::timeout(remaining_duration, run).await {
+                        Ok(result) => result,
+                        Err(_) => {
+                            let reason = budget.duration_exceeded();
+                            termination.lock().unwrap().get_or_insert(reason.clone());
+                            (Ok(Err(anyhow::anyhow!(reason))), 0)
+                        }
+                    }
+                }
+                _ => run.await,
+            };
+            match result {
                 Err(_) => {
                     debug!("Execution failed [id: {}]", task_id);
                     let msg_id = Uuid::new_v4();
@@ -373,6 +452,7 @@ impl Engine {
                         node_type: action.node_type(),
                         input_message_ids,
                         meta_log: None,
+                        retries,
                         start_time,
                         end_time: Utc::now(),
                     };
@@ -411,9 +491,13 @@ impl Engine {
                                         node_type: action.node_type(),
                                         input_message_ids: input_message_ids.clone(),
                                         meta_log,
+                                        retries,
                                         start_time,
                                         end_time: Utc::now(),
                                     };
+                                    if let Some(budget) = &budget {
+                                        budget.add_cost(message_usage(&message).1);
+                                    }
                                     node_messages.insert(id, message.clone());
 
                                     State::new(message)
@@ -469,6 +553,7 @@ impl Engine {
                                     node_type: action.node_type(),
                                     input_message_ids: input_message_ids.clone(),
                                     meta_log: None,
+                                    retries,
                                     start_time,
                                     end_time: Utc::now(),
                                 };
@@ -564,6 +649,7 @@ impl Engine {
                                 node_type: action.node_type(),
                                 input_message_ids,
                                 meta_log: None,
+                                retries,
                                 start_time,
                                 end_time: Utc::now(),
                             };
@@ -600,6 +686,46 @@ impl Engine {
                 .map(|entry| (entry.key().to_owned(), entry.value().to_owned()))
                 .collect(),
             output_message_ids: self.output_ids.as_ref().clone().into_iter().collect(),
+            termination: self.termination.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// Run the node, retrying failed and timed out runs as configured for it. Panics are not
+/// retried. Returns the result of the last run and the number of retries.
+async fn run_with_retries(
+    action: &Action,
+    inputs: HashMap<String, NodeInput>,
+    context: Arc<Context>,
+    execution_options: &NodeExecutionOptions,
+) -> (std::thread::Result<anyhow::Result<RunOutput>>, u32) {
+    let mut retries = 0;
+    loop {
+        let run = AssertUnwindSafe(action.run(inputs.clone(), context.clone())).catch_unwind();
+        let result = match execution_options.timeout_seconds {
+            Some(timeout_seconds) => {
+                match tokio::time::timeout(Duration::from_secs(timeout_seconds), run).await {
+                    Ok(result) => result,
+                    Err(_) => Ok(Err(anyhow::anyhow!(
+                        "Node timed out after {} seconds",
+                        timeout_seconds
+                    ))),
+                }
+            }
+            None => run.await,
+        };
+        match result {
+            Ok(Err(e)) if retries < execution_options.max_retries => {
+                retries += 1;
+                debug!(
+                    "Retrying node {} after error, retry {}: {}",
+                    action.node_name(),
+                    retries,
+                    e
+                );
+                tokio::time::sleep(RETRY_BACKOFF * retries).await;
+            }
+            result => return (result, retries),
         }
     }
 }
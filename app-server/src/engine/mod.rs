@@ -4,5 +4,6 @@ extern crate tokio;
 pub use engine::Engine;
 pub use task::{RunOutput, RunnableNode, Task};
 
+pub mod budget;
 pub mod engine;
 pub mod task;
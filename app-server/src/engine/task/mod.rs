@@ -29,6 +29,7 @@ use std::{collections::HashMap, fmt::Debug, sync::Arc};
 pub use self::action::{Action, RunOutput, RunnableNode};
 pub(crate) use self::state::ExecState;
 pub use self::state::State;
+use crate::pipeline::NodeExecutionOptions;
 use uuid::Uuid;
 
 mod action;
@@ -54,6 +55,7 @@ pub struct Task {
     pub next: Vec<Uuid>,
     /// Map from input handle name to input state.
     pub input_states: HashMap<String, Arc<ExecState>>,
+    pub execution_options: NodeExecutionOptions,
 }
 
 impl Task {
@@ -74,6 +76,7 @@ impl Task {
             prev: Vec::new(),
             next: Vec::new(),
             input_states: inputs,
+            execution_options: NodeExecutionOptions::default(),
         }
     }
 
@@ -0,0 +1,216 @@
+//! Agreement between the annotators that scored the same spans, to tell whether a labeling rubric
+//! is applied consistently.
+//!
+//! Categorical scores are compared with Cohen's kappa, averaged over each pair of annotators that
+//! scored common spans. Numeric scores are compared with Krippendorff's alpha on the interval
+//! scale, which also works when each span is scored by a different subset of the annotators.
+//! Both are 1 for perfect agreement and 0 for agreement expected by chance.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::ch::span_scores::AnnotatorSpanScore;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum AgreementMetric {
+    CohensKappa,
+    KrippendorffsAlpha,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreAgreement {
+    pub name: String,
+    pub metric: AgreementMetric,
+    /// None if there are too few scores, or if all of them are the same, so that agreement by
+    /// chance can't be told apart
+    pub value: Option<f64>,
+    /// Spans scored by at least two annotators
+    pub item_count: usize,
+    pub annotator_count: usize,
+}
+
+/// Agreement of the scores of one score name
+pub fn score_agreement(
+    name: String,
+    metric: AgreementMetric,
+    scores: &[AnnotatorSpanScore],
+) -> ScoreAgreement {
+    let mut by_span = HashMap::<Uuid, HashMap<Uuid, f64>>::new();
+    for score in scores {
+        by_span
+            .entry(score.span_id)
+            .or_default()
+            .insert(score.user_id, score.value);
+    }
+    // Spans scored once don't tell anything about agreement
+    by_span.retain(|_, ratings| ratings.len() >= 2);
+
+    let mut annotators = by_span
+        .values()
+        .flat_map(|ratings| ratings.keys().copied())
+        .collect::<Vec<_>>();
+    annotators.sort();
+    annotators.dedup();
+
+    let value = match metric {
+        AgreementMetric::CohensKappa => average_cohens_kappa(&by_span, &annotators),
+        AgreementMetric::KrippendorffsAlpha => krippendorffs_alpha(&by_span),
+    };
+
+    ScoreAgreement {
+        name,
+        metric,
+        value,
+        item_count: by_span.len(),
+        annotator_count: annotators.len(),
+    }
+}
+
+fn average_cohens_kappa(
+    by_span: &HashMap<Uuid, HashMap<Uuid, f64>>,
+    annotators: &[Uuid],
+) -> Option<f64> {
+    let mut kappas = Vec::new();
+    for (i, first) in annotators.iter().enumerate() {
+        for second in &annotators[i + 1..] {
+            let pairs = by_span
+                .values()
+                .filter_map(|ratings| Some((*ratings.get(first)?, *ratings.get(second)?)))
+                .collect::<Vec<_>>();
+            if let Some(kappa) = cohens_kappa(&pairs) {
+                kappas.push(kappa);
+            }
+        }
+    }
+
+    (!kappas.is_empty()).then(|| kappas.iter().sum::<f64>() / kappas.len() as f64)
+}
+
+fn cohens_kappa(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.is_empty() {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let observed = pairs.iter().filter(|(a, b)| a == b).count() as f64 / n;
+
+    // Categories are compared by their bits, as scores are the indices of the label values
+    let mut marginals = BTreeMap::<u64, (f64, f64)>::new();
+    for (a, b) in pairs {
+        marginals.entry(a.to_bits()).or_default().0 += 1.0;
+        marginals.entry(b.to_bits()).or_default().1 += 1.0;
+    }
+    let expected = marginals
+        .values()
+        .map(|(a, b)| (a / n) * (b / n))
+        .sum::<f64>();
+    if expected >= 1.0 {
+        return None;
+    }
+
+    Some((observed - expected) / (1.0 - expected))
+}
+
+/// Sum of the squared differences between each ordered pair of the values
+fn sum_of_squared_differences(values: &[f64]) -> f64 {
+    let count = values.len() as f64;
+    let sum = values.iter().sum::<f64>();
+    let sum_of_squares = values.iter().map(|v| v * v).sum::<f64>();
+    2.0 * (count * sum_of_squares - sum * sum)
+}
+
+fn krippendorffs_alpha(by_span: &HashMap<Uuid, HashMap<Uuid, f64>>) -> Option<f64> {
+    let units = by_span
+        .values()
+        .map(|ratings| ratings.values().copied().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let all_values = units.iter().flatten().copied().collect::<Vec<_>>();
+    let n = all_values.len() as f64;
+    if n < 2.0 {
+        return None;
+    }
+
+    let observed = units
+        .iter()
+        .map(|values| sum_of_squared_differences(values) / (values.len() as f64 - 1.0))
+        .sum::<f64>()
+        / n;
+    let expected = sum_of_squared_differences(&all_values) / (n * (n - 1.0));
+    if expected <= 0.0 {
+        return None;
+    }
+
+    Some(1.0 - observed / expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotator_scores(ratings: &[&[f64]]) -> Vec<AnnotatorSpanScore> {
+        let annotators = (0..3).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+        ratings
+            .iter()
+            .flat_map(|values| {
+                let span_id = Uuid::new_v4();
+                values
+                    .iter()
+                    .zip(&annotators)
+                    .map(move |(value, user_id)| AnnotatorSpanScore {
+                        span_id,
+                        user_id: *user_id,
+                        name: "correctness".to_string(),
+                        value: *value,
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cohens_kappa() {
+        let scores =
+            annotator_scores(&[&[1.0, 1.0], &[1.0, 0.0], &[0.0, 0.0], &[0.0, 0.0], &[1.0]]);
+        let agreement = score_agreement(
+            "correctness".to_string(),
+            AgreementMetric::CohensKappa,
+            &scores,
+        );
+        // Observed 0.75, expected by chance 0.5
+        assert_eq!(agreement.value, Some(0.5));
+        assert_eq!(agreement.item_count, 4);
+        assert_eq!(agreement.annotator_count, 2);
+
+        let scores = annotator_scores(&[&[1.0, 1.0], &[1.0, 1.0]]);
+        let agreement = score_agreement(
+            "correctness".to_string(),
+            AgreementMetric::CohensKappa,
+            &scores,
+        );
+        assert_eq!(agreement.value, None);
+    }
+
+    #[test]
+    fn test_krippendorffs_alpha() {
+        let perfect = annotator_scores(&[&[1.0, 1.0, 1.0], &[3.0, 3.0], &[5.0, 5.0, 5.0]]);
+        let agreement = score_agreement(
+            "quality".to_string(),
+            AgreementMetric::KrippendorffsAlpha,
+            &perfect,
+        );
+        assert_eq!(agreement.value, Some(1.0));
+        assert_eq!(agreement.annotator_count, 3);
+
+        let noisy = annotator_scores(&[&[1.0, 2.0], &[4.0, 5.0], &[2.0, 1.0], &[5.0, 4.0]]);
+        let alpha = score_agreement(
+            "quality".to_string(),
+            AgreementMetric::KrippendorffsAlpha,
+            &noisy,
+        )
+        .value
+        .unwrap();
+        assert!(alpha > 0.7 && alpha < 1.0);
+    }
+}
@@ -0,0 +1,395 @@
+//! Export of evaluation results with their scores, for analysis outside of Laminar.
+//!
+//! Results are read from Postgres in batches and each batch is encoded and sent right away, so
+//! exports of large evaluations don't have to fit into memory. Arrow and Parquet exports keep the
+//! types of the scores, so that they can be loaded into dataframes as is.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow_array::{
+    builder::{BooleanBuilder, Float64Builder, StringBuilder, TimestampMicrosecondBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self,
+        evaluations::{EvaluationDatapoint, EvaluationScoreName},
+    },
+    evaluations::utils::ScoreType,
+};
+
+const EXPORT_BATCH_SIZE: usize = 1000;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+    Parquet,
+    /// Arrow IPC stream
+    Arrow,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Jsonl => "application/jsonl",
+            Self::Parquet => "application/vnd.apache.parquet",
+            Self::Arrow => "application/vnd.apache.arrow.stream",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+            Self::Parquet => "parquet",
+            Self::Arrow => "arrows",
+        }
+    }
+}
+
+/// Writer of the Arrow and Parquet exports, which returns the encoded bytes of each batch as
+/// soon as it's written
+pub enum ColumnarWriter {
+    Arrow(StreamWriter<Vec<u8>>),
+    Parquet(ArrowWriter<Vec<u8>>),
+}
+
+impl ColumnarWriter {
+    /// None for the row-based formats
+    pub fn new(format: ExportFormat, schema: SchemaRef) -> Result<Option<Self>> {
+        match format {
+            ExportFormat::Arrow => Ok(Some(Self::Arrow(StreamWriter::try_new(
+                Vec::new(),
+                &schema,
+            )?))),
+            ExportFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                Ok(Some(Self::Parquet(ArrowWriter::try_new(
+                    Vec::new(),
+                    schema,
+                    Some(properties),
+                )?)))
+            }
+            ExportFormat::Csv | ExportFormat::Jsonl => Ok(None),
+        }
+    }
+
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<Vec<u8>> {
+        match self {
+            Self::Arrow(writer) => {
+                writer.write(batch)?;
+                Ok(std::mem::take(writer.get_mut()))
+            }
+            Self::Parquet(writer) => {
+                writer.write(batch)?;
+                writer.flush()?;
+                // Each row group is sent as soon as it's written, the writer keeps track of the
+                // offsets for the footer
+                Ok(std::mem::take(writer.inner_mut()))
+            }
+        }
+    }
+
+    /// The end of stream marker of Arrow, the footer of Parquet
+    pub fn finish(self) -> Result<Vec<u8>> {
+        match self {
+            Self::Arrow(writer) => Ok(writer.into_inner()?),
+            Self::Parquet(writer) => Ok(writer.into_inner()?),
+        }
+    }
+}
+
+/// Columns of the CSV, Arrow and Parquet exports, which are followed by a column per score
+const COLUMNS: [&str; 6] = ["id", "createdAt", "traceId", "data", "target", "output"];
+
+fn score<'a>(result: &'a EvaluationDatapoint, name: &str) -> Option<&'a Value> {
+    result.scores.get(name).filter(|score| !score.is_null())
+}
+
+pub fn to_json_string(value: &Value) -> String {
+    // Strings are exported as is, so that text datasets are readable without unquoting
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+fn encode_csv(
+    results: &[EvaluationDatapoint],
+    score_names: &[EvaluationScoreName],
+    with_header: bool,
+) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    if with_header {
+        writer.write_record(
+            COLUMNS
+                .iter()
+                .copied()
+                .chain(score_names.iter().map(|s| s.name.as_str())),
+        )?;
+    }
+    for result in results {
+        let mut record = vec![
+            result.id.to_string(),
+            result.created_at.to_rfc3339(),
+            result.trace_id.to_string(),
+            to_json_string(&result.data),
+            to_json_string(&result.target),
+            result
+                .executor_output
+                .as_ref()
+                .map(to_json_string)
+                .unwrap_or_default(),
+        ];
+        record.extend(score_names.iter().map(|score_name| {
+            score(result, &score_name.name)
+                .map(|score| match score.as_f64() {
+                    Some(value) => value.to_string(),
+                    None => to_json_string(score),
+                })
+                .unwrap_or_default()
+        }));
+        writer.write_record(record)?;
+    }
+    writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+fn encode_jsonl(results: &[EvaluationDatapoint]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for result in results {
+        let line = json!({
+            "id": result.id,
+            "createdAt": result.created_at,
+            "traceId": result.trace_id,
+            "data": result.data,
+            "target": result.target,
+            "output": result.executor_output,
+            "scores": result.scores,
+        });
+        serde_json::to_writer(&mut bytes, &line)?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+fn columnar_schema(score_names: &[EvaluationScoreName]) -> SchemaRef {
+    let mut fields = vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new(
+            "createdAt",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("traceId", DataType::Utf8, false),
+        Field::new("data", DataType::Utf8, false),
+        Field::new("target", DataType::Utf8, false),
+        Field::new("output", DataType::Utf8, true),
+    ];
+    fields.extend(score_names.iter().map(|score_name| {
+        let data_type = match score_name.score_type {
+            ScoreType::NUMERIC => DataType::Float64,
+            ScoreType::BOOLEAN => DataType::Boolean,
+            ScoreType::CATEGORICAL => DataType::Utf8,
+        };
+        Field::new(&score_name.name, data_type, true)
+    }));
+    Arc::new(Schema::new(fields))
+}
+
+/// Builder of a score column of the Arrow and Parquet exports, typed by the score's type
+enum ScoreColumnBuilder {
+    Numeric(Float64Builder),
+    Boolean(BooleanBuilder),
+    Categorical(StringBuilder),
+}
+
+impl ScoreColumnBuilder {
+    fn new(score_type: ScoreType) -> Self {
+        match score_type {
+            ScoreType::NUMERIC => Self::Numeric(Float64Builder::new()),
+            ScoreType::BOOLEAN => Self::Boolean(BooleanBuilder::new()),
+            ScoreType::CATEGORICAL => Self::Categorical(StringBuilder::new()),
+        }
+    }
+
+    /// Scores that don't match the column's type are exported as null, except for labels,
+    /// which other scores are converted to
+    fn append(&mut self, score: Option<&Value>) {
+        match self {
+            Self::Numeric(builder) => builder.append_option(score.and_then(Value::as_f64)),
+            Self::Boolean(builder) => builder.append_option(score.and_then(Value::as_bool)),
+            Self::Categorical(builder) => builder.append_option(score.map(to_json_string)),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Numeric(builder) => Arc::new(builder.finish()),
+            Self::Boolean(builder) => Arc::new(builder.finish()),
+            Self::Categorical(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+fn record_batch(
+    results: &[EvaluationDatapoint],
+    schema: SchemaRef,
+    score_names: &[EvaluationScoreName],
+) -> Result<RecordBatch> {
+    let mut ids = StringBuilder::new();
+    let mut created_at = TimestampMicrosecondBuilder::new().with_timezone("UTC");
+    let mut trace_ids = StringBuilder::new();
+    let mut datas = StringBuilder::new();
+    let mut targets = StringBuilder::new();
+    let mut outputs = StringBuilder::new();
+    let mut scores = score_names
+        .iter()
+        .map(|score_name| ScoreColumnBuilder::new(score_name.score_type))
+        .collect::<Vec<_>>();
+
+    for result in results {
+        ids.append_value(result.id.to_string());
+        created_at.append_value(result.created_at.timestamp_micros());
+        trace_ids.append_value(result.trace_id.to_string());
+        datas.append_value(to_json_string(&result.data));
+        targets.append_value(to_json_string(&result.target));
+        outputs.append_option(result.executor_output.as_ref().map(to_json_string));
+        for (score_name, builder) in score_names.iter().zip(scores.iter_mut()) {
+            builder.append(score(result, &score_name.name));
+        }
+    }
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(ids.finish()),
+        Arc::new(created_at.finish()),
+        Arc::new(trace_ids.finish()),
+        Arc::new(datas.finish()),
+        Arc::new(targets.finish()),
+        Arc::new(outputs.finish()),
+    ];
+    columns.extend(scores.iter_mut().map(ScoreColumnBuilder::finish));
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// Encoded results of the evaluation, in chunks of a batch of results each
+pub fn export_evaluation_results(
+    pool: PgPool,
+    evaluation_id: Uuid,
+    score_names: Vec<EvaluationScoreName>,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<Bytes>> {
+    async_stream::try_stream! {
+        let schema = columnar_schema(&score_names);
+        let mut columnar_writer = ColumnarWriter::new(format, schema.clone())?;
+
+        let mut batches = db::evaluations::stream_evaluation_results(&pool, evaluation_id)
+            .chunks(EXPORT_BATCH_SIZE);
+        let mut is_first_batch = true;
+        while let Some(batch) = batches.next().await {
+            let results = batch.into_iter().collect::<Result<Vec<_>, _>>()?;
+            let bytes = match (&mut columnar_writer, format) {
+                (Some(writer), _) => {
+                    writer.write(&record_batch(&results, schema.clone(), &score_names)?)?
+                }
+                (None, ExportFormat::Csv) => encode_csv(&results, &score_names, is_first_batch)?,
+                (None, _) => encode_jsonl(&results)?,
+            };
+            is_first_batch = false;
+            yield Bytes::from(bytes);
+        }
+
+        if let Some(writer) = columnar_writer {
+            yield Bytes::from(writer.finish()?);
+        } else if is_first_batch && format == ExportFormat::Csv {
+            yield Bytes::from(encode_csv(&[], &score_names, true)?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn result(scores: Value) -> EvaluationDatapoint {
+        EvaluationDatapoint {
+            id: Uuid::nil(),
+            created_at: Utc::now(),
+            evaluation_id: Uuid::nil(),
+            data: json!({"question": "2 + 2"}),
+            target: json!("4"),
+            scores,
+            executor_output: None,
+            trace_id: Uuid::nil(),
+        }
+    }
+
+    fn score_name(name: &str, score_type: ScoreType) -> EvaluationScoreName {
+        EvaluationScoreName {
+            name: name.to_string(),
+            score_type,
+        }
+    }
+
+    #[test]
+    fn test_encode_csv() {
+        let score_names = vec![
+            score_name("accuracy", ScoreType::NUMERIC),
+            score_name("relevance", ScoreType::NUMERIC),
+            score_name("correct", ScoreType::BOOLEAN),
+            score_name("tone", ScoreType::CATEGORICAL),
+        ];
+        let results = vec![
+            result(json!({"accuracy": 1.0, "relevance": 0.5, "correct": true, "tone": "neutral"})),
+            result(json!({"accuracy": 0.0})),
+        ];
+
+        let csv = String::from_utf8(encode_csv(&results, &score_names, true).unwrap()).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "id,createdAt,traceId,data,target,output,accuracy,relevance,correct,tone"
+        );
+        assert!(lines[1].ends_with(",\"{\"\"question\"\":\"\"2 + 2\"\"}\",4,,1,0.5,true,neutral"));
+        assert!(lines[2].ends_with(",4,,0,,,"));
+    }
+
+    #[test]
+    fn test_record_batch() {
+        let score_names = vec![
+            score_name("accuracy", ScoreType::NUMERIC),
+            score_name("correct", ScoreType::BOOLEAN),
+        ];
+        let results = vec![
+            result(json!({"accuracy": 1.0, "correct": false})),
+            result(json!({})),
+        ];
+
+        let batch = record_batch(&results, columnar_schema(&score_names), &score_names).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.num_columns(), COLUMNS.len() + 2);
+        assert_eq!(batch.column(COLUMNS.len()).null_count(), 1);
+        assert_eq!(
+            batch.column(COLUMNS.len() + 1).data_type(),
+            &DataType::Boolean
+        );
+    }
+}
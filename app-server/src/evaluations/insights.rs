@@ -0,0 +1,220 @@
+//! Weekly insight reports: evaluation and online scores of a week compared with the week before,
+//! with the biggest movers and a short narrative summary.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::{
+    insights::{self, ScoreAverage},
+    DB,
+};
+
+const MAX_MOVERS: usize = 5;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ScoreSource {
+    /// Scores of evaluation runs
+    Evaluation,
+    /// Labels set by online evaluators on spans
+    Online,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreMover {
+    pub source: ScoreSource,
+    pub group_id: Option<String>,
+    pub name: String,
+    pub previous_average: f64,
+    pub current_average: f64,
+    pub change: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InsightReport {
+    pub week_start: DateTime<Utc>,
+    pub week_end: DateTime<Utc>,
+    pub evaluation_count: i64,
+    pub previous_evaluation_count: i64,
+    /// Scores with the largest absolute change of their average, largest first
+    pub movers: Vec<ScoreMover>,
+    /// Scores that appeared this week
+    pub new_scores: Vec<String>,
+    pub narrative: String,
+}
+
+fn score_label(group_id: &Option<String>, name: &str) -> String {
+    match group_id {
+        Some(group_id) => format!("{} ({})", name, group_id),
+        None => name.to_string(),
+    }
+}
+
+/// Compares the averages of the scores present in both weeks
+fn find_movers(
+    source: ScoreSource,
+    previous: &[ScoreAverage],
+    current: &[ScoreAverage],
+) -> (Vec<ScoreMover>, Vec<String>) {
+    let previous = previous
+        .iter()
+        .map(|score| ((&score.group_id, &score.name), score.average))
+        .collect::<HashMap<_, _>>();
+
+    let mut movers = Vec::new();
+    let mut new_scores = Vec::new();
+    for score in current {
+        match previous.get(&(&score.group_id, &score.name)) {
+            Some(previous_average) => movers.push(ScoreMover {
+                source,
+                group_id: score.group_id.clone(),
+                name: score.name.clone(),
+                previous_average: *previous_average,
+                current_average: score.average,
+                change: score.average - previous_average,
+            }),
+            None => new_scores.push(score_label(&score.group_id, &score.name)),
+        }
+    }
+    (movers, new_scores)
+}
+
+fn narrative(report: &InsightReport) -> String {
+    let mut sentences = vec![format!(
+        "{} evaluation runs this week, {} the week before.",
+        report.evaluation_count, report.previous_evaluation_count
+    )];
+
+    let movers = report
+        .movers
+        .iter()
+        .filter(|mover| mover.change != 0.0)
+        .collect::<Vec<_>>();
+    if movers.is_empty() {
+        sentences.push("No score changed compared to last week.".to_string());
+    }
+    for mover in movers {
+        let source = match mover.source {
+            ScoreSource::Evaluation => "Evaluation score",
+            ScoreSource::Online => "Online score",
+        };
+        let direction = if mover.change > 0.0 {
+            "improved"
+        } else {
+            "dropped"
+        };
+        sentences.push(format!(
+            "{} {} {} from {:.3} to {:.3}.",
+            source,
+            score_label(&mover.group_id, &mover.name),
+            direction,
+            mover.previous_average,
+            mover.current_average
+        ));
+    }
+
+    if !report.new_scores.is_empty() {
+        sentences.push(format!("New this week: {}.", report.new_scores.join(", ")));
+    }
+
+    sentences.join(" ")
+}
+
+/// Report of the week that ends at `week_end`
+pub async fn generate_report(
+    db: &DB,
+    project_id: &Uuid,
+    week_end: DateTime<Utc>,
+) -> Result<InsightReport> {
+    let week_start = week_end - Duration::weeks(1);
+    let previous_week_start = week_start - Duration::weeks(1);
+
+    let pool = &db.pool;
+    let (evaluation_scores, previous_evaluation_scores) = tokio::try_join!(
+        insights::get_evaluation_score_averages(pool, project_id, week_start, week_end),
+        insights::get_evaluation_score_averages(pool, project_id, previous_week_start, week_start),
+    )?;
+    let (online_scores, previous_online_scores) = tokio::try_join!(
+        insights::get_online_score_averages(pool, project_id, week_start, week_end),
+        insights::get_online_score_averages(pool, project_id, previous_week_start, week_start),
+    )?;
+    let (evaluation_count, previous_evaluation_count) = tokio::try_join!(
+        insights::count_evaluations(pool, project_id, week_start, week_end),
+        insights::count_evaluations(pool, project_id, previous_week_start, week_start),
+    )?;
+
+    let (mut movers, mut new_scores) = find_movers(
+        ScoreSource::Evaluation,
+        &previous_evaluation_scores,
+        &evaluation_scores,
+    );
+    let (online_movers, new_online_scores) =
+        find_movers(ScoreSource::Online, &previous_online_scores, &online_scores);
+    movers.extend(online_movers);
+    new_scores.extend(new_online_scores);
+
+    movers.sort_by(|a, b| b.change.abs().total_cmp(&a.change.abs()));
+    movers.truncate(MAX_MOVERS);
+    new_scores.sort();
+
+    let mut report = InsightReport {
+        week_start,
+        week_end,
+        evaluation_count,
+        previous_evaluation_count,
+        movers,
+        new_scores,
+        narrative: String::new(),
+    };
+    report.narrative = narrative(&report);
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(group_id: &str, name: &str, average: f64) -> ScoreAverage {
+        ScoreAverage {
+            group_id: Some(group_id.to_string()),
+            name: name.to_string(),
+            average,
+        }
+    }
+
+    #[test]
+    fn test_find_movers() {
+        let previous = vec![score("gpt-4o", "accuracy", 0.8)];
+        let current = vec![
+            score("gpt-4o", "accuracy", 0.6),
+            score("gpt-4o", "faithfulness", 0.9),
+        ];
+        let (movers, new_scores) = find_movers(ScoreSource::Evaluation, &previous, &current);
+        assert_eq!(movers.len(), 1);
+        assert!((movers[0].change + 0.2).abs() < 1e-9);
+        assert_eq!(new_scores, vec!["faithfulness (gpt-4o)"]);
+
+        let report = InsightReport {
+            week_start: Utc::now(),
+            week_end: Utc::now(),
+            evaluation_count: 3,
+            previous_evaluation_count: 2,
+            movers,
+            new_scores,
+            narrative: String::new(),
+        };
+        assert_eq!(
+            narrative(&report),
+            "3 evaluation runs this week, 2 the week before. \
+            Evaluation score accuracy (gpt-4o) dropped from 0.800 to 0.600. \
+            New this week: faithfulness (gpt-4o)."
+        );
+    }
+}
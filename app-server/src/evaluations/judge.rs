@@ -0,0 +1,298 @@
+//! LLM judges score evaluation datapoints on the server, so that an evaluation doesn't need
+//! scorers in the client. A judge's prompt is a handlebars template over the datapoint's
+//! `input` (its data), `output` and `target`, e.g. `{{input.question}}` or `{{json target}}`.
+//! The judge's model answers with a score of the judge's score type, which is added to the
+//! datapoint's scores before they are recorded like the scores reported by the client.
+//!
+//! Online evaluation rules run the same judges on production spans, with the span's input and
+//! output and without a target, see `traces::online_evaluations`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use futures::{stream, StreamExt};
+use handlebars::Handlebars;
+use handlebars_misc_helpers::json_helpers::json_to_str_fct;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    db::{judge_evaluators::JudgeEvaluator, DB},
+    language_model::{
+        providers::utils::get_required_env_vars_for_model, ChatMessage, ChatMessageContent,
+        LanguageModelRunner, NodeInfo,
+    },
+};
+
+use super::utils::{EvaluationDatapointResult, ScoreType, ScoreValue};
+
+/// Judge calls in flight for one batch of datapoints
+const JUDGE_CONCURRENCY: usize = 8;
+
+#[derive(Deserialize)]
+struct JudgeVerdict {
+    score: Value,
+}
+
+fn handlebars() -> Handlebars<'static> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars.register_helper("json", Box::new(json_to_str_fct));
+    handlebars
+}
+
+/// Fails if the prompt is not a valid template
+pub fn validate_prompt(prompt: &str) -> Result<()> {
+    handlebars().register_template_string("prompt", prompt)?;
+    Ok(())
+}
+
+pub fn render_prompt(
+    prompt: &str,
+    input: &Value,
+    output: &Value,
+    target: &Value,
+) -> Result<String> {
+    let context = json!({
+        "input": input,
+        "output": output,
+        "target": target,
+    });
+    Ok(handlebars().render_template(prompt, &context)?)
+}
+
+fn system_prompt(judge: &JudgeEvaluator) -> String {
+    let score = match judge.score_type {
+        ScoreType::NUMERIC => "a number".to_string(),
+        ScoreType::BOOLEAN => "true if the output passes, false if it fails".to_string(),
+        ScoreType::CATEGORICAL => format!(
+            "exactly one of the following labels as a string: {}",
+            judge.labels.join(", ")
+        ),
+    };
+    format!(
+        "You are a judge that scores the output of an LLM application as instructed. \
+        Answer with JSON only, in the form {{\"reasoning\": \"<short explanation>\", \"score\": <score>}}, \
+        where the score is {score}."
+    )
+}
+
+/// Score in the judge's answer, which must match the judge's score type
+pub fn parse_score(judge: &JudgeEvaluator, text: &str) -> Result<ScoreValue> {
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text,
+    };
+    let verdict = serde_json::from_str::<JudgeVerdict>(json)
+        .map_err(|e| anyhow::anyhow!("Judge {} answered with invalid JSON: {}", judge.name, e))?;
+
+    let score = match (judge.score_type, &verdict.score) {
+        (ScoreType::NUMERIC, Value::Number(number)) => number.as_f64().map(ScoreValue::Number),
+        (ScoreType::NUMERIC, Value::String(s)) => s.trim().parse().ok().map(ScoreValue::Number),
+        (ScoreType::BOOLEAN, Value::Bool(passed)) => Some(ScoreValue::Boolean(*passed)),
+        (ScoreType::BOOLEAN, Value::String(s)) => s.trim().parse().ok().map(ScoreValue::Boolean),
+        (ScoreType::CATEGORICAL, Value::String(label)) if judge.labels.contains(label) => {
+            Some(ScoreValue::Label(label.clone()))
+        }
+        _ => None,
+    };
+    match score {
+        Some(ScoreValue::Number(value)) if !value.is_finite() => None,
+        score => score,
+    }
+    .ok_or_else(|| {
+        anyhow::anyhow!(
+            "Judge {} answered with an invalid score: {}",
+            judge.name,
+            verdict.score
+        )
+    })
+}
+
+/// Env variables, e.g. provider api keys, that the judges' models need but are not in `env`
+pub fn missing_env_vars(judges: &[JudgeEvaluator], env: &HashMap<String, String>) -> Vec<String> {
+    let mut missing = judges
+        .iter()
+        .flat_map(|judge| get_required_env_vars_for_model(&judge.model))
+        .filter(|name| !env.contains_key(name))
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing.dedup();
+    missing
+}
+
+/// Asks the judge's model to score the output
+pub async fn run_judge(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    language_model: Arc<LanguageModelRunner>,
+    env: &HashMap<String, String>,
+    judge: &JudgeEvaluator,
+    input: &Value,
+    output: &Value,
+    target: &Value,
+) -> Result<ScoreValue> {
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: ChatMessageContent::Text(system_prompt(judge)),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: ChatMessageContent::Text(render_prompt(&judge.prompt, input, output, target)?),
+        },
+    ];
+    let node_info = NodeInfo {
+        id: Uuid::new_v4(),
+        node_id: judge.id,
+        node_name: judge.name.clone(),
+        node_type: "LLM".to_string(),
+    };
+
+    let completion = language_model
+        .chat_completion(
+            &judge.model,
+            &messages,
+            &json!({ "temperature": 0 }),
+            env,
+            None,
+            &node_info,
+            db,
+            cache,
+        )
+        .await?;
+
+    parse_score(judge, &completion.text_message())
+}
+
+/// Adds the scores of the judges to the datapoints. Datapoints that already have a score with
+/// the judge's name keep it. A judge that fails on a datapoint is logged and leaves it without
+/// the score, so that one bad answer doesn't fail the whole batch.
+pub async fn score_with_judges(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    language_model: Arc<LanguageModelRunner>,
+    env: &HashMap<String, String>,
+    judges: &[JudgeEvaluator],
+    points: &mut [EvaluationDatapointResult],
+) {
+    let tasks = points
+        .iter()
+        .enumerate()
+        .flat_map(|(i, point)| {
+            judges
+                .iter()
+                .filter(|judge| !point.scores.contains_key(&judge.name))
+                .map(move |judge| (i, point, judge))
+        })
+        .collect::<Vec<_>>();
+
+    let results = stream::iter(tasks)
+        .map(|(i, point, judge)| {
+            let db = db.clone();
+            let cache = cache.clone();
+            let language_model = language_model.clone();
+            async move {
+                let output = point.executor_output.clone().unwrap_or_default();
+                let score = run_judge(
+                    db,
+                    cache,
+                    language_model,
+                    env,
+                    judge,
+                    &point.data,
+                    &output,
+                    &point.target,
+                )
+                .await;
+                (i, judge, score)
+            }
+        })
+        .buffer_unordered(JUDGE_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut scores = Vec::with_capacity(results.len());
+    for (i, judge, score) in results {
+        match score {
+            Ok(score) => scores.push((i, judge.name.clone(), score)),
+            Err(e) => log::warn!(
+                "Judge {} failed on a datapoint of project {}: {:?}",
+                judge.id,
+                judge.project_id,
+                e
+            ),
+        }
+    }
+    for (i, name, score) in scores {
+        points[i].scores.insert(name, score);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn judge(score_type: ScoreType, labels: &[&str]) -> JudgeEvaluator {
+        JudgeEvaluator {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            project_id: Uuid::new_v4(),
+            name: "correctness".to_string(),
+            model: "openai:gpt-4o-mini".to_string(),
+            prompt: String::new(),
+            score_type,
+            labels: labels.iter().map(|label| label.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_render_prompt() {
+        let point: EvaluationDatapointResult = serde_json::from_value(json!({
+            "data": {"question": "2 + 2?"},
+            "target": {"answer": 4},
+            "executorOutput": "4",
+            "traceId": Uuid::new_v4(),
+            "scores": {},
+        }))
+        .unwrap();
+        let prompt = render_prompt(
+            "Q: {{input.question}} A: {{output}} Expected: {{json target}}",
+            &point.data,
+            &point.executor_output.unwrap_or_default(),
+            &point.target,
+        )
+        .unwrap();
+        assert_eq!(prompt, "Q: 2 + 2? A: 4 Expected: {\"answer\":4}");
+
+        assert!(validate_prompt("{{#if output}}").is_err());
+    }
+
+    #[test]
+    fn test_parse_score() {
+        let numeric = judge(ScoreType::NUMERIC, &[]);
+        let answer = "```json\n{\"reasoning\": \"Mostly right\", \"score\": 0.8}\n```";
+        assert_eq!(
+            parse_score(&numeric, answer).unwrap(),
+            ScoreValue::Number(0.8)
+        );
+        assert!(parse_score(&numeric, "{\"score\": \"high\"}").is_err());
+
+        let boolean = judge(ScoreType::BOOLEAN, &[]);
+        assert_eq!(
+            parse_score(&boolean, "{\"score\": \"false\"}").unwrap(),
+            ScoreValue::Boolean(false)
+        );
+
+        let categorical = judge(ScoreType::CATEGORICAL, &["relevant", "off-topic"]);
+        assert_eq!(
+            parse_score(&categorical, "{\"score\": \"off-topic\"}").unwrap(),
+            ScoreValue::Label("off-topic".to_string())
+        );
+        assert!(parse_score(&categorical, "{\"score\": \"unsure\"}").is_err());
+    }
+}
@@ -1 +1,174 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    alerts,
+    analytics::AnalyticsStore,
+    ch::evaluation_scores::EvaluationScore,
+    db::{
+        self,
+        activity::{ActivityType, NewActivity},
+        evaluations::Evaluation,
+        DB,
+    },
+    ids, logging,
+};
+use progress::{EvaluatedDatapoint, EvaluationProgressHub};
+use utils::{datapoints_to_labeling_queues, get_columns_from_points, EvaluationDatapointResult};
+
+pub mod agreement;
+pub mod export;
+pub mod insights;
+pub mod judge;
+pub mod progress;
+pub mod prompt_suggestions;
+pub mod proposals;
+pub mod regression_gate;
+pub mod schedules;
 pub mod utils;
+
+/// Creates an evaluation with its results, shared by all versions of the evaluations API
+pub async fn create_evaluation(
+    db: Arc<DB>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    progress_hub: Arc<EvaluationProgressHub>,
+    project_id: Uuid,
+    name: String,
+    group_id: String,
+    dataset_id: Option<Uuid>,
+    dataset_version: Option<i32>,
+    points: Vec<EvaluationDatapointResult>,
+) -> Result<Evaluation> {
+    let evaluation = db::evaluations::create_evaluation(
+        &db.pool,
+        ids::new_id(),
+        &name,
+        project_id,
+        &group_id,
+        dataset_id,
+        dataset_version,
+    )
+    .await?;
+    let activity = NewActivity {
+        project_id,
+        activity_type: ActivityType::EVALUATION_RUN,
+        actor_id: None,
+        resource_id: Some(evaluation.id),
+        summary: format!(
+            "Ran evaluation {} on {} datapoints",
+            evaluation.name,
+            points.len()
+        ),
+        details: json!({ "groupId": group_id }),
+    };
+    if let Err(e) = db::activity::record_activity(&db.pool, &activity).await {
+        log::error!(
+            "Failed to record activity of evaluation {}: {:?}",
+            evaluation.id,
+            e
+        );
+    }
+
+    if !points.is_empty() {
+        add_evaluation_results(db, analytics_store, progress_hub, &evaluation, points).await?;
+    }
+
+    Ok(evaluation)
+}
+
+/// Records results of an existing evaluation and sends them to its watchers
+pub async fn add_evaluation_results(
+    db: Arc<DB>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    progress_hub: Arc<EvaluationProgressHub>,
+    evaluation: &Evaluation,
+    points: Vec<EvaluationDatapointResult>,
+) -> Result<()> {
+    let project_id = evaluation.project_id;
+    let evaluation_id = evaluation.id;
+    let alerts_db = db.clone();
+    let columns = get_columns_from_points(&points);
+    let ids = points.iter().map(|_| ids::new_id()).collect::<Vec<_>>();
+    let labeling_queues =
+        datapoints_to_labeling_queues(db.clone(), &points, &ids, &project_id).await?;
+
+    for (queue_id, entries) in labeling_queues.iter() {
+        db::labeling_queues::push_to_labeling_queue(&db.pool, queue_id, &entries).await?;
+    }
+
+    let ids_clone = ids.clone();
+    let db_task = logging::spawn(async move {
+        db::evaluations::set_evaluation_results(
+            db.clone(),
+            evaluation_id,
+            &ids_clone,
+            &columns.scores,
+            &columns.datas,
+            &columns.targets,
+            &columns.executor_outputs,
+            &columns.trace_ids,
+        )
+        .await
+    });
+
+    // Flattened scores from all evaluators to be recorded to Clickhouse
+    // Its length can be longer than the amount of evaluation datapoints
+    // since each datapoint can have multiple evaluators
+    let ch_evaluation_scores = EvaluationScore::from_evaluation_datapoint_results(
+        &points,
+        &ids,
+        project_id,
+        evaluation.group_id.clone(),
+        evaluation_id,
+        Utc::now(),
+    );
+
+    let ch_task = logging::spawn(async move {
+        analytics_store
+            .insert_evaluation_scores(ch_evaluation_scores)
+            .await
+    });
+
+    let (db_result, ch_result) = tokio::join!(db_task, ch_task);
+
+    db_result.map_err(|e| anyhow::anyhow!("Database task failed: {}", e))??;
+    ch_result.map_err(|e| anyhow::anyhow!("Clickhouse task failed: {}", e))??;
+
+    // Published after the results are stored, so that watchers never see a failed write
+    let datapoints = points
+        .into_iter()
+        .zip(ids)
+        .map(|(point, result_id)| EvaluatedDatapoint {
+            result_id,
+            trace_id: point.trace_id,
+            scores: point.scores,
+        })
+        .collect();
+    progress_hub.publish(&evaluation_id, datapoints);
+
+    let evaluation_name = evaluation.name.clone();
+    let group_id = evaluation.group_id.clone();
+    logging::spawn(async move {
+        if let Err(e) = alerts::check_evaluation_alerts(
+            alerts_db,
+            project_id,
+            evaluation_id,
+            evaluation_name,
+            group_id,
+        )
+        .await
+        {
+            log::error!(
+                "Failed to check alerts of evaluation {}: {:?}",
+                evaluation_id,
+                e
+            );
+        }
+    });
+
+    Ok(())
+}
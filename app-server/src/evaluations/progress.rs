@@ -0,0 +1,175 @@
+//! Live progress of evaluations, streamed to watchers as results are ingested.
+//!
+//! Progress is only kept for evaluations that are being watched, and only in the process that
+//! serves the stream, so watchers see the results ingested by the same app-server instance.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::{db, evaluations::utils::ScoreValue};
+
+/// Events a watcher can fall behind by before it gets a fresh snapshot instead
+const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationProgress {
+    pub datapoint_count: i64,
+    /// Average of each score over the datapoints that have it, the pass rate of boolean scores.
+    /// Categorical scores have no average.
+    pub average_scores: HashMap<String, f64>,
+    #[serde(skip)]
+    score_counts: HashMap<String, i64>,
+}
+
+impl EvaluationProgress {
+    fn add_datapoint(&mut self, scores: &HashMap<String, ScoreValue>) {
+        self.datapoint_count += 1;
+        for (name, score) in scores {
+            let Some(score) = score.as_f64() else {
+                continue;
+            };
+            let count = self.score_counts.entry(name.clone()).or_default();
+            let average = self.average_scores.entry(name.clone()).or_default();
+            *count += 1;
+            *average += (score - *average) / *count as f64;
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluatedDatapoint {
+    pub result_id: Uuid,
+    pub trace_id: Uuid,
+    pub scores: HashMap<String, ScoreValue>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatapointProgress {
+    pub datapoint: EvaluatedDatapoint,
+    /// Progress including the datapoint
+    pub progress: EvaluationProgress,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", content = "payload", rename_all = "camelCase")]
+pub enum EvaluationProgressEvent {
+    /// Progress so far, sent when the stream starts and when the watcher has fallen behind
+    Snapshot(EvaluationProgress),
+    Datapoint(DatapointProgress),
+}
+
+struct WatchedEvaluation {
+    sender: broadcast::Sender<EvaluationProgressEvent>,
+    progress: EvaluationProgress,
+}
+
+#[derive(Default)]
+pub struct EvaluationProgressHub {
+    evaluations: DashMap<Uuid, WatchedEvaluation>,
+}
+
+impl EvaluationProgressHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start watching the evaluation. Returns the progress so far, which is loaded from the
+    /// database if nobody is watching the evaluation yet.
+    pub async fn subscribe(
+        &self,
+        pool: &PgPool,
+        evaluation_id: Uuid,
+    ) -> Result<(
+        EvaluationProgress,
+        broadcast::Receiver<EvaluationProgressEvent>,
+    )> {
+        if let Some(watched) = self.evaluations.get(&evaluation_id) {
+            return Ok((watched.progress.clone(), watched.sender.subscribe()));
+        }
+
+        let (datapoint_count, averages) =
+            db::evaluations::get_evaluation_score_averages(pool, evaluation_id).await?;
+        let mut progress = EvaluationProgress {
+            datapoint_count,
+            ..Default::default()
+        };
+        for average in averages {
+            progress
+                .average_scores
+                .insert(average.name.clone(), average.average);
+            progress.score_counts.insert(average.name, average.count);
+        }
+
+        // Forget the evaluations whose watchers have all disconnected
+        self.evaluations
+            .retain(|_, watched| watched.sender.receiver_count() > 0);
+        let watched = self
+            .evaluations
+            .entry(evaluation_id)
+            .or_insert_with(|| WatchedEvaluation {
+                sender: broadcast::channel(CHANNEL_CAPACITY).0,
+                progress,
+            });
+        Ok((watched.progress.clone(), watched.sender.subscribe()))
+    }
+
+    /// Current progress of a watched evaluation
+    pub fn progress(&self, evaluation_id: &Uuid) -> Option<EvaluationProgress> {
+        self.evaluations
+            .get(evaluation_id)
+            .map(|watched| watched.progress.clone())
+    }
+
+    /// Send the datapoints to the watchers of the evaluation, if there are any
+    pub fn publish(&self, evaluation_id: &Uuid, datapoints: Vec<EvaluatedDatapoint>) {
+        let Some(mut watched) = self.evaluations.get_mut(evaluation_id) else {
+            return;
+        };
+        for datapoint in datapoints {
+            watched.progress.add_datapoint(&datapoint.scores);
+            let event = EvaluationProgressEvent::Datapoint(DatapointProgress {
+                datapoint,
+                progress: watched.progress.clone(),
+            });
+            // Fails only if all watchers have disconnected
+            let _ = watched.sender.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_datapoint() {
+        let score = |name: &str, value: ScoreValue| (name.to_string(), value);
+        let mut progress = EvaluationProgress::default();
+        progress.add_datapoint(&HashMap::from([
+            score("accuracy", ScoreValue::Number(1.0)),
+            score("correct", ScoreValue::Boolean(true)),
+        ]));
+        progress.add_datapoint(&HashMap::from([
+            score("accuracy", ScoreValue::Number(0.0)),
+            score("relevance", ScoreValue::Number(0.5)),
+            score("correct", ScoreValue::Boolean(false)),
+            score("tone", ScoreValue::Label("neutral".to_string())),
+        ]));
+        progress.add_datapoint(&HashMap::from([score("accuracy", ScoreValue::Number(0.5))]));
+
+        assert_eq!(progress.datapoint_count, 3);
+        assert_eq!(progress.average_scores["accuracy"], 0.5);
+        assert_eq!(progress.average_scores["relevance"], 0.5);
+        assert_eq!(progress.average_scores["correct"], 0.5);
+        assert!(!progress.average_scores.contains_key("tone"));
+    }
+}
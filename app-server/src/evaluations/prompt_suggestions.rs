@@ -0,0 +1,247 @@
+//! Suggests edits to the prompt of a pipeline's LLM node from a cluster of failing evaluation
+//! datapoints. Pipeline versions are where prompts are versioned, so a suggestion can be saved as
+//! a new draft version of the pipeline with only the prompt of that node changed.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    db::{evaluations::EvaluationDatapoint, DB},
+    language_model::{
+        providers::utils::missing_env_vars_for_model, ChatMessage, ChatMessageContent,
+        LanguageModelRunner, NodeInfo,
+    },
+};
+
+const DEFAULT_MODEL: &str = "openai:gpt-4o-mini";
+/// Datapoint fields longer than this are truncated to keep the request small
+const MAX_FIELD_LENGTH: usize = 2000;
+
+const SYSTEM_PROMPT: &str = r#"You improve prompts of LLM applications. You are given the current prompt and datapoints where the application failed, with their inputs, expected outputs, actual outputs and evaluation scores. Prompts can contain {{variable}} placeholders, keep all of them.
+
+Find what the failures have in common and suggest the smallest edits to the prompt that fix them. Answer with JSON only:
+{
+  "suggestedPrompt": "<the full edited prompt>",
+  "edits": [{"change": "<what was changed>", "rationale": "<which failures it addresses and why>"}]
+}"#;
+
+/// The model's answer couldn't be used as a suggestion
+#[derive(thiserror::Error, Debug)]
+#[error("{0}")]
+pub struct InvalidSuggestion(pub String);
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PromptEdit {
+    pub change: String,
+    pub rationale: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptSuggestion {
+    pub suggested_prompt: String,
+    pub edits: Vec<PromptEdit>,
+}
+
+/// Model in the format of "provider:model_name", set with `PROMPT_SUGGESTION_MODEL`
+fn model() -> String {
+    std::env::var("PROMPT_SUGGESTION_MODEL").unwrap_or(DEFAULT_MODEL.to_string())
+}
+
+/// Env variables, e.g. provider api keys, that the model needs but are not in `env`
+pub fn missing_env_vars(env: &HashMap<String, String>) -> Vec<String> {
+    missing_env_vars_for_model(&model(), env)
+}
+
+fn truncate(value: &Value) -> String {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    match text.char_indices().nth(MAX_FIELD_LENGTH) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+fn user_message(prompt: &str, datapoints: &[EvaluationDatapoint]) -> String {
+    let mut message = format!("Current prompt:\n{}\n\nFailing datapoints:", prompt);
+    for (i, datapoint) in datapoints.iter().enumerate() {
+        message.push_str(&format!(
+            "\n\n#{}\nInput: {}\nExpected output: {}\nActual output: {}\nScores: {}",
+            i + 1,
+            truncate(&datapoint.data),
+            truncate(&datapoint.target),
+            truncate(datapoint.executor_output.as_ref().unwrap_or(&Value::Null)),
+            datapoint.scores
+        ));
+    }
+    message
+}
+
+fn parse_suggestion(text: &str) -> Result<PromptSuggestion> {
+    let text = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    let suggestion = serde_json::from_str::<PromptSuggestion>(text)
+        .map_err(|e| InvalidSuggestion(format!("The generated suggestion is invalid: {}", e)))?;
+    if suggestion.suggested_prompt.trim().is_empty() {
+        return Err(InvalidSuggestion("The suggested prompt is empty".to_string()).into());
+    }
+    Ok(suggestion)
+}
+
+/// `env` holds the api keys of the language model provider, e.g. the project's stored keys
+pub async fn suggest_prompt_edits(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    env: &HashMap<String, String>,
+    prompt: &str,
+    datapoints: &[EvaluationDatapoint],
+) -> Result<PromptSuggestion> {
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: ChatMessageContent::Text(SYSTEM_PROMPT.to_string()),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: ChatMessageContent::Text(user_message(prompt, datapoints)),
+        },
+    ];
+    let node_info = NodeInfo {
+        id: Uuid::new_v4(),
+        node_id: Uuid::nil(),
+        node_name: "prompt_suggestion".to_string(),
+        node_type: "LLM".to_string(),
+    };
+
+    let completion = language_model
+        .chat_completion(
+            &model(),
+            &messages,
+            &json!({ "temperature": 0 }),
+            env,
+            None,
+            &node_info,
+            db,
+            cache,
+        )
+        .await?;
+
+    parse_suggestion(&completion.text_message())
+}
+
+/// LLM nodes of a runnable graph as (id, name, prompt)
+pub fn llm_nodes(runnable_graph: &Value) -> Vec<(String, String, String)> {
+    let Some(nodes) = runnable_graph
+        .get("nodes")
+        .and_then(|nodes| nodes.as_object())
+    else {
+        return vec![];
+    };
+    nodes
+        .values()
+        .filter(|node| node.get("type").and_then(|t| t.as_str()) == Some("LLM"))
+        .filter_map(|node| {
+            Some((
+                node.get("id")?.as_str()?.to_string(),
+                node.get("name")?.as_str()?.to_string(),
+                node.get("prompt")?.as_str()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Replaces the prompt of the LLM node with `node_id` in both graphs of a pipeline version
+pub fn replace_prompt(
+    displayable_graph: &mut Value,
+    runnable_graph: &mut Value,
+    node_id: &str,
+    prompt: &str,
+) {
+    if let Some(nodes) = runnable_graph
+        .get_mut("nodes")
+        .and_then(|nodes| nodes.as_object_mut())
+    {
+        for node in nodes.values_mut() {
+            if node.get("id").and_then(|id| id.as_str()) == Some(node_id) {
+                node["prompt"] = Value::String(prompt.to_string());
+            }
+        }
+    }
+    // displayable graph is the frontend's flow, which keeps the node in `data`
+    if let Some(nodes) = displayable_graph
+        .get_mut("nodes")
+        .and_then(|nodes| nodes.as_array_mut())
+    {
+        for node in nodes {
+            if let Some(data) = node.get_mut("data") {
+                if data.get("id").and_then(|id| id.as_str()) == Some(node_id) {
+                    data["prompt"] = Value::String(prompt.to_string());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_prompt() {
+        let suggestion = parse_suggestion(
+            "```json\n{\"suggestedPrompt\": \"Answer in one sentence: {{question}}\", \
+            \"edits\": [{\"change\": \"Limit the length\", \"rationale\": \"Answers were long\"}]}\n```",
+        )
+        .unwrap();
+        assert_eq!(suggestion.edits.len(), 1);
+
+        let node_id = "0b8a4c5e-32b1-4bb3-8d0e-2f3a6f6f7c11";
+        let mut runnable_graph = json!({
+            "nodes": {
+                "llm": {"type": "LLM", "id": node_id, "name": "llm", "prompt": "{{question}}"},
+                "output": {"type": "Output", "id": "3c6d2f0e-4a0b-4f8e-9a57-0f0e4b6f2d21"}
+            },
+            "pred": {}
+        });
+        let mut displayable_graph = json!({
+            "nodes": [{"id": node_id, "type": "LLM", "data": {"id": node_id, "prompt": "{{question}}"}}],
+            "edges": []
+        });
+        assert_eq!(
+            llm_nodes(&runnable_graph),
+            vec![(
+                node_id.to_string(),
+                "llm".to_string(),
+                "{{question}}".to_string()
+            )]
+        );
+
+        replace_prompt(
+            &mut displayable_graph,
+            &mut runnable_graph,
+            node_id,
+            &suggestion.suggested_prompt,
+        );
+        assert_eq!(
+            runnable_graph["nodes"]["llm"]["prompt"],
+            "Answer in one sentence: {{question}}"
+        );
+        assert_eq!(
+            displayable_graph["nodes"][0]["data"]["prompt"],
+            "Answer in one sentence: {{question}}"
+        );
+    }
+}
@@ -0,0 +1,476 @@
+//! Eval proposals are generated from production traces: recent spans at a path are sampled so
+//! that their inputs cover the input space (each new sample is the input least similar to the
+//! samples picked so far), a language model writes reference outputs and a judge rubric for
+//! them, and the result can be adopted as a dataset plus an online LLM judge evaluator.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    datasets::{datapoints::Datapoint, Dataset},
+    db::{
+        self,
+        eval_proposals::EvalProposal,
+        labels::{LabelClass, LabelType},
+        DB,
+    },
+    language_model::{
+        providers::utils::missing_env_vars_for_model, ChatMessage, ChatMessageContent,
+        LanguageModelRunner, NodeInfo,
+    },
+    pipeline::nodes::{
+        input::InputNode,
+        llm::{LLMNode, StructuredOutputParams},
+        output::OutputNode,
+        Handle, HandleType, Node,
+    },
+    semantic_search::SemanticSearch,
+    traces::{evaluators::get_stored_env, utils::json_value_to_string},
+};
+
+const DEFAULT_MODEL: &str = "openai:gpt-4o-mini";
+const LOOKBACK_DAYS: i64 = 7;
+/// Number of most recent spans the samples are picked from
+const MAX_CANDIDATES: i64 = 200;
+/// Span inputs and outputs longer than this are truncated
+const MAX_TEXT_LENGTH: usize = 2000;
+pub const DEFAULT_SAMPLE_SIZE: usize = 20;
+pub const MAX_SAMPLE_SIZE: usize = 50;
+
+const SYSTEM_PROMPT: &str = r#"You create evaluations for an LLM application from samples of its production traffic. You are given numbered samples with the input and the actual production output.
+
+1. Write the ideal reference output for every sample. Fix mistakes of the production output, keep it if it is already ideal.
+2. Write a rubric for an LLM judge that decides whether an output for such inputs is acceptable. The rubric should check what the samples have in common, not facts of a single sample.
+
+Answer with JSON only, with exactly one reference output per sample, in the order of the samples:
+{
+  "rubric": "<rubric>",
+  "referenceOutputs": ["<reference output of sample 1>", ...]
+}"#;
+
+const JUDGE_OUTPUT_SCHEMA: &str = r#"class Output {
+  reasoning string @description("Explanation of why the output does or does not meet the rubric")
+  value string @description("one of the following values: false, true")
+}"#;
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposedDatapoint {
+    pub span_id: Uuid,
+    pub trace_id: Uuid,
+    pub data: Value,
+    pub target: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Generation {
+    rubric: String,
+    reference_outputs: Vec<String>,
+}
+
+/// Model in the format of "provider:model_name", set with `EVAL_GENERATION_MODEL`. The adopted
+/// judge evaluator uses the same model.
+fn model() -> String {
+    std::env::var("EVAL_GENERATION_MODEL").unwrap_or(DEFAULT_MODEL.to_string())
+}
+
+/// Env variables, e.g. provider api keys, that the model needs but are not in `env`
+pub fn missing_env_vars(env: &HashMap<String, String>) -> Vec<String> {
+    missing_env_vars_for_model(&model(), env)
+}
+
+fn truncate(value: Option<Value>) -> String {
+    let text = json_value_to_string(value.unwrap_or_default());
+    match text.char_indices().nth(MAX_TEXT_LENGTH) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+/// Index of the candidate whose closest picked sample is the least similar
+fn least_covered(closest_similarity: &[f32], picked: &[bool]) -> Option<usize> {
+    closest_similarity
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !picked[*i])
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+/// Picks `count` of `texts` that cover them best, starting from the first one
+async fn coverage_sample(
+    semantic_search: &Arc<dyn SemanticSearch>,
+    texts: &[String],
+    count: usize,
+) -> Result<Vec<usize>> {
+    if texts.len() <= count {
+        return Ok((0..texts.len()).collect());
+    }
+
+    let mut closest_similarity = vec![f32::MIN; texts.len()];
+    let mut picked = vec![false; texts.len()];
+    let mut samples = vec![0];
+    picked[0] = true;
+    while samples.len() < count {
+        let last = texts[*samples.last().unwrap()].clone();
+        let scores = semantic_search
+            .calculate_similarity_scores(vec![last; texts.len()], texts.to_vec())
+            .await?
+            .scores;
+        for (closest, score) in closest_similarity.iter_mut().zip(scores) {
+            *closest = closest.max(score);
+        }
+        match least_covered(&closest_similarity, &picked) {
+            Some(i) => {
+                picked[i] = true;
+                samples.push(i);
+            }
+            None => break,
+        }
+    }
+    Ok(samples)
+}
+
+fn parse_generation(text: &str, sample_count: usize) -> Result<Generation> {
+    let text = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    let generation = serde_json::from_str::<Generation>(text)
+        .map_err(|e| anyhow::anyhow!("The generated evaluation is invalid: {}", e))?;
+    if generation.reference_outputs.len() != sample_count {
+        return Err(anyhow::anyhow!(
+            "Expected {} reference outputs, got {}",
+            sample_count,
+            generation.reference_outputs.len()
+        ));
+    }
+    Ok(generation)
+}
+
+async fn generate(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    env: &HashMap<String, String>,
+    samples: &[(String, String)],
+) -> Result<Generation> {
+    let user_message = samples
+        .iter()
+        .enumerate()
+        .map(|(i, (input, output))| {
+            format!(
+                "#{}\nInput: {}\nProduction output: {}",
+                i + 1,
+                input,
+                output
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: ChatMessageContent::Text(SYSTEM_PROMPT.to_string()),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: ChatMessageContent::Text(user_message),
+        },
+    ];
+    let node_info = NodeInfo {
+        id: Uuid::new_v4(),
+        node_id: Uuid::nil(),
+        node_name: "eval_generation".to_string(),
+        node_type: "LLM".to_string(),
+    };
+
+    let completion = language_model
+        .chat_completion(
+            &model(),
+            &messages,
+            &json!({ "temperature": 0 }),
+            env,
+            None,
+            &node_info,
+            db,
+            cache,
+        )
+        .await?;
+
+    parse_generation(&completion.text_message(), samples.len())
+}
+
+async fn generate_proposal(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    language_model: Arc<LanguageModelRunner>,
+    semantic_search: Arc<dyn SemanticSearch>,
+    proposal: &EvalProposal,
+    sample_size: usize,
+) -> Result<()> {
+    let spans = db::spans::get_recent_spans_for_path(
+        &db.pool,
+        proposal.project_id,
+        &proposal.span_path,
+        Utc::now() - Duration::days(LOOKBACK_DAYS),
+        MAX_CANDIDATES,
+    )
+    .await?;
+    if spans.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No spans with an input at {} in the last {} days",
+            proposal.span_path,
+            LOOKBACK_DAYS
+        ));
+    }
+
+    let inputs = spans
+        .iter()
+        .map(|span| truncate(span.input.clone()))
+        .collect::<Vec<_>>();
+    let sample_indices = coverage_sample(&semantic_search, &inputs, sample_size).await?;
+    let samples = sample_indices
+        .iter()
+        .map(|i| (inputs[*i].clone(), truncate(spans[*i].output.clone())))
+        .collect::<Vec<_>>();
+
+    let env = get_stored_env(db.clone(), proposal.project_id).await?;
+    let generation = generate(language_model, db.clone(), cache, &env, &samples).await?;
+
+    let datapoints = sample_indices
+        .iter()
+        .zip(generation.reference_outputs)
+        .map(|(i, reference_output)| ProposedDatapoint {
+            span_id: spans[*i].span_id,
+            trace_id: spans[*i].trace_id,
+            data: json!({ "input": spans[*i].input }),
+            target: json!({ "output": reference_output }),
+        })
+        .collect::<Vec<_>>();
+
+    db::eval_proposals::set_eval_proposal_ready(
+        &db.pool,
+        &proposal.id,
+        &serde_json::to_value(datapoints)?,
+        &generation.rubric,
+    )
+    .await
+}
+
+/// Background job that fills a GENERATING proposal, or marks it FAILED
+pub async fn run_proposal_job(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    language_model: Arc<LanguageModelRunner>,
+    semantic_search: Arc<dyn SemanticSearch>,
+    proposal: EvalProposal,
+    sample_size: usize,
+) {
+    if let Err(e) = generate_proposal(
+        db.clone(),
+        cache,
+        language_model,
+        semantic_search,
+        &proposal,
+        sample_size,
+    )
+    .await
+    {
+        log::error!("Failed to generate eval proposal {}: {:?}", proposal.id, e);
+        if let Err(e) =
+            db::eval_proposals::set_eval_proposal_failed(&db.pool, &proposal.id, &e.to_string())
+                .await
+        {
+            log::error!(
+                "Failed to mark eval proposal {} as failed: {:?}",
+                proposal.id,
+                e
+            );
+        }
+    }
+}
+
+fn handle(name: Option<&str>, handle_type: HandleType) -> Handle {
+    Handle {
+        id: Uuid::new_v4(),
+        name: name.map(String::from),
+        handle_type,
+        is_cyclic: false,
+    }
+}
+
+/// Runnable graph of an LLM judge with the `span_input` and `span_output` inputs of online
+/// evaluators, the same graph that the frontend's evaluator editor builds
+fn judge_graph(rubric: &str) -> Result<Value> {
+    let input_nodes = ["span_input", "span_output"].map(|name| InputNode {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        outputs: vec![handle(None, HandleType::String)],
+        input: None,
+        input_type: HandleType::String,
+    });
+    let dynamic_inputs = input_nodes
+        .iter()
+        .map(|node| handle(Some(&node.name), HandleType::String))
+        .collect::<Vec<_>>();
+
+    // the rubric comes from a language model, it must not add template variables
+    let rubric = rubric.replace("{{", "{").replace("}}", "}");
+    let llm_node = LLMNode {
+        id: Uuid::new_v4(),
+        name: "judge".to_string(),
+        inputs: vec![],
+        inputs_mappings: dynamic_inputs
+            .iter()
+            .zip(&input_nodes)
+            .map(|(input, node)| (input.id, node.outputs[0].id))
+            .collect(),
+        dynamic_inputs,
+        outputs: vec![handle(None, HandleType::String)],
+        prompt: format!(
+            "You are an evaluator of the outputs of a language model. Decide if the output \
+            meets the rubric below. Provide your reasoning, then a final verdict of either 'true' \
+            if the output meets the rubric, or 'false' if it does not.\n\n\
+            <rubric>{}</rubric>\n\n\
+            <llm_input>{{{{span_input}}}}</llm_input>\n\n\
+            <llm_output>{{{{span_output}}}}</llm_output>",
+            rubric
+        ),
+        model: Some(model()),
+        model_params: None,
+        stream: false,
+        structured_output_params: StructuredOutputParams {
+            structured_output_enabled: true,
+            structured_output_max_retries: 0,
+            structured_output_schema: Some(JUDGE_OUTPUT_SCHEMA.to_string()),
+            structured_output_schema_target: Some("Output".to_string()),
+        },
+    };
+    let output_input = handle(Some("output"), HandleType::Any);
+    let output_node = OutputNode {
+        id: Uuid::new_v4(),
+        name: "output".to_string(),
+        inputs_mappings: HashMap::from([(output_input.id, llm_node.outputs[0].id)]),
+        inputs: vec![output_input],
+        output_cast_type: None,
+    };
+
+    let pred = HashMap::from([
+        (
+            llm_node.id,
+            input_nodes.iter().map(|node| node.id).collect::<Vec<_>>(),
+        ),
+        (output_node.id, vec![llm_node.id]),
+    ]);
+    let mut nodes = input_nodes
+        .into_iter()
+        .map(|node| (node.id.to_string(), Node::Input(node)))
+        .collect::<HashMap<_, _>>();
+    nodes.insert(llm_node.id.to_string(), Node::LLM(llm_node));
+    nodes.insert(output_node.id.to_string(), Node::Output(output_node));
+
+    Ok(json!({ "nodes": nodes, "pred": pred }))
+}
+
+/// Creates the proposed dataset and a judge evaluator that runs on new spans at the proposal's
+/// path. The proposal must be claimed, see `claim_eval_proposal`.
+pub async fn adopt_proposal(db: &DB, proposal: &EvalProposal) -> Result<(Dataset, LabelClass)> {
+    let datapoints = serde_json::from_value::<Vec<ProposedDatapoint>>(proposal.datapoints.clone())?;
+    let rubric = proposal.rubric.clone().unwrap_or_default();
+    let name = format!(
+        "{} ({})",
+        proposal.span_path,
+        proposal.created_at.format("%Y-%m-%d %H:%M")
+    );
+
+    let dataset = db::datasets::create_dataset(&db.pool, &name, proposal.project_id).await?;
+    let datapoints = datapoints
+        .into_iter()
+        .map(|datapoint| Datapoint {
+            id: Uuid::new_v4(),
+            dataset_id: dataset.id,
+            data: datapoint.data,
+            target: Some(datapoint.target),
+            metadata: Some(json!({
+                "spanId": datapoint.span_id,
+                "traceId": datapoint.trace_id,
+            })),
+        })
+        .collect();
+    db::datapoints::insert_datapoints(&db.pool, &dataset.id, datapoints).await?;
+
+    let label_class = db::labels::create_label_class(
+        &db.pool,
+        Uuid::new_v4(),
+        name,
+        proposal.project_id,
+        &LabelType::BOOLEAN,
+        json!({ "false": 0, "true": 1 }),
+        Some(rubric.clone()),
+        Some(judge_graph(&rubric)?),
+    )
+    .await?;
+    db::labels::register_label_class_for_path(
+        &db.pool,
+        proposal.project_id,
+        label_class.id,
+        &proposal.span_path,
+    )
+    .await?;
+
+    db::eval_proposals::set_eval_proposal_adopted(
+        &db.pool,
+        &proposal.id,
+        &dataset.id,
+        &label_class.id,
+    )
+    .await?;
+
+    Ok((dataset, label_class))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::Graph;
+
+    use super::*;
+
+    #[test]
+    fn test_least_covered() {
+        let closest_similarity = vec![1.0, 0.9, 0.2, 0.5];
+        let mut picked = vec![true, false, false, false];
+        assert_eq!(least_covered(&closest_similarity, &picked), Some(2));
+
+        picked[2] = true;
+        assert_eq!(least_covered(&closest_similarity, &picked), Some(3));
+        assert_eq!(least_covered(&closest_similarity, &[true; 4]), None);
+    }
+
+    #[test]
+    fn test_judge_graph() {
+        let graph = judge_graph("The answer cites a {{source}}").unwrap();
+        let graph = serde_json::from_value::<Graph>(graph).unwrap();
+        assert_eq!(graph.nodes.len(), 4);
+
+        let Some(Node::LLM(llm_node)) = graph.nodes.values().find(|n| matches!(n, Node::LLM(_)))
+        else {
+            panic!("LLM node not found");
+        };
+        assert!(llm_node.prompt.contains("cites a {source}"));
+        assert!(llm_node
+            .prompt
+            .contains("<llm_output>{{span_output}}</llm_output>"));
+        assert_eq!(graph.pred[&llm_node.id].len(), 2);
+    }
+}
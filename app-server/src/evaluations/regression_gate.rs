@@ -0,0 +1,250 @@
+//! Regression gate for CI: compares the scores of an evaluation with the baseline evaluation of
+//! its group, so that changes that lower the scores can be blocked before they are merged.
+//!
+//! Averages of numeric scores and pass rates of boolean scores are compared, higher is better.
+//! Categorical scores have no average and are not compared.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db::evaluations::{self, Evaluation};
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RegressionThresholds {
+    /// Largest allowed drop of a score's average, e.g. 0.05. Without this and
+    /// `maxRelativeDropPercent`, any drop fails the gate.
+    #[serde(default)]
+    pub max_absolute_drop: Option<f64>,
+    /// Largest allowed drop of a score's average in percent of the baseline's average
+    #[serde(default)]
+    pub max_relative_drop_percent: Option<f64>,
+    /// Scores with fewer datapoints in either evaluation fail the gate
+    #[serde(default = "default_min_sample_size")]
+    pub min_sample_size: i64,
+}
+
+fn default_min_sample_size() -> i64 {
+    1
+}
+
+impl Default for RegressionThresholds {
+    fn default() -> Self {
+        Self {
+            max_absolute_drop: None,
+            max_relative_drop_percent: None,
+            min_sample_size: default_min_sample_size(),
+        }
+    }
+}
+
+/// Average and number of datapoints of a score in one evaluation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreSample {
+    pub average: f64,
+    pub count: i64,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreGateResult {
+    pub name: String,
+    pub average: Option<f64>,
+    pub baseline_average: Option<f64>,
+    pub count: i64,
+    pub baseline_count: i64,
+    /// `average - baseline_average`
+    pub delta: Option<f64>,
+    /// Delta in percent of the baseline's average, None if the baseline's average is 0
+    pub relative_delta_percent: Option<f64>,
+    pub passed: bool,
+    /// Why the score failed the gate
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RegressionGateResult {
+    /// Whether all scores passed
+    pub passed: bool,
+    pub evaluation_id: Uuid,
+    pub baseline_evaluation_id: Uuid,
+    pub scores: Vec<ScoreGateResult>,
+}
+
+/// Checks one score against the thresholds. A score missing in either evaluation fails.
+pub fn check_score(
+    name: String,
+    sample: Option<ScoreSample>,
+    baseline: Option<ScoreSample>,
+    thresholds: &RegressionThresholds,
+) -> ScoreGateResult {
+    let mut result = ScoreGateResult {
+        name,
+        average: sample.map(|sample| sample.average),
+        baseline_average: baseline.map(|baseline| baseline.average),
+        count: sample.map_or(0, |sample| sample.count),
+        baseline_count: baseline.map_or(0, |baseline| baseline.count),
+        delta: None,
+        relative_delta_percent: None,
+        passed: false,
+        reason: None,
+    };
+    let (Some(sample), Some(baseline)) = (sample, baseline) else {
+        result.reason = Some(match sample {
+            None => "Score is missing in the evaluation".to_string(),
+            Some(_) => "Score is missing in the baseline evaluation".to_string(),
+        });
+        return result;
+    };
+
+    let delta = sample.average - baseline.average;
+    let relative_delta_percent =
+        (baseline.average != 0.0).then(|| delta * 100.0 / baseline.average.abs());
+    result.delta = Some(delta);
+    result.relative_delta_percent = relative_delta_percent;
+
+    result.reason = if sample.count.min(baseline.count) < thresholds.min_sample_size {
+        Some(format!(
+            "Fewer than {} datapoints to compare",
+            thresholds.min_sample_size
+        ))
+    } else if let Some(max_drop) = thresholds
+        .max_absolute_drop
+        .filter(|max_drop| -delta > *max_drop)
+    {
+        Some(format!(
+            "Average dropped by {:.4}, more than {}",
+            -delta, max_drop
+        ))
+    } else if let (Some(max_drop), Some(relative_delta)) =
+        (thresholds.max_relative_drop_percent, relative_delta_percent)
+    {
+        (-relative_delta > max_drop).then(|| {
+            format!(
+                "Average dropped by {:.2}%, more than {}%",
+                -relative_delta, max_drop
+            )
+        })
+    } else if thresholds.max_absolute_drop.is_none()
+        && thresholds.max_relative_drop_percent.is_none()
+        && delta < -f64::EPSILON
+    {
+        Some(format!("Average dropped by {:.4}", -delta))
+    } else {
+        None
+    };
+    result.passed = result.reason.is_none();
+    result
+}
+
+async fn score_samples(pool: &PgPool, evaluation_id: Uuid) -> Result<HashMap<String, ScoreSample>> {
+    let (_, averages) = evaluations::get_evaluation_score_averages(pool, evaluation_id).await?;
+    Ok(averages
+        .into_iter()
+        .map(|average| {
+            (
+                average.name,
+                ScoreSample {
+                    average: average.average,
+                    count: average.count,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Compares the scores of the evaluation with the baseline. Only the given score names are
+/// compared, all scores of the baseline if there are none.
+pub async fn run_regression_gate(
+    pool: &PgPool,
+    evaluation: &Evaluation,
+    baseline: &Evaluation,
+    thresholds: &RegressionThresholds,
+    score_names: Vec<String>,
+) -> Result<RegressionGateResult> {
+    let samples = score_samples(pool, evaluation.id).await?;
+    let baseline_samples = score_samples(pool, baseline.id).await?;
+
+    let mut names = if score_names.is_empty() {
+        baseline_samples.keys().cloned().collect::<Vec<_>>()
+    } else {
+        score_names
+    };
+    names.sort();
+    names.dedup();
+
+    let scores = names
+        .into_iter()
+        .map(|name| {
+            let sample = samples.get(&name).copied();
+            let baseline = baseline_samples.get(&name).copied();
+            check_score(name, sample, baseline, thresholds)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(RegressionGateResult {
+        passed: scores.iter().all(|score| score.passed),
+        evaluation_id: evaluation.id,
+        baseline_evaluation_id: baseline.id,
+        scores,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(average: f64, count: i64) -> Option<ScoreSample> {
+        Some(ScoreSample { average, count })
+    }
+
+    #[test]
+    fn test_check_score() {
+        let check = |sample, baseline, thresholds: &RegressionThresholds| {
+            check_score("accuracy".to_string(), sample, baseline, thresholds)
+        };
+
+        // any drop fails without thresholds
+        let strict = RegressionThresholds::default();
+        assert!(check(sample(0.8, 10), sample(0.8, 10), &strict).passed);
+        assert!(check(sample(0.9, 10), sample(0.8, 10), &strict).passed);
+        assert!(!check(sample(0.79, 10), sample(0.8, 10), &strict).passed);
+
+        let absolute = RegressionThresholds {
+            max_absolute_drop: Some(0.05),
+            ..Default::default()
+        };
+        assert!(check(sample(0.76, 10), sample(0.8, 10), &absolute).passed);
+        assert!(!check(sample(0.7, 10), sample(0.8, 10), &absolute).passed);
+
+        let relative = RegressionThresholds {
+            max_relative_drop_percent: Some(10.0),
+            ..Default::default()
+        };
+        let result = check(sample(0.37, 10), sample(0.4, 10), &relative);
+        assert!((result.relative_delta_percent.unwrap() + 7.5).abs() < 1e-9);
+        assert!(result.passed);
+        assert!(!check(sample(0.3, 10), sample(0.4, 10), &relative).passed);
+
+        let min_sample = RegressionThresholds {
+            min_sample_size: 20,
+            ..Default::default()
+        };
+        let result = check(sample(0.9, 10), sample(0.8, 30), &min_sample);
+        assert!(!result.passed);
+        assert_eq!(
+            result.reason.as_deref(),
+            Some("Fewer than 20 datapoints to compare")
+        );
+
+        let result = check(None, sample(0.8, 10), &strict);
+        assert!(!result.passed);
+        assert_eq!(result.baseline_count, 10);
+    }
+}
@@ -0,0 +1,441 @@
+//! Evaluations that run on a cron schedule. Each run evaluates the current datapoints of the
+//! schedule's dataset: the pipeline version deployed to the schedule's target, or the pinned
+//! version, is run on every datapoint, the outputs are scored by the schedule's judges and the
+//! results are saved as an evaluation in the schedule's group.
+//!
+//! Like pipeline triggers, due schedules queue runs in `evaluation_schedule_runs`, which also
+//! keeps the run history, and rows are claimed with `SKIP LOCKED`. A schedule that is due while
+//! its previous run hasn't finished records a skipped run instead of running twice at once.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    api::{utils::query_deployed_pipeline_version, v1::pipelines::record_node_usage},
+    cache::Cache,
+    datasets::datapoints::Datapoint,
+    db::{
+        self,
+        activity::{ActivityType, NewActivity},
+        evaluation_schedules::{
+            self, EvaluationSchedule, EvaluationScheduleRun, EvaluationScheduleRunStatus,
+        },
+        pipelines::{pipeline_usage, PipelineVersion},
+        trace::TraceType,
+        DB,
+    },
+    language_model::LanguageModelRunner,
+    pipeline::{nodes::NodeInput, runner::PipelineRunner, triggers::next_run_at, Graph, RunType},
+    routes::activity::record_activity,
+    traces::evaluators::get_stored_env,
+};
+
+use super::{judge, progress::EvaluationProgressHub, utils::EvaluationDatapointResult};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Runs executed at once by an instance
+const MAX_CONCURRENT_RUNS: usize = 4;
+/// Datapoints of a run that the pipeline runs on at once
+const DATAPOINT_CONCURRENCY: usize = 8;
+/// Larger datasets are evaluated through the API instead
+const MAX_DATAPOINTS: usize = 1000;
+/// Runs that have been running for longer are assumed to be lost, e.g. with a restarted
+/// instance, and no longer keep the schedule's next runs from starting
+const RUN_TIMEOUT_HOURS: i64 = 6;
+
+pub async fn run_evaluation_schedules_periodically(
+    pipeline_runner: Arc<PipelineRunner>,
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    progress_hub: Arc<EvaluationProgressHub>,
+) {
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_RUNS));
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = evaluation_schedules::enqueue_due_schedule_runs(
+            &db.pool,
+            chrono::Duration::hours(RUN_TIMEOUT_HOURS),
+            |expression| next_run_at(expression, Utc::now()),
+        )
+        .await
+        {
+            log::error!("Failed to queue scheduled evaluation runs: {:?}", e);
+        }
+
+        loop {
+            let permit = permits.clone().acquire_owned().await.unwrap();
+            let run = match evaluation_schedules::claim_queued_schedule_run(&db.pool).await {
+                Ok(Some(run)) => run,
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("Failed to claim queued evaluation schedule run: {:?}", e);
+                    break;
+                }
+            };
+
+            let pipeline_runner = pipeline_runner.clone();
+            let language_model = language_model.clone();
+            let db = db.clone();
+            let cache = cache.clone();
+            let analytics_store = analytics_store.clone();
+            let progress_hub = progress_hub.clone();
+            tokio::spawn(async move {
+                execute_schedule_run(
+                    pipeline_runner,
+                    language_model,
+                    db,
+                    cache,
+                    analytics_store,
+                    progress_hub,
+                    run,
+                )
+                .await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+async fn execute_schedule_run(
+    pipeline_runner: Arc<PipelineRunner>,
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    progress_hub: Arc<EvaluationProgressHub>,
+    run: EvaluationScheduleRun,
+) {
+    let schedule = match evaluation_schedules::get_schedule(&db.pool, &run.schedule_id).await {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            log::error!("Failed to get evaluation schedule: {:?}", e);
+            None
+        }
+    };
+    let result = match &schedule {
+        Some(schedule) => {
+            run_scheduled_evaluation(
+                pipeline_runner,
+                language_model,
+                db.clone(),
+                cache,
+                analytics_store,
+                progress_hub,
+                schedule,
+                &run,
+            )
+            .await
+        }
+        None => Err(anyhow::anyhow!("Schedule has been deleted")),
+    };
+    let (status, evaluation_id, error) = match result {
+        Ok(evaluation_id) => (
+            EvaluationScheduleRunStatus::SUCCEEDED,
+            Some(evaluation_id),
+            None,
+        ),
+        Err(e) => (
+            EvaluationScheduleRunStatus::FAILED,
+            None,
+            Some(e.to_string()),
+        ),
+    };
+
+    if let Err(e) = evaluation_schedules::finish_schedule_run(
+        &db.pool,
+        &run.id,
+        status,
+        evaluation_id,
+        error.clone(),
+    )
+    .await
+    {
+        log::error!(
+            "Failed to record evaluation schedule run. id [{}]: {:?}",
+            run.id,
+            e
+        );
+    }
+
+    if let (Some(schedule), Some(error)) = (schedule, error) {
+        log::warn!(
+            "Scheduled evaluation run failed. schedule_id [{}], id [{}]: {}",
+            schedule.id,
+            run.id,
+            error
+        );
+        notify_failure(db, &schedule, &run, error);
+    }
+}
+
+/// Adds the failure to the project's activity feed, which is where members are notified of it
+fn notify_failure(
+    db: Arc<DB>,
+    schedule: &EvaluationSchedule,
+    run: &EvaluationScheduleRun,
+    error: String,
+) {
+    record_activity(
+        db,
+        NewActivity {
+            project_id: schedule.project_id,
+            activity_type: ActivityType::EVALUATION_SCHEDULE_FAILED,
+            actor_id: None,
+            resource_id: Some(schedule.id),
+            summary: format!("Scheduled evaluation {} failed: {}", schedule.name, error),
+            details: json!({
+                "scheduleId": schedule.id,
+                "scheduleRunId": run.id,
+                "error": error,
+            }),
+        },
+    );
+}
+
+/// The version of the pipeline that the schedule evaluates
+async fn evaluated_pipeline_version(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    schedule: &EvaluationSchedule,
+    pipeline_name: &str,
+) -> Result<PipelineVersion> {
+    if let Some(pipeline_version_id) = &schedule.pipeline_version_id {
+        return db::pipelines::pipeline_version::get_pipeline_version(
+            &db.pool,
+            pipeline_version_id,
+        )
+        .await;
+    }
+    query_deployed_pipeline_version(
+        db,
+        cache,
+        schedule.project_id,
+        pipeline_name.to_string(),
+        &schedule.target,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("{}", e))?
+    .ok_or(anyhow::anyhow!(
+        "Pipeline {} has no version deployed to {}",
+        pipeline_name,
+        schedule.target
+    ))
+}
+
+/// Runs the evaluation and returns the id of the created evaluation
+async fn run_scheduled_evaluation(
+    pipeline_runner: Arc<PipelineRunner>,
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    progress_hub: Arc<EvaluationProgressHub>,
+    schedule: &EvaluationSchedule,
+    run: &EvaluationScheduleRun,
+) -> Result<Uuid> {
+    let project_id = schedule.project_id;
+    let pipeline = db::pipelines::get_pipeline_by_id(&db.pool, &schedule.pipeline_id).await?;
+    let pipeline_version =
+        evaluated_pipeline_version(db.clone(), cache.clone(), schedule, &pipeline.name).await?;
+
+    let judges = db::judge_evaluators::get_judge_evaluators_by_names(
+        &db.pool,
+        &project_id,
+        &schedule.judges,
+    )
+    .await?;
+    let unknown = schedule
+        .judges
+        .iter()
+        .filter(|name| !judges.iter().any(|judge| &judge.name == *name))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Judge evaluators not found: {}",
+            unknown.join(", ")
+        ));
+    }
+    let mut env = get_stored_env(db.clone(), project_id).await?;
+    let missing = judge::missing_env_vars(&judges, &env);
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The project has no provider api keys for the judges: {}",
+            missing.join(", ")
+        ));
+    }
+
+    let dataset_version =
+        db::datasets::get_dataset_version(&db.pool, &project_id, &schedule.dataset_id)
+            .await?
+            .ok_or(anyhow::anyhow!("Dataset has been deleted"))?;
+    let datapoints = db::datapoints::get_all_datapoints(&db.pool, schedule.dataset_id).await?;
+    if datapoints.is_empty() {
+        return Err(anyhow::anyhow!("Dataset has no datapoints"));
+    }
+    if datapoints.len() > MAX_DATAPOINTS {
+        return Err(anyhow::anyhow!(
+            "Dataset has more than {} datapoints",
+            MAX_DATAPOINTS
+        ));
+    }
+
+    env.insert("collection_name".to_string(), project_id.to_string());
+    let pipeline_version_name = format!("{}.{}", pipeline.name, pipeline_version.name);
+    let metadata = HashMap::from([
+        ("evaluationScheduleId".to_string(), schedule.id.to_string()),
+        ("evaluationScheduleRunId".to_string(), run.id.to_string()),
+    ]);
+    let results = stream::iter(datapoints)
+        .map(|datapoint| {
+            run_pipeline_on_datapoint(
+                pipeline_runner.clone(),
+                db.clone(),
+                &pipeline_version,
+                &pipeline_version_name,
+                &env,
+                &metadata,
+                project_id,
+                datapoint,
+            )
+        })
+        .buffered(DATAPOINT_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut first_error = None;
+    let mut points = Vec::with_capacity(results.len());
+    for (datapoint, output) in results {
+        let executor_output = match output {
+            Ok(output) => Some(output),
+            Err(e) => {
+                first_error.get_or_insert(e);
+                None
+            }
+        };
+        points.push(EvaluationDatapointResult {
+            data: datapoint.data,
+            target: datapoint.target.unwrap_or(Value::Null),
+            executor_output,
+            trace_id: Uuid::nil(),
+            scores: HashMap::new(),
+            human_evaluators: vec![],
+            executor_span_id: Uuid::nil(),
+            timestamp: None,
+        });
+    }
+    if points.iter().all(|point| point.executor_output.is_none()) {
+        let error = first_error.unwrap_or(anyhow::anyhow!("No outputs"));
+        return Err(anyhow::anyhow!(
+            "The pipeline failed on every datapoint: {}",
+            error
+        ));
+    }
+
+    judge::score_with_judges(
+        db.clone(),
+        cache,
+        language_model,
+        &env,
+        &judges,
+        &mut points,
+    )
+    .await;
+
+    let scheduled_at = run.scheduled_at.unwrap_or(run.created_at);
+    let evaluation = super::create_evaluation(
+        db,
+        analytics_store,
+        progress_hub,
+        project_id,
+        format!(
+            "{} {}",
+            schedule.name,
+            scheduled_at.format("%Y-%m-%d %H:%M")
+        ),
+        schedule.group_id.clone(),
+        Some(schedule.dataset_id),
+        Some(dataset_version),
+        points,
+    )
+    .await?;
+
+    Ok(evaluation.id)
+}
+
+/// Runs the pipeline with the datapoint's data as its inputs. The output is the value of the
+/// pipeline's output node, or an object of the values by node name if it has several.
+async fn run_pipeline_on_datapoint(
+    pipeline_runner: Arc<PipelineRunner>,
+    db: Arc<DB>,
+    pipeline_version: &PipelineVersion,
+    pipeline_version_name: &String,
+    env: &HashMap<String, String>,
+    metadata: &HashMap<String, String>,
+    project_id: Uuid,
+    datapoint: Datapoint,
+) -> (Datapoint, Result<Value>) {
+    let output = async {
+        let Value::Object(data) = &datapoint.data else {
+            return Err(anyhow::anyhow!(
+                "Datapoint data must be an object of inputs"
+            ));
+        };
+        if !pipeline_usage::reserve_pipeline_execution(&db.pool, &pipeline_version.pipeline_id)
+            .await?
+        {
+            return Err(anyhow::anyhow!(
+                "Pipeline has used up its monthly execution quota"
+            ));
+        }
+        let inputs = data
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone().into()))
+            .collect::<HashMap<String, NodeInput>>();
+        let mut metadata = metadata.clone();
+        metadata.insert("datapointId".to_string(), datapoint.id.to_string());
+
+        let mut graph = serde_json::from_value::<Graph>(pipeline_version.runnable_graph.clone())?;
+        graph.setup(&inputs, env, &metadata, &RunType::Endpoint)?;
+        graph.project_id = Some(project_id);
+
+        let run_result = pipeline_runner.run(graph, None).await;
+        if let Err(e) = pipeline_runner
+            .record_observations(
+                &run_result,
+                &project_id,
+                pipeline_version_name,
+                None,
+                Some(TraceType::EVALUATION),
+            )
+            .await
+        {
+            log::error!(
+                "Failed to record observations of scheduled evaluation: {:?}",
+                e
+            );
+        }
+        record_node_usage(db.clone(), pipeline_version.pipeline_id, &run_result);
+
+        let mut outputs = run_result?.output_values();
+        if outputs.len() == 1 {
+            let value = outputs.drain().next().unwrap().1;
+            return Ok(value.into());
+        }
+        Ok(json!(outputs))
+    }
+    .await;
+
+    (datapoint, output)
+}
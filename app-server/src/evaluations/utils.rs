@@ -1,19 +1,78 @@
 use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Result;
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::db::{self, DB};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct HumanEvaluator {
     pub queue_name: String,
 }
 
-#[derive(Deserialize)]
+/// Kind of a score, stored with the score
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "evaluation_score_type")]
+pub enum ScoreType {
+    NUMERIC,
+    /// Pass or fail, aggregated into a pass rate
+    BOOLEAN,
+    /// Label such as `relevant` or `off-topic`, aggregated into label frequencies
+    CATEGORICAL,
+}
+
+impl ScoreType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScoreType::NUMERIC => "NUMERIC",
+            ScoreType::BOOLEAN => "BOOLEAN",
+            ScoreType::CATEGORICAL => "CATEGORICAL",
+        }
+    }
+}
+
+/// Score as reported by an evaluator: a number, a boolean or a string label
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(untagged)]
+pub enum ScoreValue {
+    Number(f64),
+    Boolean(bool),
+    Label(String),
+}
+
+impl ScoreValue {
+    pub fn score_type(&self) -> ScoreType {
+        match self {
+            ScoreValue::Number(_) => ScoreType::NUMERIC,
+            ScoreValue::Boolean(_) => ScoreType::BOOLEAN,
+            ScoreValue::Label(_) => ScoreType::CATEGORICAL,
+        }
+    }
+
+    /// Value used in numeric aggregations, 1 and 0 for booleans so that their average is the
+    /// pass rate. None for labels.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ScoreValue::Number(value) => Some(*value),
+            ScoreValue::Boolean(passed) => Some(if *passed { 1.0 } else { 0.0 }),
+            ScoreValue::Label(_) => None,
+        }
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            ScoreValue::Label(label) => Some(label),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EvaluationDatapointResult {
     pub data: Value,
@@ -21,11 +80,14 @@ pub struct EvaluationDatapointResult {
     pub executor_output: Option<Value>,
     #[serde(default)]
     pub trace_id: Uuid,
-    pub scores: HashMap<String, f64>,
+    pub scores: HashMap<String, ScoreValue>,
     #[serde(default)]
     pub human_evaluators: Vec<HumanEvaluator>,
     #[serde(default)]
     pub executor_span_id: Uuid,
+    /// When the datapoint was evaluated, defaults to when the server received it
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
 }
 
 pub struct DatapointColumns {
@@ -33,7 +95,7 @@ pub struct DatapointColumns {
     pub targets: Vec<Value>,
     pub executor_outputs: Vec<Option<Value>>,
     pub trace_ids: Vec<Uuid>,
-    pub scores: Vec<HashMap<String, f64>>,
+    pub scores: Vec<HashMap<String, ScoreValue>>,
 }
 
 pub fn get_columns_from_points(points: &Vec<EvaluationDatapointResult>) -> DatapointColumns {
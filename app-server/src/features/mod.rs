@@ -1,6 +1,12 @@
 /// This module contains feature flags that can be used to enable or disable certain features in the application.
 // TODO: consider https://doc.rust-lang.org/reference/conditional-compilation.html instead
 use std::env;
+#[cfg(feature = "test-support")]
+use std::sync::OnceLock;
+
+/// Set by processes that don't read `ENVIRONMENT`, see `set_environment`
+#[cfg(feature = "test-support")]
+static ENVIRONMENT: OnceLock<String> = OnceLock::new();
 
 pub enum Feature {
     UsageLimit,
@@ -10,22 +16,44 @@ pub enum Feature {
     Storage,
     /// Build all containers. If false, only lite part is used: app-server, postgres, frontend
     FullBuild,
+    /// Archive raw ingestion payloads to S3, so that they can be replayed later
+    PayloadArchive,
+    /// Record the app-server's own requests and ClickHouse queries as traces of a system project
+    SelfTracing,
+}
+
+/// Run in the given environment instead of the one in `ENVIRONMENT`, e.g. the test server runs
+/// in `LITE`. Only the first call has an effect.
+#[cfg(feature = "test-support")]
+pub fn set_environment(environment: &str) {
+    let _ = ENVIRONMENT.set(environment.to_string());
+}
+
+fn environment() -> Option<String> {
+    #[cfg(feature = "test-support")]
+    if let Some(environment) = ENVIRONMENT.get() {
+        return Some(environment.clone());
+    }
+    env::var("ENVIRONMENT").ok()
 }
 
 pub fn is_feature_enabled(feature: Feature) -> bool {
     match feature {
         Feature::UsageLimit | Feature::Subscription => {
-            env::var("ENVIRONMENT") == Ok("PRODUCTION".to_string())
+            environment().as_deref() == Some("PRODUCTION")
         }
         Feature::Storage => {
             env::var("AWS_ACCESS_KEY_ID").is_ok()
                 && env::var("AWS_SECRET_ACCESS_KEY").is_ok()
                 && env::var("S3_IMGS_BUCKET").is_ok()
         }
-        Feature::FullBuild => ["FULL", "PRODUCTION"].contains(
-            &env::var("ENVIRONMENT")
-                .expect("ENVIRONMENT must be set")
-                .as_str(),
-        ),
+        Feature::PayloadArchive => {
+            env::var("AWS_ACCESS_KEY_ID").is_ok()
+                && env::var("AWS_SECRET_ACCESS_KEY").is_ok()
+                && env::var("S3_INGESTION_ARCHIVE_BUCKET").is_ok()
+        }
+        Feature::SelfTracing => env::var("SELF_TRACING_PROJECT_ID").is_ok(),
+        Feature::FullBuild => ["FULL", "PRODUCTION"]
+            .contains(&environment().expect("ENVIRONMENT must be set").as_str()),
     }
 }
@@ -0,0 +1,32 @@
+//! Ids generated by the app-server, as opposed to the ones generated by Postgres defaults.
+//!
+//! With the `test-support` feature, the test server can make ids sequential, i.e.
+//! `00000000-0000-0000-0000-000000000001`, `...0002` and so on, so that tests can assert on them.
+
+use uuid::Uuid;
+
+#[cfg(feature = "test-support")]
+static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+#[cfg(feature = "test-support")]
+static DETERMINISTIC: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(feature = "test-support")]
+pub fn set_deterministic(deterministic: bool) {
+    DETERMINISTIC.store(deterministic, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub fn is_deterministic() -> bool {
+    #[cfg(feature = "test-support")]
+    return DETERMINISTIC.load(std::sync::atomic::Ordering::Relaxed);
+    #[cfg(not(feature = "test-support"))]
+    false
+}
+
+pub fn new_id() -> Uuid {
+    #[cfg(feature = "test-support")]
+    if is_deterministic() {
+        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        return Uuid::from_u128(id as u128);
+    }
+    Uuid::new_v4()
+}
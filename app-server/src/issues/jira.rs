@@ -0,0 +1,138 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::network::egress;
+
+use super::{CreatedIssue, IssueTracker, NewIssue};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JiraConfig {
+    /// e.g. https://example.atlassian.net
+    base_url: String,
+    /// Account of the api token
+    email: String,
+    project_key: String,
+    #[serde(default = "default_issue_type")]
+    issue_type: String,
+}
+
+fn default_issue_type() -> String {
+    "Bug".to_string()
+}
+
+/// Jira Cloud, authenticated with an account email and api token
+pub struct Jira {
+    client: reqwest::Client,
+    config: JiraConfig,
+    token: String,
+}
+
+impl Jira {
+    pub fn new(config: &Value, token: String) -> Result<Self> {
+        let mut config = serde_json::from_value::<JiraConfig>(config.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid Jira config: {}", e))?;
+        let base_url = url::Url::parse(&config.base_url)?;
+        if base_url.scheme() != "https" {
+            return Err(anyhow::anyhow!("Jira base url must use https"));
+        }
+        config.base_url = config.base_url.trim_end_matches('/').to_string();
+
+        Ok(Self {
+            client: egress::http_client(),
+            config,
+            token,
+        })
+    }
+}
+
+/// Jira's REST API v3 only accepts descriptions in Atlassian Document Format
+fn to_adf(text: &str) -> Value {
+    let paragraphs = text
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .map(|paragraph| {
+            json!({
+                "type": "paragraph",
+                "content": [{ "type": "text", "text": paragraph }],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "type": "doc", "version": 1, "content": paragraphs })
+}
+
+#[derive(Deserialize)]
+struct CreateIssueResponse {
+    id: String,
+    key: String,
+}
+
+#[async_trait]
+impl IssueTracker for Jira {
+    async fn create_issue(&self, issue: &NewIssue) -> Result<CreatedIssue> {
+        let body = json!({
+            "fields": {
+                "project": { "key": self.config.project_key },
+                "issuetype": { "name": self.config.issue_type },
+                "summary": issue.title,
+                "description": to_adf(&issue.description),
+            }
+        });
+
+        let res = self
+            .client
+            .post(format!("{}/rest/api/3/issue", self.config.base_url))
+            .basic_auth(&self.config.email, Some(&self.token))
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<CreateIssueResponse>()
+            .await?;
+
+        Ok(CreatedIssue {
+            url: format!("{}/browse/{}", self.config.base_url, res.key),
+            external_id: res.id,
+            key: res.key,
+        })
+    }
+
+    async fn get_issue_status(&self, external_id: &str) -> Result<String> {
+        let res = self
+            .client
+            .get(format!(
+                "{}/rest/api/3/issue/{}",
+                self.config.base_url, external_id
+            ))
+            .query(&[("fields", "status")])
+            .basic_auth(&self.config.email, Some(&self.token))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        res.pointer("/fields/status/name")
+            .and_then(|name| name.as_str())
+            .map(|name| name.to_string())
+            .ok_or(anyhow::anyhow!("Jira issue {} has no status", external_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_adf() {
+        let adf = to_adf("Trace failed\n\n\n\nView in Laminar: http://localhost:3000");
+        assert_eq!(adf["content"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            adf.pointer("/content/1/content/0/text").unwrap(),
+            "View in Laminar: http://localhost:3000"
+        );
+    }
+}
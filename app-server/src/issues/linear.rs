@@ -0,0 +1,106 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::network::egress;
+
+use super::{CreatedIssue, IssueTracker, NewIssue};
+
+const LINEAR_API_URL: &str = "https://api.linear.app/graphql";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LinearConfig {
+    team_id: String,
+}
+
+/// Linear, authenticated with a personal api key
+pub struct Linear {
+    client: reqwest::Client,
+    config: LinearConfig,
+    token: String,
+}
+
+impl Linear {
+    pub fn new(config: &Value, token: String) -> Result<Self> {
+        let config = serde_json::from_value::<LinearConfig>(config.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid Linear config: {}", e))?;
+
+        Ok(Self {
+            client: egress::http_client(),
+            config,
+            token,
+        })
+    }
+
+    async fn query(&self, query: &str, variables: Value) -> Result<Value> {
+        let mut res = self
+            .client
+            .post(LINEAR_API_URL)
+            .header("Authorization", &self.token)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        // GraphQL errors are returned with status 200
+        if let Some(errors) = res.get("errors") {
+            return Err(anyhow::anyhow!("Linear request failed: {}", errors));
+        }
+        Ok(res["data"].take())
+    }
+}
+
+#[async_trait]
+impl IssueTracker for Linear {
+    async fn create_issue(&self, issue: &NewIssue) -> Result<CreatedIssue> {
+        let data = self
+            .query(
+                "mutation IssueCreate($input: IssueCreateInput!) {
+                    issueCreate(input: $input) { success issue { id identifier url } }
+                }",
+                json!({
+                    "input": {
+                        "teamId": self.config.team_id,
+                        "title": issue.title,
+                        "description": issue.description,
+                    }
+                }),
+            )
+            .await?;
+
+        let created = &data["issueCreate"]["issue"];
+        let field = |name: &str| {
+            created[name]
+                .as_str()
+                .map(|value| value.to_string())
+                .ok_or(anyhow::anyhow!("Linear issue was not created"))
+        };
+
+        Ok(CreatedIssue {
+            external_id: field("id")?,
+            key: field("identifier")?,
+            url: field("url")?,
+        })
+    }
+
+    async fn get_issue_status(&self, external_id: &str) -> Result<String> {
+        let data = self
+            .query(
+                "query Issue($id: String!) { issue(id: $id) { state { name } } }",
+                json!({ "id": external_id }),
+            )
+            .await?;
+
+        data.pointer("/issue/state/name")
+            .and_then(|name| name.as_str())
+            .map(|name| name.to_string())
+            .ok_or(anyhow::anyhow!(
+                "Linear issue {} has no status",
+                external_id
+            ))
+    }
+}
@@ -0,0 +1,97 @@
+//! Issue tracker integrations. Issues are created pre-filled from a trace or an evaluation
+//! result, with a link back to it, and their status is shown on the resource.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    db::issues::{IssueTrackerIntegration, IssueTrackerProvider, LinkedIssueResourceType},
+    provider_api_keys,
+};
+
+pub mod jira;
+pub mod linear;
+
+pub struct NewIssue {
+    pub title: String,
+    pub description: String,
+}
+
+pub struct CreatedIssue {
+    /// Id used to query the issue later
+    pub external_id: String,
+    /// Human readable key, e.g. "LAM-123"
+    pub key: String,
+    pub url: String,
+}
+
+#[async_trait]
+pub trait IssueTracker: Sync + Send {
+    async fn create_issue(&self, issue: &NewIssue) -> Result<CreatedIssue>;
+
+    /// Name of the current status of the issue, e.g. "In Progress"
+    async fn get_issue_status(&self, external_id: &str) -> Result<String>;
+}
+
+/// Tokens are encrypted with the project and provider as associated data, so that they can't be
+/// moved to another integration
+fn token_name(project_id: &Uuid, provider: IssueTrackerProvider) -> String {
+    format!("issue_tracker:{}:{:?}", project_id, provider)
+}
+
+pub fn encode_token(
+    project_id: &Uuid,
+    provider: IssueTrackerProvider,
+    token: &String,
+) -> provider_api_keys::ValueAndNonceHex {
+    provider_api_keys::encode_api_key(&token_name(project_id, provider), token)
+}
+
+/// Validates the config and builds the tracker for it
+pub fn tracker(
+    provider: IssueTrackerProvider,
+    config: &Value,
+    token: String,
+) -> Result<Arc<dyn IssueTracker>> {
+    let tracker: Arc<dyn IssueTracker> = match provider {
+        IssueTrackerProvider::JIRA => Arc::new(jira::Jira::new(config, token)?),
+        IssueTrackerProvider::LINEAR => Arc::new(linear::Linear::new(config, token)?),
+    };
+    Ok(tracker)
+}
+
+pub fn tracker_from_integration(
+    project_id: &Uuid,
+    integration: &IssueTrackerIntegration,
+) -> Result<Arc<dyn IssueTracker>> {
+    let token = provider_api_keys::decode_api_key(
+        &token_name(project_id, integration.provider),
+        &integration.token_nonce,
+        &integration.token_value,
+    )?;
+    tracker(integration.provider, &integration.config, token)
+}
+
+/// Link to the resource in the Laminar UI, put in the issue description
+pub fn backlink(
+    project_id: &Uuid,
+    resource_type: LinkedIssueResourceType,
+    resource_id: &Uuid,
+    evaluation_id: Option<&Uuid>,
+) -> Option<String> {
+    let frontend_url = std::env::var("FRONTEND_URL").ok()?;
+    let frontend_url = frontend_url.trim_end_matches('/');
+    match resource_type {
+        LinkedIssueResourceType::TRACE => Some(format!(
+            "{frontend_url}/project/{project_id}/traces?traceId={resource_id}"
+        )),
+        LinkedIssueResourceType::EVALUATION_RESULT => Some(format!(
+            "{frontend_url}/project/{project_id}/evaluations/{}?datapointId={resource_id}",
+            evaluation_id?
+        )),
+    }
+}
@@ -0,0 +1,511 @@
+//! Hourly export of a project's spans, scores and events to the user's S3 bucket as Parquet files,
+//! so that they can be queried from a lakehouse, e.g. with DuckDB or Athena.
+//!
+//! Each table is partitioned by hour, Hive style:
+//! `{prefix}/{table}/date=2024-12-25/hour=13/part-00000.parquet`. An hour is exported once it
+//! ended more than `EXPORT_DELAY_SECONDS` ago, so that late spans are included, and only once:
+//! data that arrives later isn't exported. Scores are labels partitioned by their last update, so
+//! a label updated later is exported again, and the latest `updatedAt` of a label id wins.
+//!
+//! After all files of an hour are written, the hour's manifest lists them, and `manifest.json`
+//! at the prefix is updated with the end of the last complete hour. Readers should only read
+//! partitions before it.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow_array::{
+    builder::{Float64Builder, StringBuilder, TimestampMicrosecondBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_s3::{config::Credentials, primitives::ByteStream, Client};
+use chrono::{DateTime, Duration, Utc};
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self, events::EventWithTemplateName, field_visibility::FieldVisibility,
+        labels::ExportedLabel, lake_exports::LakeExport, DB,
+    },
+    evaluations::export::{to_json_string, ColumnarWriter, ExportFormat},
+    provider_api_keys::{self, ValueAndNonceHex},
+    traces,
+};
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Time after the end of an hour until it's exported, for spans that are still being ingested
+const EXPORT_DELAY_SECONDS: i64 = 15 * 60;
+/// Time after which an export that didn't finish is claimed again, e.g. after a restart
+const EXPORT_LEASE_SECONDS: i64 = 30 * 60;
+/// Hours exported per run, so that a long backlog doesn't hold the worker
+const MAX_HOURS_PER_RUN: i64 = 6;
+/// Rows per Parquet file, each file is built in memory before it's uploaded
+const MAX_ROWS_PER_FILE: usize = 100_000;
+const SPANS_BATCH_SIZE: usize = 1000;
+const MANIFEST_VERSION: u32 = 1;
+
+const SPANS_TABLE: &str = "spans";
+const SCORES_TABLE: &str = "scores";
+const EVENTS_TABLE: &str = "events";
+
+/// The secret is encrypted with the project as associated data, so that it can't be moved to
+/// another project's export
+fn secret_name(project_id: &Uuid) -> String {
+    format!("lake_export:{}", project_id)
+}
+
+pub fn encode_secret(project_id: &Uuid, secret_access_key: &String) -> ValueAndNonceHex {
+    provider_api_keys::encode_api_key(&secret_name(project_id), secret_access_key)
+}
+
+pub fn s3_client(region: &str, access_key_id: &str, secret_access_key: &str) -> Client {
+    let credentials = Credentials::new(access_key_id, secret_access_key, None, None, "lake_export");
+    let config = aws_sdk_s3::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new(region.to_string()))
+        .credentials_provider(credentials)
+        .build();
+    Client::from_conf(config)
+}
+
+/// Checks that the bucket exists and is accessible with the credentials
+pub async fn check_bucket_access(client: &Client, bucket: &str) -> Result<()> {
+    client.head_bucket().bucket(bucket).send().await?;
+    Ok(())
+}
+
+/// Prefix without slashes at the ends, so that keys can be joined with `/`
+pub fn normalize_prefix(prefix: &str) -> String {
+    prefix.trim_matches('/').to_string()
+}
+
+fn object_key(prefix: &str, path: &str) -> String {
+    if prefix.is_empty() {
+        path.to_string()
+    } else {
+        format!("{prefix}/{path}")
+    }
+}
+
+fn hour_partition(hour: DateTime<Utc>) -> String {
+    format!(
+        "date={}/hour={}",
+        hour.format("%Y-%m-%d"),
+        hour.format("%H")
+    )
+}
+
+fn enum_name<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+}
+
+fn scores_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("spanId", DataType::Utf8, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("value", DataType::Float64, false),
+        Field::new("source", DataType::Utf8, false),
+        Field::new("userId", DataType::Utf8, true),
+        Field::new("reasoning", DataType::Utf8, true),
+        Field::new("updatedAt", timestamp_type(), false),
+    ]))
+}
+
+fn scores_record_batch(labels: &[ExportedLabel], schema: SchemaRef) -> Result<RecordBatch> {
+    let mut ids = StringBuilder::new();
+    let mut span_ids = StringBuilder::new();
+    let mut names = StringBuilder::new();
+    let mut values = Float64Builder::new();
+    let mut sources = StringBuilder::new();
+    let mut user_ids = StringBuilder::new();
+    let mut reasonings = StringBuilder::new();
+    let mut updated_at = TimestampMicrosecondBuilder::new().with_timezone("UTC");
+
+    for label in labels {
+        ids.append_value(label.id.to_string());
+        span_ids.append_value(label.span_id.to_string());
+        names.append_value(&label.class_name);
+        values.append_value(label.value);
+        sources.append_value(enum_name(&label.label_source));
+        user_ids.append_option(label.user_id.map(|id| id.to_string()));
+        reasonings.append_option(label.reasoning.as_ref());
+        updated_at.append_value(label.updated_at.timestamp_micros());
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(ids.finish()),
+        Arc::new(span_ids.finish()),
+        Arc::new(names.finish()),
+        Arc::new(values.finish()),
+        Arc::new(sources.finish()),
+        Arc::new(user_ids.finish()),
+        Arc::new(reasonings.finish()),
+        Arc::new(updated_at.finish()),
+    ];
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn events_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("spanId", DataType::Utf8, false),
+        Field::new("timestamp", timestamp_type(), false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("eventType", DataType::Utf8, false),
+        Field::new("source", DataType::Utf8, false),
+        Field::new("value", DataType::Utf8, true),
+        Field::new("metadata", DataType::Utf8, true),
+    ]))
+}
+
+fn events_record_batch(events: &[EventWithTemplateName], schema: SchemaRef) -> Result<RecordBatch> {
+    let mut ids = StringBuilder::new();
+    let mut span_ids = StringBuilder::new();
+    let mut timestamps = TimestampMicrosecondBuilder::new().with_timezone("UTC");
+    let mut names = StringBuilder::new();
+    let mut event_types = StringBuilder::new();
+    let mut sources = StringBuilder::new();
+    let mut values = StringBuilder::new();
+    let mut metadata = StringBuilder::new();
+
+    for event in events {
+        ids.append_value(event.id.to_string());
+        span_ids.append_value(event.span_id.to_string());
+        timestamps.append_value(event.timestamp.timestamp_micros());
+        names.append_value(&event.template_name);
+        event_types.append_value(enum_name(&event.template_event_type));
+        sources.append_value(enum_name(&event.source));
+        values.append_option(event.value.as_ref().map(to_json_string));
+        metadata.append_option(event.metadata.as_ref().map(Value::to_string));
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(ids.finish()),
+        Arc::new(span_ids.finish()),
+        Arc::new(timestamps.finish()),
+        Arc::new(names.finish()),
+        Arc::new(event_types.finish()),
+        Arc::new(sources.finish()),
+        Arc::new(values.finish()),
+        Arc::new(metadata.finish()),
+    ];
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedFile {
+    table: &'static str,
+    key: String,
+    rows: usize,
+}
+
+/// Parquet files of one table and hour, uploaded as soon as they reach `MAX_ROWS_PER_FILE`
+struct PartitionWriter<'a> {
+    client: &'a Client,
+    bucket: &'a str,
+    prefix: &'a str,
+    table: &'static str,
+    hour: DateTime<Utc>,
+    schema: SchemaRef,
+    writer: Option<ColumnarWriter>,
+    bytes: Vec<u8>,
+    rows: usize,
+    files: Vec<ExportedFile>,
+}
+
+impl<'a> PartitionWriter<'a> {
+    fn new(
+        client: &'a Client,
+        export: &'a LakeExport,
+        table: &'static str,
+        hour: DateTime<Utc>,
+        schema: SchemaRef,
+    ) -> Self {
+        Self {
+            client,
+            bucket: &export.bucket,
+            prefix: &export.prefix,
+            table,
+            hour,
+            schema,
+            writer: None,
+            bytes: Vec::new(),
+            rows: 0,
+            files: Vec::new(),
+        }
+    }
+
+    async fn write(&mut self, batch: RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        if self.writer.is_none() {
+            self.writer = ColumnarWriter::new(ExportFormat::Parquet, self.schema.clone())?;
+        }
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        self.bytes.extend(writer.write(&batch)?);
+        self.rows += batch.num_rows();
+        if self.rows >= MAX_ROWS_PER_FILE {
+            self.upload_file().await?;
+        }
+        Ok(())
+    }
+
+    async fn upload_file(&mut self) -> Result<()> {
+        let Some(writer) = self.writer.take() else {
+            return Ok(());
+        };
+        let mut bytes = std::mem::take(&mut self.bytes);
+        bytes.extend(writer.finish()?);
+        let key = object_key(
+            self.prefix,
+            &format!(
+                "{}/{}/part-{:05}.parquet",
+                self.table,
+                hour_partition(self.hour),
+                self.files.len()
+            ),
+        );
+        put_object(
+            self.client,
+            self.bucket,
+            &key,
+            bytes,
+            "application/vnd.apache.parquet",
+        )
+        .await?;
+        self.files.push(ExportedFile {
+            table: self.table,
+            key,
+            rows: std::mem::take(&mut self.rows),
+        });
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<Vec<ExportedFile>> {
+        self.upload_file().await?;
+        Ok(self.files)
+    }
+}
+
+async fn put_object(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    bytes: Vec<u8>,
+    content_type: &str,
+) -> Result<()> {
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .content_type(content_type)
+        .body(ByteStream::from(bytes))
+        .send()
+        .await?;
+    Ok(())
+}
+
+/// Writes the files of the hour starting at `hour`, then its manifest and the prefix's manifest
+async fn export_hour(
+    db: &DB,
+    client: &Client,
+    export: &LakeExport,
+    hour: DateTime<Utc>,
+) -> Result<()> {
+    let end = hour + Duration::hours(1);
+    let project_id = export.project_id;
+    let mut files = Vec::new();
+
+    let spans_schema = traces::export::columnar_schema();
+    let mut spans = PartitionWriter::new(client, export, SPANS_TABLE, hour, spans_schema.clone());
+    let mut batches =
+        db::spans::stream_spans(&db.pool, project_id, hour, end, FieldVisibility::ALL)
+            .chunks(SPANS_BATCH_SIZE);
+    while let Some(batch) = batches.next().await {
+        let batch = batch.into_iter().collect::<Result<Vec<_>, _>>()?;
+        spans
+            .write(traces::export::record_batch(&batch, spans_schema.clone())?)
+            .await?;
+    }
+    files.extend(spans.finish().await?);
+
+    let labels = db::labels::get_labels_updated_in_period(&db.pool, project_id, hour, end).await?;
+    let mut scores = PartitionWriter::new(client, export, SCORES_TABLE, hour, scores_schema());
+    for chunk in labels.chunks(MAX_ROWS_PER_FILE) {
+        scores
+            .write(scores_record_batch(chunk, scores_schema())?)
+            .await?;
+    }
+    files.extend(scores.finish().await?);
+
+    let events_in_hour = db::events::get_events_in_period(&db.pool, project_id, hour, end).await?;
+    let mut events = PartitionWriter::new(client, export, EVENTS_TABLE, hour, events_schema());
+    for chunk in events_in_hour.chunks(MAX_ROWS_PER_FILE) {
+        events
+            .write(events_record_batch(chunk, events_schema())?)
+            .await?;
+    }
+    files.extend(events.finish().await?);
+
+    let hour_manifest = json!({
+        "version": MANIFEST_VERSION,
+        "projectId": project_id,
+        "start": hour,
+        "end": end,
+        "files": files,
+    });
+    put_object(
+        client,
+        &export.bucket,
+        &object_key(
+            &export.prefix,
+            &format!("manifests/{}.json", hour_partition(hour)),
+        ),
+        serde_json::to_vec(&hour_manifest)?,
+        "application/json",
+    )
+    .await?;
+
+    let manifest = json!({
+        "version": MANIFEST_VERSION,
+        "projectId": project_id,
+        "exportedUntil": end,
+        "format": "parquet",
+        "partitioning": "{table}/date={YYYY-MM-DD}/hour={HH}",
+        "tables": {
+            SPANS_TABLE: schema_columns(&spans_schema),
+            SCORES_TABLE: schema_columns(&scores_schema()),
+            EVENTS_TABLE: schema_columns(&events_schema()),
+        },
+    });
+    put_object(
+        client,
+        &export.bucket,
+        &object_key(&export.prefix, "manifest.json"),
+        serde_json::to_vec(&manifest)?,
+        "application/json",
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn schema_columns(schema: &SchemaRef) -> Value {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            json!({
+                "name": field.name(),
+                "type": field.data_type().to_string(),
+                "nullable": field.is_nullable(),
+            })
+        })
+        .collect()
+}
+
+/// Exports the hours that are due, oldest first, recording the progress after each hour
+async fn run_lake_export(db: &DB, export: &LakeExport) -> Result<()> {
+    let secret_access_key = provider_api_keys::decode_api_key(
+        &secret_name(&export.project_id),
+        &export.secret_nonce,
+        &export.secret_value,
+    )?;
+    let client = s3_client(&export.region, &export.access_key_id, &secret_access_key);
+    let due_until = Utc::now() - Duration::seconds(EXPORT_DELAY_SECONDS);
+
+    let mut hour = export.exported_until;
+    for _ in 0..MAX_HOURS_PER_RUN {
+        if hour + Duration::hours(1) > due_until {
+            break;
+        }
+        export_hour(db, &client, export, hour).await?;
+        hour = hour + Duration::hours(1);
+        db::lake_exports::update_exported_until(&db.pool, &export.project_id, hour).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn run_lake_exports_periodically(db: Arc<DB>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        let exports = match db::lake_exports::claim_due_lake_exports(
+            &db.pool,
+            EXPORT_DELAY_SECONDS,
+            EXPORT_LEASE_SECONDS,
+        )
+        .await
+        {
+            Ok(exports) => exports,
+            Err(e) => {
+                log::error!("Failed to claim due lake exports: {:?}", e);
+                continue;
+            }
+        };
+
+        for export in exports {
+            if let Err(e) = run_lake_export(&db, &export).await {
+                log::error!(
+                    "Failed to run lake export. project_id [{}]: {:?}",
+                    export.project_id,
+                    e
+                );
+                if let Err(e) = db::lake_exports::record_lake_export_error(
+                    &db.pool,
+                    &export.project_id,
+                    &e.to_string(),
+                )
+                .await
+                {
+                    log::error!(
+                        "Failed to record lake export error. project_id [{}]: {:?}",
+                        export.project_id,
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_object_keys() {
+        let hour = Utc.with_ymd_and_hms(2024, 12, 25, 9, 0, 0).unwrap();
+        assert_eq!(hour_partition(hour), "date=2024-12-25/hour=09");
+        assert_eq!(normalize_prefix("/laminar/exports/"), "laminar/exports");
+        assert_eq!(object_key("", "manifest.json"), "manifest.json");
+        assert_eq!(
+            object_key(
+                "laminar",
+                "spans/date=2024-12-25/hour=09/part-00000.parquet"
+            ),
+            "laminar/spans/date=2024-12-25/hour=09/part-00000.parquet"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::language_model::LanguageModelProviderName;
 
@@ -19,6 +19,16 @@ pub fn get_required_env_vars_for_model(model: &str) -> HashSet<String> {
     }
 }
 
+/// Env variables, e.g. provider api keys, that the model needs but are not in `env`, sorted
+pub fn missing_env_vars_for_model(model: &str, env: &HashMap<String, String>) -> Vec<String> {
+    let mut missing = get_required_env_vars_for_model(model)
+        .into_iter()
+        .filter(|name| !env.contains_key(name))
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing
+}
+
 pub fn calculate_cost(tokens: u32, price_per_million_tokens: f64) -> f64 {
     tokens as f64 * price_per_million_tokens / 1_000_000.0
 }
@@ -0,0 +1,188 @@
+//! Structured request logging and request id propagation.
+//!
+//! Every HTTP request gets an id, taken from a valid `X-Request-Id` header or generated. The id is
+//! kept in a task-local, so that every log line written while handling the request, including by
+//! database and ClickHouse calls, can be correlated. Tasks spawned with [`spawn`] inherit the id.
+//! With `LOG_FORMAT=json`, all log lines are written as JSON objects.
+
+use std::{future::Future, io::Write, time::Instant};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error, HttpMessage,
+};
+use serde_json::{json, Map, Value};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::{
+    db::{project_api_keys::ProjectApiKey, user::User},
+    traces::self_tracing::record_request_span,
+};
+
+const REQUEST_LOG_TARGET: &str = "lmnr::request";
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: Uuid;
+}
+
+pub fn current_request_id() -> Option<Uuid> {
+    REQUEST_ID.try_with(|id| *id).ok()
+}
+
+/// Same as `tokio::spawn`, but the spawned task keeps the request id of the current task
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match current_request_id() {
+        Some(request_id) => tokio::spawn(REQUEST_ID.scope(request_id, future)),
+        None => tokio::spawn(future),
+    }
+}
+
+fn is_json_format() -> bool {
+    std::env::var("LOG_FORMAT").is_ok_and(|format| format == "json")
+}
+
+pub fn init_logger() {
+    let json_format = is_json_format();
+    env_logger::Builder::from_default_env()
+        .format(move |buf, record| {
+            let request_id = current_request_id();
+            if !json_format {
+                let request_id = request_id
+                    .map(|id| format!(" request_id={id}"))
+                    .unwrap_or_default();
+                return writeln!(
+                    buf,
+                    "[{} {} {}{}] {}",
+                    buf.timestamp_millis(),
+                    record.level(),
+                    record.target(),
+                    request_id,
+                    record.args()
+                );
+            }
+
+            let mut line = Map::new();
+            line.insert("timestamp".to_string(), json!(chrono::Utc::now()));
+            line.insert("level".to_string(), json!(record.level().as_str()));
+            line.insert("target".to_string(), json!(record.target()));
+            if let Some(request_id) = request_id {
+                line.insert("requestId".to_string(), json!(request_id));
+            }
+            let message = record.args().to_string();
+            // Request logs are already structured, keep their fields at the top level
+            match serde_json::from_str::<Map<String, Value>>(&message) {
+                Ok(fields) if record.target() == REQUEST_LOG_TARGET => line.extend(fields),
+                _ => {
+                    line.insert("message".to_string(), json!(message));
+                }
+            }
+            writeln!(buf, "{}", Value::Object(line))
+        })
+        .init();
+}
+
+fn request_id_from_header(req: &ServiceRequest) -> Option<Uuid> {
+    let header = req.headers().get(REQUEST_ID_HEADER)?;
+    Uuid::parse_str(header.to_str().ok()?).ok()
+}
+
+/// Logs method, route, status, latency and project of every request
+pub async fn log_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = request_id_from_header(&req).unwrap_or_else(Uuid::new_v4);
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let start = Instant::now();
+    let start_time = chrono::Utc::now();
+
+    let res = REQUEST_ID.scope(request_id, next.call(req)).await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    let mut res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            REQUEST_ID.sync_scope(request_id, || {
+                log::error!(
+                    target: REQUEST_LOG_TARGET,
+                    "{}",
+                    json!({
+                        "method": method,
+                        "path": path,
+                        "latencyMs": latency_ms,
+                        "error": e.to_string(),
+                    })
+                );
+            });
+            return Err(e);
+        }
+    };
+
+    let request = res.request();
+    let project_id = request
+        .match_info()
+        .get("project_id")
+        .map(String::from)
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<ProjectApiKey>()
+                .map(|api_key| api_key.project_id.to_string())
+        });
+    let user_id = request.extensions().get::<User>().map(|user| user.id);
+    let fields = json!({
+        "method": method,
+        "route": request.match_pattern(),
+        "path": path,
+        "status": res.status().as_u16(),
+        "latencyMs": latency_ms,
+        "projectId": project_id,
+        "userId": user_id,
+    });
+
+    record_request_span(
+        request_id,
+        format!("{} {}", method, request.match_pattern().unwrap_or(path.clone())),
+        start_time,
+        chrono::Utc::now(),
+        json!({
+            "http.method": method,
+            "http.route": request.match_pattern(),
+            "http.status_code": res.status().as_u16(),
+            "lmnr.project_id": project_id,
+        }),
+    );
+
+    REQUEST_ID.sync_scope(request_id, || {
+        if is_json_format() {
+            log::info!(target: REQUEST_LOG_TARGET, "{}", fields);
+        } else {
+            log::info!(
+                target: REQUEST_LOG_TARGET,
+                "{} {} {} {}ms project_id={}",
+                method,
+                path,
+                res.status().as_u16(),
+                latency_ms,
+                project_id.as_deref().unwrap_or("-")
+            );
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+        res.headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+
+    Ok(res)
+}
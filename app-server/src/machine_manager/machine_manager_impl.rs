@@ -0,0 +1,83 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use super::{MachineManager, StartedMachine};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StartMachineResponse {
+    machine_id: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct HealthResponse {
+    healthy: bool,
+}
+
+pub struct MachineManagerImpl {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl MachineManagerImpl {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl MachineManager for MachineManagerImpl {
+    async fn start_machine(&self) -> Result<StartedMachine> {
+        let response: StartMachineResponse = self
+            .client
+            .post(format!("{}/machines", self.url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(StartedMachine {
+            provider_machine_id: response.machine_id,
+            url: response.url,
+        })
+    }
+
+    async fn terminate_machine(&self, provider_machine_id: &str) -> Result<()> {
+        let response = self
+            .client
+            .delete(format!("{}/machines/{}", self.url, provider_machine_id))
+            .send()
+            .await?;
+        // Already gone
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        response.error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn is_healthy(&self, provider_machine_id: &str) -> Result<bool> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/machines/{}/health",
+                self.url, provider_machine_id
+            ))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        let health: HealthResponse = response.error_for_status()?.json().await?;
+
+        Ok(health.healthy)
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::{MachineManager, StartedMachine};
+
+pub struct MockMachineManager {}
+
+#[async_trait]
+impl MachineManager for MockMachineManager {
+    async fn start_machine(&self) -> Result<StartedMachine> {
+        let provider_machine_id = Uuid::new_v4().to_string();
+        Ok(StartedMachine {
+            url: format!(
+                "ws://localhost:9222/devtools/browser/{}",
+                provider_machine_id
+            ),
+            provider_machine_id,
+        })
+    }
+
+    async fn terminate_machine(&self, _provider_machine_id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn is_healthy(&self, _provider_machine_id: &str) -> Result<bool> {
+        Ok(true)
+    }
+}
@@ -0,0 +1,50 @@
+//! Browser machines for agents, started on demand by the machine manager service.
+//!
+//! Machines are requested through the API and queued in `machines`. The worker started by
+//! `pool::run_machine_pool_periodically` starts them when the pool has capacity and the project is
+//! under its limit, so that requests wait in the queue instead of failing when the pool is full.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::db::{
+    machines::{self, Machine},
+    DB,
+};
+
+pub mod machine_manager_impl;
+pub mod mock;
+pub mod pool;
+
+pub struct StartedMachine {
+    pub provider_machine_id: String,
+    /// DevTools endpoint of the machine's browser
+    pub url: String,
+}
+
+#[async_trait]
+pub trait MachineManager: Sync + Send {
+    async fn start_machine(&self) -> Result<StartedMachine>;
+    async fn terminate_machine(&self, provider_machine_id: &str) -> Result<()>;
+    async fn is_healthy(&self, provider_machine_id: &str) -> Result<bool>;
+}
+
+/// Terminates the machine, whatever its status. Returns None if the machine doesn't exist, and the
+/// machine unchanged if it has already ended.
+pub async fn terminate_machine(
+    db: &DB,
+    machine_manager: &dyn MachineManager,
+    project_id: &Uuid,
+    id: &Uuid,
+) -> Result<Option<Machine>> {
+    let Some(machine) = machines::terminate_machine(&db.pool, project_id, id).await? else {
+        return machines::get_machine(&db.pool, project_id, id).await;
+    };
+    // Machines still starting are terminated by the pool once they have started
+    if let Some(provider_machine_id) = &machine.provider_machine_id {
+        pool::terminate(machine_manager, provider_machine_id).await;
+    }
+
+    Ok(Some(machine))
+}
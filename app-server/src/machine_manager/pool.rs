@@ -0,0 +1,118 @@
+//! Worker starting the queued machines and stopping the unhealthy ones. Queued machines are
+//! claimed under an advisory lock, so any number of app-server instances can run the worker.
+
+use std::{sync::Arc, time::Duration};
+
+use crate::db::{machines, DB};
+
+use super::MachineManager;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_POOL_CAPACITY: i64 = 16;
+/// Queued machines fail after waiting this long, so that clients don't wait forever
+const MAX_QUEUE_WAIT_SECONDS: i64 = 600;
+const HEALTH_CHECK_INTERVAL_SECONDS: i64 = 30;
+
+/// Machines that can be starting or running at once, across all projects
+pub fn pool_capacity() -> i64 {
+    std::env::var("MACHINE_POOL_CAPACITY")
+        .ok()
+        .and_then(|capacity| capacity.parse().ok())
+        .unwrap_or(DEFAULT_POOL_CAPACITY)
+}
+
+pub async fn run_machine_pool_periodically(db: Arc<DB>, machine_manager: Arc<dyn MachineManager>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        match machines::expire_queued_machines(&db.pool, MAX_QUEUE_WAIT_SECONDS).await {
+            Ok(0) => {}
+            Ok(count) => log::warn!("{} queued machines waited too long and failed", count),
+            Err(e) => log::error!("Failed to expire queued machines: {:?}", e),
+        }
+
+        check_health(db.clone(), machine_manager.clone()).await;
+
+        let queued = match machines::claim_queued_machines(&db.pool, pool_capacity()).await {
+            Ok(queued) => queued,
+            Err(e) => {
+                log::error!("Failed to claim queued machines: {:?}", e);
+                continue;
+            }
+        };
+        for machine in queued {
+            let db = db.clone();
+            let machine_manager = machine_manager.clone();
+            tokio::spawn(async move {
+                start_machine(db, machine_manager, machine.id).await;
+            });
+        }
+    }
+}
+
+async fn start_machine(db: Arc<DB>, machine_manager: Arc<dyn MachineManager>, id: uuid::Uuid) {
+    let started = match machine_manager.start_machine().await {
+        Ok(started) => started,
+        Err(e) => {
+            log::error!("Failed to start machine. id [{}]: {:?}", id, e);
+            if let Err(e) = machines::set_machine_failed(&db.pool, &id, &e.to_string()).await {
+                log::error!("Failed to record machine failure. id [{}]: {:?}", id, e);
+            }
+            return;
+        }
+    };
+
+    match machines::set_machine_running(&db.pool, &id, &started.provider_machine_id, &started.url)
+        .await
+    {
+        Ok(true) => {}
+        // Terminated while starting
+        Ok(false) => terminate(machine_manager.as_ref(), &started.provider_machine_id).await,
+        Err(e) => {
+            log::error!("Failed to record started machine. id [{}]: {:?}", id, e);
+            terminate(machine_manager.as_ref(), &started.provider_machine_id).await;
+        }
+    }
+}
+
+async fn check_health(db: Arc<DB>, machine_manager: Arc<dyn MachineManager>) {
+    let running = match machines::claim_health_checks(&db.pool, HEALTH_CHECK_INTERVAL_SECONDS).await
+    {
+        Ok(running) => running,
+        Err(e) => {
+            log::error!("Failed to claim machine health checks: {:?}", e);
+            return;
+        }
+    };
+
+    for machine in running {
+        let Some(provider_machine_id) = machine.provider_machine_id else {
+            continue;
+        };
+        let error = match machine_manager.is_healthy(&provider_machine_id).await {
+            Ok(true) => continue,
+            Ok(false) => "Machine became unhealthy".to_string(),
+            Err(e) => format!("Machine health check failed: {}", e),
+        };
+        log::warn!("Stopping machine. id [{}]: {}", machine.id, error);
+        if let Err(e) = machines::set_machine_failed(&db.pool, &machine.id, &error).await {
+            log::error!(
+                "Failed to record machine failure. id [{}]: {:?}",
+                machine.id,
+                e
+            );
+        }
+        terminate(machine_manager.as_ref(), &provider_machine_id).await;
+    }
+}
+
+pub async fn terminate(machine_manager: &dyn MachineManager, provider_machine_id: &str) {
+    if let Err(e) = machine_manager.terminate_machine(provider_machine_id).await {
+        log::error!(
+            "Failed to terminate machine. provider_machine_id [{}]: {:?}",
+            provider_machine_id,
+            e
+        );
+    }
+}
@@ -1,27 +1,37 @@
 use actix_service::Service;
 use actix_web::{
-    middleware::{Logger, NormalizePath},
+    middleware::{from_fn, NormalizePath},
     web::{self, PayloadConfig},
     App, HttpMessage, HttpServer,
 };
 use actix_web_httpauth::middleware::HttpAuthentication;
+use analytics::{clickhouse::ClickHouseStore, AnalyticsStore};
 use aws_config::BehaviorVersion;
 use code_executor::{code_executor_grpc::code_executor_client::CodeExecutorClient, CodeExecutor};
 use dashmap::DashMap;
+use evaluations::progress::EvaluationProgressHub;
 use db::{pipelines::PipelineVersion, project_api_keys::ProjectApiKey, user::User};
 use features::{is_feature_enabled, Feature};
+use machine_manager::MachineManager;
 use names::NameGenerator;
+use network::ip_allowlist::WorkspaceIpAllowlist;
 use opentelemetry::opentelemetry::proto::collector::trace::v1::trace_service_server::TraceServiceServer;
 use projects::Project;
 use runtime::{create_general_purpose_runtime, wait_stop_signal};
 use storage::{mock::MockStorage, Storage};
 use tonic::transport::Server;
 use traces::{
-    consumer::process_queue_spans, grpc_service::ProcessTracesService,
-    limits::WorkspaceLimitsExceeded, OBSERVATIONS_EXCHANGE, OBSERVATIONS_QUEUE,
+    archive::PayloadArchive, client_metadata::ClientRegionSettings, consumer::process_queue_spans,
+    grpc_service::ProcessTracesService, limits::WorkspaceLimitsExceeded,
+    online_evaluations::ProjectOnlineEvaluationRules,
+    promoted_attributes::ProjectPromotedAttributes, sampling::SamplingSettings,
+    OBSERVATIONS_DEAD_LETTER_QUEUE, OBSERVATIONS_EXCHANGE, OBSERVATIONS_QUEUE,
 };
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 use cache::{cache::CacheTrait, Cache};
+use ch::score_writer::ScoreWriter;
 use chunk::{
     character_split::CharacterSplitChunker,
     runner::{Chunker, ChunkerRunner, ChunkerType},
@@ -49,31 +59,100 @@ use std::{
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+mod alerts;
+mod analytics;
 mod api;
 mod auth;
+mod backups;
 mod cache;
 mod ch;
+mod chaos;
 mod chunk;
 mod code_executor;
 mod datasets;
 mod db;
+#[cfg(feature = "embedded")]
+mod embedded;
 mod engine;
 mod evaluations;
 mod features;
+mod ids;
+mod issues;
+mod lake_export;
 mod language_model;
+mod logging;
+mod machine_manager;
+mod mcp;
+mod metrics;
 mod names;
+mod network;
 mod opentelemetry;
 mod pipeline;
 mod projects;
 mod provider_api_keys;
 mod routes;
 mod runtime;
+mod secrets;
 mod semantic_search;
 mod storage;
+#[cfg(feature = "test-support")]
+mod test_support;
+mod tls;
 mod traces;
+mod warehouse_sync;
 
 const DEFAULT_CACHE_SIZE: u64 = 100; // entries
 
+fn create_cache() -> Cache {
+    let mut caches: HashMap<TypeId, Arc<dyn CacheTrait>> = HashMap::new();
+    let auth_cache: Arc<MokaCache<String, User>> = Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(TypeId::of::<User>(), auth_cache);
+    let project_api_key_cache: Arc<MokaCache<String, ProjectApiKey>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(TypeId::of::<ProjectApiKey>(), project_api_key_cache);
+    let pipeline_version_cache: Arc<MokaCache<String, PipelineVersion>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(TypeId::of::<PipelineVersion>(), pipeline_version_cache);
+    let project_cache: Arc<MokaCache<String, Project>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(TypeId::of::<Project>(), project_cache);
+    let workspace_limits_cache: Arc<MokaCache<String, WorkspaceLimitsExceeded>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(
+        TypeId::of::<WorkspaceLimitsExceeded>(),
+        workspace_limits_cache,
+    );
+    let llm_costs_cache: Arc<MokaCache<String, LLMPriceEntry>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(TypeId::of::<LLMPriceEntry>(), llm_costs_cache);
+    let ip_allowlist_cache: Arc<MokaCache<String, WorkspaceIpAllowlist>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(TypeId::of::<WorkspaceIpAllowlist>(), ip_allowlist_cache);
+    let client_region_settings_cache: Arc<MokaCache<String, ClientRegionSettings>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(
+        TypeId::of::<ClientRegionSettings>(),
+        client_region_settings_cache,
+    );
+    let promoted_attributes_cache: Arc<MokaCache<String, ProjectPromotedAttributes>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(
+        TypeId::of::<ProjectPromotedAttributes>(),
+        promoted_attributes_cache,
+    );
+    let sampling_settings_cache: Arc<MokaCache<String, SamplingSettings>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(TypeId::of::<SamplingSettings>(), sampling_settings_cache);
+    let online_evaluation_rules_cache: Arc<MokaCache<String, ProjectOnlineEvaluationRules>> =
+        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
+    caches.insert(
+        TypeId::of::<ProjectOnlineEvaluationRules>(),
+        online_evaluation_rules_cache,
+    );
+
+    Cache::new(caches)
+}
+
 fn tonic_error_to_io_error(err: tonic::transport::Error) -> io::Error {
     io::Error::new(io::ErrorKind::Other, err)
 }
@@ -94,7 +173,7 @@ fn main() -> anyhow::Result<()> {
     let mut handles: Vec<JoinHandle<Result<(), Error>>> = vec![];
 
     std::env::set_var("RUST_LOG", "info");
-    env_logger::init();
+    logging::init_logger();
 
     let port = env::var("PORT")
         .unwrap_or(String::from("8000"))
@@ -106,31 +185,56 @@ fn main() -> anyhow::Result<()> {
         .unwrap();
     let grpc_address = format!("0.0.0.0:{}", grpc_port).parse().unwrap();
 
-    let mut caches: HashMap<TypeId, Arc<dyn CacheTrait>> = HashMap::new();
-    let auth_cache: Arc<MokaCache<String, User>> = Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
-    caches.insert(TypeId::of::<User>(), auth_cache);
-    let project_api_key_cache: Arc<MokaCache<String, ProjectApiKey>> =
-        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
-    caches.insert(TypeId::of::<ProjectApiKey>(), project_api_key_cache);
-    let pipeline_version_cache: Arc<MokaCache<String, PipelineVersion>> =
-        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
-    caches.insert(TypeId::of::<PipelineVersion>(), pipeline_version_cache);
-    let project_cache: Arc<MokaCache<String, Project>> =
-        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
-    caches.insert(TypeId::of::<Project>(), project_cache);
-    let workspace_limits_cache: Arc<MokaCache<String, WorkspaceLimitsExceeded>> =
-        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
-    caches.insert(
-        TypeId::of::<WorkspaceLimitsExceeded>(),
-        workspace_limits_cache,
-    );
-    let llm_costs_cache: Arc<MokaCache<String, LLMPriceEntry>> =
-        Arc::new(MokaCache::new(DEFAULT_CACHE_SIZE));
-    caches.insert(TypeId::of::<LLMPriceEntry>(), llm_costs_cache);
+    // Test server for SDK test suites: prints its url and credentials as JSON and runs until killed
+    #[cfg(feature = "test-support")]
+    if env::var("TEST_SERVER").is_ok_and(|v| v == "true") {
+        runtime_handle.block_on(async {
+            let server = test_support::TestServer::start(test_support::TestServerConfig {
+                port,
+                deterministic_ids: env::var("DETERMINISTIC_IDS").is_ok_and(|v| v == "true"),
+            })
+            .await
+            .expect("Failed to start test server");
+            println!("{}", serde_json::to_string(&server.info).unwrap());
+            std::future::pending::<()>().await;
+        });
+        return Ok(());
+    }
+
+    let cache = Arc::new(create_cache());
 
-    let cache = Arc::new(Cache::new(caches));
+    let mut secrets_provider = None;
+    runtime_handle.block_on(async {
+        secrets_provider = secrets::create_provider()
+            .await
+            .expect("Failed to configure secrets provider");
+        if let Some(provider) = &secrets_provider {
+            secrets::init(provider.as_ref())
+                .await
+                .expect("Failed to load secrets");
+        }
+    });
 
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    // Kept until the end of main, Postgres stops when it is dropped
+    #[cfg(feature = "embedded")]
+    let mut embedded_postgres = None;
+    #[cfg(feature = "embedded")]
+    if embedded::is_enabled() {
+        runtime_handle.block_on(async {
+            embedded_postgres = Some(
+                embedded::start_postgres(Some(embedded::data_dir()))
+                    .await
+                    .expect("Failed to start embedded Postgres"),
+            );
+        });
+    }
+    #[cfg(feature = "embedded")]
+    let db_url = match &embedded_postgres {
+        Some((_, url)) => url.clone(),
+        None => secrets::get("DATABASE_URL").expect("DATABASE_URL must be set"),
+    };
+    #[cfg(not(feature = "embedded"))]
+    let db_url = secrets::get("DATABASE_URL").expect("DATABASE_URL must be set");
 
     let mut pool = None;
     runtime_handle.block_on(async {
@@ -142,7 +246,10 @@ fn main() -> anyhow::Result<()> {
                         .parse()
                         .unwrap_or(10),
                 )
-                .connect(&db_url)
+                .connect_with(
+                    tls::postgres_connect_options(&db_url)
+                        .expect("Failed to configure Postgres connection"),
+                )
                 .await
                 .unwrap(),
         );
@@ -151,6 +258,25 @@ fn main() -> anyhow::Result<()> {
 
     let db = Arc::new(db::DB::new(pool));
 
+    #[cfg(feature = "embedded")]
+    if embedded_postgres.is_some() {
+        runtime_handle.block_on(async {
+            embedded::migrate(&db.pool)
+                .await
+                .expect("Failed to apply migrations");
+            embedded::seed(&db.pool)
+                .await
+                .expect("Failed to seed database");
+            embedded::ensure_local_project(&db.pool)
+                .await
+                .expect("Failed to create local project");
+        });
+    }
+
+    if let Some(provider) = secrets_provider {
+        runtime_handle.spawn(secrets::rotate_periodically(provider, db.clone()));
+    }
+
     let mut chunkers = HashMap::new();
     let character_split_chunker = CharacterSplitChunker {};
     chunkers.insert(
@@ -160,20 +286,22 @@ fn main() -> anyhow::Result<()> {
     let chunker_runner = Arc::new(ChunkerRunner::new(chunkers));
 
     let interrupt_senders = Arc::new(DashMap::<Uuid, mpsc::Sender<GraphInterruptMessage>>::new());
+    let evaluation_progress_hub = Arc::new(EvaluationProgressHub::new());
 
     let clickhouse = if is_feature_enabled(Feature::FullBuild) {
         let clickhouse_url = env::var("CLICKHOUSE_URL").expect("CLICKHOUSE_URL must be set");
         let clickhouse_user = env::var("CLICKHOUSE_USER").expect("CLICKHOUSE_USER must be set");
-        let clickhouse_password = env::var("CLICKHOUSE_PASSWORD");
+        let clickhouse_password = secrets::get("CLICKHOUSE_PASSWORD");
         // https://clickhouse.com/docs/en/cloud/bestpractices/asynchronous-inserts -> Create client which will wait for async inserts
         // For now, we're not waiting for inserts to finish, but later need to add queue and batch on client-side
-        let mut client = clickhouse::Client::default()
+        let mut client = tls::clickhouse_client()
+            .expect("Failed to configure ClickHouse client")
             .with_url(clickhouse_url)
             .with_user(clickhouse_user)
             .with_database("default")
             .with_option("async_insert", "1")
             .with_option("wait_for_async_insert", "0");
-        if let Ok(clickhouse_password) = clickhouse_password {
+        if let Some(clickhouse_password) = clickhouse_password {
             client = client.with_password(clickhouse_password);
         } else {
             log::warn!("CLICKHOUSE_PASSWORD not set, using without password");
@@ -184,6 +312,22 @@ fn main() -> anyhow::Result<()> {
         // TODO: wrap this in a dyn trait object
         clickhouse::Client::default()
     };
+    let (score_writer, score_writer_handle) =
+        runtime_handle.block_on(async { ScoreWriter::start(clickhouse.clone()) });
+    #[cfg(feature = "embedded")]
+    let analytics_store: Arc<dyn AnalyticsStore> = if embedded_postgres.is_some() {
+        let store = runtime_handle
+            .block_on(analytics::embedded::EmbeddedAnalyticsStore::open(
+                &embedded::analytics_path(),
+            ))
+            .expect("Failed to open embedded analytics store");
+        Arc::new(store)
+    } else {
+        Arc::new(ClickHouseStore::new(clickhouse, score_writer))
+    };
+    #[cfg(not(feature = "embedded"))]
+    let analytics_store: Arc<dyn AnalyticsStore> =
+        Arc::new(ClickHouseStore::new(clickhouse, score_writer));
 
     let mut rabbitmq_connection = None;
     runtime_handle.block_on(async {
@@ -215,11 +359,24 @@ fn main() -> anyhow::Result<()> {
                 )
                 .await
                 .unwrap();
+
+            channel
+                .queue_declare(
+                    OBSERVATIONS_DEAD_LETTER_QUEUE,
+                    QueueDeclareOptions::default(),
+                    FieldTable::default(),
+                )
+                .await
+                .unwrap();
             rabbitmq_connection = Some(connection);
         }
     });
     let rabbitmq_connection_grpc = rabbitmq_connection.clone();
 
+    runtime_handle.block_on(async {
+        traces::self_tracing::init(rabbitmq_connection.clone(), db.clone());
+    });
+
     let mut aws_sdk_config = None;
     runtime_handle.block_on(async {
         aws_sdk_config = Some(
@@ -243,6 +400,18 @@ fn main() -> anyhow::Result<()> {
         Arc::new(MockStorage {})
     };
 
+    let payload_archive = if is_feature_enabled(Feature::PayloadArchive) {
+        let s3_client = aws_sdk_s3::Client::new(&aws_sdk_config);
+        Some(Arc::new(PayloadArchive::new(
+            s3_client,
+            env::var("S3_INGESTION_ARCHIVE_BUCKET")
+                .expect("S3_INGESTION_ARCHIVE_BUCKET must be set"),
+        )))
+    } else {
+        None
+    };
+    let payload_archive_grpc = payload_archive.clone();
+
     let runtime_handle_for_http = runtime_handle.clone();
     let db_for_http = db.clone();
     let cache_for_http = cache.clone();
@@ -285,7 +454,15 @@ fn main() -> anyhow::Result<()> {
                     Arc::new(code_executor::mock::MockCodeExecutor {})
                 };
 
-                let client = reqwest::Client::new();
+                let machine_manager: Arc<dyn MachineManager> =
+                    match env::var("MACHINE_MANAGER_URL") {
+                        Ok(url) if is_feature_enabled(Feature::FullBuild) => Arc::new(
+                            machine_manager::machine_manager_impl::MachineManagerImpl::new(url),
+                        ),
+                        _ => Arc::new(machine_manager::mock::MockMachineManager {}),
+                    };
+
+                let client = network::egress::http_client();
                 let anthropic = language_model::Anthropic::new(client.clone());
                 let openai = language_model::OpenAI::new(client.clone());
                 let openai_azure = language_model::OpenAIAzure::new(client.clone());
@@ -329,12 +506,64 @@ fn main() -> anyhow::Result<()> {
                     Arc::new(language_model::LanguageModelRunner::new(language_models));
 
                 let name_generator = Arc::new(NameGenerator::new());
+                let openapi = api::openapi::ApiDoc::openapi();
+
+                tokio::spawn(pipeline::triggers::run_triggers_periodically(
+                    Arc::new(pipeline::runner::PipelineRunner::new(
+                        language_model_runner.clone(),
+                        chunker_runner.clone(),
+                        semantic_search.clone(),
+                        rabbitmq_connection.clone(),
+                        code_executor.clone(),
+                        db_for_http.clone(),
+                        cache_for_http.clone(),
+                    )),
+                    db_for_http.clone(),
+                    cache_for_http.clone(),
+                ));
+                tokio::spawn(machine_manager::pool::run_machine_pool_periodically(
+                    db_for_http.clone(),
+                    machine_manager.clone(),
+                ));
+                tokio::spawn(alerts::run_online_alerts_periodically(
+                    db_for_http.clone(),
+                    analytics_store.clone(),
+                ));
+                tokio::spawn(alerts::root_cause::run_root_cause_summaries_periodically(
+                    db_for_http.clone(),
+                    cache_for_http.clone(),
+                    language_model_runner.clone(),
+                    analytics_store.clone(),
+                ));
+                tokio::spawn(lake_export::run_lake_exports_periodically(
+                    db_for_http.clone(),
+                ));
+                tokio::spawn(warehouse_sync::run_warehouse_syncs_periodically(
+                    db_for_http.clone(),
+                ));
+                tokio::spawn(evaluations::schedules::run_evaluation_schedules_periodically(
+                    Arc::new(pipeline::runner::PipelineRunner::new(
+                        language_model_runner.clone(),
+                        chunker_runner.clone(),
+                        semantic_search.clone(),
+                        rabbitmq_connection.clone(),
+                        code_executor.clone(),
+                        db_for_http.clone(),
+                        cache_for_http.clone(),
+                    )),
+                    language_model_runner.clone(),
+                    db_for_http.clone(),
+                    cache_for_http.clone(),
+                    analytics_store.clone(),
+                    evaluation_progress_hub.clone(),
+                ));
 
                 HttpServer::new(move || {
                     let auth = HttpAuthentication::bearer(auth::validator);
                     let project_auth = HttpAuthentication::bearer(auth::project_validator);
                     let shared_secret_auth =
                         HttpAuthentication::bearer(auth::shared_secret_validator);
+                    let scim_auth = HttpAuthentication::bearer(auth::scim_validator);
 
                     let pipeline_runner = Arc::new(pipeline::runner::PipelineRunner::new(
                         language_model_runner.clone(),
@@ -355,7 +584,7 @@ fn main() -> anyhow::Result<()> {
                                 cache_for_http.clone(),
                                 semantic_search.clone(),
                                 rabbitmq_connection.clone(),
-                                clickhouse.clone(),
+                                analytics_store.clone(),
                                 chunker_runner.clone(),
                                 storage.clone(),
                             ));
@@ -363,7 +592,7 @@ fn main() -> anyhow::Result<()> {
                     }
 
                     App::new()
-                        .wrap(Logger::default())
+                        .wrap(from_fn(logging::log_request))
                         .wrap(NormalizePath::trim())
                         .app_data(web::Data::from(cache_for_http.clone()))
                         .app_data(web::Data::from(db_for_http.clone()))
@@ -372,11 +601,14 @@ fn main() -> anyhow::Result<()> {
                         .app_data(web::Data::new(interrupt_senders.clone()))
                         .app_data(web::Data::new(language_model_runner.clone()))
                         .app_data(web::Data::new(rabbitmq_connection.clone()))
-                        .app_data(web::Data::new(clickhouse.clone()))
+                        .app_data(web::Data::new(analytics_store.clone()))
                         .app_data(web::Data::new(name_generator.clone()))
+                        .app_data(web::Data::new(evaluation_progress_hub.clone()))
                         .app_data(web::Data::new(semantic_search.clone()))
                         .app_data(web::Data::new(chunker_runner.clone()))
                         .app_data(web::Data::new(storage.clone()))
+                        .app_data(web::Data::new(payload_archive.clone()))
+                        .app_data(web::Data::new(machine_manager.clone()))
                         // Scopes with specific auth or no auth
                         .service(
                             web::scope("api/v1/auth")
@@ -390,12 +622,33 @@ fn main() -> anyhow::Result<()> {
                         )
                         .service(
                             web::scope("api/v1/manage-subscriptions")
-                                .wrap(shared_secret_auth)
+                                .wrap(shared_secret_auth.clone())
                                 .service(routes::subscriptions::update_subscription),
                         )
+                        .service(
+                            web::scope("api/v1/internal")
+                                .wrap(shared_secret_auth)
+                                .service(routes::internal::get_batch_writer_stats)
+                                .service(routes::internal::get_sdk_adoption)
+                                .service(routes::internal::replay_ingestion_archive)
+                                .service(routes::internal::get_ingestion_shadow_diff)
+                                .service(routes::internal::create_workspace_backup)
+                                .service(
+                                    web::scope("")
+                                        .app_data(PayloadConfig::new(backups::MAX_BACKUP_BYTES))
+                                        .service(routes::internal::restore_workspace_backup),
+                                ),
+                        )
+                        .service(routes::internal::get_prometheus_metrics)
+                        .service(routes::pipelines::receive_pipeline_trigger_webhook)
+                        .service(
+                            SwaggerUi::new("/swagger-ui/{_:.*}")
+                                .url("/api-docs/openapi.json", openapi.clone()),
+                        )
                         .service(
                             web::scope("/v1")
                                 .wrap(project_auth.clone())
+                                .wrap(from_fn(api::deprecation::deprecate_v1))
                                 .service(api::v1::pipelines::run_pipeline_graph)
                                 .service(api::v1::pipelines::ping_healthcheck)
                                 .service(api::v1::traces::get_events_for_session)
@@ -405,6 +658,42 @@ fn main() -> anyhow::Result<()> {
                                 .service(api::v1::metrics::process_metrics)
                                 .app_data(PayloadConfig::new(10 * 1024 * 1024)),
                         )
+                        // Endpoints with unchanged payloads are shared with v1
+                        .service(
+                            web::scope("/v2")
+                                .wrap(project_auth.clone())
+                                .service(api::v1::pipelines::run_pipeline_graph)
+                                .service(api::v1::pipelines::ping_healthcheck)
+                                .service(api::v1::traces::get_events_for_session)
+                                .service(api::v1::traces::process_traces)
+                                .service(api::v2::traces::get_ingestion_status)
+                                .service(api::v1::datasets::get_datapoints)
+                                .service(api::v2::datasets::get_dataset_changes)
+                                .service(api::v2::evaluations::create_evaluation)
+                                .service(api::v2::evaluations::add_evaluation_datapoints)
+                                .service(api::v2::evaluations::stream_evaluation_progress)
+                                .service(api::v2::evaluations::check_regression_gate)
+                                .service(api::v2::evaluations::export_evaluation)
+                                .service(api::v2::traces::export_project_spans)
+                                .service(api::v2::grafana::grafana_health)
+                                .service(api::v2::grafana::grafana_search)
+                                .service(api::v2::grafana::grafana_metrics)
+                                .service(api::v2::grafana::grafana_query)
+                                .service(api::v2::browser_sessions::record_browser_events)
+                                .service(api::v2::browser_sessions::record_browser_snapshots)
+                                .service(api::v1::metrics::process_metrics)
+                                .service(api::v2::machines::create_machine)
+                                .service(api::v2::machines::get_machine)
+                                .service(api::v2::machines::terminate_machine)
+                                .service(api::v2::agent_runs::create_agent_checkpoint)
+                                .service(api::v2::agent_runs::resume_agent_run)
+                                .app_data(PayloadConfig::new(10 * 1024 * 1024)),
+                        )
+                        .service(
+                            web::scope("/mcp")
+                                .wrap(project_auth.clone())
+                                .service(api::mcp::handle_mcp),
+                        )
                         // Scopes with generic auth
                         .service(
                             web::scope("/api/v1/workspaces")
@@ -412,7 +701,65 @@ fn main() -> anyhow::Result<()> {
                                 .service(routes::workspace::get_all_workspaces_of_user)
                                 .service(routes::workspace::get_workspace)
                                 .service(routes::workspace::create_workspace)
-                                .service(routes::workspace::add_user_to_workspace),
+                                .service(routes::workspace::add_user_to_workspace)
+                                .service(routes::workspace::get_workspace_policy)
+                                .service(routes::workspace::get_ip_allowlist)
+                                .service(routes::workspace::update_ip_allowlist)
+                                .service(routes::workspace::get_retention)
+                                .service(routes::workspace::update_retention),
+                        )
+                        .service(
+                            web::scope("/api/v1/organizations")
+                                .wrap(auth.clone())
+                                .service(routes::organizations::get_organizations_of_user)
+                                .service(routes::organizations::create_organization)
+                                .service(routes::organizations::get_organization)
+                                .service(routes::organizations::get_organization_members)
+                                .service(routes::organizations::add_user_to_organization)
+                                .service(routes::organizations::attach_workspace_to_organization)
+                                .service(routes::organizations::update_organization_policy)
+                                .service(routes::organizations::create_scim_token)
+                                .service(routes::organizations::get_scim_groups)
+                                .service(routes::organizations::map_scim_group),
+                        )
+                        .service(
+                            web::scope("/api/v1/personal-access-tokens")
+                                .wrap(auth.clone())
+                                .service(routes::personal_access_tokens::get_personal_access_tokens)
+                                .service(
+                                    routes::personal_access_tokens::create_personal_access_token,
+                                )
+                                .service(
+                                    routes::personal_access_tokens::revoke_personal_access_token,
+                                ),
+                        )
+                        .service(
+                            web::scope("/api/v1/mentions")
+                                .wrap(auth.clone())
+                                .service(routes::comments::get_unread_mentions)
+                                .service(routes::comments::mark_mention_read),
+                        )
+                        .service(
+                            web::scope("/api/v1/sessions")
+                                .wrap(auth.clone())
+                                .service(routes::sessions::create_session)
+                                .service(routes::sessions::refresh_session_token)
+                                .service(routes::sessions::revoke_session),
+                        )
+                        .service(
+                            web::scope("/scim/v2")
+                                .wrap(scim_auth)
+                                .service(routes::scim::list_users)
+                                .service(routes::scim::get_user)
+                                .service(routes::scim::create_user)
+                                .service(routes::scim::replace_user)
+                                .service(routes::scim::patch_user)
+                                .service(routes::scim::delete_user)
+                                .service(routes::scim::list_groups)
+                                .service(routes::scim::get_group)
+                                .service(routes::scim::create_group)
+                                .service(routes::scim::patch_group)
+                                .service(routes::scim::delete_group),
                         )
                         .service(
                             web::scope("/api/v1/limits")
@@ -465,7 +812,83 @@ fn main() -> anyhow::Result<()> {
                                         })
                                         .service(routes::projects::get_project)
                                         .service(routes::projects::delete_project)
+                                        .service(routes::projects::get_client_metadata_settings)
+                                        .service(routes::projects::update_client_metadata_settings)
+                                        .service(routes::projects::get_agent_checkpoint_settings)
+                                        .service(routes::projects::update_agent_checkpoint_settings)
+                                        .service(routes::projects::get_retention)
+                                        .service(routes::projects::update_retention)
+                                        .service(routes::projects::get_sampling_settings)
+                                        .service(routes::projects::update_sampling_settings)
+                                        .service(routes::projects::create_sampling_exemption)
+                                        .service(routes::projects::delete_sampling_exemption)
+                                        .service(routes::promoted_attributes::get_promoted_attributes)
+                                        .service(routes::promoted_attributes::promote_attribute)
+                                        .service(
+                                            routes::promoted_attributes::retry_promoted_attribute_backfill,
+                                        )
+                                        .service(routes::promoted_attributes::delete_promoted_attribute)
+                                        .service(routes::custom_metrics::get_custom_metrics)
+                                        .service(routes::custom_metrics::create_custom_metric)
+                                        .service(routes::custom_metrics::delete_custom_metric)
+                                        .service(
+                                            routes::custom_metrics::get_custom_metric_distribution,
+                                        )
+                                        .service(routes::judge_evaluators::get_judge_evaluators)
+                                        .service(routes::judge_evaluators::create_judge_evaluator)
+                                        .service(routes::judge_evaluators::delete_judge_evaluator)
+                                        .service(
+                                            routes::online_evaluation_rules::get_online_evaluation_rules,
+                                        )
+                                        .service(
+                                            routes::online_evaluation_rules::create_online_evaluation_rule,
+                                        )
+                                        .service(
+                                            routes::online_evaluation_rules::update_online_evaluation_rule,
+                                        )
+                                        .service(
+                                            routes::online_evaluation_rules::delete_online_evaluation_rule,
+                                        )
+                                        .service(
+                                            routes::analytics_definitions::export_analytics_definitions,
+                                        )
+                                        .service(
+                                            routes::analytics_definitions::apply_analytics_definitions,
+                                        )
+                                        .service(routes::evaluation_schedules::get_evaluation_schedules)
+                                        .service(
+                                            routes::evaluation_schedules::create_evaluation_schedule,
+                                        )
+                                        .service(
+                                            routes::evaluation_schedules::update_evaluation_schedule,
+                                        )
+                                        .service(
+                                            routes::evaluation_schedules::delete_evaluation_schedule,
+                                        )
+                                        .service(
+                                            routes::evaluation_schedules::get_evaluation_schedule_runs,
+                                        )
+                                        .service(routes::field_visibility::get_field_visibility_rules)
+                                        .service(routes::field_visibility::set_field_visibility_rule)
+                                        .service(
+                                            routes::field_visibility::delete_field_visibility_rule,
+                                        )
+                                        .service(routes::masking_profiles::get_masking_profiles)
+                                        .service(routes::masking_profiles::set_masking_profile)
+                                        .service(routes::masking_profiles::delete_masking_profile)
+                                        .service(routes::legal_holds::get_legal_holds)
+                                        .service(routes::legal_holds::place_legal_hold)
+                                        .service(routes::legal_holds::release_legal_hold)
+                                        .service(routes::legal_holds::get_legal_hold_audit_log)
+                                        .service(routes::machines::get_machines)
+                                        .service(routes::machines::get_machine_pool)
+                                        .service(routes::machines::get_machine_health)
+                                        .service(routes::machines::terminate_machine)
+                                        .service(routes::machines::update_machine_limit)
+                                        .service(routes::compliance::get_access_report)
+                                        .service(routes::activity::get_activity)
                                         .service(routes::pipelines::run_pipeline_graph)
+                                        .service(routes::pipelines::validate_pipeline_graph)
                                         .service(routes::pipelines::get_pipelines)
                                         .service(routes::pipelines::create_pipeline)
                                         .service(routes::pipelines::update_pipeline)
@@ -483,31 +906,94 @@ fn main() -> anyhow::Result<()> {
                                         .service(routes::pipelines::create_template)
                                         .service(routes::pipelines::run_pipeline_interrupt_graph)
                                         .service(routes::pipelines::update_target_pipeline_version)
+                                        .service(routes::pipelines::get_pipeline_deployments)
+                                        .service(routes::pipelines::deploy_pipeline)
+                                        .service(routes::pipelines::rollback_pipeline_deployment)
+                                        .service(routes::pipelines::get_pipeline_usage)
+                                        .service(routes::pipelines::update_pipeline_quota)
+                                        .service(routes::pipelines::get_approval_tasks)
+                                        .service(routes::pipelines::resolve_approval_task)
+                                        .service(routes::pipelines::get_pipeline_triggers)
+                                        .service(routes::pipelines::create_pipeline_trigger)
+                                        .service(routes::pipelines::update_pipeline_trigger)
+                                        .service(routes::pipelines::delete_pipeline_trigger)
+                                        .service(routes::pipelines::get_pipeline_trigger_runs)
                                         .service(routes::api_keys::create_project_api_key)
                                         .service(routes::api_keys::get_api_keys_for_project)
                                         .service(routes::api_keys::revoke_project_api_key)
                                         .service(routes::evaluations::get_evaluation)
                                         .service(routes::evaluations::delete_evaluation)
+                                        .service(routes::evaluations::submit_human_scores)
                                         .service(routes::evaluations::get_evaluation_score_stats)
+                                        .service(
+                                            routes::evaluations::get_evaluation_score_pass_rate,
+                                        )
+                                        .service(routes::evaluations::get_evaluation_score_labels)
+                                        .service(routes::evaluations::get_evaluation_score_trend)
+                                        .service(routes::evaluations::query_evaluation_scores)
+                                        .service(routes::evaluations::get_group_baseline)
+                                        .service(routes::evaluations::pin_group_baseline)
                                         .service(
                                             routes::evaluations::get_evaluation_score_distribution,
                                         )
+                                        .service(routes::evaluations::get_evaluation_score_scatter)
+                                        .service(
+                                            routes::evaluations::get_evaluation_score_percentiles,
+                                        )
+                                        .service(routes::evaluations::compare_evaluations)
+                                        .service(routes::evaluations::export_evaluation)
+                                        .service(routes::evaluations::get_evaluation_insights)
+                                        .service(routes::span_scores::get_span_score_stats)
+                                        .service(routes::span_scores::get_span_score_distribution)
+                                        .service(routes::span_scores::get_span_score_trend)
+                                        .service(routes::span_scores::get_span_score_scatter)
+                                        .service(routes::span_scores::get_span_score_agreement)
+                                        .service(routes::evaluations::suggest_prompt_edits)
+                                        .service(routes::evaluations::create_eval_proposal)
+                                        .service(routes::evaluations::get_eval_proposals)
+                                        .service(routes::evaluations::adopt_eval_proposal)
+                                        .service(routes::evaluations::delete_eval_proposal)
+                                        .service(routes::shadow_deployments::get_shadow_deployments)
+                                        .service(routes::shadow_deployments::create_shadow_deployment)
+                                        .service(routes::shadow_deployments::update_shadow_deployment)
+                                        .service(routes::shadow_deployments::delete_shadow_deployment)
+                                        .service(
+                                            routes::shadow_deployments::get_shadow_deployment_comparison,
+                                        )
                                         .service(routes::datasets::get_datasets)
                                         .service(routes::datasets::create_dataset)
                                         .service(routes::datasets::get_dataset)
                                         .service(routes::datasets::rename_dataset)
                                         .service(routes::datasets::delete_dataset)
                                         .service(routes::datasets::upload_datapoint_file)
+                                        .service(routes::datasets::import_datapoints)
+                                        .service(routes::datasets::import_huggingface_dataset)
+                                        .service(routes::datasets::get_dataset_imports)
+                                        .service(routes::datasets::resume_dataset_import)
                                         .service(routes::datasets::create_datapoints)
                                         .service(routes::datasets::get_datapoints)
                                         .service(routes::datasets::update_datapoint_data)
                                         .service(routes::datasets::delete_datapoints)
                                         .service(routes::datasets::delete_all_datapoints)
+                                        .service(routes::datasets::get_dataset_versions)
+                                        .service(routes::datasets::diff_dataset_versions)
+                                        .service(routes::datasets::get_dataset_version_datapoints)
                                         .service(routes::datasets::index_dataset)
                                         .service(routes::evaluations::get_evaluations)
                                         .service(routes::evaluations::get_evaluation)
+                                        .service(routes::traces::get_ingestion_status)
+                                        .service(routes::traces::get_traces_by_external_id)
                                         .service(routes::traces::get_traces)
+                                        .service(routes::traces::query_traces)
                                         .service(routes::traces::get_single_trace)
+                                        .service(routes::traces::get_browser_timeline)
+                                        .service(routes::traces::get_agent_checkpoints)
+                                        .service(routes::traces::get_agent_graph)
+                                        .service(routes::traces::get_nearest_browser_snapshot)
+                                        .service(routes::traces::get_browser_snapshot_diff)
+                                        // before `spans/{span_id}`, which would match the path
+                                        .service(routes::traces::search_spans)
+                                        .service(routes::traces::query_spans)
                                         .service(routes::traces::get_single_span)
                                         .service(routes::traces::get_sessions)
                                         .service(routes::labels::get_label_types)
@@ -520,6 +1006,19 @@ fn main() -> anyhow::Result<()> {
                                             routes::labels::get_registered_label_classes_for_path,
                                         )
                                         .service(routes::labels::update_label_class)
+                                        .service(routes::labeling_queues::get_labeling_queues)
+                                        .service(routes::labeling_queues::create_labeling_queue)
+                                        .service(routes::labeling_queues::delete_labeling_queue)
+                                        .service(routes::labeling_queues::push_labeling_queue_items)
+                                        .service(
+                                            routes::labeling_queues::claim_next_labeling_queue_item,
+                                        )
+                                        .service(
+                                            routes::labeling_queues::release_labeling_queue_item,
+                                        )
+                                        .service(
+                                            routes::labeling_queues::complete_labeling_queue_item,
+                                        )
                                         .service(routes::events::get_event_templates)
                                         .service(routes::events::get_event_template)
                                         .service(routes::events::update_event_template)
@@ -527,6 +1026,37 @@ fn main() -> anyhow::Result<()> {
                                         .service(routes::events::get_events_by_template_id)
                                         .service(routes::events::get_events_metrics)
                                         .service(routes::traces::get_traces_metrics)
+                                        .service(routes::tags::get_project_tags)
+                                        .service(routes::tags::get_tagged_resources)
+                                        .service(routes::tags::get_resource_tags)
+                                        .service(routes::tags::set_resource_tags)
+                                        .service(routes::comments::get_comments)
+                                        .service(routes::comments::create_comment)
+                                        .service(routes::comments::update_comment)
+                                        .service(routes::comments::delete_comment)
+                                        .service(routes::issues::get_issue_trackers)
+                                        .service(routes::issues::set_issue_tracker)
+                                        .service(routes::issues::delete_issue_tracker)
+                                        .service(routes::issues::get_linked_issues)
+                                        .service(routes::issues::create_linked_issue)
+                                        .service(routes::issues::delete_linked_issue)
+                                        .service(routes::lake_exports::get_lake_export)
+                                        .service(routes::lake_exports::set_lake_export)
+                                        .service(routes::lake_exports::delete_lake_export)
+                                        .service(routes::warehouse_syncs::get_warehouse_syncs)
+                                        .service(routes::warehouse_syncs::set_warehouse_sync)
+                                        .service(routes::warehouse_syncs::delete_warehouse_sync)
+                                        .service(routes::analytics::ask_question)
+                                        .service(routes::analytics::run_query)
+                                        .service(routes::analytics::get_canary_analysis)
+                                        .service(routes::analytics::get_pipeline_latency)
+                                        .service(routes::analytics::get_hour_of_week_heatmap)
+                                        .service(routes::analytics::get_latency_percentiles)
+                                        .service(routes::analytics::get_agent_actions)
+                                        .service(routes::alerts::get_alert_rules)
+                                        .service(routes::alerts::get_alert_incidents)
+                                        .service(routes::alerts::create_alert_rule)
+                                        .service(routes::alerts::delete_alert_rule)
                                         .service(routes::provider_api_keys::save_api_key),
                                 ),
                         )
@@ -547,6 +1077,7 @@ fn main() -> anyhow::Result<()> {
                     db.clone(),
                     cache.clone(),
                     rabbitmq_connection_grpc.clone(),
+                    payload_archive_grpc.clone(),
                 );
 
                 Server::builder()
@@ -568,5 +1099,9 @@ fn main() -> anyhow::Result<()> {
         );
         handle.join().expect("thread is not panicking")?;
     }
+    // Flushes the buffered evaluation scores
+    if let Err(e) = runtime_handle.block_on(score_writer_handle) {
+        log::error!("Evaluation score writer failed: {:?}", e);
+    }
     Ok(())
 }
@@ -0,0 +1,143 @@
+//! Model Context Protocol server, so that AI coding assistants and agents can query traces,
+//! evaluations and datasets of a project while debugging.
+//!
+//! Implements the JSON-RPC messages of the Streamable HTTP transport, without server-initiated
+//! streams: every request is answered with a single JSON response.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::db::DB;
+
+pub mod tools;
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+const SERVER_NAME: &str = "laminar";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Deserialize)]
+pub struct JsonRpcRequest {
+    jsonrpc: String,
+    /// Absent for notifications, which are not answered
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+pub struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Serialize)]
+pub struct JsonRpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+
+    pub fn parse_error(message: impl Into<String>) -> Self {
+        Self::error(Value::Null, PARSE_ERROR, message)
+    }
+}
+
+/// Handles a single message. Returns None for notifications.
+pub async fn handle_message(
+    db: Arc<DB>,
+    project_id: Uuid,
+    message: Value,
+) -> Option<JsonRpcResponse> {
+    let request = match serde_json::from_value::<JsonRpcRequest>(message) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(request) => {
+            return Some(JsonRpcResponse::error(
+                request.id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "jsonrpc must be \"2.0\"",
+            ))
+        }
+        Err(e) => {
+            return Some(JsonRpcResponse::error(
+                Value::Null,
+                INVALID_REQUEST,
+                e.to_string(),
+            ))
+        }
+    };
+    let id = request.id?;
+
+    let response = match request.method.as_str() {
+        "initialize" => JsonRpcResponse::result(
+            id,
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": { "listChanged": false } },
+                "serverInfo": { "name": SERVER_NAME, "version": env!("CARGO_PKG_VERSION") },
+            }),
+        ),
+        "ping" => JsonRpcResponse::result(id, json!({})),
+        "tools/list" => JsonRpcResponse::result(id, json!({ "tools": tools::definitions() })),
+        "tools/call" => {
+            let Some(name) = request.params.get("name").and_then(|name| name.as_str()) else {
+                return Some(JsonRpcResponse::error(
+                    id,
+                    INVALID_PARAMS,
+                    "Tool name is required",
+                ));
+            };
+            let arguments = request
+                .params
+                .get("arguments")
+                .cloned()
+                .unwrap_or(json!({}));
+            match tools::call(db, project_id, name, arguments).await {
+                Some(result) => JsonRpcResponse::result(id, result),
+                None => {
+                    JsonRpcResponse::error(id, INVALID_PARAMS, format!("Unknown tool: {}", name))
+                }
+            }
+        }
+        method => JsonRpcResponse::error(
+            id,
+            METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        ),
+    };
+
+    Some(response)
+}
@@ -0,0 +1,261 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::db::{
+    datapoints, datasets, evaluations,
+    field_visibility::FieldVisibility,
+    modifiers::{DateRange, Filter, FilterOperator, RelativeDateInterval},
+    trace, DB,
+};
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+/// Tools in the format of the `tools/list` result
+pub fn definitions() -> Value {
+    json!([
+        {
+            "name": "search_traces",
+            "description": "Search the most recent traces of the project. Returns trace ids, \
+                timing, tokens, cost, status and previews of the top span's input and output.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Full text search in span inputs and outputs"
+                    },
+                    "pastHours": {
+                        "type": "integer",
+                        "description": "Only traces started in the past hours, defaults to 24"
+                    },
+                    "failedOnly": {
+                        "type": "boolean",
+                        "description": "Only failed traces"
+                    },
+                    "limit": { "type": "integer", "description": "At most 100, defaults to 20" }
+                }
+            }
+        },
+        {
+            "name": "get_evaluation_summary",
+            "description": "Summary of an evaluation: number of results and the average, \
+                minimum and maximum of every score.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "evaluationId": { "type": "string", "format": "uuid" }
+                },
+                "required": ["evaluationId"]
+            }
+        },
+        {
+            "name": "fetch_dataset_items",
+            "description": "Datapoints of a dataset, newest first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "dataset": { "type": "string", "description": "Dataset name" },
+                    "limit": { "type": "integer", "description": "At most 100, defaults to 20" },
+                    "offset": { "type": "integer" }
+                },
+                "required": ["dataset"]
+            }
+        }
+    ])
+}
+
+/// Calls the tool and returns the `tools/call` result, None if there is no such tool.
+/// Errors of the tool are returned as results with `isError`, so that the model can see them.
+pub async fn call(db: Arc<DB>, project_id: Uuid, name: &str, arguments: Value) -> Option<Value> {
+    let result = match name {
+        "search_traces" => match parse_arguments(arguments) {
+            Ok(arguments) => search_traces(db, project_id, arguments).await,
+            Err(e) => Err(e),
+        },
+        "get_evaluation_summary" => match parse_arguments(arguments) {
+            Ok(arguments) => get_evaluation_summary(db, project_id, arguments).await,
+            Err(e) => Err(e),
+        },
+        "fetch_dataset_items" => match parse_arguments(arguments) {
+            Ok(arguments) => fetch_dataset_items(db, project_id, arguments).await,
+            Err(e) => Err(e),
+        },
+        _ => return None,
+    };
+
+    let (text, is_error) = match result {
+        Ok(value) => (value.to_string(), false),
+        Err(e) => (e.to_string(), true),
+    };
+    Some(json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    }))
+}
+
+fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> Result<T> {
+    serde_json::from_value(arguments).map_err(|e| anyhow::anyhow!("Invalid arguments: {}", e))
+}
+
+fn limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchTracesArguments {
+    query: Option<String>,
+    past_hours: Option<u32>,
+    #[serde(default)]
+    failed_only: bool,
+    limit: Option<usize>,
+}
+
+async fn search_traces(
+    db: Arc<DB>,
+    project_id: Uuid,
+    arguments: SearchTracesArguments,
+) -> Result<Value> {
+    let date_range = Some(DateRange::Relative(RelativeDateInterval {
+        past_hours: arguments.past_hours.unwrap_or(24).to_string(),
+    }));
+    let filters = arguments.failed_only.then(|| {
+        vec![Filter {
+            filter_value: json!("Failed"),
+            filter_operator: FilterOperator::Eq,
+            filter_column: "status".to_string(),
+        }]
+    });
+    let query = arguments.query.filter(|query| !query.trim().is_empty());
+
+    let traces = trace::get_traces(
+        &db.pool,
+        project_id,
+        limit(arguments.limit),
+        0,
+        &filters,
+        &date_range,
+        query,
+        &FieldVisibility::ALL,
+    )
+    .await?;
+
+    Ok(serde_json::to_value(traces)?)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetEvaluationSummaryArguments {
+    evaluation_id: Uuid,
+}
+
+#[derive(Serialize)]
+struct ScoreSummary {
+    average: f64,
+    min: f64,
+    max: f64,
+    count: usize,
+}
+
+fn summarize_scores(scores: &[Value]) -> BTreeMap<String, ScoreSummary> {
+    let mut values = BTreeMap::<String, Vec<f64>>::new();
+    for score in scores.iter().filter_map(|scores| scores.as_object()) {
+        for (name, value) in score {
+            // Booleans count as 1 or 0, so that their average is the pass rate
+            if let Some(value) = value.as_f64().or_else(|| value.as_bool().map(f64::from)) {
+                values.entry(name.clone()).or_default().push(value);
+            }
+        }
+    }
+
+    values
+        .into_iter()
+        .map(|(name, values)| {
+            let summary = ScoreSummary {
+                average: values.iter().sum::<f64>() / values.len() as f64,
+                min: values.iter().cloned().fold(f64::INFINITY, f64::min),
+                max: values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+                count: values.len(),
+            };
+            (name, summary)
+        })
+        .collect()
+}
+
+async fn get_evaluation_summary(
+    db: Arc<DB>,
+    project_id: Uuid,
+    arguments: GetEvaluationSummaryArguments,
+) -> Result<Value> {
+    let evaluation = evaluations::get_evaluation(db.clone(), project_id, arguments.evaluation_id)
+        .await
+        .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => anyhow::anyhow!("Evaluation not found"),
+            _ => e,
+        })?;
+    let results = evaluations::get_evaluation_results(&db.pool, evaluation.id).await?;
+    let scores = results
+        .into_iter()
+        .map(|result| result.scores)
+        .collect::<Vec<_>>();
+
+    Ok(json!({
+        "evaluation": evaluation,
+        "resultCount": scores.len(),
+        "scores": summarize_scores(&scores),
+    }))
+}
+
+#[derive(Deserialize)]
+struct FetchDatasetItemsArguments {
+    dataset: String,
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+}
+
+async fn fetch_dataset_items(
+    db: Arc<DB>,
+    project_id: Uuid,
+    arguments: FetchDatasetItemsArguments,
+) -> Result<Value> {
+    let Some(dataset) =
+        datasets::get_dataset_by_name(&db.pool, &arguments.dataset, project_id).await?
+    else {
+        return Err(anyhow::anyhow!("Dataset {} not found", arguments.dataset));
+    };
+
+    let items = datapoints::get_datapoints(
+        &db.pool,
+        dataset.id,
+        limit(arguments.limit) as i64,
+        arguments.offset as i64,
+    )
+    .await?;
+    let total_count = datapoints::count_datapoints(&db.pool, dataset.id).await?;
+
+    Ok(json!({ "totalCount": total_count, "items": items }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_scores() {
+        let scores = vec![
+            json!({ "accuracy": 1.0, "relevance": 0.5 }),
+            json!({ "accuracy": 0.0 }),
+            Value::Null,
+        ];
+        let summary = summarize_scores(&scores);
+        assert_eq!(summary["accuracy"].average, 0.5);
+        assert_eq!(summary["accuracy"].count, 2);
+        assert_eq!(summary["relevance"].max, 0.5);
+    }
+}
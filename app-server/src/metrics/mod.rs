@@ -0,0 +1,292 @@
+//! In-process metrics for the ClickHouse batch writers and the SDKs of ingestion requests.
+//!
+//! Metrics are kept in global registries keyed by table name or SDK, so that insert functions
+//! and ingestion endpoints can record them without threading extra state through every call site.
+//! They are rendered in Prometheus text format on `/metrics` and as JSON on the
+//! internal debug endpoint.
+
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+/// Upper bounds of flush latency buckets, in milliseconds
+const FLUSH_LATENCY_BUCKETS_MS: [u64; 11] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+/// Upper bounds of rows-per-flush buckets
+const ROWS_PER_FLUSH_BUCKETS: [u64; 8] = [1, 10, 50, 100, 500, 1000, 5000, 10000];
+
+lazy_static! {
+    static ref BATCH_WRITERS: DashMap<&'static str, Arc<BatchWriterMetrics>> = DashMap::new();
+    static ref SDK_REQUESTS: DashMap<SdkKey, AtomicU64> = DashMap::new();
+}
+
+/// SDK name, language and version of an ingestion request
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+pub struct SdkKey {
+    pub name: String,
+    pub language: String,
+    pub version: String,
+}
+
+/// Cumulative histogram with fixed upper bounds, following Prometheus semantics
+pub struct Histogram {
+    bounds: &'static [u64],
+    /// One counter per bound, plus the last one for `+Inf`
+    counts: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: u64) {
+        let idx = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[idx].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn count(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    fn render(&self, out: &mut String, name: &str, table: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter()) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{table=\"{table}\",le=\"{bound}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{table=\"{table}\",le=\"+Inf\"}} {}",
+            self.count()
+        );
+        let _ = writeln!(out, "{name}_sum{{table=\"{table}\"}} {}", self.sum());
+        let _ = writeln!(out, "{name}_count{{table=\"{table}\"}} {}", self.count());
+    }
+}
+
+pub struct BatchWriterMetrics {
+    rows_per_flush: Histogram,
+    flush_latency_ms: Histogram,
+    rows_written: AtomicU64,
+    failed_flushes: AtomicU64,
+    retries: AtomicU64,
+    dropped_rows: AtomicU64,
+}
+
+impl BatchWriterMetrics {
+    fn new() -> Self {
+        Self {
+            rows_per_flush: Histogram::new(&ROWS_PER_FLUSH_BUCKETS),
+            flush_latency_ms: Histogram::new(&FLUSH_LATENCY_BUCKETS_MS),
+            rows_written: AtomicU64::new(0),
+            failed_flushes: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            dropped_rows: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_flush(&self, rows: usize, latency: Duration) {
+        self.rows_per_flush.observe(rows as u64);
+        self.flush_latency_ms.observe(latency.as_millis() as u64);
+        self.rows_written.fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_failed_flush(&self) {
+        self.failed_flushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, rows: usize) {
+        self.dropped_rows.fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    pub fn avg_flush_latency_ms(&self) -> f64 {
+        match self.flush_latency_ms.count() {
+            0 => 0.0,
+            flushes => self.flush_latency_ms.sum() as f64 / flushes as f64,
+        }
+    }
+}
+
+/// Get or register metrics for the writer of the given ClickHouse table
+pub fn batch_writer(table: &'static str) -> Arc<BatchWriterMetrics> {
+    BATCH_WRITERS
+        .entry(table)
+        .or_insert_with(|| Arc::new(BatchWriterMetrics::new()))
+        .clone()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchWriterSnapshot {
+    pub table: String,
+    pub flushes: u64,
+    pub rows_written: u64,
+    pub avg_rows_per_flush: f64,
+    pub avg_flush_latency_ms: f64,
+    pub failed_flushes: u64,
+    pub retries: u64,
+    pub dropped_rows: u64,
+}
+
+pub fn snapshot() -> Vec<BatchWriterSnapshot> {
+    let mut res = BATCH_WRITERS
+        .iter()
+        .map(|entry| {
+            let metrics = entry.value();
+            let flushes = metrics.rows_per_flush.count();
+            let avg = |sum: u64| {
+                if flushes == 0 {
+                    0.0
+                } else {
+                    sum as f64 / flushes as f64
+                }
+            };
+            BatchWriterSnapshot {
+                table: entry.key().to_string(),
+                flushes,
+                rows_written: metrics.rows_written.load(Ordering::Relaxed),
+                avg_rows_per_flush: avg(metrics.rows_per_flush.sum()),
+                avg_flush_latency_ms: avg(metrics.flush_latency_ms.sum()),
+                failed_flushes: metrics.failed_flushes.load(Ordering::Relaxed),
+                retries: metrics.retries.load(Ordering::Relaxed),
+                dropped_rows: metrics.dropped_rows.load(Ordering::Relaxed),
+            }
+        })
+        .collect::<Vec<_>>();
+    res.sort_by(|a, b| a.table.cmp(&b.table));
+    res
+}
+
+pub fn record_sdk_request(sdk: SdkKey) {
+    SDK_REQUESTS
+        .entry(sdk)
+        .or_insert_with(|| AtomicU64::new(0))
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// Ingestion requests per SDK since the process started, sorted by SDK
+pub fn sdk_requests() -> Vec<(SdkKey, u64)> {
+    let mut res = SDK_REQUESTS
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().load(Ordering::Relaxed)))
+        .collect::<Vec<_>>();
+    res.sort_by(|a, b| {
+        (&a.0.name, &a.0.language, &a.0.version).cmp(&(&b.0.name, &b.0.language, &b.0.version))
+    });
+    res
+}
+
+/// Render all registered metrics in Prometheus text exposition format
+pub fn render_prometheus() -> String {
+    let mut out = String::new();
+    let mut tables = BATCH_WRITERS
+        .iter()
+        .map(|entry| (*entry.key(), entry.value().clone()))
+        .collect::<Vec<_>>();
+    tables.sort_by(|a, b| a.0.cmp(b.0));
+
+    let _ = writeln!(out, "# TYPE lmnr_batch_writer_rows_per_flush histogram");
+    for (table, metrics) in &tables {
+        metrics
+            .rows_per_flush
+            .render(&mut out, "lmnr_batch_writer_rows_per_flush", table);
+    }
+    let _ = writeln!(out, "# TYPE lmnr_batch_writer_flush_latency_ms histogram");
+    for (table, metrics) in &tables {
+        metrics
+            .flush_latency_ms
+            .render(&mut out, "lmnr_batch_writer_flush_latency_ms", table);
+    }
+
+    let counters: [(&str, fn(&BatchWriterMetrics) -> &AtomicU64); 4] = [
+        ("lmnr_batch_writer_rows_written_total", |m| &m.rows_written),
+        ("lmnr_batch_writer_failed_flushes_total", |m| {
+            &m.failed_flushes
+        }),
+        ("lmnr_batch_writer_retries_total", |m| &m.retries),
+        ("lmnr_batch_writer_dropped_rows_total", |m| &m.dropped_rows),
+    ];
+    for (name, counter) in counters {
+        let _ = writeln!(out, "# TYPE {name} counter");
+        for (table, metrics) in &tables {
+            let _ = writeln!(
+                out,
+                "{name}{{table=\"{table}\"}} {}",
+                counter(metrics).load(Ordering::Relaxed)
+            );
+        }
+    }
+
+    let _ = writeln!(out, "# TYPE lmnr_ingestion_requests_total counter");
+    for (sdk, count) in sdk_requests() {
+        let _ = writeln!(
+            out,
+            "lmnr_ingestion_requests_total{{sdk_name=\"{}\",sdk_language=\"{}\",sdk_version=\"{}\"}} {count}",
+            escape_label_value(&sdk.name),
+            escape_label_value(&sdk.language),
+            escape_label_value(&sdk.version),
+        );
+    }
+
+    out
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&ROWS_PER_FLUSH_BUCKETS);
+        histogram.observe(1);
+        histogram.observe(7);
+        histogram.observe(20000);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "rows", "spans");
+
+        assert!(out.contains("rows_bucket{table=\"spans\",le=\"1\"} 1\n"));
+        assert!(out.contains("rows_bucket{table=\"spans\",le=\"10\"} 2\n"));
+        assert!(out.contains("rows_bucket{table=\"spans\",le=\"10000\"} 2\n"));
+        assert!(out.contains("rows_bucket{table=\"spans\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("rows_sum{table=\"spans\"} 20008\n"));
+        assert!(out.contains("rows_count{table=\"spans\"} 3\n"));
+    }
+}
@@ -7,6 +7,8 @@ use std::{
 };
 use tokio::sync::RwLock;
 
+use crate::ids;
+
 fn read_lines<P>(filename: P) -> io::Result<Vec<String>>
 where
     P: AsRef<Path>,
@@ -27,7 +29,11 @@ impl NameGenerator {
     pub fn new() -> Self {
         let adjectives = read_lines("data/adjectives.txt").unwrap();
         let nouns = read_lines("data/nouns.txt").unwrap();
-        let rng = RwLock::new(StdRng::from_entropy());
+        let rng = if ids::is_deterministic() {
+            RwLock::new(StdRng::seed_from_u64(0))
+        } else {
+            RwLock::new(StdRng::from_entropy())
+        };
         Self {
             adjectives,
             nouns,
@@ -0,0 +1,173 @@
+//! Egress policy for server-initiated HTTP calls, such as LLM provider adapters and pipeline nodes.
+//!
+//! If `EGRESS_ALLOWED_HOSTS` is set, the shared HTTP client refuses to resolve any host that is not
+//! in the comma-separated list. Entries are exact host names, or `*.example.com` to allow all
+//! subdomains. The check is done at DNS resolution, so it also applies to redirects and to
+//! user-configured endpoints, e.g. Azure OpenAI resources. URLs with an IP address as host are
+//! never resolved, so the host of every request is checked as well, and IP addresses are only
+//! reached if they are in the list. With a policy, proxies from the environment are not used.
+//! Calls made through AWS SDK clients, such as Bedrock, are not covered and must be restricted at
+//! the network level.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use lazy_static::lazy_static;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect, Client, Proxy, Url,
+};
+
+/// Requests to hosts that are not allowed are sent to this proxy, which the resolver refuses, so
+/// that they fail before any connection is made
+const BLOCKED_PROXY_URL: &str = "http://blocked-egress.invalid";
+
+lazy_static! {
+    static ref EGRESS_POLICY: Option<EgressPolicy> = EgressPolicy::from_env();
+    static ref HTTP_CLIENT: Client = build_client(EGRESS_POLICY.clone());
+}
+
+#[derive(Clone, Debug)]
+pub struct EgressPolicy {
+    allowed_hosts: Vec<String>,
+}
+
+impl EgressPolicy {
+    fn from_env() -> Option<Self> {
+        let hosts = std::env::var("EGRESS_ALLOWED_HOSTS").ok()?;
+        Some(Self::new(&hosts))
+    }
+
+    fn new(hosts: &str) -> Self {
+        Self {
+            allowed_hosts: hosts
+                .split(',')
+                .map(|host| host.trim().to_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn is_host_allowed(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_lowercase();
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host.ends_with(&format!(".{domain}")),
+                None => host == *allowed,
+            })
+    }
+}
+
+struct EgressResolver {
+    policy: EgressPolicy,
+}
+
+impl Resolve for EgressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let allowed = self.policy.is_host_allowed(&host);
+        Box::pin(async move {
+            if !allowed {
+                log::warn!(
+                    "Blocked egress to host not in EGRESS_ALLOWED_HOSTS: {}",
+                    host
+                );
+                return Err(format!("egress to {host} is not allowed").into());
+            }
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+fn build_client(policy: Option<EgressPolicy>) -> Client {
+    let Some(policy) = policy else {
+        return Client::new();
+    };
+    let redirect_policy = policy.clone();
+    let request_policy = policy.clone();
+    Client::builder()
+        .dns_resolver(Arc::new(EgressResolver { policy }))
+        // The resolver is not called for IP literals, e.g. `https://169.254.169.254/`
+        .proxy(Proxy::custom(move |url: &Url| {
+            let host = url.host_str().unwrap_or_default();
+            if request_policy.is_host_allowed(host) {
+                return None;
+            }
+            log::warn!(
+                "Blocked egress to host not in EGRESS_ALLOWED_HOSTS: {}",
+                host
+            );
+            Some(BLOCKED_PROXY_URL.to_string())
+        }))
+        // Hosts are resolved by the resolver above, but IP literals in redirects bypass DNS
+        .redirect(redirect::Policy::custom(move |attempt| {
+            let allowed = attempt
+                .url()
+                .host_str()
+                .is_some_and(|host| redirect_policy.is_host_allowed(host));
+            if !allowed || attempt.previous().len() >= 10 {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+/// Shared HTTP client for all outgoing calls. Cloning is cheap, the connection pool is shared.
+pub fn http_client() -> Client {
+    HTTP_CLIENT.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_host_allowed() {
+        let policy = EgressPolicy::new("api.openai.com, *.openai.azure.com,");
+        assert!(policy.is_host_allowed("api.openai.com"));
+        assert!(policy.is_host_allowed("API.OpenAI.com."));
+        assert!(policy.is_host_allowed("my-resource.openai.azure.com"));
+        assert!(!policy.is_host_allowed("openai.azure.com"));
+        assert!(!policy.is_host_allowed("api.openai.com.evil.com"));
+        assert!(!policy.is_host_allowed("169.254.169.254"));
+    }
+
+    /// Answers one request with an empty 200 and returns whether a connection was accepted
+    async fn serve_once(listener: tokio::net::TcpListener) -> bool {
+        let accept = tokio::time::timeout(std::time::Duration::from_millis(500), listener.accept());
+        let Ok(Ok((mut stream, _))) = accept.await else {
+            return false;
+        };
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut buf = [0; 1024];
+        let _ = stream.read(&mut buf).await;
+        let _ = stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            .await;
+        true
+    }
+
+    #[tokio::test]
+    async fn test_ip_literal_requests() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_once(listener));
+        let client = build_client(Some(EgressPolicy::new("api.openai.com")));
+        assert!(client.get(&url).send().await.is_err());
+        assert!(!server.await.unwrap());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_once(listener));
+        let client = build_client(Some(EgressPolicy::new("api.openai.com, 127.0.0.1")));
+        let res = client.get(&url).send().await.unwrap();
+        assert!(res.status().is_success());
+        assert!(server.await.unwrap());
+    }
+}
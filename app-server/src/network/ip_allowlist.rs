@@ -0,0 +1,177 @@
+use std::{net::IpAddr, str::FromStr, sync::Arc};
+
+use actix_web::dev::ConnectionInfo;
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    db::{self, DB},
+    traces::limits::get_workspace_id_for_project_id,
+};
+
+/// An IPv4 or IPv6 network in CIDR notation. A plain address is a network with a full prefix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for IpNetwork {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr = IpAddr::from_str(addr)?;
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>()?,
+            None => max_prefix,
+        };
+        if prefix > max_prefix {
+            return Err(anyhow::anyhow!("Invalid prefix length in {}", s));
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        // IPv4 clients are often seen as IPv4-mapped IPv6 addresses on dual-stack sockets
+        let ip = match ip {
+            IpAddr::V6(v6) => v6
+                .to_ipv4_mapped()
+                .map(IpAddr::V4)
+                .unwrap_or(IpAddr::V6(*v6)),
+            IpAddr::V4(_) => *ip,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Networks that may call the API on behalf of a workspace. Empty means no restriction.
+#[derive(Clone, Default)]
+pub struct WorkspaceIpAllowlist {
+    pub networks: Vec<IpNetwork>,
+}
+
+impl WorkspaceIpAllowlist {
+    pub fn parse(entries: &[String]) -> Result<Self> {
+        let networks = entries
+            .iter()
+            .map(|entry| IpNetwork::from_str(entry))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { networks })
+    }
+
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        if self.networks.is_empty() {
+            return true;
+        }
+        ip.is_some_and(|ip| self.networks.iter().any(|network| network.contains(&ip)))
+    }
+}
+
+/// Address of the client. Proxy headers are only trusted if the server is configured to run
+/// behind a proxy, otherwise clients could spoof them to bypass the allowlist.
+pub fn client_ip(connection_info: &ConnectionInfo) -> Option<IpAddr> {
+    let trust_proxy = std::env::var("TRUST_PROXY_HEADERS").is_ok_and(|v| v == "true");
+    let addr = if trust_proxy {
+        connection_info.realip_remote_addr()
+    } else {
+        connection_info.peer_addr()
+    }?;
+    parse_ip(addr)
+}
+
+/// Parses an address that may contain a port, e.g. `1.2.3.4:5678` or `[::1]:5678`
+fn parse_ip(addr: &str) -> Option<IpAddr> {
+    if let Ok(ip) = IpAddr::from_str(addr) {
+        return Some(ip);
+    }
+    std::net::SocketAddr::from_str(addr)
+        .ok()
+        .map(|socket_addr| socket_addr.ip())
+}
+
+pub async fn get_ip_allowlist_by_workspace_id(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    workspace_id: Uuid,
+) -> Result<WorkspaceIpAllowlist> {
+    let cache_res = cache
+        .get::<WorkspaceIpAllowlist>(&workspace_id.to_string())
+        .await;
+    match cache_res {
+        Ok(Some(allowlist)) => Ok(allowlist),
+        Ok(None) | Err(_) => {
+            let entries = db::workspace::get_ip_allowlist(&db.pool, &workspace_id).await?;
+            let allowlist = WorkspaceIpAllowlist::parse(&entries)?;
+            let _ = cache
+                .insert::<WorkspaceIpAllowlist>(workspace_id.to_string(), &allowlist)
+                .await;
+            Ok(allowlist)
+        }
+    }
+}
+
+pub async fn is_ip_allowed_for_project(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+    ip: Option<IpAddr>,
+) -> Result<bool> {
+    let workspace_id =
+        get_workspace_id_for_project_id(db.clone(), cache.clone(), project_id).await?;
+    let allowlist = get_ip_allowlist_by_workspace_id(db, cache, workspace_id).await?;
+    Ok(allowlist.allows(ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_network_contains() {
+        let network = IpNetwork::from_str("10.1.0.0/16").unwrap();
+        assert!(network.contains(&"10.1.200.3".parse().unwrap()));
+        assert!(!network.contains(&"10.2.0.1".parse().unwrap()));
+        assert!(network.contains(&"::ffff:10.1.0.7".parse().unwrap()));
+
+        let single = IpNetwork::from_str("192.168.0.1").unwrap();
+        assert!(single.contains(&"192.168.0.1".parse().unwrap()));
+        assert!(!single.contains(&"192.168.0.2".parse().unwrap()));
+
+        let any = IpNetwork::from_str("0.0.0.0/0").unwrap();
+        assert!(any.contains(&"8.8.8.8".parse().unwrap()));
+
+        let v6 = IpNetwork::from_str("2001:db8::/32").unwrap();
+        assert!(v6.contains(&"2001:db8:1::1".parse().unwrap()));
+        assert!(!v6.contains(&"2001:db9::1".parse().unwrap()));
+
+        assert!(IpNetwork::from_str("10.0.0.0/33").is_err());
+        assert!(IpNetwork::from_str("not-an-ip").is_err());
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let allowlist = WorkspaceIpAllowlist::parse(&["10.0.0.0/8".to_string()]).unwrap();
+        assert!(allowlist.allows(parse_ip("10.3.4.5:443")));
+        assert!(!allowlist.allows(parse_ip("[::1]:443")));
+        assert!(!allowlist.allows(None));
+        assert!(WorkspaceIpAllowlist::default().allows(None));
+    }
+}
@@ -0,0 +1,5 @@
+//! Network access policies: which clients may call the API, and which destinations
+//! the server may call.
+
+pub mod egress;
+pub mod ip_allowlist;
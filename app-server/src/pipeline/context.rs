@@ -9,7 +9,7 @@ use crate::{
     language_model::LanguageModelRunner, semantic_search::SemanticSearch,
 };
 
-use super::{nodes::StreamChunk, runner::PipelineRunner, RunType};
+use super::{nodes::StreamChunk, runner::PipelineRunner, RunBudget, RunType};
 
 pub struct Context {
     pub language_model: Arc<LanguageModelRunner>,
@@ -27,4 +27,6 @@ pub struct Context {
     pub code_executor: Arc<dyn CodeExecutor>,
     pub db: Arc<DB>,
     pub cache: Arc<Cache>,
+    pub project_id: Option<Uuid>,
+    pub budget: Option<RunBudget>,
 }
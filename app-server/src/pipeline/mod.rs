@@ -7,6 +7,7 @@ use nodes::output::OutputNode;
 use nodes::Handle;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use self::nodes::{Node, NodeInput};
@@ -17,18 +18,69 @@ pub mod nodes;
 pub mod runner;
 pub mod templates;
 pub mod trace;
+pub mod triggers;
 pub mod utils;
+pub mod validation;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Graph {
     pub nodes: HashMap<String, Node>,
     pub pred: HashMap<Uuid, Vec<Uuid>>,
+    /// Retries and timeouts by node id, nodes without options run once without timeout
+    #[serde(
+        default,
+        rename = "executionOptions",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub execution_options: HashMap<Uuid, NodeExecutionOptions>,
+    /// Limits of the run, the run is stopped once any of them is reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget: Option<RunBudget>,
     #[serde(skip)]
     pub env: HashMap<String, String>,
     #[serde(skip)]
     pub metadata: HashMap<String, String>,
     #[serde(skip)]
     pub run_type: RunType,
+    /// Project the graph runs in, None for runs that aren't made on behalf of a project
+    #[serde(skip)]
+    pub project_id: Option<Uuid>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeExecutionOptions {
+    /// Number of times a failed or timed out run is retried. Chunks streamed by the failed
+    /// runs are not retracted.
+    #[serde(default)]
+    pub max_retries: u32,
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RunBudget {
+    /// Number of node runs, including input and output nodes and each run of a node in a cycle
+    #[serde(default)]
+    pub max_steps: Option<u32>,
+    /// Approximate cost in dollars of the LLM calls of the run
+    #[serde(default)]
+    pub max_cost: Option<f64>,
+    #[serde(default)]
+    pub max_duration_seconds: Option<u64>,
+}
+
+impl RunBudget {
+    pub fn is_valid(&self) -> bool {
+        self.max_steps.map_or(true, |max_steps| max_steps > 0)
+            && self
+                .max_cost
+                .map_or(true, |max_cost| max_cost.is_finite() && max_cost > 0.0)
+            && self
+                .max_duration_seconds
+                .map_or(true, |max_duration| max_duration > 0)
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -135,9 +187,12 @@ impl Graph {
         Ok(Self {
             nodes,
             pred,
+            execution_options: HashMap::new(),
+            budget: None,
             env: HashMap::new(),
             metadata: HashMap::new(),
             run_type: RunType::AutoLabel,
+            project_id: None,
         })
     }
 
@@ -253,7 +308,9 @@ impl Graph {
                 | Node::SemanticSearch(_)
                 | Node::SemanticSimilarity(_)
                 | Node::StringTemplate(_)
-                | Node::Code(_) => {}
+                | Node::Code(_)
+                | Node::Reduce(_)
+                | Node::Approval(_) => {}
             }
         }
         env_vars
@@ -0,0 +1,119 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::db::pipelines::pipeline_approvals::{self, ApprovalTaskStatus};
+use crate::engine::{RunOutput, RunnableNode};
+use crate::pipeline::context::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::utils::map_handles;
+use super::{Handle, NodeInput};
+
+/// Interval of checking whether the task has been resolved. The task is resolved through the
+/// API, possibly on another instance, so the node polls the database instead of waiting for a
+/// message.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const DEFAULT_TIMEOUT_SECONDS: u64 = 24 * 60 * 60;
+
+/// Pauses the run until a human approves or rejects its input, e.g. before an agent takes a risky
+/// action. The approver can edit the input, and the node outputs the approved value.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalNode {
+    pub id: Uuid,
+    pub name: String,
+    pub inputs: Vec<Handle>,
+    pub outputs: Vec<Handle>,
+    pub inputs_mappings: HashMap<Uuid, Uuid>,
+    /// Shown to the approver with the input
+    #[serde(default)]
+    pub instructions: String,
+    /// How long to wait for the approver before failing, defaults to a day
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+}
+
+#[async_trait]
+impl RunnableNode for ApprovalNode {
+    fn handles_mapping(&self) -> Vec<(Uuid, Handle)> {
+        map_handles(&self.inputs, &self.inputs_mappings)
+    }
+
+    fn output_handle_id(&self) -> Uuid {
+        self.outputs.first().unwrap().id
+    }
+
+    fn node_name(&self) -> String {
+        self.name.to_owned()
+    }
+
+    fn node_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn node_type(&self) -> String {
+        "Approval".to_string()
+    }
+
+    async fn run(
+        &self,
+        inputs: HashMap<String, NodeInput>,
+        context: Arc<Context>,
+    ) -> Result<RunOutput> {
+        let project_id = context
+            .project_id
+            .ok_or(anyhow::anyhow!("Approval node can only run in a project"))?;
+        let payload: Value = inputs.into_values().next().unwrap().into();
+
+        let pool = &context.db.pool;
+        let task = pipeline_approvals::create_approval_task(
+            pool,
+            &project_id,
+            &self.name,
+            &self.instructions,
+            &payload,
+        )
+        .await?;
+
+        let timeout = Duration::from_secs(self.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+        let started_at = tokio::time::Instant::now();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let task = pipeline_approvals::get_approval_task(pool, &task.id)
+                .await?
+                .ok_or(anyhow::anyhow!("Approval task has been deleted"))?;
+            match task.status {
+                ApprovalTaskStatus::APPROVED => {
+                    let output = task.resolved_payload.unwrap_or(task.payload);
+                    return Ok(RunOutput::Success((output.into(), None)));
+                }
+                ApprovalTaskStatus::REJECTED => {
+                    return Err(anyhow::anyhow!(
+                        "Rejected by the approver{}",
+                        task.comment
+                            .map(|comment| format!(": {}", comment))
+                            .unwrap_or_default()
+                    ));
+                }
+                ApprovalTaskStatus::EXPIRED => {
+                    return Err(anyhow::anyhow!("Approval task has expired"));
+                }
+                ApprovalTaskStatus::PENDING => {}
+            }
+
+            // The task may be resolved between the check and expiring it, then it's checked again
+            if started_at.elapsed() >= timeout
+                && pipeline_approvals::expire_approval_task(pool, &task.id).await?
+            {
+                return Err(anyhow::anyhow!(
+                    "Nobody resolved the approval task within {} seconds",
+                    timeout.as_secs()
+                ));
+            }
+        }
+    }
+}
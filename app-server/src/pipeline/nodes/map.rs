@@ -21,6 +21,8 @@ use crate::{
 use super::{utils::map_handles, Handle, NodeInput};
 
 const BATCH_SIZE: usize = 50;
+/// Default number of subpipeline runs in flight, to avoid hitting the rate limits of language models
+const DEFAULT_MAX_CONCURRENCY: usize = 50;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -39,6 +41,9 @@ pub struct MapNode {
     #[serde(default)]
     pub pipeline_version_id: Option<Uuid>,
     pub runnable_graph: Value,
+    /// Maximum number of subpipeline runs in flight, at most the batch size
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, FromRow, Deserialize)]
@@ -62,12 +67,13 @@ impl PipelineRunner {
         graph: Graph,
         inputs_vec: &Vec<HashMap<String, NodeInput>>,
         context: Arc<Context>,
+        max_concurrency: usize,
     ) -> Vec<SubpipelineRunResult> {
         // attempt for bounded concurrency
         // ref: https://medium.com/@jaderd/you-should-never-do-bounded-concurrency-like-this-in-rust-851971728cfb
 
         // we limit the number of concurrent calls to avoid hitting the rate limit on the language model
-        let permits = Arc::new(Semaphore::new(50));
+        let permits = Arc::new(Semaphore::new(max_concurrency.max(1)));
 
         let run_calls = inputs_vec.iter().map(|inputs| {
             let permits = permits.clone();
@@ -76,6 +82,7 @@ impl PipelineRunner {
             let env = context.env.clone();
             let metadata = context.metadata.clone();
             let run_type = context.run_type.clone();
+            graph.project_id = context.project_id;
 
             async move {
                 let _permit = permits.acquire().await.unwrap();
@@ -162,6 +169,10 @@ impl RunnableNode for MapNode {
         let mut outputs_list: Vec<String> = Vec::new();
         let mut total_token_count = 0;
         let mut approximate_cost = Some(0.0);
+        let max_concurrency = self
+            .max_concurrency
+            .unwrap_or(DEFAULT_MAX_CONCURRENCY)
+            .clamp(1, BATCH_SIZE);
 
         for batch in input_list.chunks(BATCH_SIZE) {
             let inputs_vec = batch
@@ -175,7 +186,12 @@ impl RunnableNode for MapNode {
 
             let run_results = context
                 .pipeline_runner
-                .batch_run_with_metadata(graph.clone(), &inputs_vec, context.clone())
+                .batch_run_with_metadata(
+                    graph.clone(),
+                    &inputs_vec,
+                    context.clone(),
+                    max_concurrency,
+                )
                 .await;
 
             for res in run_results {
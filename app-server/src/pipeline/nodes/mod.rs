@@ -13,6 +13,7 @@ use crate::language_model::{ChatMessageContent, ChatMessageContentPart};
 use super::runner::PipelineRunnerError;
 use super::trace::{MetaLog, RunTrace};
 
+mod approval;
 pub mod code;
 mod condition;
 mod error;
@@ -23,6 +24,7 @@ mod json_extractor;
 pub mod llm;
 pub mod map;
 pub mod output;
+mod reduce;
 mod semantic_search;
 mod semantic_search_utils;
 mod semantic_similarity;
@@ -198,6 +200,9 @@ pub struct Message {
     pub node_type: String,
     /// all node per-run metadata that needs to be logged at the end of execution
     pub meta_log: Option<MetaLog>,
+    /// Number of failed runs of the node before this message, see `NodeExecutionOptions`
+    #[serde(default)]
+    pub retries: u32,
 }
 
 impl Message {
@@ -212,6 +217,7 @@ impl Message {
             node_name: String::new(),
             node_type: String::new(),
             meta_log: None,
+            retries: 0,
         }
     }
 }
@@ -274,6 +280,8 @@ pub enum Node {
     Switch(switch::SwitchNode),
     SemanticSimilarity(semantic_similarity::SemanticSimilarityNode),
     Code(code::CodeNode),
+    Reduce(reduce::ReduceNode),
+    Approval(approval::ApprovalNode),
 }
 
 impl Node {
@@ -298,6 +306,8 @@ impl Node {
             Self::JsonExtractor(node) => node.id,
             Self::SemanticSimilarity(node) => node.id,
             Self::Code(node) => node.id,
+            Self::Reduce(node) => node.id,
+            Self::Approval(node) => node.id,
         }
         .clone()
     }
@@ -336,9 +346,93 @@ impl Node {
             Self::JsonExtractor(node) => node.name.as_str(),
             Self::SemanticSimilarity(node) => node.name.as_str(),
             Self::Code(node) => node.name.as_str(),
+            Self::Reduce(node) => node.name.as_str(),
+            Self::Approval(node) => node.name.as_str(),
         }
         .to_owned()
     }
+
+    /// Input handles, including the inputs of template variables
+    pub fn input_handles(&self) -> Vec<&Handle> {
+        match self {
+            Self::Input(_) => vec![],
+            Self::LLM(node) => node
+                .inputs
+                .iter()
+                .chain(node.dynamic_inputs.iter())
+                .collect(),
+            Self::StringTemplate(node) => node
+                .inputs
+                .iter()
+                .chain(node.dynamic_inputs.iter())
+                .collect(),
+            Self::Output(node) => node.inputs.iter().collect(),
+            Self::Error(node) => node.inputs.iter().collect(),
+            Self::Subpipeline(node) => node.inputs.iter().collect(),
+            Self::Map(node) => node.inputs.iter().collect(),
+            Self::SemanticSearch(node) => node.inputs.iter().collect(),
+            Self::SemanticSwitch(node) => node.inputs.iter().collect(),
+            Self::Condition(node) => node.inputs.iter().collect(),
+            Self::FormatValidator(node) => node.inputs.iter().collect(),
+            Self::Extractor(node) => node.inputs.iter().collect(),
+            Self::Zenguard(node) => node.inputs.iter().collect(),
+            Self::Switch(node) => node.inputs.iter().collect(),
+            Self::JsonExtractor(node) => node.inputs.iter().collect(),
+            Self::SemanticSimilarity(node) => node.inputs.iter().collect(),
+            Self::Code(node) => node.inputs.iter().collect(),
+            Self::Reduce(node) => node.inputs.iter().collect(),
+            Self::Approval(node) => node.inputs.iter().collect(),
+        }
+    }
+
+    pub fn output_handles(&self) -> &[Handle] {
+        match self {
+            Self::Output(_) => &[],
+            Self::Input(node) => &node.outputs,
+            Self::StringTemplate(node) => &node.outputs,
+            Self::LLM(node) => &node.outputs,
+            Self::Error(node) => &node.outputs,
+            Self::Subpipeline(node) => &node.outputs,
+            Self::Map(node) => &node.outputs,
+            Self::SemanticSearch(node) => &node.outputs,
+            Self::SemanticSwitch(node) => &node.outputs,
+            Self::Condition(node) => &node.outputs,
+            Self::FormatValidator(node) => &node.outputs,
+            Self::Extractor(node) => &node.outputs,
+            Self::Zenguard(node) => &node.outputs,
+            Self::Switch(node) => &node.outputs,
+            Self::JsonExtractor(node) => &node.outputs,
+            Self::SemanticSimilarity(node) => &node.outputs,
+            Self::Code(node) => &node.outputs,
+            Self::Reduce(node) => &node.outputs,
+            Self::Approval(node) => &node.outputs,
+        }
+    }
+
+    /// Mapping from the node's input handle ids to the output handle ids of preceding nodes
+    pub fn inputs_mappings(&self) -> Option<&HashMap<Uuid, Uuid>> {
+        match self {
+            Self::Input(_) => None,
+            Self::Output(node) => Some(&node.inputs_mappings),
+            Self::StringTemplate(node) => Some(&node.inputs_mappings),
+            Self::LLM(node) => Some(&node.inputs_mappings),
+            Self::Error(node) => Some(&node.inputs_mappings),
+            Self::Subpipeline(node) => Some(&node.inputs_mappings),
+            Self::Map(node) => Some(&node.inputs_mappings),
+            Self::SemanticSearch(node) => Some(&node.inputs_mappings),
+            Self::SemanticSwitch(node) => Some(&node.inputs_mappings),
+            Self::Condition(node) => Some(&node.inputs_mappings),
+            Self::FormatValidator(node) => Some(&node.inputs_mappings),
+            Self::Extractor(node) => Some(&node.inputs_mappings),
+            Self::Zenguard(node) => Some(&node.inputs_mappings),
+            Self::Switch(node) => Some(&node.inputs_mappings),
+            Self::JsonExtractor(node) => Some(&node.inputs_mappings),
+            Self::SemanticSimilarity(node) => Some(&node.inputs_mappings),
+            Self::Code(node) => Some(&node.inputs_mappings),
+            Self::Reduce(node) => Some(&node.inputs_mappings),
+            Self::Approval(node) => Some(&node.inputs_mappings),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
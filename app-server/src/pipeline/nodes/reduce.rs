@@ -0,0 +1,126 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::engine::{RunOutput, RunnableNode};
+use crate::pipeline::context::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::utils::map_handles;
+use super::{Handle, NodeInput};
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReduceOperation {
+    /// Joins the items with the separator
+    Join,
+    Count,
+    Sum,
+    Average,
+    Min,
+    Max,
+    /// Most frequent item, e.g. for majority voting over the outputs of a map node. Ties go to
+    /// the item that appears first.
+    MostCommon,
+}
+
+/// Aggregates a list, e.g. the outputs of a map node, into a single value
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReduceNode {
+    pub id: Uuid,
+    pub name: String,
+    pub inputs: Vec<Handle>,
+    pub outputs: Vec<Handle>,
+    pub inputs_mappings: HashMap<Uuid, Uuid>,
+    pub operation: ReduceOperation,
+    #[serde(default)]
+    pub separator: String,
+}
+
+fn parse_numbers(items: &[String]) -> Result<Vec<f64>> {
+    items
+        .iter()
+        .map(|item| {
+            item.trim()
+                .parse::<f64>()
+                .map_err(|_| anyhow::anyhow!("Item is not a number: {}", item))
+        })
+        .collect()
+}
+
+impl ReduceNode {
+    fn reduce(&self, items: Vec<String>) -> Result<NodeInput> {
+        let output = match self.operation {
+            ReduceOperation::Join => NodeInput::String(items.join(&self.separator)),
+            ReduceOperation::Count => NodeInput::Float(items.len() as f64),
+            ReduceOperation::Sum => NodeInput::Float(parse_numbers(&items)?.iter().sum()),
+            ReduceOperation::Average => {
+                if items.is_empty() {
+                    return Err(anyhow::anyhow!("Can't average an empty list"));
+                }
+                let numbers = parse_numbers(&items)?;
+                NodeInput::Float(numbers.iter().sum::<f64>() / numbers.len() as f64)
+            }
+            ReduceOperation::Min | ReduceOperation::Max => {
+                let numbers = parse_numbers(&items)?.into_iter();
+                let extreme = if self.operation == ReduceOperation::Min {
+                    numbers.reduce(f64::min)
+                } else {
+                    numbers.reduce(f64::max)
+                };
+                NodeInput::Float(extreme.ok_or(anyhow::anyhow!("List is empty"))?)
+            }
+            ReduceOperation::MostCommon => {
+                let mut counts = HashMap::<&str, usize>::new();
+                for item in &items {
+                    *counts.entry(item.as_str()).or_default() += 1;
+                }
+                let max_count = counts.values().copied().max().unwrap_or_default();
+                let most_common = items
+                    .iter()
+                    .find(|item| counts[item.as_str()] == max_count)
+                    .ok_or(anyhow::anyhow!("List is empty"))?;
+                NodeInput::String(most_common.clone())
+            }
+        };
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl RunnableNode for ReduceNode {
+    fn handles_mapping(&self) -> Vec<(Uuid, Handle)> {
+        map_handles(&self.inputs, &self.inputs_mappings)
+    }
+
+    fn output_handle_id(&self) -> Uuid {
+        self.outputs.first().unwrap().id
+    }
+
+    fn node_name(&self) -> String {
+        self.name.to_owned()
+    }
+
+    fn node_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn node_type(&self) -> String {
+        "Reduce".to_string()
+    }
+
+    async fn run(
+        &self,
+        inputs: HashMap<String, NodeInput>,
+        _context: Arc<Context>,
+    ) -> Result<RunOutput> {
+        let items = match inputs.into_values().next().unwrap() {
+            NodeInput::StringList(items) => items,
+            _ => return Err(anyhow::anyhow!("Input must be a list of strings")),
+        };
+
+        Ok(RunOutput::Success((self.reduce(items)?, None)))
+    }
+}
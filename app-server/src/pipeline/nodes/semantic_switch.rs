@@ -9,6 +9,7 @@ use uuid::Uuid;
 use super::utils::map_handles;
 use super::{ConditionedValue, Handle, NodeInput};
 use crate::engine::{RunOutput, RunnableNode};
+use crate::network;
 use crate::pipeline::context::Context;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -83,7 +84,7 @@ impl RunnableNode for SemanticSwitchNode {
             .flatten()
             .collect::<Vec<Value>>();
 
-        let client = reqwest::Client::new();
+        let client = network::egress::http_client();
 
         let cohere_api_key = std::env::var("COHERE_API_KEY").unwrap();
 
@@ -80,6 +80,7 @@ impl RunnableNode for SubpipelineNode {
 
         let mut graph = serde_json::from_value::<Graph>(self.runnable_graph.clone())?;
         graph.setup(&inputs, &env, &context.metadata, &context.run_type)?;
+        graph.project_id = context.project_id;
         // TODO: Add streaming and websocket streaming here so that subpipelines can stream and use external functions.
         let run_result = context.pipeline_runner.run(graph, context.tx.clone()).await;
 
@@ -9,6 +9,7 @@ use tonic::async_trait;
 use uuid::Uuid;
 
 use super::{utils::map_handles, ConditionedValue, Handle, NodeInput};
+use crate::network;
 use crate::pipeline::{context::Context, trace::MetaLog};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -186,8 +187,7 @@ impl RunnableNode for ZenguardNode {
         let mut redacted_input = input.clone();
         let mut condition = String::from("passthrough");
 
-        // TODO: Implement a pool of reqwest clients or make shared client
-        let client = Client::new();
+        let client = network::egress::http_client();
         let mut responses: HashMap<String, HashMap<String, Value>> = HashMap::new();
 
         // It's inevitable to call one-by-one sequentially, because message possibly will get
@@ -5,6 +5,7 @@ use crate::{
     cache::Cache,
     code_executor::CodeExecutor,
     db::{
+        events::EventObservation,
         spans::Span,
         trace::{CurrentTraceAndSpan, TraceType},
         DB,
@@ -13,11 +14,13 @@ use crate::{
     features::{is_feature_enabled, Feature},
     routes::pipelines::GraphInterruptMessage,
     traces::{
+        span_attributes::PIPELINE_TERMINATION,
         utils::{get_llm_usage_for_span, record_span_to_db},
         OBSERVATIONS_EXCHANGE, OBSERVATIONS_ROUTING_KEY,
     },
 };
 use anyhow::Result;
+use chrono::Utc;
 use itertools::Itertools;
 use lapin::{options::BasicPublishOptions, BasicProperties, Connection};
 use serde::Serialize;
@@ -37,6 +40,9 @@ use super::{
     Graph, GraphError, InvalidSchemasError,
 };
 
+/// Event on the span of a run that was stopped before it finished
+const RUN_TERMINATED_EVENT: &str = "run_terminated";
+
 #[derive(Debug)]
 pub struct RunningError {
     pub partial_trace: EngineOutput,
@@ -130,6 +136,10 @@ impl PipelineRunner {
         }
     }
 
+    pub fn language_model(&self) -> Arc<LanguageModelRunner> {
+        self.language_model.clone()
+    }
+
     pub async fn run(
         &self,
         graph: Graph,
@@ -157,6 +167,8 @@ impl PipelineRunner {
             code_executor: self.code_executor.clone(),
             db: self.db.clone(),
             cache: self.cache.clone(),
+            project_id: graph.project_id,
+            budget: graph.budget.clone(),
         };
 
         let tasks = parse_graph(graph)?;
@@ -202,6 +214,8 @@ impl PipelineRunner {
             code_executor: self.code_executor.clone(),
             db: self.db.clone(),
             cache: self.cache.clone(),
+            project_id: graph.project_id,
+            budget: graph.budget.clone(),
         };
 
         let tasks = parse_graph(graph)?;
@@ -247,6 +261,18 @@ impl PipelineRunner {
             &engine_output.messages,
             trace_type.unwrap_or_default(),
         );
+        // the graceful termination of the run is recorded on its span
+        let mut events = vec![];
+        if let Some(termination) = &engine_output.termination {
+            parent_span.attributes[PIPELINE_TERMINATION] = serde_json::json!(termination);
+            events.push(EventObservation {
+                id: Uuid::new_v4(),
+                span_id: parent_span.span_id,
+                timestamp: Utc::now(),
+                template_name: RUN_TERMINATED_EVENT.to_string(),
+                value: Some(serde_json::json!(termination)),
+            });
+        }
 
         let message_spans = Span::from_messages(
             &engine_output.messages,
@@ -257,7 +283,8 @@ impl PipelineRunner {
         let parent_span_mq_message = RabbitMqSpanMessage {
             project_id: *project_id,
             span: parent_span.clone(),
-            events: vec![],
+            events,
+            accepted_at: None,
         };
 
         if is_feature_enabled(Feature::FullBuild) {
@@ -286,6 +313,7 @@ impl PipelineRunner {
                     project_id: *project_id,
                     span: message_span,
                     events: vec![],
+                    accepted_at: None,
                 };
 
                 let payload = serde_json::to_string(&message_mq_message)?;
@@ -14,6 +14,25 @@ pub struct RunTraceStats {
     pub end_time: DateTime<Utc>,
     pub total_token_count: i64,
     pub approximate_cost: Option<f64>,
+    /// Most expensive nodes first
+    pub node_stats: Vec<NodeRunStats>,
+}
+
+/// Cost attribution of a node within a run. The tokens and cost of subpipeline and map nodes
+/// include those of the nodes of their subpipelines.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeRunStats {
+    pub node_id: Uuid,
+    pub node_name: String,
+    pub node_type: String,
+    /// Number of times the node has run, more than one in cycles
+    pub executions: u32,
+    /// Failed runs of the node, which were retried
+    pub retries: u32,
+    pub total_token_count: i64,
+    /// None if the cost of some execution is unknown
+    pub approximate_cost: Option<f64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -43,12 +62,34 @@ pub struct RunTrace {
     pub trace_id: Option<Uuid>,
 }
 
+/// Tokens and approximate cost of the node run that produced the message
+pub fn message_usage(message: &Message) -> (i64, Option<f64>) {
+    match &message.meta_log {
+        Some(MetaLog::LLM(llm_meta)) => (llm_meta.total_token_count, llm_meta.approximate_cost),
+        // TODO: Update Zenguard cost when they become paid, but they are indeed free now
+        // I should've put None, but we care more about LLM prices, so not to make whole `approximate_cost` None because of Zenguard
+        Some(MetaLog::Zenguard(_)) => (0, Some(0.0)),
+        Some(MetaLog::Subpipeline(subpipeline_meta)) => (
+            subpipeline_meta.total_token_count,
+            subpipeline_meta.approximate_cost,
+        ),
+        Some(MetaLog::Map(map_meta)) => (map_meta.total_token_count, map_meta.approximate_cost),
+        None => (0, Some(0.0)),
+    }
+}
+
+/// Add up at least the costs we can, the sum is None if any cost is unknown
+fn add_cost(total: Option<f64>, cost: Option<f64>) -> Option<f64> {
+    Some(total? + cost?)
+}
+
 impl RunTraceStats {
     pub fn from_messages(messages: &HashMap<Uuid, Message>) -> Self {
         let mut earliest_start_time = Utc::now();
         let mut latest_end_time = DateTime::<Utc>::default(); // UNIX_EPOCH
         let mut total_token_count = 0;
         let mut approximate_cost = Some(0.0);
+        let mut node_stats = HashMap::<Uuid, NodeRunStats>::new();
 
         messages.values().into_iter().for_each(|message| {
             if message.start_time < earliest_start_time {
@@ -57,31 +98,34 @@ impl RunTraceStats {
             if message.end_time > latest_end_time {
                 latest_end_time = message.end_time;
             }
-            total_token_count += match &message.meta_log {
-                Some(MetaLog::LLM(llm_meta)) => llm_meta.total_token_count,
-                Some(MetaLog::Zenguard(_)) => 0,
-                Some(MetaLog::Subpipeline(subpipeline_meta)) => subpipeline_meta.total_token_count,
-                Some(MetaLog::Map(map_meta)) => map_meta.total_token_count,
-                None => 0,
-            };
+            let (message_token_count, message_cost) = message_usage(message);
+            total_token_count += message_token_count;
+            approximate_cost = add_cost(approximate_cost, message_cost);
 
-            let message_cost = match &message.meta_log {
-                Some(MetaLog::LLM(llm_meta)) => llm_meta.approximate_cost,
-                // TODO: Update Zenguard cost when they become paid, but they are indeed free now
-                // I should've put None, but we care more about LLM prices, so not to make whole `approximate_cost` None because of Zenguard
-                Some(MetaLog::Zenguard(_)) => Some(0.0),
-                Some(MetaLog::Subpipeline(subpipeline_meta)) => subpipeline_meta.approximate_cost,
-                Some(MetaLog::Map(map_meta)) => map_meta.approximate_cost,
-                None => Some(0.0),
-            };
-            if let Some(cost) = approximate_cost {
-                // add up at least the costs we can
-                if let Some(message_cost) = message_cost {
-                    approximate_cost = Some(cost + message_cost)
-                } else {
-                    approximate_cost = None;
-                }
-            }
+            let stats = node_stats
+                .entry(message.node_id)
+                .or_insert_with(|| NodeRunStats {
+                    node_id: message.node_id,
+                    node_name: message.node_name.clone(),
+                    node_type: message.node_type.clone(),
+                    executions: 0,
+                    retries: 0,
+                    total_token_count: 0,
+                    approximate_cost: Some(0.0),
+                });
+            stats.executions += 1;
+            stats.retries += message.retries;
+            stats.total_token_count += message_token_count;
+            stats.approximate_cost = add_cost(stats.approximate_cost, message_cost);
+        });
+
+        let mut node_stats = node_stats.into_values().collect::<Vec<_>>();
+        // unknown costs first, since they may be the highest
+        node_stats.sort_by(|a, b| {
+            let cost = |stats: &NodeRunStats| stats.approximate_cost.unwrap_or(f64::INFINITY);
+            cost(b)
+                .total_cmp(&cost(a))
+                .then_with(|| a.node_name.cmp(&b.node_name))
         });
 
         Self {
@@ -89,6 +133,7 @@ impl RunTraceStats {
             end_time: latest_end_time,
             total_token_count,
             approximate_cost,
+            node_stats,
         }
     }
 }
@@ -0,0 +1,275 @@
+//! Runs of deployed pipelines that aren't requested through the API: on a cron schedule, when a
+//! dataset is updated or when a webhook is called.
+//!
+//! Triggers queue runs in `pipeline_trigger_runs`, which also keeps the run history. The worker
+//! started by `run_triggers_periodically` queues the due schedules and executes the queued runs.
+//! Rows are claimed with `SKIP LOCKED`, so any number of app-server instances can run the worker.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::{
+    api::{utils::query_deployed_pipeline_version, v1::pipelines::record_node_usage},
+    cache::Cache,
+    db::{
+        self,
+        activity::{ActivityType, NewActivity},
+        pipelines::{
+            pipeline_triggers::{self, PipelineTriggerRun, PipelineTriggerRunStatus},
+            pipeline_usage,
+        },
+        DB,
+    },
+    logging,
+    routes::activity::record_activity,
+    traces::evaluators::get_stored_env,
+};
+
+use super::{
+    nodes::{GraphOutput, NodeInput},
+    runner::PipelineRunner,
+    Graph, RunType,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Runs executed at once by an instance. Runs can wait for long, e.g. on approval nodes.
+const MAX_CONCURRENT_RUNS: usize = 16;
+
+/// Parses a cron expression. Standard five field expressions are accepted in addition to the
+/// ones with seconds, and run at the start of the minute.
+pub fn parse_cron_schedule(expression: &str) -> Result<Schedule> {
+    let expression = expression.trim();
+    let expression = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    Schedule::from_str(&expression)
+        .map_err(|e| anyhow::anyhow!("Invalid cron schedule {}: {}", expression, e))
+}
+
+/// Next time the schedule runs after `after`, None if the expression is invalid or never runs
+pub fn next_run_at(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    parse_cron_schedule(expression).ok()?.after(&after).next()
+}
+
+/// Queues runs of the dataset triggers watching the dataset in the background
+pub fn on_dataset_updated(db: Arc<DB>, project_id: Uuid, dataset_id: Uuid, action: &str) {
+    let event = json!({ "datasetId": dataset_id, "action": action });
+    logging::spawn(async move {
+        if let Err(e) = pipeline_triggers::enqueue_dataset_trigger_runs(
+            &db.pool,
+            &project_id,
+            &dataset_id,
+            &event,
+        )
+        .await
+        {
+            log::error!(
+                "Failed to queue dataset trigger runs. dataset_id [{}]: {:?}",
+                dataset_id,
+                e
+            );
+        }
+    });
+}
+
+pub async fn run_triggers_periodically(
+    pipeline_runner: Arc<PipelineRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+) {
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_RUNS));
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = pipeline_triggers::enqueue_due_schedule_runs(&db.pool, |expression| {
+            next_run_at(expression, Utc::now())
+        })
+        .await
+        {
+            log::error!("Failed to queue scheduled pipeline runs: {:?}", e);
+        }
+
+        loop {
+            let permit = permits.clone().acquire_owned().await.unwrap();
+            let run = match pipeline_triggers::claim_queued_trigger_run(&db.pool).await {
+                Ok(Some(run)) => run,
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("Failed to claim queued pipeline trigger run: {:?}", e);
+                    break;
+                }
+            };
+
+            let pipeline_runner = pipeline_runner.clone();
+            let db = db.clone();
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                execute_trigger_run(pipeline_runner, db, cache, run).await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+async fn execute_trigger_run(
+    pipeline_runner: Arc<PipelineRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    run: PipelineTriggerRun,
+) {
+    let (status, outputs, error) =
+        match run_triggered_pipeline(pipeline_runner, db.clone(), cache, &run).await {
+            Ok(outputs) => (PipelineTriggerRunStatus::SUCCEEDED, Some(outputs), None),
+            Err(e) => (PipelineTriggerRunStatus::FAILED, None, Some(e.to_string())),
+        };
+
+    if let Err(e) =
+        pipeline_triggers::finish_trigger_run(&db.pool, &run.id, status, outputs, error.clone())
+            .await
+    {
+        log::error!(
+            "Failed to record pipeline trigger run. id [{}]: {:?}",
+            run.id,
+            e
+        );
+    }
+
+    if let Some(error) = error {
+        log::warn!(
+            "Triggered pipeline run failed. trigger_id [{}], id [{}]: {}",
+            run.trigger_id,
+            run.id,
+            error
+        );
+        notify_failure(db, &run, error).await;
+    }
+}
+
+/// Adds the failure to the project's activity feed, which is where members are notified of it
+async fn notify_failure(db: Arc<DB>, run: &PipelineTriggerRun, error: String) {
+    let trigger = match pipeline_triggers::get_trigger(&db.pool, &run.trigger_id).await {
+        Ok(Some(trigger)) => trigger,
+        // The trigger has been deleted while running
+        Ok(None) => return,
+        Err(e) => {
+            log::error!("Failed to get pipeline trigger: {:?}", e);
+            return;
+        }
+    };
+    record_activity(
+        db,
+        NewActivity {
+            project_id: trigger.project_id,
+            activity_type: ActivityType::PIPELINE_TRIGGER_FAILED,
+            actor_id: None,
+            resource_id: Some(trigger.pipeline_id),
+            summary: format!("{:?} trigger run failed: {}", trigger.trigger_type, error),
+            details: json!({
+                "triggerId": trigger.id,
+                "triggerRunId": run.id,
+                "error": error,
+            }),
+        },
+    );
+}
+
+/// Runs the version deployed to the trigger's target and returns the outputs. The run's trace is
+/// recorded like the ones of the API's runs, with the trigger in its metadata.
+async fn run_triggered_pipeline(
+    pipeline_runner: Arc<PipelineRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    run: &PipelineTriggerRun,
+) -> Result<Value> {
+    let trigger = pipeline_triggers::get_trigger(&db.pool, &run.trigger_id)
+        .await?
+        .ok_or(anyhow::anyhow!("Trigger has been deleted"))?;
+    let project_id = trigger.project_id;
+    let pipeline = db::pipelines::get_pipeline_by_id(&db.pool, &trigger.pipeline_id).await?;
+
+    let pipeline_version = query_deployed_pipeline_version(
+        db.clone(),
+        cache,
+        project_id,
+        pipeline.name.clone(),
+        &trigger.target,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("{}", e))?
+    .ok_or(anyhow::anyhow!(
+        "Pipeline {} has no version deployed to {}",
+        pipeline.name,
+        trigger.target
+    ))?;
+    if !pipeline_usage::reserve_pipeline_execution(&db.pool, &trigger.pipeline_id).await? {
+        return Err(anyhow::anyhow!(
+            "Pipeline {} has used up its monthly execution quota",
+            pipeline.name
+        ));
+    }
+
+    let mut inputs = serde_json::from_value::<HashMap<String, NodeInput>>(trigger.inputs)
+        .map_err(|e| anyhow::anyhow!("Invalid trigger inputs: {}", e))?;
+    inputs
+        .entry("event".to_string())
+        .or_insert_with(|| run.event.clone().into());
+    let mut env = get_stored_env(db.clone(), project_id).await?;
+    env.insert("collection_name".to_string(), project_id.to_string());
+    let metadata = HashMap::from([
+        ("triggerId".to_string(), trigger.id.to_string()),
+        ("triggerRunId".to_string(), run.id.to_string()),
+    ]);
+
+    let mut graph = serde_json::from_value::<Graph>(pipeline_version.runnable_graph)?;
+    graph.setup(&inputs, &env, &metadata, &RunType::Endpoint)?;
+    graph.project_id = Some(project_id);
+
+    let run_result = pipeline_runner.run(graph, None).await;
+    let pipeline_version_name = format!("{}.{}", pipeline.name, pipeline_version.name);
+    if let Err(e) = pipeline_runner
+        .record_observations(&run_result, &project_id, &pipeline_version_name, None, None)
+        .await
+    {
+        log::error!("Failed to record observations of triggered run: {:?}", e);
+    }
+    record_node_usage(db, trigger.pipeline_id, &run_result);
+
+    let outputs = run_result?
+        .output_values()
+        .into_iter()
+        .map(|(node_name, value)| (node_name, GraphOutput { value }))
+        .collect::<HashMap<_, _>>();
+
+    Ok(json!(outputs))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_next_run_at() {
+        let after = Utc.with_ymd_and_hms(2024, 12, 13, 10, 17, 30).unwrap();
+
+        assert_eq!(
+            next_run_at("*/15 * * * *", after),
+            Some(Utc.with_ymd_and_hms(2024, 12, 13, 10, 30, 0).unwrap())
+        );
+        assert_eq!(
+            next_run_at("0 0 9 * * Mon", after),
+            Some(Utc.with_ymd_and_hms(2024, 12, 16, 9, 0, 0).unwrap())
+        );
+        assert!(parse_cron_schedule("every minute").is_err());
+    }
+}
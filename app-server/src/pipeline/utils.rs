@@ -18,6 +18,12 @@ pub fn parse_graph(graph: Graph) -> Result<HashMap<Uuid, Task>> {
         .map(|(_, node)| (node.id(), task_from_node(node)))
         .collect();
 
+    for (node_id, execution_options) in graph.execution_options {
+        if let Some(task) = tasks.get_mut(&node_id) {
+            task.execution_options = execution_options;
+        }
+    }
+
     for (to, from) in graph.pred {
         for from_node in from {
             tasks.get_mut(&to).unwrap().add_prev(from_node);
@@ -91,6 +97,12 @@ fn task_from_node(node: Node) -> Task {
             Arc::new(semantic_similarity_node),
         ),
         Node::Code(code_node) => Task::with_action(code_node.id.clone(), Arc::new(code_node)),
+        Node::Reduce(reduce_node) => {
+            Task::with_action(reduce_node.id.clone(), Arc::new(reduce_node))
+        }
+        Node::Approval(approval_node) => {
+            Task::with_action(approval_node.id.clone(), Arc::new(approval_node))
+        }
     }
 }
 
@@ -98,6 +110,14 @@ pub fn get_target_pipeline_version_cache_key(project_id: &str, pipeline_name: &s
     format!("{}:{}", project_id, pipeline_name)
 }
 
+pub fn get_pipeline_deployment_cache_key(
+    project_id: &str,
+    pipeline_name: &str,
+    target: &str,
+) -> String {
+    format!("{}:{}:deployment:{}", project_id, pipeline_name, target)
+}
+
 pub fn render_chat_message_list(messages: Vec<ChatMessage>) -> String {
     messages
         .iter()
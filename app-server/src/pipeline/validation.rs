@@ -0,0 +1,299 @@
+//! Checks of a pipeline graph that can be made before running it, so that the builder can show
+//! all problems of a graph at once instead of the first one the engine runs into.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{
+    nodes::{Handle, HandleType, Node},
+    Graph,
+};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum GraphValidationErrorKind {
+    NoOutputNode,
+    UnknownNode,
+    UnconnectedInput,
+    UnknownHandle,
+    TypeMismatch,
+    Cycle,
+    MissingEnvVar,
+    InvalidSchema,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphValidationError {
+    pub kind: GraphValidationErrorKind,
+    /// None for errors of the graph as a whole
+    pub node_name: Option<String>,
+    pub message: String,
+}
+
+impl GraphValidationError {
+    fn new(kind: GraphValidationErrorKind, node_name: Option<&str>, message: String) -> Self {
+        Self {
+            kind,
+            node_name: node_name.map(String::from),
+            message,
+        }
+    }
+}
+
+fn is_compatible(from: &HandleType, to: &HandleType) -> bool {
+    from == to || *from == HandleType::Any || *to == HandleType::Any
+}
+
+impl Graph {
+    /// All problems of the graph, empty if it can be run. Env vars are checked against the env
+    /// the graph has been set up with.
+    pub fn validate(&self) -> Vec<GraphValidationError> {
+        let mut errors = self.validate_structure();
+
+        let mut missing_env_vars = self.get_missing_env_vars().into_iter().collect::<Vec<_>>();
+        missing_env_vars.sort();
+        errors.extend(missing_env_vars.into_iter().map(|var| {
+            GraphValidationError::new(
+                GraphValidationErrorKind::MissingEnvVar,
+                None,
+                format!("Env var {var} is required, but not set"),
+            )
+        }));
+
+        if let Err(e) = self.validate_baml_schemas() {
+            errors.extend(e.invalid_schemas.into_iter().map(|(node_name, message)| {
+                GraphValidationError::new(
+                    GraphValidationErrorKind::InvalidSchema,
+                    Some(node_name.as_str()),
+                    message,
+                )
+            }));
+        }
+
+        errors
+    }
+
+    /// Problems of the nodes and the edges between them, which don't depend on the env
+    pub fn validate_structure(&self) -> Vec<GraphValidationError> {
+        let mut errors = Vec::new();
+        // sorted, so that the errors are in the same order for the same graph
+        let nodes = self
+            .nodes
+            .values()
+            .map(|node| (node.name(), node))
+            .collect::<BTreeMap<_, _>>();
+
+        if !nodes
+            .values()
+            .any(|node| matches!(node, Node::Output(_) | Node::Error(_)))
+        {
+            errors.push(GraphValidationError::new(
+                GraphValidationErrorKind::NoOutputNode,
+                None,
+                "Graph must contain at least one output node".to_string(),
+            ));
+        }
+
+        let node_ids = nodes.values().map(|node| node.id()).collect::<HashSet<_>>();
+        let mut unknown_ids = self
+            .pred
+            .iter()
+            .flat_map(|(to, from)| std::iter::once(to).chain(from.iter()))
+            .chain(self.execution_options.keys())
+            .filter(|id| !node_ids.contains(id))
+            .collect::<Vec<_>>();
+        unknown_ids.sort();
+        unknown_ids.dedup();
+        errors.extend(unknown_ids.into_iter().map(|id| {
+            GraphValidationError::new(
+                GraphValidationErrorKind::UnknownNode,
+                None,
+                format!("Edge or execution options reference node {id}, which is not in the graph"),
+            )
+        }));
+
+        let output_handles = nodes
+            .iter()
+            .flat_map(|(name, node)| {
+                node.output_handles()
+                    .iter()
+                    .map(move |handle| (handle.id, (name.as_str(), handle)))
+            })
+            .collect::<HashMap<Uuid, (&str, &Handle)>>();
+
+        // Edges between nodes, without the edges into cyclic handles, which loops are made of
+        let mut next_nodes = nodes
+            .keys()
+            .map(|name| (name.as_str(), Vec::new()))
+            .collect::<BTreeMap<_, _>>();
+        for (name, node) in &nodes {
+            let Some(inputs_mappings) = node.inputs_mappings() else {
+                continue;
+            };
+            for input in node.input_handles() {
+                let input_name = input.name.clone().unwrap_or_default();
+                let Some(from_id) = inputs_mappings.get(&input.id) else {
+                    errors.push(GraphValidationError::new(
+                        GraphValidationErrorKind::UnconnectedInput,
+                        Some(name.as_str()),
+                        format!("Input {input_name} is not connected"),
+                    ));
+                    continue;
+                };
+                let Some((from_node, output)) = output_handles.get(from_id) else {
+                    errors.push(GraphValidationError::new(
+                        GraphValidationErrorKind::UnknownHandle,
+                        Some(name.as_str()),
+                        format!("Input {input_name} is connected to a missing output"),
+                    ));
+                    continue;
+                };
+                if !is_compatible(&output.handle_type, &input.handle_type) {
+                    errors.push(GraphValidationError::new(
+                        GraphValidationErrorKind::TypeMismatch,
+                        Some(name.as_str()),
+                        format!(
+                            "Input {input_name} of type {:?} is connected to output of {from_node} of type {:?}",
+                            input.handle_type, output.handle_type
+                        ),
+                    ));
+                }
+                if !input.is_cyclic {
+                    if let Some(next) = next_nodes.get_mut(from_node) {
+                        next.push(name.as_str());
+                    }
+                }
+            }
+        }
+
+        if let Some(cycle) = find_cycle(&next_nodes) {
+            errors.push(GraphValidationError::new(
+                GraphValidationErrorKind::Cycle,
+                cycle.first().copied(),
+                format!("Nodes form a cycle: {}", cycle.join(" -> ")),
+            ));
+        }
+
+        errors
+    }
+}
+
+/// Node names along the first cycle found, starting and ending with the same node
+fn find_cycle<'a>(next_nodes: &BTreeMap<&'a str, Vec<&'a str>>) -> Option<Vec<&'a str>> {
+    fn visit<'a>(
+        node: &'a str,
+        next_nodes: &BTreeMap<&'a str, Vec<&'a str>>,
+        visited: &mut HashSet<&'a str>,
+        path: &mut Vec<&'a str>,
+    ) -> Option<Vec<&'a str>> {
+        if let Some(start) = path.iter().position(|n| *n == node) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(node);
+            return Some(cycle);
+        }
+        if !visited.insert(node) {
+            return None;
+        }
+        path.push(node);
+        for next in next_nodes.get(node).into_iter().flatten() {
+            if let Some(cycle) = visit(*next, next_nodes, visited, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    let mut visited = HashSet::new();
+    next_nodes
+        .keys()
+        .find_map(|node| visit(*node, next_nodes, &mut visited, &mut Vec::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn handle(id: Uuid, handle_type: &str) -> serde_json::Value {
+        json!({"id": id, "name": "value", "type": handle_type})
+    }
+
+    fn template(id: Uuid, name: &str, input: Uuid, output: Uuid, from: Uuid) -> serde_json::Value {
+        json!({
+            "type": "StringTemplate",
+            "id": id,
+            "name": name,
+            "inputs": [handle(input, "String")],
+            "outputs": [handle(output, "String")],
+            "inputsMappings": {input.to_string(): from},
+            "text": "{{value}}",
+        })
+    }
+
+    #[test]
+    fn test_validate_structure() {
+        let (input_id, input_output) = (Uuid::new_v4(), Uuid::new_v4());
+        let (output_id, output_input) = (Uuid::new_v4(), Uuid::new_v4());
+        let graph: Graph = serde_json::from_value(json!({
+            "nodes": {
+                "question": {
+                    "type": "Input",
+                    "id": input_id,
+                    "name": "question",
+                    "outputs": [handle(input_output, "StringList")],
+                    "inputType": "StringList",
+                },
+                "answer": {
+                    "type": "Output",
+                    "id": output_id,
+                    "name": "answer",
+                    "inputs": [handle(output_input, "ChatMessageList")],
+                    "inputsMappings": {output_input.to_string(): input_output},
+                },
+            },
+            "pred": {output_id.to_string(): [input_id]},
+        }))
+        .unwrap();
+
+        let errors = graph.validate_structure();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, GraphValidationErrorKind::TypeMismatch);
+        assert_eq!(errors[0].node_name.as_deref(), Some("answer"));
+    }
+
+    #[test]
+    fn test_validate_structure_cycle() {
+        let (a_id, a_input, a_output) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let (b_id, b_input, b_output) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let (output_id, output_input) = (Uuid::new_v4(), Uuid::new_v4());
+        let graph: Graph = serde_json::from_value(json!({
+            "nodes": {
+                "a": template(a_id, "a", a_input, a_output, b_output),
+                "b": template(b_id, "b", b_input, b_output, a_output),
+                "answer": {
+                    "type": "Output",
+                    "id": output_id,
+                    "name": "answer",
+                    "inputs": [handle(output_input, "String")],
+                    "inputsMappings": {output_input.to_string(): b_output},
+                },
+            },
+            "pred": {
+                a_id.to_string(): [b_id],
+                b_id.to_string(): [a_id],
+                output_id.to_string(): [b_id],
+            },
+        }))
+        .unwrap();
+
+        let errors = graph.validate_structure();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, GraphValidationErrorKind::Cycle);
+        assert_eq!(errors[0].message, "Nodes form a cycle: a -> b -> a");
+    }
+}
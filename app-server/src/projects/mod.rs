@@ -17,6 +17,9 @@ pub struct Project {
     pub id: Uuid,
     pub name: String,
     pub workspace_id: Uuid,
+    /// Unique within the workspace, used by automation to provision projects idempotently
+    #[sqlx(default)]
+    pub slug: Option<String>,
 }
 
 pub async fn create_project(
@@ -26,8 +29,9 @@ pub async fn create_project(
     user_id: &Uuid,
     name: &str,
     workspace_id: Uuid,
+    slug: Option<&str>,
 ) -> Result<Project> {
-    let project = db::projects::create_project(pool, &user_id, name, workspace_id).await?;
+    let project = db::projects::create_project(pool, &user_id, name, workspace_id, slug).await?;
     log::info!(
         "Created new project: id: {}, name: {}, workspace_id: {}",
         project.id,
@@ -57,3 +61,34 @@ pub async fn create_project(
 
     Ok(project)
 }
+
+/// Returns the project with the slug in the workspace, or creates it, so that provisioning can be
+/// repeated. Without a slug, a new project is always created.
+pub async fn get_or_create_project(
+    pool: &PgPool,
+    cache: Arc<Cache>,
+    semantic_search: Arc<dyn SemanticSearch>,
+    user_id: &Uuid,
+    name: &str,
+    workspace_id: Uuid,
+    slug: Option<&str>,
+) -> Result<Project> {
+    if let Some(slug) = slug {
+        if let Some(project) =
+            db::projects::get_project_by_slug(pool, user_id, &workspace_id, slug).await?
+        {
+            return Ok(project);
+        }
+    }
+
+    create_project(
+        pool,
+        cache,
+        semantic_search,
+        user_id,
+        name,
+        workspace_id,
+        slug,
+    )
+    .await
+}
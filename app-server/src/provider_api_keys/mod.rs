@@ -4,14 +4,20 @@ use sodiumoxide::{
     hex,
 };
 
+use crate::secrets;
+
 pub struct ValueAndNonceHex {
     pub value: String,
     pub nonce: String, // 192 bytes (384 hex characters)
 }
 
+fn get_key(secret_name: &str) -> Option<Key> {
+    let key_hex = secrets::get(secret_name)?;
+    Key::from_slice(hex::decode(key_hex).ok()?.as_slice())
+}
+
 pub fn encode_api_key(name: &String, api_key: &String) -> ValueAndNonceHex {
-    let key_hex = std::env::var("AEAD_SECRET_KEY").unwrap();
-    let key = Key::from_slice(hex::decode(key_hex).unwrap().as_slice()).unwrap();
+    let key = get_key("AEAD_SECRET_KEY").unwrap();
 
     let nonce = gen_nonce();
     let encrypted = seal(api_key.as_bytes(), Some(name.as_bytes()), &nonce, &key);
@@ -23,8 +29,7 @@ pub fn encode_api_key(name: &String, api_key: &String) -> ValueAndNonceHex {
 }
 
 pub fn decode_api_key(name: &String, nonce: &String, value: &String) -> Result<String> {
-    let key_hex = std::env::var("AEAD_SECRET_KEY").unwrap();
-    let key = Key::from_slice(hex::decode(key_hex).unwrap().as_slice()).unwrap();
+    let key = get_key("AEAD_SECRET_KEY").unwrap();
 
     let encrypted = hex::decode(value).or(Err(anyhow::anyhow!(
         "Failed to decode hex value for api_key {}",
@@ -40,8 +45,15 @@ pub fn decode_api_key(name: &String, nonce: &String, value: &String) -> Result<S
         name
     ))?;
 
+    // While the key is being rotated, values encrypted with the previous key are still readable
     let decrypted = open(encrypted.as_slice(), Some(name.as_bytes()), &nonce, &key)
-        .expect(&format!("Failed to decrypt api_key {}", name));
+        .or_else(|_| match get_key("AEAD_SECRET_KEY_PREVIOUS") {
+            Some(previous_key) => {
+                open(encrypted.as_slice(), Some(name.as_bytes()), &nonce, &previous_key)
+            }
+            None => Err(()),
+        })
+        .or(Err(anyhow::anyhow!("Failed to decrypt api_key {}", name)))?;
 
     String::from_utf8(decrypted).or(Err(anyhow::anyhow!(
         "Failed to convert decrypted bytes to utf8 for api_key {}",
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{error::Error, PaginatedResponse, ResponseResult, DEFAULT_PAGE_SIZE};
+use crate::{
+    db::{
+        self,
+        activity::{Activity, ActivityFilter, ActivityType, NewActivity},
+        DB,
+    },
+    logging,
+};
+
+/// Adds the activity to the project's feed in the background
+pub fn record_activity(db: Arc<DB>, activity: NewActivity) {
+    logging::spawn(async move {
+        if let Err(e) = db::activity::record_activity(&db.pool, &activity).await {
+            log::error!(
+                "Failed to record activity. project_id [{}], type [{:?}]: {:?}",
+                activity.project_id,
+                activity.activity_type,
+                e
+            );
+        }
+    });
+}
+
+fn parse_activity_type(activity_type: &str) -> Result<ActivityType, Error> {
+    serde_json::from_value(serde_json::json!(activity_type.trim())).map_err(|_| {
+        Error::invalid_request(Some(&format!("Unknown activity type: {}", activity_type)))
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetActivityParams {
+    #[serde(default)]
+    page_number: usize,
+    #[serde(default)]
+    page_size: Option<usize>,
+    /// Comma separated activity types, e.g. `EVALUATION_RUN,PROMPT_DEPLOYED`
+    #[serde(default)]
+    activity_types: Option<String>,
+    #[serde(default)]
+    actor_id: Option<Uuid>,
+    #[serde(default)]
+    resource_id: Option<Uuid>,
+}
+
+/// Chronological feed of what changed in the project, newest first
+#[get("activity")]
+async fn get_activity(
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    params: web::Query<GetActivityParams>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let params = params.into_inner();
+    let limit = params.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = limit * params.page_number;
+
+    let activity_types = params
+        .activity_types
+        .iter()
+        .flat_map(|types| types.split(','))
+        .map(parse_activity_type)
+        .collect::<Result<Vec<_>, _>>()?;
+    let filter = ActivityFilter {
+        activity_types,
+        actor_id: params.actor_id,
+        resource_id: params.resource_id,
+    };
+
+    let items =
+        db::activity::get_activity(&db.pool, &project_id, &filter, limit as i64, offset as i64)
+            .await?;
+    let total_count = db::activity::count_activity(&db.pool, &project_id, &filter).await? as u64;
+
+    Ok(HttpResponse::Ok().json(PaginatedResponse::<Activity> {
+        total_count,
+        items,
+        any_in_project: total_count > 0,
+    }))
+}
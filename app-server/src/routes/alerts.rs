@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    alerts,
+    db::{
+        self,
+        alerts::{
+            AlertChannelInfo, AlertChannelType, AlertComparison, AlertRule, AlertScoreSource,
+            NewAlertChannel, NewAlertRule,
+        },
+        user::User,
+        DB,
+    },
+    ids,
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+const DEFAULT_WINDOW_MINUTES: i32 = 60;
+const MAX_WINDOW_MINUTES: i32 = 7 * 24 * 60;
+const MAX_CHANNELS: usize = 10;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AlertRuleWithChannels {
+    #[serde(flatten)]
+    rule: AlertRule,
+    channels: Vec<AlertChannelInfo>,
+}
+
+#[get("alert-rules")]
+pub async fn get_alert_rules(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let rules = db::alerts::get_alert_rules(&db.pool, &project_id).await?;
+    let mut channels = HashMap::<Uuid, Vec<AlertChannelInfo>>::new();
+    for channel in db::alerts::get_alert_channel_infos(&db.pool, &project_id).await? {
+        channels.entry(channel.rule_id).or_default().push(channel);
+    }
+
+    let rules = rules
+        .into_iter()
+        .map(|rule| AlertRuleWithChannels {
+            channels: channels.remove(&rule.id).unwrap_or_default(),
+            rule,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+const DEFAULT_INCIDENTS_LIMIT: i64 = 20;
+const MAX_INCIDENTS_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+struct GetAlertIncidentsParams {
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// Most recent incidents of the rule with their root-cause summaries, which are null until they
+/// are written
+#[get("alert-rules/{rule_id}/incidents")]
+pub async fn get_alert_incidents(
+    path: web::Path<(Uuid, Uuid)>,
+    params: web::Query<GetAlertIncidentsParams>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, rule_id) = path.into_inner();
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_INCIDENTS_LIMIT)
+        .clamp(1, MAX_INCIDENTS_LIMIT);
+
+    let Some(incidents) =
+        db::alerts::get_alert_incidents(&db.pool, &project_id, &rule_id, limit).await?
+    else {
+        return Err(Error::not_found("Alert rule not found"));
+    };
+
+    Ok(HttpResponse::Ok().json(incidents))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AlertChannelRequest {
+    channel_type: AlertChannelType,
+    /// URL of the webhook or Slack incoming webhook, or an email address
+    target: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateAlertRuleRequest {
+    name: String,
+    source: AlertScoreSource,
+    score_name: String,
+    #[serde(default)]
+    group_id: Option<String>,
+    comparison: AlertComparison,
+    threshold: f64,
+    #[serde(default)]
+    window_minutes: Option<i32>,
+    channels: Vec<AlertChannelRequest>,
+}
+
+/// Validates the target and returns what is shown of it to users
+fn target_preview(channel: &AlertChannelRequest) -> Result<String, Error> {
+    let target = channel.target.trim();
+    match channel.channel_type {
+        AlertChannelType::WEBHOOK | AlertChannelType::SLACK => {
+            let url = url::Url::parse(target)
+                .map_err(|_| Error::invalid_request(Some("Invalid webhook URL")))?;
+            match (url.scheme(), url.host_str()) {
+                ("https", Some(host)) => Ok(host.to_string()),
+                _ => Err(Error::invalid_request(Some("Webhook URL must use https"))),
+            }
+        }
+        AlertChannelType::EMAIL => {
+            let valid = target
+                .split_once('@')
+                .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'));
+            if !valid {
+                return Err(Error::invalid_request(Some("Invalid email address")));
+            }
+            Ok(target.to_string())
+        }
+    }
+}
+
+/// Evaluation rules apply to the evaluations of `groupId`, or of all groups without it. Online
+/// rules average the span scores over `windowMinutes`.
+#[post("alert-rules")]
+pub async fn create_alert_rule(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateAlertRuleRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+
+    if req.name.trim().is_empty() || req.score_name.trim().is_empty() {
+        return Err(Error::invalid_request(Some(
+            "Name and score name must not be empty",
+        )));
+    }
+    if !req.threshold.is_finite() {
+        return Err(Error::invalid_request(Some("Threshold must be a number")));
+    }
+    if req.source == AlertScoreSource::ONLINE && req.group_id.is_some() {
+        return Err(Error::invalid_request(Some(
+            "Online rules don't apply to evaluation groups",
+        )));
+    }
+    let window_minutes = req.window_minutes.unwrap_or(DEFAULT_WINDOW_MINUTES);
+    if !(1..=MAX_WINDOW_MINUTES).contains(&window_minutes) {
+        return Err(Error::invalid_request(Some(
+            "Window must be between 1 minute and 7 days",
+        )));
+    }
+    if req.channels.is_empty() || req.channels.len() > MAX_CHANNELS {
+        return Err(Error::invalid_request(Some(
+            "Rule must have between 1 and 10 channels",
+        )));
+    }
+
+    let rule = NewAlertRule {
+        id: ids::new_id(),
+        name: req.name.trim().to_string(),
+        source: req.source,
+        score_name: req.score_name.trim().to_string(),
+        group_id: req.group_id,
+        comparison: req.comparison,
+        threshold: req.threshold,
+        window_minutes,
+    };
+    let channels = req
+        .channels
+        .iter()
+        .map(|channel| {
+            let target_preview = target_preview(channel)?;
+            let target = alerts::encode_target(
+                &rule.id,
+                channel.channel_type,
+                &channel.target.trim().to_string(),
+            );
+            Ok(NewAlertChannel {
+                channel_type: channel.channel_type,
+                target_preview,
+                target_nonce: target.nonce,
+                target_value: target.value,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let rule = db::alerts::create_alert_rule(&db.pool, &project_id, &rule, &channels).await?;
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[delete("alert-rules/{rule_id}")]
+pub async fn delete_alert_rule(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, rule_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "alert_rule", Some(&rule_id)).await?;
+
+    if !db::alerts::delete_alert_rule(&db.pool, &project_id, &rule_id).await? {
+        return Err(Error::not_found("Alert rule not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,396 @@
+use std::sync::Arc;
+
+use actix_web::{get, post, web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    analytics::{
+        canary,
+        custom_metrics::custom_metric_expressions,
+        heatmap::HourOfWeekHeatmap,
+        latency_sla,
+        natural_language::{self, UntranslatableQuestion},
+        AnalyticsStore,
+    },
+    cache::Cache,
+    ch::{
+        downsampling::MAX_CHART_POINTS,
+        modifiers::GroupByInterval,
+        query::{AnalyticsQuery, QueryContext, QueryResultRow},
+        spans::{
+            AgentActionStats, AgentFailureSelector, HeatmapMetric, LatencyLevel, LatencySpanFilter,
+        },
+    },
+    db::{self, DB},
+    language_model::LanguageModelRunner,
+    traces::{
+        evaluators::get_stored_env,
+        promoted_attributes::{attribute_slots, get_promoted_attributes},
+    },
+};
+
+use super::{error::Error, ResponseResult};
+
+const MAX_QUESTION_LENGTH: usize = 1000;
+
+/// Project's promoted attributes and custom metrics that queries are compiled against
+async fn query_context(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+) -> anyhow::Result<QueryContext> {
+    let custom_metrics = db::custom_metrics::get_custom_metrics(&db.pool, &project_id).await?;
+    let attribute_slots = attribute_slots(&get_promoted_attributes(db, cache, project_id).await?);
+
+    Ok(QueryContext {
+        attribute_slots,
+        custom_metrics: custom_metric_expressions(&custom_metrics),
+    })
+}
+
+/// Runs the aggregate query over the project's spans or evaluation scores
+#[post("analytics/query")]
+pub async fn run_query(
+    path: web::Path<Uuid>,
+    req: web::Json<AnalyticsQuery>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = req.into_inner();
+
+    let context = query_context(db.into_inner(), cache.into_inner(), project_id).await?;
+    if let Err(e) = query.to_sql(project_id, &context) {
+        return Err(Error::invalid_request(Some(&e.to_string())));
+    }
+    let results = analytics_store
+        .run_query(project_id, &query, &context)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(results))
+}
+
+#[derive(Deserialize)]
+struct AskQuestionRequest {
+    question: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AskQuestionResponse {
+    /// Query generated from the question, so that users can check what was answered
+    query: AnalyticsQuery,
+    sql: String,
+    results: Vec<QueryResultRow>,
+}
+
+/// Answers a natural language question about the project's spans and evaluation scores. The
+/// question is translated with the language model in `ANALYTICS_QUERY_MODEL`, using the
+/// project's provider api keys.
+#[post("analytics/ask")]
+pub async fn ask_question(
+    path: web::Path<Uuid>,
+    req: web::Json<AskQuestionRequest>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    language_model: web::Data<Arc<LanguageModelRunner>>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let question = req.into_inner().question;
+    if question.trim().is_empty() || question.len() > MAX_QUESTION_LENGTH {
+        return Err(Error::invalid_request(Some(&format!(
+            "Question must be between 1 and {} characters",
+            MAX_QUESTION_LENGTH
+        ))));
+    }
+    let db = db.into_inner();
+
+    let env = get_stored_env(db.clone(), project_id).await?;
+    let missing_env_vars = natural_language::missing_env_vars(&env);
+    if !missing_env_vars.is_empty() {
+        return Err(Error::invalid_request(Some(&format!(
+            "Add {} to the project's api keys to ask questions",
+            missing_env_vars.join(", ")
+        ))));
+    }
+    let cache = cache.into_inner();
+    let context = query_context(db.clone(), cache.clone(), project_id).await?;
+    let mut custom_metrics = context.custom_metrics.keys().cloned().collect::<Vec<_>>();
+    custom_metrics.sort();
+    let query = natural_language::translate_question(
+        language_model.as_ref().clone(),
+        db.clone(),
+        cache.clone(),
+        &env,
+        &custom_metrics,
+        &question,
+    )
+    .await
+    .map_err(|e| match e.downcast_ref::<UntranslatableQuestion>() {
+        Some(e) => Error::invalid_request(Some(&e.0)),
+        None => e.into(),
+    })?;
+
+    let sql = match query.to_sql(project_id, &context) {
+        Ok(sql) => sql,
+        Err(e) => {
+            return Err(Error::invalid_request(Some(&format!(
+                "The generated query is not supported: {}",
+                e
+            ))))
+        }
+    };
+    let results = analytics_store
+        .run_query(project_id, &query, &context)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(AskQuestionResponse {
+        query,
+        sql,
+        results,
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CanaryAnalysisQuery {
+    /// Release of the current production traces
+    baseline: String,
+    /// Release under test
+    canary: String,
+    start_time: DateTime<Utc>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+/// Compares error rate, latency, cost and online scores of the traces of two releases and
+/// recommends to promote or roll back the canary
+#[get("analytics/canary")]
+pub async fn get_canary_analysis(
+    path: web::Path<Uuid>,
+    query: web::Query<CanaryAnalysisQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    let end_time = query.end_time.unwrap_or(Utc::now());
+    if query.start_time >= end_time {
+        return Err(Error::invalid_request(Some(
+            "Start time must be before end time",
+        )));
+    }
+    if query.baseline == query.canary {
+        return Err(Error::invalid_request(Some(
+            "Baseline and canary must be different releases",
+        )));
+    }
+
+    let analysis = canary::analyze_canary(
+        &db,
+        &project_id,
+        &query.baseline,
+        &query.canary,
+        query.start_time,
+        end_time,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(analysis))
+}
+
+const DEFAULT_CURRENT_HOURS: i64 = 24;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PipelineLatencyQuery {
+    /// Length of the current window, which ends now
+    #[serde(default)]
+    current_hours: Option<i64>,
+}
+
+/// p50/p95/p99 latency of each pipeline in the current window, compared with the weekly
+/// baselines of the previous weeks
+#[get("analytics/pipeline-latency")]
+pub async fn get_pipeline_latency(
+    path: web::Path<Uuid>,
+    query: web::Query<PipelineLatencyQuery>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let current_hours = query.current_hours.unwrap_or(DEFAULT_CURRENT_HOURS);
+    if !(1..=24 * 7).contains(&current_hours) {
+        return Err(Error::invalid_request(Some(
+            "Current hours must be between 1 and 168",
+        )));
+    }
+
+    let current_end = Utc::now();
+    let report = latency_sla::get_pipeline_latency_report(
+        analytics_store.as_ref().clone(),
+        project_id,
+        current_end - Duration::hours(current_hours),
+        current_end,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HeatmapQuery {
+    metric: HeatmapMetric,
+    /// Only include the traces of this pipeline's runs
+    #[serde(default)]
+    pipeline: Option<String>,
+    start_time: DateTime<Utc>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+/// Traffic, error rate or cost by day of the week and hour of the day
+#[get("analytics/heatmap")]
+pub async fn get_hour_of_week_heatmap(
+    path: web::Path<Uuid>,
+    query: web::Query<HeatmapQuery>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    let end_time = query.end_time.unwrap_or(Utc::now());
+    if query.start_time >= end_time {
+        return Err(Error::invalid_request(Some(
+            "Start time must be before end time",
+        )));
+    }
+
+    let cells = analytics_store
+        .get_hour_of_week_heatmap(
+            project_id,
+            query.pipeline.as_deref(),
+            query.metric,
+            query.start_time,
+            end_time,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(HourOfWeekHeatmap::from_cells(cells)))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LatencyPercentilesQuery {
+    /// Span, which is the default, or trace latency
+    #[serde(default)]
+    level: LatencyLevel,
+    #[serde(flatten)]
+    filter: LatencySpanFilter,
+    start_time: DateTime<Utc>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    /// Coarsened if the time window would have too many buckets
+    #[serde(default)]
+    group_by_interval: GroupByInterval,
+}
+
+/// p50, p90, p95 and p99 of span or trace latency over time, optionally only of the spans with a
+/// name, path or model, or of the traces that contain such spans
+#[get("analytics/latency-percentiles")]
+pub async fn get_latency_percentiles(
+    path: web::Path<Uuid>,
+    query: web::Query<LatencyPercentilesQuery>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    let end_time = query.end_time.unwrap_or(Utc::now());
+    if query.start_time >= end_time {
+        return Err(Error::invalid_request(Some(
+            "Start time must be before end time",
+        )));
+    }
+    let group_by_interval = query.group_by_interval.coarsen_for_range(
+        (end_time - query.start_time).num_seconds(),
+        MAX_CHART_POINTS,
+    );
+
+    let points = analytics_store
+        .get_latency_percentiles_over_time(
+            project_id,
+            query.level,
+            &query.filter,
+            group_by_interval,
+            query.start_time,
+            end_time,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(points))
+}
+
+const DEFAULT_FAILURE_SELECTOR_LIMIT: u64 = 20;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentActionsQuery {
+    start_time: DateTime<Utc>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    /// Number of failure selectors to return
+    #[serde(default)]
+    failure_selector_limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentActionsResponse {
+    actions: Vec<AgentActionStats>,
+    failure_selectors: Vec<AgentFailureSelector>,
+}
+
+/// Reliability of agent steps: success rate and average retries per action type, and the
+/// selectors with the most failed actions
+#[get("analytics/agent-actions")]
+pub async fn get_agent_actions(
+    path: web::Path<Uuid>,
+    query: web::Query<AgentActionsQuery>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    let end_time = query.end_time.unwrap_or(Utc::now());
+    if query.start_time >= end_time {
+        return Err(Error::invalid_request(Some(
+            "Start time must be before end time",
+        )));
+    }
+    let failure_selector_limit = query
+        .failure_selector_limit
+        .unwrap_or(DEFAULT_FAILURE_SELECTOR_LIMIT);
+    if !(1..=100).contains(&failure_selector_limit) {
+        return Err(Error::invalid_request(Some(
+            "Failure selector limit must be between 1 and 100",
+        )));
+    }
+
+    let actions = analytics_store
+        .get_agent_action_stats(project_id, query.start_time, end_time)
+        .await?;
+    let failure_selectors = analytics_store
+        .get_agent_failure_selectors(
+            project_id,
+            query.start_time,
+            end_time,
+            failure_selector_limit,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(AgentActionsResponse {
+        actions,
+        failure_selectors,
+    }))
+}
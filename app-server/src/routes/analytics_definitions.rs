@@ -0,0 +1,121 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    analytics::definitions::{self, AnalyticsDefinitions, AnalyticsDefinitionsPlan},
+    cache::Cache,
+    db::DB,
+    language_model::providers::utils::get_required_env_vars_for_model,
+    traces::{evaluators::get_stored_env, online_evaluations::invalidate_online_evaluation_rules},
+};
+
+use super::{error::Error, ResponseResult};
+
+/// Custom metrics, judge evaluators and online evaluation rules of the project as a file that
+/// can be versioned in git and applied back with `analytics-definitions/apply`
+#[get("analytics-definitions/export")]
+pub async fn export_analytics_definitions(
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let definitions = definitions::get_project_definitions(&db.pool, &project_id).await?;
+    // pretty-printed, so that changes of the file read well in diffs
+    let mut body = serde_json::to_string_pretty(&definitions).map_err(anyhow::Error::from)?;
+    body.push('\n');
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((
+            "Content-Disposition",
+            "attachment; filename=\"analytics-definitions.json\"",
+        ))
+        .body(body))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApplyAnalyticsDefinitionsQuery {
+    /// Returns the changes without making them
+    #[serde(default)]
+    dry_run: bool,
+    /// Also deletes the definitions that are not in the file
+    #[serde(default)]
+    prune: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApplyAnalyticsDefinitionsResponse {
+    #[serde(flatten)]
+    plan: AnalyticsDefinitionsPlan,
+    applied: bool,
+}
+
+/// Creates and updates the project's definitions by name to match the file. All definitions are
+/// checked before any is changed, and applying the same file again changes nothing.
+#[post("analytics-definitions/apply")]
+pub async fn apply_analytics_definitions(
+    path: web::Path<Uuid>,
+    query: web::Query<ApplyAnalyticsDefinitionsQuery>,
+    req: web::Json<AnalyticsDefinitions>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    let desired = req
+        .into_inner()
+        .normalize()
+        .map_err(|e| Error::invalid_request(Some(&e.to_string())))?;
+
+    let current = definitions::get_project_definitions(&db.pool, &project_id).await?;
+    let plan = AnalyticsDefinitionsPlan::between(&current, &desired, query.prune);
+
+    let judges_after = plan.judges_after(&current, &desired);
+    let referenced_judges = desired.referenced_judges();
+    let unknown = referenced_judges
+        .iter()
+        .filter(|name| !judges_after.contains(name))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return Err(Error::invalid_request(Some(&format!(
+            "Judge evaluators not found: {}",
+            unknown.join(", ")
+        ))));
+    }
+    if !referenced_judges.is_empty() {
+        // the file's version of a judge replaces the current one
+        let models = referenced_judges.iter().filter_map(|name| {
+            desired
+                .judge_evaluators
+                .iter()
+                .chain(current.judge_evaluators.iter())
+                .find(|judge| &judge.name == name)
+                .map(|judge| judge.model.as_str())
+        });
+        let env = get_stored_env(db.clone().into_inner(), project_id).await?;
+        let mut missing_env_vars = models
+            .flat_map(get_required_env_vars_for_model)
+            .filter(|name| !env.contains_key(name))
+            .collect::<Vec<_>>();
+        missing_env_vars.sort();
+        missing_env_vars.dedup();
+        if !missing_env_vars.is_empty() {
+            return Err(Error::invalid_request(Some(&format!(
+                "Add {} to the project's api keys to run the judges",
+                missing_env_vars.join(", ")
+            ))));
+        }
+    }
+
+    let applied = !query.dry_run && !plan.is_empty();
+    if applied {
+        definitions::apply_definitions(&db.pool, &project_id, &desired, &plan).await?;
+        invalidate_online_evaluation_rules(cache.into_inner(), project_id).await;
+    }
+
+    Ok(HttpResponse::Ok().json(ApplyAnalyticsDefinitionsResponse { plan, applied }))
+}
@@ -4,12 +4,14 @@ use log::info;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    auth::sso::{is_verified_by_identity_provider, IdentityToken},
     db::{
+        self,
         user::{get_api_key_for_user_from_email, write_api_key, write_user, ApiKey, User},
         utils::generate_random_key,
         DB,
     },
-    routes::ResponseResult,
+    routes::{error::Error, ResponseResult},
 };
 
 fn validate_user_email(email: &str) -> Result<()> {
@@ -21,10 +23,15 @@ fn validate_user_email(email: &str) -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct SignInParams {
     name: String,
     email: String,
+    /// Token of the identity provider the user signed in with, none for the `email` provider of
+    /// local deployments
+    #[serde(default)]
+    identity_token: Option<IdentityToken>,
 }
 
 #[derive(Serialize)]
@@ -40,6 +47,16 @@ async fn signin(params: web::Json<SignInParams>, db: web::Data<DB>) -> ResponseR
     let email = params.email;
     let name = params.name;
 
+    if db::organizations::is_sso_required_for_email(&db.pool, &email).await? {
+        let verified = match &params.identity_token {
+            Some(token) => is_verified_by_identity_provider(token, &email).await,
+            None => false,
+        };
+        if !verified {
+            return Err(Error::Forbidden);
+        }
+    }
+
     if let Some(api_key) = get_api_key_for_user_from_email(&db.pool, &email).await {
         let res = SignInResponse {
             api_key,
@@ -0,0 +1,194 @@
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use regex::Regex;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::db::{self, comments::CommentTargetType, user::User, DB};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+const MAX_COMMENT_LENGTH: usize = 10_000;
+
+lazy_static::lazy_static! {
+    /// Users are mentioned by email, e.g. `@alice@example.com`
+    static ref MENTION_REGEX: Regex =
+        Regex::new(r"(?:^|\s)@([A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,})").unwrap();
+}
+
+fn parse_mentions(content: &str) -> Vec<String> {
+    let mut emails = MENTION_REGEX
+        .captures_iter(content)
+        .map(|captures| captures[1].to_lowercase())
+        .collect::<Vec<_>>();
+    emails.sort();
+    emails.dedup();
+    emails
+}
+
+fn validate_content(content: &str) -> Result<(), Error> {
+    if content.trim().is_empty() {
+        return Err(Error::invalid_request(Some("Comment must not be empty")));
+    }
+    if content.len() > MAX_COMMENT_LENGTH {
+        return Err(Error::invalid_request(Some(&format!(
+            "Comment must be at most {} characters",
+            MAX_COMMENT_LENGTH
+        ))));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetCommentsQuery {
+    target_type: CommentTargetType,
+    target_id: Uuid,
+}
+
+/// All comments on the target, oldest first. Threads are built from `parentId`.
+#[get("comments")]
+pub async fn get_comments(
+    path: web::Path<Uuid>,
+    query: web::Query<GetCommentsQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    let comments =
+        db::comments::get_comments(&db.pool, &project_id, query.target_type, &query.target_id)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(comments))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateCommentRequest {
+    target_type: CommentTargetType,
+    target_id: Uuid,
+    #[serde(default)]
+    parent_id: Option<Uuid>,
+    content: String,
+}
+
+#[post("comments")]
+pub async fn create_comment(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateCommentRequest>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    validate_content(&req.content)?;
+
+    if !db::comments::target_exists(&db.pool, &project_id, req.target_type, &req.target_id).await? {
+        return Err(Error::not_found("Comment target not found"));
+    }
+
+    let Some(comment) = db::comments::create_comment(
+        &db.pool,
+        &project_id,
+        &user.id,
+        req.target_type,
+        &req.target_id,
+        req.parent_id,
+        &req.content,
+    )
+    .await?
+    else {
+        return Err(Error::invalid_request(Some(
+            "Parent comment must be on the same target",
+        )));
+    };
+
+    let mentions = parse_mentions(&comment.content);
+    if !mentions.is_empty() {
+        db::comments::add_mentions(&db.pool, &project_id, &comment.id, &user.id, &mentions).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(comment))
+}
+
+#[derive(Deserialize)]
+struct UpdateCommentRequest {
+    content: String,
+}
+
+/// Users mentioned in the new content are notified, earlier mentions are kept
+#[put("comments/{comment_id}")]
+pub async fn update_comment(
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<UpdateCommentRequest>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, comment_id) = path.into_inner();
+    let content = req.into_inner().content;
+    validate_content(&content)?;
+
+    let Some(comment) =
+        db::comments::update_comment(&db.pool, &project_id, &comment_id, &user.id, &content)
+            .await?
+    else {
+        return Err(Error::not_found("Comment not found"));
+    };
+
+    let mentions = parse_mentions(&comment.content);
+    if !mentions.is_empty() {
+        db::comments::add_mentions(&db.pool, &project_id, &comment.id, &user.id, &mentions).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(comment))
+}
+
+#[delete("comments/{comment_id}")]
+pub async fn delete_comment(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, comment_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "comment", Some(&comment_id)).await?;
+
+    if !db::comments::delete_comment(&db.pool, &project_id, &comment_id, &user.id).await? {
+        return Err(Error::not_found("Comment not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Unread mentions of the user across all projects
+#[get("")]
+pub async fn get_unread_mentions(user: User, db: web::Data<DB>) -> ResponseResult {
+    let mentions = db::comments::get_unread_mentions(&db.pool, &user.id).await?;
+
+    Ok(HttpResponse::Ok().json(mentions))
+}
+
+#[post("{mention_id}/read")]
+pub async fn mark_mention_read(
+    path: web::Path<Uuid>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let mention_id = path.into_inner();
+    db::comments::mark_mention_read(&db.pool, &user.id, &mention_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mentions() {
+        let content = "@bob@example.com can you check this? cc @Alice@Example.com, not a@b.com \
+            and @bob@example.com again";
+        assert_eq!(
+            parse_mentions(content),
+            vec!["alice@example.com", "bob@example.com"]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{error::Error, field_visibility::require_owner, ResponseResult};
+use crate::{
+    db::{self, data_access_log::AccessedResourceType, user::User, DB},
+    logging,
+};
+
+/// Writes the user's access to the resources to the data access log in the background
+pub(super) fn record_data_access(
+    db: Arc<DB>,
+    project_id: Uuid,
+    user_id: Uuid,
+    resource_type: AccessedResourceType,
+    resource_ids: Vec<Uuid>,
+) {
+    if resource_ids.is_empty() {
+        return;
+    }
+    logging::spawn(async move {
+        if let Err(e) = db::data_access_log::record_data_access(
+            &db.pool,
+            &project_id,
+            &user_id,
+            resource_type,
+            &resource_ids,
+        )
+        .await
+        {
+            log::error!(
+                "Failed to record data access. project_id [{}], user_id [{}]: {:?}",
+                project_id,
+                user_id,
+                e
+            );
+        }
+    });
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessReportParams {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    #[serde(default)]
+    resource_type: Option<AccessedResourceType>,
+    #[serde(default)]
+    resource_id: Option<Uuid>,
+    /// Only accesses of this user
+    #[serde(default)]
+    user_id: Option<Uuid>,
+    #[serde(default)]
+    format: ReportFormat,
+}
+
+/// Who accessed which traces, spans and datapoints of the project over a period, as evidence
+/// for compliance audits
+#[get("compliance/access-report")]
+async fn get_access_report(
+    path: web::Path<Uuid>,
+    user: User,
+    db: web::Data<DB>,
+    params: web::Query<AccessReportParams>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let params = params.into_inner();
+    require_owner(&db, &user, &project_id).await?;
+    if params.start_time >= params.end_time {
+        return Err(Error::invalid_request(Some(
+            "startTime must be before endTime",
+        )));
+    }
+
+    let rows = db::data_access_log::get_access_report(
+        &db.pool,
+        &project_id,
+        params.start_time,
+        params.end_time,
+        params.resource_type,
+        params.resource_id,
+        params.user_id,
+    )
+    .await?;
+
+    if params.format == ReportFormat::Json {
+        return Ok(HttpResponse::Ok().json(rows));
+    }
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in rows {
+        writer.serialize(row).map_err(anyhow::Error::from)?;
+    }
+    let body = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"access-report-{}-{}.csv\"",
+                params.start_time.format("%Y%m%d"),
+                params.end_time.format("%Y%m%d")
+            ),
+        ))
+        .body(body))
+}
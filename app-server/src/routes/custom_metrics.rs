@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use actix_web::{delete, get, post, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    analytics::{custom_metrics::MetricExpression, AnalyticsStore},
+    db::{self, user::User, DB},
+};
+
+use super::{
+    error::Error, evaluations::DEFAULT_BUCKET_COUNT, legal_holds::ensure_no_legal_hold,
+    ResponseResult,
+};
+
+const MAX_NAME_LENGTH: usize = 64;
+
+#[get("custom-metrics")]
+pub async fn get_custom_metrics(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let metrics = db::custom_metrics::get_custom_metrics(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(metrics))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateCustomMetricRequest {
+    name: String,
+    /// Arithmetic over span fields, e.g. `completion_tokens / duration_seconds`
+    expression: String,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+/// Registers a metric computed from span fields, that analytics queries can use by name
+#[post("custom-metrics")]
+pub async fn create_custom_metric(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateCustomMetricRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    let name = req.name.trim();
+    if name.is_empty()
+        || name.len() > MAX_NAME_LENGTH
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(Error::invalid_request(Some(&format!(
+            "Name must be between 1 and {} letters, digits, underscores or dashes",
+            MAX_NAME_LENGTH
+        ))));
+    }
+    let expression = req.expression.trim();
+    if let Err(e) = MetricExpression::parse(expression) {
+        return Err(Error::invalid_request(Some(&format!(
+            "Invalid expression: {}",
+            e
+        ))));
+    }
+
+    let Some(metric) = db::custom_metrics::create_custom_metric(
+        &db.pool,
+        &project_id,
+        name,
+        expression,
+        req.description.as_deref(),
+    )
+    .await?
+    else {
+        return Err(Error::invalid_request(Some(&format!(
+            "Custom metric {} already exists",
+            name
+        ))));
+    };
+
+    Ok(HttpResponse::Ok().json(metric))
+}
+
+#[delete("custom-metrics/{metric_id}")]
+pub async fn delete_custom_metric(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, metric_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "custom_metric", Some(&metric_id)).await?;
+    let Some(metric) =
+        db::custom_metrics::delete_custom_metric(&db.pool, &project_id, &metric_id).await?
+    else {
+        return Err(Error::not_found("Custom metric not found"));
+    };
+
+    Ok(HttpResponse::Ok().json(metric))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CustomMetricDistributionQuery {
+    start_time: DateTime<Utc>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CustomMetricBucket {
+    lower_bound: f64,
+    upper_bound: f64,
+    height: u64,
+}
+
+/// Distribution of the metric over the spans that started in the time window
+#[get("custom-metrics/{metric_id}/distribution")]
+pub async fn get_custom_metric_distribution(
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<CustomMetricDistributionQuery>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let (project_id, metric_id) = path.into_inner();
+    let query = query.into_inner();
+    let end_time = query.end_time.unwrap_or(Utc::now());
+    if query.start_time >= end_time {
+        return Err(Error::invalid_request(Some(
+            "Start time must be before end time",
+        )));
+    }
+
+    let metrics = db::custom_metrics::get_custom_metrics(&db.pool, &project_id).await?;
+    let Some(metric) = metrics.into_iter().find(|metric| metric.id == metric_id) else {
+        return Err(Error::not_found("Custom metric not found"));
+    };
+    let expression = MetricExpression::parse(&metric.expression)?;
+
+    let buckets = analytics_store
+        .get_custom_metric_buckets(
+            project_id,
+            &expression,
+            query.start_time,
+            end_time,
+            DEFAULT_BUCKET_COUNT,
+        )
+        .await?
+        .into_iter()
+        .map(|bucket| CustomMetricBucket {
+            lower_bound: bucket.lower_bound,
+            upper_bound: bucket.upper_bound,
+            height: bucket.height,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(buckets))
+}
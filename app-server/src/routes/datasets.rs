@@ -2,18 +2,67 @@ use std::{collections::HashMap, sync::Arc};
 
 use actix_multipart::Multipart;
 use actix_web::{delete, get, post, web, HttpResponse};
-use serde::Deserialize;
-use serde_json::Value;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use uuid::Uuid;
 
 use crate::{
-    datasets::{datapoints, utils::read_multipart_file, Dataset},
-    db::{self, datapoints::DatapointView, datasets, DB},
-    routes::{PaginatedGetQueryParams, PaginatedResponse, ResponseResult},
+    datasets::{
+        datapoints,
+        huggingface::{self, HuggingFaceClient},
+        import::{DatapointImporter, ImportFormat, ImportMapping, ImportRowError},
+        utils::read_multipart_file,
+        Dataset,
+    },
+    db::{
+        self,
+        activity::{ActivityType, NewActivity},
+        data_access_log::AccessedResourceType,
+        datapoints::DatapointView,
+        dataset_imports::NewDatasetImport,
+        datasets,
+        user::User,
+        DB,
+    },
+    pipeline::triggers,
+    routes::{
+        activity::record_activity, compliance::record_data_access, error::Error,
+        legal_holds::ensure_no_legal_hold, PaginatedGetQueryParams, PaginatedResponse,
+        ResponseResult,
+    },
     semantic_search::SemanticSearch,
 };
 
 const DEFAULT_PAGE_SIZE: usize = 50;
+/// Running imports without progress for this long are resumable
+const IMPORT_STALLED_AFTER_MINUTES: i32 = 10;
+
+/// Records the change in the activity feed. Changes of the datapoints also queue runs of the
+/// pipeline triggers watching the dataset.
+fn record_dataset_modified(
+    db: Arc<DB>,
+    project_id: Uuid,
+    user: &User,
+    dataset_id: Uuid,
+    action: &str,
+    summary: String,
+) {
+    if !matches!(action, "create" | "rename" | "delete") {
+        triggers::on_dataset_updated(db.clone(), project_id, dataset_id, action);
+    }
+    record_activity(
+        db,
+        NewActivity {
+            project_id,
+            activity_type: ActivityType::DATASET_MODIFIED,
+            actor_id: Some(user.id),
+            resource_id: Some(dataset_id),
+            summary,
+            details: json!({ "action": action }),
+        },
+    );
+}
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -24,6 +73,7 @@ struct CreateDatasetRequest {
 #[post("datasets")]
 async fn create_dataset(
     db: web::Data<DB>,
+    user: User,
     project_id: web::Path<Uuid>,
     req: web::Json<CreateDatasetRequest>,
 ) -> ResponseResult {
@@ -31,6 +81,14 @@ async fn create_dataset(
     let req = req.into_inner();
 
     let dataset = datasets::create_dataset(&db.pool, &req.name, project_id).await?;
+    record_dataset_modified(
+        db.into_inner(),
+        project_id,
+        &user,
+        dataset.id,
+        "create",
+        format!("Created dataset {}", dataset.name),
+    );
 
     Ok(HttpResponse::Ok().json(dataset))
 }
@@ -76,6 +134,7 @@ struct UpdateDatasetRequest {
 #[post("datasets/{dataset_id}")]
 async fn rename_dataset(
     db: web::Data<DB>,
+    user: User,
     path: web::Path<(Uuid, Uuid)>,
     new_name: web::Json<UpdateDatasetRequest>,
 ) -> ResponseResult {
@@ -84,6 +143,14 @@ async fn rename_dataset(
 
     let updated_dataset =
         datasets::rename_dataset(&db.pool, dataset_id, project_id, &new_name).await?;
+    record_dataset_modified(
+        db.into_inner(),
+        project_id,
+        &user,
+        dataset_id,
+        "rename",
+        format!("Renamed dataset to {}", new_name),
+    );
 
     Ok(HttpResponse::Ok().json(updated_dataset))
 }
@@ -91,12 +158,22 @@ async fn rename_dataset(
 #[delete("datasets/{dataset_id}")]
 async fn delete_dataset(
     db: web::Data<DB>,
+    user: User,
     path: web::Path<(Uuid, Uuid)>,
     semantic_search: web::Data<Arc<dyn SemanticSearch>>,
 ) -> ResponseResult {
     let (project_id, dataset_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "dataset", Some(&dataset_id)).await?;
 
     datasets::delete_dataset(&db.pool, dataset_id).await?;
+    record_dataset_modified(
+        db.clone().into_inner(),
+        project_id,
+        &user,
+        dataset_id,
+        "delete",
+        "Deleted a dataset".to_string(),
+    );
 
     semantic_search
         .delete_embeddings(
@@ -117,6 +194,7 @@ async fn delete_dataset(
 #[post("datasets/{dataset_id}/file-upload")]
 async fn upload_datapoint_file(
     payload: Multipart,
+    user: User,
     path: web::Path<(Uuid, Uuid)>,
     db: web::Data<DB>,
     semantic_search: web::Data<Arc<dyn SemanticSearch>>,
@@ -140,6 +218,19 @@ async fn upload_datapoint_file(
 
     let datapoints =
         datapoints::insert_datapoints_from_file(&bytes, &filename, dataset_id, db.clone()).await?;
+    record_dataset_modified(
+        db.clone(),
+        project_id,
+        &user,
+        dataset_id,
+        "upload",
+        format!(
+            "Uploaded {} datapoints from {} to dataset {}",
+            datapoints.len(),
+            filename,
+            dataset.name
+        ),
+    );
 
     if indexed_on.is_some() {
         dataset
@@ -155,6 +246,226 @@ async fn upload_datapoint_file(
     Ok(HttpResponse::Ok().json(datapoints))
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportDatapointsResponse {
+    imported_count: usize,
+    failed_count: usize,
+    /// Errors of the first failed rows
+    errors: Vec<ImportRowError>,
+}
+
+/// Imports the rows of a CSV or JSONL file as datapoints. The `mapping` field, a JSON
+/// `{"data": [...], "target": [...], "metadata": [...]}` of column names, must come before the
+/// `file` field, which is parsed as it is received. Failed rows are reported and skipped.
+#[post("datasets/{dataset_id}/import")]
+async fn import_datapoints(
+    mut payload: Multipart,
+    user: User,
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    semantic_search: web::Data<Arc<dyn SemanticSearch>>,
+) -> ResponseResult {
+    let (project_id, dataset_id) = path.into_inner();
+    let db = db.into_inner();
+    let dataset = db::datasets::get_dataset(&db.pool, project_id, dataset_id).await?;
+
+    let mut mapping = None;
+    let mut importer = None;
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let content = field.content_disposition();
+        let name = content.get_name().unwrap_or_default().to_string();
+        let filename = content.get_filename().map(str::to_string);
+        match name.as_str() {
+            "mapping" => {
+                let mut value = Vec::new();
+                while let Some(chunk) = field.next().await {
+                    value.extend_from_slice(&chunk?);
+                }
+                let value = serde_json::from_slice::<ImportMapping>(&value)
+                    .map_err(|_| Error::invalid_request(Some("Invalid mapping")))?;
+                value
+                    .validate()
+                    .map_err(|e| Error::invalid_request(Some(&e.to_string())))?;
+                mapping = Some(value);
+            }
+            "file" => {
+                let format = filename
+                    .as_deref()
+                    .and_then(ImportFormat::from_filename)
+                    .ok_or_else(|| {
+                        Error::invalid_request(Some("File must be a CSV or JSONL file"))
+                    })?;
+                let mapping = mapping.take().ok_or_else(|| {
+                    Error::invalid_request(Some("Mapping must be sent before the file"))
+                })?;
+                let mut file_importer = DatapointImporter::new(format, mapping, dataset_id);
+                while let Some(chunk) = field.next().await {
+                    file_importer
+                        .push(&chunk?)
+                        .map_err(|e| Error::invalid_request(Some(&e.to_string())))?;
+                }
+                file_importer
+                    .finish()
+                    .map_err(|e| Error::invalid_request(Some(&e.to_string())))?;
+                importer = Some(file_importer);
+            }
+            _ => {}
+        }
+    }
+    let importer = importer.ok_or_else(|| Error::invalid_request(Some("File is missing")))?;
+
+    let datapoints =
+        db::datapoints::import_datapoints(&db.pool, &dataset_id, importer.datapoints).await?;
+    if !datapoints.is_empty() {
+        record_dataset_modified(
+            db.clone(),
+            project_id,
+            &user,
+            dataset_id,
+            "import",
+            format!(
+                "Imported {} datapoints to dataset {}",
+                datapoints.len(),
+                dataset.name
+            ),
+        );
+    }
+
+    let imported_count = datapoints.len();
+    if dataset.indexed_on.is_some() {
+        dataset
+            .index_new_points(
+                datapoints,
+                semantic_search.as_ref().clone(),
+                project_id.to_string(),
+                dataset.indexed_on.clone(),
+            )
+            .await?;
+    }
+
+    Ok(HttpResponse::Ok().json(ImportDatapointsResponse {
+        imported_count,
+        failed_count: importer.failed_count,
+        errors: importer.errors,
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HuggingFaceImportRequest {
+    /// e.g. `openai/gsm8k`
+    repo_id: String,
+    /// The first config with the split if not set
+    #[serde(default)]
+    config: Option<String>,
+    split: String,
+    mapping: ImportMapping,
+}
+
+/// Starts importing a split of a Hugging Face Hub dataset in the background. The progress is
+/// reported by `datasets/{dataset_id}/imports`.
+#[post("datasets/{dataset_id}/imports/huggingface")]
+async fn import_huggingface_dataset(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+    req: web::Json<HuggingFaceImportRequest>,
+    semantic_search: web::Data<Arc<dyn SemanticSearch>>,
+) -> ResponseResult {
+    let (project_id, dataset_id) = path.into_inner();
+    let req = req.into_inner();
+    let db = db.into_inner();
+    req.mapping
+        .validate()
+        .map_err(|e| Error::invalid_request(Some(&e.to_string())))?;
+    let repo_id = req.repo_id.trim();
+    if repo_id.is_empty() || req.split.trim().is_empty() {
+        return Err(Error::invalid_request(Some(
+            "Repo id and split must not be empty",
+        )));
+    }
+    let dataset = db::datasets::get_dataset(&db.pool, project_id, dataset_id).await?;
+
+    let client = HuggingFaceClient::for_project(db.clone(), project_id).await?;
+    let config = client
+        .resolve_config(repo_id, req.config.as_deref(), req.split.trim())
+        .await
+        .map_err(|e| Error::invalid_request(Some(&e.to_string())))?;
+    let import = db::dataset_imports::create_dataset_import(
+        &db.pool,
+        &project_id,
+        &dataset_id,
+        &NewDatasetImport {
+            source: "huggingface".to_string(),
+            repo_id: repo_id.to_string(),
+            config,
+            split: req.split.trim().to_string(),
+            mapping: serde_json::to_value(&req.mapping).map_err(anyhow::Error::from)?,
+        },
+    )
+    .await?;
+    record_dataset_modified(
+        db.clone(),
+        project_id,
+        &user,
+        dataset_id,
+        "import",
+        format!(
+            "Started importing {} into dataset {}",
+            import.repo_id, dataset.name
+        ),
+    );
+
+    huggingface::spawn_import(db, semantic_search.as_ref().clone(), import.clone());
+
+    Ok(HttpResponse::Ok().json(import))
+}
+
+#[get("datasets/{dataset_id}/imports")]
+async fn get_dataset_imports(path: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> ResponseResult {
+    let (project_id, dataset_id) = path.into_inner();
+    let imports =
+        db::dataset_imports::get_dataset_imports(&db.pool, &project_id, &dataset_id).await?;
+
+    Ok(HttpResponse::Ok().json(imports))
+}
+
+/// Resumes a failed import, or one that stalled, from the first row that wasn't imported
+#[post("datasets/{dataset_id}/imports/{import_id}/resume")]
+async fn resume_dataset_import(
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    db: web::Data<DB>,
+    semantic_search: web::Data<Arc<dyn SemanticSearch>>,
+) -> ResponseResult {
+    let (project_id, _dataset_id, import_id) = path.into_inner();
+    let db = db.into_inner();
+
+    if db::dataset_imports::get_dataset_import(&db.pool, &project_id, &import_id)
+        .await?
+        .is_none()
+    {
+        return Err(Error::not_found("Dataset import not found"));
+    }
+    let Some(import) = db::dataset_imports::restart_dataset_import(
+        &db.pool,
+        &project_id,
+        &import_id,
+        IMPORT_STALLED_AFTER_MINUTES,
+    )
+    .await?
+    else {
+        return Err(Error::invalid_request(Some(
+            "Only failed or stalled imports can be resumed",
+        )));
+    };
+
+    huggingface::spawn_import(db, semantic_search.as_ref().clone(), import);
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
 #[derive(Deserialize)]
 struct CreateDatapointsRequest {
     datapoints: Vec<serde_json::Value>,
@@ -163,6 +474,7 @@ struct CreateDatapointsRequest {
 #[post("datasets/{dataset_id}/datapoints")]
 async fn create_datapoints(
     path: web::Path<(Uuid, Uuid)>,
+    user: User,
     db: web::Data<DB>,
     req: web::Json<CreateDatapointsRequest>,
     semantic_search: web::Data<Arc<dyn SemanticSearch>>,
@@ -174,6 +486,18 @@ async fn create_datapoints(
 
     let datapoints =
         db::datapoints::insert_raw_data(&db.pool, &dataset_id, &input_datapoints).await?;
+    record_dataset_modified(
+        db.clone().into_inner(),
+        project_id,
+        &user,
+        dataset_id,
+        "add_datapoints",
+        format!(
+            "Added {} datapoints to dataset {}",
+            datapoints.len(),
+            dataset.name
+        ),
+    );
 
     if dataset.indexed_on.is_some() {
         dataset
@@ -199,6 +523,7 @@ struct UpdateDatapointRequest {
 #[post("datasets/{dataset_id}/datapoints/{datapoint_id}")]
 async fn update_datapoint_data(
     path: web::Path<(Uuid, Uuid, Uuid)>,
+    user: User,
     db: web::Data<DB>,
     req: web::Json<UpdateDatapointRequest>,
     semantic_search: web::Data<Arc<dyn SemanticSearch>>,
@@ -208,6 +533,7 @@ async fn update_datapoint_data(
 
     let updated_datapoint = db::datapoints::update_datapoint(
         &db.pool,
+        &dataset_id,
         &datapoint_id,
         &req.data,
         &req.target,
@@ -216,6 +542,14 @@ async fn update_datapoint_data(
     .await?;
 
     let dataset = db::datasets::get_dataset(&db.pool, project_id, dataset_id).await?;
+    record_dataset_modified(
+        db.clone().into_inner(),
+        project_id,
+        &user,
+        dataset_id,
+        "update_datapoint",
+        format!("Updated a datapoint of dataset {}", dataset.name),
+    );
     if dataset.indexed_on.is_some() {
         dataset
             .index_new_points(
@@ -238,14 +572,24 @@ pub struct DeleteDatapointRequest {
 #[delete("datasets/{dataset_id}/datapoints")]
 async fn delete_datapoints(
     path: web::Path<(Uuid, Uuid)>,
+    user: User,
     db: web::Data<DB>,
     req: web::Json<DeleteDatapointRequest>,
     semantic_search: web::Data<Arc<dyn SemanticSearch>>,
 ) -> ResponseResult {
-    let (project_id, _dataset_id) = path.into_inner();
+    let (project_id, dataset_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "datapoints", Some(&dataset_id)).await?;
     let datapoint_ids = req.into_inner().ids;
 
-    db::datapoints::delete_datapoints(&db.pool, &datapoint_ids).await?;
+    db::datapoints::delete_datapoints(&db.pool, &dataset_id, &datapoint_ids).await?;
+    record_dataset_modified(
+        db.clone().into_inner(),
+        project_id,
+        &user,
+        dataset_id,
+        "delete_datapoints",
+        format!("Deleted {} datapoints", datapoint_ids.len()),
+    );
 
     semantic_search
         .delete_embeddings(
@@ -263,12 +607,22 @@ async fn delete_datapoints(
 #[delete("datasets/{dataset_id}/datapoints/all")]
 async fn delete_all_datapoints(
     path: web::Path<(Uuid, Uuid)>,
+    user: User,
     db: web::Data<DB>,
     semantic_search: web::Data<Arc<dyn SemanticSearch>>,
 ) -> ResponseResult {
     let (project_id, dataset_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "datapoints", Some(&dataset_id)).await?;
 
     let deleted_dp_ids = db::datapoints::delete_all_datapoints(&db.pool, &dataset_id).await?;
+    record_dataset_modified(
+        db.clone().into_inner(),
+        project_id,
+        &user,
+        dataset_id,
+        "delete_all_datapoints",
+        format!("Deleted all {} datapoints", deleted_dp_ids.len()),
+    );
 
     semantic_search
         .delete_embeddings(
@@ -286,14 +640,22 @@ async fn delete_all_datapoints(
 #[get("datasets/{dataset_id}/datapoints")]
 async fn get_datapoints(
     db: web::Data<DB>,
+    user: User,
     path: web::Path<(Uuid, Uuid)>,
     query_params: web::Query<PaginatedGetQueryParams>,
 ) -> ResponseResult {
-    let (_project_id, dataset_id) = path.into_inner();
+    let (project_id, dataset_id) = path.into_inner();
     let limit = query_params.page_size.unwrap_or(DEFAULT_PAGE_SIZE) as i64;
     let offset = limit * (query_params.page_number) as i64;
     let datapoints = db::datapoints::get_datapoints(&db.pool, dataset_id, limit, offset).await?;
     let total_entries = db::datapoints::count_datapoints(&db.pool, dataset_id).await?;
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::DATAPOINT,
+        datapoints.iter().map(|datapoint| datapoint.id).collect(),
+    );
 
     let response = PaginatedResponse::<DatapointView> {
         items: datapoints,
@@ -304,6 +666,95 @@ async fn get_datapoints(
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[get("datasets/{dataset_id}/versions")]
+async fn get_dataset_versions(db: web::Data<DB>, path: web::Path<(Uuid, Uuid)>) -> ResponseResult {
+    let (project_id, dataset_id) = path.into_inner();
+    let dataset = db::datasets::get_dataset(&db.pool, project_id, dataset_id).await?;
+    let versions = db::dataset_versions::get_dataset_versions(&db.pool, &dataset.id).await?;
+
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+/// Datapoints as they were in the version, immutable once the version is created
+#[get("datasets/{dataset_id}/versions/{version}/datapoints")]
+async fn get_dataset_version_datapoints(
+    db: web::Data<DB>,
+    user: User,
+    path: web::Path<(Uuid, Uuid, i32)>,
+    query_params: web::Query<PaginatedGetQueryParams>,
+) -> ResponseResult {
+    let (project_id, dataset_id, version) = path.into_inner();
+    let dataset = db::datasets::get_dataset(&db.pool, project_id, dataset_id).await?;
+    if !(0..=dataset.version).contains(&version) {
+        return Err(Error::not_found("Dataset version not found"));
+    }
+    let limit = query_params.page_size.unwrap_or(DEFAULT_PAGE_SIZE) as i64;
+    let offset = limit * (query_params.page_number) as i64;
+    let datapoints = db::dataset_versions::get_datapoints_at_version(
+        &db.pool,
+        &dataset_id,
+        version,
+        limit,
+        offset,
+    )
+    .await?;
+    let total_entries =
+        db::dataset_versions::count_datapoints_at_version(&db.pool, &dataset_id, version).await?;
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::DATAPOINT,
+        datapoints.iter().map(|datapoint| datapoint.id).collect(),
+    );
+
+    let response = PaginatedResponse::<DatapointView> {
+        items: datapoints,
+        total_count: total_entries,
+        any_in_project: true,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[derive(Deserialize)]
+struct DiffDatasetVersionsQuery {
+    from: i32,
+    to: i32,
+}
+
+/// Datapoints added, removed and modified between two versions
+#[get("datasets/{dataset_id}/versions/diff")]
+async fn diff_dataset_versions(
+    db: web::Data<DB>,
+    user: User,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<DiffDatasetVersionsQuery>,
+) -> ResponseResult {
+    let (project_id, dataset_id) = path.into_inner();
+    let DiffDatasetVersionsQuery { from, to } = query.into_inner();
+    let dataset = db::datasets::get_dataset(&db.pool, project_id, dataset_id).await?;
+    let versions = 0..=dataset.version;
+    if !versions.contains(&from) || !versions.contains(&to) {
+        return Err(Error::invalid_request(Some(&format!(
+            "Versions must be between 0 and {}",
+            dataset.version
+        ))));
+    }
+
+    let changes =
+        db::dataset_versions::diff_dataset_versions(&db.pool, &dataset_id, from, to).await?;
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::DATAPOINT,
+        changes.iter().map(|change| change.datapoint_id).collect(),
+    );
+
+    Ok(HttpResponse::Ok().json(changes))
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct IndexDatasetRequest {
@@ -4,13 +4,143 @@ use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
 use itertools::Itertools;
 use log::error;
+use serde::Serialize;
 use serde_json::Value;
 use uuid::Uuid;
 
+use crate::analytics::UnsupportedInDeploymentMode;
 use crate::db::workspace::WorkspaceError;
 use crate::engine::engine::EngineOutput;
 use crate::pipeline::runner::PipelineRunnerError;
+use crate::pipeline::validation::GraphValidationError;
 use crate::pipeline::GraphError;
+use crate::traces::protocol::ProtocolVersionError;
+
+/// Stable error codes of the API, see the error codes section of the README.
+///
+/// SDKs rely on them, e.g. to retry requests that failed with a retryable code, so existing
+/// codes must not be renamed or change their status.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    InternalError,
+    InvalidRequest,
+    InvalidPayload,
+    UnsupportedProtocolVersion,
+    InvalidGraph,
+    GraphRunFailed,
+    Forbidden,
+    NotFound,
+    EvaluationNotFound,
+    DatasetNotFound,
+    WorkspaceNotFound,
+    MachineNotFound,
+    CheckpointNotFound,
+    QuotaExceeded,
+    Conflict,
+    UnderLegalHold,
+    ChUnavailable,
+    DbUnavailable,
+    QueueUnavailable,
+    UnsupportedInDeploymentMode,
+}
+
+impl ErrorCode {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidRequest
+            | Self::InvalidPayload
+            | Self::UnsupportedProtocolVersion
+            | Self::InvalidGraph
+            | Self::GraphRunFailed => StatusCode::BAD_REQUEST,
+            Self::Forbidden | Self::QuotaExceeded => StatusCode::FORBIDDEN,
+            Self::NotFound
+            | Self::EvaluationNotFound
+            | Self::DatasetNotFound
+            | Self::WorkspaceNotFound
+            | Self::MachineNotFound
+            | Self::CheckpointNotFound => StatusCode::NOT_FOUND,
+            Self::Conflict | Self::UnderLegalHold => StatusCode::CONFLICT,
+            Self::ChUnavailable | Self::DbUnavailable | Self::QueueUnavailable => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Self::UnsupportedInDeploymentMode => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::InternalError => "Internal error",
+            Self::InvalidRequest => "Invalid request",
+            Self::InvalidPayload => "Invalid payload",
+            Self::UnsupportedProtocolVersion => "Unsupported protocol version",
+            Self::InvalidGraph => "Invalid graph",
+            Self::GraphRunFailed => "Graph run failed",
+            Self::Forbidden => "Forbidden",
+            Self::NotFound => "Not found",
+            Self::EvaluationNotFound => "Evaluation not found",
+            Self::DatasetNotFound => "Dataset not found",
+            Self::WorkspaceNotFound => "Workspace not found",
+            Self::MachineNotFound => "Machine not found",
+            Self::CheckpointNotFound => "Checkpoint not found",
+            Self::QuotaExceeded => "Quota exceeded",
+            Self::Conflict => "Conflict",
+            Self::UnderLegalHold => "Project is under legal hold",
+            Self::ChUnavailable => "ClickHouse is unavailable",
+            Self::DbUnavailable => "Database is unavailable",
+            Self::QueueUnavailable => "Queue is unavailable",
+            Self::UnsupportedInDeploymentMode => "Not supported in this deployment mode",
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::ChUnavailable | Self::DbUnavailable | Self::QueueUnavailable
+        )
+    }
+
+    /// Missing rows, unavailable dependencies and queries the deployment can't run are recognized
+    /// from the error chain, everything else is internal
+    fn from_anyhow(e: &anyhow::Error) -> Self {
+        for cause in e.chain() {
+            if let Some(e) = cause.downcast_ref::<sqlx::Error>() {
+                if matches!(e, sqlx::Error::RowNotFound) {
+                    return Self::NotFound;
+                }
+                if matches!(
+                    e,
+                    sqlx::Error::PoolTimedOut
+                        | sqlx::Error::PoolClosed
+                        | sqlx::Error::Io(_)
+                        | sqlx::Error::Tls(_)
+                        | sqlx::Error::WorkerCrashed
+                ) {
+                    return Self::DbUnavailable;
+                }
+            }
+            if let Some(e) = cause.downcast_ref::<clickhouse::error::Error>() {
+                if matches!(
+                    e,
+                    clickhouse::error::Error::Network(_) | clickhouse::error::Error::TimedOut
+                ) {
+                    return Self::ChUnavailable;
+                }
+            }
+            if cause.downcast_ref::<lapin::Error>().is_some() {
+                return Self::QueueUnavailable;
+            }
+            if cause
+                .downcast_ref::<UnsupportedInDeploymentMode>()
+                .is_some()
+            {
+                return Self::UnsupportedInDeploymentMode;
+            }
+        }
+        Self::InternalError
+    }
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -20,33 +150,80 @@ pub enum Error {
     MultipartError(#[from] actix_multipart::MultipartError),
     #[error("Request error, error_code: {error_code:?}, error_message: {error_message:?}")]
     RequestError {
-        error_code: String,
+        error_code: ErrorCode,
         error_message: Option<serde_json::Value>,
     },
     #[error("Forbidden")]
     Forbidden,
+    #[error("{code:?}: {message}")]
+    Api { code: ErrorCode, message: String },
 }
 
 // This can be refactored, but for now it can be used as a single source to see
 // all the error codes to be handled in frontend
 impl Error {
+    pub fn api(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::Api {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::api(ErrorCode::NotFound, message)
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InternalAnyhowError(e) => ErrorCode::from_anyhow(e),
+            Self::MultipartError(_) => ErrorCode::InvalidRequest,
+            Self::RequestError { error_code, .. } => *error_code,
+            Self::Forbidden => ErrorCode::Forbidden,
+            Self::Api { code, .. } => *code,
+        }
+    }
+
     pub fn invalid_request(error_message: Option<&str>) -> Self {
         Self::RequestError {
-            error_code: "api.invalidRequest".to_string(),
+            error_code: ErrorCode::InvalidRequest,
             error_message: error_message.map(|s| Value::String(s.to_string())),
         }
     }
 
     pub fn deserialization_error(error: Option<serde_json::Error>) -> Self {
         Self::RequestError {
-            error_code: "api.deserializationError".to_string(),
+            error_code: ErrorCode::InvalidRequest,
             error_message: error.map(|s| Value::String(s.to_string())),
         }
     }
 
+    /// See [`crate::traces::protocol`]
+    pub fn unsupported_protocol_version(error: &ProtocolVersionError) -> Self {
+        Self::RequestError {
+            error_code: ErrorCode::UnsupportedProtocolVersion,
+            error_message: Some(Value::String(error.to_string())),
+        }
+    }
+
+    /// See [`crate::api::validation`]
+    pub fn invalid_payload(error_message: Value) -> Self {
+        Self::RequestError {
+            error_code: ErrorCode::InvalidPayload,
+            error_message: Some(error_message),
+        }
+    }
+
+    /// See [`crate::pipeline::validation`]
+    pub fn invalid_graph(errors: Vec<GraphValidationError>) -> Self {
+        Self::RequestError {
+            error_code: ErrorCode::InvalidGraph,
+            error_message: Some(serde_json::json!(errors)),
+        }
+    }
+
     pub fn runner_missing_graph_input(input_name: Option<&str>) -> Self {
         Self::RequestError {
-            error_code: "api.missingGraphInput".to_string(),
+            error_code: ErrorCode::InvalidRequest,
             error_message: if let Some(input_name) = input_name {
                 Some(Value::String(format!(
                     "Graph input is missing: {}",
@@ -60,7 +237,7 @@ impl Error {
 
     pub fn no_target_pipeline(pipeline_name: &String) -> Self {
         Self::RequestError {
-            error_code: "api.noTargetPipeline".to_string(),
+            error_code: ErrorCode::NotFound,
             error_message: Some(Value::String(
                 format!("Pipeline has no target pipeline. There is no pipeline '{pipeline_name}', or it does not have a target version.
             
@@ -69,6 +246,15 @@ Set the target version for the pipeline in the pipeline builder."),
         }
     }
 
+    pub fn no_pipeline_deployment(pipeline_name: &String, target: &str) -> Self {
+        Self::RequestError {
+            error_code: ErrorCode::NotFound,
+            error_message: Some(Value::String(format!(
+                "There is no pipeline '{pipeline_name}', or it is not deployed to '{target}'."
+            ))),
+        }
+    }
+
     pub fn graph_running_error(trace: EngineOutput, run_id: Uuid) -> Self {
         let node_messages = trace.messages;
         let truncated = node_messages
@@ -84,7 +270,7 @@ Set the target version for the pipeline in the pipeline builder."),
             })
             .collect::<HashMap<_, _>>();
         Self::RequestError {
-            error_code: "api.GraphRunningError".to_string(),
+            error_code: ErrorCode::GraphRunFailed,
             error_message: Some(serde_json::json!(
             {
                 "runId": run_id.to_string(),
@@ -95,14 +281,14 @@ Set the target version for the pipeline in the pipeline builder."),
 
     pub fn user_not_found(email: String) -> Self {
         Self::RequestError {
-            error_code: "api.UserNotFound".to_string(),
+            error_code: ErrorCode::NotFound,
             error_message: Some(Value::String(format!("User not found: {}", email))),
         }
     }
 
     pub fn limit_error(error_message: &str) -> Self {
         Self::RequestError {
-            error_code: "api.LimitReached".to_string(),
+            error_code: ErrorCode::QuotaExceeded,
             error_message: Some(Value::String(error_message.to_string())),
         }
     }
@@ -161,25 +347,63 @@ pub fn pipeline_runner_to_http_error(e: PipelineRunnerError, run_id: Uuid) -> Er
 
 impl ResponseError for Error {
     fn status_code(&self) -> StatusCode {
-        match &self {
-            Self::InternalAnyhowError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Self::MultipartError(_) => StatusCode::BAD_REQUEST,
-            Self::RequestError { .. } => StatusCode::BAD_REQUEST,
-            Self::Forbidden => StatusCode::FORBIDDEN,
-        }
+        self.code().status_code()
     }
 
     fn error_response(&self) -> HttpResponse {
         error!("Error: {:?}", self.to_string());
-        match &self {
+        let code = self.code();
+        // Internal error details are only logged
+        let message = match self {
+            Self::Api { message, .. } => Value::String(message.clone()),
             Self::RequestError {
-                error_code,
-                error_message,
-            } => HttpResponse::BadRequest().json(serde_json::json!({
-                    "error_code": error_code.clone(),
-                    "error_message": error_message.clone(),
-            })),
-            _ => HttpResponse::build(self.status_code()).finish(),
-        }
+                error_message: Some(error_message),
+                ..
+            } => error_message.clone(),
+            _ => Value::String(code.description().to_string()),
+        };
+        HttpResponse::build(code.status_code()).json(serde_json::json!({
+            "error_code": code,
+            "error_message": message,
+            "retryable": code.is_retryable(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        let e: Error = anyhow::Error::from(sqlx::Error::PoolTimedOut)
+            .context("Failed to get evaluation")
+            .into();
+        assert_eq!(e.code(), ErrorCode::DbUnavailable);
+        assert_eq!(e.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let e: Error = anyhow::anyhow!("Unexpected").into();
+        assert_eq!(e.code(), ErrorCode::InternalError);
+
+        let e: Error = anyhow::Error::from(sqlx::Error::RowNotFound).into();
+        assert_eq!(e.code(), ErrorCode::NotFound);
+        assert_eq!(e.status_code(), StatusCode::NOT_FOUND);
+
+        let e = Error::invalid_request(Some("Page size is too large"));
+        assert_eq!(e.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(serde_json::to_value(e.code()).unwrap(), "INVALID_REQUEST");
+
+        let e = Error::api(ErrorCode::QuotaExceeded, "Workspace span limit exceeded");
+        assert_eq!(e.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            serde_json::to_value(ErrorCode::ChUnavailable).unwrap(),
+            "CH_UNAVAILABLE"
+        );
+
+        let e: Error = anyhow::Error::from(UnsupportedInDeploymentMode("Aggregate queries"))
+            .context("Failed to run query")
+            .into();
+        assert_eq!(e.code(), ErrorCode::UnsupportedInDeploymentMode);
+        assert_eq!(e.status_code(), StatusCode::NOT_IMPLEMENTED);
     }
 }
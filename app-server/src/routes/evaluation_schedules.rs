@@ -0,0 +1,228 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self,
+        evaluation_schedules::{self, NewEvaluationSchedule},
+        pipelines::pipeline_deployments::PRODUCTION_TARGET,
+        user::User,
+        DB,
+    },
+    evaluations::judge,
+    pipeline::triggers,
+    traces::evaluators::get_stored_env,
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+const MAX_NAME_LENGTH: usize = 64;
+const DEFAULT_SCHEDULE_RUNS_LIMIT: i64 = 100;
+
+#[get("evaluation-schedules")]
+pub async fn get_evaluation_schedules(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let schedules = evaluation_schedules::get_schedules(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(schedules))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateEvaluationScheduleRequest {
+    name: String,
+    /// Cron expression in UTC, with or without the seconds field
+    cron_schedule: String,
+    dataset_id: Uuid,
+    pipeline_id: Uuid,
+    /// Deployment target to evaluate, defaults to production
+    #[serde(default)]
+    target: Option<String>,
+    /// Evaluates this version of the pipeline instead of the deployed one
+    #[serde(default)]
+    pipeline_version_id: Option<Uuid>,
+    #[serde(default)]
+    judges: Vec<String>,
+    /// Defaults to the schedule's name
+    #[serde(default)]
+    group_id: Option<String>,
+}
+
+/// Evaluates the pipeline on the dataset's datapoints on the cron schedule, scored by the judges
+#[post("evaluation-schedules")]
+pub async fn create_evaluation_schedule(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateEvaluationScheduleRequest>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(Error::invalid_request(Some(&format!(
+            "Name must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        ))));
+    }
+    triggers::parse_cron_schedule(&req.cron_schedule)
+        .map_err(|e| Error::invalid_request(Some(&e.to_string())))?;
+
+    db::datasets::get_dataset_version(&db.pool, &project_id, &req.dataset_id)
+        .await?
+        .ok_or_else(|| Error::invalid_request(Some("Dataset not found")))?;
+    let pipeline = db::pipelines::get_pipeline_by_id(&db.pool, &req.pipeline_id).await?;
+    if pipeline.project_id != project_id {
+        return Err(Error::invalid_request(Some("Pipeline not found")));
+    }
+    if let Some(pipeline_version_id) = &req.pipeline_version_id {
+        let version =
+            db::pipelines::pipeline_version::get_pipeline_version(&db.pool, pipeline_version_id)
+                .await
+                .map_err(|_| Error::invalid_request(Some("Pipeline version not found")))?;
+        if version.pipeline_id != pipeline.id {
+            return Err(Error::invalid_request(Some("Pipeline version not found")));
+        }
+    }
+
+    let mut judges = req.judges;
+    judges.sort();
+    judges.dedup();
+    if !judges.is_empty() {
+        let found =
+            db::judge_evaluators::get_judge_evaluators_by_names(&db.pool, &project_id, &judges)
+                .await?;
+        let unknown = judges
+            .iter()
+            .filter(|name| !found.iter().any(|judge| &judge.name == *name))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            return Err(Error::invalid_request(Some(&format!(
+                "Judge evaluators not found: {}",
+                unknown.join(", ")
+            ))));
+        }
+        let env = get_stored_env(db.clone().into_inner(), project_id).await?;
+        let missing_env_vars = judge::missing_env_vars(&found, &env);
+        if !missing_env_vars.is_empty() {
+            return Err(Error::invalid_request(Some(&format!(
+                "Add {} to the project's api keys to run the judges",
+                missing_env_vars.join(", ")
+            ))));
+        }
+    }
+
+    let schedule = NewEvaluationSchedule {
+        project_id,
+        name: name.to_string(),
+        next_run_at: triggers::next_run_at(&req.cron_schedule, Utc::now()),
+        cron_schedule: req.cron_schedule,
+        dataset_id: req.dataset_id,
+        pipeline_id: req.pipeline_id,
+        target: req.target.unwrap_or(PRODUCTION_TARGET.to_string()),
+        pipeline_version_id: req.pipeline_version_id,
+        judges,
+        group_id: req
+            .group_id
+            .filter(|group_id| !group_id.is_empty())
+            .unwrap_or(name.to_string()),
+        created_by: Some(user.id),
+    };
+    let Some(schedule) = evaluation_schedules::create_schedule(&db.pool, &schedule).await? else {
+        return Err(Error::invalid_request(Some(&format!(
+            "Evaluation schedule {} already exists",
+            name
+        ))));
+    };
+
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+#[derive(Deserialize)]
+struct UpdateEvaluationScheduleRequest {
+    enabled: bool,
+}
+
+/// Pauses or resumes a schedule
+#[post("evaluation-schedules/{schedule_id}")]
+pub async fn update_evaluation_schedule(
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<UpdateEvaluationScheduleRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, schedule_id) = path.into_inner();
+    let enabled = req.into_inner().enabled;
+
+    let Some(schedule) = evaluation_schedules::get_schedule(&db.pool, &schedule_id)
+        .await?
+        .filter(|schedule| schedule.project_id == project_id)
+    else {
+        return Err(Error::not_found("Evaluation schedule not found"));
+    };
+    // Resumed schedules continue from now instead of catching up on the missed runs
+    let next_run_at = if enabled && !schedule.enabled {
+        triggers::next_run_at(&schedule.cron_schedule, Utc::now())
+    } else {
+        schedule.next_run_at
+    };
+    let Some(schedule) = evaluation_schedules::set_schedule_enabled(
+        &db.pool,
+        &project_id,
+        &schedule_id,
+        enabled,
+        next_run_at,
+    )
+    .await?
+    else {
+        return Err(Error::not_found("Evaluation schedule not found"));
+    };
+
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+#[delete("evaluation-schedules/{schedule_id}")]
+pub async fn delete_evaluation_schedule(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, schedule_id) = path.into_inner();
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "evaluation_schedule",
+        Some(&schedule_id),
+    )
+    .await?;
+
+    if !evaluation_schedules::delete_schedule(&db.pool, &project_id, &schedule_id).await? {
+        return Err(Error::not_found("Evaluation schedule not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+struct GetEvaluationScheduleRunsQuery {
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// Run history of the schedule, the newest first, with the evaluations of the succeeded runs
+#[get("evaluation-schedules/{schedule_id}/runs")]
+pub async fn get_evaluation_schedule_runs(
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<GetEvaluationScheduleRunsQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, schedule_id) = path.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_SCHEDULE_RUNS_LIMIT);
+    let runs =
+        evaluation_schedules::get_schedule_runs(&db.pool, &project_id, &schedule_id, limit).await?;
+
+    Ok(HttpResponse::Ok().json(runs))
+}
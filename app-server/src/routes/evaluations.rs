@@ -1,26 +1,66 @@
-use actix_web::{delete, get, web, HttpResponse};
+use std::{collections::HashMap, sync::Arc};
+
+use actix_web::{delete, get, post, web, HttpResponse};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    ch::evaluation_scores::{
-        get_average_evaluation_score, get_evaluation_score_buckets_based_on_bounds,
-        get_global_evaluation_scores_bounds, EvaluationScoreBucket,
+    analytics::AnalyticsStore,
+    cache::Cache,
+    ch::{
+        evaluation_scores::{
+            datapoint_key, summarize_score_diffs, EvaluationScore, EvaluationScoreBucket,
+            EvaluationScoreChangeSummary, EvaluationScoreDiff, ScoreSource,
+        },
+        filter_expression::FilterTarget,
+        span_scores::ScoreScatterPoint,
     },
+    datasets::Dataset,
     db::{
+        eval_proposals,
         evaluations::{self, Evaluation, EvaluationDatapoint},
+        labels::LabelClass,
+        pipelines::{
+            pipeline::get_pipeline_by_version_id,
+            pipeline_version::{
+                create_pipeline_version, get_pipeline_version, PipelineVersionInfo,
+            },
+        },
+        user::User,
         DB,
     },
+    evaluations::{
+        export::{self, ExportFormat},
+        insights,
+        prompt_suggestions::{self, InvalidSuggestion, PromptSuggestion},
+        proposals,
+        utils::ScoreValue,
+    },
+    language_model::LanguageModelRunner,
+    logging,
+    semantic_search::SemanticSearch,
+    traces::evaluators::get_stored_env,
 };
 
-use super::ResponseResult;
+use super::{
+    error::{Error, ErrorCode},
+    legal_holds::ensure_no_legal_hold,
+    FilterQueryRequest, ResponseResult,
+};
 
-const DEFAULT_LOWER_BOUND: f64 = 0.0;
-const DEFAULT_BUCKET_COUNT: u64 = 10;
+pub(super) const DEFAULT_LOWER_BOUND: f64 = 0.0;
+pub(super) const DEFAULT_BUCKET_COUNT: u64 = 10;
+const MAX_HISTOGRAM_BUCKET_COUNT: u64 = 50;
 
 #[delete("evaluations/{evaluation_id}")]
-async fn delete_evaluation(path: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> ResponseResult {
-    let (_project_id, evaluation_id) = path.into_inner();
+async fn delete_evaluation(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, evaluation_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "evaluation", Some(&evaluation_id)).await?;
     evaluations::delete_evaluation(&db.pool, &evaluation_id).await?;
 
     Ok(HttpResponse::Ok().finish())
@@ -72,11 +112,11 @@ async fn get_evaluation(path: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> Res
     let db = db.into_inner();
 
     let db_clone = db.clone();
-    let get_evaluation_task = tokio::task::spawn(async move {
+    let get_evaluation_task = logging::spawn(async move {
         evaluations::get_evaluation(db_clone, project_id, evaluation_id).await
     });
 
-    let get_evaluation_results = tokio::task::spawn(async move {
+    let get_evaluation_results = logging::spawn(async move {
         evaluations::get_evaluation_results(&db.pool, evaluation_id).await
     });
 
@@ -85,7 +125,12 @@ async fn get_evaluation(path: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> Res
         return Err(anyhow::anyhow!("Error getting evaluation: {}", e).into());
     }
     let (evaluation, results) = join_res.unwrap();
-    let evaluation = evaluation?;
+    let evaluation = evaluation.map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::RowNotFound) => {
+            Error::api(ErrorCode::EvaluationNotFound, "Evaluation not found")
+        }
+        _ => e.into(),
+    })?;
     let results = results?;
 
     let response = GetEvaluationResponse {
@@ -98,34 +143,244 @@ async fn get_evaluation(path: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> Res
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct GetEvaluationScoreStatsQuery {
-    evaluation_id: Uuid,
-    score_name: String,
+struct SubmitHumanScoresRequest {
+    scores: HashMap<String, ScoreValue>,
 }
 
-#[derive(Serialize)]
+/// Manual scores of an evaluation result. They are aggregated with the scores of the evaluators,
+/// and can't replace a score of the same name.
+#[post("evaluations/{evaluation_id}/results/{result_id}/scores")]
+async fn submit_human_scores(
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    req: web::Json<SubmitHumanScoresRequest>,
+    user: User,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let (project_id, evaluation_id, result_id) = path.into_inner();
+    let scores = req.into_inner().scores;
+    if scores.is_empty() {
+        return Err(Error::invalid_request(Some(
+            "At least one score is required",
+        )));
+    }
+    if scores.keys().any(|name| name.trim().is_empty()) {
+        return Err(Error::invalid_request(Some(
+            "Score names must not be empty",
+        )));
+    }
+    if scores
+        .values()
+        .any(|value| matches!(value, ScoreValue::Number(n) if !n.is_finite()))
+    {
+        return Err(Error::invalid_request(Some(
+            "Scores must be finite numbers",
+        )));
+    }
+    let db = db.into_inner();
+
+    let evaluation = evaluations::get_evaluation(db.clone(), project_id, evaluation_id)
+        .await
+        .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => {
+                Error::api(ErrorCode::EvaluationNotFound, "Evaluation not found")
+            }
+            _ => e.into(),
+        })?;
+    let Some(result) =
+        evaluations::get_evaluation_results_by_ids(&db.pool, project_id, &[result_id])
+            .await?
+            .into_iter()
+            .find(|result| result.evaluation_id == evaluation_id)
+    else {
+        return Err(Error::not_found("Evaluation result not found"));
+    };
+
+    if !evaluations::insert_human_evaluation_scores(&db.pool, result_id, user.id, &scores).await? {
+        return Err(Error::invalid_request(Some(
+            "The result already has a score with this name",
+        )));
+    }
+
+    let timestamp = Utc::now();
+    let datapoint_key = datapoint_key(&result.data, &result.target);
+    let ch_scores = scores
+        .iter()
+        .map(|(name, value)| EvaluationScore {
+            project_id,
+            group_id: evaluation.group_id.clone(),
+            evaluation_id,
+            result_id,
+            name: name.clone(),
+            value: value.as_f64().unwrap_or_default(),
+            timestamp,
+            datapoint_key: datapoint_key.clone(),
+            score_type: value.score_type().into(),
+            label: value.label().unwrap_or_default().to_string(),
+            source: ScoreSource::HUMAN,
+        })
+        .collect::<Vec<_>>();
+    analytics_store
+        .as_ref()
+        .insert_evaluation_scores(ch_scores)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(scores))
+}
+
+#[derive(Deserialize)]
+pub struct ExportEvaluationQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// Download of the evaluation's results with their scores as CSV, JSONL, Parquet or Arrow
+#[get("evaluations/{evaluation_id}/export")]
+async fn export_evaluation(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    query: web::Query<ExportEvaluationQuery>,
+) -> ResponseResult {
+    let (project_id, evaluation_id) = path.into_inner();
+    let format = query.into_inner().format;
+    let db = db.into_inner();
+
+    let evaluation = evaluations::get_evaluation(db.clone(), project_id, evaluation_id)
+        .await
+        .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => {
+                Error::api(ErrorCode::EvaluationNotFound, "Evaluation not found")
+            }
+            _ => e.into(),
+        })?;
+    let score_names = evaluations::get_evaluation_score_names(&db.pool, evaluation_id).await?;
+
+    let stream =
+        export::export_evaluation_results(db.pool.clone(), evaluation_id, score_names, format);
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{}.{}\"",
+                evaluation.name.replace('"', ""),
+                format.extension()
+            ),
+        ))
+        .streaming(stream))
+}
+
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct GetEvaluationScoreStatsResponse {
-    average_value: f64,
+pub struct GetEvaluationScoreStatsQuery {
+    evaluation_id: Uuid,
+    score_name: String,
 }
 
+/// Count, mean, extremes, percentiles and standard deviation of the evaluation's score
 #[get("evaluation-score-stats")]
 async fn get_evaluation_score_stats(
     path: web::Path<Uuid>,
-    clickhouse: web::Data<clickhouse::Client>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
     query: web::Query<GetEvaluationScoreStatsQuery>,
 ) -> ResponseResult {
     let project_id = path.into_inner();
-    let clickhouse = clickhouse.into_inner().as_ref().clone();
+    let analytics_store = analytics_store.as_ref().clone();
     let query = query.into_inner();
     let evaluation_id = query.evaluation_id;
     let score_name = query.score_name;
 
-    let average_value =
-        get_average_evaluation_score(clickhouse, project_id, evaluation_id, score_name).await?;
+    let stats = analytics_store
+        .get_evaluation_score_stats(project_id, evaluation_id, score_name)
+        .await?;
 
-    let response = GetEvaluationScoreStatsResponse { average_value };
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+/// Number of passed scores and the pass rate of the evaluation's boolean score
+#[get("evaluation-score-pass-rate")]
+async fn get_evaluation_score_pass_rate(
+    path: web::Path<Uuid>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<GetEvaluationScoreStatsQuery>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+
+    let pass_rate = analytics_store
+        .get_evaluation_score_pass_rate(project_id, query.evaluation_id, query.score_name)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(pass_rate))
+}
+
+/// Count and frequency of each label of the evaluation's categorical score
+#[get("evaluation-score-labels")]
+async fn get_evaluation_score_labels(
+    path: web::Path<Uuid>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<GetEvaluationScoreStatsQuery>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+
+    let frequencies = analytics_store
+        .get_evaluation_score_label_frequencies(project_id, query.evaluation_id, query.score_name)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(frequencies))
+}
+
+/// Scores of the evaluations of the period that match the filter expression, the newest first
+#[post("evaluation-scores/query")]
+async fn query_evaluation_scores(
+    path: web::Path<Uuid>,
+    req: web::Json<FilterQueryRequest>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    let filter = req.compile_filter(FilterTarget::EvaluationScores, &HashMap::new(), &[])?;
+    let (start_time, end_time) = req.time_range()?;
+
+    let scores = analytics_store
+        .query_evaluation_scores(
+            project_id,
+            &filter,
+            start_time,
+            end_time,
+            req.limit()?,
+            req.offset,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(scores))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEvaluationScoreTrendQuery {
+    group_id: String,
+    score_name: String,
+}
+
+/// Average and percentile band of the score in each evaluation of the group, ordered by time, to
+/// spot regressions across runs
+#[get("evaluation-score-trend")]
+async fn get_evaluation_score_trend(
+    path: web::Path<Uuid>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<GetEvaluationScoreTrendQuery>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+
+    let trend = analytics_store
+        .get_evaluation_score_trend(project_id, query.group_id, query.score_name)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(trend))
 }
 
 #[derive(Deserialize)]
@@ -133,6 +388,9 @@ async fn get_evaluation_score_stats(
 pub struct GetEvaluationScoreDistributionQuery {
     evaluation_ids: String,
     score_name: String,
+    /// Buckets between 0 and the maximum score instead of the buckets fitted to the data
+    #[serde(default)]
+    bucket_count: Option<u64>,
 }
 
 #[derive(Serialize)]
@@ -146,15 +404,16 @@ pub struct GetEvaluationScoreDistributionResponseBucket {
 
 /// Get the score distribution where global lower and upper bounds for all requested evaluation ids are calculated
 ///
-/// Currently, distributes into 10 buckets
+/// By default, bounds and bucket width are fitted to the scores, see `HistogramBounds`. With
+/// `bucketCount`, scores are distributed into that many buckets between 0 and the maximum score.
 #[get("evaluation-score-distribution")]
 async fn get_evaluation_score_distribution(
     path: web::Path<Uuid>,
-    clickhouse: web::Data<clickhouse::Client>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
     query: web::Query<GetEvaluationScoreDistributionQuery>,
 ) -> ResponseResult {
     let project_id = path.into_inner();
-    let clickhouse = clickhouse.into_inner().as_ref().clone();
+    let analytics_store = analytics_store.as_ref().clone();
     let query = query.into_inner();
     let score_name = query.score_name;
     let evaluation_ids_str = query.evaluation_ids;
@@ -164,17 +423,31 @@ async fn get_evaluation_score_distribution(
         .map(|id| Uuid::parse_str(id).unwrap())
         .collect::<Vec<Uuid>>();
     if evaluation_ids.is_empty() {
-        return Err(anyhow::anyhow!("No evaluation ids provided").into());
+        return Err(Error::invalid_request(Some("No evaluation ids provided")));
+    }
+
+    let Some(bucket_count) = query.bucket_count else {
+        let buckets = analytics_store
+            .get_evaluation_score_histogram(
+                project_id,
+                &evaluation_ids,
+                score_name,
+                MAX_HISTOGRAM_BUCKET_COUNT,
+            )
+            .await?;
+        return Ok(HttpResponse::Ok().json(buckets));
+    };
+    if bucket_count == 0 || bucket_count > MAX_HISTOGRAM_BUCKET_COUNT {
+        return Err(Error::invalid_request(Some(&format!(
+            "Bucket count must be between 1 and {}",
+            MAX_HISTOGRAM_BUCKET_COUNT
+        ))));
     }
 
     // Get bounds among all evaluations
-    let global_bounds = get_global_evaluation_scores_bounds(
-        clickhouse.clone(),
-        project_id,
-        &evaluation_ids,
-        score_name.clone(),
-    )
-    .await?;
+    let global_bounds = analytics_store
+        .get_global_evaluation_scores_bounds(project_id, &evaluation_ids, score_name.clone())
+        .await?;
     // TODO: Figure out better way to handle this in both backend and frontend
     if global_bounds.upper_bound < DEFAULT_LOWER_BOUND {
         return Err(anyhow::anyhow!(
@@ -187,26 +460,26 @@ async fn get_evaluation_score_distribution(
 
     let evaluation_buckets: Vec<Vec<EvaluationScoreBucket>> =
         futures::future::try_join_all(evaluation_ids.into_iter().map(|evaluation_id| {
-            let clickhouse = clickhouse.clone();
+            let analytics_store = analytics_store.clone();
             let score_name = score_name.clone();
             async move {
-                get_evaluation_score_buckets_based_on_bounds(
-                    clickhouse,
-                    project_id,
-                    evaluation_id,
-                    score_name,
-                    DEFAULT_LOWER_BOUND,
-                    global_bounds.upper_bound,
-                    DEFAULT_BUCKET_COUNT,
-                )
-                .await
+                analytics_store
+                    .get_evaluation_score_buckets_based_on_bounds(
+                        project_id,
+                        evaluation_id,
+                        score_name,
+                        DEFAULT_LOWER_BOUND,
+                        global_bounds.upper_bound,
+                        bucket_count,
+                    )
+                    .await
             }
         }))
         .await?;
 
     let mut res_buckets: Vec<GetEvaluationScoreDistributionResponseBucket> = Vec::new();
 
-    for i in 0..DEFAULT_BUCKET_COUNT as usize {
+    for i in 0..bucket_count as usize {
         // Simply get the lower and upper bounds from the first evaluation, since they are the same for all evaluations
         let lower_bound = evaluation_buckets[0][i].lower_bound;
         let upper_bound = evaluation_buckets[0][i].upper_bound;
@@ -224,3 +497,480 @@ async fn get_evaluation_score_distribution(
 
     Ok(HttpResponse::Ok().json(res_buckets))
 }
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEvaluationScoreScatterQuery {
+    score_name: String,
+}
+
+/// Score of each datapoint next to the latency and cost of its trace, to see whether cheaper or
+/// faster configurations trade off quality. Datapoints without the score are skipped.
+#[get("evaluations/{evaluation_id}/score-scatter")]
+async fn get_evaluation_score_scatter(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<GetEvaluationScoreScatterQuery>,
+) -> ResponseResult {
+    let (project_id, evaluation_id) = path.into_inner();
+    let score_name = query.into_inner().score_name;
+    let db = db.into_inner();
+
+    evaluations::get_evaluation(db.clone(), project_id, evaluation_id)
+        .await
+        .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => {
+                Error::api(ErrorCode::EvaluationNotFound, "Evaluation not found")
+            }
+            _ => e.into(),
+        })?;
+    let results = evaluations::get_evaluation_results(&db.pool, evaluation_id).await?;
+
+    let trace_ids = results.iter().map(|r| r.trace_id).collect::<Vec<_>>();
+    let traces = analytics_store
+        .get_traces_latency_and_cost(project_id, &trace_ids)
+        .await?
+        .into_iter()
+        .map(|trace| (trace.trace_id, trace))
+        .collect::<HashMap<_, _>>();
+
+    let points = results
+        .iter()
+        .filter_map(|result| {
+            let score = result.scores.get(&score_name)?.as_f64()?;
+            let trace = traces.get(&result.trace_id)?;
+            Some(ScoreScatterPoint {
+                id: result.id,
+                trace_id: result.trace_id,
+                score,
+                latency: trace.latency,
+                cost: trace.cost,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(points))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEvaluationScorePercentilesQuery {
+    baseline_evaluation_id: Uuid,
+}
+
+/// Scores of the evaluation's datapoints as percentiles of the baseline evaluation's scores with
+/// the same name, so that scores on different scales can be compared
+#[get("evaluations/{evaluation_id}/score-percentiles")]
+async fn get_evaluation_score_percentiles(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<GetEvaluationScorePercentilesQuery>,
+) -> ResponseResult {
+    let (project_id, evaluation_id) = path.into_inner();
+    let baseline_evaluation_id = query.into_inner().baseline_evaluation_id;
+    let db = db.into_inner();
+
+    for id in [evaluation_id, baseline_evaluation_id] {
+        evaluations::get_evaluation(db.clone(), project_id, id)
+            .await
+            .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+                Some(sqlx::Error::RowNotFound) => {
+                    Error::api(ErrorCode::EvaluationNotFound, "Evaluation not found")
+                }
+                _ => e.into(),
+            })?;
+    }
+
+    let percentiles = analytics_store
+        .get_evaluation_score_percentiles(project_id, evaluation_id, baseline_evaluation_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(percentiles))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareEvaluationsQuery {
+    baseline_evaluation_id: Uuid,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EvaluationComparison {
+    summaries: Vec<EvaluationScoreChangeSummary>,
+    diffs: Vec<EvaluationScoreDiff>,
+}
+
+/// Per-datapoint score deltas against the baseline evaluation of the same group, and the number
+/// of improved and regressed datapoints for each score name
+#[get("evaluations/{evaluation_id}/compare")]
+async fn compare_evaluations(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<CompareEvaluationsQuery>,
+) -> ResponseResult {
+    let (project_id, evaluation_id) = path.into_inner();
+    let baseline_evaluation_id = query.into_inner().baseline_evaluation_id;
+    let db = db.into_inner();
+
+    let mut group_ids = Vec::new();
+    for id in [evaluation_id, baseline_evaluation_id] {
+        let evaluation = evaluations::get_evaluation(db.clone(), project_id, id)
+            .await
+            .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+                Some(sqlx::Error::RowNotFound) => {
+                    Error::api(ErrorCode::EvaluationNotFound, "Evaluation not found")
+                }
+                _ => e.into(),
+            })?;
+        group_ids.push(evaluation.group_id);
+    }
+    if group_ids[0] != group_ids[1] {
+        return Err(Error::invalid_request(Some(
+            "Only evaluations of the same group can be compared",
+        )));
+    }
+
+    let diffs = analytics_store
+        .get_evaluation_score_diffs(project_id, evaluation_id, baseline_evaluation_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(EvaluationComparison {
+        summaries: summarize_score_diffs(&diffs),
+        diffs,
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GroupBaseline {
+    evaluation_id: Option<Uuid>,
+}
+
+/// Baseline evaluation pinned for the group, which regression gates compare against
+#[get("evaluation-groups/{group_id}/baseline")]
+async fn get_group_baseline(path: web::Path<(Uuid, String)>, db: web::Data<DB>) -> ResponseResult {
+    let (project_id, group_id) = path.into_inner();
+    let evaluation_id = evaluations::get_group_baseline(&db.pool, &project_id, &group_id).await?;
+
+    Ok(HttpResponse::Ok().json(GroupBaseline { evaluation_id }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PinGroupBaselineRequest {
+    /// None unpins the baseline
+    evaluation_id: Option<Uuid>,
+}
+
+#[post("evaluation-groups/{group_id}/baseline")]
+async fn pin_group_baseline(
+    path: web::Path<(Uuid, String)>,
+    req: web::Json<PinGroupBaselineRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, group_id) = path.into_inner();
+    let evaluation_id = req.into_inner().evaluation_id;
+    let db = db.into_inner();
+
+    match evaluation_id {
+        Some(evaluation_id) => {
+            let evaluation = evaluations::get_evaluation(db.clone(), project_id, evaluation_id)
+                .await
+                .map_err(|e| match e.downcast_ref::<sqlx::Error>() {
+                    Some(sqlx::Error::RowNotFound) => {
+                        Error::api(ErrorCode::EvaluationNotFound, "Evaluation not found")
+                    }
+                    _ => e.into(),
+                })?;
+            if evaluation.group_id != group_id {
+                return Err(Error::invalid_request(Some(
+                    "The evaluation is of another group",
+                )));
+            }
+            evaluations::set_group_baseline(&db.pool, &project_id, &group_id, &evaluation_id)
+                .await?;
+        }
+        None => evaluations::delete_group_baseline(&db.pool, &project_id, &group_id).await?,
+    }
+
+    Ok(HttpResponse::Ok().json(GroupBaseline { evaluation_id }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEvaluationInsightsQuery {
+    /// End of the reported week, defaults to now
+    #[serde(default)]
+    week_end: Option<DateTime<Utc>>,
+}
+
+/// Weekly report comparing evaluation and online scores with the week before
+#[get("evaluation-insights")]
+async fn get_evaluation_insights(
+    path: web::Path<Uuid>,
+    query: web::Query<GetEvaluationInsightsQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let week_end = query.into_inner().week_end.unwrap_or(Utc::now());
+
+    let report = insights::generate_report(&db, &project_id, week_end).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+const MAX_SUGGESTION_DATAPOINTS: usize = 20;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestPromptEditsRequest {
+    pipeline_version_id: Uuid,
+    /// Name of the LLM node whose prompt is edited, can be omitted if the pipeline has only one
+    #[serde(default)]
+    node_name: Option<String>,
+    /// Cluster of failing evaluation datapoints
+    evaluation_result_ids: Vec<Uuid>,
+    /// Saves the suggested prompt as a new version of the pipeline
+    #[serde(default)]
+    create_draft: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SuggestPromptEditsResponse {
+    node_name: String,
+    current_prompt: String,
+    #[serde(flatten)]
+    suggestion: PromptSuggestion,
+    draft_version: Option<PipelineVersionInfo>,
+}
+
+/// Suggests edits to the prompt of a pipeline version's LLM node that fix the given failing
+/// evaluation datapoints. The suggestion is generated with the language model in
+/// `PROMPT_SUGGESTION_MODEL`, using the project's provider api keys.
+#[post("prompt-suggestions")]
+async fn suggest_prompt_edits(
+    path: web::Path<Uuid>,
+    req: web::Json<SuggestPromptEditsRequest>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    language_model: web::Data<Arc<LanguageModelRunner>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    if req.evaluation_result_ids.is_empty()
+        || req.evaluation_result_ids.len() > MAX_SUGGESTION_DATAPOINTS
+    {
+        return Err(Error::invalid_request(Some(&format!(
+            "Between 1 and {} failing datapoints are required",
+            MAX_SUGGESTION_DATAPOINTS
+        ))));
+    }
+    let db = db.into_inner();
+
+    match get_pipeline_by_version_id(&db.pool, &req.pipeline_version_id).await {
+        Ok(pipeline) if pipeline.project_id == project_id => {}
+        _ => return Err(Error::not_found("Pipeline version not found")),
+    }
+    let version = get_pipeline_version(&db.pool, &req.pipeline_version_id).await?;
+
+    let llm_nodes = prompt_suggestions::llm_nodes(&version.runnable_graph);
+    let (node_id, node_name, current_prompt) = match &req.node_name {
+        Some(node_name) => llm_nodes
+            .into_iter()
+            .find(|(_, name, _)| name == node_name)
+            .ok_or_else(|| {
+                Error::invalid_request(Some(&format!("No LLM node named {}", node_name)))
+            })?,
+        None if llm_nodes.len() == 1 => llm_nodes.into_iter().next().unwrap(),
+        None => {
+            return Err(Error::invalid_request(Some(
+                "The pipeline must have exactly one LLM node, or nodeName must be set",
+            )))
+        }
+    };
+
+    let datapoints = evaluations::get_evaluation_results_by_ids(
+        &db.pool,
+        project_id,
+        &req.evaluation_result_ids,
+    )
+    .await?;
+    if datapoints.len() != req.evaluation_result_ids.len() {
+        return Err(Error::not_found("Evaluation results not found"));
+    }
+
+    let env = get_stored_env(db.clone(), project_id).await?;
+    let missing_env_vars = prompt_suggestions::missing_env_vars(&env);
+    if !missing_env_vars.is_empty() {
+        return Err(Error::invalid_request(Some(&format!(
+            "Add {} to the project's api keys to get prompt suggestions",
+            missing_env_vars.join(", ")
+        ))));
+    }
+    let suggestion = prompt_suggestions::suggest_prompt_edits(
+        language_model.as_ref().clone(),
+        db.clone(),
+        cache.into_inner(),
+        &env,
+        &current_prompt,
+        &datapoints,
+    )
+    .await
+    .map_err(|e| match e.downcast_ref::<InvalidSuggestion>() {
+        Some(e) => Error::api(ErrorCode::InternalError, &e.0),
+        None => e.into(),
+    })?;
+
+    let draft_version = if req.create_draft {
+        let mut displayable_graph = version.displayable_graph;
+        let mut runnable_graph = version.runnable_graph;
+        prompt_suggestions::replace_prompt(
+            &mut displayable_graph,
+            &mut runnable_graph,
+            &node_id,
+            &suggestion.suggested_prompt,
+        );
+        let draft = create_pipeline_version(
+            &db.pool,
+            Uuid::new_v4(),
+            version.pipeline_id,
+            "COMMIT",
+            &format!("{} (suggested prompt)", version.name),
+            &displayable_graph,
+            &runnable_graph,
+        )
+        .await?;
+        Some(PipelineVersionInfo {
+            id: draft.id,
+            pipeline_id: draft.pipeline_id,
+            pipeline_type: draft.pipeline_type,
+            name: draft.name,
+            created_at: draft.created_at,
+        })
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(SuggestPromptEditsResponse {
+        node_name,
+        current_prompt,
+        suggestion,
+        draft_version,
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateEvalProposalRequest {
+    /// Path of the spans to mine, e.g. "my_app.generate_answer"
+    span_path: String,
+    #[serde(default)]
+    sample_size: Option<usize>,
+}
+
+/// Starts generating an eval proposal from recent spans at a path. The proposal is returned
+/// right away in the GENERATING status and becomes READY or FAILED when the job is done.
+#[post("eval-proposals")]
+async fn create_eval_proposal(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateEvalProposalRequest>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    language_model: web::Data<Arc<LanguageModelRunner>>,
+    semantic_search: web::Data<Arc<dyn SemanticSearch>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    let sample_size = req.sample_size.unwrap_or(proposals::DEFAULT_SAMPLE_SIZE);
+    if sample_size == 0 || sample_size > proposals::MAX_SAMPLE_SIZE {
+        return Err(Error::invalid_request(Some(&format!(
+            "Sample size must be between 1 and {}",
+            proposals::MAX_SAMPLE_SIZE
+        ))));
+    }
+    if req.span_path.trim().is_empty() {
+        return Err(Error::invalid_request(Some("Span path must not be empty")));
+    }
+    let db = db.into_inner();
+
+    let env = get_stored_env(db.clone(), project_id).await?;
+    let missing_env_vars = proposals::missing_env_vars(&env);
+    if !missing_env_vars.is_empty() {
+        return Err(Error::invalid_request(Some(&format!(
+            "Add {} to the project's api keys to generate evals",
+            missing_env_vars.join(", ")
+        ))));
+    }
+
+    let proposal =
+        eval_proposals::create_eval_proposal(&db.pool, &project_id, &req.span_path).await?;
+    let response = HttpResponse::Ok().json(&proposal);
+    tokio::spawn(proposals::run_proposal_job(
+        db,
+        cache.into_inner(),
+        language_model.as_ref().clone(),
+        semantic_search.as_ref().clone(),
+        proposal,
+        sample_size,
+    ));
+
+    Ok(response)
+}
+
+#[get("eval-proposals")]
+async fn get_eval_proposals(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let proposals = eval_proposals::get_eval_proposals(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(proposals))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AdoptEvalProposalResponse {
+    dataset: Dataset,
+    label_class: LabelClass,
+}
+
+/// Creates the proposed dataset and registers the proposed judge evaluator for the span path
+#[post("eval-proposals/{proposal_id}/adopt")]
+async fn adopt_eval_proposal(path: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> ResponseResult {
+    let (project_id, proposal_id) = path.into_inner();
+
+    let Some(proposal) =
+        eval_proposals::claim_eval_proposal(&db.pool, &project_id, &proposal_id).await?
+    else {
+        return Err(Error::not_found("No ready eval proposal found"));
+    };
+    match proposals::adopt_proposal(&db, &proposal).await {
+        Ok((dataset, label_class)) => Ok(HttpResponse::Ok().json(AdoptEvalProposalResponse {
+            dataset,
+            label_class,
+        })),
+        Err(e) => {
+            eval_proposals::release_eval_proposal(&db.pool, &proposal.id).await?;
+            Err(e.into())
+        }
+    }
+}
+
+#[delete("eval-proposals/{proposal_id}")]
+async fn delete_eval_proposal(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, proposal_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "eval_proposal", Some(&proposal_id)).await?;
+
+    if !eval_proposals::delete_eval_proposal(&db.pool, &project_id, &proposal_id).await? {
+        return Err(Error::not_found("Eval proposal not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,19 +1,25 @@
+use std::sync::Arc;
+
 use actix_web::{delete, get, post, web, HttpResponse};
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
-    ch::{self, utils::get_bounds, Aggregation},
+    analytics::AnalyticsStore,
+    ch::Aggregation,
     db::{
         self,
         events::EventWithTemplateName,
         modifiers::{AbsoluteDateInterval, DateRange, Filter, RelativeDateInterval},
+        user::User,
         DB,
     },
     routes::{PaginatedGetQueryParams, PaginatedResponse, DEFAULT_PAGE_SIZE},
 };
 
-use super::{GetMetricsQueryParams, ResponseResult};
+use super::{
+    error::Error, legal_holds::ensure_no_legal_hold, GetMetricsQueryParams, ResponseResult,
+};
 
 #[get("event-templates")]
 pub async fn get_event_templates(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
@@ -65,9 +71,18 @@ pub async fn update_event_template(
 #[delete("event-templates/{template_id}")]
 pub async fn delete_event_template(
     path: web::Path<(Uuid, Uuid)>,
+    user: User,
     db: web::Data<DB>,
 ) -> ResponseResult {
-    let (_project_id, template_id) = path.into_inner();
+    let (project_id, template_id) = path.into_inner();
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "event_template",
+        Some(&template_id),
+    )
+    .await?;
     db::event_templates::delete_event_template(&db.pool, &template_id).await?;
 
     Ok(HttpResponse::Ok().finish())
@@ -142,11 +157,11 @@ struct GetEventMetricsParams {
 #[get("event-templates/{event_template_id}/metrics")]
 pub async fn get_events_metrics(
     params: web::Path<(Uuid, Uuid)>,
-    clickhouse: web::Data<clickhouse::Client>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
     req: web::Query<GetEventMetricsParams>,
 ) -> ResponseResult {
     let (project_id, event_template_id) = params.into_inner();
-    let clickhouse = clickhouse.into_inner().as_ref().clone();
+    let analytics_store = analytics_store.as_ref().clone();
     let req = req.into_inner();
     let metric = req.metric;
     let aggregation = req.base_params.aggregation;
@@ -162,8 +177,9 @@ pub async fn get_events_metrics(
 
     let range = if let DateRange::Relative(RelativeDateInterval { past_hours }) = &defaulted_range {
         if past_hours == "all" {
-            let (start_date, end_date) =
-                get_bounds(&clickhouse, &project_id, "events", "timestamp").await?;
+            let (start_date, end_date) = analytics_store
+                .get_bounds(&project_id, "events", "timestamp")
+                .await?;
             DateRange::Absolute(AbsoluteDateInterval {
                 start_date,
                 end_date,
@@ -180,27 +196,25 @@ pub async fn get_events_metrics(
             let past_hours = interval
                 .past_hours
                 .parse::<i64>()
-                .map_err(|e| anyhow::anyhow!("Failed to parse past_hours as i64: {}", e))?;
+                .map_err(|e| Error::invalid_request(Some(&format!("Invalid past_hours: {}", e))))?;
             match metric {
                 EventMetric::EventCount => match aggregation {
                     Aggregation::Total => {
-                        let values = ch::events::get_total_event_count_metrics_relative(
-                            clickhouse,
-                            group_by_interval,
-                            project_id,
-                            event_template_id,
-                            past_hours,
-                        )
-                        .await?;
+                        let values = analytics_store
+                            .get_total_event_count_metrics_relative(
+                                group_by_interval,
+                                project_id,
+                                event_template_id,
+                                past_hours,
+                            )
+                            .await?;
                         Ok(HttpResponse::Ok().json(values))
                     }
                     _ => {
-                        return Err(anyhow::anyhow!(
+                        return Err(Error::invalid_request(Some(&format!(
                             "Unsupported aggregation {:?} for metric {}",
-                            aggregation,
-                            "eventCount"
-                        )
-                        .into());
+                            aggregation, "eventCount"
+                        ))));
                     }
                 },
             }
@@ -208,24 +222,22 @@ pub async fn get_events_metrics(
         DateRange::Absolute(interval) => match metric {
             EventMetric::EventCount => match aggregation {
                 Aggregation::Total => {
-                    let values = ch::events::get_total_event_count_metrics_absolute(
-                        clickhouse,
-                        group_by_interval,
-                        project_id,
-                        event_template_id,
-                        interval.start_date,
-                        interval.end_date,
-                    )
-                    .await?;
+                    let values = analytics_store
+                        .get_total_event_count_metrics_absolute(
+                            group_by_interval,
+                            project_id,
+                            event_template_id,
+                            interval.start_date,
+                            interval.end_date,
+                        )
+                        .await?;
                     Ok(HttpResponse::Ok().json(values))
                 }
                 _ => {
-                    return Err(anyhow::anyhow!(
+                    return Err(Error::invalid_request(Some(&format!(
                         "Unsupported aggregation {:?} for metric {}",
-                        aggregation,
-                        metric
-                    )
-                    .into());
+                        aggregation, metric
+                    ))));
                 }
             },
         },
@@ -0,0 +1,86 @@
+use actix_web::{delete, get, put, web, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+use crate::db::{
+    self,
+    field_visibility::{SensitiveField, WorkspaceRole},
+    user::User,
+    DB,
+};
+
+/// Only owners of the project's workspace can change who sees sensitive fields
+pub(super) async fn require_owner(db: &DB, user: &User, project_id: &Uuid) -> Result<(), Error> {
+    let role =
+        db::field_visibility::get_project_member_role(&db.pool, &user.id, project_id).await?;
+    match role {
+        Some(WorkspaceRole::Owner) => Ok(()),
+        _ => Err(Error::Forbidden),
+    }
+}
+
+#[get("field-visibility-rules")]
+async fn get_field_visibility_rules(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let rules = db::field_visibility::get_field_visibility_rules(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetFieldVisibilityRuleRequest {
+    role: WorkspaceRole,
+    field: SensitiveField,
+    visible: bool,
+}
+
+#[put("field-visibility-rules")]
+async fn set_field_visibility_rule(
+    path: web::Path<Uuid>,
+    user: User,
+    db: web::Data<DB>,
+    req: web::Json<SetFieldVisibilityRuleRequest>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    require_owner(&db, &user, &project_id).await?;
+
+    let rule = db::field_visibility::set_field_visibility_rule(
+        &db.pool,
+        &project_id,
+        req.role,
+        req.field,
+        req.visible,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[delete("field-visibility-rules/{rule_id}")]
+async fn delete_field_visibility_rule(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, rule_id) = path.into_inner();
+    require_owner(&db, &user, &project_id).await?;
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "field_visibility_rule",
+        Some(&rule_id),
+    )
+    .await?;
+
+    let deleted =
+        db::field_visibility::delete_field_visibility_rule(&db.pool, &project_id, &rule_id).await?;
+    if !deleted {
+        return Err(Error::not_found("Field visibility rule not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,218 @@
+use std::sync::Arc;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use lapin::Connection;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{error::Error, ResponseResult};
+use crate::{
+    analytics::AnalyticsStore,
+    backups::{self, EncryptedBackup, MIN_PASSPHRASE_LENGTH},
+    cache::Cache,
+    db::DB,
+    logging, metrics,
+    traces::{
+        archive::{self, PayloadArchive},
+        sdk_versions,
+    },
+};
+
+/// Prometheus scrape endpoint
+#[get("/metrics")]
+async fn get_prometheus_metrics() -> ResponseResult {
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render_prometheus()))
+}
+
+/// Human-readable batch writer stats for tuning flush thresholds
+#[get("batch-writers")]
+async fn get_batch_writer_stats() -> ResponseResult {
+    Ok(HttpResponse::Ok().json(metrics::snapshot()))
+}
+
+/// Ingestion requests by SDK language and version since the process started, with the share of
+/// deprecated versions, to coordinate breaking protocol changes
+#[get("sdk-adoption")]
+async fn get_sdk_adoption() -> ResponseResult {
+    Ok(HttpResponse::Ok().json(sdk_versions::get_sdk_adoption()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplayIngestionArchiveRequest {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    #[serde(default)]
+    project_id: Option<Uuid>,
+}
+
+/// Re-process archived ingestion payloads for the time range through the current pipeline.
+///
+/// Replay may take long for wide ranges, so it runs in the background and the result is logged.
+#[post("replay")]
+async fn replay_ingestion_archive(
+    req: web::Json<ReplayIngestionArchiveRequest>,
+    payload_archive: web::Data<Option<Arc<PayloadArchive>>>,
+    rabbitmq_connection: web::Data<Option<Arc<Connection>>>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let req = req.into_inner();
+    let Some(payload_archive) = payload_archive.as_ref().clone() else {
+        return Err(Error::invalid_request(Some(
+            "Ingestion payload archive is not enabled",
+        )));
+    };
+    if req.start_time > req.end_time {
+        return Err(Error::invalid_request(Some(
+            "startTime must be before endTime",
+        )));
+    }
+    let rabbitmq_connection = rabbitmq_connection.as_ref().clone();
+    let db = db.into_inner();
+    let cache = cache.into_inner();
+
+    logging::spawn(async move {
+        log::info!(
+            "Replaying ingestion archive from {} to {}, project_id: {:?}",
+            req.start_time,
+            req.end_time,
+            req.project_id
+        );
+        match archive::replay(
+            payload_archive,
+            req.start_time,
+            req.end_time,
+            req.project_id,
+            rabbitmq_connection,
+            db,
+            cache,
+        )
+        .await
+        {
+            Ok(stats) => log::info!(
+                "Finished replaying ingestion archive. Replayed: {}, failed: {}",
+                stats.replayed_payloads,
+                stats.failed_payloads
+            ),
+            Err(e) => log::error!("Failed to replay ingestion archive: {:?}", e),
+        }
+    });
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetShadowDiffQuery {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    #[serde(default)]
+    project_id: Option<Uuid>,
+}
+
+/// Diff report between spans written by the primary and the shadow ingestion pipelines
+#[get("ingestion-shadow/diff")]
+async fn get_ingestion_shadow_diff(
+    query: web::Query<GetShadowDiffQuery>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let query = query.into_inner();
+    let analytics_store = analytics_store.as_ref().clone();
+
+    let report = analytics_store
+        .get_shadow_diff_report(query.project_id, query.start_time, query.end_time)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+const BACKUP_PASSPHRASE_HEADER: &str = "X-Backup-Passphrase";
+
+/// The passphrase is sent in a header, so that it doesn't end up in access logs with the path
+fn backup_passphrase(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(BACKUP_PASSPHRASE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+/// Encrypted backup of the workspace's projects and settings, see `backups`
+#[post("workspaces/{workspace_id}/backups")]
+async fn create_workspace_backup(
+    http_req: HttpRequest,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let workspace_id = path.into_inner();
+    let Some(passphrase) = backup_passphrase(&http_req)
+        .filter(|passphrase| passphrase.chars().count() >= MIN_PASSPHRASE_LENGTH)
+    else {
+        return Err(Error::invalid_request(Some(&format!(
+            "{BACKUP_PASSPHRASE_HEADER} must be at least {MIN_PASSPHRASE_LENGTH} characters"
+        ))));
+    };
+
+    let Some(backup) = backups::create_backup(
+        db.into_inner(),
+        analytics_store.as_ref().clone(),
+        workspace_id,
+        passphrase,
+    )
+    .await?
+    else {
+        return Err(Error::not_found("Workspace not found"));
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"workspace-backup-{workspace_id}.json\""),
+        ))
+        .json(backup))
+}
+
+/// Restores the rows of the backup that don't exist, with their original ids. The body is the
+/// backup file.
+#[post("workspace-backups/restore")]
+async fn restore_workspace_backup(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let Some(passphrase) = backup_passphrase(&http_req) else {
+        return Err(Error::invalid_request(Some(&format!(
+            "{BACKUP_PASSPHRASE_HEADER} is required"
+        ))));
+    };
+    let backup = match serde_json::from_slice::<EncryptedBackup>(&body) {
+        Ok(backup) => backup,
+        Err(e) => {
+            return Err(Error::invalid_request(Some(&format!(
+                "Invalid backup file: {e}"
+            ))));
+        }
+    };
+
+    let snapshot = tokio::task::spawn_blocking(move || backups::decrypt(&backup, &passphrase))
+        .await
+        .map_err(anyhow::Error::from)?;
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(e) => return Err(Error::invalid_request(Some(&e.to_string()))),
+    };
+
+    let report = backups::restore_snapshot(db.into_inner(), &snapshot).await?;
+    log::info!(
+        "Restored backup of workspace {} from {}",
+        snapshot.workspace_id,
+        snapshot.created_at
+    );
+
+    Ok(HttpResponse::Ok().json(report))
+}
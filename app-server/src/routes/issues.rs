@@ -0,0 +1,320 @@
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self,
+        issues::{IssueTrackerProvider, LinkedIssue, LinkedIssueResourceType, NewLinkedIssue},
+        user::User,
+        DB,
+    },
+    issues::{self, NewIssue},
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+/// Statuses older than this are fetched from the issue tracker again
+const STATUS_REFRESH_INTERVAL_MINUTES: i64 = 5;
+const MAX_FIELD_LENGTH: usize = 2000;
+
+#[get("issue-trackers")]
+pub async fn get_issue_trackers(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let integrations = db::issues::get_integrations(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(integrations))
+}
+
+#[derive(Deserialize)]
+struct SetIssueTrackerRequest {
+    config: Value,
+    token: String,
+}
+
+/// Jira config: `{"baseUrl", "email", "projectKey", "issueType"?}`, Linear config: `{"teamId"}`
+#[put("issue-trackers/{provider}")]
+pub async fn set_issue_tracker(
+    path: web::Path<(Uuid, IssueTrackerProvider)>,
+    req: web::Json<SetIssueTrackerRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, provider) = path.into_inner();
+    let req = req.into_inner();
+
+    if let Err(e) = issues::tracker(provider, &req.config, req.token.clone()) {
+        return Err(Error::invalid_request(Some(&e.to_string())));
+    }
+    let token = issues::encode_token(&project_id, provider, &req.token);
+    db::issues::set_integration(
+        &db.pool,
+        &project_id,
+        provider,
+        &req.config,
+        &token.nonce,
+        &token.value,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("issue-trackers/{provider}")]
+pub async fn delete_issue_tracker(
+    path: web::Path<(Uuid, IssueTrackerProvider)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, provider) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "issue_tracker", None).await?;
+
+    if !db::issues::delete_integration(&db.pool, &project_id, provider).await? {
+        return Err(Error::not_found("Issue tracker not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn truncate(value: &Value) -> String {
+    let mut text = value.to_string();
+    if text.len() > MAX_FIELD_LENGTH {
+        let mut end = MAX_FIELD_LENGTH;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("...");
+    }
+    text
+}
+
+/// Title and description of the issue, pre-filled from the resource.
+/// Returns None if the resource is not in the project.
+async fn prefill_issue(
+    db: &DB,
+    project_id: &Uuid,
+    resource_type: LinkedIssueResourceType,
+    resource_id: &Uuid,
+) -> anyhow::Result<Option<NewIssue>> {
+    let mut sections = Vec::new();
+    let (title, evaluation_id) = match resource_type {
+        LinkedIssueResourceType::TRACE => {
+            let Some(trace) =
+                db::issues::get_trace_summary(&db.pool, project_id, resource_id).await?
+            else {
+                return Ok(None);
+            };
+            let outcome = if trace.success { "succeeded" } else { "failed" };
+            sections.push(format!("Trace {} {}", trace.id, outcome));
+            if let (Some(start_time), Some(end_time)) = (trace.start_time, trace.end_time) {
+                sections.push(format!(
+                    "Started at {}, took {} ms",
+                    start_time.to_rfc3339(),
+                    (end_time - start_time).num_milliseconds()
+                ));
+            }
+            sections.push(format!(
+                "Tokens: {}, cost: ${:.6}",
+                trace.total_token_count, trace.cost
+            ));
+            (format!("Trace {} {}", trace.id, outcome), None)
+        }
+        LinkedIssueResourceType::EVALUATION_RESULT => {
+            let Some(result) =
+                db::issues::get_evaluation_result_summary(&db.pool, project_id, resource_id)
+                    .await?
+            else {
+                return Ok(None);
+            };
+            sections.push(format!("Evaluation: {}", result.evaluation_name));
+            sections.push(format!("Data: {}", truncate(&result.data)));
+            sections.push(format!("Target: {}", truncate(&result.target)));
+            if let Some(executor_output) = &result.executor_output {
+                sections.push(format!("Output: {}", truncate(executor_output)));
+            }
+            if let Some(scores) = &result.scores {
+                sections.push(format!("Scores: {}", truncate(scores)));
+            }
+            sections.push(format!("Trace: {}", result.trace_id));
+            (
+                format!(
+                    "Evaluation {}: datapoint {}",
+                    result.evaluation_name, result.id
+                ),
+                Some(result.evaluation_id),
+            )
+        }
+    };
+
+    if let Some(link) = issues::backlink(
+        project_id,
+        resource_type,
+        resource_id,
+        evaluation_id.as_ref(),
+    ) {
+        sections.push(format!("View in Laminar: {}", link));
+    }
+
+    Ok(Some(NewIssue {
+        title,
+        description: sections.join("\n\n"),
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateLinkedIssueRequest {
+    provider: IssueTrackerProvider,
+    resource_type: LinkedIssueResourceType,
+    resource_id: Uuid,
+    /// Overrides the pre-filled title
+    #[serde(default)]
+    title: Option<String>,
+    /// Prepended to the pre-filled description
+    #[serde(default)]
+    description: Option<String>,
+}
+
+/// Creates an issue in the tracker, pre-filled from the resource, and links it to the resource
+#[post("linked-issues")]
+pub async fn create_linked_issue(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateLinkedIssueRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+
+    let Some(integration) =
+        db::issues::get_integration(&db.pool, &project_id, req.provider).await?
+    else {
+        return Err(Error::invalid_request(Some(
+            "Issue tracker is not configured for this project",
+        )));
+    };
+    let Some(mut issue) =
+        prefill_issue(&db, &project_id, req.resource_type, &req.resource_id).await?
+    else {
+        return Err(Error::not_found("Resource not found"));
+    };
+    if let Some(title) = req.title.filter(|title| !title.trim().is_empty()) {
+        issue.title = title;
+    }
+    if let Some(description) = req.description {
+        issue.description = format!("{}\n\n{}", description, issue.description);
+    }
+
+    let tracker = issues::tracker_from_integration(&project_id, &integration)?;
+    let created = match tracker.create_issue(&issue).await {
+        Ok(created) => created,
+        Err(e) => {
+            log::error!("Failed to create {:?} issue: {}", req.provider, e);
+            return Ok(HttpResponse::BadGateway().json(format!("Failed to create issue: {}", e)));
+        }
+    };
+
+    let linked_issue = db::issues::create_linked_issue(
+        &db.pool,
+        &project_id,
+        &NewLinkedIssue {
+            resource_type: req.resource_type,
+            resource_id: req.resource_id,
+            provider: req.provider,
+            external_id: created.external_id,
+            key: created.key,
+            url: created.url,
+            title: issue.title,
+        },
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(linked_issue))
+}
+
+async fn refresh_status(db: &DB, project_id: &Uuid, issue: &mut LinkedIssue) -> anyhow::Result<()> {
+    let Some(integration) =
+        db::issues::get_integration(&db.pool, project_id, issue.provider).await?
+    else {
+        return Ok(());
+    };
+    let tracker = issues::tracker_from_integration(project_id, &integration)?;
+    let status = tracker.get_issue_status(&issue.external_id).await?;
+    db::issues::set_linked_issue_status(&db.pool, &issue.id, &status).await?;
+    issue.status = Some(status);
+    issue.status_updated_at = Some(Utc::now());
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetLinkedIssuesQuery {
+    resource_type: LinkedIssueResourceType,
+    resource_id: Uuid,
+}
+
+/// Linked issues of the resource. Stale statuses are refreshed from the issue tracker,
+/// if that fails the last known status is returned.
+#[get("linked-issues")]
+pub async fn get_linked_issues(
+    path: web::Path<Uuid>,
+    query: web::Query<GetLinkedIssuesQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    let mut linked_issues = db::issues::get_linked_issues(
+        &db.pool,
+        &project_id,
+        query.resource_type,
+        &query.resource_id,
+    )
+    .await?;
+
+    let stale_before = Utc::now() - Duration::minutes(STATUS_REFRESH_INTERVAL_MINUTES);
+    futures::future::join_all(
+        linked_issues
+            .iter_mut()
+            .filter(|issue| {
+                issue
+                    .status_updated_at
+                    .map_or(true, |updated_at| updated_at < stale_before)
+            })
+            .map(|issue| {
+                let db = &db;
+                async move {
+                    if let Err(e) = refresh_status(db, &project_id, issue).await {
+                        log::warn!("Failed to refresh status of issue {}: {}", issue.key, e);
+                    }
+                }
+            }),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(linked_issues))
+}
+
+/// Unlinks the issue, it is not deleted in the issue tracker
+#[delete("linked-issues/{linked_issue_id}")]
+pub async fn delete_linked_issue(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, linked_issue_id) = path.into_inner();
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "linked_issue",
+        Some(&linked_issue_id),
+    )
+    .await?;
+
+    if !db::issues::delete_linked_issue(&db.pool, &project_id, &linked_issue_id).await? {
+        return Err(Error::not_found("Linked issue not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,127 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    db::{self, judge_evaluators::NewJudgeEvaluator, user::User, DB},
+    evaluations::{judge, utils::ScoreType},
+    language_model::providers::utils::get_required_env_vars_for_model,
+    traces::online_evaluations::invalidate_online_evaluation_rules,
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+const MAX_NAME_LENGTH: usize = 64;
+
+#[get("judge-evaluators")]
+pub async fn get_judge_evaluators(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let judges = db::judge_evaluators::get_judge_evaluators(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(judges))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateJudgeEvaluatorRequest {
+    name: String,
+    /// In the format of "provider:model_name", e.g. "openai:gpt-4o-mini"
+    model: String,
+    /// Handlebars template over `input`, `output` and `target` of the datapoint
+    prompt: String,
+    score_type: ScoreType,
+    /// Allowed labels, required for categorical scores
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+/// Registers a judge that evaluations submitted through the API can name to be scored on the
+/// server, with the project's provider api keys
+#[post("judge-evaluators")]
+pub async fn create_judge_evaluator(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateJudgeEvaluatorRequest>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(Error::invalid_request(Some(&format!(
+            "Name must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        ))));
+    }
+    let model = req.model.trim();
+    if !model.contains(':') || get_required_env_vars_for_model(model).is_empty() {
+        return Err(Error::invalid_request(Some(
+            "Model must be in the format of provider:model_name with a supported provider",
+        )));
+    }
+    if let Err(e) = judge::validate_prompt(&req.prompt) {
+        return Err(Error::invalid_request(Some(&format!(
+            "Invalid prompt template: {}",
+            e
+        ))));
+    }
+    let labels = req
+        .labels
+        .iter()
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty())
+        .collect::<Vec<_>>();
+    match req.score_type {
+        ScoreType::CATEGORICAL if labels.is_empty() => {
+            return Err(Error::invalid_request(Some(
+                "Categorical judges require at least one label",
+            )));
+        }
+        ScoreType::NUMERIC | ScoreType::BOOLEAN if !labels.is_empty() => {
+            return Err(Error::invalid_request(Some(
+                "Only categorical judges have labels",
+            )));
+        }
+        _ => {}
+    }
+
+    let judge = NewJudgeEvaluator {
+        name: name.to_string(),
+        model: model.to_string(),
+        prompt: req.prompt,
+        score_type: req.score_type,
+        labels,
+    };
+    let Some(judge) =
+        db::judge_evaluators::create_judge_evaluator(&db.pool, &project_id, &judge).await?
+    else {
+        return Err(Error::invalid_request(Some(&format!(
+            "Judge evaluator {} already exists",
+            name
+        ))));
+    };
+    // Online evaluation rules that name the judge start running it
+    invalidate_online_evaluation_rules(cache.into_inner(), project_id).await;
+
+    Ok(HttpResponse::Ok().json(judge))
+}
+
+#[delete("judge-evaluators/{judge_id}")]
+pub async fn delete_judge_evaluator(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let (project_id, judge_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "judge_evaluator", Some(&judge_id)).await?;
+    let Some(judge) =
+        db::judge_evaluators::delete_judge_evaluator(&db.pool, &project_id, &judge_id).await?
+    else {
+        return Err(Error::not_found("Judge evaluator not found"));
+    };
+    invalidate_online_evaluation_rules(cache.into_inner(), project_id).await;
+
+    Ok(HttpResponse::Ok().json(judge))
+}
@@ -0,0 +1,320 @@
+//! Labeling queues, where spans and evaluation results are reviewed by annotators.
+//!
+//! Annotators claim the items of a queue one at a time. A claimed item is locked for the
+//! annotator until they complete or release it, or until the lock expires, so that several
+//! annotators can work through the same queue.
+
+use std::{collections::HashSet, sync::Arc};
+
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    db::{
+        self,
+        labels::{LabelJobStatus, LabelSource},
+        user::User,
+        DB,
+    },
+    evaluations::utils::LabelingQueueEntry,
+};
+
+use super::{
+    error::{Error, ErrorCode},
+    labels::record_span_score,
+    legal_holds::ensure_no_legal_hold,
+    ResponseResult,
+};
+
+/// How long a claimed item stays locked for the annotator
+const ITEM_LOCK_SECS: f64 = 600.0;
+
+#[get("labeling-queues")]
+pub async fn get_labeling_queues(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let queues = db::labeling_queues::get_labeling_queues(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(queues))
+}
+
+#[derive(Deserialize)]
+struct CreateLabelingQueueRequest {
+    name: String,
+}
+
+#[post("labeling-queues")]
+pub async fn create_labeling_queue(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateLabelingQueueRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let name = req.into_inner().name;
+    if name.trim().is_empty() {
+        return Err(Error::invalid_request(Some("Queue name must not be empty")));
+    }
+    // Evaluations push to queues by name, so names are unique in the project
+    if db::labeling_queues::get_labeling_queue_by_name(&db.pool, &name, &project_id)
+        .await?
+        .is_some()
+    {
+        return Err(Error::invalid_request(Some(
+            "A queue with this name already exists",
+        )));
+    }
+
+    let queue = db::labeling_queues::create_labeling_queue(&db.pool, &project_id, &name).await?;
+
+    Ok(HttpResponse::Ok().json(queue))
+}
+
+#[delete("labeling-queues/{queue_id}")]
+pub async fn delete_labeling_queue(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, queue_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "labeling_queue", Some(&queue_id)).await?;
+    if !db::labeling_queues::delete_labeling_queue(&db.pool, &project_id, &queue_id).await? {
+        return Err(Error::not_found("Labeling queue not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PushLabelingQueueItemsRequest {
+    #[serde(default)]
+    span_ids: Vec<Uuid>,
+    #[serde(default)]
+    evaluation_result_ids: Vec<Uuid>,
+}
+
+#[post("labeling-queues/{queue_id}/items")]
+pub async fn push_labeling_queue_items(
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<PushLabelingQueueItemsRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, queue_id) = path.into_inner();
+    let req = req.into_inner();
+    if db::labeling_queues::get_labeling_queue(&db.pool, &project_id, &queue_id)
+        .await?
+        .is_none()
+    {
+        return Err(Error::not_found("Labeling queue not found"));
+    }
+
+    let span_ids = req
+        .span_ids
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let result_ids = req
+        .evaluation_result_ids
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let found_span_ids =
+        db::labeling_queues::get_project_span_ids(&db.pool, &project_id, &span_ids).await?;
+    if found_span_ids.len() != span_ids.len() {
+        return Err(Error::invalid_request(Some(
+            "Some spans were not found in the project",
+        )));
+    }
+    let result_spans =
+        db::labeling_queues::get_evaluation_result_spans(&db.pool, &project_id, &result_ids)
+            .await?;
+    if result_spans.len() != result_ids.len() {
+        return Err(Error::invalid_request(Some(
+            "Some evaluation results were not found in the project",
+        )));
+    }
+
+    // Same action as the items pushed by evaluations, see `datapoints_to_labeling_queues`
+    let entries = found_span_ids
+        .into_iter()
+        .map(|span_id| LabelingQueueEntry {
+            span_id,
+            action: serde_json::json!({}),
+        })
+        .chain(
+            result_spans
+                .into_iter()
+                .map(|(result_id, span_id)| LabelingQueueEntry {
+                    span_id,
+                    action: serde_json::json!({
+                        "resultId": result_id.to_string(),
+                    }),
+                }),
+        )
+        .collect::<Vec<_>>();
+    if !entries.is_empty() {
+        db::labeling_queues::push_to_labeling_queue(&db.pool, &queue_id, &entries).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "pushedCount": entries.len() })))
+}
+
+/// Claims the next item of the queue, or the item the user already holds. No content if all
+/// items are completed or locked by others.
+#[post("labeling-queues/{queue_id}/items/next")]
+pub async fn claim_next_labeling_queue_item(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    user: User,
+) -> ResponseResult {
+    let (project_id, queue_id) = path.into_inner();
+    if db::labeling_queues::get_labeling_queue(&db.pool, &project_id, &queue_id)
+        .await?
+        .is_none()
+    {
+        return Err(Error::not_found("Labeling queue not found"));
+    }
+
+    let item =
+        db::labeling_queues::claim_next_item(&db.pool, &queue_id, &user.id, ITEM_LOCK_SECS).await?;
+
+    match item {
+        Some(item) => Ok(HttpResponse::Ok().json(item)),
+        None => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+#[post("labeling-queues/{queue_id}/items/{item_id}/release")]
+pub async fn release_labeling_queue_item(
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    db: web::Data<DB>,
+    user: User,
+) -> ResponseResult {
+    let (project_id, queue_id, item_id) = path.into_inner();
+    if db::labeling_queues::get_labeling_queue(&db.pool, &project_id, &queue_id)
+        .await?
+        .is_none()
+    {
+        return Err(Error::not_found("Labeling queue not found"));
+    }
+
+    if !db::labeling_queues::release_item(&db.pool, &queue_id, &item_id, &user.id).await? {
+        return Err(Error::not_found("Labeling queue item not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemLabel {
+    class_id: Uuid,
+    value: f64,
+    #[serde(default)]
+    reasoning: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompleteLabelingQueueItemRequest {
+    #[serde(default)]
+    labels: Vec<ItemLabel>,
+}
+
+/// Records the annotator's labels on the span of the item and marks the item as completed.
+/// Manual labels are kept next to the automatic ones of the same class.
+#[post("labeling-queues/{queue_id}/items/{item_id}/complete")]
+pub async fn complete_labeling_queue_item(
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    req: web::Json<CompleteLabelingQueueItemRequest>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    user: User,
+) -> ResponseResult {
+    let (project_id, queue_id, item_id) = path.into_inner();
+    let labels = req.into_inner().labels;
+    if db::labeling_queues::get_labeling_queue(&db.pool, &project_id, &queue_id)
+        .await?
+        .is_none()
+    {
+        return Err(Error::not_found("Labeling queue not found"));
+    }
+    let Some(item) =
+        db::labeling_queues::get_labeling_queue_item(&db.pool, &queue_id, &item_id).await?
+    else {
+        return Err(Error::not_found("Labeling queue item not found"));
+    };
+    if item.completed_at.is_some() {
+        return Err(Error::invalid_request(Some("Item is already completed")));
+    }
+    let locked_by_other = item.assigned_to.is_some_and(|id| id != user.id)
+        && item
+            .locked_until
+            .is_some_and(|locked_until| locked_until > chrono::Utc::now());
+    if locked_by_other {
+        return Err(Error::api(
+            ErrorCode::Conflict,
+            "Item is locked by another annotator",
+        ));
+    }
+
+    let class_ids = labels
+        .iter()
+        .map(|label| label.class_id)
+        .collect::<Vec<_>>();
+    let label_classes =
+        db::labels::get_label_classes_by_project_id(&db.pool, project_id, Some(class_ids)).await?;
+    if labels
+        .iter()
+        .any(|label| !label_classes.iter().any(|c| c.id == label.class_id))
+    {
+        return Err(Error::invalid_request(Some("Label class not found")));
+    }
+
+    for label in labels {
+        db::labels::update_span_label(
+            &db.pool,
+            item.span_id,
+            label.value,
+            Some(user.id),
+            label.class_id,
+            LabelSource::MANUAL,
+            Some(LabelJobStatus::DONE),
+            label.reasoning,
+        )
+        .await?;
+
+        // span scores are analytics only, the label is saved even if they can't be written
+        if let Err(e) = record_span_score(
+            &db,
+            analytics_store.as_ref().clone(),
+            project_id,
+            item.span_id,
+            label.class_id,
+            label.value,
+            Some(user.id),
+        )
+        .await
+        {
+            log::error!(
+                "Failed to record span score. project_id [{}], span_id [{}]: {:?}",
+                project_id,
+                item.span_id,
+                e
+            );
+        }
+    }
+
+    // The lock may have expired and been taken by someone else since it was checked
+    match db::labeling_queues::complete_item(&db.pool, &queue_id, &item_id, &user.id).await? {
+        Some(item) => Ok(HttpResponse::Ok().json(item)),
+        None => Err(Error::api(
+            ErrorCode::Conflict,
+            "Item is locked by another annotator",
+        )),
+    }
+}
@@ -1,16 +1,24 @@
+use std::sync::Arc;
+
 use actix_web::{delete, get, post, web, HttpResponse};
+use chrono::Utc;
 use serde::Deserialize;
 use serde_json::Value;
 use uuid::Uuid;
 
-use crate::db::{
-    self,
-    labels::{LabelJobStatus, LabelSource},
-    user::User,
-    DB,
+use crate::{
+    analytics::AnalyticsStore,
+    ch::span_scores::SpanScore,
+    db::{
+        self,
+        field_visibility::FieldVisibility,
+        labels::{LabelJobStatus, LabelSource},
+        user::User,
+        DB,
+    },
 };
 
-use super::ResponseResult;
+use super::{legal_holds::ensure_no_legal_hold, ResponseResult};
 
 #[get("label-classes")]
 pub async fn get_label_types(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
@@ -77,9 +85,11 @@ pub async fn register_label_class_for_path(
 #[delete("label-classes/{class_id}/registered-paths/{id}")]
 pub async fn remove_label_class_from_path(
     path: web::Path<(Uuid, Uuid, Uuid)>,
+    user: User,
     db: web::Data<DB>,
 ) -> ResponseResult {
     let (project_id, class_id, id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "label_class_path", Some(&id)).await?;
 
     db::labels::remove_label_class_from_path(&db.pool, project_id, class_id, id).await?;
 
@@ -120,9 +130,10 @@ pub async fn update_span_label(
     path: web::Path<(Uuid, Uuid)>,
     req: web::Json<UpdateSpanLabelRequest>,
     db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
     user: User,
 ) -> ResponseResult {
-    let (_project_id, span_id) = path.into_inner();
+    let (project_id, span_id) = path.into_inner();
     let req = req.into_inner();
     let class_id = req.class_id;
     let value = req.value;
@@ -148,15 +159,65 @@ pub async fn update_span_label(
         reasoning,
     )
     .await?;
+
+    // span scores are analytics only, the label is saved even if they can't be written
+    if let Err(e) = record_span_score(
+        &db,
+        analytics_store.as_ref().clone(),
+        project_id,
+        span_id,
+        class_id,
+        value,
+        user_id,
+    )
+    .await
+    {
+        log::error!(
+            "Failed to record span score. project_id [{}], span_id [{}]: {:?}",
+            project_id,
+            span_id,
+            e
+        );
+    }
+
     Ok(HttpResponse::Ok().json(label))
 }
 
+pub(super) async fn record_span_score(
+    db: &DB,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    project_id: Uuid,
+    span_id: Uuid,
+    class_id: Uuid,
+    value: f64,
+    user_id: Option<Uuid>,
+) -> anyhow::Result<()> {
+    let span = db::spans::get_span(&db.pool, span_id, project_id, &FieldVisibility::ALL).await?;
+    let label_class = db::labels::get_label_class(&db.pool, project_id, class_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Label class {} not found", class_id))?;
+
+    analytics_store
+        .insert_span_scores(vec![SpanScore {
+            project_id,
+            timestamp: Utc::now(),
+            span_id,
+            trace_id: span.trace_id,
+            name: label_class.name,
+            value,
+            user_id: user_id.unwrap_or(Uuid::nil()),
+        }])
+        .await
+}
+
 #[delete("spans/{span_id}/labels/{label_id}")]
 pub async fn delete_span_label(
     path: web::Path<(Uuid, Uuid, Uuid)>,
+    user: User,
     db: web::Data<DB>,
 ) -> ResponseResult {
-    let (_project_id, span_id, label_id) = path.into_inner();
+    let (project_id, span_id, label_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "span_label", Some(&label_id)).await?;
 
     db::labels::delete_span_label(&db.pool, span_id, label_id).await?;
 
@@ -0,0 +1,103 @@
+use actix_web::{delete, get, put, web, HttpResponse};
+use chrono::{Duration, DurationRound, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db::{self, lake_exports::NewLakeExport, user::User, DB},
+    lake_export,
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+/// Hours before the current one that a new export starts from
+const MAX_BACKFILL_HOURS: i64 = 30 * 24;
+
+#[get("lake-export")]
+pub async fn get_lake_export(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+
+    match db::lake_exports::get_lake_export(&db.pool, &project_id).await? {
+        Some(export) => Ok(HttpResponse::Ok().json(export)),
+        None => Err(Error::not_found("Lake export not found")),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetLakeExportRequest {
+    bucket: String,
+    #[serde(default)]
+    prefix: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    /// Only used when the export is created, existing exports continue where they are
+    #[serde(default)]
+    backfill_hours: i64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Exports the project's spans, scores and events to the bucket every hour. The credentials need
+/// `s3:ListBucket` on the bucket, which is checked before they are saved, and `s3:PutObject` on
+/// the prefix.
+#[put("lake-export")]
+pub async fn set_lake_export(
+    path: web::Path<Uuid>,
+    req: web::Json<SetLakeExportRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    if !(0..=MAX_BACKFILL_HOURS).contains(&req.backfill_hours) {
+        return Err(Error::invalid_request(Some(&format!(
+            "backfillHours must be between 0 and {MAX_BACKFILL_HOURS}"
+        ))));
+    }
+
+    let client = lake_export::s3_client(&req.region, &req.access_key_id, &req.secret_access_key);
+    if let Err(e) = lake_export::check_bucket_access(&client, &req.bucket).await {
+        return Err(Error::invalid_request(Some(&format!(
+            "Bucket is not accessible with the credentials: {e}"
+        ))));
+    }
+
+    let secret = lake_export::encode_secret(&project_id, &req.secret_access_key);
+    let current_hour = Utc::now()
+        .duration_trunc(Duration::hours(1))
+        .map_err(anyhow::Error::from)?;
+    let export = NewLakeExport {
+        bucket: req.bucket,
+        prefix: lake_export::normalize_prefix(&req.prefix),
+        region: req.region,
+        access_key_id: req.access_key_id,
+        secret_nonce: secret.nonce,
+        secret_value: secret.value,
+        enabled: req.enabled,
+        exported_until: current_hour - Duration::hours(req.backfill_hours),
+    };
+    let info = db::lake_exports::set_lake_export(&db.pool, &project_id, &export).await?;
+
+    Ok(HttpResponse::Ok().json(info))
+}
+
+#[delete("lake-export")]
+pub async fn delete_lake_export(
+    path: web::Path<Uuid>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "lake_export", None).await?;
+
+    if !db::lake_exports::delete_lake_export(&db.pool, &project_id).await? {
+        return Err(Error::not_found("Lake export not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,139 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{
+    error::{Error, ErrorCode},
+    field_visibility::require_owner,
+    PaginatedGetQueryParams, ResponseResult, DEFAULT_PAGE_SIZE,
+};
+use crate::db::{self, user::User, DB};
+
+/// Rejects the deletion of project data while the project is under legal hold. Called by every
+/// user-initiated delete of a project's resources. Blocked deletions are written to the audit log.
+pub(super) async fn ensure_no_legal_hold(
+    db: &DB,
+    project_id: &Uuid,
+    user: &User,
+    resource: &str,
+    resource_id: Option<&Uuid>,
+) -> Result<(), Error> {
+    let Some(hold) = db::legal_holds::get_active_legal_hold(&db.pool, project_id).await? else {
+        return Ok(());
+    };
+    db::legal_holds::write_audit_log(
+        &db.pool,
+        project_id,
+        Some(&user.id),
+        "delete_blocked",
+        &json!({
+            "legalHoldId": hold.id,
+            "resource": resource,
+            "resourceId": resource_id,
+        }),
+    )
+    .await?;
+    log::info!(
+        "Blocked deletion of {} in project {} under legal hold {}",
+        resource,
+        project_id,
+        hold.id
+    );
+
+    Err(Error::api(
+        ErrorCode::UnderLegalHold,
+        format!("The project is under legal hold since {}", hold.created_at),
+    ))
+}
+
+#[get("legal-holds")]
+async fn get_legal_holds(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let holds = db::legal_holds::get_legal_holds(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(holds))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaceLegalHoldRequest {
+    reason: String,
+}
+
+/// Suspends the deletion of the project's data until the hold is released
+#[post("legal-holds")]
+async fn place_legal_hold(
+    path: web::Path<Uuid>,
+    user: User,
+    db: web::Data<DB>,
+    req: web::Json<PlaceLegalHoldRequest>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let reason = req.into_inner().reason.trim().to_string();
+    require_owner(&db, &user, &project_id).await?;
+    if reason.is_empty() {
+        return Err(Error::invalid_request(Some(
+            "The reason of the legal hold is required",
+        )));
+    }
+
+    let Some(hold) =
+        db::legal_holds::place_legal_hold(&db.pool, &project_id, &user.id, &reason).await?
+    else {
+        return Err(Error::invalid_request(Some(
+            "The project is already under legal hold",
+        )));
+    };
+    db::legal_holds::write_audit_log(
+        &db.pool,
+        &project_id,
+        Some(&user.id),
+        "hold_placed",
+        &json!({ "legalHoldId": hold.id, "reason": reason }),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+#[post("legal-holds/release")]
+async fn release_legal_hold(
+    path: web::Path<Uuid>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    require_owner(&db, &user, &project_id).await?;
+
+    let Some(hold) = db::legal_holds::release_legal_hold(&db.pool, &project_id, &user.id).await?
+    else {
+        return Err(Error::not_found("The project is not under legal hold"));
+    };
+    db::legal_holds::write_audit_log(
+        &db.pool,
+        &project_id,
+        Some(&user.id),
+        "hold_released",
+        &json!({ "legalHoldId": hold.id }),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+#[get("legal-holds/audit-log")]
+async fn get_legal_hold_audit_log(
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    query_params: web::Query<PaginatedGetQueryParams>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let limit = query_params.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = limit * query_params.page_number;
+
+    let entries =
+        db::legal_holds::get_audit_log(&db.pool, &project_id, limit as i64, offset as i64).await?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
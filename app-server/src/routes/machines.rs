@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use actix_web::{get, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{
+    error::{Error, ErrorCode},
+    ResponseResult, DEFAULT_PAGE_SIZE,
+};
+use crate::{
+    db::{
+        machines::{self, MachinePoolUsage, MachineStatus},
+        DB,
+    },
+    machine_manager::{self, pool::pool_capacity, MachineManager},
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetMachinesQuery {
+    /// Also list terminated and failed machines
+    #[serde(default)]
+    include_ended: bool,
+}
+
+#[get("machines")]
+async fn get_machines(
+    path: web::Path<Uuid>,
+    query: web::Query<GetMachinesQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let machines = machines::get_machines(
+        &db.pool,
+        &project_id,
+        query.include_ended,
+        DEFAULT_PAGE_SIZE as i64,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(machines))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MachinePoolResponse {
+    capacity: i64,
+    #[serde(flatten)]
+    usage: MachinePoolUsage,
+}
+
+/// Capacity of the pool shared by all projects, and the machines of the project in it
+#[get("machine-pool")]
+async fn get_machine_pool(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let usage = machines::get_pool_usage(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(MachinePoolResponse {
+        capacity: pool_capacity(),
+        usage,
+    }))
+}
+
+/// Checks the machine's health now, instead of waiting for the pool's periodic check
+#[get("machines/{machine_id}/health")]
+async fn get_machine_health(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    machine_manager: web::Data<Arc<dyn MachineManager>>,
+) -> ResponseResult {
+    let (project_id, machine_id) = path.into_inner();
+    let machine = machines::get_machine(&db.pool, &project_id, &machine_id)
+        .await?
+        .ok_or_else(|| {
+            Error::api(
+                ErrorCode::MachineNotFound,
+                format!("machine {} not found", machine_id),
+            )
+        })?;
+
+    let healthy = match (machine.status, &machine.provider_machine_id) {
+        (MachineStatus::RUNNING, Some(provider_machine_id)) => {
+            machine_manager.is_healthy(provider_machine_id).await?
+        }
+        _ => false,
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": machine.status,
+        "healthy": healthy,
+    })))
+}
+
+/// Terminates the machine even if an agent is still using it
+#[post("machines/{machine_id}/terminate")]
+async fn terminate_machine(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    machine_manager: web::Data<Arc<dyn MachineManager>>,
+) -> ResponseResult {
+    let (project_id, machine_id) = path.into_inner();
+    let machine = machine_manager::terminate_machine(
+        &db,
+        machine_manager.as_ref().as_ref(),
+        &project_id,
+        &machine_id,
+    )
+    .await?
+    .ok_or_else(|| {
+        Error::api(
+            ErrorCode::MachineNotFound,
+            format!("machine {} not found", machine_id),
+        )
+    })?;
+
+    Ok(HttpResponse::Ok().json(machine))
+}
+
+#[derive(Deserialize)]
+struct UpdateMachineLimitRequest {
+    /// None removes the limit
+    limit: Option<i32>,
+}
+
+/// Limit the machines the project can have at once, further machines wait in the queue
+#[post("machine-limit")]
+async fn update_machine_limit(
+    path: web::Path<Uuid>,
+    req: web::Json<UpdateMachineLimitRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let limit = req.into_inner().limit;
+    if limit.is_some_and(|limit| limit < 0) {
+        return Err(Error::invalid_request(Some(
+            "Machine limit must not be negative",
+        )));
+    }
+
+    machines::set_machine_limit(&db.pool, &project_id, limit).await?;
+    let usage = machines::get_pool_usage(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(MachinePoolResponse {
+        capacity: pool_capacity(),
+        usage,
+    }))
+}
@@ -0,0 +1,77 @@
+use actix_web::{delete, get, put, web, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{
+    error::Error, field_visibility::require_owner, legal_holds::ensure_no_legal_hold,
+    ResponseResult,
+};
+use crate::db::{self, field_visibility::WorkspaceRole, user::User, DB};
+
+#[get("masking-profiles")]
+async fn get_masking_profiles(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let profiles = db::masking_profiles::get_masking_profiles(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(profiles))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetMaskingProfileRequest {
+    role: WorkspaceRole,
+    #[serde(default)]
+    hash_user_ids: bool,
+    #[serde(default)]
+    redact_emails: bool,
+}
+
+/// Sets the masking of the data served to members of the role. Stored data is not changed.
+#[put("masking-profiles")]
+async fn set_masking_profile(
+    path: web::Path<Uuid>,
+    user: User,
+    db: web::Data<DB>,
+    req: web::Json<SetMaskingProfileRequest>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    require_owner(&db, &user, &project_id).await?;
+
+    let profile = db::masking_profiles::set_masking_profile(
+        &db.pool,
+        &project_id,
+        req.role,
+        req.hash_user_ids,
+        req.redact_emails,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+#[delete("masking-profiles/{profile_id}")]
+async fn delete_masking_profile(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, profile_id) = path.into_inner();
+    require_owner(&db, &user, &project_id).await?;
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "masking_profile",
+        Some(&profile_id),
+    )
+    .await?;
+
+    let deleted =
+        db::masking_profiles::delete_masking_profile(&db.pool, &project_id, &profile_id).await?;
+    if !deleted {
+        return Err(Error::not_found("Masking profile not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,29 +1,67 @@
+pub mod activity;
+pub mod alerts;
+pub mod analytics;
+pub mod analytics_definitions;
 pub mod api_keys;
 pub mod auth;
+pub mod comments;
+pub mod compliance;
+pub mod custom_metrics;
 pub mod datasets;
 pub mod error;
+pub mod evaluation_schedules;
 pub mod evaluations;
 pub mod events;
+pub mod field_visibility;
+pub mod internal;
+pub mod issues;
+pub mod judge_evaluators;
+pub mod labeling_queues;
 pub mod labels;
+pub mod lake_exports;
+pub mod legal_holds;
 pub mod limits;
+pub mod machines;
+pub mod masking_profiles;
+pub mod online_evaluation_rules;
+pub mod organizations;
+pub mod personal_access_tokens;
 pub mod pipelines;
 pub mod projects;
+pub mod promoted_attributes;
 pub mod provider_api_keys;
+pub mod scim;
+pub mod sessions;
+pub mod shadow_deployments;
+pub mod span_scores;
 pub mod subscriptions;
+pub mod tags;
 pub mod traces;
 pub mod types;
+pub mod warehouse_syncs;
 pub mod workspace;
 
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use types::*;
 
 use crate::{
-    ch::{modifiers::GroupByInterval, Aggregation},
+    ch::{
+        filter_expression::{CompiledFilter, FilterExpression, FilterTarget},
+        modifiers::GroupByInterval,
+        Aggregation,
+    },
     db::modifiers::DateRange,
 };
 
+use self::error::Error;
+
 pub const DEFAULT_PAGE_SIZE: usize = 50;
+const DEFAULT_FILTER_QUERY_LIMIT: u64 = 100;
+const MAX_FILTER_QUERY_LIMIT: u64 = 1000;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -67,3 +105,85 @@ struct GetMetricsQueryParams {
     #[serde(default)]
     pub group_by_interval: GroupByInterval,
 }
+
+/// Body of the endpoints that query spans, traces and evaluation scores with a filter
+/// expression, see `ch::filter_expression`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterQueryRequest {
+    /// Matches everything if missing or empty
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Defaults to the past 24 hours
+    #[serde(default, flatten)]
+    pub date_range: Option<DateRange>,
+    #[serde(default)]
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub offset: u64,
+}
+
+impl FilterQueryRequest {
+    /// Parses and compiles the filter for the target. Filters on `hidden_fields` are rejected,
+    /// so that they can't be used to infer the values that are hidden from the user.
+    pub fn compile_filter(
+        &self,
+        target: FilterTarget,
+        attribute_slots: &HashMap<String, usize>,
+        hidden_fields: &[&str],
+    ) -> Result<CompiledFilter, Error> {
+        let Some(filter) = self.filter.as_deref().filter(|f| !f.trim().is_empty()) else {
+            return Ok(CompiledFilter::all());
+        };
+        let expression = FilterExpression::parse(filter)
+            .map_err(|e| Error::invalid_request(Some(&e.to_string())))?;
+        if let Some(field) = expression
+            .fields()
+            .into_iter()
+            .find(|field| hidden_fields.contains(field))
+        {
+            return Err(Error::invalid_request(Some(&format!(
+                "Field {} is hidden for your role and can't be filtered on",
+                field
+            ))));
+        }
+        expression
+            .compile(target, attribute_slots)
+            .map_err(|e| Error::invalid_request(Some(&e.to_string())))
+    }
+
+    pub fn time_range(&self) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+        let (start_time, end_time) = match self.date_range.clone().unwrap_or_default() {
+            DateRange::Relative(interval) if interval.past_hours == "all" => {
+                (DateTime::UNIX_EPOCH, Utc::now())
+            }
+            DateRange::Relative(interval) => {
+                let past_hours = interval
+                    .past_hours
+                    .parse::<i64>()
+                    .map_err(|_| Error::invalid_request(Some("Invalid pastHours")))?;
+                (Utc::now() - chrono::Duration::hours(past_hours), Utc::now())
+            }
+            DateRange::Absolute(interval) => (interval.start_date, interval.end_date),
+        };
+        if start_time > end_time {
+            return Err(Error::invalid_request(Some(
+                "Start date must be before end date",
+            )));
+        }
+        Ok((start_time, end_time))
+    }
+
+    pub fn limit(&self) -> Result<u64, Error> {
+        match self.limit {
+            None => Ok(DEFAULT_FILTER_QUERY_LIMIT),
+            Some(limit) if limit == 0 || limit > MAX_FILTER_QUERY_LIMIT => {
+                Err(Error::invalid_request(Some(&format!(
+                    "Limit must be between 1 and {}",
+                    MAX_FILTER_QUERY_LIMIT
+                ))))
+            }
+            Some(limit) => Ok(limit),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    db::{self, online_evaluation_rules::NewOnlineEvaluationRule, user::User, DB},
+    evaluations::judge,
+    traces::{
+        evaluators::get_stored_env,
+        online_evaluations::{self, invalidate_online_evaluation_rules, OnlineEvaluator},
+    },
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+const MAX_NAME_LENGTH: usize = 64;
+
+#[get("online-evaluation-rules")]
+pub async fn get_online_evaluation_rules(
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let rules =
+        db::online_evaluation_rules::get_online_evaluation_rules(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateOnlineEvaluationRuleRequest {
+    name: String,
+    #[serde(default)]
+    span_path: Option<String>,
+    #[serde(default)]
+    span_name: Option<String>,
+    /// Attribute keys with the values that matched spans must have
+    #[serde(default)]
+    attribute_filters: serde_json::Map<String, Value>,
+    #[serde(default = "default_sample_percentage")]
+    sample_percentage: f64,
+    /// See `traces::online_evaluations::OnlineEvaluator`
+    evaluator: Value,
+}
+
+fn default_sample_percentage() -> f64 {
+    100.0
+}
+
+/// Starts scoring `samplePercentage` percent of the incoming spans that match the rule. The
+/// scores are recorded as span scores with the rule's name.
+#[post("online-evaluation-rules")]
+pub async fn create_online_evaluation_rule(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateOnlineEvaluationRuleRequest>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(Error::invalid_request(Some(&format!(
+            "Name must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        ))));
+    }
+    if !(req.sample_percentage > 0.0 && req.sample_percentage <= 100.0) {
+        return Err(Error::invalid_request(Some(
+            "Sample percentage must be greater than 0 and at most 100",
+        )));
+    }
+    let evaluator = online_evaluations::parse_evaluator(&req.evaluator)
+        .map_err(|e| Error::invalid_request(Some(&format!("Invalid evaluator: {}", e))))?;
+
+    if let OnlineEvaluator::Judge { judge: judge_name } = &evaluator {
+        let judges = db::judge_evaluators::get_judge_evaluators_by_names(
+            &db.pool,
+            &project_id,
+            &[judge_name.clone()],
+        )
+        .await?;
+        if judges.is_empty() {
+            return Err(Error::invalid_request(Some(&format!(
+                "Judge evaluator {} not found",
+                judge_name
+            ))));
+        }
+        let env = get_stored_env(db.clone().into_inner(), project_id).await?;
+        let missing_env_vars = judge::missing_env_vars(&judges, &env);
+        if !missing_env_vars.is_empty() {
+            return Err(Error::invalid_request(Some(&format!(
+                "Add {} to the project's api keys to run the judge",
+                missing_env_vars.join(", ")
+            ))));
+        }
+    }
+
+    let rule = NewOnlineEvaluationRule {
+        name: name.to_string(),
+        span_path: req.span_path.filter(|path| !path.is_empty()),
+        span_name: req.span_name.filter(|name| !name.is_empty()),
+        attribute_filters: Value::Object(req.attribute_filters),
+        sample_percentage: req.sample_percentage,
+        evaluator: serde_json::to_value(&evaluator).map_err(anyhow::Error::from)?,
+    };
+    let Some(rule) =
+        db::online_evaluation_rules::create_online_evaluation_rule(&db.pool, &project_id, &rule)
+            .await?
+    else {
+        return Err(Error::invalid_request(Some(&format!(
+            "Online evaluation rule {} already exists",
+            name
+        ))));
+    };
+    invalidate_online_evaluation_rules(cache.into_inner(), project_id).await;
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[derive(Deserialize)]
+struct UpdateOnlineEvaluationRuleRequest {
+    enabled: bool,
+}
+
+/// Pauses or resumes a rule
+#[post("online-evaluation-rules/{rule_id}")]
+pub async fn update_online_evaluation_rule(
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<UpdateOnlineEvaluationRuleRequest>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let (project_id, rule_id) = path.into_inner();
+
+    let Some(rule) = db::online_evaluation_rules::set_online_evaluation_rule_enabled(
+        &db.pool,
+        &project_id,
+        &rule_id,
+        req.enabled,
+    )
+    .await?
+    else {
+        return Err(Error::not_found("Online evaluation rule not found"));
+    };
+    invalidate_online_evaluation_rules(cache.into_inner(), project_id).await;
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[delete("online-evaluation-rules/{rule_id}")]
+pub async fn delete_online_evaluation_rule(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let (project_id, rule_id) = path.into_inner();
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "online_evaluation_rule",
+        Some(&rule_id),
+    )
+    .await?;
+
+    if !db::online_evaluation_rules::delete_online_evaluation_rule(&db.pool, &project_id, &rule_id)
+        .await?
+    {
+        return Err(Error::not_found("Online evaluation rule not found"));
+    }
+    invalidate_online_evaluation_rules(cache.into_inner(), project_id).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
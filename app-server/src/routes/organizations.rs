@@ -0,0 +1,282 @@
+use actix_web::{get, post, put, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{api_keys::hash_api_key, error::Error, ResponseResult};
+use crate::db::{
+    self,
+    organizations::OrganizationWithWorkspaces,
+    user::{get_by_email, User},
+    utils::generate_random_key,
+    DB,
+};
+
+const ADMIN_ROLES: [&str; 2] = ["owner", "admin"];
+
+async fn require_role(
+    db: &DB,
+    organization_id: &Uuid,
+    user: &User,
+    allowed_roles: &[&str],
+) -> Result<(), Error> {
+    let role = db::organizations::get_member_role(&db.pool, organization_id, &user.id).await?;
+    match role {
+        Some(role) if allowed_roles.is_empty() || allowed_roles.contains(&role.as_str()) => Ok(()),
+        _ => Err(Error::Forbidden),
+    }
+}
+
+#[get("")]
+async fn get_organizations_of_user(user: User, db: web::Data<DB>) -> ResponseResult {
+    let organizations = db::organizations::get_organizations_of_user(&db.pool, &user.id).await?;
+    Ok(HttpResponse::Ok().json(organizations))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateOrganizationRequest {
+    name: String,
+    #[serde(default)]
+    billing_email: Option<String>,
+}
+
+#[post("")]
+async fn create_organization(
+    user: User,
+    db: web::Data<DB>,
+    req: web::Json<CreateOrganizationRequest>,
+) -> ResponseResult {
+    let req = req.into_inner();
+    let organization =
+        db::organizations::create_organization(&db.pool, &req.name, &req.billing_email, &user.id)
+            .await?;
+    log::info!(
+        "Created new organization: id {}, name {}, owner {}",
+        organization.id,
+        organization.name,
+        user.id
+    );
+
+    Ok(HttpResponse::Ok().json(organization))
+}
+
+#[get("{organization_id}")]
+async fn get_organization(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let organization_id = path.into_inner();
+    require_role(&db, &organization_id, &user, &[]).await?;
+
+    let organization = db::organizations::get_organization(&db.pool, &organization_id).await?;
+    let workspaces =
+        db::organizations::get_organization_workspaces(&db.pool, &organization_id).await?;
+
+    Ok(HttpResponse::Ok().json(OrganizationWithWorkspaces {
+        organization,
+        workspaces,
+    }))
+}
+
+#[get("{organization_id}/users")]
+async fn get_organization_members(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let organization_id = path.into_inner();
+    require_role(&db, &organization_id, &user, &[]).await?;
+
+    let members = db::organizations::get_organization_members(&db.pool, &organization_id).await?;
+    Ok(HttpResponse::Ok().json(members))
+}
+
+#[derive(Deserialize)]
+struct AddOrganizationMemberRequest {
+    email: String,
+    #[serde(default = "default_member_role")]
+    role: String,
+}
+
+fn default_member_role() -> String {
+    "member".to_string()
+}
+
+#[post("{organization_id}/users")]
+async fn add_user_to_organization(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    req: web::Json<AddOrganizationMemberRequest>,
+) -> ResponseResult {
+    let organization_id = path.into_inner();
+    let req = req.into_inner();
+    require_role(&db, &organization_id, &user, &ADMIN_ROLES).await?;
+
+    if !["member", "admin", "owner"].contains(&req.role.as_str()) {
+        return Err(Error::invalid_request(Some("Unknown organization role")));
+    }
+    let Some(member) = get_by_email(&db.pool, &req.email).await? else {
+        return Err(Error::user_not_found(req.email));
+    };
+    if member.id == user.id {
+        return Err(Error::Forbidden);
+    }
+    // only owners can make other owners, or change the role of an owner
+    let current_role =
+        db::organizations::get_member_role(&db.pool, &organization_id, &member.id).await?;
+    if req.role == "owner" || current_role.as_deref() == Some("owner") {
+        require_role(&db, &organization_id, &user, &["owner"]).await?;
+    }
+
+    db::organizations::add_user_to_organization_by_email(
+        &db.pool,
+        &req.email,
+        &organization_id,
+        &req.role,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AttachWorkspaceRequest {
+    workspace_id: Uuid,
+}
+
+/// Move a workspace under the organization. The user must own the workspace
+/// and be an admin of the organization.
+#[post("{organization_id}/workspaces")]
+async fn attach_workspace_to_organization(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    req: web::Json<AttachWorkspaceRequest>,
+) -> ResponseResult {
+    let organization_id = path.into_inner();
+    let workspace_id = req.into_inner().workspace_id;
+    require_role(&db, &organization_id, &user, &ADMIN_ROLES).await?;
+
+    let owned_workspaces = db::workspace::get_owned_workspaces(&db.pool, &user.id).await?;
+    if !owned_workspaces.iter().any(|w| w.id == workspace_id) {
+        return Err(Error::Forbidden);
+    }
+
+    db::organizations::attach_workspace_to_organization(&db.pool, &workspace_id, &organization_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateOrganizationPolicyRequest {
+    sso_required: bool,
+    #[serde(default)]
+    min_retention_days: Option<i64>,
+}
+
+#[put("{organization_id}/policy")]
+async fn update_organization_policy(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    req: web::Json<UpdateOrganizationPolicyRequest>,
+) -> ResponseResult {
+    let organization_id = path.into_inner();
+    let req = req.into_inner();
+    require_role(&db, &organization_id, &user, &["owner"]).await?;
+
+    if req.min_retention_days.is_some_and(|days| days <= 0) {
+        return Err(Error::invalid_request(Some(
+            "minRetentionDays must be positive",
+        )));
+    }
+
+    db::organizations::update_organization_policy(
+        &db.pool,
+        &organization_id,
+        req.sso_required,
+        req.min_retention_days,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateScimTokenResponse {
+    value: String,
+    shorthand: String,
+}
+
+/// Create a token for the identity provider's SCIM client. The value is only returned once.
+#[post("{organization_id}/scim-tokens")]
+async fn create_scim_token(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let organization_id = path.into_inner();
+    require_role(&db, &organization_id, &user, &["owner"]).await?;
+
+    let value = generate_random_key();
+    let shorthand = format!("{}...{}", &value[..4], &value[value.len() - 4..]);
+    db::scim::create_scim_token(&db.pool, &organization_id, &hash_api_key(&value), &shorthand)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(CreateScimTokenResponse { value, shorthand }))
+}
+
+#[get("{organization_id}/scim-groups")]
+async fn get_scim_groups(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let organization_id = path.into_inner();
+    require_role(&db, &organization_id, &user, &ADMIN_ROLES).await?;
+
+    let groups = db::scim::get_groups(&db.pool, &organization_id, None, None, 0).await?;
+    Ok(HttpResponse::Ok().json(groups))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MapScimGroupRequest {
+    workspace_id: Uuid,
+    role: String,
+}
+
+/// Map an identity provider group to a workspace role. Current and future members of the
+/// group are added to the workspace with that role.
+#[put("{organization_id}/scim-groups/{group_id}")]
+async fn map_scim_group(
+    user: User,
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    req: web::Json<MapScimGroupRequest>,
+) -> ResponseResult {
+    let (organization_id, group_id) = path.into_inner();
+    let req = req.into_inner();
+    require_role(&db, &organization_id, &user, &ADMIN_ROLES).await?;
+
+    if !["member", "owner"].contains(&req.role.as_str()) {
+        return Err(Error::invalid_request(Some("Unknown workspace role")));
+    }
+
+    let group = db::scim::map_group_to_workspace(
+        &db.pool,
+        &organization_id,
+        &group_id,
+        &req.workspace_id,
+        &req.role,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(group))
+}
@@ -0,0 +1,110 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{api_keys::hash_api_key, error::Error, ResponseResult};
+use crate::{
+    auth::authenticated_with_api_key,
+    db::{
+        self,
+        personal_access_tokens::{PersonalAccessToken, TokenScope},
+        user::User,
+        utils::generate_random_key,
+        DB,
+    },
+};
+
+/// Distinguishes personal access tokens from user API keys in the `Authorization` header
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "lmnr_pat_";
+const MAX_EXPIRY_DAYS: i64 = 365;
+
+/// Tokens must not be usable to mint or revoke other tokens
+fn require_api_key(req: &HttpRequest) -> Result<(), Error> {
+    if !authenticated_with_api_key(req) {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatePersonalAccessTokenRequest {
+    name: String,
+    scope: TokenScope,
+    /// If not set, the token never expires
+    #[serde(default)]
+    expires_in_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatePersonalAccessTokenResponse {
+    value: String,
+    #[serde(flatten)]
+    token: PersonalAccessToken,
+}
+
+#[post("")]
+async fn create_personal_access_token(
+    http_req: HttpRequest,
+    user: User,
+    db: web::Data<DB>,
+    req: web::Json<CreatePersonalAccessTokenRequest>,
+) -> ResponseResult {
+    require_api_key(&http_req)?;
+    let req = req.into_inner();
+
+    if req
+        .expires_in_days
+        .is_some_and(|days| days <= 0 || days > MAX_EXPIRY_DAYS)
+    {
+        return Err(Error::invalid_request(Some(&format!(
+            "expiresInDays must be between 1 and {MAX_EXPIRY_DAYS}"
+        ))));
+    }
+    let expires_at = req
+        .expires_in_days
+        .map(|days| Utc::now() + Duration::days(days));
+
+    let value = format!("{PERSONAL_ACCESS_TOKEN_PREFIX}{}", generate_random_key());
+    let shorthand = format!(
+        "{}...{}",
+        &value[..PERSONAL_ACCESS_TOKEN_PREFIX.len() + 4],
+        &value[value.len() - 4..]
+    );
+
+    let token = db::personal_access_tokens::create_token(
+        &db.pool,
+        &user.id,
+        &req.name,
+        &hash_api_key(&value),
+        &shorthand,
+        req.scope,
+        expires_at,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(CreatePersonalAccessTokenResponse { value, token }))
+}
+
+#[get("")]
+async fn get_personal_access_tokens(user: User, db: web::Data<DB>) -> ResponseResult {
+    let tokens = db::personal_access_tokens::get_tokens_of_user(&db.pool, &user.id).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[delete("{token_id}")]
+async fn revoke_personal_access_token(
+    http_req: HttpRequest,
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    require_api_key(&http_req)?;
+    let token_id = path.into_inner();
+
+    db::personal_access_tokens::delete_token(&db.pool, &user.id, &token_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
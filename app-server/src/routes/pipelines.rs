@@ -2,36 +2,54 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use actix_web::{delete, get, post, web, HttpResponse};
+use chrono::Utc;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
-use super::ResponseResult;
+use super::{legal_holds::ensure_no_legal_hold, ResponseResult};
 use crate::db::pipelines::pipeline_version::PipelineVersionInfo;
 use crate::pipeline::nodes::{GraphOutput, GraphRunOutput, Message};
 use crate::pipeline::trace::{RunTrace, RunTraceStats};
-use crate::pipeline::utils::get_target_pipeline_version_cache_key;
+use crate::pipeline::utils::{
+    get_pipeline_deployment_cache_key, get_target_pipeline_version_cache_key,
+};
 use crate::routes::error::pipeline_runner_to_http_error;
 use crate::traces::evaluators::get_stored_env;
 use crate::{
     cache::Cache,
     db::{
         self,
-        pipelines::{pipeline_version, write_pipeline, Pipeline, PipelineVersion},
+        activity::{ActivityType, NewActivity},
+        pipelines::{
+            pipeline_approvals::{self, ApprovalTaskStatus},
+            pipeline_deployments::{self, PipelineDeployment, PRODUCTION_TARGET},
+            pipeline_triggers::{self, NewPipelineTrigger, PipelineTriggerType},
+            pipeline_usage, pipeline_version, write_pipeline, Pipeline, PipelineVersion,
+        },
+        user::User,
+        utils::generate_random_key,
         DB,
     },
+    logging,
     pipeline::{
         nodes::{NodeInput, StreamChunk},
         runner::PipelineRunner,
         templates::insert_node_ids_to_template,
+        triggers,
+        validation::GraphValidationError,
         Graph, RunType,
     },
-    routes::error::{self, graph_error_to_http_error},
+    routes::{
+        activity::record_activity,
+        error::{self, graph_error_to_http_error},
+    },
 };
 
 const DEFAULT_NEW_PIPELINE_VERSION_ID_STRING: &str = "db6d1708-9836-42f2-a3ea-732ca7709039";
 const DEFAULT_PIPELINE_VERSION_NAME: &str = "main";
+const MAX_DEPLOYMENT_TARGET_LENGTH: usize = 64;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum GraphInterruptMessage {
@@ -110,11 +128,16 @@ async fn run_pipeline_graph(
     graph
         .setup(&inputs, &env, &HashMap::new(), &run_type)
         .map_err(graph_error_to_http_error)?;
+    graph.project_id = Some(project_id);
+    let errors = graph.validate();
+    if !errors.is_empty() {
+        return Err(error::Error::invalid_graph(errors));
+    }
 
     if params.stream {
         let (tx, mut rx) = mpsc::channel::<StreamChunk>(100);
 
-        tokio::spawn(async move {
+        logging::spawn(async move {
             let run_result = pipeline_runner
                 .run_workshop(
                     graph,
@@ -207,6 +230,45 @@ async fn run_pipeline_graph(
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ValidateGraphRequest {
+    graph: Graph,
+    /// Added to the env stored in the project
+    #[serde(default)]
+    env: HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ValidateGraphResponse {
+    valid: bool,
+    errors: Vec<GraphValidationError>,
+}
+
+/// Checks the graph, e.g. for cycles, mismatched handle types and missing env vars, without
+/// running it
+#[post("pipelines/validate/graph")]
+async fn validate_pipeline_graph(
+    project_id: web::Path<Uuid>,
+    req: web::Json<ValidateGraphRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let req = req.into_inner();
+    let mut graph = req.graph;
+
+    let mut env = req.env;
+    env.extend(get_stored_env(db.into_inner(), project_id).await?);
+    graph.env = env;
+
+    let errors = graph.validate();
+    Ok(HttpResponse::Ok().json(ValidateGraphResponse {
+        valid: errors.is_empty(),
+        errors,
+    }))
+}
+
 #[get("pipelines")]
 async fn get_pipelines(project_id: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
     let pipelines =
@@ -300,60 +362,534 @@ async fn update_pipeline(
 }
 
 #[delete("pipelines/{pipeline_id}")]
-async fn delete_pipeline(params: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> ResponseResult {
-    let (_project_id, pipeline_id) = params.into_inner();
+async fn delete_pipeline(
+    params: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, pipeline_id) = params.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "pipeline", Some(&pipeline_id)).await?;
 
     db::pipelines::delete_pipeline(&db.pool, &pipeline_id).await?;
 
     Ok(HttpResponse::Ok().finish())
 }
 
+fn validate_deployment_target(target: &str) -> Result<(), error::Error> {
+    if target.is_empty()
+        || target.len() > MAX_DEPLOYMENT_TARGET_LENGTH
+        || !target
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        return Err(error::Error::invalid_request(Some(&format!(
+            "Deployment target must be 1 to {} lowercase letters, digits, '-' or '_'",
+            MAX_DEPLOYMENT_TARGET_LENGTH
+        ))));
+    }
+    Ok(())
+}
+
+/// Invalidate the cached version of the deployment, and mirror production deployments to the
+/// target pipeline version, which runs without a deployment target use
+async fn sync_deployment(
+    db: &DB,
+    cache: &Cache,
+    project_id: Uuid,
+    pipeline_name: &str,
+    deployment: &PipelineDeployment,
+) -> Result<(), error::Error> {
+    if deployment.target == PRODUCTION_TARGET {
+        pipeline_version::create_or_update_target_pipeline_version(
+            &db.pool,
+            deployment.pipeline_id,
+            deployment.pipeline_version_id,
+        )
+        .await?;
+        let cache_key =
+            get_target_pipeline_version_cache_key(&project_id.to_string(), pipeline_name);
+        let _ = cache.remove::<PipelineVersion>(&cache_key).await;
+    } else {
+        let cache_key = get_pipeline_deployment_cache_key(
+            &project_id.to_string(),
+            pipeline_name,
+            &deployment.target,
+        );
+        let _ = cache.remove::<PipelineVersion>(&cache_key).await;
+    }
+    Ok(())
+}
+
+async fn deploy_pipeline_version(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+    pipeline_id: Uuid,
+    target: &str,
+    pipeline_version_id: Uuid,
+    user: &User,
+) -> Result<PipelineDeployment, error::Error> {
+    validate_deployment_target(target)?;
+    let pipeline_version =
+        pipeline_version::get_pipeline_version(&db.pool, &pipeline_version_id).await?;
+    if pipeline_version.pipeline_id != pipeline_id {
+        return Err(error::Error::invalid_request(Some(
+            "Pipeline version does not belong to the pipeline",
+        )));
+    }
+    if pipeline_version.pipeline_type != "COMMIT" {
+        return Err(error::Error::invalid_request(Some(
+            "Only COMMIT pipeline versions can be deployed",
+        )));
+    }
+    let pipeline = db::pipelines::get_pipeline_by_id(&db.pool, &pipeline_id).await?;
+
+    let deployment = pipeline_deployments::deploy_pipeline_version(
+        &db.pool,
+        &pipeline_id,
+        target,
+        &pipeline_version_id,
+        Some(user.id),
+    )
+    .await?;
+    sync_deployment(&db, &cache, project_id, &pipeline.name, &deployment).await?;
+
+    record_activity(
+        db,
+        NewActivity {
+            project_id,
+            activity_type: ActivityType::PROMPT_DEPLOYED,
+            actor_id: Some(user.id),
+            resource_id: Some(pipeline_id),
+            summary: format!(
+                "Deployed version {} of pipeline {} to {}",
+                pipeline_version.name, pipeline.name, target
+            ),
+            details: serde_json::json!({
+                "pipelineVersionId": pipeline_version_id,
+                "target": target,
+            }),
+        },
+    );
+
+    Ok(deployment)
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct UpdateTargetPipelineVersionRequest {
     pipeline_version_id: Uuid,
 }
 
-/// Create or update target pipeline version for a pipeline
+/// Create or update target pipeline version for a pipeline, i.e. deploy it to production
 #[post("pipelines/{pipeline_id}/target")]
 async fn update_target_pipeline_version(
     params: web::Path<(Uuid, Uuid)>,
     req: web::Json<UpdateTargetPipelineVersionRequest>,
+    user: User,
     db: web::Data<DB>,
     cache: web::Data<Cache>,
 ) -> ResponseResult {
-    let req = req.into_inner();
     let (project_id, pipeline_id) = params.into_inner();
-    let cache = cache.into_inner();
-    let pipeline_version_id = req.pipeline_version_id;
+    let deployment = deploy_pipeline_version(
+        db.into_inner(),
+        cache.into_inner(),
+        project_id,
+        pipeline_id,
+        PRODUCTION_TARGET,
+        req.into_inner().pipeline_version_id,
+        &user,
+    )
+    .await?;
 
-    let pipeline_version =
-        db::pipelines::pipeline_version::get_pipeline_version_with_pipeline_name(
-            &db.pool,
-            &pipeline_version_id,
-        )
-        .await?;
-    if pipeline_version.pipeline_type != "COMMIT" {
+    Ok(HttpResponse::Ok().json(deployment))
+}
+
+/// Deployment targets of the pipeline and the versions they point at
+#[get("pipelines/{pipeline_id}/deployments")]
+async fn get_pipeline_deployments(
+    params: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (_project_id, pipeline_id) = params.into_inner();
+    let deployments =
+        pipeline_deployments::get_pipeline_deployments(&db.pool, &pipeline_id).await?;
+
+    Ok(HttpResponse::Ok().json(deployments))
+}
+
+/// Point the deployment target, e.g. `staging`, at a commit version. Endpoint runs with the
+/// target use the version from then on.
+#[post("pipelines/{pipeline_id}/deployments/{target}")]
+async fn deploy_pipeline(
+    params: web::Path<(Uuid, Uuid, String)>,
+    req: web::Json<UpdateTargetPipelineVersionRequest>,
+    user: User,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let (project_id, pipeline_id, target) = params.into_inner();
+    let deployment = deploy_pipeline_version(
+        db.into_inner(),
+        cache.into_inner(),
+        project_id,
+        pipeline_id,
+        &target,
+        req.into_inner().pipeline_version_id,
+        &user,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(deployment))
+}
+
+/// Point the deployment target back at the version it pointed at before the last deploy
+#[post("pipelines/{pipeline_id}/deployments/{target}/rollback")]
+async fn rollback_pipeline_deployment(
+    params: web::Path<(Uuid, Uuid, String)>,
+    user: User,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let (project_id, pipeline_id, target) = params.into_inner();
+    let db = db.into_inner();
+
+    let Some(deployment) = pipeline_deployments::rollback_pipeline_deployment(
+        &db.pool,
+        &pipeline_id,
+        &target,
+        Some(user.id),
+    )
+    .await?
+    else {
+        return Err(error::Error::invalid_request(Some(&format!(
+            "Deployment target {} has no previous version to roll back to",
+            target
+        ))));
+    };
+    let pipeline = db::pipelines::get_pipeline_by_id(&db.pool, &pipeline_id).await?;
+    sync_deployment(&db, &cache, project_id, &pipeline.name, &deployment).await?;
+
+    record_activity(
+        db,
+        NewActivity {
+            project_id,
+            activity_type: ActivityType::PROMPT_DEPLOYED,
+            actor_id: Some(user.id),
+            resource_id: Some(pipeline_id),
+            summary: format!("Rolled back {} of pipeline {}", target, pipeline.name),
+            details: serde_json::json!({
+                "pipelineVersionId": deployment.pipeline_version_id,
+                "target": target,
+                "rollback": true,
+            }),
+        },
+    );
+
+    Ok(HttpResponse::Ok().json(deployment))
+}
+
+/// Executions of the pipeline's endpoint runs in the current month against its quota, and the
+/// executions, tokens and cost of each node
+#[get("pipelines/{pipeline_id}/usage")]
+async fn get_pipeline_usage(params: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> ResponseResult {
+    let (_project_id, pipeline_id) = params.into_inner();
+    let usage = pipeline_usage::get_pipeline_usage(&db.pool, &pipeline_id).await?;
+
+    Ok(HttpResponse::Ok().json(usage))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdatePipelineQuotaRequest {
+    /// None removes the quota
+    monthly_execution_quota: Option<i64>,
+}
+
+/// Limit the endpoint runs of the pipeline per month, further runs are rejected
+#[post("pipelines/{pipeline_id}/quota")]
+async fn update_pipeline_quota(
+    params: web::Path<(Uuid, Uuid)>,
+    req: web::Json<UpdatePipelineQuotaRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (_project_id, pipeline_id) = params.into_inner();
+    let monthly_execution_quota = req.into_inner().monthly_execution_quota;
+    if monthly_execution_quota.is_some_and(|quota| quota < 0) {
         return Err(error::Error::invalid_request(Some(
-            "Only COMMIT pipeline versions can be set as target",
+            "Monthly execution quota must not be negative",
         )));
     }
 
-    let target_pipeline_version =
-        db::pipelines::pipeline_version::create_or_update_target_pipeline_version(
-            &db.pool,
-            pipeline_id,
-            pipeline_version_id,
-        )
+    pipeline_usage::set_pipeline_execution_quota(&db.pool, &pipeline_id, monthly_execution_quota)
         .await?;
+    let usage = pipeline_usage::get_pipeline_usage(&db.pool, &pipeline_id).await?;
 
-    let cache_key = get_target_pipeline_version_cache_key(
-        &project_id.to_string(),
-        &pipeline_version.pipeline_name,
-    );
-    let _ = cache.remove::<PipelineVersion>(&cache_key).await;
+    Ok(HttpResponse::Ok().json(usage))
+}
+
+#[derive(Deserialize)]
+struct GetApprovalTasksQuery {
+    #[serde(default)]
+    status: Option<ApprovalTaskStatus>,
+}
+
+/// Tasks created by approval nodes of the project's pipelines, e.g. the pending ones
+#[get("approval-tasks")]
+async fn get_approval_tasks(
+    project_id: web::Path<Uuid>,
+    query: web::Query<GetApprovalTasksQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let tasks =
+        pipeline_approvals::get_approval_tasks(&db.pool, &project_id, query.into_inner().status)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(tasks))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResolveApprovalTaskRequest {
+    approved: bool,
+    /// Replaces the payload the pipeline continues with, only if approved
+    #[serde(default)]
+    payload: Option<serde_json::Value>,
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+/// Approve or reject a pending task, after which the waiting pipeline run continues or fails
+#[post("approval-tasks/{task_id}/resolve")]
+async fn resolve_approval_task(
+    params: web::Path<(Uuid, Uuid)>,
+    req: web::Json<ResolveApprovalTaskRequest>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, task_id) = params.into_inner();
+    let req = req.into_inner();
+    let (status, payload) = if req.approved {
+        (ApprovalTaskStatus::APPROVED, req.payload)
+    } else {
+        (ApprovalTaskStatus::REJECTED, None)
+    };
+
+    let task = pipeline_approvals::resolve_approval_task(
+        &db.pool,
+        &project_id,
+        &task_id,
+        status,
+        payload,
+        req.comment,
+        Some(user.id),
+    )
+    .await?
+    .ok_or_else(|| error::Error::not_found("Approval task doesn't exist or has been resolved"))?;
 
-    Ok(HttpResponse::Ok().json(target_pipeline_version))
+    Ok(HttpResponse::Ok().json(task))
+}
+
+const DEFAULT_TRIGGER_RUNS_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatePipelineTriggerRequest {
+    trigger_type: PipelineTriggerType,
+    /// Deployment target to run, defaults to production
+    #[serde(default)]
+    target: Option<String>,
+    /// Cron expression of schedules in UTC, with or without the seconds field
+    #[serde(default)]
+    cron_schedule: Option<String>,
+    /// Dataset watched by dataset triggers, all datasets of the project if not set
+    #[serde(default)]
+    dataset_id: Option<Uuid>,
+    #[serde(default)]
+    inputs: Option<serde_json::Value>,
+}
+
+#[get("pipelines/{pipeline_id}/triggers")]
+async fn get_pipeline_triggers(
+    params: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, pipeline_id) = params.into_inner();
+    let triggers =
+        pipeline_triggers::get_pipeline_triggers(&db.pool, &project_id, &pipeline_id).await?;
+
+    Ok(HttpResponse::Ok().json(triggers))
+}
+
+/// Run the pipeline's deployed version on a schedule, when a dataset is updated or when the
+/// trigger's webhook is called
+#[post("pipelines/{pipeline_id}/triggers")]
+async fn create_pipeline_trigger(
+    params: web::Path<(Uuid, Uuid)>,
+    req: web::Json<CreatePipelineTriggerRequest>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, pipeline_id) = params.into_inner();
+    let req = req.into_inner();
+
+    let pipeline = db::pipelines::get_pipeline_by_id(&db.pool, &pipeline_id).await?;
+    if pipeline.project_id != project_id {
+        return Err(error::Error::not_found("Pipeline not found"));
+    }
+    let target = req.target.unwrap_or(PRODUCTION_TARGET.to_string());
+    validate_deployment_target(&target)?;
+    let inputs = req.inputs.unwrap_or(serde_json::json!({}));
+    if !inputs.is_object() {
+        return Err(error::Error::invalid_request(Some(
+            "Trigger inputs must be an object",
+        )));
+    }
+
+    let mut trigger = NewPipelineTrigger {
+        project_id,
+        pipeline_id,
+        target,
+        trigger_type: req.trigger_type,
+        cron_schedule: None,
+        dataset_id: None,
+        webhook_token: None,
+        inputs,
+        next_run_at: None,
+        created_by: Some(user.id),
+    };
+    match req.trigger_type {
+        PipelineTriggerType::SCHEDULE => {
+            let cron_schedule = req.cron_schedule.ok_or_else(|| {
+                error::Error::invalid_request(Some("Schedules require a cron schedule"))
+            })?;
+            triggers::parse_cron_schedule(&cron_schedule)
+                .map_err(|e| error::Error::invalid_request(Some(&e.to_string())))?;
+            trigger.next_run_at = triggers::next_run_at(&cron_schedule, Utc::now());
+            trigger.cron_schedule = Some(cron_schedule);
+        }
+        PipelineTriggerType::DATASET_UPDATED => {
+            if let Some(dataset_id) = req.dataset_id {
+                db::datasets::get_dataset(&db.pool, project_id, dataset_id)
+                    .await
+                    .map_err(|_| error::Error::invalid_request(Some("Dataset not found")))?;
+            }
+            trigger.dataset_id = req.dataset_id;
+        }
+        PipelineTriggerType::WEBHOOK => {
+            trigger.webhook_token = Some(generate_random_key());
+        }
+    }
+
+    let trigger = pipeline_triggers::create_trigger(&db.pool, &trigger).await?;
+
+    Ok(HttpResponse::Ok().json(trigger))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdatePipelineTriggerRequest {
+    enabled: bool,
+}
+
+#[post("pipelines/{pipeline_id}/triggers/{trigger_id}")]
+async fn update_pipeline_trigger(
+    params: web::Path<(Uuid, Uuid, Uuid)>,
+    req: web::Json<UpdatePipelineTriggerRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, _pipeline_id, trigger_id) = params.into_inner();
+    let enabled = req.into_inner().enabled;
+
+    let trigger = pipeline_triggers::get_trigger(&db.pool, &trigger_id)
+        .await?
+        .filter(|trigger| trigger.project_id == project_id)
+        .ok_or_else(|| error::Error::not_found("Trigger not found"))?;
+    // Re-enabled schedules continue from now instead of catching up on the missed runs
+    let next_run_at = match &trigger.cron_schedule {
+        Some(cron_schedule) if enabled && !trigger.enabled => {
+            triggers::next_run_at(cron_schedule, Utc::now())
+        }
+        _ => trigger.next_run_at,
+    };
+    let trigger = pipeline_triggers::set_trigger_enabled(
+        &db.pool,
+        &project_id,
+        &trigger_id,
+        enabled,
+        next_run_at,
+    )
+    .await?
+    .ok_or_else(|| error::Error::not_found("Trigger not found"))?;
+
+    Ok(HttpResponse::Ok().json(trigger))
+}
+
+#[delete("pipelines/{pipeline_id}/triggers/{trigger_id}")]
+async fn delete_pipeline_trigger(
+    params: web::Path<(Uuid, Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, _pipeline_id, trigger_id) = params.into_inner();
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "pipeline_trigger",
+        Some(&trigger_id),
+    )
+    .await?;
+    if !pipeline_triggers::delete_trigger(&db.pool, &project_id, &trigger_id).await? {
+        return Err(error::Error::not_found("Trigger not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetPipelineTriggerRunsQuery {
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// Run history of the trigger, the newest first
+#[get("pipelines/{pipeline_id}/triggers/{trigger_id}/runs")]
+async fn get_pipeline_trigger_runs(
+    params: web::Path<(Uuid, Uuid, Uuid)>,
+    query: web::Query<GetPipelineTriggerRunsQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, _pipeline_id, trigger_id) = params.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_TRIGGER_RUNS_LIMIT);
+    let runs =
+        pipeline_triggers::get_trigger_runs(&db.pool, &project_id, &trigger_id, limit).await?;
+
+    Ok(HttpResponse::Ok().json(runs))
+}
+
+/// Queues a run of a webhook trigger with the request's body as the event. Not authenticated,
+/// the trigger's token in the URL is the secret.
+#[post("/api/v1/pipeline-triggers/webhooks/{webhook_token}")]
+async fn receive_pipeline_trigger_webhook(
+    webhook_token: web::Path<String>,
+    body: Option<web::Json<serde_json::Value>>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let webhook_token = webhook_token.into_inner();
+    let trigger = pipeline_triggers::get_trigger_by_webhook_token(&db.pool, &webhook_token)
+        .await?
+        .filter(|trigger| trigger.enabled)
+        .ok_or_else(|| error::Error::not_found("Unknown or disabled webhook"))?;
+    let event = body
+        .map(|body| body.into_inner())
+        .unwrap_or(serde_json::Value::Null);
+
+    let run = pipeline_triggers::enqueue_trigger_run(&db.pool, &trigger.id, &event).await?;
+
+    Ok(HttpResponse::Accepted().json(run))
 }
 
 #[derive(Deserialize)]
@@ -383,6 +919,17 @@ async fn create_pipeline_version(
         )));
     }
 
+    // Workshop versions are saved while being edited, but commits must be runnable. Env vars
+    // are not checked, since they can be added to the project later.
+    let ref_version =
+        pipeline_version::get_pipeline_version(&db.pool, &ref_pipeline_version_id).await?;
+    let graph = serde_json::from_value::<Graph>(ref_version.runnable_graph)
+        .map_err(|e| error::Error::deserialization_error(Some(e)))?;
+    let errors = graph.validate_structure();
+    if !errors.is_empty() {
+        return Err(error::Error::invalid_graph(errors));
+    }
+
     db::pipelines::pipeline_version::clone_pipeline_version(
         &db.pool,
         ref_pipeline_version_id,
@@ -439,7 +986,7 @@ async fn get_pipeline_versions_info(
         .iter()
         .find(|version| version.pipeline_type == "WORKSHOP")
         .cloned()
-        .ok_or(anyhow::anyhow!("No workshop version found"))?;
+        .ok_or(error::Error::not_found("No workshop version found"))?;
 
     let commit_versions = versions
         .into_iter()
@@ -1,16 +1,25 @@
 use std::sync::Arc;
 
-use actix_web::{delete, get, post, web, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpResponse};
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
     cache::Cache,
-    db::{self, user::User, DB},
+    db::{self, sampling_exemptions::SamplingExemptionKind, user::User, utils::is_valid_slug, DB},
     projects,
-    routes::ResponseResult,
+    routes::{
+        error::{Error, ErrorCode},
+        legal_holds::ensure_no_legal_hold,
+        workspace::{check_retention_floor, RetentionSettings, UpdateRetentionRequest},
+        ResponseResult,
+    },
     semantic_search::SemanticSearch,
+    traces::{
+        client_metadata::ClientRegionSettings,
+        sampling::{self, invalidate_sampling_settings},
+    },
 };
 
 #[get("")] // scope: /projects
@@ -58,11 +67,13 @@ async fn get_project(project_id: web::Path<Uuid>, db: web::Data<DB>) -> Response
 #[delete("")]
 async fn delete_project(
     project_id: web::Path<Uuid>,
+    user: User,
     db: web::Data<DB>,
     cache: web::Data<Cache>,
     semantic_search: web::Data<Arc<dyn SemanticSearch>>,
 ) -> ResponseResult {
     let project_id = project_id.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "project", None).await?;
 
     let project = db::projects::get_project(&db.pool, &project_id).await?;
 
@@ -98,7 +109,15 @@ async fn delete_project(
 #[serde(rename_all = "camelCase")]
 struct CreateProjectRequest {
     name: String,
-    workspace_id: Uuid,
+    /// Either workspace id or workspace slug must be set
+    #[serde(default)]
+    workspace_id: Option<Uuid>,
+    #[serde(default)]
+    workspace_slug: Option<String>,
+    /// If set, the existing project with the slug in the workspace is returned instead of
+    /// creating a new one
+    #[serde(default)]
+    slug: Option<String>,
 }
 
 #[post("")]
@@ -113,15 +132,274 @@ async fn create_project(
     let cache = cache.into_inner();
     let semantic_search = semantic_search.into_inner().as_ref().clone();
 
-    let project = projects::create_project(
+    if let Some(slug) = &req.slug {
+        if !is_valid_slug(slug) {
+            return Err(Error::invalid_request(Some("Invalid project slug")));
+        }
+    }
+    let workspace_id = match (req.workspace_id, &req.workspace_slug) {
+        (Some(workspace_id), _) => workspace_id,
+        (None, Some(workspace_slug)) => {
+            db::workspace::get_workspace_of_user_by_slug(&db.pool, &user.id, workspace_slug)
+                .await?
+                .ok_or(Error::api(
+                    ErrorCode::WorkspaceNotFound,
+                    format!("Workspace {} not found", workspace_slug),
+                ))?
+                .id
+        }
+        (None, None) => {
+            return Err(Error::invalid_request(Some(
+                "Either workspaceId or workspaceSlug must be set",
+            )))
+        }
+    };
+
+    let project = projects::get_or_create_project(
         &db.pool,
         cache.clone(),
         semantic_search.clone(),
         &user.id,
         &req.name,
-        req.workspace_id,
+        workspace_id,
+        req.slug.as_deref(),
     )
     .await?;
 
     Ok(HttpResponse::Ok().json(project))
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientMetadataSettings {
+    /// Keep the client's country code, derived by the edge proxy from the client's IP address
+    collect_client_region: bool,
+}
+
+#[get("client-metadata")]
+async fn get_client_metadata_settings(
+    project_id: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let collect_client_region =
+        db::projects::get_collect_client_region(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(ClientMetadataSettings {
+        collect_client_region,
+    }))
+}
+
+/// SDK language and version and the deployment environment are always collected, the region
+/// only if the project opts in
+#[put("client-metadata")]
+async fn update_client_metadata_settings(
+    project_id: web::Path<Uuid>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    req: web::Json<ClientMetadataSettings>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let settings = req.into_inner();
+
+    db::projects::update_collect_client_region(
+        &db.pool,
+        &project_id,
+        settings.collect_client_region,
+    )
+    .await?;
+    let _ = cache
+        .insert::<ClientRegionSettings>(
+            project_id.to_string(),
+            &ClientRegionSettings {
+                collect_client_region: settings.collect_client_region,
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+const MAX_AGENT_CHECKPOINT_INTERVAL: i32 = 1000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentCheckpointSettings {
+    /// Steps between the checkpoints agents take of their runs
+    checkpoint_interval: i32,
+}
+
+#[get("agent-checkpoints")]
+async fn get_agent_checkpoint_settings(
+    project_id: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let checkpoint_interval =
+        db::projects::get_agent_checkpoint_interval(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(AgentCheckpointSettings {
+        checkpoint_interval,
+    }))
+}
+
+#[put("agent-checkpoints")]
+async fn update_agent_checkpoint_settings(
+    project_id: web::Path<Uuid>,
+    db: web::Data<DB>,
+    req: web::Json<AgentCheckpointSettings>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let settings = req.into_inner();
+    if !(1..=MAX_AGENT_CHECKPOINT_INTERVAL).contains(&settings.checkpoint_interval) {
+        return Err(Error::invalid_request(Some(
+            "Checkpoint interval must be between 1 and 1000 steps",
+        )));
+    }
+
+    db::projects::update_agent_checkpoint_interval(
+        &db.pool,
+        &project_id,
+        settings.checkpoint_interval,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SamplingRateSettings {
+    /// Share of the traces that are kept, from 0 to 1
+    sample_rate: f64,
+}
+
+#[get("sampling")]
+async fn get_sampling_settings(
+    project_id: web::Path<Uuid>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let settings =
+        sampling::get_sampling_settings(db.into_inner(), cache.into_inner(), project_id).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "sampleRate": settings.sample_rate,
+        "exemptions": settings.exemptions,
+    })))
+}
+
+/// Spans that match an exemption are kept whatever the sample rate, see `traces::sampling`
+#[put("sampling")]
+async fn update_sampling_settings(
+    project_id: web::Path<Uuid>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    req: web::Json<SamplingRateSettings>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let settings = req.into_inner();
+    if !(0.0..=1.0).contains(&settings.sample_rate) {
+        return Err(Error::invalid_request(Some(
+            "Sample rate must be between 0 and 1",
+        )));
+    }
+
+    db::projects::update_span_sample_rate(&db.pool, &project_id, settings.sample_rate).await?;
+    invalidate_sampling_settings(cache.into_inner(), project_id).await;
+
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateSamplingExemptionRequest {
+    kind: SamplingExemptionKind,
+    value: String,
+}
+
+#[post("sampling/exemptions")]
+async fn create_sampling_exemption(
+    project_id: web::Path<Uuid>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    req: web::Json<CreateSamplingExemptionRequest>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let req = req.into_inner();
+    let value = req.value.trim();
+    if value.is_empty() {
+        return Err(Error::invalid_request(Some(
+            "Exemption value must not be empty",
+        )));
+    }
+
+    let exemption =
+        db::sampling_exemptions::create_sampling_exemption(&db.pool, &project_id, req.kind, value)
+            .await?;
+    invalidate_sampling_settings(cache.into_inner(), project_id).await;
+
+    Ok(HttpResponse::Ok().json(exemption))
+}
+
+#[delete("sampling/exemptions/{exemption_id}")]
+async fn delete_sampling_exemption(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+) -> ResponseResult {
+    let (project_id, exemption_id) = path.into_inner();
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "sampling_exemption",
+        Some(&exemption_id),
+    )
+    .await?;
+    if !db::sampling_exemptions::delete_sampling_exemption(&db.pool, &project_id, &exemption_id)
+        .await?
+    {
+        return Err(Error::not_found("Sampling exemption not found"));
+    }
+    invalidate_sampling_settings(cache.into_inner(), project_id).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[get("retention")]
+async fn get_retention(project_id: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let retention_days = db::projects::get_retention_days(&db.pool, &project_id).await?;
+    let project = db::projects::get_project(&db.pool, &project_id).await?;
+    let policy = db::organizations::get_workspace_policy(&db.pool, &project.workspace_id).await?;
+
+    Ok(HttpResponse::Ok().json(RetentionSettings {
+        retention_days,
+        min_retention_days: policy.min_retention_days,
+    }))
+}
+
+/// Projects inherit the floor of the organization of their workspace
+#[put("retention")]
+async fn update_retention(
+    project_id: web::Path<Uuid>,
+    db: web::Data<DB>,
+    req: web::Json<UpdateRetentionRequest>,
+) -> ResponseResult {
+    let project_id = project_id.into_inner();
+    let retention_days = req.into_inner().retention_days;
+
+    let project = db::projects::get_project(&db.pool, &project_id).await?;
+    let policy = db::organizations::get_workspace_policy(&db.pool, &project.workspace_id).await?;
+    check_retention_floor(&policy, retention_days)?;
+
+    db::projects::update_retention_days(&db.pool, &project_id, retention_days).await?;
+
+    Ok(HttpResponse::Ok().json(RetentionSettings {
+        retention_days,
+        min_retention_days: policy.min_retention_days,
+    }))
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    cache::Cache,
+    ch::spans::MAX_PROMOTED_ATTRIBUTES,
+    db::{
+        self,
+        promoted_attributes::{BackfillStatus, PromotedAttribute},
+        user::User,
+        DB,
+    },
+    logging,
+    traces::promoted_attributes::{backfill_promoted_attribute, invalidate_promoted_attributes},
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+const MAX_ATTRIBUTE_KEY_LENGTH: usize = 256;
+
+fn spawn_backfill(
+    db: Arc<DB>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    attribute: PromotedAttribute,
+) {
+    logging::spawn(async move {
+        let (id, key) = (attribute.id, attribute.attribute_key.clone());
+        if let Err(e) = backfill_promoted_attribute(db, analytics_store, attribute).await {
+            log::error!(
+                "Failed to backfill promoted attribute. id [{}], key [{}]: {:?}",
+                id,
+                key,
+                e
+            );
+        }
+    });
+}
+
+#[get("promoted-attributes")]
+pub async fn get_promoted_attributes(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let attributes =
+        db::promoted_attributes::get_promoted_attributes(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(attributes))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromoteAttributeRequest {
+    attribute_key: String,
+}
+
+/// Copies the span attribute to a dedicated column, so that analytics queries can filter on it.
+/// Existing spans are backfilled in the background.
+#[post("promoted-attributes")]
+pub async fn promote_attribute(
+    path: web::Path<Uuid>,
+    req: web::Json<PromoteAttributeRequest>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let attribute_key = req.into_inner().attribute_key.trim().to_string();
+    if attribute_key.is_empty() || attribute_key.len() > MAX_ATTRIBUTE_KEY_LENGTH {
+        return Err(Error::invalid_request(Some(&format!(
+            "Attribute key must be between 1 and {} characters",
+            MAX_ATTRIBUTE_KEY_LENGTH
+        ))));
+    }
+    let db = db.into_inner();
+
+    let existing = db::promoted_attributes::get_promoted_attributes(&db.pool, &project_id).await?;
+    if existing
+        .iter()
+        .any(|attribute| attribute.attribute_key == attribute_key)
+    {
+        return Err(Error::invalid_request(Some(
+            "The attribute is already promoted",
+        )));
+    }
+    let Some(attribute) = db::promoted_attributes::create_promoted_attribute(
+        &db.pool,
+        &project_id,
+        &attribute_key,
+        MAX_PROMOTED_ATTRIBUTES as i32,
+    )
+    .await?
+    else {
+        return Err(Error::invalid_request(Some(&format!(
+            "A project can promote at most {} attributes, remove one first",
+            MAX_PROMOTED_ATTRIBUTES
+        ))));
+    };
+    invalidate_promoted_attributes(cache.into_inner(), project_id).await;
+
+    spawn_backfill(db, analytics_store.as_ref().clone(), attribute.clone());
+
+    Ok(HttpResponse::Ok().json(attribute))
+}
+
+/// Restarts the backfill of an attribute whose backfill failed
+#[post("promoted-attributes/{id}/backfill")]
+pub async fn retry_promoted_attribute_backfill(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let (project_id, id) = path.into_inner();
+    let db = db.into_inner();
+
+    let Some(attribute) = db::promoted_attributes::get_promoted_attributes(&db.pool, &project_id)
+        .await?
+        .into_iter()
+        .find(|attribute| attribute.id == id)
+    else {
+        return Err(Error::not_found("Promoted attribute not found"));
+    };
+    if attribute.backfill_status != BackfillStatus::FAILED {
+        return Err(Error::invalid_request(Some(
+            "Only failed backfills can be restarted",
+        )));
+    }
+
+    spawn_backfill(db, analytics_store.as_ref().clone(), attribute);
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+/// Stops promoting the attribute and frees its column
+#[delete("promoted-attributes/{id}")]
+pub async fn delete_promoted_attribute(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let (project_id, id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "promoted_attribute", Some(&id)).await?;
+
+    let Some(attribute) =
+        db::promoted_attributes::delete_promoted_attribute(&db.pool, &project_id, &id).await?
+    else {
+        return Err(Error::not_found("Promoted attribute not found"));
+    };
+    invalidate_promoted_attributes(cache.into_inner(), project_id).await;
+    analytics_store
+        .clear_promoted_attribute(project_id, attribute.slot as usize)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
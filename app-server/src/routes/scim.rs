@@ -0,0 +1,616 @@
+//! SCIM 2.0 (RFC 7643/7644) endpoints for provisioning users and groups from identity providers.
+//!
+//! Requests are authenticated with an organization-scoped SCIM token. Users are provisioned into
+//! the organization, and SCIM groups that are mapped to a workspace role grant their members
+//! access to that workspace.
+
+use std::collections::HashSet;
+
+use actix_web::{delete, get, http::StatusCode, patch, post, put, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::ResponseResult;
+use crate::{
+    auth::ScimOrganization,
+    cache::Cache,
+    db::{
+        self,
+        scim::{ScimGroup, ScimGroupMember, ScimUser},
+        user::User,
+        DB,
+    },
+};
+
+const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+const DEFAULT_PAGE_SIZE: i64 = 100;
+
+fn scim_error(status: StatusCode, detail: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("application/scim+json")
+        .json(json!({
+            "schemas": [ERROR_SCHEMA],
+            "status": status.as_u16().to_string(),
+            "detail": detail,
+        }))
+}
+
+fn scim_response(status: StatusCode, body: Value) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("application/scim+json")
+        .json(body)
+}
+
+fn list_response(resources: Vec<Value>, total_results: i64, start_index: i64) -> HttpResponse {
+    scim_response(
+        StatusCode::OK,
+        json!({
+            "schemas": [LIST_RESPONSE_SCHEMA],
+            "totalResults": total_results,
+            "startIndex": start_index,
+            "itemsPerPage": resources.len(),
+            "Resources": resources,
+        }),
+    )
+}
+
+fn user_resource(user: &ScimUser) -> Value {
+    json!({
+        "schemas": [USER_SCHEMA],
+        "id": user.id,
+        "externalId": user.scim_external_id,
+        "userName": user.email,
+        "displayName": user.name,
+        "name": { "formatted": user.name },
+        "emails": [{ "value": user.email, "primary": true }],
+        "active": user.active,
+        "meta": { "resourceType": "User" },
+    })
+}
+
+fn group_resource(group: &ScimGroup, members: &[ScimGroupMember]) -> Value {
+    let members = members
+        .iter()
+        .filter(|member| member.group_id == group.id)
+        .map(|member| json!({ "value": member.user_id, "display": member.email }))
+        .collect::<Vec<_>>();
+    json!({
+        "schemas": [GROUP_SCHEMA],
+        "id": group.id,
+        "externalId": group.external_id,
+        "displayName": group.display_name,
+        "members": members,
+        "meta": { "resourceType": "Group" },
+    })
+}
+
+/// Parses the only filter form identity providers use for lookups: `<attribute> eq "<value>"`
+fn parse_eq_filter(filter: &str, attribute: &str) -> Option<String> {
+    let mut parts = filter.trim().splitn(3, ' ');
+    let filter_attribute = parts.next()?;
+    let op = parts.next()?;
+    let value = parts.next()?.trim();
+    if !filter_attribute.eq_ignore_ascii_case(attribute) || !op.eq_ignore_ascii_case("eq") {
+        return None;
+    }
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .map(String::from)
+}
+
+/// Users lose access to the organization's projects, so cached user entries must be dropped
+async fn invalidate_user_cache(db: &DB, cache: &Cache, user_id: &Uuid) -> anyhow::Result<()> {
+    for api_key in db::scim::get_user_api_keys(&db.pool, user_id).await? {
+        let _ = cache.remove::<User>(&api_key).await;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListQuery {
+    filter: Option<String>,
+    start_index: Option<i64>,
+    count: Option<i64>,
+}
+
+#[get("Users")]
+async fn list_users(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    query: web::Query<ListQuery>,
+) -> ResponseResult {
+    let query = query.into_inner();
+    let email = match &query.filter {
+        Some(filter) => match parse_eq_filter(filter, "userName") {
+            Some(email) => Some(email),
+            None => {
+                return Ok(scim_error(
+                    StatusCode::BAD_REQUEST,
+                    "Only 'userName eq' filters are supported",
+                ))
+            }
+        },
+        None => None,
+    };
+    // SCIM pagination is 1-based
+    let start_index = query.start_index.unwrap_or(1).max(1);
+    let count = query.count.unwrap_or(DEFAULT_PAGE_SIZE).clamp(0, DEFAULT_PAGE_SIZE);
+
+    let users = db::scim::get_users(
+        &db.pool,
+        &organization.id,
+        email.as_deref(),
+        count,
+        start_index - 1,
+    )
+    .await?;
+    let total = db::scim::count_users(&db.pool, &organization.id, email.as_deref()).await?;
+
+    Ok(list_response(
+        users.iter().map(user_resource).collect(),
+        total,
+        start_index,
+    ))
+}
+
+#[get("Users/{user_id}")]
+async fn get_user(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    path: web::Path<Uuid>,
+) -> ResponseResult {
+    let user_id = path.into_inner();
+    match db::scim::get_user(&db.pool, &organization.id, &user_id).await? {
+        Some(user) => Ok(scim_response(StatusCode::OK, user_resource(&user))),
+        None => Ok(scim_error(StatusCode::NOT_FOUND, "User not found")),
+    }
+}
+
+#[derive(Deserialize)]
+struct ScimName {
+    formatted: Option<String>,
+    #[serde(rename = "givenName")]
+    given_name: Option<String>,
+    #[serde(rename = "familyName")]
+    family_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScimUserRequest {
+    user_name: String,
+    external_id: Option<String>,
+    display_name: Option<String>,
+    name: Option<ScimName>,
+    #[serde(default = "default_active")]
+    active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl ScimUserRequest {
+    fn full_name(&self) -> String {
+        if let Some(display_name) = &self.display_name {
+            return display_name.clone();
+        }
+        match &self.name {
+            Some(ScimName {
+                formatted: Some(formatted),
+                ..
+            }) => formatted.clone(),
+            Some(name) => [&name.given_name, &name.family_name]
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => self.user_name.clone(),
+        }
+    }
+}
+
+#[post("Users")]
+async fn create_user(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    req: web::Json<ScimUserRequest>,
+) -> ResponseResult {
+    let req = req.into_inner();
+    let user = db::scim::provision_user(
+        &db.pool,
+        &organization.id,
+        &req.user_name,
+        &req.full_name(),
+        &req.external_id,
+        req.active,
+    )
+    .await?;
+    log::info!(
+        "SCIM provisioned user {} into organization {}",
+        user.id,
+        organization.id
+    );
+
+    Ok(scim_response(StatusCode::CREATED, user_resource(&user)))
+}
+
+/// Applies a change of the `active` flag. Deactivated users are removed from all
+/// workspaces and groups of the organization, but stay members of the organization.
+async fn set_user_active(
+    db: &DB,
+    cache: &Cache,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+    active: bool,
+) -> anyhow::Result<()> {
+    db::scim::update_user(&db.pool, organization_id, user_id, None, Some(active)).await?;
+    if !active {
+        db::scim::deprovision_user(&db.pool, organization_id, user_id, false).await?;
+        invalidate_user_cache(db, cache, user_id).await?;
+    }
+    Ok(())
+}
+
+#[put("Users/{user_id}")]
+async fn replace_user(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    path: web::Path<Uuid>,
+    req: web::Json<ScimUserRequest>,
+) -> ResponseResult {
+    let user_id = path.into_inner();
+    let req = req.into_inner();
+    if db::scim::get_user(&db.pool, &organization.id, &user_id)
+        .await?
+        .is_none()
+    {
+        return Ok(scim_error(StatusCode::NOT_FOUND, "User not found"));
+    }
+
+    db::scim::update_user(
+        &db.pool,
+        &organization.id,
+        &user_id,
+        req.external_id.as_deref(),
+        None,
+    )
+    .await?;
+    set_user_active(&db, &cache, &organization.id, &user_id, req.active).await?;
+
+    let user = db::scim::get_user(&db.pool, &organization.id, &user_id).await?;
+    match user {
+        Some(user) => Ok(scim_response(StatusCode::OK, user_resource(&user))),
+        None => Ok(scim_error(StatusCode::NOT_FOUND, "User not found")),
+    }
+}
+
+#[derive(Deserialize)]
+struct PatchOperation {
+    op: String,
+    path: Option<String>,
+    value: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct PatchRequest {
+    #[serde(rename = "Operations")]
+    operations: Vec<PatchOperation>,
+}
+
+/// Some identity providers send booleans as strings, e.g. `"False"`
+fn value_as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) => s.to_lowercase().parse::<bool>().ok(),
+        _ => None,
+    }
+}
+
+#[patch("Users/{user_id}")]
+async fn patch_user(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    path: web::Path<Uuid>,
+    req: web::Json<PatchRequest>,
+) -> ResponseResult {
+    let user_id = path.into_inner();
+    if db::scim::get_user(&db.pool, &organization.id, &user_id)
+        .await?
+        .is_none()
+    {
+        return Ok(scim_error(StatusCode::NOT_FOUND, "User not found"));
+    }
+
+    for operation in req.into_inner().operations {
+        if !["replace", "add"].contains(&operation.op.to_lowercase().as_str()) {
+            return Ok(scim_error(
+                StatusCode::BAD_REQUEST,
+                "Only 'replace' and 'add' operations are supported for users",
+            ));
+        }
+        let Some(value) = operation.value else {
+            continue;
+        };
+        // Either `{"path": "active", "value": false}` or `{"value": {"active": false}}`.
+        // Other attributes, e.g. `displayName`, are of the user, which is shared between
+        // organizations, so they are not changed.
+        let active = match operation.path.as_deref() {
+            Some("active") => value_as_bool(&value),
+            Some(_) => None,
+            None => value.get("active").and_then(value_as_bool),
+        };
+        if let Some(active) = active {
+            set_user_active(&db, &cache, &organization.id, &user_id, active).await?;
+        }
+    }
+
+    let user = db::scim::get_user(&db.pool, &organization.id, &user_id).await?;
+    match user {
+        Some(user) => Ok(scim_response(StatusCode::OK, user_resource(&user))),
+        None => Ok(scim_error(StatusCode::NOT_FOUND, "User not found")),
+    }
+}
+
+#[delete("Users/{user_id}")]
+async fn delete_user(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    path: web::Path<Uuid>,
+) -> ResponseResult {
+    let user_id = path.into_inner();
+    if db::scim::get_user(&db.pool, &organization.id, &user_id)
+        .await?
+        .is_none()
+    {
+        return Ok(scim_error(StatusCode::NOT_FOUND, "User not found"));
+    }
+
+    db::scim::deprovision_user(&db.pool, &organization.id, &user_id, true).await?;
+    invalidate_user_cache(&db, &cache, &user_id).await?;
+    log::info!(
+        "SCIM deprovisioned user {} from organization {}",
+        user_id,
+        organization.id
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("Groups")]
+async fn list_groups(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    query: web::Query<ListQuery>,
+) -> ResponseResult {
+    let query = query.into_inner();
+    let display_name = match &query.filter {
+        Some(filter) => match parse_eq_filter(filter, "displayName") {
+            Some(display_name) => Some(display_name),
+            None => {
+                return Ok(scim_error(
+                    StatusCode::BAD_REQUEST,
+                    "Only 'displayName eq' filters are supported",
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let start_index = query.start_index.unwrap_or(1).max(1);
+    let count = query.count.unwrap_or(DEFAULT_PAGE_SIZE).clamp(0, DEFAULT_PAGE_SIZE);
+
+    let groups = db::scim::get_groups(
+        &db.pool,
+        &organization.id,
+        display_name.as_deref(),
+        Some(count),
+        start_index - 1,
+    )
+    .await?;
+    let group_ids = groups.iter().map(|group| group.id).collect();
+    let members = db::scim::get_group_members(&db.pool, &group_ids).await?;
+    let total = db::scim::count_groups(&db.pool, &organization.id, display_name.as_deref()).await?;
+
+    Ok(list_response(
+        groups
+            .iter()
+            .map(|group| group_resource(group, &members))
+            .collect(),
+        total,
+        start_index,
+    ))
+}
+
+#[get("Groups/{group_id}")]
+async fn get_group(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    path: web::Path<Uuid>,
+) -> ResponseResult {
+    let group_id = path.into_inner();
+    let Some(group) = db::scim::get_group(&db.pool, &organization.id, &group_id).await? else {
+        return Ok(scim_error(StatusCode::NOT_FOUND, "Group not found"));
+    };
+    let members = db::scim::get_group_members(&db.pool, &vec![group.id]).await?;
+
+    Ok(scim_response(
+        StatusCode::OK,
+        group_resource(&group, &members),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ScimMemberRef {
+    value: Uuid,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScimGroupRequest {
+    display_name: String,
+    external_id: Option<String>,
+    #[serde(default)]
+    members: Vec<ScimMemberRef>,
+}
+
+#[post("Groups")]
+async fn create_group(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    req: web::Json<ScimGroupRequest>,
+) -> ResponseResult {
+    let req = req.into_inner();
+    let group =
+        db::scim::create_group(&db.pool, &organization.id, &req.display_name, &req.external_id)
+            .await?;
+    let user_ids = req.members.iter().map(|m| m.value).collect();
+    db::scim::add_group_members(&db.pool, &organization.id, &group, &user_ids).await?;
+    let members = db::scim::get_group_members(&db.pool, &vec![group.id]).await?;
+
+    Ok(scim_response(
+        StatusCode::CREATED,
+        group_resource(&group, &members),
+    ))
+}
+
+/// Extracts member ids either from the value (`[{"value": "<id>"}]`) or from a
+/// path filter (`members[value eq "<id>"]`), as sent by different identity providers.
+fn patch_member_ids(operation: &PatchOperation) -> Vec<Uuid> {
+    if let Some(id) = operation
+        .path
+        .as_deref()
+        .and_then(|path| path.strip_prefix("members["))
+        .and_then(|filter| filter.strip_suffix(']'))
+        .and_then(|filter| parse_eq_filter(filter, "value"))
+        .and_then(|id| Uuid::parse_str(&id).ok())
+    {
+        return vec![id];
+    }
+    match &operation.value {
+        Some(value) => serde_json::from_value::<Vec<ScimMemberRef>>(value.clone())
+            .map(|members| members.into_iter().map(|m| m.value).collect())
+            .unwrap_or_default(),
+        None => vec![],
+    }
+}
+
+#[patch("Groups/{group_id}")]
+async fn patch_group(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    path: web::Path<Uuid>,
+    req: web::Json<PatchRequest>,
+) -> ResponseResult {
+    let group_id = path.into_inner();
+    let Some(group) = db::scim::get_group(&db.pool, &organization.id, &group_id).await? else {
+        return Ok(scim_error(StatusCode::NOT_FOUND, "Group not found"));
+    };
+
+    let mut affected_users = HashSet::new();
+    for operation in req.into_inner().operations {
+        let is_members_op = operation
+            .path
+            .as_deref()
+            .is_some_and(|path| path.starts_with("members"));
+        if !is_members_op {
+            // Renames and other attribute changes do not affect access
+            continue;
+        }
+        let user_ids = patch_member_ids(&operation);
+        match operation.op.to_lowercase().as_str() {
+            "add" => {
+                db::scim::add_group_members(&db.pool, &organization.id, &group, &user_ids).await?
+            }
+            "remove" => db::scim::remove_group_members(&db.pool, &group, &user_ids).await?,
+            "replace" => {
+                let current = db::scim::get_group_members(&db.pool, &vec![group.id])
+                    .await?
+                    .into_iter()
+                    .map(|member| member.user_id)
+                    .filter(|id| !user_ids.contains(id))
+                    .collect::<Vec<_>>();
+                db::scim::remove_group_members(&db.pool, &group, &current).await?;
+                affected_users.extend(current);
+                db::scim::add_group_members(&db.pool, &organization.id, &group, &user_ids).await?
+            }
+            _ => {
+                return Ok(scim_error(
+                    StatusCode::BAD_REQUEST,
+                    "Unsupported patch operation",
+                ))
+            }
+        }
+        affected_users.extend(user_ids);
+    }
+
+    for user_id in affected_users {
+        invalidate_user_cache(&db, &cache, &user_id).await?;
+    }
+
+    let members = db::scim::get_group_members(&db.pool, &vec![group.id]).await?;
+    Ok(scim_response(
+        StatusCode::OK,
+        group_resource(&group, &members),
+    ))
+}
+
+#[delete("Groups/{group_id}")]
+async fn delete_group(
+    organization: ScimOrganization,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    path: web::Path<Uuid>,
+) -> ResponseResult {
+    let group_id = path.into_inner();
+    let Some(group) = db::scim::get_group(&db.pool, &organization.id, &group_id).await? else {
+        return Ok(scim_error(StatusCode::NOT_FOUND, "Group not found"));
+    };
+
+    let user_ids = db::scim::get_group_members(&db.pool, &vec![group.id])
+        .await?
+        .into_iter()
+        .map(|member| member.user_id)
+        .collect::<Vec<_>>();
+    db::scim::remove_group_members(&db.pool, &group, &user_ids).await?;
+    db::scim::delete_group(&db.pool, &organization.id, &group.id).await?;
+    for user_id in user_ids {
+        invalidate_user_cache(&db, &cache, &user_id).await?;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_eq_filter() {
+        assert_eq!(
+            parse_eq_filter("userName eq \"jane@example.com\"", "userName"),
+            Some("jane@example.com".to_string())
+        );
+        assert_eq!(
+            parse_eq_filter("username EQ \"jane@example.com\"", "userName"),
+            Some("jane@example.com".to_string())
+        );
+        assert_eq!(
+            parse_eq_filter("displayName eq \"Engineering Team\"", "displayName"),
+            Some("Engineering Team".to_string())
+        );
+        assert_eq!(parse_eq_filter("userName co \"jane\"", "userName"), None);
+        assert_eq!(parse_eq_filter("userName eq jane", "userName"), None);
+    }
+}
@@ -0,0 +1,76 @@
+use actix_web::{delete, post, web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{error::Error, ResponseResult};
+use crate::{
+    auth::{
+        authenticated_with_api_key,
+        session_tokens::{self, SessionTokenClaims, SESSION_TTL_HOURS},
+    },
+    db::{self, user::User, DB},
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionTokenResponse {
+    session_id: Uuid,
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Start a UI session and mint its first token. Called by the frontend server with the
+/// user's API key on sign in, so that the key itself never reaches the browser.
+#[post("")]
+async fn create_session(http_req: HttpRequest, user: User, db: web::Data<DB>) -> ResponseResult {
+    if !authenticated_with_api_key(&http_req) {
+        return Err(Error::Forbidden);
+    }
+
+    let session_expires_at = Utc::now() + Duration::hours(SESSION_TTL_HOURS);
+    let session_id = db::ui_sessions::create_session(&db.pool, &user.id, session_expires_at).await?;
+    let (token, expires_at) = session_tokens::mint_token(session_id, user.id, session_expires_at)?;
+
+    Ok(HttpResponse::Ok().json(SessionTokenResponse {
+        session_id,
+        token,
+        expires_at,
+    }))
+}
+
+/// Exchange a valid session token for a fresh one, as long as the session is active
+#[post("refresh")]
+async fn refresh_session_token(http_req: HttpRequest, db: web::Data<DB>) -> ResponseResult {
+    let Some(claims) = http_req.extensions().get::<SessionTokenClaims>().cloned() else {
+        return Err(Error::invalid_request(Some(
+            "Only session tokens can be refreshed",
+        )));
+    };
+
+    let session_expires_at =
+        db::ui_sessions::get_active_session_expiry(&db.pool, &claims.session_id, &claims.user_id)
+            .await?
+            .ok_or(Error::Forbidden)?;
+    let (token, expires_at) =
+        session_tokens::mint_token(claims.session_id, claims.user_id, session_expires_at)?;
+
+    Ok(HttpResponse::Ok().json(SessionTokenResponse {
+        session_id: claims.session_id,
+        token,
+        expires_at,
+    }))
+}
+
+/// Revoke the session on logout. All tokens minted for it stop working immediately.
+#[delete("{session_id}")]
+async fn revoke_session(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let session_id = path.into_inner();
+    db::ui_sessions::revoke_session(&db.pool, &session_id, &user.id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,185 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self,
+        shadow_deployments::{
+            NewShadowDeployment, ShadowComparison, ShadowComparisonStats, ShadowDeployment,
+        },
+        user::User,
+        DB,
+    },
+    language_model::providers::utils::get_required_env_vars_for_model,
+    traces::evaluators::get_stored_env,
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+const RECENT_COMPARISONS_LIMIT: i64 = 50;
+
+#[get("shadow-deployments")]
+pub async fn get_shadow_deployments(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let deployments = db::shadow_deployments::get_shadow_deployments(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(deployments))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateShadowDeploymentRequest {
+    name: String,
+    span_path: String,
+    candidate_model: String,
+    #[serde(default)]
+    candidate_system_prompt: Option<String>,
+    sample_percentage: f64,
+}
+
+/// Starts re-executing `samplePercentage` percent of the LLM spans at `spanPath` with the
+/// candidate model and system prompt
+#[post("shadow-deployments")]
+pub async fn create_shadow_deployment(
+    path: web::Path<Uuid>,
+    req: web::Json<CreateShadowDeploymentRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    if !(req.sample_percentage > 0.0 && req.sample_percentage <= 100.0) {
+        return Err(Error::invalid_request(Some(
+            "Sample percentage must be greater than 0 and at most 100",
+        )));
+    }
+    if !req.candidate_model.contains(':') {
+        return Err(Error::invalid_request(Some(
+            "Candidate model must be in the format of \"provider:model_name\"",
+        )));
+    }
+
+    let env = get_stored_env(db.clone().into_inner(), project_id).await?;
+    let mut missing_env_vars = get_required_env_vars_for_model(&req.candidate_model)
+        .into_iter()
+        .filter(|name| !env.contains_key(name))
+        .collect::<Vec<_>>();
+    if !missing_env_vars.is_empty() {
+        missing_env_vars.sort();
+        return Err(Error::invalid_request(Some(&format!(
+            "Add {} to the project's api keys to run the candidate model",
+            missing_env_vars.join(", ")
+        ))));
+    }
+
+    let deployment = db::shadow_deployments::create_shadow_deployment(
+        &db.pool,
+        &project_id,
+        &NewShadowDeployment {
+            name: req.name,
+            span_path: req.span_path,
+            candidate_model: req.candidate_model,
+            candidate_system_prompt: req.candidate_system_prompt,
+            sample_percentage: req.sample_percentage,
+        },
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(deployment))
+}
+
+#[derive(Deserialize)]
+struct UpdateShadowDeploymentRequest {
+    active: bool,
+}
+
+/// Pauses or resumes a shadow deployment
+#[post("shadow-deployments/{deployment_id}")]
+pub async fn update_shadow_deployment(
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<UpdateShadowDeploymentRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, deployment_id) = path.into_inner();
+
+    match db::shadow_deployments::set_shadow_deployment_active(
+        &db.pool,
+        &project_id,
+        &deployment_id,
+        req.active,
+    )
+    .await?
+    {
+        Some(deployment) => Ok(HttpResponse::Ok().json(deployment)),
+        None => Err(Error::not_found("Shadow deployment not found")),
+    }
+}
+
+#[delete("shadow-deployments/{deployment_id}")]
+pub async fn delete_shadow_deployment(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, deployment_id) = path.into_inner();
+    ensure_no_legal_hold(
+        &db,
+        &project_id,
+        &user,
+        "shadow_deployment",
+        Some(&deployment_id),
+    )
+    .await?;
+
+    if !db::shadow_deployments::delete_shadow_deployment(&db.pool, &project_id, &deployment_id)
+        .await?
+    {
+        return Err(Error::not_found("Shadow deployment not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShadowDeploymentComparison {
+    deployment: ShadowDeployment,
+    stats: ShadowComparisonStats,
+    /// Share of the decided comparisons, i.e. without ties, where the candidate was better
+    candidate_win_rate: Option<f64>,
+    recent_comparisons: Vec<ShadowComparison>,
+}
+
+/// Candidate-vs-production comparison so far, updated as sampled spans are judged
+#[get("shadow-deployments/{deployment_id}/comparison")]
+pub async fn get_shadow_deployment_comparison(
+    path: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, deployment_id) = path.into_inner();
+
+    let Some(deployment) =
+        db::shadow_deployments::get_shadow_deployment(&db.pool, &project_id, &deployment_id)
+            .await?
+    else {
+        return Err(Error::not_found("Shadow deployment not found"));
+    };
+    let stats =
+        db::shadow_deployments::get_shadow_comparison_stats(&db.pool, &deployment_id).await?;
+    let recent_comparisons = db::shadow_deployments::get_recent_shadow_comparisons(
+        &db.pool,
+        &deployment_id,
+        RECENT_COMPARISONS_LIMIT,
+    )
+    .await?;
+
+    let decided = stats.candidate_wins + stats.production_wins;
+    let candidate_win_rate = (decided > 0).then(|| stats.candidate_wins as f64 / decided as f64);
+
+    Ok(HttpResponse::Ok().json(ShadowDeploymentComparison {
+        deployment,
+        stats,
+        candidate_win_rate,
+        recent_comparisons,
+    }))
+}
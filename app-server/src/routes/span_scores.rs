@@ -0,0 +1,243 @@
+use std::{collections::HashMap, sync::Arc};
+
+use actix_web::{get, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    error::Error,
+    evaluations::{DEFAULT_BUCKET_COUNT, DEFAULT_LOWER_BOUND},
+    ResponseResult,
+};
+use crate::{
+    analytics::AnalyticsStore,
+    ch::{
+        downsampling::{downsample, MAX_CHART_POINTS},
+        modifiers::GroupByInterval,
+        span_scores::AnnotatorSpanScore,
+    },
+    db::{self, DB},
+    evaluations::agreement::{score_agreement, AgreementMetric},
+};
+
+const MAX_SCATTER_POINTS: u64 = 5000;
+const MAX_AGREEMENT_SCORES: u64 = 100_000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanScoresQuery {
+    score_name: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    #[serde(default)]
+    group_by_interval: GroupByInterval,
+}
+
+impl SpanScoresQuery {
+    fn validate(&self) -> Result<(), Error> {
+        if self.start_time >= self.end_time {
+            return Err(Error::invalid_request(Some(
+                "startTime must be before endTime",
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GetSpanScoreStatsResponse {
+    average_value: f64,
+}
+
+#[get("span-score-stats")]
+async fn get_span_score_stats(
+    path: web::Path<Uuid>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<SpanScoresQuery>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    query.validate()?;
+
+    let average_value = analytics_store
+        .get_average_span_score(
+            project_id,
+            query.score_name,
+            query.start_time,
+            query.end_time,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(GetSpanScoreStatsResponse { average_value }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpanScoreBucket {
+    lower_bound: f64,
+    upper_bound: f64,
+    height: u64,
+}
+
+/// Distribution of the online scores in the period, in the same buckets as evaluation scores
+#[get("span-score-distribution")]
+async fn get_span_score_distribution(
+    path: web::Path<Uuid>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<SpanScoresQuery>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    query.validate()?;
+
+    let bounds = analytics_store
+        .get_span_scores_bounds(
+            project_id,
+            query.score_name.clone(),
+            query.start_time,
+            query.end_time,
+        )
+        .await?;
+    if bounds.upper_bound < DEFAULT_LOWER_BOUND {
+        return Err(anyhow::anyhow!(
+            "Upper bound is less than lower bound: {} < {}",
+            bounds.upper_bound,
+            DEFAULT_LOWER_BOUND
+        )
+        .into());
+    }
+
+    let buckets = analytics_store
+        .get_span_score_buckets_based_on_bounds(
+            project_id,
+            query.score_name,
+            query.start_time,
+            query.end_time,
+            DEFAULT_LOWER_BOUND,
+            bounds.upper_bound,
+            DEFAULT_BUCKET_COUNT,
+        )
+        .await?;
+
+    let buckets = buckets
+        .into_iter()
+        .map(|bucket| SpanScoreBucket {
+            lower_bound: bucket.lower_bound,
+            upper_bound: bucket.upper_bound,
+            height: bucket.height,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(buckets))
+}
+
+/// Average online score per time interval
+#[get("span-score-trend")]
+async fn get_span_score_trend(
+    path: web::Path<Uuid>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<SpanScoresQuery>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    query.validate()?;
+
+    let group_by_interval = query.group_by_interval.coarsen_for_range(
+        (query.end_time - query.start_time).num_seconds(),
+        MAX_CHART_POINTS,
+    );
+    let values = analytics_store
+        .get_span_score_trend(
+            group_by_interval,
+            project_id,
+            query.score_name,
+            query.start_time,
+            query.end_time,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
+}
+
+/// Score of the most recently scored spans next to their latency and cost, to see whether
+/// cheaper or faster configurations trade off quality
+#[get("span-score-scatter")]
+async fn get_span_score_scatter(
+    path: web::Path<Uuid>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<SpanScoresQuery>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    query.validate()?;
+
+    let points = analytics_store
+        .get_span_score_scatter(
+            project_id,
+            query.score_name,
+            query.start_time,
+            query.end_time,
+            MAX_SCATTER_POINTS,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(points))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanScoreAgreementQuery {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+/// Agreement between the annotators of each manual score in the period, for spans scored by
+/// several annotators, e.g. through labeling queues. Scores of label classes are categorical, other
+/// scores are treated as numeric.
+#[get("span-score-agreement")]
+async fn get_span_score_agreement(
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    query: web::Query<SpanScoreAgreementQuery>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    if query.start_time >= query.end_time {
+        return Err(Error::invalid_request(Some(
+            "startTime must be before endTime",
+        )));
+    }
+
+    let scores = analytics_store
+        .get_annotator_span_scores(
+            project_id,
+            query.start_time,
+            query.end_time,
+            MAX_AGREEMENT_SCORES,
+        )
+        .await?;
+    let label_classes =
+        db::labels::get_label_classes_by_project_id(&db.pool, project_id, None).await?;
+
+    let mut by_name = HashMap::<String, Vec<AnnotatorSpanScore>>::new();
+    for score in scores {
+        by_name.entry(score.name.clone()).or_default().push(score);
+    }
+    let mut agreements = by_name
+        .into_iter()
+        .map(|(name, scores)| {
+            let metric = if label_classes.iter().any(|class| class.name == name) {
+                AgreementMetric::CohensKappa
+            } else {
+                AgreementMetric::KrippendorffsAlpha
+            };
+            score_agreement(name, metric, &scores)
+        })
+        .collect::<Vec<_>>();
+    agreements.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(HttpResponse::Ok().json(agreements))
+}
@@ -24,7 +24,8 @@ use uuid::Uuid;
 use crate::{
     db::{self, user::User},
     features::{is_feature_enabled, Feature},
-    routes::ResponseResult,
+    logging,
+    routes::{error::Error, ResponseResult},
     traces::limits::update_workspace_limit_exceeded_by_workspace_id,
 };
 
@@ -41,7 +42,7 @@ pub async fn save_stripe_customer_id(
     user: User,
 ) -> ResponseResult {
     if !is_feature_enabled(Feature::Subscription) {
-        return Ok(HttpResponse::Forbidden().finish());
+        return Err(Error::Forbidden);
     }
     db::subscriptions::save_stripe_customer_id(&db.pool, &user.id, &request.stripe_customer_id)
         .await?;
@@ -52,7 +53,7 @@ pub async fn save_stripe_customer_id(
 #[get("")] // GET /api/v1/subscriptions
 pub async fn get_user_subscription_info(db: web::Data<db::DB>, user: User) -> ResponseResult {
     if !is_feature_enabled(Feature::Subscription) {
-        return Ok(HttpResponse::Forbidden().finish());
+        return Err(Error::Forbidden);
     }
     let stripe_customer_id =
         db::subscriptions::get_user_subscription_info(&db.pool, &user.id).await?;
@@ -82,7 +83,7 @@ pub async fn update_subscription(
     request: web::Json<ManageSubscriptionRequest>,
 ) -> ResponseResult {
     if !is_feature_enabled(Feature::Subscription) {
-        return Ok(HttpResponse::Forbidden().finish());
+        return Err(Error::Forbidden);
     }
     let db = db.into_inner();
     let cache = cache.into_inner();
@@ -109,7 +110,7 @@ pub async fn update_subscription(
     .await?;
 
     if is_upgrade_from_free {
-        tokio::spawn(async move {
+        logging::spawn(async move {
             let _ = db::subscriptions::reset_workspace_usage(db.clone(), workspace_id).await;
             let _ = update_workspace_limit_exceeded_by_workspace_id(
                 db.clone(),
@@ -0,0 +1,89 @@
+use actix_web::{get, put, web, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::db::{self, tags::TaggedResourceType, utils::is_valid_slug, DB};
+
+use super::{error::Error, ResponseResult};
+
+const MAX_TAGS_PER_RESOURCE: usize = 50;
+
+#[get("tags")]
+pub async fn get_project_tags(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let tags = db::tags::get_project_tags(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(tags))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetTaggedResourcesQuery {
+    tag: String,
+    #[serde(default)]
+    resource_type: Option<TaggedResourceType>,
+}
+
+/// Datasets, evaluations and pipelines with the tag
+#[get("tagged-resources")]
+pub async fn get_tagged_resources(
+    path: web::Path<Uuid>,
+    query: web::Query<GetTaggedResourcesQuery>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query = query.into_inner();
+    let resources =
+        db::tags::get_tagged_resources(&db.pool, &project_id, &query.tag, query.resource_type)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(resources))
+}
+
+#[get("tags/{resource_type}/{resource_id}")]
+pub async fn get_resource_tags(
+    path: web::Path<(Uuid, TaggedResourceType, Uuid)>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, resource_type, resource_id) = path.into_inner();
+    let tags =
+        db::tags::get_resource_tags(&db.pool, &project_id, resource_type, &resource_id).await?;
+
+    Ok(HttpResponse::Ok().json(tags))
+}
+
+#[derive(Deserialize)]
+struct SetResourceTagsRequest {
+    tags: Vec<String>,
+}
+
+/// Tags have the same format as slugs, e.g. `checkout-flow`
+#[put("tags/{resource_type}/{resource_id}")]
+pub async fn set_resource_tags(
+    path: web::Path<(Uuid, TaggedResourceType, Uuid)>,
+    req: web::Json<SetResourceTagsRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, resource_type, resource_id) = path.into_inner();
+    let tags = req.into_inner().tags;
+
+    if tags.len() > MAX_TAGS_PER_RESOURCE {
+        return Err(Error::invalid_request(Some(&format!(
+            "At most {} tags are allowed per resource",
+            MAX_TAGS_PER_RESOURCE
+        ))));
+    }
+    if let Some(tag) = tags.iter().find(|tag| !is_valid_slug(tag)) {
+        return Err(Error::invalid_request(Some(&format!(
+            "Invalid tag: {}. Tags must be lowercase alphanumeric words separated by dashes",
+            tag
+        ))));
+    }
+    if !db::tags::resource_exists(&db.pool, &project_id, resource_type, &resource_id).await? {
+        return Err(Error::not_found("Resource not found"));
+    }
+
+    db::tags::set_resource_tags(&db.pool, &project_id, resource_type, &resource_id, &tags).await?;
+
+    Ok(HttpResponse::Ok().json(tags))
+}
@@ -1,16 +1,40 @@
-use super::{GetMetricsQueryParams, ResponseResult};
-use super::{PaginatedGetQueryParams, PaginatedResponse, DEFAULT_PAGE_SIZE};
-use crate::ch::utils::get_bounds;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use super::{compliance::record_data_access, error::Error, GetMetricsQueryParams, ResponseResult};
+use super::{FilterQueryRequest, PaginatedGetQueryParams, PaginatedResponse, DEFAULT_PAGE_SIZE};
 use crate::{
-    ch::{self, modifiers::GroupByInterval, Aggregation},
+    analytics::AnalyticsStore,
+    cache::Cache,
+    ch::{
+        downsampling::{downsample, MAX_CHART_POINTS},
+        filter_expression::FilterTarget,
+        modifiers::GroupByInterval,
+        span_search::{SpanSearchField, SpanSearchResult, MIN_QUERY_LENGTH},
+        Aggregation,
+    },
     db::{
         self,
+        data_access_log::AccessedResourceType,
         events::EventWithTemplateName,
+        field_visibility::get_field_visibility,
+        labels::{SpanLabel, TraceScoreSummary},
+        masking_profiles::get_masking_profile_of_user,
         modifiers::{DateRange, Filter, RelativeDateInterval},
         spans::Span,
         trace::{Session, Trace, TraceWithTopSpan},
+        user::User,
         DB,
     },
+    logging,
+    storage::Storage,
+    traces::{
+        agent_graph::build_agent_graph,
+        browser::build_timeline,
+        ingestion_lag::{ingestion_status, insert_ingestion_headers, wait_for_in_flight_spans},
+        masking,
+        promoted_attributes::{attribute_slots, get_promoted_attributes},
+        snapshots::{self, SnapshotView},
+    },
 };
 use actix_web::{get, post, web, HttpResponse};
 use chrono::{DateTime, Utc};
@@ -22,9 +46,12 @@ use uuid::Uuid;
 pub async fn get_traces(
     path: web::Path<Uuid>,
     db: web::Data<DB>,
+    user: User,
     query_params: web::Query<PaginatedGetQueryParams>,
 ) -> ResponseResult {
     let project_id = path.into_inner();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+    let masking_profile = get_masking_profile_of_user(&db.pool, &user.id, &project_id).await?;
     let query_params = query_params.into_inner();
     let limit = query_params.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
     let offset = limit * query_params.page_number;
@@ -43,7 +70,7 @@ pub async fn get_traces(
     let date_range_clone = date_range.clone();
     let text_search_filter_clone = text_search_filter.clone();
 
-    let get_traces_task = tokio::spawn(async move {
+    let get_traces_task = logging::spawn(async move {
         db::trace::get_traces(
             &pool,
             project_id,
@@ -52,16 +79,18 @@ pub async fn get_traces(
             &Some(filters_vec_clone),
             &date_range_clone,
             text_search_filter_clone,
+            &visibility,
         )
         .await
     });
-    let count_traces_task = tokio::spawn(async move {
+    let count_traces_task = logging::spawn(async move {
         let total_count = db::trace::count_traces(
             &db.pool,
             project_id,
             &Some(filters_vec),
             &date_range,
             text_search_filter,
+            &visibility,
         )
         .await
         .unwrap_or(0) as u64;
@@ -72,7 +101,13 @@ pub async fn get_traces(
     });
 
     let (traces_result, count_result) = tokio::join!(get_traces_task, count_traces_task);
-    let traces = traces_result.map_err(|e| anyhow::anyhow!("Failed to get traces: {:?}", e))??;
+    let mut traces =
+        traces_result.map_err(|e| anyhow::anyhow!("Failed to get traces: {:?}", e))??;
+    if let Some(profile) = &masking_profile {
+        traces
+            .iter_mut()
+            .for_each(|trace| masking::mask_trace_with_top_span(trace, profile));
+    }
 
     let (total_count, any_in_project) =
         count_result.map_err(|e| anyhow::anyhow!("Failed to count traces: {:?}", e))?;
@@ -83,7 +118,9 @@ pub async fn get_traces(
         any_in_project,
     };
 
-    Ok(HttpResponse::Ok().json(response))
+    let mut res = HttpResponse::Ok();
+    insert_ingestion_headers(&mut res, &project_id);
+    Ok(res.json(response))
 }
 
 #[derive(Serialize)]
@@ -92,34 +129,266 @@ struct TraceWithSpanPreviews {
     #[serde(flatten)]
     trace: Trace,
     spans: Vec<Span>,
+    /// Aggregates of the scores of the trace's spans by label class
+    scores: Vec<TraceScoreSummary>,
 }
 
+/// How long consistent reads wait for the spans of the trace to be ingested
+const CONSISTENT_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GetTraceParams {
     #[serde(default)]
     search: Option<String>,
+    /// Read-your-writes: waits for the spans of the trace that were just accepted, and returns
+    /// those still being ingested along with the recorded ones
+    #[serde(default)]
+    consistent: bool,
 }
 
 #[get("traces/{trace_id}")]
 pub async fn get_single_trace(
     params: web::Path<(Uuid, Uuid)>,
     db: web::Data<DB>,
+    user: User,
     query_params: web::Query<GetTraceParams>,
 ) -> ResponseResult {
     let (project_id, trace_id) = params.into_inner();
     let search = query_params.search.clone();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+    let masking_profile = get_masking_profile_of_user(&db.pool, &user.id, &project_id).await?;
 
-    let trace = db::trace::get_single_trace(&db.pool, trace_id).await?;
+    let in_flight_spans = if query_params.consistent {
+        wait_for_in_flight_spans(project_id, trace_id, CONSISTENT_READ_TIMEOUT).await
+    } else {
+        Vec::new()
+    };
 
-    let span_previews = db::spans::get_trace_spans(&db.pool, trace_id, project_id, search).await?;
+    let mut trace = db::trace::get_single_trace(&db.pool, trace_id, &visibility).await?;
+
+    let mut span_previews =
+        db::spans::get_trace_spans(&db.pool, trace_id, project_id, search.clone(), &visibility)
+            .await?;
+    // In-flight spans can't be searched, and may have been recorded since they were read
+    if search.is_none() {
+        for mut span in in_flight_spans {
+            if span_previews.iter().any(|s| s.span_id == span.span_id) {
+                continue;
+            }
+            if !visibility.span_input {
+                span.input = None;
+            }
+            if !visibility.span_output {
+                span.output = None;
+            }
+            span_previews.push(span);
+        }
+        span_previews.sort_by_key(|span| span.start_time);
+    }
+    let scores = db::labels::get_trace_score_summaries(&db.pool, trace_id, project_id).await?;
+
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::TRACE,
+        vec![trace_id],
+    );
+
+    if let Some(profile) = &masking_profile {
+        masking::mask_trace(&mut trace, profile);
+        span_previews
+            .iter_mut()
+            .for_each(|span| masking::mask_span(span, profile));
+    }
 
     let trace_with_spans = TraceWithSpanPreviews {
         trace,
         spans: span_previews,
+        scores,
     };
 
-    Ok(HttpResponse::Ok().json(trace_with_spans))
+    let mut res = HttpResponse::Ok();
+    insert_ingestion_headers(&mut res, &project_id);
+    Ok(res.json(trace_with_spans))
+}
+
+const MAX_EXTERNAL_ID_TRACES: i64 = 100;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetTracesByExternalIdParams {
+    external_id: String,
+}
+
+/// Traces with the external id that SDKs set in the `external_id` association property, e.g.
+/// the order id or the ticket number of a support request
+#[get("traces/by-external-id")]
+pub async fn get_traces_by_external_id(
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    user: User,
+    query_params: web::Query<GetTracesByExternalIdParams>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let external_id = query_params.external_id.trim();
+    if external_id.is_empty() {
+        return Err(Error::invalid_request(Some(
+            "External id must not be empty",
+        )));
+    }
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+
+    let traces = db::trace::get_traces_by_external_id(
+        &db.pool,
+        project_id,
+        external_id,
+        &visibility,
+        MAX_EXTERNAL_ID_TRACES,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(traces))
+}
+
+/// Spans accepted for the project that are not yet visible, see `traces::ingestion_lag`
+#[get("traces/ingestion-status")]
+pub async fn get_ingestion_status(path: web::Path<Uuid>) -> ResponseResult {
+    let project_id = path.into_inner();
+    Ok(HttpResponse::Ok().json(ingestion_status(&project_id)))
+}
+
+/// Spans of the trace interleaved with the events recorded in the browser of a browser agent,
+/// each event attributed to the LLM span whose action resulted in it
+#[get("traces/{trace_id}/browser-timeline")]
+pub async fn get_browser_timeline(
+    params: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    user: User,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let (project_id, trace_id) = params.into_inner();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+
+    let spans =
+        db::spans::get_trace_spans(&db.pool, trace_id, project_id, None, &visibility).await?;
+    let events = analytics_store
+        .get_trace_browser_events(project_id, trace_id)
+        .await?;
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::TRACE,
+        vec![trace_id],
+    );
+
+    Ok(HttpResponse::Ok().json(build_timeline(spans, events)))
+}
+
+/// Agents of a multi-agent run and the messages between them
+#[get("traces/{trace_id}/agent-graph")]
+pub async fn get_agent_graph(
+    params: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    user: User,
+) -> ResponseResult {
+    let (project_id, trace_id) = params.into_inner();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+
+    let spans =
+        db::spans::get_trace_spans(&db.pool, trace_id, project_id, None, &visibility).await?;
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::TRACE,
+        vec![trace_id],
+    );
+
+    Ok(HttpResponse::Ok().json(build_agent_graph(spans)))
+}
+
+/// Checkpoints of an agent run, with the number of times the run was resumed from each
+#[get("traces/{trace_id}/checkpoints")]
+pub async fn get_agent_checkpoints(
+    params: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    user: User,
+) -> ResponseResult {
+    let (project_id, trace_id) = params.into_inner();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+    let masking_profile = get_masking_profile_of_user(&db.pool, &user.id, &project_id).await?;
+    let mut checkpoints =
+        db::agent_checkpoints::get_agent_checkpoints(&db.pool, &project_id, &trace_id).await?;
+
+    // the state holds the conversation of the agent, i.e. the inputs and outputs of its spans
+    if !visibility.span_input || !visibility.span_output {
+        checkpoints
+            .iter_mut()
+            .for_each(|checkpoint| checkpoint.state = Value::Null);
+    } else if let Some(profile) = &masking_profile {
+        checkpoints
+            .iter_mut()
+            .for_each(|checkpoint| masking::mask_agent_checkpoint(checkpoint, profile));
+    }
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::TRACE,
+        vec![trace_id],
+    );
+
+    Ok(HttpResponse::Ok().json(checkpoints))
+}
+
+#[derive(Deserialize)]
+pub struct GetNearestSnapshotQuery {
+    timestamp: DateTime<Utc>,
+}
+
+/// Screenshot and DOM snapshot of the browser session taken closest to the timestamp
+#[get("browser-sessions/{session_id}/snapshots/nearest")]
+pub async fn get_nearest_browser_snapshot(
+    params: web::Path<(Uuid, String)>,
+    query: web::Query<GetNearestSnapshotQuery>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+) -> ResponseResult {
+    let (project_id, session_id) = params.into_inner();
+    let snapshot = analytics_store
+        .get_nearest_browser_snapshot(project_id, session_id, query.into_inner().timestamp)
+        .await?;
+
+    match snapshot {
+        Some(snapshot) => Ok(HttpResponse::Ok().json(SnapshotView::from(snapshot))),
+        None => Err(Error::not_found("Browser session has no snapshots")),
+    }
+}
+
+/// What changed in the browser between the previous step of the session and the step: the page,
+/// the screenshot and the added and removed parts of the DOM
+#[get("browser-sessions/{session_id}/snapshots/{step}/diff")]
+pub async fn get_browser_snapshot_diff(
+    params: web::Path<(Uuid, String, u32)>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> ResponseResult {
+    let (project_id, session_id, step) = params.into_inner();
+    let diff = snapshots::diff_step(
+        analytics_store.as_ref().clone(),
+        storage.as_ref().clone(),
+        project_id,
+        session_id,
+        step,
+    )
+    .await?;
+
+    match diff {
+        Some(diff) => Ok(HttpResponse::Ok().json(diff)),
+        None => Err(Error::not_found("Step has no snapshot")),
+    }
 }
 
 #[derive(Serialize)]
@@ -128,16 +397,224 @@ struct SpanWithEvents {
     #[serde(flatten)]
     span: Span,
     events: Vec<EventWithTemplateName>,
+    /// Labels from online evaluators and manual feedback
+    scores: Vec<SpanLabel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchSpansQueryParams {
+    query: String,
+    /// Input, output or all, which is the default
+    #[serde(default)]
+    field: SpanSearchField,
+    #[serde(default)]
+    page_number: usize,
+    #[serde(default)]
+    page_size: Option<usize>,
+    /// Defaults to the past 24 hours
+    #[serde(default, flatten)]
+    date_range: Option<DateRange>,
+}
+
+/// Finds the spans whose input or output contains the query, ignoring case. Hidden fields of
+/// the member's role are not searched.
+#[get("spans/search")]
+pub async fn search_spans(
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    user: User,
+    query_params: web::Query<SearchSpansQueryParams>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let query_params = query_params.into_inner();
+    let query = query_params.query.trim();
+    if query.chars().count() < MIN_QUERY_LENGTH {
+        return Err(Error::invalid_request(Some(&format!(
+            "Search query must be at least {} characters",
+            MIN_QUERY_LENGTH
+        ))));
+    }
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+    let masking_profile = get_masking_profile_of_user(&db.pool, &user.id, &project_id).await?;
+    let field = match (
+        query_params.field.searches_input() && visibility.span_input,
+        query_params.field.searches_output() && visibility.span_output,
+    ) {
+        (true, true) => SpanSearchField::All,
+        (true, false) => SpanSearchField::Input,
+        (false, true) => SpanSearchField::Output,
+        (false, false) => {
+            return Ok(
+                HttpResponse::Ok().json(PaginatedResponse::<SpanSearchResult> {
+                    total_count: 0,
+                    items: vec![],
+                    any_in_project: true,
+                }),
+            )
+        }
+    };
+
+    let (start_time, end_time) = match query_params.date_range.unwrap_or_default() {
+        DateRange::Relative(interval) if interval.past_hours == "all" => {
+            (DateTime::UNIX_EPOCH, Utc::now())
+        }
+        DateRange::Relative(interval) => {
+            let past_hours = interval
+                .past_hours
+                .parse::<i64>()
+                .map_err(|_| Error::invalid_request(Some("Invalid pastHours")))?;
+            (Utc::now() - chrono::Duration::hours(past_hours), Utc::now())
+        }
+        DateRange::Absolute(interval) => (interval.start_date, interval.end_date),
+    };
+    let limit = query_params.page_size.unwrap_or(DEFAULT_PAGE_SIZE) as u64;
+    let offset = limit * query_params.page_number as u64;
+
+    let (results, total_count) = tokio::join!(
+        analytics_store.search_spans(project_id, query, field, start_time, end_time, limit, offset),
+        analytics_store.count_span_search_results(project_id, query, field, start_time, end_time),
+    );
+    let mut results = results?;
+    if let Some(profile) = &masking_profile {
+        results
+            .iter_mut()
+            .for_each(|result| masking::mask_span_search_result(result, profile));
+    }
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::SPAN,
+        results.iter().map(|result| result.span_id).collect(),
+    );
+
+    let response = PaginatedResponse::<SpanSearchResult> {
+        total_count: total_count?,
+        items: results,
+        any_in_project: true,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Spans of the period that match the filter expression, the newest first. Promoted attributes
+/// are filtered on as `attributes.<key>`.
+#[post("spans/query")]
+pub async fn query_spans(
+    path: web::Path<Uuid>,
+    req: web::Json<FilterQueryRequest>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    user: User,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+    let hidden_fields: &[&str] = if visibility.user_id {
+        &[]
+    } else {
+        &["user_id"]
+    };
+    let attribute_slots = attribute_slots(
+        &get_promoted_attributes(db.clone().into_inner(), cache.into_inner(), project_id).await?,
+    );
+    let filter = req.compile_filter(FilterTarget::Spans, &attribute_slots, hidden_fields)?;
+    let (start_time, end_time) = req.time_range()?;
+
+    let spans = analytics_store
+        .query_spans(
+            project_id,
+            &filter,
+            start_time,
+            end_time,
+            req.limit()?,
+            req.offset,
+        )
+        .await?;
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::SPAN,
+        spans.iter().map(|span| span.span_id).collect(),
+    );
+
+    Ok(HttpResponse::Ok().json(spans))
+}
+
+/// Traces that started in the period whose aggregates, e.g. latency, cost or span count, match
+/// the filter expression, the newest first
+#[post("traces/query")]
+pub async fn query_traces(
+    path: web::Path<Uuid>,
+    req: web::Json<FilterQueryRequest>,
+    db: web::Data<DB>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
+    user: User,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+    let hidden_fields: &[&str] = if visibility.user_id {
+        &[]
+    } else {
+        &["user_id"]
+    };
+    let filter = req.compile_filter(FilterTarget::Traces, &HashMap::new(), hidden_fields)?;
+    let (start_time, end_time) = req.time_range()?;
+
+    let traces = analytics_store
+        .query_traces(
+            project_id,
+            &filter,
+            start_time,
+            end_time,
+            req.limit()?,
+            req.offset,
+        )
+        .await?;
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::TRACE,
+        traces.iter().map(|trace| trace.trace_id).collect(),
+    );
+
+    Ok(HttpResponse::Ok().json(traces))
 }
 
 #[get("spans/{span_id}")]
-pub async fn get_single_span(params: web::Path<(Uuid, Uuid)>, db: web::Data<DB>) -> ResponseResult {
+pub async fn get_single_span(
+    params: web::Path<(Uuid, Uuid)>,
+    db: web::Data<DB>,
+    user: User,
+) -> ResponseResult {
     let (project_id, span_id) = params.into_inner();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
+    let masking_profile = get_masking_profile_of_user(&db.pool, &user.id, &project_id).await?;
 
-    let span = db::spans::get_span(&db.pool, span_id, project_id).await?;
+    let mut span = db::spans::get_span(&db.pool, span_id, project_id, &visibility).await?;
+    if let Some(profile) = &masking_profile {
+        masking::mask_span(&mut span, profile);
+    }
     let events = db::events::get_events_for_span(&db.pool, span_id).await?;
-
-    let span_with_events = SpanWithEvents { span, events };
+    let scores = db::labels::get_span_labels(&db.pool, span_id).await?;
+    record_data_access(
+        db.into_inner(),
+        project_id,
+        user.id,
+        AccessedResourceType::SPAN,
+        vec![span_id],
+    );
+
+    let span_with_events = SpanWithEvents {
+        span,
+        events,
+        scores,
+    };
 
     Ok(HttpResponse::Ok().json(span_with_events))
 }
@@ -164,11 +641,11 @@ struct GetTraceMetricsParams {
 #[post("traces/metrics")]
 pub async fn get_traces_metrics(
     params: web::Path<Uuid>,
-    clickhouse: web::Data<clickhouse::Client>,
+    analytics_store: web::Data<Arc<dyn AnalyticsStore>>,
     req: web::Json<GetTraceMetricsParams>,
 ) -> ResponseResult {
     let project_id = params.into_inner();
-    let clickhouse = clickhouse.into_inner().as_ref().clone();
+    let analytics_store = analytics_store.as_ref().clone();
     let req = req.into_inner();
     let metric = req.metric;
     let aggregation = req.base_params.aggregation;
@@ -187,10 +664,13 @@ pub async fn get_traces_metrics(
     match defaulted_range {
         DateRange::Relative(interval) => {
             if interval.past_hours == "all" {
-                let (start_time, end_time) =
-                    get_bounds(&clickhouse, &project_id, "spans", "start_time").await?;
+                let (start_time, end_time) = analytics_store
+                    .get_bounds(&project_id, "spans", "start_time")
+                    .await?;
+                let group_by_interval = group_by_interval
+                    .coarsen_for_range((end_time - start_time).num_seconds(), MAX_CHART_POINTS);
                 return get_metrics_absolute_time(
-                    clickhouse.clone(),
+                    analytics_store.clone(),
                     metric,
                     project_id,
                     start_time,
@@ -200,12 +680,13 @@ pub async fn get_traces_metrics(
                 )
                 .await;
             } else {
-                let past_hours = interval
-                    .past_hours
-                    .parse::<i64>()
-                    .map_err(|e| anyhow::anyhow!("Failed to parse past_hours as i64: {}", e))?;
+                let past_hours = interval.past_hours.parse::<i64>().map_err(|e| {
+                    Error::invalid_request(Some(&format!("Invalid past_hours: {}", e)))
+                })?;
+                let group_by_interval =
+                    group_by_interval.coarsen_for_range(past_hours * 60 * 60, MAX_CHART_POINTS);
                 get_metrics_relative_time(
-                    clickhouse.clone(),
+                    analytics_store.clone(),
                     metric,
                     project_id,
                     past_hours,
@@ -216,8 +697,12 @@ pub async fn get_traces_metrics(
             }
         }
         DateRange::Absolute(interval) => {
+            let group_by_interval = group_by_interval.coarsen_for_range(
+                (interval.end_date - interval.start_date).num_seconds(),
+                MAX_CHART_POINTS,
+            );
             get_metrics_absolute_time(
-                clickhouse.clone(),
+                analytics_store.clone(),
                 metric,
                 project_id,
                 interval.start_date,
@@ -233,19 +718,30 @@ pub async fn get_traces_metrics(
 #[get("sessions")]
 pub async fn get_sessions(
     db: web::Data<DB>,
+    user: User,
     project_id: web::Path<Uuid>,
     params: web::Query<PaginatedGetQueryParams>,
 ) -> ResponseResult {
     let project_id = project_id.into_inner();
+    let visibility = get_field_visibility(&db.pool, &user.id, &project_id).await?;
     let date_range = &params.date_range;
     let limit = params.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
     let offset = limit * (params.page_number);
     let filters = Filter::from_url_params(params.filter.clone());
-    let sessions =
-        db::trace::get_sessions(&db.pool, project_id, limit, offset, &filters, date_range).await?;
+    let sessions = db::trace::get_sessions(
+        &db.pool,
+        project_id,
+        limit,
+        offset,
+        &filters,
+        date_range,
+        &visibility,
+    )
+    .await?;
 
     let total_count =
-        db::trace::count_sessions(&db.pool, project_id, &filters, date_range).await? as u64;
+        db::trace::count_sessions(&db.pool, project_id, &filters, date_range, &visibility).await?
+            as u64;
     let any_in_project = if total_count == 0 {
         db::trace::count_all_sessions_in_project(&db.pool, project_id).await? > 0
     } else {
@@ -260,7 +756,7 @@ pub async fn get_sessions(
 }
 
 async fn get_metrics_relative_time(
-    clickhouse: clickhouse::Client,
+    analytics_store: Arc<dyn AnalyticsStore>,
     metric: TraceMetric,
     project_id: Uuid,
     past_hours: i64,
@@ -270,88 +766,84 @@ async fn get_metrics_relative_time(
     match metric {
         TraceMetric::TraceCount => match aggregation {
             Aggregation::Average => {
-                return Err(anyhow::anyhow!(
-                    "Average grouping is not supported for traceCount metric"
-                )
-                .into());
+                return Err(Error::invalid_request(Some(
+                    "Average grouping is not supported for traceCount metric",
+                )));
             }
             Aggregation::Total => {
-                let values = ch::spans::get_total_trace_count_metrics_relative(
-                    clickhouse,
-                    group_by_interval,
-                    project_id,
-                    past_hours,
-                )
-                .await?;
-
-                Ok(HttpResponse::Ok().json(values))
+                let values = analytics_store
+                    .get_total_trace_count_metrics_relative(
+                        group_by_interval,
+                        project_id,
+                        past_hours,
+                    )
+                    .await?;
+
+                Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
             }
         },
         TraceMetric::TraceLatencySeconds => match aggregation {
             Aggregation::Total => {
-                return Err(anyhow::anyhow!(
-                    "Total grouping is not supported for traceLatency metric"
-                )
-                .into());
+                return Err(Error::invalid_request(Some(
+                    "Total grouping is not supported for traceLatency metric",
+                )));
             }
             Aggregation::Average => {
-                let values = ch::spans::get_trace_latency_seconds_metrics_relative(
-                    clickhouse,
-                    group_by_interval,
-                    project_id,
-                    past_hours,
-                    aggregation,
-                )
-                .await?;
-
-                Ok(HttpResponse::Ok().json(values))
+                let values = analytics_store
+                    .get_trace_latency_seconds_metrics_relative(
+                        group_by_interval,
+                        project_id,
+                        past_hours,
+                        aggregation,
+                    )
+                    .await?;
+
+                Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
             }
         },
         TraceMetric::TotalTokenCount => match aggregation {
             Aggregation::Total => {
-                let values = ch::spans::get_total_token_count_metrics_relative(
-                    clickhouse,
-                    group_by_interval,
-                    project_id,
-                    past_hours,
-                    aggregation,
-                )
-                .await?;
-
-                Ok(HttpResponse::Ok().json(values))
+                let values = analytics_store
+                    .get_total_token_count_metrics_relative(
+                        group_by_interval,
+                        project_id,
+                        past_hours,
+                        aggregation,
+                    )
+                    .await?;
+
+                Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
             }
             Aggregation::Average => {
-                return Err(anyhow::anyhow!(
-                    "Average grouping is not supported for totalTokenCount metric"
-                )
-                .into());
+                return Err(Error::invalid_request(Some(
+                    "Average grouping is not supported for totalTokenCount metric",
+                )));
             }
         },
         TraceMetric::CostUsd => match aggregation {
             Aggregation::Total => {
-                let values = ch::spans::get_cost_usd_metrics_relative(
-                    clickhouse,
-                    group_by_interval,
-                    project_id,
-                    past_hours,
-                    aggregation,
-                )
-                .await?;
-
-                Ok(HttpResponse::Ok().json(values))
+                let values = analytics_store
+                    .get_cost_usd_metrics_relative(
+                        group_by_interval,
+                        project_id,
+                        past_hours,
+                        aggregation,
+                    )
+                    .await?;
+
+                Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
             }
             Aggregation::Average => {
-                return Err(anyhow::anyhow!(
-                    "Average grouping is not supported for costUsd metric"
-                )
-                .into());
+                return Err(Error::invalid_request(Some(
+                    "Average grouping is not supported for costUsd metric",
+                )));
             }
         },
     }
 }
 
 async fn get_metrics_absolute_time(
-    clickhouse: clickhouse::Client,
+    analytics_store: Arc<dyn AnalyticsStore>,
     metric: TraceMetric,
     project_id: Uuid,
     start_time: DateTime<Utc>,
@@ -362,85 +854,81 @@ async fn get_metrics_absolute_time(
     match metric {
         TraceMetric::TraceCount => match aggregation {
             Aggregation::Average => {
-                return Err(anyhow::anyhow!(
-                    "Average grouping is not supported for traceCount metric"
-                )
-                .into());
+                return Err(Error::invalid_request(Some(
+                    "Average grouping is not supported for traceCount metric",
+                )));
             }
             Aggregation::Total => {
-                let values = ch::spans::get_total_trace_count_metrics_absolute(
-                    clickhouse,
-                    group_by_interval,
-                    project_id,
-                    start_time,
-                    end_time,
-                )
-                .await?;
-
-                Ok(HttpResponse::Ok().json(values))
+                let values = analytics_store
+                    .get_total_trace_count_metrics_absolute(
+                        group_by_interval,
+                        project_id,
+                        start_time,
+                        end_time,
+                    )
+                    .await?;
+
+                Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
             }
         },
         TraceMetric::TraceLatencySeconds => match aggregation {
             Aggregation::Total => {
-                return Err(anyhow::anyhow!(
-                    "Total grouping is not supported for traceLatency metric"
-                )
-                .into());
+                return Err(Error::invalid_request(Some(
+                    "Total grouping is not supported for traceLatency metric",
+                )));
             }
             Aggregation::Average => {
-                let values = ch::spans::get_trace_latency_seconds_metrics_absolute(
-                    clickhouse,
-                    group_by_interval,
-                    project_id,
-                    start_time,
-                    end_time,
-                    aggregation,
-                )
-                .await?;
-
-                Ok(HttpResponse::Ok().json(values))
+                let values = analytics_store
+                    .get_trace_latency_seconds_metrics_absolute(
+                        group_by_interval,
+                        project_id,
+                        start_time,
+                        end_time,
+                        aggregation,
+                    )
+                    .await?;
+
+                Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
             }
         },
         TraceMetric::TotalTokenCount => match aggregation {
             Aggregation::Total => {
-                let values = ch::spans::get_total_token_count_metrics_absolute(
-                    clickhouse,
-                    group_by_interval,
-                    project_id,
-                    start_time,
-                    end_time,
-                    aggregation,
-                )
-                .await?;
-
-                Ok(HttpResponse::Ok().json(values))
+                let values = analytics_store
+                    .get_total_token_count_metrics_absolute(
+                        group_by_interval,
+                        project_id,
+                        start_time,
+                        end_time,
+                        aggregation,
+                    )
+                    .await?;
+
+                Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
             }
             Aggregation::Average => {
-                return Err(anyhow::anyhow!(
-                    "Average grouping is not supported for totalTokenCount metric"
-                )
-                .into());
+                return Err(Error::invalid_request(Some(
+                    "Average grouping is not supported for totalTokenCount metric",
+                )));
             }
         },
         TraceMetric::CostUsd => match aggregation {
             Aggregation::Total => {
-                let values = ch::spans::get_cost_usd_metrics_absolute(
-                    clickhouse,
-                    group_by_interval,
-                    project_id,
-                    start_time,
-                    end_time,
-                    aggregation,
-                )
-                .await?;
-
-                Ok(HttpResponse::Ok().json(values))
+                let values = analytics_store
+                    .get_cost_usd_metrics_absolute(
+                        group_by_interval,
+                        project_id,
+                        start_time,
+                        end_time,
+                        aggregation,
+                    )
+                    .await?;
+
+                Ok(HttpResponse::Ok().json(downsample(values, MAX_CHART_POINTS)))
             }
             Aggregation::Average => {
-                return Err(anyhow::anyhow!(
-                    "Average grouping is not supported for costUsd metric"
-                )
-                .into());
+                return Err(Error::invalid_request(Some(
+                    "Average grouping is not supported for costUsd metric",
+                )));
             }
         },
     }
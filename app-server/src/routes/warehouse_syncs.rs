@@ -0,0 +1,113 @@
+use actix_web::{delete, get, put, web, HttpResponse};
+use chrono::{Duration, DurationRound, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self,
+        user::User,
+        warehouse_syncs::{NewWarehouseSync, WarehouseDestination, WarehouseSyncMode},
+        DB,
+    },
+    warehouse_sync::{self, WarehouseClient},
+};
+
+use super::{error::Error, legal_holds::ensure_no_legal_hold, ResponseResult};
+
+/// Hours before the current one that a new sync starts from
+const MAX_BACKFILL_HOURS: i64 = 30 * 24;
+
+#[get("warehouse-syncs")]
+pub async fn get_warehouse_syncs(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let project_id = path.into_inner();
+    let syncs = db::warehouse_syncs::get_warehouse_syncs(&db.pool, &project_id).await?;
+
+    Ok(HttpResponse::Ok().json(syncs))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetWarehouseSyncRequest {
+    destination: WarehouseDestination,
+    #[serde(default = "default_mode")]
+    mode: WarehouseSyncMode,
+    /// See `warehouse_sync::bigquery::BigQueryConfig` and `warehouse_sync::snowflake::SnowflakeConfig`
+    config: Value,
+    /// Service account key JSON for BigQuery, PEM private key of the user for Snowflake
+    credentials: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    /// Only used when the sync is created or its mode changes, otherwise the sync continues
+    /// where it is
+    #[serde(default)]
+    backfill_hours: i64,
+}
+
+fn default_mode() -> WarehouseSyncMode {
+    WarehouseSyncMode::AGGREGATED
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Syncs the project's data to BigQuery or Snowflake every hour, one sync per destination. The
+/// credentials are checked against the destination before they are saved.
+#[put("warehouse-syncs")]
+pub async fn set_warehouse_sync(
+    path: web::Path<Uuid>,
+    req: web::Json<SetWarehouseSyncRequest>,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let project_id = path.into_inner();
+    let req = req.into_inner();
+    if !(0..=MAX_BACKFILL_HOURS).contains(&req.backfill_hours) {
+        return Err(Error::invalid_request(Some(&format!(
+            "backfillHours must be between 0 and {MAX_BACKFILL_HOURS}"
+        ))));
+    }
+
+    let client = WarehouseClient::new(req.destination, &req.config, &req.credentials)
+        .map_err(|e| Error::invalid_request(Some(&format!("Invalid config: {e}"))))?;
+    if let Err(e) = client.check_access().await {
+        return Err(Error::invalid_request(Some(&format!(
+            "Warehouse is not accessible with the credentials: {e}"
+        ))));
+    }
+
+    let credentials =
+        warehouse_sync::encode_credentials(&project_id, req.destination, &req.credentials);
+    let current_hour = Utc::now()
+        .duration_trunc(Duration::hours(1))
+        .map_err(anyhow::Error::from)?;
+    let sync = NewWarehouseSync {
+        destination: req.destination,
+        mode: req.mode,
+        config: client.config(),
+        credentials_nonce: credentials.nonce,
+        credentials_value: credentials.value,
+        enabled: req.enabled,
+        synced_until: current_hour - Duration::hours(req.backfill_hours),
+    };
+    let info = db::warehouse_syncs::set_warehouse_sync(&db.pool, &project_id, &sync).await?;
+
+    Ok(HttpResponse::Ok().json(info))
+}
+
+#[delete("warehouse-syncs/{sync_id}")]
+pub async fn delete_warehouse_sync(
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+    db: web::Data<DB>,
+) -> ResponseResult {
+    let (project_id, sync_id) = path.into_inner();
+    ensure_no_legal_hold(&db, &project_id, &user, "warehouse_sync", Some(&sync_id)).await?;
+
+    if !db::warehouse_syncs::delete_warehouse_sync(&db.pool, &project_id, &sync_id).await? {
+        return Err(Error::not_found("Warehouse sync not found"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,19 +1,24 @@
 use std::sync::Arc;
 
-use actix_web::{get, post, web, HttpResponse};
-use serde::Deserialize;
+use actix_web::{get, post, put, web, HttpResponse};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::error::workspace_error_to_http_error;
+use super::error::{workspace_error_to_http_error, Error};
 use crate::{
     cache::Cache,
     db::{
-        self, stats,
+        self,
+        field_visibility::WorkspaceRole,
+        organizations::WorkspacePolicy,
+        stats,
         user::{get_by_email, User},
+        utils::is_valid_slug,
         workspace::{WorkspaceError, WorkspaceWithProjects},
         DB,
     },
     features::{is_feature_enabled, Feature},
+    network::ip_allowlist::WorkspaceIpAllowlist,
     projects,
     routes::ResponseResult,
     semantic_search::SemanticSearch,
@@ -34,9 +39,22 @@ async fn get_workspace(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResu
     Ok(HttpResponse::Ok().json(workspace))
 }
 
+/// Policies inherited from the organization of the workspace, if any
+#[get("{workspace_id}/policy")]
+async fn get_workspace_policy(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let workspace_id = path.into_inner();
+
+    let policy = db::organizations::get_workspace_policy(&db.pool, &workspace_id).await?;
+
+    Ok(HttpResponse::Ok().json(policy))
+}
+
 #[derive(Deserialize)]
 struct AddUserRequest {
     email: String,
+    /// Members are added with the `member` role by default
+    #[serde(default)]
+    role: Option<WorkspaceRole>,
 }
 
 #[derive(Deserialize)]
@@ -45,6 +63,10 @@ struct CreateWorkspaceRequest {
     name: String,
     #[serde(default)]
     project_name: Option<String>,
+    /// If set, the existing workspace of the user with the slug is returned instead of creating
+    /// a new one
+    #[serde(default)]
+    slug: Option<String>,
 }
 
 #[post("")]
@@ -58,11 +80,31 @@ async fn create_workspace(
     let req = req.into_inner();
     let name = req.name;
     let project_name = req.project_name;
+    let slug = req.slug;
 
     let cache = cache.into_inner();
     let semantic_search = semantic_search.into_inner().as_ref().clone();
 
-    let workspace = db::workspace::create_new_workspace(&db.pool, Uuid::new_v4(), name).await?;
+    if let Some(slug) = &slug {
+        if !is_valid_slug(slug) {
+            return Err(Error::invalid_request(Some("Invalid workspace slug")));
+        }
+        if let Some(workspace) =
+            db::workspace::get_workspace_of_user_by_slug(&db.pool, &user.id, slug).await?
+        {
+            let projects = db::projects::get_projects_of_workspace(&db.pool, &workspace.id).await?;
+            let response = WorkspaceWithProjects {
+                id: workspace.id,
+                name: workspace.name,
+                tier_name: workspace.tier_name,
+                projects,
+            };
+            return Ok(HttpResponse::Ok().json(response));
+        }
+    }
+
+    let workspace =
+        db::workspace::create_new_workspace(&db.pool, Uuid::new_v4(), name, slug).await?;
     log::info!(
         "Created new workspace: id {}, name {}, tier_name {}, is_free_tier {}",
         workspace.id,
@@ -81,6 +123,7 @@ async fn create_workspace(
             &user.id,
             &project_name,
             workspace.id,
+            None,
         )
         .await?;
 
@@ -108,7 +151,9 @@ async fn add_user_to_workspace(
     cache: web::Data<Cache>,
 ) -> ResponseResult {
     let workspace_id = path.into_inner();
-    let email = req.into_inner().email;
+    let req = req.into_inner();
+    let email = req.email;
+    let role = req.role.unwrap_or(WorkspaceRole::Member);
 
     if is_feature_enabled(Feature::UsageLimit) {
         let limits = stats::get_workspace_stats(&db.pool, &workspace_id).await?;
@@ -154,7 +199,7 @@ async fn add_user_to_workspace(
         return Err(workspace_error_to_http_error(WorkspaceError::NotAllowed));
     }
 
-    db::workspace::add_user_to_workspace_by_email(&db.pool, &email, &workspace_id).await?;
+    db::workspace::add_user_to_workspace_by_email(&db.pool, &email, &workspace_id, role).await?;
 
     // after user is added to workspace, we need to invalidate the cache
     let remove_res = cache.remove::<User>(&user.api_key.unwrap()).await;
@@ -165,3 +210,126 @@ async fn add_user_to_workspace(
 
     Ok(HttpResponse::Ok().finish())
 }
+
+#[get("{workspace_id}/ip-allowlist")]
+async fn get_ip_allowlist(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let workspace_id = path.into_inner();
+
+    let ip_allowlist = db::workspace::get_ip_allowlist(&db.pool, &workspace_id).await?;
+
+    Ok(HttpResponse::Ok().json(ip_allowlist))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateIpAllowlistRequest {
+    /// Addresses or networks in CIDR notation. Empty list allows all addresses.
+    ip_allowlist: Vec<String>,
+}
+
+/// Restrict project API key access for all projects of the workspace to the given networks
+#[put("{workspace_id}/ip-allowlist")]
+async fn update_ip_allowlist(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    cache: web::Data<Cache>,
+    req: web::Json<UpdateIpAllowlistRequest>,
+) -> ResponseResult {
+    let workspace_id = path.into_inner();
+    let ip_allowlist = req.into_inner().ip_allowlist;
+
+    let owned_workspaces = db::workspace::get_owned_workspaces(&db.pool, &user.id).await?;
+    if !owned_workspaces.iter().any(|w| w.id == workspace_id) {
+        return Err(Error::Forbidden);
+    }
+
+    let allowlist = match WorkspaceIpAllowlist::parse(&ip_allowlist) {
+        Ok(allowlist) => allowlist,
+        Err(e) => return Err(Error::invalid_request(Some(&e.to_string()))),
+    };
+
+    db::workspace::update_ip_allowlist(&db.pool, &workspace_id, &ip_allowlist).await?;
+    let _ = cache
+        .insert::<WorkspaceIpAllowlist>(workspace_id.to_string(), &allowlist)
+        .await;
+
+    Ok(HttpResponse::Ok().json(ip_allowlist))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct UpdateRetentionRequest {
+    /// None to keep data for the inherited retention
+    pub retention_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct RetentionSettings {
+    pub retention_days: Option<i64>,
+    /// Floor set by the organization of the workspace
+    pub min_retention_days: Option<i64>,
+}
+
+/// Retention set by a workspace or project must be positive and can't be lower than the floor of
+/// the organization. Inherited retention is raised to the floor where it applies.
+pub(super) fn check_retention_floor(
+    policy: &WorkspacePolicy,
+    retention_days: Option<i64>,
+) -> Result<(), Error> {
+    let Some(retention_days) = retention_days else {
+        return Ok(());
+    };
+    if retention_days <= 0 {
+        return Err(Error::invalid_request(Some(
+            "retentionDays must be positive",
+        )));
+    }
+    if let Some(min_retention_days) = policy.min_retention_days {
+        if retention_days < min_retention_days {
+            return Err(Error::invalid_request(Some(&format!(
+                "The organization requires a retention of at least {} days",
+                min_retention_days
+            ))));
+        }
+    }
+    Ok(())
+}
+
+#[get("{workspace_id}/retention")]
+async fn get_retention(path: web::Path<Uuid>, db: web::Data<DB>) -> ResponseResult {
+    let workspace_id = path.into_inner();
+    let retention_days = db::workspace::get_retention_days(&db.pool, &workspace_id).await?;
+    let policy = db::organizations::get_workspace_policy(&db.pool, &workspace_id).await?;
+
+    Ok(HttpResponse::Ok().json(RetentionSettings {
+        retention_days,
+        min_retention_days: policy.min_retention_days,
+    }))
+}
+
+#[put("{workspace_id}/retention")]
+async fn update_retention(
+    user: User,
+    path: web::Path<Uuid>,
+    db: web::Data<DB>,
+    req: web::Json<UpdateRetentionRequest>,
+) -> ResponseResult {
+    let workspace_id = path.into_inner();
+    let retention_days = req.into_inner().retention_days;
+
+    let owned_workspaces = db::workspace::get_owned_workspaces(&db.pool, &user.id).await?;
+    if !owned_workspaces.iter().any(|w| w.id == workspace_id) {
+        return Err(Error::Forbidden);
+    }
+    let policy = db::organizations::get_workspace_policy(&db.pool, &workspace_id).await?;
+    check_retention_floor(&policy, retention_days)?;
+
+    db::workspace::update_retention_days(&db.pool, &workspace_id, retention_days).await?;
+
+    Ok(HttpResponse::Ok().json(RetentionSettings {
+        retention_days,
+        min_retention_days: policy.min_retention_days,
+    }))
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+
+use super::{json_to_secrets, SecretsProvider};
+
+/// Reads a JSON secret from AWS Secrets Manager, identified by `AWS_SECRETS_MANAGER_SECRET_ID`
+pub struct AwsSecretsManager {
+    client: aws_sdk_secretsmanager::Client,
+    secret_id: String,
+}
+
+impl AwsSecretsManager {
+    pub async fn from_env() -> Result<Self> {
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .region(aws_config::Region::new(
+                std::env::var("AWS_REGION").unwrap_or("us-east-1".to_string()),
+            ))
+            .load()
+            .await;
+        Ok(Self {
+            client: aws_sdk_secretsmanager::Client::new(&config),
+            secret_id: std::env::var("AWS_SECRETS_MANAGER_SECRET_ID")
+                .map_err(|_| anyhow::anyhow!("AWS_SECRETS_MANAGER_SECRET_ID must be set"))?,
+        })
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for AwsSecretsManager {
+    async fn fetch(&self) -> Result<HashMap<String, String>> {
+        let res = self
+            .client
+            .get_secret_value()
+            .secret_id(&self.secret_id)
+            .send()
+            .await?;
+        let secret_string = res.secret_string().ok_or(anyhow::anyhow!(
+            "Secret {} has no string value",
+            self.secret_id
+        ))?;
+        json_to_secrets(serde_json::from_str(secret_string)?)
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::Deserialize;
+
+use super::{json_to_secrets, SecretsProvider};
+
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Reads the latest version of a JSON secret from GCP Secret Manager, identified by
+/// `GCP_SECRET_NAME=projects/<project>/secrets/<secret>`. Authenticates with the service account
+/// of the instance, or with `GCP_ACCESS_TOKEN` if set.
+pub struct GcpSecretManager {
+    client: reqwest::Client,
+    secret_name: String,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct SecretPayload {
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct AccessSecretVersionResponse {
+    payload: SecretPayload,
+}
+
+impl GcpSecretManager {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::new(),
+            secret_name: std::env::var("GCP_SECRET_NAME")
+                .map_err(|_| anyhow::anyhow!("GCP_SECRET_NAME must be set"))?,
+        })
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        if let Ok(token) = std::env::var("GCP_ACCESS_TOKEN") {
+            return Ok(token);
+        }
+        let token: AccessToken = self
+            .client
+            .get(METADATA_TOKEN_URL)
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(token.access_token)
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for GcpSecretManager {
+    async fn fetch(&self) -> Result<HashMap<String, String>> {
+        let res: AccessSecretVersionResponse = self
+            .client
+            .get(format!(
+                "https://secretmanager.googleapis.com/v1/{}/versions/latest:access",
+                self.secret_name
+            ))
+            .bearer_auth(self.access_token().await?)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let data = BASE64_STANDARD.decode(res.payload.data)?;
+        json_to_secrets(serde_json::from_slice(&data)?)
+    }
+}
@@ -0,0 +1,153 @@
+//! Secrets, such as database credentials and the data-encryption key, can be loaded from
+//! a secrets manager instead of environment variables.
+//!
+//! The provider is selected with `SECRETS_PROVIDER` (`env`, `vault`, `aws` or `gcp`). Each provider
+//! reads a single secret that holds a JSON object keyed by environment variable name, e.g.
+//! `{"DATABASE_URL": "...", "AEAD_SECRET_KEY": "..."}`. Secrets missing from the provider fall back
+//! to environment variables. Secrets are re-fetched periodically, so that rotated values are
+//! picked up without a restart.
+
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+
+use crate::{db::DB, tls::postgres_connect_options};
+
+pub mod aws;
+pub mod gcp;
+pub mod vault;
+
+const DEFAULT_ROTATION_INTERVAL_SECS: u64 = 300;
+
+lazy_static! {
+    static ref SECRETS: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
+
+#[async_trait]
+pub trait SecretsProvider: Sync + Send {
+    /// Fetch the current values of all secrets
+    async fn fetch(&self) -> Result<HashMap<String, String>>;
+}
+
+/// Get a secret by its environment variable name
+pub fn get(name: &str) -> Option<String> {
+    SECRETS
+        .read()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .or_else(|| env::var(name).ok())
+}
+
+pub async fn create_provider() -> Result<Option<Arc<dyn SecretsProvider>>> {
+    let provider: Arc<dyn SecretsProvider> = match env::var("SECRETS_PROVIDER")
+        .unwrap_or("env".to_string())
+        .as_str()
+    {
+        "env" => return Ok(None),
+        "vault" => Arc::new(vault::VaultSecrets::from_env()?),
+        "aws" => Arc::new(aws::AwsSecretsManager::from_env().await?),
+        "gcp" => Arc::new(gcp::GcpSecretManager::from_env()?),
+        other => return Err(anyhow::anyhow!("Unknown SECRETS_PROVIDER: {}", other)),
+    };
+    Ok(Some(provider))
+}
+
+/// Replace the cached secrets, returning names of secrets whose values changed
+async fn refresh(provider: &dyn SecretsProvider) -> Result<Vec<String>> {
+    let fetched = provider.fetch().await?;
+    let mut secrets = SECRETS.write().unwrap();
+    let changed = fetched
+        .iter()
+        .filter(|(name, value)| secrets.get(*name) != Some(value))
+        .map(|(name, _)| name.clone())
+        .collect();
+    *secrets = fetched;
+    Ok(changed)
+}
+
+/// Load secrets once, before any of them are read at startup
+pub async fn init(provider: &dyn SecretsProvider) -> Result<()> {
+    let loaded = refresh(provider).await?;
+    log::info!("Loaded {} secrets from secrets provider", loaded.len());
+    Ok(())
+}
+
+/// Periodically re-fetch secrets. New connections to Postgres use the rotated `DATABASE_URL`,
+/// while existing connections are closed by the pool as they expire.
+pub async fn rotate_periodically(provider: Arc<dyn SecretsProvider>, db: Arc<DB>) {
+    let interval_secs = env::var("SECRETS_ROTATION_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_ROTATION_INTERVAL_SECS);
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    // the first tick completes immediately, and secrets have just been loaded
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let changed = match refresh(provider.as_ref()).await {
+            Ok(changed) => changed,
+            Err(e) => {
+                log::error!("Failed to refresh secrets: {:?}", e);
+                continue;
+            }
+        };
+        if changed.is_empty() {
+            continue;
+        }
+        log::info!("Secrets rotated: {}", changed.join(", "));
+
+        if changed.iter().any(|name| name == "DATABASE_URL") {
+            let db_url = get("DATABASE_URL").unwrap_or_default();
+            match postgres_connect_options(&db_url) {
+                Ok(options) => db.pool.set_connect_options(options),
+                Err(e) => log::error!("Invalid rotated DATABASE_URL: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Flatten a JSON object of secrets, so that non-string values can also be used
+fn json_to_secrets(value: serde_json::Value) -> Result<HashMap<String, String>> {
+    let serde_json::Value::Object(map) = value else {
+        return Err(anyhow::anyhow!("Secret must be a JSON object"));
+    };
+    Ok(map
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            (name, value)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_to_secrets() {
+        let secrets = json_to_secrets(serde_json::json!({
+            "DATABASE_URL": "postgres://user:pass@db/laminar",
+            "DATABASE_MAX_CONNECTIONS": 20,
+        }))
+        .unwrap();
+        assert_eq!(
+            secrets.get("DATABASE_URL").unwrap(),
+            "postgres://user:pass@db/laminar"
+        );
+        assert_eq!(secrets.get("DATABASE_MAX_CONNECTIONS").unwrap(), "20");
+        assert!(json_to_secrets(serde_json::json!("not an object")).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{json_to_secrets, SecretsProvider};
+
+/// Reads a HashiCorp Vault KV v2 secret, e.g. `VAULT_SECRET_PATH=secret/data/laminar`
+pub struct VaultSecrets {
+    client: reqwest::Client,
+    address: String,
+    token: String,
+    secret_path: String,
+}
+
+impl VaultSecrets {
+    pub fn from_env() -> Result<Self> {
+        let token = match std::env::var("VAULT_TOKEN") {
+            Ok(token) => token,
+            // e.g. written by the Vault agent sidecar, which also renews it
+            Err(_) => std::fs::read_to_string(
+                std::env::var("VAULT_TOKEN_FILE")
+                    .map_err(|_| anyhow::anyhow!("VAULT_TOKEN or VAULT_TOKEN_FILE must be set"))?,
+            )?
+            .trim()
+            .to_string(),
+        };
+        Ok(Self {
+            client: reqwest::Client::new(),
+            address: std::env::var("VAULT_ADDR")
+                .map_err(|_| anyhow::anyhow!("VAULT_ADDR must be set"))?
+                .trim_end_matches('/')
+                .to_string(),
+            token,
+            secret_path: std::env::var("VAULT_SECRET_PATH")
+                .map_err(|_| anyhow::anyhow!("VAULT_SECRET_PATH must be set"))?
+                .trim_matches('/')
+                .to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for VaultSecrets {
+    async fn fetch(&self) -> Result<HashMap<String, String>> {
+        let res = self
+            .client
+            .get(format!("{}/v1/{}", self.address, self.secret_path))
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut body: serde_json::Value = res.json().await?;
+        let data = body
+            .pointer_mut("/data/data")
+            .map(|data| data.take())
+            .ok_or(anyhow::anyhow!(
+                "Unexpected Vault response, expected a KV v2 secret"
+            ))?;
+        json_to_secrets(data)
+    }
+}
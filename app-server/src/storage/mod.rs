@@ -12,9 +12,15 @@ pub trait Storage: Sync + Send {
     async fn retrieve(&self, key: &str) -> Result<Vec<u8>>;
 }
 
+/// Prefix of all of a project's objects
+pub fn project_prefix(project_id: &Uuid) -> String {
+    format!("project/{project_id}/")
+}
+
 pub fn create_key(project_id: &Uuid, file_extension: &Option<String>) -> String {
     format!(
-        "project/{project_id}/{}{}",
+        "{}{}{}",
+        project_prefix(project_id),
         Uuid::new_v4(),
         file_extension
             .as_ref()
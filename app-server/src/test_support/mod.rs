@@ -0,0 +1,183 @@
+//! Test harness for integration tests and SDK authors, compiled with the `test-support` feature.
+//!
+//! [`TestServer`] runs the public ingestion API, i.e. the real `/v1/traces`, `/v1/evaluations`
+//! and `/v1/metrics` handlers, in-process. Analytics are kept in an [`InMemoryAnalyticsStore`]
+//! that tests can inspect. Postgres is the embedded one with a temporary data directory, there is
+//! no in-memory replacement for it, as route handlers use Postgres queries directly.
+//!
+//! SDK test suites can run the harness as a process with `TEST_SERVER=true`, see `main.rs`.
+//!
+//! The server always runs in the `LITE` environment, without RabbitMQ and ClickHouse, whatever
+//! `ENVIRONMENT` is set to. Other settings are passed in [`TestServerConfig`].
+
+use std::{net::SocketAddr, sync::Arc};
+
+use actix_web::{dev::ServerHandle, web, App, HttpServer};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use anyhow::Result;
+use lapin::Connection;
+use postgresql_embedded::PostgreSQL;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    analytics::{in_memory::InMemoryAnalyticsStore, AnalyticsStore},
+    api, auth, create_cache,
+    db::DB,
+    embedded,
+    evaluations::progress::EvaluationProgressHub,
+    features, ids,
+    names::NameGenerator,
+    traces::archive::PayloadArchive,
+};
+
+/// Api key of the project created by the harness
+pub const TEST_PROJECT_API_KEY: &str = "lmnr-test-project-api-key";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestServerInfo {
+    pub url: String,
+    pub project_id: Uuid,
+    pub project_api_key: String,
+}
+
+#[derive(Default)]
+pub struct TestServerConfig {
+    /// Port to listen on, a free one if 0
+    pub port: u16,
+    /// Generate sequential ids, see `ids`. Ids are generated process-wide, so this also applies
+    /// to other servers in the process.
+    pub deterministic_ids: bool,
+}
+
+pub struct TestServer {
+    pub info: TestServerInfo,
+    pub db: Arc<DB>,
+    pub analytics_store: Arc<InMemoryAnalyticsStore>,
+    handle: ServerHandle,
+    // Postgres stops when the server is dropped
+    _postgres: PostgreSQL,
+}
+
+impl TestServer {
+    pub async fn start(config: TestServerConfig) -> Result<Self> {
+        // Without RabbitMQ and ClickHouse, spans are written straight to Postgres
+        features::set_environment("LITE");
+        ids::set_deterministic(config.deterministic_ids);
+
+        let (postgres, db_url) = embedded::start_postgres(None).await?;
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(5)
+            .connect(&db_url)
+            .await?;
+        embedded::migrate(&pool).await?;
+        embedded::seed(&pool).await?;
+        let project_id = embedded::create_local_project(&pool, TEST_PROJECT_API_KEY).await?;
+
+        let db = Arc::new(DB::new(pool));
+        let cache = Arc::new(create_cache());
+        let analytics_store = Arc::new(InMemoryAnalyticsStore::default());
+        let name_generator = Arc::new(NameGenerator::new());
+        let progress_hub = Arc::new(EvaluationProgressHub::new());
+
+        let db_for_http = db.clone();
+        let analytics_store_for_http: Arc<dyn AnalyticsStore> = analytics_store.clone();
+        let server = HttpServer::new(move || {
+            let project_auth = HttpAuthentication::bearer(auth::project_validator);
+            App::new()
+                .app_data(web::Data::from(cache.clone()))
+                .app_data(web::Data::from(db_for_http.clone()))
+                .app_data(web::Data::new(analytics_store_for_http.clone()))
+                .app_data(web::Data::new(name_generator.clone()))
+                .app_data(web::Data::new(progress_hub.clone()))
+                .app_data(web::Data::new(None::<Arc<Connection>>))
+                .app_data(web::Data::new(None::<Arc<PayloadArchive>>))
+                .service(
+                    web::scope("/v1")
+                        .wrap(project_auth)
+                        .service(api::v1::traces::process_traces)
+                        .service(api::v1::evaluations::create_evaluation)
+                        .service(api::v1::metrics::process_metrics),
+                )
+                .service(
+                    web::scope("/v2")
+                        .wrap(HttpAuthentication::bearer(auth::project_validator))
+                        .service(api::v1::traces::process_traces)
+                        .service(api::v2::evaluations::create_evaluation)
+                        .service(api::v2::evaluations::add_evaluation_datapoints)
+                        .service(api::v2::evaluations::stream_evaluation_progress)
+                        .service(api::v1::metrics::process_metrics),
+                )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", config.port))?;
+        let addr: SocketAddr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        tokio::spawn(server);
+
+        Ok(Self {
+            info: TestServerInfo {
+                url: format!("http://{}", addr),
+                project_id,
+                project_api_key: TEST_PROJECT_API_KEY.to_string(),
+            },
+            db,
+            analytics_store,
+            handle,
+            _postgres: postgres,
+        })
+    }
+
+    pub async fn stop(self) {
+        self.handle.stop(true).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    // Embedded Postgres downloads its binaries on first use
+    #[tokio::test]
+    #[ignore = "needs network, run with --ignored"]
+    async fn test_create_evaluation() {
+        let server = TestServer::start(TestServerConfig {
+            deterministic_ids: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let res = reqwest::Client::new()
+            .post(format!("{}/v1/evaluations", server.info.url))
+            .bearer_auth(&server.info.project_api_key)
+            .json(&json!({
+                "name": "harness",
+                "points": [{
+                    "data": {"question": "2 + 2"},
+                    "target": {"answer": "4"},
+                    "executorOutput": "4",
+                    "scores": {"accuracy": 1.0},
+                }],
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+
+        let scores = server.analytics_store.evaluation_scores.lock().unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].project_id, server.info.project_id);
+        assert_eq!(scores[0].name, "accuracy");
+        assert_eq!(scores[0].value, 1.0);
+        // Sequential ids have no version bits, unlike v4 ones
+        assert_eq!(scores[0].result_id.get_version(), None);
+        drop(scores);
+
+        server.stop().await;
+    }
+}
@@ -0,0 +1,139 @@
+//! TLS and mutual TLS configuration for database connections.
+//!
+//! Postgres is configured with `DATABASE_SSL_MODE`, `DATABASE_SSL_ROOT_CERT`,
+//! `DATABASE_SSL_CLIENT_CERT` and `DATABASE_SSL_CLIENT_KEY`, on top of any `sslmode` set in
+//! `DATABASE_URL`. ClickHouse is configured with `CLICKHOUSE_TLS_CA_CERT`,
+//! `CLICKHOUSE_TLS_CLIENT_CERT`, `CLICKHOUSE_TLS_CLIENT_KEY` and `CLICKHOUSE_TLS_SERVER_NAME`,
+//! the latter overriding the name used for SNI and certificate verification.
+//! Object storage clients use the platform trust store, which honors `SSL_CERT_FILE`.
+
+use std::{env, fs::File, io::BufReader, str::FromStr};
+
+use anyhow::{Context, Result};
+use hyper_rustls::{FixedServerNameResolver, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client as HyperClient},
+    rt::TokioExecutor,
+};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+    ClientConfig, RootCertStore,
+};
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+
+pub fn postgres_connect_options(db_url: &str) -> Result<PgConnectOptions> {
+    let mut options = PgConnectOptions::from_str(db_url)?;
+
+    if let Ok(ssl_mode) = env::var("DATABASE_SSL_MODE") {
+        options = options.ssl_mode(PgSslMode::from_str(&ssl_mode)?);
+    }
+    if let Ok(root_cert) = env::var("DATABASE_SSL_ROOT_CERT") {
+        options = options.ssl_root_cert(root_cert);
+    }
+    match (
+        env::var("DATABASE_SSL_CLIENT_CERT"),
+        env::var("DATABASE_SSL_CLIENT_KEY"),
+    ) {
+        (Ok(client_cert), Ok(client_key)) => {
+            options = options
+                .ssl_client_cert(client_cert)
+                .ssl_client_key(client_key);
+        }
+        (Err(_), Err(_)) => {}
+        _ => {
+            return Err(anyhow::anyhow!(
+                "DATABASE_SSL_CLIENT_CERT and DATABASE_SSL_CLIENT_KEY must be set together"
+            ))
+        }
+    }
+
+    Ok(options)
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path).context(format!("Failed to open {path}"))?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!("No certificates found in {}", path));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path).context(format!("Failed to open {path}"))?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or(anyhow::anyhow!("No private key found in {}", path))
+}
+
+/// Custom CA bundle if given, otherwise the platform trust store
+fn root_cert_store(ca_cert_path: Option<&str>) -> Result<RootCertStore> {
+    let mut root_store = RootCertStore::empty();
+    match ca_cert_path {
+        Some(path) => {
+            for cert in load_certs(path)? {
+                root_store.add(cert)?;
+            }
+        }
+        None => {
+            let native_certs = rustls_native_certs::load_native_certs();
+            for error in native_certs.errors {
+                log::warn!("Failed to load a native certificate: {}", error);
+            }
+            root_store.add_parsable_certificates(native_certs.certs);
+        }
+    }
+    Ok(root_store)
+}
+
+fn is_clickhouse_tls_configured() -> bool {
+    [
+        "CLICKHOUSE_TLS_CA_CERT",
+        "CLICKHOUSE_TLS_CLIENT_CERT",
+        "CLICKHOUSE_TLS_CLIENT_KEY",
+        "CLICKHOUSE_TLS_SERVER_NAME",
+    ]
+    .iter()
+    .any(|var| env::var(var).is_ok())
+}
+
+/// Base ClickHouse client. With no TLS variables set, this is the default client, which
+/// still uses TLS with the platform trust store for `https://` URLs.
+pub fn clickhouse_client() -> Result<clickhouse::Client> {
+    if !is_clickhouse_tls_configured() {
+        return Ok(clickhouse::Client::default());
+    }
+
+    let root_store = root_cert_store(env::var("CLICKHOUSE_TLS_CA_CERT").ok().as_deref())?;
+    let config_builder = ClientConfig::builder().with_root_certificates(root_store);
+    let tls_config = match (
+        env::var("CLICKHOUSE_TLS_CLIENT_CERT"),
+        env::var("CLICKHOUSE_TLS_CLIENT_KEY"),
+    ) {
+        (Ok(client_cert), Ok(client_key)) => config_builder
+            .with_client_auth_cert(load_certs(&client_cert)?, load_private_key(&client_key)?)?,
+        (Err(_), Err(_)) => config_builder.with_no_client_auth(),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "CLICKHOUSE_TLS_CLIENT_CERT and CLICKHOUSE_TLS_CLIENT_KEY must be set together"
+            ))
+        }
+    };
+
+    let mut http_connector = HttpConnector::new();
+    http_connector.enforce_http(false);
+
+    let mut connector_builder = HttpsConnectorBuilder::new()
+        .with_tls_config(tls_config)
+        .https_or_http();
+    if let Ok(server_name) = env::var("CLICKHOUSE_TLS_SERVER_NAME") {
+        let server_name = ServerName::try_from(server_name)?;
+        connector_builder =
+            connector_builder.with_server_name_resolver(FixedServerNameResolver::new(server_name));
+    }
+    let connector = connector_builder
+        .enable_http1()
+        .wrap_connector(http_connector);
+
+    let http_client = HyperClient::builder(TokioExecutor::new()).build(connector);
+    Ok(clickhouse::Client::with_http_client(http_client))
+}
@@ -0,0 +1,225 @@
+//! Conversation graph of the agents cooperating in a run.
+//!
+//! Spans name the agent that recorded them, and spans without one belong to the agent of their
+//! parent, so an agent's tool calls and LLM calls are attributed to it without being tagged.
+//! A span with a recipient is a message from its agent to the recipient, the messages between
+//! two agents form an edge of the graph.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::spans::Span;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentNode {
+    pub id: String,
+    pub name: Option<String>,
+    /// Spans recorded by the agent, zero for agents that were only sent messages
+    pub span_count: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMessage {
+    pub span_id: Uuid,
+    pub name: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentEdge {
+    pub from: String,
+    pub to: String,
+    /// Messages from `from` to `to` in time order
+    pub messages: Vec<AgentMessage>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentGraph {
+    /// Agents in the order they first appear in the run
+    pub agents: Vec<AgentNode>,
+    /// Edges in the order of their first message
+    pub edges: Vec<AgentEdge>,
+}
+
+/// Agent of the span, inherited from the closest ancestor that names one
+fn resolve_agent(
+    span_id: Uuid,
+    parents: &HashMap<Uuid, Option<Uuid>>,
+    own_agents: &HashMap<Uuid, String>,
+    resolved: &mut HashMap<Uuid, Option<String>>,
+) -> Option<String> {
+    let mut chain = Vec::new();
+    let mut current = Some(span_id);
+    let agent = loop {
+        let Some(id) = current else {
+            break None;
+        };
+        if let Some(agent) = resolved.get(&id) {
+            break agent.clone();
+        }
+        if let Some(agent) = own_agents.get(&id) {
+            break Some(agent.clone());
+        }
+        // Guards against cycles in malformed traces
+        if chain.contains(&id) {
+            break None;
+        }
+        chain.push(id);
+        current = parents.get(&id).copied().flatten();
+    };
+    for id in chain {
+        resolved.insert(id, agent.clone());
+    }
+    agent
+}
+
+pub fn build_agent_graph(mut spans: Vec<Span>) -> AgentGraph {
+    spans.sort_by_key(|span| span.start_time);
+
+    let parents = spans
+        .iter()
+        .map(|span| (span.span_id, span.parent_span_id))
+        .collect::<HashMap<_, _>>();
+    let mut own_agents = HashMap::new();
+    let mut names = HashMap::<String, String>::new();
+    let mut recipients = HashMap::new();
+    for span in &spans {
+        let attributes = span.get_attributes();
+        if let Some(agent_id) = attributes.agent_id() {
+            if let Some(name) = attributes.agent_name() {
+                names.entry(agent_id.clone()).or_insert(name);
+            }
+            own_agents.insert(span.span_id, agent_id);
+        }
+        if let Some(recipient) = attributes.agent_message_to() {
+            recipients.insert(span.span_id, recipient);
+        }
+    }
+    if own_agents.is_empty() {
+        return AgentGraph::default();
+    }
+
+    let mut graph = AgentGraph::default();
+    let mut agent_indexes = HashMap::<String, usize>::new();
+    let mut edge_indexes = HashMap::<(String, String), usize>::new();
+    let mut resolved = HashMap::new();
+    let mut node_index = |agents: &mut Vec<AgentNode>, id: &String| {
+        *agent_indexes.entry(id.clone()).or_insert_with(|| {
+            agents.push(AgentNode {
+                id: id.clone(),
+                name: names.get(id).cloned(),
+                span_count: 0,
+                start_time: None,
+                end_time: None,
+            });
+            agents.len() - 1
+        })
+    };
+
+    for span in &spans {
+        let agent = resolve_agent(span.span_id, &parents, &own_agents, &mut resolved);
+        if let Some(agent) = &agent {
+            let index = node_index(&mut graph.agents, agent);
+            let node = &mut graph.agents[index];
+            node.span_count += 1;
+            node.start_time = Some(
+                node.start_time
+                    .map_or(span.start_time, |t| t.min(span.start_time)),
+            );
+            node.end_time = Some(
+                node.end_time
+                    .map_or(span.end_time, |t| t.max(span.end_time)),
+            );
+        }
+
+        let (Some(from), Some(to)) = (agent, recipients.get(&span.span_id)) else {
+            continue;
+        };
+        node_index(&mut graph.agents, to);
+        let edge_index = *edge_indexes
+            .entry((from.clone(), to.clone()))
+            .or_insert_with(|| {
+                graph.edges.push(AgentEdge {
+                    from,
+                    to: to.clone(),
+                    messages: Vec::new(),
+                });
+                graph.edges.len() - 1
+            });
+        graph.edges[edge_index].messages.push(AgentMessage {
+            span_id: span.span_id,
+            name: span.name.clone(),
+            timestamp: span.start_time,
+        });
+    }
+
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn span(parent: Option<&Span>, seconds: i64, attributes: Value) -> Span {
+        let start_time = DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(seconds);
+        Span {
+            span_id: Uuid::new_v4(),
+            parent_span_id: parent.map(|parent| parent.span_id),
+            name: "span".to_string(),
+            attributes,
+            start_time,
+            end_time: start_time + Duration::seconds(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_agent_graph() {
+        let root = span(None, 0, json!({}));
+        let planner = span(
+            Some(&root),
+            1,
+            json!({"lmnr.agent.id": "planner", "lmnr.agent.name": "Planner"}),
+        );
+        let delegate = span(
+            Some(&planner),
+            2,
+            json!({"lmnr.agent.message.to": "browser"}),
+        );
+        let browser = span(Some(&root), 3, json!({"gen_ai.agent.name": "browser"}));
+        let reply = span(
+            Some(&browser),
+            4,
+            json!({"lmnr.agent.message.to": "planner"}),
+        );
+        let delegate_id = delegate.span_id;
+
+        let graph = build_agent_graph(vec![reply, browser, delegate, planner, root]);
+
+        let agents = graph
+            .agents
+            .iter()
+            .map(|agent| (agent.id.as_str(), agent.span_count))
+            .collect::<Vec<_>>();
+        assert_eq!(agents, vec![("planner", 2), ("browser", 2)]);
+        assert_eq!(graph.agents[0].name.as_deref(), Some("Planner"));
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!(
+            (graph.edges[0].from.as_str(), graph.edges[0].to.as_str()),
+            ("planner", "browser")
+        );
+        assert_eq!(graph.edges[0].messages[0].span_id, delegate_id);
+    }
+}
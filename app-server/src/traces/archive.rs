@@ -0,0 +1,240 @@
+//! This module archives raw ingestion payloads to object storage and replays
+//! archived payloads through the current ingestion pipeline, so that data can be
+//! re-processed after a processing bug without asking customers to resend it.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use aws_sdk_s3::Client;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use lapin::Connection;
+use prost::Message;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    ch::utils::chrono_to_nanoseconds,
+    db::DB,
+    logging,
+    opentelemetry::opentelemetry::proto::collector::trace::v1::ExportTraceServiceRequest,
+};
+
+use super::producer::push_spans_to_queue;
+
+const ARCHIVE_PREFIX: &str = "ingestion-archive";
+
+pub struct PayloadArchive {
+    client: Client,
+    bucket: String,
+}
+
+impl PayloadArchive {
+    pub fn new(client: Client, bucket: String) -> Self {
+        Self { client, bucket }
+    }
+
+    /// Store the raw protobuf-encoded `ExportTraceServiceRequest` as received from the client
+    pub async fn archive(&self, project_id: Uuid, payload: Vec<u8>) -> Result<()> {
+        let key = archive_key(&project_id, Utc::now());
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(payload.into())
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let resp = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await?;
+
+            keys.extend(
+                resp.contents()
+                    .iter()
+                    .filter_map(|object| object.key().map(String::from)),
+            );
+
+            match resp.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_string()),
+                None => break,
+            }
+        }
+        Ok(keys)
+    }
+
+    async fn retrieve(&self, key: &str) -> Result<Vec<u8>> {
+        let resp = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(resp.body.collect().await?.to_vec())
+    }
+}
+
+/// Archive the payload in the background, so that ingestion latency is not affected
+pub fn archive_in_background(
+    archive: Option<Arc<PayloadArchive>>,
+    project_id: Uuid,
+    payload: Vec<u8>,
+) {
+    let Some(archive) = archive else {
+        return;
+    };
+    logging::spawn(async move {
+        if let Err(e) = archive.archive(project_id, payload).await {
+            log::error!(
+                "Failed to archive ingestion payload. project_id [{}]: {:?}",
+                project_id,
+                e
+            );
+        }
+    });
+}
+
+/// Payloads are partitioned by hour, so that a time range can be replayed by listing
+/// only the relevant prefixes.
+fn hour_prefix(time: DateTime<Utc>) -> String {
+    format!("{ARCHIVE_PREFIX}/{}/", time.format("%Y-%m-%d/%H"))
+}
+
+fn archive_key(project_id: &Uuid, time: DateTime<Utc>) -> String {
+    format!(
+        "{}{project_id}/{}-{}.pb",
+        hour_prefix(time),
+        chrono_to_nanoseconds(time),
+        Uuid::new_v4()
+    )
+}
+
+/// Returns project id and archival time in nanoseconds
+fn parse_archive_key(key: &str) -> Option<(Uuid, i64)> {
+    let mut parts = key.rsplit('/');
+    let file_name = parts.next()?;
+    let project_id = Uuid::parse_str(parts.next()?).ok()?;
+    let timestamp = file_name.split('-').next()?.parse::<i64>().ok()?;
+    Some((project_id, timestamp))
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayStats {
+    pub replayed_payloads: usize,
+    pub failed_payloads: usize,
+}
+
+/// Re-process all payloads archived within `[start_time, end_time]`, optionally
+/// limited to a single project.
+pub async fn replay(
+    archive: Arc<PayloadArchive>,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    project_id: Option<Uuid>,
+    rabbitmq_connection: Option<Arc<Connection>>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+) -> Result<ReplayStats> {
+    let start_nanos = chrono_to_nanoseconds(start_time);
+    let end_nanos = chrono_to_nanoseconds(end_time);
+    let mut stats = ReplayStats::default();
+
+    let mut hour = start_time.duration_trunc(Duration::hours(1))?;
+    while hour <= end_time {
+        let mut keys = archive.list_keys(&hour_prefix(hour)).await?;
+        keys.sort();
+
+        for key in keys {
+            let Some((key_project_id, timestamp)) = parse_archive_key(&key) else {
+                log::warn!("Skipping unrecognized archive key: {}", key);
+                continue;
+            };
+            if timestamp < start_nanos || timestamp > end_nanos {
+                continue;
+            }
+            if project_id.is_some_and(|id| id != key_project_id) {
+                continue;
+            }
+
+            let res = replay_payload(
+                &archive,
+                &key,
+                key_project_id,
+                rabbitmq_connection.clone(),
+                db.clone(),
+                cache.clone(),
+            )
+            .await;
+            match res {
+                Ok(_) => stats.replayed_payloads += 1,
+                Err(e) => {
+                    log::error!("Failed to replay archived payload {}: {:?}", key, e);
+                    stats.failed_payloads += 1;
+                }
+            }
+        }
+
+        hour += Duration::hours(1);
+    }
+
+    Ok(stats)
+}
+
+async fn replay_payload(
+    archive: &PayloadArchive,
+    key: &str,
+    project_id: Uuid,
+    rabbitmq_connection: Option<Arc<Connection>>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+) -> Result<()> {
+    let payload = archive.retrieve(key).await?;
+    let request = ExportTraceServiceRequest::decode(payload.as_slice())?;
+    // replays don't have the client's region or debug flag, they are not archived
+    push_spans_to_queue(
+        request,
+        project_id,
+        None,
+        None,
+        rabbitmq_connection,
+        db,
+        cache,
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_archive_key_roundtrip() {
+        let project_id = Uuid::new_v4();
+        let time = Utc.with_ymd_and_hms(2024, 11, 8, 13, 45, 10).unwrap();
+        let key = archive_key(&project_id, time);
+
+        assert!(key.starts_with("ingestion-archive/2024-11-08/13/"));
+        assert_eq!(
+            parse_archive_key(&key),
+            Some((project_id, chrono_to_nanoseconds(time)))
+        );
+        assert_eq!(parse_archive_key("ingestion-archive/README"), None);
+    }
+}
@@ -3,6 +3,8 @@ use uuid::Uuid;
 
 use crate::db::trace::TraceType;
 
+use super::client_metadata::ClientMetadata;
+
 #[derive(Default, Clone, Debug)]
 pub struct TraceAttributes {
     pub id: Uuid,
@@ -19,7 +21,9 @@ pub struct TraceAttributes {
     pub success: Option<bool>,
     pub session_id: Option<String>,
     pub user_id: Option<String>,
+    pub external_id: Option<String>,
     pub trace_type: Option<TraceType>,
+    pub client_metadata: ClientMetadata,
 }
 
 impl TraceAttributes {
@@ -73,7 +77,15 @@ impl TraceAttributes {
         self.user_id = user_id;
     }
 
+    pub fn update_external_id(&mut self, external_id: Option<String>) {
+        self.external_id = external_id;
+    }
+
     pub fn update_trace_type(&mut self, trace_type: Option<TraceType>) {
         self.trace_type = trace_type;
     }
+
+    pub fn update_client_metadata(&mut self, client_metadata: ClientMetadata) {
+        self.client_metadata = client_metadata;
+    }
 }
@@ -0,0 +1,182 @@
+//! Correlation of the events recorded in the browser of a browser agent with the agent's spans.
+//!
+//! An agent decides on an action in an LLM call and the browser changes once the action is
+//! taken, so each browser event is attributed to the last LLM span that ended before it. The
+//! timeline interleaves the spans and the events in time order, for stepping through a run.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    ch::browser_events::BrowserEvent,
+    db::spans::{Span, SpanType},
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineSpan {
+    pub span_id: Uuid,
+    pub parent_span_id: Option<Uuid>,
+    pub name: String,
+    pub span_type: SpanType,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// Number of browser events attributed to the span, only LLM spans have them
+    pub resulting_event_count: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineBrowserEvent {
+    pub id: Uuid,
+    pub session_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub event_type: String,
+    pub data: Value,
+    pub screenshot_url: Option<String>,
+    /// LLM span whose action resulted in the event, None for the events before the first action
+    pub action_span_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TimelineItem {
+    Span(TimelineSpan),
+    BrowserEvent(TimelineBrowserEvent),
+}
+
+impl TimelineItem {
+    fn time(&self) -> DateTime<Utc> {
+        match self {
+            TimelineItem::Span(span) => span.start_time,
+            TimelineItem::BrowserEvent(event) => event.timestamp,
+        }
+    }
+}
+
+/// Spans and browser events of a trace in time order, with the events attributed to actions.
+/// A span comes before the events recorded at its start time.
+pub fn build_timeline(spans: Vec<Span>, events: Vec<BrowserEvent>) -> Vec<TimelineItem> {
+    let mut actions = spans
+        .iter()
+        .filter(|span| span.span_type == SpanType::LLM)
+        .map(|span| (span.end_time, span.span_id))
+        .collect::<Vec<_>>();
+    actions.sort();
+
+    let mut resulting_event_counts = HashMap::<Uuid, usize>::new();
+    let events = events
+        .into_iter()
+        .map(|event| {
+            let ended_actions =
+                actions.partition_point(|(end_time, _)| *end_time <= event.timestamp);
+            let action_span_id = ended_actions.checked_sub(1).map(|index| actions[index].1);
+            if let Some(span_id) = action_span_id {
+                *resulting_event_counts.entry(span_id).or_default() += 1;
+            }
+            TimelineBrowserEvent {
+                id: event.id,
+                session_id: event.session_id,
+                timestamp: event.timestamp,
+                event_type: event.event_type,
+                data: serde_json::from_str(&event.data).unwrap_or(Value::String(event.data)),
+                screenshot_url: Some(event.screenshot_url).filter(|url| !url.is_empty()),
+                action_span_id,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut items = spans
+        .into_iter()
+        .map(|span| {
+            TimelineItem::Span(TimelineSpan {
+                resulting_event_count: resulting_event_counts
+                    .get(&span.span_id)
+                    .copied()
+                    .unwrap_or_default(),
+                span_id: span.span_id,
+                parent_span_id: span.parent_span_id,
+                name: span.name,
+                span_type: span.span_type,
+                start_time: span.start_time,
+                end_time: span.end_time,
+            })
+        })
+        .chain(events.into_iter().map(TimelineItem::BrowserEvent))
+        .collect::<Vec<_>>();
+    // Stable, so spans stay before the events at the same time
+    items.sort_by_key(|item| item.time());
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn span(span_type: SpanType, start_time: DateTime<Utc>, seconds: i64) -> Span {
+        Span {
+            span_id: Uuid::new_v4(),
+            span_type,
+            start_time,
+            end_time: start_time + Duration::seconds(seconds),
+            ..Default::default()
+        }
+    }
+
+    fn event(timestamp: DateTime<Utc>) -> BrowserEvent {
+        BrowserEvent {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            session_id: "session".to_string(),
+            trace_id: Uuid::nil(),
+            timestamp,
+            event_type: "mutation".to_string(),
+            data: r#"{"adds":1}"#.to_string(),
+            screenshot_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_build_timeline() {
+        let start = Utc::now();
+        let root = span(SpanType::DEFAULT, start, 10);
+        let first_action = span(SpanType::LLM, start + Duration::seconds(1), 2);
+        let second_action = span(SpanType::LLM, start + Duration::seconds(5), 2);
+        let (first_action_id, second_action_id) = (first_action.span_id, second_action.span_id);
+
+        let timeline = build_timeline(
+            vec![second_action, root, first_action],
+            vec![
+                event(start),
+                event(start + Duration::seconds(4)),
+                event(start + Duration::seconds(7)),
+            ],
+        );
+
+        let action_span_ids = timeline
+            .iter()
+            .filter_map(|item| match item {
+                TimelineItem::BrowserEvent(event) => Some(event.action_span_id),
+                TimelineItem::Span(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            action_span_ids,
+            vec![None, Some(first_action_id), Some(second_action_id)]
+        );
+        // The root span starts with the first event, and comes before it
+        assert!(
+            matches!(&timeline[0], TimelineItem::Span(span) if span.span_type == SpanType::DEFAULT)
+        );
+        assert!(
+            matches!(&timeline[2], TimelineItem::Span(span) if span.resulting_event_count == 1)
+        );
+        assert_eq!(timeline.len(), 6);
+    }
+}
@@ -0,0 +1,218 @@
+//! Client metadata enrichment at ingestion. The SDK language and version and the deployment
+//! environment come from the OpenTelemetry resource of the exported spans. The region is the
+//! country code that the edge proxy derives from the client's IP address and sets in the header
+//! named by `CLIENT_REGION_HEADER`, e.g. `cf-ipcountry`. Regions are only kept for projects
+//! that opted in with `collect_client_region`, and the IP address itself is never stored.
+//!
+//! The metadata is added to every span as `lmnr.client.*` attributes, and copied to the trace
+//! and to the ClickHouse span, so that it is available in trace filters and analytics breakdowns.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    db::{self, spans::Span, utils::convert_any_value_to_json_value, DB},
+    opentelemetry::opentelemetry_proto_resource_v1::Resource,
+};
+
+use super::{
+    span_attributes::{CLIENT_ENVIRONMENT, CLIENT_REGION, CLIENT_SDK_LANGUAGE, CLIENT_SDK_VERSION},
+    spans::SpanAttributes,
+};
+
+const MAX_VALUE_LENGTH: usize = 64;
+
+const SDK_LANGUAGE_RESOURCE_ATTRIBUTES: [&str; 1] = ["telemetry.sdk.language"];
+const SDK_VERSION_RESOURCE_ATTRIBUTES: [&str; 1] = ["telemetry.sdk.version"];
+const ENVIRONMENT_RESOURCE_ATTRIBUTES: [&str; 2] =
+    ["deployment.environment.name", "deployment.environment"];
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct ClientMetadata {
+    pub sdk_language: Option<String>,
+    pub sdk_version: Option<String>,
+    pub environment: Option<String>,
+    /// ISO 3166 country code
+    pub region: Option<String>,
+}
+
+/// Whether the project keeps the region of its clients
+#[derive(Clone)]
+pub struct ClientRegionSettings {
+    pub collect_client_region: bool,
+}
+
+fn dimension_value(value: &Value) -> Option<String> {
+    let value = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Null => return None,
+        v => v.to_string(),
+    };
+    if value.is_empty() {
+        return None;
+    }
+    Some(value.chars().take(MAX_VALUE_LENGTH).collect())
+}
+
+/// Value of the first of `keys` that the resource has
+pub(super) fn resource_attribute(resource: &Resource, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| {
+        resource
+            .attributes
+            .iter()
+            .find(|attribute| attribute.key == *key)
+            // converting an empty value would panic
+            .and_then(|attribute| attribute.value.clone().filter(|v| v.value.is_some()))
+            .and_then(|value| dimension_value(&convert_any_value_to_json_value(Some(value))))
+    })
+}
+
+impl ClientMetadata {
+    pub fn from_resource(resource: Option<&Resource>) -> Self {
+        let Some(resource) = resource else {
+            return Self::default();
+        };
+        Self {
+            sdk_language: resource_attribute(resource, &SDK_LANGUAGE_RESOURCE_ATTRIBUTES),
+            sdk_version: resource_attribute(resource, &SDK_VERSION_RESOURCE_ATTRIBUTES),
+            environment: resource_attribute(resource, &ENVIRONMENT_RESOURCE_ATTRIBUTES),
+            region: None,
+        }
+    }
+
+    pub fn from_span_attributes(attributes: &SpanAttributes) -> Self {
+        let get = |key: &str| attributes.attributes.get(key).and_then(dimension_value);
+        Self {
+            sdk_language: get(CLIENT_SDK_LANGUAGE),
+            sdk_version: get(CLIENT_SDK_VERSION),
+            environment: get(CLIENT_ENVIRONMENT),
+            region: get(CLIENT_REGION),
+        }
+    }
+
+    /// Adds the metadata to the span's attributes. Values that the span already has are kept.
+    pub fn apply(&self, span: &mut Span) {
+        let Value::Object(attributes) = &mut span.attributes else {
+            return;
+        };
+        for (key, value) in [
+            (CLIENT_SDK_LANGUAGE, &self.sdk_language),
+            (CLIENT_SDK_VERSION, &self.sdk_version),
+            (CLIENT_ENVIRONMENT, &self.environment),
+            (CLIENT_REGION, &self.region),
+        ] {
+            if let Some(value) = value {
+                attributes
+                    .entry(key.to_string())
+                    .or_insert_with(|| json!(value));
+            }
+        }
+    }
+}
+
+/// Name of the header with the client's country code, set by the edge proxy
+pub fn client_region_header() -> Option<String> {
+    std::env::var("CLIENT_REGION_HEADER")
+        .ok()
+        .map(|header| header.trim().to_lowercase())
+        .filter(|header| !header.is_empty())
+}
+
+/// Country code from the header value. Proxies use "XX" for unknown countries and "T1" for Tor.
+pub fn parse_region(value: &str) -> Option<String> {
+    let region = value.trim().to_uppercase();
+    let is_country = region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic());
+    (is_country && region != "XX").then_some(region)
+}
+
+pub async fn collects_client_region(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+) -> Result<bool> {
+    let cache_res = cache
+        .get::<ClientRegionSettings>(&project_id.to_string())
+        .await;
+    match cache_res {
+        Ok(Some(settings)) => Ok(settings.collect_client_region),
+        Ok(None) | Err(_) => {
+            let collect_client_region =
+                db::projects::get_collect_client_region(&db.pool, &project_id).await?;
+            let _ = cache
+                .insert::<ClientRegionSettings>(
+                    project_id.to_string(),
+                    &ClientRegionSettings {
+                        collect_client_region,
+                    },
+                )
+                .await;
+            Ok(collect_client_region)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opentelemetry::opentelemetry_proto_common_v1::{any_value, AnyValue, KeyValue};
+
+    use super::*;
+
+    fn string_attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(value.to_string())),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_from_resource() {
+        let resource = Resource {
+            attributes: vec![
+                string_attribute("telemetry.sdk.language", "python"),
+                string_attribute("telemetry.sdk.version", "1.27.0"),
+                string_attribute("deployment.environment", "staging"),
+            ],
+            dropped_attributes_count: 0,
+        };
+        let metadata = ClientMetadata::from_resource(Some(&resource));
+        assert_eq!(metadata.sdk_language.as_deref(), Some("python"));
+        assert_eq!(metadata.sdk_version.as_deref(), Some("1.27.0"));
+        assert_eq!(metadata.environment.as_deref(), Some("staging"));
+        assert_eq!(
+            ClientMetadata::from_resource(None),
+            ClientMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut span = Span {
+            attributes: json!({ CLIENT_ENVIRONMENT: "production" }),
+            ..Default::default()
+        };
+        ClientMetadata {
+            sdk_language: Some("typescript".to_string()),
+            environment: Some("staging".to_string()),
+            ..Default::default()
+        }
+        .apply(&mut span);
+
+        let metadata = ClientMetadata::from_span_attributes(&span.get_attributes());
+        assert_eq!(metadata.sdk_language.as_deref(), Some("typescript"));
+        assert_eq!(metadata.environment.as_deref(), Some("production"));
+        assert!(metadata.region.is_none());
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!(parse_region(" de "), Some("DE".to_string()));
+        assert_eq!(parse_region("XX"), None);
+        assert_eq!(parse_region("Germany"), None);
+    }
+}
@@ -1,32 +1,213 @@
 //! This module reads spans from RabbitMQ and processes them: writes to DB,
 //! clickhouse, and semantic search.
+//!
+//! A span is written to ClickHouse only after it is written to Postgres. If the Postgres write
+//! fails, the message is published to the queue again, and after `MAX_DELIVERY_ATTEMPTS` failed
+//! attempts it's moved to the dead-letter queue.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use anyhow::Result;
 use futures::StreamExt;
-use lapin::{options::BasicConsumeOptions, options::*, types::FieldTable, Connection};
+use lapin::{
+    message::Delivery,
+    options::BasicConsumeOptions,
+    options::*,
+    types::{AMQPValue, FieldTable},
+    BasicProperties, Channel, Connection,
+};
+use uuid::Uuid;
 
-use super::{OBSERVATIONS_EXCHANGE, OBSERVATIONS_QUEUE, OBSERVATIONS_ROUTING_KEY};
+use super::{
+    spans::SpanUsage, OBSERVATIONS_DEAD_LETTER_QUEUE, OBSERVATIONS_EXCHANGE, OBSERVATIONS_QUEUE,
+    OBSERVATIONS_ROUTING_KEY,
+};
 use crate::{
+    analytics::AnalyticsStore,
     api::v1::traces::RabbitMqSpanMessage,
     cache::Cache,
-    ch::{self, spans::CHSpan},
+    ch::{span_search::CHSpanSearchEntry, spans::CHSpan},
     chunk,
     db::{labels::get_registered_label_classes_for_path, spans::Span, stats, DB},
     features::{is_feature_enabled, Feature},
     pipeline::runner::PipelineRunner,
     semantic_search::SemanticSearch,
     storage::Storage,
-    traces::{evaluators::run_evaluator, utils::record_span_to_db},
+    traces::{
+        evaluators::run_evaluator,
+        ingestion_lag, online_evaluations,
+        promoted_attributes::{apply_promoted_attributes, get_promoted_attributes},
+        shadow::{self, shadow_percentage, should_shadow},
+        shadow_deployments::shadow_deployments_for_span,
+        utils::record_span_to_db,
+    },
 };
 
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+/// Header with the number of failed attempts to process the message
+const DELIVERY_ATTEMPTS_HEADER: &str = "x-lmnr-delivery-attempts";
+/// Failed messages are published again after this delay times the number of failed attempts
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Debug, PartialEq)]
+enum Settlement {
+    Ack,
+    /// Publish the message again, with the number of failed attempts so far
+    Retry(u32),
+    DeadLetter,
+}
+
+fn delivery_attempts(properties: &BasicProperties) -> u32 {
+    properties
+        .headers()
+        .as_ref()
+        .and_then(|headers| headers.inner().get(DELIVERY_ATTEMPTS_HEADER).cloned())
+        .and_then(|value| match value {
+            AMQPValue::LongUInt(attempts) => Some(attempts),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+fn retry_properties(attempts: u32) -> BasicProperties {
+    let mut headers = FieldTable::default();
+    headers.insert(
+        DELIVERY_ATTEMPTS_HEADER.into(),
+        AMQPValue::LongUInt(attempts),
+    );
+    BasicProperties::default().with_headers(headers)
+}
+
+fn settlement(written: &Result<()>, properties: &BasicProperties) -> Settlement {
+    if written.is_ok() {
+        return Settlement::Ack;
+    }
+    let attempts = delivery_attempts(properties) + 1;
+    if attempts >= MAX_DELIVERY_ATTEMPTS {
+        Settlement::DeadLetter
+    } else {
+        Settlement::Retry(attempts)
+    }
+}
+
+/// Acks the delivery once it's processed, or once it's published again to be retried or
+/// dead-lettered. If publishing fails, the delivery is nacked so that RabbitMQ redelivers it.
+async fn settle(channel: &Channel, delivery: &Delivery, settlement: Settlement) {
+    let published = match settlement {
+        Settlement::Ack => Ok(()),
+        Settlement::Retry(attempts) => {
+            tokio::time::sleep(RETRY_BACKOFF * attempts).await;
+            publish(
+                channel,
+                OBSERVATIONS_QUEUE,
+                delivery,
+                retry_properties(attempts),
+            )
+            .await
+        }
+        Settlement::DeadLetter => {
+            let properties = delivery.properties.clone();
+            publish(
+                channel,
+                OBSERVATIONS_DEAD_LETTER_QUEUE,
+                delivery,
+                properties,
+            )
+            .await
+        }
+    };
+
+    let settled = match published {
+        Ok(()) => delivery.ack(BasicAckOptions::default()).await,
+        Err(e) => {
+            log::error!("Failed to publish RabbitMQ delivery again: {:?}", e);
+            delivery
+                .nack(BasicNackOptions {
+                    requeue: true,
+                    ..Default::default()
+                })
+                .await
+        }
+    };
+    if let Err(e) = settled {
+        log::error!("Failed to settle RabbitMQ delivery: {:?}", e);
+    }
+}
+
+/// Publishes the delivery to the queue through the default exchange
+async fn publish(
+    channel: &Channel,
+    queue: &str,
+    delivery: &Delivery,
+    properties: BasicProperties,
+) -> Result<()> {
+    channel
+        .basic_publish(
+            "",
+            queue,
+            BasicPublishOptions::default(),
+            &delivery.data,
+            properties,
+        )
+        .await?
+        .await?;
+    Ok(())
+}
+
+/// Writes the span to Postgres and, only if that succeeds, to ClickHouse. Failed ClickHouse
+/// writes are logged, they are retried by the analytics store.
+async fn write_span(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    project_id: Uuid,
+    span: &mut Span,
+    span_usage: SpanUsage,
+) -> Result<()> {
+    record_span_to_db(db.clone(), &span_usage, &project_id, span).await?;
+
+    let mut ch_span = CHSpan::from_db_span(span, span_usage, project_id);
+    match get_promoted_attributes(db, cache, project_id).await {
+        Ok(promoted) => apply_promoted_attributes(&mut ch_span, &span.get_attributes(), &promoted),
+        Err(e) => log::error!(
+            "Failed to get promoted attributes. project_id [{}]: {:?}",
+            project_id,
+            e
+        ),
+    }
+    // TODO: Queue batches and send them every 1-2 seconds
+    if let Err(e) = analytics_store.insert_span(&ch_span).await {
+        log::error!(
+            "Failed to insert span into Clickhouse. span_id [{}], project_id [{}]: {:?}",
+            span.span_id,
+            project_id,
+            e
+        );
+    }
+    let search_entry = CHSpanSearchEntry::from_db_span(span, project_id);
+    if !search_entry.is_empty() {
+        if let Err(e) = analytics_store
+            .insert_span_search_entry(&search_entry)
+            .await
+        {
+            log::error!(
+                "Failed to insert span search entry. span_id [{}], project_id [{}]: {:?}",
+                span.span_id,
+                project_id,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
 pub async fn process_queue_spans<T: Storage + ?Sized>(
     pipeline_runner: Arc<PipelineRunner>,
     db: Arc<DB>,
     cache: Arc<Cache>,
     semantic_search: Arc<dyn SemanticSearch>,
     rabbitmq_connection: Option<Arc<Connection>>,
-    clickhouse: clickhouse::Client,
+    analytics_store: Arc<dyn AnalyticsStore>,
     chunker_runner: Arc<chunk::runner::ChunkerRunner>,
     storage: Arc<T>,
 ) {
@@ -37,7 +218,7 @@ pub async fn process_queue_spans<T: Storage + ?Sized>(
             cache.clone(),
             semantic_search.clone(),
             rabbitmq_connection.clone(),
-            clickhouse.clone(),
+            analytics_store.clone(),
             chunker_runner.clone(),
             storage.clone(),
         )
@@ -52,7 +233,7 @@ async fn inner_process_queue_spans<T: Storage + ?Sized>(
     cache: Arc<Cache>,
     _semantic_search: Arc<dyn SemanticSearch>,
     rabbitmq_connection: Option<Arc<Connection>>,
-    clickhouse: clickhouse::Client,
+    analytics_store: Arc<dyn AnalyticsStore>,
     _chunker_runner: Arc<chunk::runner::ChunkerRunner>,
     storage: Arc<T>,
 ) {
@@ -85,6 +266,11 @@ async fn inner_process_queue_spans<T: Storage + ?Sized>(
 
     log::info!("Started processing spans from RabbitMQ");
 
+    let shadow_percentage = shadow_percentage();
+    if shadow_percentage > 0.0 {
+        log::info!("Shadowing {}% of spans to spans_shadow", shadow_percentage);
+    }
+
     while let Some(delivery) = consumer.next().await {
         let Ok(delivery) = delivery else {
             log::error!("Failed to get delivery from RabbitMQ. Continuing...");
@@ -93,31 +279,38 @@ async fn inner_process_queue_spans<T: Storage + ?Sized>(
 
         let Ok(payload) = String::from_utf8(delivery.data.clone()) else {
             log::error!("Failed to parse delivery data as UTF-8. Continuing...");
+            settle(&channel, &delivery, Settlement::DeadLetter).await;
             continue;
         };
 
         let Ok(rabbitmq_span_message) = serde_json::from_str::<RabbitMqSpanMessage>(&payload)
         else {
             log::error!("Failed to parse delivery data as `RabbitMqSpanMessage`. Continuing...");
+            settle(&channel, &delivery, Settlement::DeadLetter).await;
             continue;
         };
+        let is_retry = delivery_attempts(&delivery.properties) > 0;
 
         let mut span: Span = rabbitmq_span_message.span;
+        let shadow_span = should_shadow(&span.span_id, shadow_percentage).then(|| span.clone());
 
         let events_count = rabbitmq_span_message.events.len();
 
-        if let Err(e) = stats::add_spans_and_events_to_project_usage_stats(
-            &db.pool,
-            &rabbitmq_span_message.project_id,
-            1,
-            events_count as i64,
-        )
-        .await
-        {
-            log::error!(
-                "Failed to add spans and events to project usage stats: {:?}",
-                e
-            );
+        // usage is counted on the first attempt only
+        if !is_retry {
+            if let Err(e) = stats::add_spans_and_events_to_project_usage_stats(
+                &db.pool,
+                &rabbitmq_span_message.project_id,
+                1,
+                events_count as i64,
+            )
+            .await
+            {
+                log::error!(
+                    "Failed to add spans and events to project usage stats: {:?}",
+                    e
+                );
+            }
         }
 
         if is_feature_enabled(Feature::UsageLimit) {
@@ -138,6 +331,11 @@ async fn inner_process_queue_spans<T: Storage + ?Sized>(
                 Ok(limits_exceeded) => {
                     // TODO: do the same for events
                     if limits_exceeded.spans {
+                        ingestion_lag::record_discarded(
+                            rabbitmq_span_message.project_id,
+                            &span,
+                            rabbitmq_span_message.accepted_at,
+                        );
                         let _ = delivery
                             .ack(BasicAckOptions::default())
                             .await
@@ -169,38 +367,59 @@ async fn inner_process_queue_spans<T: Storage + ?Sized>(
             }
         }
 
-        if let Err(e) = record_span_to_db(
+        let written = write_span(
             db.clone(),
-            &span_usage,
-            &rabbitmq_span_message.project_id,
+            cache.clone(),
+            analytics_store.clone(),
+            rabbitmq_span_message.project_id,
             &mut span,
+            span_usage,
         )
-        .await
-        {
+        .await;
+        // settle the message as soon as the span is recorded
+        let settlement = settlement(&written, &delivery.properties);
+        if settlement == Settlement::DeadLetter {
+            ingestion_lag::record_discarded(
+                rabbitmq_span_message.project_id,
+                &span,
+                rabbitmq_span_message.accepted_at,
+            );
+        }
+        settle(&channel, &delivery, settlement).await;
+        if let Err(e) = written {
             log::error!(
                 "Failed to record span. span_id [{}], project_id [{}]: {:?}",
                 span.span_id,
                 rabbitmq_span_message.project_id,
                 e
             );
-        } else {
-            // ack the message as soon as the span is recorded
-            let _ = delivery
-                .ack(BasicAckOptions::default())
-                .await
-                .map_err(|e| log::error!("Failed to ack RabbitMQ delivery: {:?}", e));
+            continue;
         }
 
-        let ch_span = CHSpan::from_db_span(&span, span_usage, rabbitmq_span_message.project_id);
-        // TODO: Queue batches and send them every 1-2 seconds
-        let insert_span_res = ch::spans::insert_span(clickhouse.clone(), &ch_span).await;
-        if let Err(e) = insert_span_res {
-            log::error!(
-                "Failed to insert span into Clickhouse. span_id [{}], project_id [{}]: {:?}",
-                span.span_id,
+        ingestion_lag::record_processed(
+            rabbitmq_span_message.project_id,
+            &span,
+            rabbitmq_span_message.accepted_at,
+        );
+
+        if let Some(shadow_span) = shadow_span {
+            let span_id = shadow_span.span_id;
+            if let Err(e) = shadow::shadow_span(
+                analytics_store.clone(),
+                shadow_span,
                 rabbitmq_span_message.project_id,
-                e
-            );
+                db.clone(),
+                cache.clone(),
+            )
+            .await
+            {
+                log::error!(
+                    "Failed to process shadow span. span_id [{}], project_id [{}]: {:?}",
+                    span_id,
+                    rabbitmq_span_message.project_id,
+                    e
+                );
+            }
         }
 
         let registered_label_classes = match get_registered_label_classes_for_path(
@@ -228,6 +447,7 @@ async fn inner_process_queue_spans<T: Storage + ?Sized>(
                 registered_label_class.label_class_id,
                 &span,
                 db.clone(),
+                analytics_store.clone(),
             )
             .await
             {
@@ -235,7 +455,131 @@ async fn inner_process_queue_spans<T: Storage + ?Sized>(
                 Err(e) => log::error!("Failed to run evaluator: {:?}", e),
             }
         }
+
+        if let Err(e) = online_evaluations::evaluate_span(
+            pipeline_runner.language_model(),
+            db.clone(),
+            cache.clone(),
+            analytics_store.clone(),
+            rabbitmq_span_message.project_id,
+            &span,
+        )
+        .await
+        {
+            log::error!(
+                "Failed to start online evaluations. span_id [{}], project_id [{}]: {:?}",
+                span.span_id,
+                rabbitmq_span_message.project_id,
+                e
+            );
+        }
+
+        if let Err(e) = shadow_deployments_for_span(
+            pipeline_runner.language_model(),
+            db.clone(),
+            cache.clone(),
+            rabbitmq_span_message.project_id,
+            &span,
+        )
+        .await
+        {
+            log::error!(
+                "Failed to start shadow deployment comparisons. span_id [{}], project_id [{}]: {:?}",
+                span.span_id,
+                rabbitmq_span_message.project_id,
+                e
+            );
+        }
     }
 
     log::warn!("RabbitMQ closed connection. Shutting down span listener");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settlement() {
+        let mut properties = BasicProperties::default();
+        assert_eq!(settlement(&Ok(()), &properties), Settlement::Ack);
+
+        let failed = Err(anyhow::anyhow!("Postgres is down"));
+        for attempts in 1..MAX_DELIVERY_ATTEMPTS {
+            assert_eq!(
+                settlement(&failed, &properties),
+                Settlement::Retry(attempts)
+            );
+            properties = retry_properties(attempts);
+        }
+        assert_eq!(settlement(&failed, &properties), Settlement::DeadLetter);
+    }
+
+    #[cfg(feature = "chaos")]
+    #[tokio::test]
+    async fn test_postgres_failure() {
+        use std::{collections::HashMap, str::FromStr};
+
+        use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+
+        use crate::{
+            analytics::in_memory::InMemoryAnalyticsStore,
+            chaos::{self, ChaosConfig},
+        };
+
+        let _config_lock = chaos::lock_config().await;
+        chaos::set_config(ChaosConfig {
+            postgres_failure_rate: 1.0,
+            ..Default::default()
+        });
+
+        // The injected failure happens before the lazy pool is connected to
+        let pool = PgPoolOptions::new().connect_lazy_with(
+            PgConnectOptions::from_str("postgres://chaos@localhost/chaos").unwrap(),
+        );
+        let db = Arc::new(DB::new(pool));
+        let cache = Arc::new(Cache::new(HashMap::new()));
+        let in_memory_store = Arc::new(InMemoryAnalyticsStore::default());
+        let analytics_store: Arc<dyn AnalyticsStore> = in_memory_store.clone();
+
+        // Every redelivery fails the same way, until the message is dead-lettered
+        let mut properties = BasicProperties::default();
+        let mut redeliveries = 0;
+        loop {
+            let mut span = Span {
+                span_id: Uuid::new_v4(),
+                trace_id: Uuid::new_v4(),
+                attributes: serde_json::json!({}),
+                ..Default::default()
+            };
+            let written = write_span(
+                db.clone(),
+                cache.clone(),
+                analytics_store.clone(),
+                Uuid::new_v4(),
+                &mut span,
+                SpanUsage::default(),
+            )
+            .await;
+            assert!(written
+                .as_ref()
+                .unwrap_err()
+                .to_string()
+                .contains("injected Postgres failure"));
+
+            match settlement(&written, &properties) {
+                Settlement::Retry(attempts) => {
+                    redeliveries += 1;
+                    properties = retry_properties(attempts);
+                }
+                Settlement::DeadLetter => break,
+                Settlement::Ack => panic!("A failed write must not be acked"),
+            }
+        }
+        chaos::set_config(ChaosConfig::default());
+
+        assert_eq!(redeliveries, MAX_DELIVERY_ATTEMPTS - 1);
+        assert!(in_memory_store.spans.lock().unwrap().is_empty());
+        assert!(in_memory_store.span_search.lock().unwrap().is_empty());
+    }
+}
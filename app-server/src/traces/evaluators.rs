@@ -1,9 +1,12 @@
 use anyhow::Result;
+use chrono::Utc;
 use serde::Deserialize;
 use std::{collections::HashMap, sync::Arc};
 use uuid::Uuid;
 
 use crate::{
+    analytics::AnalyticsStore,
+    ch::span_scores::SpanScore,
     db::{
         self,
         labels::{LabelJobStatus, LabelSource},
@@ -35,6 +38,7 @@ pub async fn run_evaluator(
     label_class_id: Uuid,
     span: &Span,
     db: Arc<DB>,
+    analytics_store: Arc<dyn AnalyticsStore>,
 ) -> Result<()> {
     let label_class = db::labels::get_label_class(&db.pool, project_id, label_class_id)
         .await?
@@ -69,6 +73,7 @@ pub async fn run_evaluator(
 
     let run_type = RunType::AutoLabel;
     graph.setup(&inputs, &env, &HashMap::new(), &run_type)?;
+    graph.project_id = Some(project_id);
 
     let run_result = pipeline_runner.run(graph, None).await.map_err(|e| {
         anyhow::anyhow!(
@@ -117,6 +122,18 @@ pub async fn run_evaluator(
     )
     .await?;
 
+    analytics_store
+        .insert_span_scores(vec![SpanScore {
+            project_id,
+            timestamp: Utc::now(),
+            span_id: span.span_id,
+            trace_id: span.trace_id,
+            name: label_class.name,
+            value: label_value,
+            user_id: Uuid::nil(),
+        }])
+        .await?;
+
     Ok(())
 }
 
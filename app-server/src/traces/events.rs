@@ -5,7 +5,8 @@ use serde_json::Value;
 use uuid::Uuid;
 
 use crate::{
-    ch::{self, events::CHEvent},
+    analytics::AnalyticsStore,
+    ch::events::CHEvent,
     db::{
         self,
         event_templates::{EventTemplate, EventType},
@@ -17,7 +18,7 @@ use crate::{
 // TODO: Make this function more readable and separate into smaller functions
 pub async fn create_events(
     db: Arc<DB>,
-    clickhouse: clickhouse::Client,
+    analytics_store: Arc<dyn AnalyticsStore>,
     event_payloads: Vec<EventObservation>,
     event_source: EventSource,
     project_id: Uuid,
@@ -148,5 +149,5 @@ pub async fn create_events(
         })
         .collect::<Vec<CHEvent>>();
 
-    ch::events::insert_events(clickhouse, ch_events).await
+    analytics_store.insert_events(ch_events).await
 }
@@ -0,0 +1,232 @@
+//! Bulk export of a project's spans, e.g. to load them into a dataframe.
+//!
+//! Like evaluation results, see `evaluations::export`, spans are read from Postgres in batches
+//! and each batch is encoded and sent right away. Input, output and attributes are exported as
+//! JSON strings, and hidden fields as null.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow_array::{
+    builder::{StringBuilder, TimestampMicrosecondBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    db::{self, field_visibility::FieldVisibility, spans::Span},
+    evaluations::export::{to_json_string, ColumnarWriter, ExportFormat},
+};
+
+const EXPORT_BATCH_SIZE: usize = 1000;
+
+const COLUMNS: [&str; 10] = [
+    "spanId",
+    "traceId",
+    "parentSpanId",
+    "name",
+    "spanType",
+    "startTime",
+    "endTime",
+    "input",
+    "output",
+    "attributes",
+];
+
+pub fn columnar_schema() -> SchemaRef {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("spanId", DataType::Utf8, false),
+        Field::new("traceId", DataType::Utf8, false),
+        Field::new("parentSpanId", DataType::Utf8, true),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("spanType", DataType::Utf8, false),
+        Field::new("startTime", timestamp.clone(), false),
+        Field::new("endTime", timestamp, false),
+        Field::new("input", DataType::Utf8, true),
+        Field::new("output", DataType::Utf8, true),
+        Field::new("attributes", DataType::Utf8, false),
+    ]))
+}
+
+pub fn record_batch(spans: &[Span], schema: SchemaRef) -> Result<RecordBatch> {
+    let mut span_ids = StringBuilder::new();
+    let mut trace_ids = StringBuilder::new();
+    let mut parent_span_ids = StringBuilder::new();
+    let mut names = StringBuilder::new();
+    let mut span_types = StringBuilder::new();
+    let mut start_times = TimestampMicrosecondBuilder::new().with_timezone("UTC");
+    let mut end_times = TimestampMicrosecondBuilder::new().with_timezone("UTC");
+    let mut inputs = StringBuilder::new();
+    let mut outputs = StringBuilder::new();
+    let mut attributes = StringBuilder::new();
+
+    for span in spans {
+        span_ids.append_value(span.span_id.to_string());
+        trace_ids.append_value(span.trace_id.to_string());
+        parent_span_ids.append_option(span.parent_span_id.map(|id| id.to_string()));
+        names.append_value(&span.name);
+        span_types.append_value(format!("{:?}", span.span_type));
+        start_times.append_value(span.start_time.timestamp_micros());
+        end_times.append_value(span.end_time.timestamp_micros());
+        inputs.append_option(span.input.as_ref().map(to_json_string));
+        outputs.append_option(span.output.as_ref().map(to_json_string));
+        attributes.append_value(span.attributes.to_string());
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(span_ids.finish()),
+        Arc::new(trace_ids.finish()),
+        Arc::new(parent_span_ids.finish()),
+        Arc::new(names.finish()),
+        Arc::new(span_types.finish()),
+        Arc::new(start_times.finish()),
+        Arc::new(end_times.finish()),
+        Arc::new(inputs.finish()),
+        Arc::new(outputs.finish()),
+        Arc::new(attributes.finish()),
+    ];
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn encode_csv(spans: &[Span], with_header: bool) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    if with_header {
+        writer.write_record(COLUMNS)?;
+    }
+    for span in spans {
+        writer.write_record([
+            span.span_id.to_string(),
+            span.trace_id.to_string(),
+            span.parent_span_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            span.name.clone(),
+            format!("{:?}", span.span_type),
+            span.start_time.to_rfc3339(),
+            span.end_time.to_rfc3339(),
+            span.input.as_ref().map(to_json_string).unwrap_or_default(),
+            span.output.as_ref().map(to_json_string).unwrap_or_default(),
+            span.attributes.to_string(),
+        ])?;
+    }
+    writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+fn encode_jsonl(spans: &[Span]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for span in spans {
+        let line = json!({
+            "spanId": span.span_id,
+            "traceId": span.trace_id,
+            "parentSpanId": span.parent_span_id,
+            "name": span.name,
+            "spanType": span.span_type,
+            "startTime": span.start_time,
+            "endTime": span.end_time,
+            "input": span.input,
+            "output": span.output,
+            "attributes": span.attributes,
+        });
+        serde_json::to_writer(&mut bytes, &line)?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+/// Encoded spans of the project that started in the period, in chunks of a batch of spans each
+pub fn export_spans(
+    pool: PgPool,
+    project_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    visibility: FieldVisibility,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<Bytes>> {
+    async_stream::try_stream! {
+        let schema = columnar_schema();
+        let mut columnar_writer = ColumnarWriter::new(format, schema.clone())?;
+
+        let mut batches =
+            db::spans::stream_spans(&pool, project_id, start_time, end_time, visibility)
+                .chunks(EXPORT_BATCH_SIZE);
+        let mut is_first_batch = true;
+        while let Some(batch) = batches.next().await {
+            let spans = batch.into_iter().collect::<Result<Vec<_>, _>>()?;
+            let bytes = match (&mut columnar_writer, format) {
+                (Some(writer), _) => writer.write(&record_batch(&spans, schema.clone())?)?,
+                (None, ExportFormat::Csv) => encode_csv(&spans, is_first_batch)?,
+                (None, _) => encode_jsonl(&spans)?,
+            };
+            is_first_batch = false;
+            yield Bytes::from(bytes);
+        }
+
+        if let Some(writer) = columnar_writer {
+            yield Bytes::from(writer.finish()?);
+        } else if is_first_batch && format == ExportFormat::Csv {
+            yield Bytes::from(encode_csv(&[], true)?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow_ipc::reader::StreamReader;
+    use serde_json::Value;
+
+    use super::*;
+
+    fn span(input: Option<Value>) -> Span {
+        Span {
+            span_id: Uuid::new_v4(),
+            trace_id: Uuid::new_v4(),
+            name: "openai.chat".to_string(),
+            attributes: json!({"gen_ai.system": "openai"}),
+            input,
+            start_time: Utc::now(),
+            end_time: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_arrow_export() {
+        let schema = columnar_schema();
+        let mut writer = ColumnarWriter::new(ExportFormat::Arrow, schema.clone())
+            .unwrap()
+            .unwrap();
+        let spans = vec![span(Some(json!([{"role": "user"}]))), span(None)];
+        let mut bytes = writer
+            .write(&record_batch(&spans, schema.clone()).unwrap())
+            .unwrap();
+        bytes.extend(writer.finish().unwrap());
+
+        let batches = StreamReader::try_new(Cursor::new(bytes), None)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].schema(), schema);
+        assert_eq!(batches[0].num_rows(), 2);
+        // Neither span has a parent, the second one has no input
+        assert_eq!(batches[0].column(2).null_count(), 2);
+        assert_eq!(batches[0].column(7).null_count(), 1);
+    }
+
+    #[test]
+    fn test_encode_csv() {
+        let csv = String::from_utf8(encode_csv(&[span(None)], true).unwrap()).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert!(lines[1].contains(",openai.chat,DEFAULT,"));
+    }
+}
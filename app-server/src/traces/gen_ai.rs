@@ -0,0 +1,219 @@
+//! Mapping of the OpenTelemetry semantic conventions for generative AI onto Laminar spans, so
+//! that apps instrumented with any OpenTelemetry library, not only the Laminar SDKs, get LLM
+//! spans with their messages, model and usage.
+//!
+//! Refer to https://github.com/open-telemetry/semantic-conventions/blob/main/docs/gen-ai/gen-ai-spans.md
+//! and https://github.com/open-telemetry/semantic-conventions/blob/main/docs/gen-ai/gen-ai-events.md
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    db::{spans::SpanType, utils::convert_any_value_to_json_value},
+    opentelemetry::opentelemetry_proto_trace_v1::span::Event as OtelEvent,
+};
+
+use super::span_attributes::{GEN_AI_SYSTEM, SPAN_TYPE};
+
+/// Replaces `gen_ai.system` in the newer versions of the conventions
+const GEN_AI_PROVIDER_NAME: &str = "gen_ai.provider.name";
+pub(super) const GEN_AI_OPERATION_NAME: &str = "gen_ai.operation.name";
+pub const GEN_AI_INPUT_MESSAGES: &str = "gen_ai.input.messages";
+pub const GEN_AI_OUTPUT_MESSAGES: &str = "gen_ai.output.messages";
+pub const GEN_AI_SYSTEM_INSTRUCTIONS: &str = "gen_ai.system_instructions";
+pub(super) const GEN_AI_AGENT_ID: &str = "gen_ai.agent.id";
+pub(super) const GEN_AI_AGENT_NAME: &str = "gen_ai.agent.name";
+
+/// Operations that are calls of a model, unlike e.g. `execute_tool` or `invoke_agent`
+const MODEL_OPERATIONS: [&str; 4] = ["chat", "text_completion", "generate_content", "embeddings"];
+
+/// Sets the attributes Laminar uses from their semantic convention counterparts, before the
+/// span type is derived from the attributes
+pub fn normalize_attributes(attributes: &mut Map<String, Value>) {
+    if !attributes.contains_key(GEN_AI_SYSTEM) {
+        if let Some(provider) = attributes.get(GEN_AI_PROVIDER_NAME).cloned() {
+            attributes.insert(GEN_AI_SYSTEM.to_string(), provider);
+        }
+    }
+
+    let is_model_operation = attributes
+        .get(GEN_AI_OPERATION_NAME)
+        .and_then(Value::as_str)
+        .is_some_and(|operation| MODEL_OPERATIONS.contains(&operation));
+    if is_model_operation && !attributes.contains_key(SPAN_TYPE) {
+        attributes.insert(SPAN_TYPE.to_string(), json!(SpanType::LLM));
+    }
+}
+
+/// Messages in the `{"role", "content"}` format of the other instrumentations, with the content
+/// being the text if the message only has text parts
+fn convert_message(message: &Value) -> Value {
+    let role = message.get("role").cloned().unwrap_or(json!("user"));
+    let Some(parts) = message.get("parts").and_then(Value::as_array) else {
+        return message.clone();
+    };
+    let texts = parts
+        .iter()
+        .map(|part| match part.get("type").and_then(Value::as_str) {
+            Some("text") => part.get("content").and_then(Value::as_str),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+
+    match texts {
+        Some(texts) => json!({"role": role, "content": texts.join("")}),
+        None => json!({"role": role, "content": parts}),
+    }
+}
+
+fn messages_attribute(attributes: &Map<String, Value>, key: &str) -> Option<Vec<Value>> {
+    let messages = match attributes.get(key)? {
+        Value::String(s) => serde_json::from_str::<Value>(s).ok()?,
+        value => value.clone(),
+    };
+    Some(messages.as_array()?.iter().map(convert_message).collect())
+}
+
+fn event_message(event: &OtelEvent) -> Option<(bool, Value)> {
+    let role = match event.name.as_str() {
+        "gen_ai.system.message" => "system",
+        "gen_ai.user.message" => "user",
+        "gen_ai.assistant.message" => "assistant",
+        "gen_ai.tool.message" => "tool",
+        "gen_ai.choice" => "assistant",
+        _ => return None,
+    };
+    let attributes = event
+        .attributes
+        .iter()
+        .map(|kv| {
+            (
+                kv.key.clone(),
+                convert_any_value_to_json_value(kv.value.clone()),
+            )
+        })
+        .collect::<Map<String, Value>>();
+
+    // The choice event nests the message, the others have its fields at the top level
+    let message = match attributes.get("message") {
+        Some(Value::String(s)) => serde_json::from_str::<Value>(s).unwrap_or(json!(s)),
+        Some(message) => message.clone(),
+        None => Value::Object(attributes.clone()),
+    };
+    let content = message
+        .get("content")
+        .cloned()
+        .unwrap_or_else(|| message.clone());
+
+    Some((
+        event.name == "gen_ai.choice",
+        json!({"role": role, "content": content}),
+    ))
+}
+
+/// Input and output messages of an LLM span from the message attributes, or from the message
+/// events of the older versions of the conventions
+pub fn input_output_messages(
+    attributes: &Map<String, Value>,
+    events: &[OtelEvent],
+) -> (Option<Value>, Option<Value>) {
+    let mut input = messages_attribute(attributes, GEN_AI_INPUT_MESSAGES);
+    if let Some(instructions) = attributes.get(GEN_AI_SYSTEM_INSTRUCTIONS) {
+        // Either a list of parts or plain text
+        let parts = match instructions {
+            Value::String(s) => serde_json::from_str::<Value>(s).unwrap_or(json!(s)),
+            value => value.clone(),
+        };
+        let system_message = if parts.is_array() {
+            convert_message(&json!({"role": "system", "parts": parts}))
+        } else {
+            json!({"role": "system", "content": parts})
+        };
+        input.get_or_insert_with(Vec::new).insert(0, system_message);
+    }
+    let mut output = messages_attribute(attributes, GEN_AI_OUTPUT_MESSAGES);
+
+    if input.is_none() && output.is_none() {
+        let (choices, messages): (Vec<_>, Vec<_>) = events
+            .iter()
+            .filter_map(event_message)
+            .partition(|(is_choice, _)| *is_choice);
+        if !messages.is_empty() {
+            input = Some(messages.into_iter().map(|(_, message)| message).collect());
+        }
+        if !choices.is_empty() {
+            output = Some(choices.into_iter().map(|(_, message)| message).collect());
+        }
+    }
+
+    (input.map(Value::Array), output.map(Value::Array))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opentelemetry::opentelemetry_proto_common_v1::{any_value, AnyValue, KeyValue};
+
+    use super::*;
+
+    fn event(name: &str, attributes: &[(&str, &str)]) -> OtelEvent {
+        OtelEvent {
+            name: name.to_string(),
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| KeyValue {
+                    key: key.to_string(),
+                    value: Some(AnyValue {
+                        value: Some(any_value::Value::StringValue(value.to_string())),
+                    }),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_attributes() {
+        let mut attributes = Map::new();
+        attributes.insert(GEN_AI_PROVIDER_NAME.to_string(), json!("openai"));
+        attributes.insert(GEN_AI_OPERATION_NAME.to_string(), json!("chat"));
+        normalize_attributes(&mut attributes);
+
+        assert_eq!(attributes[GEN_AI_SYSTEM], json!("openai"));
+        assert_eq!(attributes[SPAN_TYPE], json!("LLM"));
+    }
+
+    #[test]
+    fn test_input_output_from_attributes() {
+        let mut attributes = Map::new();
+        attributes.insert(
+            GEN_AI_INPUT_MESSAGES.to_string(),
+            json!(r#"[{"role":"user","parts":[{"type":"text","content":"2 + 2?"}]}]"#),
+        );
+        attributes.insert(
+            GEN_AI_OUTPUT_MESSAGES.to_string(),
+            json!(r#"[{"role":"assistant","parts":[{"type":"text","content":"4"}]}]"#),
+        );
+        attributes.insert(GEN_AI_SYSTEM_INSTRUCTIONS.to_string(), json!("Be brief"));
+
+        let (input, output) = input_output_messages(&attributes, &[]);
+        assert_eq!(
+            input,
+            Some(json!([
+                {"role": "system", "content": "Be brief"},
+                {"role": "user", "content": "2 + 2?"},
+            ]))
+        );
+        assert_eq!(output, Some(json!([{"role": "assistant", "content": "4"}])));
+    }
+
+    #[test]
+    fn test_input_output_from_events() {
+        let events = vec![
+            event("gen_ai.user.message", &[("content", "2 + 2?")]),
+            event("gen_ai.choice", &[("message", r#"{"content":"4"}"#)]),
+        ];
+
+        let (input, output) = input_output_messages(&Map::new(), &events);
+        assert_eq!(input, Some(json!([{"role": "user", "content": "2 + 2?"}])));
+        assert_eq!(output, Some(json!([{"role": "assistant", "content": "4"}])));
+    }
+}
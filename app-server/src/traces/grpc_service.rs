@@ -7,19 +7,33 @@ use crate::{
     cache::Cache,
     db::{project_api_keys::ProjectApiKey, DB},
     features::{is_feature_enabled, Feature},
+    network::ip_allowlist::is_ip_allowed_for_project,
     opentelemetry::opentelemetry::proto::collector::trace::v1::{
         trace_service_server::TraceService, ExportTraceServiceRequest, ExportTraceServiceResponse,
     },
 };
 use lapin::Connection;
+use prost::Message;
 use tonic::{Request, Response, Status};
 
-use super::{limits::get_workspace_limit_exceeded_by_project_id, producer::push_spans_to_queue};
+use super::{
+    archive::{archive_in_background, PayloadArchive},
+    client_metadata::{client_region_header, parse_region},
+    limits::get_workspace_limit_exceeded_by_project_id,
+    producer::push_spans_to_queue,
+    protocol::{
+        adapt_request, negotiate_protocol_version, CURRENT_PROTOCOL_VERSION,
+        PROTOCOL_VERSION_HEADER,
+    },
+    sampling::DEBUG_FLAG_HEADER,
+    sdk_versions::track_sdk_version,
+};
 
 pub struct ProcessTracesService {
     db: Arc<DB>,
     cache: Arc<Cache>,
     rabbitmq_connection: Option<Arc<Connection>>,
+    payload_archive: Option<Arc<PayloadArchive>>,
 }
 
 impl ProcessTracesService {
@@ -27,11 +41,13 @@ impl ProcessTracesService {
         db: Arc<DB>,
         cache: Arc<Cache>,
         rabbitmq_connection: Option<Arc<Connection>>,
+        payload_archive: Option<Arc<PayloadArchive>>,
     ) -> Self {
         Self {
             db,
             cache,
             rabbitmq_connection,
+            payload_archive,
         }
     }
 }
@@ -46,7 +62,39 @@ impl TraceService for ProcessTracesService {
             .await
             .map_err(|_| Status::unauthenticated("Failed to authenticate request"))?;
         let project_id = api_key.project_id;
-        let request = request.into_inner();
+
+        let ip = request.remote_addr().map(|addr| addr.ip());
+        let ip_allowed =
+            is_ip_allowed_for_project(self.db.clone(), self.cache.clone(), project_id, ip)
+                .await
+                .map_err(|e| {
+                    log::error!("Failed to check IP allowlist: {:?}", e);
+                    Status::internal("Failed to check IP allowlist")
+                })?;
+        if !ip_allowed {
+            return Err(Status::permission_denied("IP address is not allowed"));
+        }
+
+        let client_region = client_region_header()
+            .and_then(|header| request.metadata().get(header.as_str()))
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_region);
+        let debug_flag = request
+            .metadata()
+            .get(DEBUG_FLAG_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+
+        let protocol_version = negotiate_protocol_version(
+            request
+                .metadata()
+                .get(PROTOCOL_VERSION_HEADER)
+                .and_then(|value| value.to_str().ok()),
+        )
+        .map_err(|e| Status::failed_precondition(e.to_string()))?;
+
+        let mut request = request.into_inner();
+        adapt_request(&mut request, protocol_version);
 
         if is_feature_enabled(Feature::UsageLimit) {
             let limits_exceeded = get_workspace_limit_exceeded_by_project_id(
@@ -66,9 +114,21 @@ impl TraceService for ProcessTracesService {
             }
         }
 
+        if self.payload_archive.is_some() {
+            archive_in_background(
+                self.payload_archive.clone(),
+                project_id,
+                request.encode_to_vec(),
+            );
+        }
+
+        let sdk_warning = track_sdk_version(&request);
+
         let response = push_spans_to_queue(
             request,
             project_id,
+            client_region,
+            debug_flag,
             self.rabbitmq_connection.clone(),
             self.db.clone(),
             self.cache.clone(),
@@ -79,7 +139,15 @@ impl TraceService for ProcessTracesService {
             Status::internal("Failed to process traces")
         })?;
 
-        Ok(Response::new(response))
+        let mut response = Response::new(response);
+        response.metadata_mut().insert(
+            PROTOCOL_VERSION_HEADER,
+            CURRENT_PROTOCOL_VERSION.to_string().parse().unwrap(),
+        );
+        if let Some(warning) = sdk_warning.and_then(|warning| warning.parse().ok()) {
+            response.metadata_mut().insert("warning", warning);
+        }
+        Ok(response)
     }
 }
 
@@ -0,0 +1,223 @@
+//! Ingestion lag of each project: spans accepted by the ingestion endpoints that are not yet
+//! queryable, and how long the last spans took to become queryable.
+//!
+//! Spans are queued when they are accepted and written to the databases by the consumer, so
+//! a trace is only visible some time after it is sent. The lag is reported by the ingestion
+//! status endpoints and in headers of the trace read responses, so that users can tell a slow
+//! queue from lost spans.
+//!
+//! The accepted spans are also buffered until they are processed, for reads that must see
+//! the spans that were just sent, see `wait_for_in_flight_spans`.
+//!
+//! Like `metrics`, the counters and the buffer are in-process. They are exact when the same
+//! instance accepts and consumes the spans, as in the default deployment.
+
+use std::{
+    sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use actix_web::HttpResponseBuilder;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{db::spans::Span, metrics};
+
+pub const INGESTION_PENDING_SPANS_HEADER: &str = "x-lmnr-ingestion-pending-spans";
+pub const INGESTION_LAG_HEADER: &str = "x-lmnr-ingestion-lag-ms";
+/// Spans buffered across all projects, further spans are only counted
+const MAX_IN_FLIGHT_SPANS: usize = 10_000;
+const IN_FLIGHT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    static ref PROJECTS: DashMap<Uuid, ProjectIngestion> = DashMap::new();
+    /// Accepted spans that are not yet processed, by project and trace
+    static ref IN_FLIGHT: DashMap<(Uuid, Uuid), Vec<Span>> = DashMap::new();
+    static ref IN_FLIGHT_COUNT: AtomicUsize = AtomicUsize::new(0);
+}
+
+#[derive(Default)]
+struct ProjectIngestion {
+    /// Signed, so that spans consumed after a restart, which were accepted by the previous
+    /// process, can't wrap it around
+    pending_spans: AtomicI64,
+    last_lag_ms: AtomicU64,
+    /// Unix timestamp in milliseconds, 0 if no span was processed yet
+    last_processed_at: AtomicI64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestionStatus {
+    /// Spans accepted but not yet queryable
+    pub pending_spans: u64,
+    /// Time from acceptance until the last processed span became queryable
+    pub last_lag_ms: Option<u64>,
+    pub last_processed_at: Option<DateTime<Utc>>,
+    /// Average time to flush spans to ClickHouse, where the traces are read from
+    pub avg_flush_latency_ms: f64,
+}
+
+/// Must be called before the span is queued, so that it can't be processed before it is
+/// buffered
+pub fn record_accepted(project_id: Uuid, span: &Span) {
+    PROJECTS
+        .entry(project_id)
+        .or_default()
+        .pending_spans
+        .fetch_add(1, Ordering::Relaxed);
+    if IN_FLIGHT_COUNT.fetch_add(1, Ordering::Relaxed) < MAX_IN_FLIGHT_SPANS {
+        IN_FLIGHT
+            .entry((project_id, span.trace_id))
+            .or_default()
+            .push(span.clone());
+    } else {
+        IN_FLIGHT_COUNT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn remove_in_flight(project_id: Uuid, span: &Span) {
+    let key = (project_id, span.trace_id);
+    if let Some(mut spans) = IN_FLIGHT.get_mut(&key) {
+        if let Some(index) = spans.iter().position(|s| s.span_id == span.span_id) {
+            spans.swap_remove(index);
+            IN_FLIGHT_COUNT.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+    IN_FLIGHT.remove_if(&key, |_, spans| spans.is_empty());
+}
+
+/// Records a span that is now queryable. Spans without an acceptance time, e.g. those of
+/// pipeline runs, are not counted.
+pub fn record_processed(project_id: Uuid, span: &Span, accepted_at: Option<DateTime<Utc>>) {
+    let Some(accepted_at) = accepted_at else {
+        return;
+    };
+    remove_in_flight(project_id, span);
+    let now = Utc::now();
+    let project = PROJECTS.entry(project_id).or_default();
+    project.pending_spans.fetch_sub(1, Ordering::Relaxed);
+    project.last_lag_ms.store(
+        (now - accepted_at).num_milliseconds().max(0) as u64,
+        Ordering::Relaxed,
+    );
+    project
+        .last_processed_at
+        .store(now.timestamp_millis(), Ordering::Relaxed);
+}
+
+/// Records a span that won't be processed, e.g. over the workspace limits or not queued
+pub fn record_discarded(project_id: Uuid, span: &Span, accepted_at: Option<DateTime<Utc>>) {
+    if accepted_at.is_some() {
+        remove_in_flight(project_id, span);
+        PROJECTS
+            .entry(project_id)
+            .or_default()
+            .pending_spans
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub fn ingestion_status(project_id: &Uuid) -> IngestionStatus {
+    let avg_flush_latency_ms = metrics::batch_writer("spans").avg_flush_latency_ms();
+    let Some(project) = PROJECTS.get(project_id) else {
+        return IngestionStatus {
+            pending_spans: 0,
+            last_lag_ms: None,
+            last_processed_at: None,
+            avg_flush_latency_ms,
+        };
+    };
+    let last_processed_at =
+        DateTime::from_timestamp_millis(project.last_processed_at.load(Ordering::Relaxed))
+            .filter(|time| time.timestamp_millis() > 0);
+
+    IngestionStatus {
+        pending_spans: project.pending_spans.load(Ordering::Relaxed).max(0) as u64,
+        last_lag_ms: last_processed_at.map(|_| project.last_lag_ms.load(Ordering::Relaxed)),
+        last_processed_at,
+        avg_flush_latency_ms,
+    }
+}
+
+/// Waits up to `timeout` for the spans of the trace that were accepted to be processed. Returns
+/// the spans that are still in flight after it.
+pub async fn wait_for_in_flight_spans(
+    project_id: Uuid,
+    trace_id: Uuid,
+    timeout: Duration,
+) -> Vec<Span> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let spans = IN_FLIGHT
+            .get(&(project_id, trace_id))
+            .map(|spans| spans.clone())
+            .unwrap_or_default();
+        if spans.is_empty() || tokio::time::Instant::now() >= deadline {
+            return spans;
+        }
+        tokio::time::sleep(IN_FLIGHT_POLL_INTERVAL).await;
+    }
+}
+
+/// Adds the pending spans and the last lag of the project to a read response
+pub fn insert_ingestion_headers(response: &mut HttpResponseBuilder, project_id: &Uuid) {
+    let status = ingestion_status(project_id);
+    response.insert_header((
+        INGESTION_PENDING_SPANS_HEADER,
+        status.pending_spans.to_string(),
+    ));
+    if let Some(last_lag_ms) = status.last_lag_ms {
+        response.insert_header((INGESTION_LAG_HEADER, last_lag_ms.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(trace_id: Uuid) -> Span {
+        Span {
+            span_id: Uuid::new_v4(),
+            trace_id,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_status() {
+        let (project_id, trace_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let (first, second) = (span(trace_id), span(trace_id));
+        record_accepted(project_id, &first);
+        record_accepted(project_id, &second);
+        record_processed(
+            project_id,
+            &first,
+            Some(Utc::now() - chrono::Duration::seconds(2)),
+        );
+        // Not accepted by the ingestion endpoints
+        record_processed(project_id, &span(trace_id), None);
+
+        let status = ingestion_status(&project_id);
+        assert_eq!(status.pending_spans, 1);
+        assert!(status.last_lag_ms.is_some_and(|lag| lag >= 2000));
+        assert!(status.last_processed_at.is_some());
+        let in_flight =
+            wait_for_in_flight_spans(project_id, trace_id, Duration::from_millis(10)).await;
+        assert_eq!(in_flight.len(), 1);
+        assert_eq!(in_flight[0].span_id, second.span_id);
+
+        record_discarded(project_id, &second, Some(Utc::now()));
+        assert_eq!(ingestion_status(&project_id).pending_spans, 0);
+        assert!(
+            wait_for_in_flight_spans(project_id, trace_id, Duration::ZERO)
+                .await
+                .is_empty()
+        );
+        assert!(ingestion_status(&Uuid::new_v4()).last_lag_ms.is_none());
+    }
+}
@@ -80,7 +80,7 @@ pub async fn update_workspace_limit_exceeded_by_workspace_id(
     Ok(workspace_limits_exceeded)
 }
 
-async fn get_workspace_id_for_project_id(
+pub async fn get_workspace_id_for_project_id(
     db: Arc<DB>,
     cache: Arc<Cache>,
     project_id: Uuid,
@@ -0,0 +1,179 @@
+//! Read-time masking of trace data. Unlike redaction at ingestion, masking doesn't change the
+//! stored data: a project's masking profiles mask what is served to members of some workspace
+//! roles, while other members keep seeing the original values.
+//!
+//! Hashed user ids are stable within a project, so masked traces can still be grouped by user.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+use sha3::{Digest, Sha3_256};
+use uuid::Uuid;
+
+use crate::{
+    ch::span_search::SpanSearchResult,
+    db::{
+        agent_checkpoints::AgentCheckpoint,
+        masking_profiles::MaskingProfile,
+        spans::Span,
+        trace::{Trace, TraceWithTopSpan},
+    },
+};
+
+const REDACTED_EMAIL: &str = "[REDACTED EMAIL]";
+const HASHED_USER_ID_LENGTH: usize = 16;
+
+lazy_static! {
+    static ref EMAIL_REGEX: Regex =
+        Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap();
+}
+
+fn hash_user_id(project_id: &Uuid, user_id: &str) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(project_id.as_bytes());
+    hasher.update(user_id.as_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    format!("user_{}", &hash[..HASHED_USER_ID_LENGTH])
+}
+
+fn redact_emails_in_text(text: &str) -> String {
+    EMAIL_REGEX.replace_all(text, REDACTED_EMAIL).into_owned()
+}
+
+fn redact_emails(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = redact_emails_in_text(s),
+        Value::Array(values) => values.iter_mut().for_each(redact_emails),
+        Value::Object(map) => map.values_mut().for_each(redact_emails),
+        _ => {}
+    }
+}
+
+fn mask_user_id(user_id: &mut Option<String>, profile: &MaskingProfile) {
+    if profile.hash_user_ids {
+        if let Some(id) = user_id {
+            *id = hash_user_id(&profile.project_id, id);
+        }
+    }
+}
+
+fn mask_preview(preview: &mut Option<String>, profile: &MaskingProfile) {
+    if profile.redact_emails {
+        if let Some(preview) = preview {
+            *preview = redact_emails_in_text(preview);
+        }
+    }
+}
+
+pub fn mask_span(span: &mut Span, profile: &MaskingProfile) {
+    if profile.redact_emails {
+        span.input.iter_mut().for_each(redact_emails);
+        span.output.iter_mut().for_each(redact_emails);
+    }
+}
+
+pub fn mask_trace(trace: &mut Trace, profile: &MaskingProfile) {
+    mask_user_id(&mut trace.user_id, profile);
+}
+
+pub fn mask_trace_with_top_span(trace: &mut TraceWithTopSpan, profile: &MaskingProfile) {
+    mask_user_id(&mut trace.user_id, profile);
+    mask_preview(&mut trace.top_span_input_preview, profile);
+    mask_preview(&mut trace.top_span_output_preview, profile);
+}
+
+pub fn mask_agent_checkpoint(checkpoint: &mut AgentCheckpoint, profile: &MaskingProfile) {
+    if profile.redact_emails {
+        redact_emails(&mut checkpoint.state);
+    }
+}
+
+pub fn mask_span_search_result(result: &mut SpanSearchResult, profile: &MaskingProfile) {
+    mask_preview(&mut result.input_snippet, profile);
+    mask_preview(&mut result.output_snippet, profile);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use crate::db::field_visibility::WorkspaceRole;
+
+    use super::*;
+
+    #[test]
+    fn test_mask_span() {
+        let profile = MaskingProfile {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            project_id: Uuid::nil(),
+            role: WorkspaceRole::Viewer,
+            hash_user_ids: true,
+            redact_emails: true,
+        };
+        let mut span = Span {
+            input: Some(json!({ "to": "jane.doe@example.com", "retries": 2 })),
+            output: Some(json!(["Sent to jane.doe@example.com and ops@corp.io."])),
+            ..Default::default()
+        };
+        mask_span(&mut span, &profile);
+
+        assert_eq!(
+            span.input,
+            Some(json!({ "to": REDACTED_EMAIL, "retries": 2 }))
+        );
+        assert_eq!(
+            span.output,
+            Some(json!([format!(
+                "Sent to {REDACTED_EMAIL} and {REDACTED_EMAIL}."
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_mask_agent_checkpoint() {
+        let profile = MaskingProfile {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            project_id: Uuid::nil(),
+            role: WorkspaceRole::Viewer,
+            hash_user_ids: false,
+            redact_emails: true,
+        };
+        let mut checkpoint = AgentCheckpoint {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            trace_id: Uuid::new_v4(),
+            span_id: Uuid::new_v4(),
+            span_path: None,
+            step: 3,
+            state: json!({
+                "messages": [{ "role": "user", "content": "Email jane.doe@example.com" }],
+                "memory": { "contact": "ops@corp.io" },
+            }),
+            browser_session_id: None,
+            resume_count: 0,
+            resumed_at: None,
+        };
+        mask_agent_checkpoint(&mut checkpoint, &profile);
+
+        assert_eq!(
+            checkpoint.state,
+            json!({
+                "messages": [{ "role": "user", "content": format!("Email {REDACTED_EMAIL}") }],
+                "memory": { "contact": REDACTED_EMAIL },
+            })
+        );
+    }
+
+    #[test]
+    fn test_hash_user_id() {
+        let (project_a, project_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let hashed = hash_user_id(&project_a, "user-42");
+
+        assert_eq!(hashed, hash_user_id(&project_a, "user-42"));
+        assert_ne!(hashed, hash_user_id(&project_b, "user-42"));
+        assert_eq!(hashed.len(), "user_".len() + HASHED_USER_ID_LENGTH);
+    }
+}
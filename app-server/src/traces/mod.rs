@@ -1,15 +1,34 @@
+pub mod agent_graph;
+pub mod archive;
 pub mod attributes;
+pub mod browser;
+pub mod client_metadata;
 pub mod consumer;
 pub mod evaluators;
 pub mod events;
+pub mod export;
+pub mod gen_ai;
 pub mod grpc_service;
 mod index;
+pub mod ingestion_lag;
 pub mod limits;
+pub mod masking;
+pub mod online_evaluations;
 pub mod producer;
+pub mod promoted_attributes;
+pub mod protocol;
+pub mod sampling;
+pub mod sdk_versions;
+pub mod self_tracing;
+pub mod shadow;
+pub mod shadow_deployments;
+pub mod snapshots;
 pub mod span_attributes;
 pub mod spans;
 pub mod utils;
 
 pub const OBSERVATIONS_QUEUE: &str = "observations_queue";
+/// Spans that failed to be recorded after all delivery attempts
+pub const OBSERVATIONS_DEAD_LETTER_QUEUE: &str = "observations_dead_letter_queue";
 pub const OBSERVATIONS_EXCHANGE: &str = "observations_exchange";
 pub const OBSERVATIONS_ROUTING_KEY: &str = "observations_routing_key";
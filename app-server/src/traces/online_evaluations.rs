@@ -0,0 +1,492 @@
+//! Online evaluations continuously score production traffic. A project's rules match incoming
+//! spans by path, name and attribute values, and a sample of the matched spans is scored by the
+//! rule's evaluator: an LLM judge registered with the project's judge evaluators, a regex, or a
+//! heuristic check. Scores are recorded as span scores with the rule's name, like the scores of
+//! label class evaluators.
+//!
+//! Evaluations run in the background after the span is stored. Their number is bounded by
+//! `MAX_CONCURRENT_EVALUATIONS` across projects, and spans that arrive while all permits are
+//! taken are left unscored rather than delaying ingestion.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use chrono::Utc;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    cache::Cache,
+    ch::span_scores::SpanScore,
+    db::{
+        self, judge_evaluators::JudgeEvaluator, online_evaluation_rules::OnlineEvaluationRule,
+        spans::Span, DB,
+    },
+    evaluations::{judge, utils::ScoreValue},
+    language_model::LanguageModelRunner,
+    traces::{evaluators::get_stored_env, shadow::should_shadow, utils::json_value_to_string},
+};
+
+const MAX_CONCURRENT_EVALUATIONS: usize = 32;
+
+lazy_static! {
+    static ref EVALUATION_PERMITS: Arc<Semaphore> =
+        Arc::new(Semaphore::new(MAX_CONCURRENT_EVALUATIONS));
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SpanField {
+    Input,
+    #[default]
+    Output,
+}
+
+/// Evaluator of a rule, stored as JSON, e.g. `{"type": "judge", "judge": "helpfulness"}` or
+/// `{"type": "regex", "pattern": "(?i)sorry", "field": "output"}`. Apart from judges, scores
+/// are 1 if the check passes and 0 otherwise.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum OnlineEvaluator {
+    /// Judge evaluator with the name, prompted with the span's `input` and `output`
+    Judge { judge: String },
+    /// The pattern matches the text of the field
+    Regex {
+        pattern: String,
+        #[serde(default)]
+        field: SpanField,
+    },
+    /// The field is a JSON object or array, or a string that parses as JSON
+    ValidJson {
+        #[serde(default)]
+        field: SpanField,
+    },
+    /// The field has text other than whitespace
+    NotEmpty {
+        #[serde(default)]
+        field: SpanField,
+    },
+    /// The text of the field has at most `max_length` characters
+    MaxLength {
+        #[serde(default)]
+        field: SpanField,
+        max_length: usize,
+    },
+    /// The span took at most `max_seconds`
+    MaxLatency { max_seconds: f64 },
+}
+
+#[derive(Clone)]
+enum RuleEvaluator {
+    Judge(JudgeEvaluator),
+    Regex(Regex, SpanField),
+    Heuristic(OnlineEvaluator),
+}
+
+#[derive(Clone)]
+pub struct ActiveRule {
+    pub rule: OnlineEvaluationRule,
+    evaluator: RuleEvaluator,
+}
+
+/// Enabled online evaluation rules of a project, cached by project id
+#[derive(Clone)]
+pub struct ProjectOnlineEvaluationRules {
+    pub rules: Vec<ActiveRule>,
+}
+
+/// Parses the evaluator of a rule and checks its settings. Judges are checked by the caller.
+pub fn parse_evaluator(evaluator: &Value) -> Result<OnlineEvaluator> {
+    let evaluator = serde_json::from_value::<OnlineEvaluator>(evaluator.clone())?;
+    match &evaluator {
+        OnlineEvaluator::Regex { pattern, .. } => {
+            Regex::new(pattern)?;
+        }
+        OnlineEvaluator::MaxLatency { max_seconds } if !(*max_seconds > 0.0) => {
+            return Err(anyhow::anyhow!("maxSeconds must be greater than 0"));
+        }
+        _ => {}
+    }
+    Ok(evaluator)
+}
+
+async fn load_rules(db: &DB, project_id: &Uuid) -> Result<Vec<ActiveRule>> {
+    let rules =
+        db::online_evaluation_rules::get_enabled_online_evaluation_rules(&db.pool, project_id)
+            .await?;
+    let parsed = rules
+        .into_iter()
+        .filter_map(|rule| match parse_evaluator(&rule.evaluator) {
+            Ok(evaluator) => Some((rule, evaluator)),
+            Err(e) => {
+                log::error!(
+                    "Invalid evaluator of online evaluation rule [{}]: {:?}",
+                    rule.id,
+                    e
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let judge_names = parsed
+        .iter()
+        .filter_map(|(_, evaluator)| match evaluator {
+            OnlineEvaluator::Judge { judge } => Some(judge.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let judges = if judge_names.is_empty() {
+        HashMap::new()
+    } else {
+        db::judge_evaluators::get_judge_evaluators_by_names(&db.pool, project_id, &judge_names)
+            .await?
+            .into_iter()
+            .map(|judge| (judge.name.clone(), judge))
+            .collect::<HashMap<_, _>>()
+    };
+
+    let active = parsed
+        .into_iter()
+        .filter_map(|(rule, evaluator)| {
+            let evaluator = match evaluator {
+                // A judge deleted after the rule was created disables the rule
+                OnlineEvaluator::Judge { judge } => {
+                    RuleEvaluator::Judge(judges.get(&judge)?.clone())
+                }
+                // Valid, parse_evaluator compiled it
+                OnlineEvaluator::Regex { pattern, field } => {
+                    RuleEvaluator::Regex(Regex::new(&pattern).ok()?, field)
+                }
+                evaluator => RuleEvaluator::Heuristic(evaluator),
+            };
+            Some(ActiveRule { rule, evaluator })
+        })
+        .collect();
+
+    Ok(active)
+}
+
+pub async fn get_active_rules(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+) -> Result<Vec<ActiveRule>> {
+    let cache_res = cache
+        .get::<ProjectOnlineEvaluationRules>(&project_id.to_string())
+        .await;
+    match cache_res {
+        Ok(Some(rules)) => Ok(rules.rules),
+        Ok(None) | Err(_) => {
+            let rules = load_rules(&db, &project_id).await?;
+            let _ = cache
+                .insert::<ProjectOnlineEvaluationRules>(
+                    project_id.to_string(),
+                    &ProjectOnlineEvaluationRules {
+                        rules: rules.clone(),
+                    },
+                )
+                .await;
+            Ok(rules)
+        }
+    }
+}
+
+/// Drops the cached rules after the project's rules or judge evaluators changed
+pub async fn invalidate_online_evaluation_rules(cache: Arc<Cache>, project_id: Uuid) {
+    let _ = cache
+        .remove::<ProjectOnlineEvaluationRules>(&project_id.to_string())
+        .await;
+}
+
+fn rule_matches(rule: &OnlineEvaluationRule, span: &Span) -> bool {
+    if let Some(span_path) = &rule.span_path {
+        if span.get_attributes().path().as_ref() != Some(span_path) {
+            return false;
+        }
+    }
+    if let Some(span_name) = &rule.span_name {
+        if &span.name != span_name {
+            return false;
+        }
+    }
+    match rule.attribute_filters.as_object() {
+        Some(filters) => filters
+            .iter()
+            .all(|(key, value)| span.attributes.get(key) == Some(value)),
+        None => true,
+    }
+}
+
+fn field_value(span: &Span, field: SpanField) -> Option<&Value> {
+    let value = match field {
+        SpanField::Input => span.input.as_ref(),
+        SpanField::Output => span.output.as_ref(),
+    };
+    value.filter(|value| !value.is_null())
+}
+
+fn field_text(span: &Span, field: SpanField) -> String {
+    field_value(span, field)
+        .map(|value| json_value_to_string(value.clone()))
+        .unwrap_or_default()
+}
+
+fn passed(passed: bool) -> f64 {
+    if passed {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn heuristic_score(evaluator: &OnlineEvaluator, span: &Span) -> f64 {
+    match evaluator {
+        OnlineEvaluator::ValidJson { field } => passed(match field_value(span, *field) {
+            Some(Value::String(s)) => serde_json::from_str::<Value>(s).is_ok(),
+            Some(Value::Object(_)) | Some(Value::Array(_)) => true,
+            _ => false,
+        }),
+        OnlineEvaluator::NotEmpty { field } => passed(!field_text(span, *field).trim().is_empty()),
+        OnlineEvaluator::MaxLength { field, max_length } => {
+            passed(field_text(span, *field).chars().count() <= *max_length)
+        }
+        OnlineEvaluator::MaxLatency { max_seconds } => {
+            let latency = (span.end_time - span.start_time).num_milliseconds() as f64 / 1000.0;
+            passed(latency <= *max_seconds)
+        }
+        OnlineEvaluator::Judge { .. } | OnlineEvaluator::Regex { .. } => {
+            unreachable!("judges and regexes are compiled into their own evaluators")
+        }
+    }
+}
+
+/// Numeric value of a judge's score, the index of the label for categorical scores
+fn judge_score_value(judge: &JudgeEvaluator, score: &ScoreValue) -> Option<f64> {
+    match score {
+        ScoreValue::Label(label) => judge
+            .labels
+            .iter()
+            .position(|l| l == label)
+            .map(|i| i as f64),
+        score => score.as_f64(),
+    }
+}
+
+async fn score_span(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    env: &HashMap<String, String>,
+    evaluator: &RuleEvaluator,
+    span: &Span,
+) -> Result<f64> {
+    match evaluator {
+        RuleEvaluator::Judge(judge) => {
+            let score = judge::run_judge(
+                db,
+                cache,
+                language_model,
+                env,
+                judge,
+                &span.input.clone().unwrap_or_default(),
+                &span.output.clone().unwrap_or_default(),
+                &Value::Null,
+            )
+            .await?;
+            judge_score_value(judge, &score)
+                .ok_or_else(|| anyhow::anyhow!("Judge {} has no value for {:?}", judge.name, score))
+        }
+        RuleEvaluator::Regex(regex, field) => Ok(passed(regex.is_match(&field_text(span, *field)))),
+        RuleEvaluator::Heuristic(evaluator) => Ok(heuristic_score(evaluator, span)),
+    }
+}
+
+async fn evaluate(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    project_id: Uuid,
+    rules: Vec<ActiveRule>,
+    span: &Span,
+) -> Result<()> {
+    let has_judges = rules
+        .iter()
+        .any(|active| matches!(active.evaluator, RuleEvaluator::Judge(_)));
+    let env = if has_judges {
+        get_stored_env(db.clone(), project_id).await?
+    } else {
+        HashMap::new()
+    };
+
+    let mut scores = Vec::with_capacity(rules.len());
+    for active in rules {
+        match score_span(
+            language_model.clone(),
+            db.clone(),
+            cache.clone(),
+            &env,
+            &active.evaluator,
+            span,
+        )
+        .await
+        {
+            Ok(value) => scores.push(SpanScore {
+                project_id,
+                timestamp: Utc::now(),
+                span_id: span.span_id,
+                trace_id: span.trace_id,
+                name: active.rule.name,
+                value,
+                user_id: Uuid::nil(),
+            }),
+            Err(e) => log::warn!(
+                "Online evaluation rule [{}] failed on span [{}]: {:?}",
+                active.rule.id,
+                span.span_id,
+                e
+            ),
+        }
+    }
+
+    analytics_store.insert_span_scores(scores).await
+}
+
+/// Starts scoring the span with the project's matching rules that sample it
+pub async fn evaluate_span(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    project_id: Uuid,
+    span: &Span,
+) -> Result<()> {
+    let rules = get_active_rules(db.clone(), cache.clone(), project_id)
+        .await?
+        .into_iter()
+        .filter(|active| {
+            rule_matches(&active.rule, span)
+                && should_shadow(&span.span_id, active.rule.sample_percentage)
+        })
+        .collect::<Vec<_>>();
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let Ok(permit) = EVALUATION_PERMITS.clone().try_acquire_owned() else {
+        log::warn!(
+            "Skipping online evaluations of span [{}], project_id [{}]: too many are running",
+            span.span_id,
+            project_id
+        );
+        return Ok(());
+    };
+    let span = span.clone();
+    tokio::spawn(async move {
+        if let Err(e) = evaluate(
+            language_model,
+            db,
+            cache,
+            analytics_store,
+            project_id,
+            rules,
+            &span,
+        )
+        .await
+        {
+            log::error!(
+                "Failed to record online evaluations. span_id [{}], project_id [{}]: {:?}",
+                span.span_id,
+                project_id,
+                e
+            );
+        }
+        drop(permit);
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use serde_json::json;
+
+    use super::*;
+
+    fn rule(span_path: Option<&str>, attribute_filters: Value) -> OnlineEvaluationRule {
+        OnlineEvaluationRule {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            project_id: Uuid::new_v4(),
+            name: "valid_json".to_string(),
+            span_path: span_path.map(String::from),
+            span_name: None,
+            attribute_filters,
+            sample_percentage: 100.0,
+            evaluator: json!({"type": "validJson"}),
+            enabled: true,
+        }
+    }
+
+    fn span(output: Value) -> Span {
+        let start_time = Utc::now();
+        Span {
+            name: "openai.chat".to_string(),
+            attributes: json!({"lmnr.span.path": "agent.openai.chat", "gen_ai.system": "openai"}),
+            output: Some(output),
+            start_time,
+            end_time: start_time + Duration::milliseconds(1500),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let span = span(json!("{}"));
+        assert!(rule_matches(&rule(None, json!({})), &span));
+        assert!(rule_matches(
+            &rule(
+                Some("agent.openai.chat"),
+                json!({"gen_ai.system": "openai"})
+            ),
+            &span
+        ));
+        assert!(!rule_matches(&rule(Some("agent"), json!({})), &span));
+        assert!(!rule_matches(
+            &rule(None, json!({"gen_ai.system": "anthropic"})),
+            &span
+        ));
+    }
+
+    #[test]
+    fn test_heuristic_score() {
+        let evaluator = parse_evaluator(&json!({"type": "validJson"})).unwrap();
+        assert_eq!(
+            evaluator,
+            OnlineEvaluator::ValidJson {
+                field: SpanField::Output
+            }
+        );
+        assert_eq!(heuristic_score(&evaluator, &span(json!("{\"a\": 1}"))), 1.0);
+        assert_eq!(heuristic_score(&evaluator, &span(json!("not json"))), 0.0);
+
+        let max_length = parse_evaluator(&json!({"type": "maxLength", "maxLength": 3})).unwrap();
+        assert_eq!(heuristic_score(&max_length, &span(json!("abcd"))), 0.0);
+
+        let max_latency = parse_evaluator(&json!({"type": "maxLatency", "maxSeconds": 2})).unwrap();
+        assert_eq!(heuristic_score(&max_latency, &span(json!(""))), 1.0);
+
+        assert!(parse_evaluator(&json!({"type": "regex", "pattern": "("})).is_err());
+    }
+}
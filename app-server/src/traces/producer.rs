@@ -4,12 +4,14 @@
 use std::sync::Arc;
 
 use anyhow::Result;
+use chrono::Utc;
 use lapin::{options::BasicPublishOptions, BasicProperties, Connection};
 use uuid::Uuid;
 
 use crate::{
     api::v1::traces::RabbitMqSpanMessage,
     cache::Cache,
+    chaos,
     db::{events::EventObservation, spans::Span, utils::convert_any_value_to_json_value, DB},
     features::{is_feature_enabled, Feature},
     opentelemetry::opentelemetry::proto::collector::trace::v1::{
@@ -18,23 +20,48 @@ use crate::{
 };
 
 use super::{
-    span_attributes::EVENT_TYPE, utils::record_span_to_db, OBSERVATIONS_EXCHANGE,
-    OBSERVATIONS_ROUTING_KEY,
+    client_metadata::{collects_client_region, ClientMetadata},
+    ingestion_lag,
+    sampling::SpanSampler,
+    span_attributes::EVENT_TYPE,
+    utils::record_span_to_db,
+    OBSERVATIONS_EXCHANGE, OBSERVATIONS_ROUTING_KEY,
 };
 
+/// `client_region` is the country code from the edge proxy's geo header, it is only kept if
+/// the project collects client regions, see `traces::client_metadata`. `debug_flag` is the value
+/// of the debug flag header, spans are sampled as in `traces::sampling`.
 // TODO: Implement partial_success
 pub async fn push_spans_to_queue(
     request: ExportTraceServiceRequest,
     project_id: Uuid,
+    client_region: Option<String>,
+    debug_flag: Option<String>,
     rabbitmq_connection: Option<Arc<Connection>>,
     db: Arc<DB>,
     cache: Arc<Cache>,
 ) -> Result<ExportTraceServiceResponse> {
+    let client_region = match client_region {
+        Some(region) if collects_client_region(db.clone(), cache.clone(), project_id).await? => {
+            Some(region)
+        }
+        _ => None,
+    };
+    let sampler =
+        SpanSampler::for_project(db.clone(), cache.clone(), project_id, debug_flag).await?;
+
     if !is_feature_enabled(Feature::FullBuild) {
         for resource_span in request.resource_spans {
+            let mut client_metadata =
+                ClientMetadata::from_resource(resource_span.resource.as_ref());
+            client_metadata.region = client_region.clone();
             for scope_span in resource_span.scope_spans {
                 for otel_span in scope_span.spans {
                     let mut span = Span::from_otel_span(otel_span.clone());
+                    if !sampler.keep(&span) {
+                        continue;
+                    }
+                    client_metadata.apply(&mut span);
 
                     let span_usage = super::utils::get_llm_usage_for_span(
                         &mut span.get_attributes(),
@@ -64,9 +91,15 @@ pub async fn push_spans_to_queue(
     let channel = rabbitmq_connection.unwrap().create_channel().await?;
 
     for resource_span in request.resource_spans {
+        let mut client_metadata = ClientMetadata::from_resource(resource_span.resource.as_ref());
+        client_metadata.region = client_region.clone();
         for scope_span in resource_span.scope_spans {
             for otel_span in scope_span.spans {
-                let span = Span::from_otel_span(otel_span.clone());
+                let mut span = Span::from_otel_span(otel_span.clone());
+                if !sampler.keep(&span) {
+                    continue;
+                }
+                client_metadata.apply(&mut span);
 
                 let mut events = vec![];
 
@@ -81,7 +114,11 @@ pub async fn push_spans_to_queue(
                     let Some(serde_json::Value::String(event_type)) =
                         event_attributes.get(EVENT_TYPE)
                     else {
-                        if event.name != "llm.content.completion.chunk" {
+                        // messages of the OpenTelemetry conventions are parsed to the span's
+                        // input and output
+                        if event.name != "llm.content.completion.chunk"
+                            && !event.name.starts_with("gen_ai.")
+                        {
                             log::warn!("Unknown event type: {:?}", event);
                         }
                         continue;
@@ -98,21 +135,38 @@ pub async fn push_spans_to_queue(
                     project_id,
                     span,
                     events,
+                    accepted_at: Some(Utc::now()),
                 };
 
+                if chaos::drop_queue_message() {
+                    continue;
+                }
+
                 let payload = serde_json::to_string(&rabbitmq_span_message).unwrap();
                 let payload = payload.as_bytes();
 
-                channel
-                    .basic_publish(
-                        OBSERVATIONS_EXCHANGE,
-                        OBSERVATIONS_ROUTING_KEY,
-                        BasicPublishOptions::default(),
-                        payload,
-                        BasicProperties::default(),
-                    )
-                    .await?
-                    .await?;
+                ingestion_lag::record_accepted(project_id, &rabbitmq_span_message.span);
+                let published = async {
+                    channel
+                        .basic_publish(
+                            OBSERVATIONS_EXCHANGE,
+                            OBSERVATIONS_ROUTING_KEY,
+                            BasicPublishOptions::default(),
+                            payload,
+                            BasicProperties::default(),
+                        )
+                        .await?
+                        .await
+                }
+                .await;
+                if let Err(e) = published {
+                    ingestion_lag::record_discarded(
+                        project_id,
+                        &rabbitmq_span_message.span,
+                        rabbitmq_span_message.accepted_at,
+                    );
+                    return Err(e.into());
+                }
             }
         }
     }
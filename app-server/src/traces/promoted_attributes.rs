@@ -0,0 +1,210 @@
+//! Promoted attributes: per-project span attributes that are copied to dedicated ClickHouse
+//! columns, so that analytics queries can filter on them without parsing every span's attributes.
+//!
+//! The spans table has `MAX_PROMOTED_ATTRIBUTES` string columns `attribute_<slot>` with bloom
+//! filter indexes, and each promoted attribute of a project takes one slot. New spans get the
+//! values at ingestion. Spans ingested before the attribute was promoted are backfilled from
+//! Postgres in the background, in small mutations one at a time, and a slot is reset when its
+//! attribute is removed.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    cache::Cache,
+    ch::spans::CHSpan,
+    db::{
+        self,
+        promoted_attributes::{BackfillStatus, PromotedAttribute},
+        DB,
+    },
+};
+
+use super::{spans::SpanAttributes, utils::json_value_to_string};
+
+const BACKFILL_BATCH_SIZE: i64 = 1_000;
+/// Pause between batches, so that merges of the backfill's mutations don't starve ingestion
+const BACKFILL_BATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Promoted attributes of a project, cached by project id
+#[derive(Clone)]
+pub struct ProjectPromotedAttributes {
+    pub attributes: Vec<PromotedAttribute>,
+}
+
+pub async fn get_promoted_attributes(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+) -> Result<Vec<PromotedAttribute>> {
+    let cache_res = cache
+        .get::<ProjectPromotedAttributes>(&project_id.to_string())
+        .await;
+    match cache_res {
+        Ok(Some(promoted)) => Ok(promoted.attributes),
+        Ok(None) | Err(_) => {
+            let attributes =
+                db::promoted_attributes::get_promoted_attributes(&db.pool, &project_id).await?;
+            let _ = cache
+                .insert::<ProjectPromotedAttributes>(
+                    project_id.to_string(),
+                    &ProjectPromotedAttributes {
+                        attributes: attributes.clone(),
+                    },
+                )
+                .await;
+            Ok(attributes)
+        }
+    }
+}
+
+/// Drops the cached attributes after the project's promoted attributes changed
+pub async fn invalidate_promoted_attributes(cache: Arc<Cache>, project_id: Uuid) {
+    let _ = cache
+        .remove::<ProjectPromotedAttributes>(&project_id.to_string())
+        .await;
+}
+
+/// Promoted attribute keys with the slot of their column
+pub fn attribute_slots(attributes: &[PromotedAttribute]) -> HashMap<String, usize> {
+    attributes
+        .iter()
+        .map(|attribute| (attribute.attribute_key.clone(), attribute.slot as usize))
+        .collect()
+}
+
+pub fn apply_promoted_attributes(
+    ch_span: &mut CHSpan,
+    span_attributes: &SpanAttributes,
+    promoted: &[PromotedAttribute],
+) {
+    for attribute in promoted {
+        if let Some(value) = span_attributes.attributes.get(&attribute.attribute_key) {
+            ch_span.set_promoted_attribute(
+                attribute.slot as usize,
+                json_value_to_string(value.clone()),
+            );
+        }
+    }
+}
+
+/// Copies the attribute's values of the project's existing spans to its column
+pub async fn backfill_promoted_attribute(
+    db: Arc<DB>,
+    analytics_store: Arc<dyn AnalyticsStore>,
+    attribute: PromotedAttribute,
+) -> Result<()> {
+    let pool = &db.pool;
+    let slot = attribute.slot as usize;
+    let mut backfilled_spans = 0;
+    let mut after_span_id = None;
+
+    db::promoted_attributes::update_backfill_progress(
+        pool,
+        &attribute.id,
+        BackfillStatus::RUNNING,
+        backfilled_spans,
+    )
+    .await?;
+
+    let res: Result<()> = async {
+        loop {
+            let values = db::promoted_attributes::get_span_attribute_values(
+                pool,
+                &attribute.project_id,
+                &attribute.attribute_key,
+                after_span_id,
+                BACKFILL_BATCH_SIZE,
+            )
+            .await?;
+            let Some(last) = values.last() else {
+                return Ok(());
+            };
+            after_span_id = Some(last.span_id);
+            let batch_len = values.len() as i64;
+
+            analytics_store
+                .update_promoted_attribute_values(
+                    attribute.project_id,
+                    slot,
+                    values
+                        .into_iter()
+                        .map(|value| (value.span_id, json_value_to_string(value.value)))
+                        .collect(),
+                )
+                .await?;
+
+            backfilled_spans += batch_len;
+            db::promoted_attributes::update_backfill_progress(
+                pool,
+                &attribute.id,
+                BackfillStatus::RUNNING,
+                backfilled_spans,
+            )
+            .await?;
+            if batch_len < BACKFILL_BATCH_SIZE {
+                return Ok(());
+            }
+            tokio::time::sleep(BACKFILL_BATCH_INTERVAL).await;
+        }
+    }
+    .await;
+
+    let status = if res.is_ok() {
+        BackfillStatus::DONE
+    } else {
+        BackfillStatus::FAILED
+    };
+    db::promoted_attributes::update_backfill_progress(
+        pool,
+        &attribute.id,
+        status,
+        backfilled_spans,
+    )
+    .await?;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use crate::db::spans::Span;
+
+    use super::*;
+
+    #[test]
+    fn test_apply_promoted_attributes() {
+        let span = Span {
+            attributes: json!({ "customer_tier": "enterprise", "retries": 2 }),
+            ..Default::default()
+        };
+        let promoted = |key: &str, slot: i32| PromotedAttribute {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            project_id: Uuid::nil(),
+            attribute_key: key.to_string(),
+            slot,
+            backfill_status: BackfillStatus::DONE,
+            backfilled_spans: 0,
+        };
+        let mut ch_span = CHSpan::from_db_span(&span, Default::default(), Uuid::nil());
+        apply_promoted_attributes(
+            &mut ch_span,
+            &span.get_attributes(),
+            &[
+                promoted("customer_tier", 0),
+                promoted("retries", 3),
+                promoted("region", 1),
+            ],
+        );
+
+        assert_eq!(ch_span.attribute_0, "enterprise");
+        assert_eq!(ch_span.attribute_1, "<null>");
+        assert_eq!(ch_span.attribute_3, "2");
+    }
+}
@@ -0,0 +1,168 @@
+//! Ingestion protocol version negotiation.
+//!
+//! SDKs send the version of the span attribute conventions they were built for in the
+//! `x-lmnr-protocol-version` header, or gRPC metadata. Payloads of older versions are upgraded
+//! to the current conventions by the adapters below before they reach the producer, so that
+//! old SDKs keep working across server upgrades. Versions newer than the server's are rejected
+//! with an explicit error instead of silently dropping the fields that the server doesn't know.
+//!
+//! Requests without the header come from SDKs released before negotiation and are version 1.
+//! Every response has the header with the server's version.
+
+use crate::opentelemetry::{
+    opentelemetry::proto::collector::trace::v1::ExportTraceServiceRequest,
+    opentelemetry_proto_common_v1::KeyValue,
+};
+
+use super::span_attributes::{
+    GEN_AI_COMPLETION_TOKENS, GEN_AI_INPUT_TOKENS, GEN_AI_OUTPUT_TOKENS, GEN_AI_PROMPT_TOKENS,
+};
+
+pub const PROTOCOL_VERSION_HEADER: &str = "x-lmnr-protocol-version";
+pub const CURRENT_PROTOCOL_VERSION: u32 = 2;
+const DEFAULT_PROTOCOL_VERSION: u32 = 1;
+
+/// Span attributes renamed by each version. Payloads of older versions have them renamed to
+/// the new names, unless the span already has the new one.
+const ATTRIBUTE_RENAMES: [(u32, &[(&str, &str)]); 1] = [(
+    2,
+    &[
+        (GEN_AI_PROMPT_TOKENS, GEN_AI_INPUT_TOKENS),
+        (GEN_AI_COMPLETION_TOKENS, GEN_AI_OUTPUT_TOKENS),
+    ],
+)];
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ProtocolVersionError {
+    #[error("Invalid protocol version \"{0}\", expected a positive integer")]
+    Invalid(String),
+    #[error(
+        "Protocol version {0} is not supported, the latest supported version is {CURRENT_PROTOCOL_VERSION}. Upgrade the server or downgrade the SDK."
+    )]
+    Unsupported(u32),
+}
+
+/// Protocol version of the request from the value of its version header
+pub fn negotiate_protocol_version(header: Option<&str>) -> Result<u32, ProtocolVersionError> {
+    let Some(header) = header else {
+        return Ok(DEFAULT_PROTOCOL_VERSION);
+    };
+    let version = header
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|version| *version > 0)
+        .ok_or_else(|| ProtocolVersionError::Invalid(header.to_string()))?;
+    if version > CURRENT_PROTOCOL_VERSION {
+        return Err(ProtocolVersionError::Unsupported(version));
+    }
+    Ok(version)
+}
+
+fn rename_attributes(attributes: &mut [KeyValue], renames: &[(&str, &str)]) {
+    for (old_key, new_key) in renames {
+        if attributes.iter().any(|attribute| attribute.key == *new_key) {
+            continue;
+        }
+        if let Some(attribute) = attributes
+            .iter_mut()
+            .find(|attribute| attribute.key == *old_key)
+        {
+            attribute.key = new_key.to_string();
+        }
+    }
+}
+
+/// Upgrades the payload of a request of the given version to the current version
+pub fn adapt_request(request: &mut ExportTraceServiceRequest, version: u32) {
+    for (introduced_in, renames) in ATTRIBUTE_RENAMES {
+        if version >= introduced_in {
+            continue;
+        }
+        for resource_span in request.resource_spans.iter_mut() {
+            for scope_span in resource_span.scope_spans.iter_mut() {
+                for span in scope_span.spans.iter_mut() {
+                    rename_attributes(&mut span.attributes, renames);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opentelemetry::{
+        opentelemetry_proto_common_v1::{any_value, AnyValue},
+        opentelemetry_proto_trace_v1::{ResourceSpans, ScopeSpans, Span as OtelSpan},
+    };
+
+    use super::*;
+
+    fn int_attribute(key: &str, value: i64) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::IntValue(value)),
+            }),
+        }
+    }
+
+    fn request(attributes: Vec<KeyValue>) -> ExportTraceServiceRequest {
+        ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![OtelSpan {
+                        attributes,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn keys(request: &ExportTraceServiceRequest) -> Vec<&str> {
+        request.resource_spans[0].scope_spans[0].spans[0]
+            .attributes
+            .iter()
+            .map(|attribute| attribute.key.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_negotiate_protocol_version() {
+        assert_eq!(negotiate_protocol_version(None), Ok(1));
+        assert_eq!(negotiate_protocol_version(Some(" 2 ")), Ok(2));
+        assert_eq!(
+            negotiate_protocol_version(Some("3")),
+            Err(ProtocolVersionError::Unsupported(3))
+        );
+        assert_eq!(
+            negotiate_protocol_version(Some("0")),
+            Err(ProtocolVersionError::Invalid("0".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_adapt_request() {
+        let mut v1 = request(vec![
+            int_attribute(GEN_AI_PROMPT_TOKENS, 10),
+            int_attribute(GEN_AI_COMPLETION_TOKENS, 5),
+            int_attribute(GEN_AI_OUTPUT_TOKENS, 7),
+        ]);
+        adapt_request(&mut v1, 1);
+        assert_eq!(
+            keys(&v1),
+            vec![
+                GEN_AI_INPUT_TOKENS,
+                GEN_AI_COMPLETION_TOKENS,
+                GEN_AI_OUTPUT_TOKENS
+            ]
+        );
+
+        let mut v2 = request(vec![int_attribute(GEN_AI_PROMPT_TOKENS, 10)]);
+        adapt_request(&mut v2, 2);
+        assert_eq!(keys(&v2), vec![GEN_AI_PROMPT_TOKENS]);
+    }
+}
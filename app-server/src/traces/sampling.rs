@@ -0,0 +1,184 @@
+//! Head sampling of ingested spans. Projects keep `span_sample_rate` of their traces, and define
+//! exemption rules for spans that are always kept, e.g. of users being supported or of a pipeline
+//! under debugging. Exemptions are checked before the sampling decision.
+//!
+//! Sampling is deterministic by trace id, so that the spans of a trace are kept or dropped
+//! together, also across export requests. Exemptions match single spans, they keep whole traces
+//! when the matched attributes are on all their spans, as association properties and paths are.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    db::{
+        self,
+        sampling_exemptions::{SamplingExemption, SamplingExemptionKind},
+        spans::Span,
+        DB,
+    },
+};
+
+/// Header, or gRPC metadata, of export requests with spans to keep, if its value is in a
+/// `DEBUG_FLAG` exemption of the project
+pub const DEBUG_FLAG_HEADER: &str = "x-lmnr-debug";
+
+/// Sampling settings of a project, cached by project id
+#[derive(Clone)]
+pub struct SamplingSettings {
+    pub sample_rate: f64,
+    pub exemptions: Vec<SamplingExemption>,
+}
+
+pub async fn get_sampling_settings(
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+) -> Result<SamplingSettings> {
+    let cache_res = cache.get::<SamplingSettings>(&project_id.to_string()).await;
+    match cache_res {
+        Ok(Some(settings)) => Ok(settings),
+        Ok(None) | Err(_) => {
+            let sample_rate = db::projects::get_span_sample_rate(&db.pool, &project_id).await?;
+            let exemptions =
+                db::sampling_exemptions::get_sampling_exemptions(&db.pool, &project_id).await?;
+            let settings = SamplingSettings {
+                sample_rate,
+                exemptions,
+            };
+            let _ = cache
+                .insert::<SamplingSettings>(project_id.to_string(), &settings)
+                .await;
+            Ok(settings)
+        }
+    }
+}
+
+/// Drops the cached settings after the project's sample rate or exemptions changed
+pub async fn invalidate_sampling_settings(cache: Arc<Cache>, project_id: Uuid) {
+    let _ = cache
+        .remove::<SamplingSettings>(&project_id.to_string())
+        .await;
+}
+
+/// Whether the trace is in the sampled share of traces
+pub fn sample_trace(trace_id: &Uuid, sample_rate: f64) -> bool {
+    if sample_rate >= 1.0 {
+        return true;
+    }
+    let bucket = (trace_id.as_u128() % 10_000) as f64;
+    bucket < sample_rate * 10_000.0
+}
+
+/// Sampler of the spans of one export request
+pub struct SpanSampler {
+    settings: SamplingSettings,
+    debug_flag: Option<String>,
+}
+
+impl SpanSampler {
+    pub async fn for_project(
+        db: Arc<DB>,
+        cache: Arc<Cache>,
+        project_id: Uuid,
+        debug_flag: Option<String>,
+    ) -> Result<Self> {
+        let settings = get_sampling_settings(db, cache, project_id).await?;
+        Ok(Self {
+            settings,
+            debug_flag,
+        })
+    }
+
+    pub fn keep(&self, span: &Span) -> bool {
+        self.settings.sample_rate >= 1.0
+            || self.is_exempt(span)
+            || sample_trace(&span.trace_id, self.settings.sample_rate)
+    }
+
+    fn is_exempt(&self, span: &Span) -> bool {
+        if self.settings.exemptions.is_empty() {
+            return false;
+        }
+        let attributes = span.get_attributes();
+        let user_id = attributes.user_id();
+        let path = attributes.path();
+        self.settings
+            .exemptions
+            .iter()
+            .any(|exemption| match exemption.kind {
+                SamplingExemptionKind::USER_ID => user_id.as_ref() == Some(&exemption.value),
+                SamplingExemptionKind::SPAN_PATH => path.as_ref().is_some_and(|path| {
+                    path == &exemption.value
+                        || path
+                            .strip_prefix(exemption.value.as_str())
+                            .is_some_and(|rest| rest.starts_with('.'))
+                }),
+                SamplingExemptionKind::DEBUG_FLAG => {
+                    self.debug_flag.as_ref() == Some(&exemption.value)
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use super::*;
+
+    fn exemption(kind: SamplingExemptionKind, value: &str) -> SamplingExemption {
+        SamplingExemption {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            project_id: Uuid::nil(),
+            kind,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_exemptions_bypass_sampling() {
+        let sampler = SpanSampler {
+            settings: SamplingSettings {
+                sample_rate: 0.0,
+                exemptions: vec![
+                    exemption(SamplingExemptionKind::USER_ID, "user-1"),
+                    exemption(SamplingExemptionKind::SPAN_PATH, "pipeline"),
+                ],
+            },
+            debug_flag: None,
+        };
+        let span = |attributes| Span {
+            attributes,
+            ..Default::default()
+        };
+
+        assert!(sampler.keep(&span(json!({
+            "lmnr.association.properties.user_id": "user-1",
+        }))));
+        assert!(sampler.keep(&span(json!({"lmnr.span.path": "pipeline.llm"}))));
+        assert!(!sampler.keep(&span(json!({"lmnr.span.path": "pipeline_v2"}))));
+        assert!(!sampler.keep(&span(json!({}))));
+
+        let sampler = SpanSampler {
+            settings: SamplingSettings {
+                sample_rate: 0.0,
+                exemptions: vec![exemption(SamplingExemptionKind::DEBUG_FLAG, "on")],
+            },
+            debug_flag: Some("on".to_string()),
+        };
+        assert!(sampler.keep(&span(json!({}))));
+    }
+
+    #[test]
+    fn test_sample_trace() {
+        let trace_id = Uuid::new_v4();
+        assert!(sample_trace(&trace_id, 1.0));
+        assert!(!sample_trace(&trace_id, 0.0));
+        assert_eq!(sample_trace(&trace_id, 0.5), sample_trace(&trace_id, 0.5));
+    }
+}
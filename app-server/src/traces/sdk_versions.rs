@@ -0,0 +1,251 @@
+//! SDK version tracking at ingestion. Every ingestion request is counted by the name, language
+//! and version of the OpenTelemetry SDK that exported it, so that maintainers can see how many
+//! clients still run old versions before a breaking protocol change.
+//!
+//! Deprecated versions are configured in `DEPRECATED_SDK_VERSIONS` as the minimum supported
+//! version of each SDK language, e.g. `python<0.5.0,javascript<0.4.0`. Requests from older
+//! versions are still accepted, but get a `Warning` header, or `warning` metadata over gRPC,
+//! that the SDK can surface to the user.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use crate::{
+    metrics::{self, SdkKey},
+    opentelemetry::opentelemetry::proto::collector::trace::v1::ExportTraceServiceRequest,
+};
+
+use super::client_metadata::resource_attribute;
+
+const UNKNOWN: &str = "unknown";
+
+lazy_static! {
+    static ref MIN_SUPPORTED_VERSIONS: HashMap<String, String> =
+        std::env::var("DEPRECATED_SDK_VERSIONS")
+            .map(|value| parse_min_supported_versions(&value))
+            .unwrap_or_default();
+}
+
+/// SDK language to minimum supported version
+fn parse_min_supported_versions(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|rule| {
+            let (language, version) = rule.split_once('<')?;
+            let (language, version) = (language.trim().to_lowercase(), version.trim());
+            (!language.is_empty() && !version.is_empty()).then(|| (language, version.to_string()))
+        })
+        .collect()
+}
+
+/// Numeric components of a version, e.g. `[1, 2, 0]` for `v1.2.0-beta`
+fn version_components(version: &str) -> Vec<u64> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map_while(|component| component.parse().ok())
+        .collect()
+}
+
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a, b) = (version_components(a), version_components(b));
+    for i in 0..a.len().max(b.len()) {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Minimum supported version of the SDK, if its version is older than that
+fn deprecated_in_favor_of<'a>(
+    min_supported_versions: &'a HashMap<String, String>,
+    sdk: &SdkKey,
+) -> Option<&'a str> {
+    if version_components(&sdk.version).is_empty() {
+        return None;
+    }
+    min_supported_versions
+        .get(&sdk.language)
+        .filter(|min_version| compare_versions(&sdk.version, min_version) == Ordering::Less)
+        .map(|min_version| min_version.as_str())
+}
+
+/// SDK of the first resource that identifies it. Requests come from a single exporter, so all
+/// resources usually have the same SDK.
+pub fn sdk_from_request(request: &ExportTraceServiceRequest) -> SdkKey {
+    let sdk = request.resource_spans.iter().find_map(|resource_span| {
+        let resource = resource_span.resource.as_ref()?;
+        let language = resource_attribute(resource, &["telemetry.sdk.language"])?;
+        Some(SdkKey {
+            name: resource_attribute(resource, &["telemetry.sdk.name"])
+                .unwrap_or_else(|| UNKNOWN.to_string()),
+            language: language.to_lowercase(),
+            version: resource_attribute(resource, &["telemetry.sdk.version"])
+                .unwrap_or_else(|| UNKNOWN.to_string()),
+        })
+    });
+    sdk.unwrap_or(SdkKey {
+        name: UNKNOWN.to_string(),
+        language: UNKNOWN.to_string(),
+        version: UNKNOWN.to_string(),
+    })
+}
+
+/// Counts the request by its SDK and returns a deprecation warning if the SDK version is deprecated
+pub fn track_sdk_version(request: &ExportTraceServiceRequest) -> Option<String> {
+    let sdk = sdk_from_request(request);
+    let warning = deprecated_in_favor_of(&MIN_SUPPORTED_VERSIONS, &sdk).map(|min_version| {
+        format!(
+            "299 lmnr \"{} SDK {} is deprecated, upgrade to {} or later\"",
+            sdk.language, sdk.version, min_version
+        )
+    });
+    metrics::record_sdk_request(sdk);
+    warning
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdkVersionAdoption {
+    pub name: String,
+    pub version: String,
+    pub requests: u64,
+    /// Share of the language's requests, between 0 and 1
+    pub share: f64,
+    pub deprecated: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdkLanguageAdoption {
+    pub language: String,
+    pub requests: u64,
+    pub min_supported_version: Option<String>,
+    /// Share of the language's requests from deprecated versions, between 0 and 1
+    pub deprecated_share: f64,
+    /// Most used versions first
+    pub versions: Vec<SdkVersionAdoption>,
+}
+
+fn adoption_breakdown(
+    requests: Vec<(SdkKey, u64)>,
+    min_supported_versions: &HashMap<String, String>,
+) -> Vec<SdkLanguageAdoption> {
+    let mut by_language = HashMap::<String, Vec<(SdkKey, u64)>>::new();
+    for (sdk, count) in requests {
+        by_language
+            .entry(sdk.language.clone())
+            .or_default()
+            .push((sdk, count));
+    }
+
+    let mut res = by_language
+        .into_iter()
+        .map(|(language, sdks)| {
+            let total = sdks.iter().map(|(_, count)| count).sum::<u64>();
+            let share = |count: u64| {
+                if total == 0 {
+                    0.0
+                } else {
+                    count as f64 / total as f64
+                }
+            };
+            let mut versions = sdks
+                .into_iter()
+                .map(|(sdk, requests)| SdkVersionAdoption {
+                    deprecated: deprecated_in_favor_of(min_supported_versions, &sdk).is_some(),
+                    share: share(requests),
+                    name: sdk.name,
+                    version: sdk.version,
+                    requests,
+                })
+                .collect::<Vec<_>>();
+            versions.sort_by(|a, b| b.requests.cmp(&a.requests));
+            let deprecated_requests = versions
+                .iter()
+                .filter(|version| version.deprecated)
+                .map(|version| version.requests)
+                .sum();
+
+            SdkLanguageAdoption {
+                min_supported_version: min_supported_versions.get(&language).cloned(),
+                deprecated_share: share(deprecated_requests),
+                language,
+                requests: total,
+                versions,
+            }
+        })
+        .collect::<Vec<_>>();
+    res.sort_by(|a, b| b.requests.cmp(&a.requests));
+    res
+}
+
+/// SDK adoption of the ingestion requests since the process started
+pub fn get_sdk_adoption() -> Vec<SdkLanguageAdoption> {
+    adoption_breakdown(metrics::sdk_requests(), &MIN_SUPPORTED_VERSIONS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sdk(language: &str, version: &str) -> SdkKey {
+        SdkKey {
+            name: "opentelemetry".to_string(),
+            language: language.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_deprecated_in_favor_of() {
+        let min_versions = parse_min_supported_versions("python<0.5.0, JavaScript < 0.4");
+        assert_eq!(
+            deprecated_in_favor_of(&min_versions, &sdk("python", "0.4.12")),
+            Some("0.5.0")
+        );
+        assert_eq!(
+            deprecated_in_favor_of(&min_versions, &sdk("python", "v0.5.0-beta")),
+            None
+        );
+        assert_eq!(
+            deprecated_in_favor_of(&min_versions, &sdk("javascript", "0.3.9")),
+            Some("0.4")
+        );
+        assert_eq!(
+            deprecated_in_favor_of(&min_versions, &sdk("python", UNKNOWN)),
+            None
+        );
+        assert_eq!(
+            deprecated_in_favor_of(&min_versions, &sdk("go", "0.1.0")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_adoption_breakdown() {
+        let min_versions = parse_min_supported_versions("python<0.5.0");
+        let adoption = adoption_breakdown(
+            vec![
+                (sdk("python", "0.4.0"), 25),
+                (sdk("python", "0.5.1"), 75),
+                (sdk("javascript", "0.3.0"), 10),
+            ],
+            &min_versions,
+        );
+        assert_eq!(adoption[0].language, "python");
+        assert_eq!(adoption[0].requests, 100);
+        assert_eq!(adoption[0].deprecated_share, 0.25);
+        assert_eq!(adoption[0].versions[0].version, "0.5.1");
+        assert!(adoption[0].versions[1].deprecated);
+        assert_eq!(adoption[1].min_supported_version, None);
+    }
+}
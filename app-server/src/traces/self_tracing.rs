@@ -0,0 +1,178 @@
+//! Self-instrumentation: the app-server records spans for its own HTTP requests and ClickHouse
+//! queries into a designated project, `SELF_TRACING_PROJECT_ID`, through the regular ingestion
+//! pipeline.
+//!
+//! The trace id of a request is its request id, so a trace can be found from a log line and
+//! vice versa. Only work done within a request is traced, so that processing of the self-traced
+//! spans does not produce more spans.
+
+use std::sync::{Arc, OnceLock};
+
+use chrono::{DateTime, Utc};
+use lapin::{options::BasicPublishOptions, BasicProperties, Connection};
+use serde_json::Value;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+    api::v1::traces::RabbitMqSpanMessage,
+    db::{
+        spans::{Span, SpanType},
+        trace::DEFAULT_VERSION,
+        DB,
+    },
+    features::{is_feature_enabled, Feature},
+    logging::current_request_id,
+};
+
+use super::{
+    spans::SpanUsage, utils::record_span_to_db, OBSERVATIONS_EXCHANGE, OBSERVATIONS_ROUTING_KEY,
+};
+
+/// Spans are dropped, rather than slowing down requests, if the exporter falls behind
+const CHANNEL_CAPACITY: usize = 10000;
+
+static SPAN_SENDER: OnceLock<mpsc::Sender<Span>> = OnceLock::new();
+
+pub fn self_tracing_project_id() -> Option<Uuid> {
+    std::env::var("SELF_TRACING_PROJECT_ID")
+        .ok()
+        .and_then(|id| Uuid::parse_str(&id).ok())
+}
+
+/// Start the background exporter. Does nothing unless self-tracing is enabled.
+pub fn init(rabbitmq_connection: Option<Arc<Connection>>, db: Arc<DB>) {
+    if !is_feature_enabled(Feature::SelfTracing) {
+        return;
+    }
+    let Some(project_id) = self_tracing_project_id() else {
+        log::error!("SELF_TRACING_PROJECT_ID is not a valid UUID, self-tracing is disabled");
+        return;
+    };
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    if SPAN_SENDER.set(tx).is_err() {
+        return;
+    }
+    tokio::spawn(export_spans(rx, project_id, rabbitmq_connection, db));
+    log::info!("Self-tracing enabled into project {}", project_id);
+}
+
+async fn export_spans(
+    mut rx: mpsc::Receiver<Span>,
+    project_id: Uuid,
+    rabbitmq_connection: Option<Arc<Connection>>,
+    db: Arc<DB>,
+) {
+    let channel = match &rabbitmq_connection {
+        Some(connection) if is_feature_enabled(Feature::FullBuild) => {
+            match connection.create_channel().await {
+                Ok(channel) => Some(channel),
+                Err(e) => {
+                    log::error!("Failed to create channel for self-tracing: {:?}", e);
+                    return;
+                }
+            }
+        }
+        _ => None,
+    };
+
+    while let Some(mut span) = rx.recv().await {
+        let res = match &channel {
+            Some(channel) => {
+                let message = RabbitMqSpanMessage {
+                    project_id,
+                    span,
+                    events: vec![],
+                    accepted_at: None,
+                };
+                let payload = serde_json::to_vec(&message).unwrap_or_default();
+                match channel
+                    .basic_publish(
+                        OBSERVATIONS_EXCHANGE,
+                        OBSERVATIONS_ROUTING_KEY,
+                        BasicPublishOptions::default(),
+                        &payload,
+                        BasicProperties::default(),
+                    )
+                    .await
+                {
+                    Ok(confirm) => confirm.await.map(|_| ()).map_err(anyhow::Error::from),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            None => {
+                record_span_to_db(db.clone(), &SpanUsage::default(), &project_id, &mut span).await
+            }
+        };
+        if let Err(e) = res {
+            // not using error level, to avoid noise if the system project is misconfigured
+            log::warn!("Failed to export self-tracing span: {:?}", e);
+        }
+    }
+}
+
+fn send(span: Span) {
+    let Some(sender) = SPAN_SENDER.get() else {
+        return;
+    };
+    // Full channel or closed exporter only means lost telemetry
+    let _ = sender.try_send(span);
+}
+
+/// Record the root span of an HTTP request. The span id is the request id.
+pub fn record_request_span(
+    request_id: Uuid,
+    name: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    attributes: Value,
+) {
+    if SPAN_SENDER.get().is_none() {
+        return;
+    }
+    send(Span {
+        version: String::from(DEFAULT_VERSION),
+        span_id: request_id,
+        trace_id: request_id,
+        parent_span_id: None,
+        name,
+        attributes,
+        span_type: SpanType::DEFAULT,
+        start_time,
+        end_time,
+        ..Default::default()
+    });
+}
+
+/// Record a ClickHouse query as a child of the current request span, if any
+pub fn record_clickhouse_query_span(
+    query: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    rows: Option<usize>,
+    error: Option<String>,
+) {
+    if SPAN_SENDER.get().is_none() {
+        return;
+    }
+    let Some(request_id) = current_request_id() else {
+        return;
+    };
+    send(Span {
+        version: String::from(DEFAULT_VERSION),
+        span_id: Uuid::new_v4(),
+        trace_id: request_id,
+        parent_span_id: Some(request_id),
+        name: "clickhouse.query".to_string(),
+        attributes: serde_json::json!({
+            "db.system": "clickhouse",
+            "db.rows": rows,
+            "error": error,
+        }),
+        input: Some(Value::String(query.to_string())),
+        span_type: SpanType::DEFAULT,
+        start_time,
+        end_time,
+        ..Default::default()
+    });
+}
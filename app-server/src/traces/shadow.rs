@@ -0,0 +1,72 @@
+//! This module processes a sample of ingested spans a second time with a candidate
+//! version of the processing logic, and writes the results to `spans_shadow`.
+//!
+//! Risky processing changes go into `process_span_shadow` first, so that they can be
+//! validated against real traffic by comparing `spans_shadow` with `spans`, before cutover.
+
+use std::{env, sync::Arc};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{
+    analytics::AnalyticsStore,
+    cache::Cache,
+    ch::spans::CHSpan,
+    db::{spans::Span, DB},
+};
+
+/// Percentage of spans, from 0 to 100, to additionally process in shadow mode
+pub fn shadow_percentage() -> f64 {
+    env::var("INGESTION_SHADOW_PERCENTAGE")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(0.0)
+        .clamp(0.0, 100.0)
+}
+
+/// Sampling is deterministic by span id, so that retried deliveries of the same span
+/// are either always or never shadowed.
+pub fn should_shadow(span_id: &Uuid, percentage: f64) -> bool {
+    if percentage <= 0.0 {
+        return false;
+    }
+    let bucket = (span_id.as_u128() % 10_000) as f64;
+    bucket < percentage * 100.0
+}
+
+/// Candidate version of span processing. Currently mirrors the primary path.
+async fn process_span_shadow(
+    span: &Span,
+    project_id: Uuid,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+) -> CHSpan {
+    let span_usage =
+        super::utils::get_llm_usage_for_span(&mut span.get_attributes(), db, cache).await;
+    CHSpan::from_db_span(span, span_usage, project_id)
+}
+
+pub async fn shadow_span(
+    analytics_store: Arc<dyn AnalyticsStore>,
+    span: Span,
+    project_id: Uuid,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+) -> Result<()> {
+    let ch_span = process_span_shadow(&span, project_id, db, cache).await;
+    analytics_store.insert_shadow_span(&ch_span).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_shadow_bounds() {
+        let span_id = Uuid::new_v4();
+        assert!(!should_shadow(&span_id, 0.0));
+        assert!(should_shadow(&span_id, 100.0));
+        assert!(should_shadow(&Uuid::nil(), 0.01));
+    }
+}
@@ -0,0 +1,262 @@
+//! Shadow deployments re-execute a sample of production LLM requests with a candidate model
+//! and/or system prompt, and a judge model compares the candidate's output with the production
+//! output. Unlike `traces::shadow`, which validates ingestion changes, this compares what the
+//! project's application would answer with the candidate.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    cache::Cache,
+    db::{
+        self,
+        shadow_deployments::{ShadowDeployment, ShadowVerdict},
+        spans::{Span, SpanType},
+        DB,
+    },
+    language_model::{ChatMessage, ChatMessageContent, LanguageModelRunner, NodeInfo},
+    pipeline::utils::render_chat_message_list,
+    traces::{evaluators::get_stored_env, shadow::should_shadow, utils::json_value_to_string},
+};
+
+const DEFAULT_JUDGE_MODEL: &str = "openai:gpt-4o-mini";
+
+const JUDGE_PROMPT: &str = r#"You compare two responses of language models to the same request. Decide which response is better: more correct, more helpful and following the request's instructions more closely. Length and style alone don't make a response better. Answer with JSON only:
+{"reasoning": "<why>", "winner": "A" | "B" | "tie"}"#;
+
+#[derive(Deserialize)]
+struct Judgement {
+    reasoning: String,
+    winner: String,
+}
+
+/// Model in the format of "provider:model_name", set with `SHADOW_JUDGE_MODEL`
+fn judge_model() -> String {
+    std::env::var("SHADOW_JUDGE_MODEL").unwrap_or(DEFAULT_JUDGE_MODEL.to_string())
+}
+
+/// Production request with the system message replaced by `system_prompt`, if set
+fn candidate_messages(
+    mut messages: Vec<ChatMessage>,
+    system_prompt: Option<&str>,
+) -> Vec<ChatMessage> {
+    if let Some(system_prompt) = system_prompt {
+        messages.retain(|message| message.role != "system");
+        messages.insert(
+            0,
+            ChatMessage {
+                role: "system".to_string(),
+                content: ChatMessageContent::Text(system_prompt.to_string()),
+            },
+        );
+    }
+    messages
+}
+
+/// The judge sees the candidate as response A or B depending on `candidate_first`, so that a
+/// preference for either position doesn't favor one side
+fn parse_judgement(text: &str, candidate_first: bool) -> Result<(ShadowVerdict, String)> {
+    let text = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+    let judgement = serde_json::from_str::<Judgement>(text)?;
+
+    let verdict = match (judgement.winner.to_lowercase().as_str(), candidate_first) {
+        ("a", true) | ("b", false) => ShadowVerdict::CANDIDATE,
+        ("a", false) | ("b", true) => ShadowVerdict::PRODUCTION,
+        ("tie", _) => ShadowVerdict::TIE,
+        (winner, _) => return Err(anyhow::anyhow!("Unknown winner: {}", winner)),
+    };
+    Ok((verdict, judgement.reasoning))
+}
+
+async fn complete(
+    language_model: &Arc<LanguageModelRunner>,
+    db: &Arc<DB>,
+    cache: &Arc<Cache>,
+    env: &HashMap<String, String>,
+    model: &str,
+    messages: Vec<ChatMessage>,
+) -> Result<String> {
+    let node_info = NodeInfo {
+        id: Uuid::new_v4(),
+        node_id: Uuid::nil(),
+        node_name: "shadow_deployment".to_string(),
+        node_type: "LLM".to_string(),
+    };
+    let completion = language_model
+        .chat_completion(
+            model,
+            &messages,
+            &json!({}),
+            env,
+            None,
+            &node_info,
+            db.clone(),
+            cache.clone(),
+        )
+        .await?;
+
+    Ok(completion.text_message())
+}
+
+async fn compare(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    deployment: &ShadowDeployment,
+    span: &Span,
+) -> Result<()> {
+    let production_messages = span
+        .input
+        .clone()
+        .and_then(|input| serde_json::from_value::<Vec<ChatMessage>>(input).ok())
+        .filter(|messages| !messages.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Span input is not a list of chat messages"))?;
+    let messages = candidate_messages(
+        production_messages.clone(),
+        deployment.candidate_system_prompt.as_deref(),
+    );
+    let env = get_stored_env(db.clone(), deployment.project_id).await?;
+
+    let candidate_output = complete(
+        &language_model,
+        &db,
+        &cache,
+        &env,
+        &deployment.candidate_model,
+        messages,
+    )
+    .await?;
+    let production_output = json_value_to_string(span.output.clone().unwrap_or_default());
+
+    let candidate_first = span.span_id.as_u128() % 2 == 0;
+    let (response_a, response_b) = if candidate_first {
+        (&candidate_output, &production_output)
+    } else {
+        (&production_output, &candidate_output)
+    };
+    let judge_messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: ChatMessageContent::Text(JUDGE_PROMPT.to_string()),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: ChatMessageContent::Text(format!(
+                "<request>{}</request>\n\n<response_a>{}</response_a>\n\n<response_b>{}</response_b>",
+                render_chat_message_list(production_messages),
+                response_a,
+                response_b
+            )),
+        },
+    ];
+    let judgement = complete(
+        &language_model,
+        &db,
+        &cache,
+        &env,
+        &judge_model(),
+        judge_messages,
+    )
+    .await?;
+    let (verdict, reasoning) = parse_judgement(&judgement, candidate_first)?;
+
+    db::shadow_deployments::record_shadow_comparison(
+        &db.pool,
+        &deployment.id,
+        &span.span_id,
+        &span.trace_id,
+        &candidate_output,
+        verdict,
+        &reasoning,
+    )
+    .await
+}
+
+/// Starts comparisons for the active shadow deployments of the span's path that sample it.
+/// Comparisons run in the background, so that they don't slow down span processing.
+pub async fn shadow_deployments_for_span(
+    language_model: Arc<LanguageModelRunner>,
+    db: Arc<DB>,
+    cache: Arc<Cache>,
+    project_id: Uuid,
+    span: &Span,
+) -> Result<()> {
+    if span.span_type != SpanType::LLM {
+        return Ok(());
+    }
+    let Some(path) = span.get_attributes().path() else {
+        return Ok(());
+    };
+
+    let deployments = db::shadow_deployments::get_active_shadow_deployments_for_path(
+        &db.pool,
+        &project_id,
+        &path,
+    )
+    .await?;
+    for deployment in deployments {
+        if !should_shadow(&span.span_id, deployment.sample_percentage) {
+            continue;
+        }
+        let language_model = language_model.clone();
+        let db = db.clone();
+        let cache = cache.clone();
+        let span = span.clone();
+        tokio::spawn(async move {
+            if let Err(e) = compare(language_model, db, cache, &deployment, &span).await {
+                log::error!(
+                    "Failed to compare shadow deployment. deployment_id [{}], span_id [{}]: {:?}",
+                    deployment.id,
+                    span.span_id,
+                    e
+                );
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidate_messages() {
+        let input = serde_json::from_value::<Vec<ChatMessage>>(json!([
+            {"role": "system", "content": "Be brief."},
+            {"role": "user", "content": "What is the capital of France?"}
+        ]))
+        .unwrap();
+        let messages = candidate_messages(input, Some("Answer in French."));
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(
+            &messages[0].content,
+            ChatMessageContent::Text(text) if text == "Answer in French."
+        ));
+        assert_eq!(messages[1].role, "user");
+    }
+
+    #[test]
+    fn test_parse_judgement() {
+        let judgement = "{\"reasoning\": \"B is correct\", \"winner\": \"B\"}";
+        assert_eq!(
+            parse_judgement(judgement, true).unwrap().0,
+            ShadowVerdict::PRODUCTION
+        );
+        assert_eq!(
+            parse_judgement(judgement, false).unwrap().0,
+            ShadowVerdict::CANDIDATE
+        );
+        assert!(parse_judgement("{\"reasoning\": \"\", \"winner\": \"C\"}", true).is_err());
+    }
+}
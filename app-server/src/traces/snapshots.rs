@@ -0,0 +1,232 @@
+//! Screenshots and DOM snapshots taken by browser agents after each step, and the changes
+//! between consecutive steps.
+//!
+//! DOMs are compared tag by tag: the serialized DOM is split after each `>`, so that DOMs
+//! serialized on one line are diffed as precisely as pretty-printed ones.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{analytics::AnalyticsStore, ch::browser_snapshots::BrowserSnapshot, storage::Storage};
+
+/// Above this many compared pairs of tokens, the changed part of the DOM is reported as replaced
+/// instead of diffed
+const MAX_DIFF_CELLS: usize = 4_000_000;
+/// Changes returned for a step, the rest are only counted
+const MAX_DOM_CHANGES: usize = 1000;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotView {
+    pub id: Uuid,
+    pub trace_id: Uuid,
+    pub step: u32,
+    pub timestamp: DateTime<Utc>,
+    pub page_url: String,
+    pub screenshot_url: Option<String>,
+    pub dom_url: Option<String>,
+}
+
+impl From<BrowserSnapshot> for SnapshotView {
+    fn from(snapshot: BrowserSnapshot) -> Self {
+        Self {
+            id: snapshot.id,
+            trace_id: snapshot.trace_id,
+            step: snapshot.step,
+            timestamp: snapshot.timestamp,
+            page_url: snapshot.page_url,
+            screenshot_url: Some(snapshot.screenshot_url).filter(|url| !url.is_empty()),
+            dom_url: Some(snapshot.dom_url).filter(|url| !url.is_empty()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum DomChangeType {
+    Added,
+    Removed,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DomChange {
+    pub change_type: DomChangeType,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    /// None for the first step of the session
+    pub previous: Option<SnapshotView>,
+    pub current: SnapshotView,
+    pub page_url_changed: bool,
+    pub screenshot_changed: bool,
+    pub dom_changed: bool,
+    /// Added and removed parts of the DOM in document order, at most `MAX_DOM_CHANGES`
+    pub dom_changes: Vec<DomChange>,
+    pub dom_change_count: usize,
+}
+
+fn dom_tokens(dom: &str) -> Vec<&str> {
+    dom.split_inclusive('>')
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+/// Added and removed tokens of the DOM, in the order of the new DOM
+pub fn diff_dom(old: &str, new: &str) -> Vec<DomChange> {
+    let old = dom_tokens(old);
+    let new = dom_tokens(new);
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    let change = |change_type, content: &&str| DomChange {
+        change_type,
+        content: content.to_string(),
+    };
+    if (old.len() + 1) * (new.len() + 1) > MAX_DIFF_CELLS {
+        return old
+            .iter()
+            .map(|token| change(DomChangeType::Removed, token))
+            .chain(new.iter().map(|token| change(DomChangeType::Added, token)))
+            .collect();
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lcs = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            changes.push(change(DomChangeType::Removed, &old[i]));
+            i += 1;
+        } else {
+            changes.push(change(DomChangeType::Added, &new[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+async fn retrieve_dom(storage: &dyn Storage, snapshot: &BrowserSnapshot) -> Result<String> {
+    if snapshot.dom_url.is_empty() {
+        return Ok(String::new());
+    }
+    let dom = storage.retrieve(&snapshot.dom_url).await?;
+    Ok(String::from_utf8_lossy(&dom).into_owned())
+}
+
+/// Changes between the step's snapshot and the previous step's, None if the step has no snapshot.
+/// The DOMs are fetched only if their hashes differ.
+pub async fn diff_step(
+    analytics_store: Arc<dyn AnalyticsStore>,
+    storage: Arc<dyn Storage>,
+    project_id: Uuid,
+    session_id: String,
+    step: u32,
+) -> Result<Option<SnapshotDiff>> {
+    let mut snapshots = analytics_store
+        .get_browser_snapshots_at_step(project_id, session_id, step)
+        .await?
+        .into_iter();
+    let Some(current) = snapshots.next() else {
+        return Ok(None);
+    };
+    let previous = snapshots.next();
+
+    let (page_url_changed, screenshot_changed, dom_changed) = match &previous {
+        Some(previous) => (
+            previous.page_url != current.page_url,
+            previous.screenshot_hash != current.screenshot_hash,
+            previous.dom_hash != current.dom_hash,
+        ),
+        None => (true, true, true),
+    };
+    let mut dom_changes = if dom_changed {
+        let old = match &previous {
+            Some(previous) => retrieve_dom(storage.as_ref(), previous).await?,
+            None => String::new(),
+        };
+        let new = retrieve_dom(storage.as_ref(), &current).await?;
+        diff_dom(&old, &new)
+    } else {
+        Vec::new()
+    };
+    let dom_change_count = dom_changes.len();
+    dom_changes.truncate(MAX_DOM_CHANGES);
+
+    Ok(Some(SnapshotDiff {
+        previous: previous.map(Into::into),
+        current: current.into(),
+        page_url_changed,
+        screenshot_changed,
+        dom_changed,
+        dom_changes,
+        dom_change_count,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(old: &str, new: &str) -> Vec<(DomChangeType, String)> {
+        diff_dom(old, new)
+            .into_iter()
+            .map(|change| (change.change_type, change.content))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_dom() {
+        assert!(changes("<div><p>a</p></div>", "<div>\n  <p>a</p>\n</div>").is_empty());
+        assert_eq!(
+            changes(
+                "<ul><li>a</li><li>b</li></ul>",
+                "<ul><li>a</li><li>c</li></ul>"
+            ),
+            vec![
+                (DomChangeType::Removed, "b</li>".to_string()),
+                (DomChangeType::Added, "c</li>".to_string()),
+            ]
+        );
+        assert_eq!(
+            changes("<form><input></form>", "<form><input><button></form>"),
+            vec![(DomChangeType::Added, "<button>".to_string())]
+        );
+        assert_eq!(
+            changes("", "<body>"),
+            vec![(DomChangeType::Added, "<body>".to_string())]
+        );
+    }
+}
@@ -31,3 +31,26 @@ pub const SPAN_PATH: &str = "lmnr.span.path";
 pub const EVENT_TYPE: &str = "lmnr.event.type";
 pub const EVENT_VALUE: &str = "lmnr.event.value";
 pub const LLM_NODE_RENDERED_PROMPT: &str = "lmnr.span.prompt";
+// Retries of a pipeline node before the run the span records
+pub const PIPELINE_NODE_RETRIES: &str = "lmnr.pipeline.node.retries";
+// Why a pipeline run was stopped before it finished, e.g. its budget was exceeded
+pub const PIPELINE_TERMINATION: &str = "lmnr.pipeline.termination";
+// Set to "error" for spans with the OpenTelemetry error status
+pub const SPAN_STATUS: &str = "lmnr.span.status";
+// Client metadata, see `traces::client_metadata`
+pub const CLIENT_SDK_LANGUAGE: &str = "lmnr.client.sdk_language";
+pub const CLIENT_SDK_VERSION: &str = "lmnr.client.sdk_version";
+pub const CLIENT_ENVIRONMENT: &str = "lmnr.client.environment";
+pub const CLIENT_REGION: &str = "lmnr.client.region";
+// Action taken by an agent in a step span, e.g. click, type, navigate or tool_call
+pub const AGENT_ACTION_TYPE: &str = "lmnr.agent.action.type";
+// Element the action targeted, e.g. a CSS selector
+pub const AGENT_ACTION_SELECTOR: &str = "lmnr.agent.action.selector";
+// Retries of the action before the attempt the span records
+pub const AGENT_ACTION_RETRIES: &str = "lmnr.agent.action.retries";
+// Agent that recorded the span, in runs of several cooperating agents. Spans without it belong
+// to the agent of their parent.
+pub const AGENT_ID: &str = "lmnr.agent.id";
+pub const AGENT_NAME: &str = "lmnr.agent.name";
+// Id of the agent the span sends a message to, the sender being the agent of the span
+pub const AGENT_MESSAGE_TO: &str = "lmnr.agent.message.to";
@@ -17,19 +17,27 @@ use crate::{
         ChatMessage, ChatMessageContent, ChatMessageContentPart,
         InstrumentationChatMessageContentPart,
     },
-    opentelemetry::opentelemetry_proto_trace_v1::Span as OtelSpan,
+    opentelemetry::opentelemetry_proto_trace_v1::{status::StatusCode, Span as OtelSpan},
     pipeline::{nodes::Message, trace::MetaLog},
     storage::Storage,
 };
 
+use super::gen_ai::{
+    self, GEN_AI_AGENT_ID, GEN_AI_AGENT_NAME, GEN_AI_INPUT_MESSAGES, GEN_AI_OPERATION_NAME,
+    GEN_AI_OUTPUT_MESSAGES, GEN_AI_SYSTEM_INSTRUCTIONS,
+};
 use super::span_attributes::{
-    ASSOCIATION_PROPERTIES_PREFIX, GEN_AI_COMPLETION_TOKENS, GEN_AI_INPUT_COST,
+    AGENT_ACTION_RETRIES, AGENT_ACTION_SELECTOR, AGENT_ACTION_TYPE, AGENT_ID, AGENT_MESSAGE_TO,
+    AGENT_NAME, ASSOCIATION_PROPERTIES_PREFIX, GEN_AI_COMPLETION_TOKENS, GEN_AI_INPUT_COST,
     GEN_AI_INPUT_TOKENS, GEN_AI_OUTPUT_COST, GEN_AI_OUTPUT_TOKENS, GEN_AI_PROMPT_TOKENS,
     GEN_AI_REQUEST_MODEL, GEN_AI_RESPONSE_MODEL, GEN_AI_SYSTEM, GEN_AI_TOTAL_COST,
-    GEN_AI_TOTAL_TOKENS, LLM_NODE_RENDERED_PROMPT, SPAN_PATH, SPAN_TYPE,
+    GEN_AI_TOTAL_TOKENS, LLM_NODE_RENDERED_PROMPT, PIPELINE_NODE_RETRIES, SPAN_PATH, SPAN_STATUS,
+    SPAN_TYPE,
 };
 
 const INPUT_ATTRIBUTE_NAME: &str = "lmnr.span.input";
+const MAX_AGENT_ACTION_LENGTH: usize = 64;
+const MAX_AGENT_ACTION_SELECTOR_LENGTH: usize = 512;
 const OUTPUT_ATTRIBUTE_NAME: &str = "lmnr.span.output";
 /// If this attribute is set to true, the parent span will be overridden with
 /// null. We hackily use this when we wrap a span in a NonRecordingSpan that
@@ -65,6 +73,18 @@ impl SpanAttributes {
         }
     }
 
+    /// Id of the trace in the customer's systems, e.g. an order id or a ticket number
+    pub fn external_id(&self) -> Option<String> {
+        match self
+            .attributes
+            .get(format!("{ASSOCIATION_PROPERTIES_PREFIX}external_id").as_str())
+        {
+            Some(Value::String(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+
     pub fn trace_type(&self) -> Option<TraceType> {
         self.attributes
             .get(format!("{ASSOCIATION_PROPERTIES_PREFIX}trace_type").as_str())
@@ -155,6 +175,69 @@ impl SpanAttributes {
             .and_then(|p| p.as_str().map(|s| s.to_string()))
     }
 
+    pub fn is_error(&self) -> bool {
+        self.attributes.get(SPAN_STATUS).and_then(|s| s.as_str()) == Some("error")
+    }
+
+    /// Lowercase action of an agent step span. Tool executions of the OpenTelemetry conventions
+    /// are `tool_call` actions.
+    pub fn agent_action(&self) -> Option<String> {
+        let action = self
+            .attributes
+            .get(AGENT_ACTION_TYPE)
+            .and_then(|a| a.as_str())
+            .map(|a| a.trim().to_lowercase())
+            .filter(|a| !a.is_empty());
+        let operation = self
+            .attributes
+            .get(GEN_AI_OPERATION_NAME)
+            .and_then(|o| o.as_str());
+        match action {
+            Some(action) => Some(action.chars().take(MAX_AGENT_ACTION_LENGTH).collect()),
+            None if operation == Some("execute_tool") => Some(String::from("tool_call")),
+            None => None,
+        }
+    }
+
+    pub fn agent_action_selector(&self) -> Option<String> {
+        self.attributes
+            .get(AGENT_ACTION_SELECTOR)
+            .and_then(|s| s.as_str())
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.chars().take(MAX_AGENT_ACTION_SELECTOR_LENGTH).collect())
+    }
+
+    pub fn agent_action_retries(&self) -> u32 {
+        self.attributes
+            .get(AGENT_ACTION_RETRIES)
+            .and_then(|r| r.as_u64())
+            .map_or(0, |r| r.min(u32::MAX as u64) as u32)
+    }
+
+    fn non_empty_str(&self, keys: &[&str]) -> Option<String> {
+        keys.iter()
+            .filter_map(|key| self.attributes.get(*key).and_then(|v| v.as_str()))
+            .map(|v| v.trim())
+            .find(|v| !v.is_empty())
+            .map(|v| v.to_string())
+    }
+
+    /// Agent that recorded the span, identified by its name if it has no id
+    pub fn agent_id(&self) -> Option<String> {
+        self.non_empty_str(&[AGENT_ID, GEN_AI_AGENT_ID])
+            .or_else(|| self.agent_name())
+    }
+
+    pub fn agent_name(&self) -> Option<String> {
+        self.non_empty_str(&[AGENT_NAME, GEN_AI_AGENT_NAME])
+    }
+
+    /// Agent the span sends a message to
+    pub fn agent_message_to(&self) -> Option<String> {
+        self.non_empty_str(&[AGENT_MESSAGE_TO])
+    }
+
     pub fn set_usage(&mut self, usage: &SpanUsage) {
         self.attributes
             .insert(GEN_AI_INPUT_TOKENS.to_string(), json!(usage.input_tokens));
@@ -229,11 +312,17 @@ impl Span {
             Some(span_id_to_uuid(&otel_span.parent_span_id))
         };
 
-        let attributes = otel_span
+        let is_error = otel_span
+            .status
+            .as_ref()
+            .is_some_and(|status| status.code == StatusCode::Error as i32);
+
+        let mut attributes = otel_span
             .attributes
             .into_iter()
             .map(|k| (k.key, convert_any_value_to_json_value(k.value)))
             .collect::<serde_json::Map<String, serde_json::Value>>();
+        gen_ai::normalize_attributes(&mut attributes);
 
         let mut span = Span {
             version: String::from(DEFAULT_VERSION),
@@ -260,6 +349,13 @@ impl Span {
         };
 
         span.span_type = span.get_attributes().span_type();
+        if is_error {
+            let mut span_attributes = span.get_attributes();
+            span_attributes
+                .attributes
+                .insert(SPAN_STATUS.to_string(), json!("error"));
+            span.set_attributes(&span_attributes);
+        }
 
         // to handle Traceloop's prompt/completion messages
         if span.span_type == SpanType::LLM {
@@ -306,6 +402,11 @@ impl Span {
                     "function_call",
                     false,
                 );
+            } else {
+                // handling instrumentations that follow the OpenTelemetry conventions
+                let (input, output) = gen_ai::input_output_messages(&attributes, &otel_span.events);
+                span.input = input;
+                span.output = output;
             }
         } else {
             if let Some(serde_json::Value::String(s)) = attributes.get(INPUT_ATTRIBUTE_NAME) {
@@ -417,7 +518,18 @@ impl Span {
         messages
             .iter()
             .filter_map(|(msg_id, message)| {
-                if !["LLM", "SemanticSearch"].contains(&message.node_type.as_str()) {
+                if ![
+                    "LLM",
+                    "SemanticSearch",
+                    "Code",
+                    "Switch",
+                    "SemanticSwitch",
+                    "Condition",
+                    "Reduce",
+                    "Approval",
+                ]
+                .contains(&message.node_type.as_str())
+                {
                     return None;
                 }
 
@@ -451,7 +563,11 @@ impl Span {
                     trace_id,
                     parent_span_id: Some(parent_span_id),
                     name: message.node_name.clone(),
-                    attributes: span_attributes_from_meta_log(message.meta_log.clone(), span_path),
+                    attributes: span_attributes_from_meta_log(
+                        message.meta_log.clone(),
+                        span_path,
+                        message.retries,
+                    ),
                     input: Some(serde_json::to_value(input_values).unwrap()),
                     output: Some(message.value.clone().into()),
                     span_type: match message.node_type.as_str() {
@@ -492,7 +608,11 @@ impl Span {
     }
 }
 
-fn span_attributes_from_meta_log(meta_log: Option<MetaLog>, span_path: String) -> Value {
+fn span_attributes_from_meta_log(
+    meta_log: Option<MetaLog>,
+    span_path: String,
+    retries: u32,
+) -> Value {
     let mut attributes = HashMap::new();
 
     if let Some(MetaLog::LLM(llm_log)) = meta_log {
@@ -517,6 +637,9 @@ fn span_attributes_from_meta_log(meta_log: Option<MetaLog>, span_path: String) -
         attributes.insert(LLM_NODE_RENDERED_PROMPT.to_string(), json!(llm_log.prompt));
     }
     attributes.insert(SPAN_PATH.to_string(), json!(span_path));
+    if retries > 0 {
+        attributes.insert(PIPELINE_NODE_RETRIES.to_string(), json!(retries));
+    }
 
     serde_json::to_value(attributes).unwrap()
 }
@@ -537,6 +660,14 @@ fn should_keep_attribute(attribute: &str) -> bool {
         return false;
     }
 
+    // parsed to LLM span's input/output
+    if attribute == GEN_AI_INPUT_MESSAGES
+        || attribute == GEN_AI_OUTPUT_MESSAGES
+        || attribute == GEN_AI_SYSTEM_INSTRUCTIONS
+    {
+        return false;
+    }
+
     if attribute == OVERRIDE_PARENT_SPAN_ATTRIBUTE_NAME {
         return false;
     }
@@ -559,6 +690,7 @@ fn should_keep_attribute(attribute: &str) -> bool {
     true
 }
 
+#[derive(Default)]
 pub struct SpanUsage {
     pub input_tokens: i64,
     pub output_tokens: i64,
@@ -5,6 +5,7 @@ use uuid::Uuid;
 
 use crate::{
     cache::Cache,
+    chaos,
     db::{
         self,
         spans::{Span, SpanType},
@@ -15,6 +16,7 @@ use crate::{
 
 use super::{
     attributes::TraceAttributes,
+    client_metadata::ClientMetadata,
     spans::{SpanAttributes, SpanUsage},
 };
 
@@ -89,6 +91,8 @@ pub async fn record_span_to_db(
     project_id: &Uuid,
     span: &mut Span,
 ) -> anyhow::Result<()> {
+    chaos::postgres_failure()?;
+
     let mut trace_attributes = TraceAttributes::new(span.trace_id);
 
     trace_attributes.update_start_time(span.start_time);
@@ -98,7 +102,9 @@ pub async fn record_span_to_db(
 
     trace_attributes.update_user_id(span_attributes.user_id());
     trace_attributes.update_session_id(span_attributes.session_id());
+    trace_attributes.update_external_id(span_attributes.external_id());
     trace_attributes.update_trace_type(span_attributes.trace_type());
+    trace_attributes.update_client_metadata(ClientMetadata::from_span_attributes(&span_attributes));
 
     if span.span_type == SpanType::LLM {
         trace_attributes.add_input_cost(span_usage.input_cost);
@@ -0,0 +1,313 @@
+//! BigQuery destination. Rows are loaded with load jobs of newline-delimited JSON into the hour's
+//! partition of an hourly partitioned table, e.g. `laminar_spans$2024122509`. The first load of
+//! an hour truncates the partition, which makes loading an hour again idempotent. Load jobs
+//! create missing tables and add missing columns themselves.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::network::egress;
+
+use super::{ColumnType, Row, WarehouseTable};
+
+const API_URL: &str = "https://bigquery.googleapis.com/bigquery/v2";
+const UPLOAD_URL: &str = "https://bigquery.googleapis.com/upload/bigquery/v2";
+const SCOPE: &str = "https://www.googleapis.com/auth/bigquery";
+const JOB_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+const MAX_JOB_POLLS: usize = 300;
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BigQueryConfig {
+    /// Google Cloud project of the dataset
+    pub project_id: String,
+    pub dataset: String,
+    /// Location of the dataset, e.g. `US` or `europe-west1`
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+/// Service account key file, as downloaded from the Google Cloud console
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default = "default_token_uri")]
+    token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+#[derive(Serialize)]
+struct TokenClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+pub struct BigQueryClient {
+    pub config: BigQueryConfig,
+    client_email: String,
+    token_uri: String,
+    key: EncodingKey,
+    http: reqwest::Client,
+    /// Access token and when it expires
+    token: Mutex<Option<(String, DateTime<Utc>)>>,
+}
+
+fn is_valid_name(name: &str, extra_chars: &[char]) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || extra_chars.contains(&c))
+}
+
+fn column_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::String => "STRING",
+        ColumnType::Integer => "INT64",
+        ColumnType::Float => "FLOAT64",
+        ColumnType::Timestamp => "TIMESTAMP",
+    }
+}
+
+fn table_schema(table: &WarehouseTable) -> Value {
+    let fields = table
+        .columns
+        .iter()
+        .map(|column| {
+            json!({
+                "name": column.name,
+                "type": column_type(column.column_type),
+                "mode": "NULLABLE",
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({ "fields": fields })
+}
+
+fn to_ndjson(table: &WarehouseTable, rows: &[Row]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for row in rows {
+        let object = table
+            .columns
+            .iter()
+            .zip(row)
+            .map(|(column, value)| (column.name.to_string(), value.clone()))
+            .collect::<Map<_, _>>();
+        serde_json::to_writer(&mut bytes, &object)?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+/// Error message of a failed API response
+async fn response_error(response: reqwest::Response) -> anyhow::Error {
+    let status = response.status();
+    let body = response.json::<Value>().await.unwrap_or_default();
+    let message = body["error"]["message"].as_str().unwrap_or("unknown error");
+    anyhow::anyhow!("BigQuery request failed with {}: {}", status, message)
+}
+
+impl BigQueryClient {
+    pub fn new(config: BigQueryConfig, credentials: &str) -> Result<Self> {
+        if !is_valid_name(&config.project_id, &['-', '.', ':']) {
+            return Err(anyhow::anyhow!("Invalid BigQuery project id"));
+        }
+        if !is_valid_name(&config.dataset, &[]) {
+            return Err(anyhow::anyhow!("Invalid BigQuery dataset name"));
+        }
+        let key = serde_json::from_str::<ServiceAccountKey>(credentials)
+            .map_err(|e| anyhow::anyhow!("Invalid service account key: {}", e))?;
+        let encoding_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+            .map_err(|e| anyhow::anyhow!("Invalid service account private key: {}", e))?;
+
+        Ok(Self {
+            config,
+            client_email: key.client_email,
+            token_uri: key.token_uri,
+            key: encoding_key,
+            http: egress::http_client(),
+            token: Mutex::new(None),
+        })
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some((access_token, expires_at)) = token.as_ref() {
+            if *expires_at > Utc::now() + Duration::minutes(5) {
+                return Ok(access_token.clone());
+            }
+        }
+
+        let now = Utc::now().timestamp();
+        let claims = TokenClaims {
+            iss: &self.client_email,
+            scope: SCOPE,
+            aud: &self.token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)?;
+        let response = self
+            .http
+            .post(&self.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "Failed to get a BigQuery access token with {}: {}",
+                status,
+                body
+            ));
+        }
+        let response = response.json::<TokenResponse>().await?;
+        let expires_at = Utc::now() + Duration::seconds(response.expires_in);
+        *token = Some((response.access_token.clone(), expires_at));
+
+        Ok(response.access_token)
+    }
+
+    /// Checks that the dataset exists and is accessible with the service account
+    pub async fn check_access(&self) -> Result<()> {
+        let response = self
+            .http
+            .get(format!(
+                "{API_URL}/projects/{}/datasets/{}",
+                self.config.project_id, self.config.dataset
+            ))
+            .bearer_auth(self.access_token().await?)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(response_error(response).await);
+        }
+        Ok(())
+    }
+
+    pub async fn load(
+        &self,
+        table: &WarehouseTable,
+        hour: DateTime<Utc>,
+        rows: &[Row],
+        replace: bool,
+    ) -> Result<()> {
+        let mut job_reference = json!({
+            "projectId": self.config.project_id,
+            "jobId": format!("laminar_{}_{}", table.name, Uuid::new_v4().simple()),
+        });
+        if let Some(location) = &self.config.location {
+            job_reference["location"] = json!(location);
+        }
+        let job = json!({
+            "jobReference": job_reference,
+            "configuration": {
+                "load": {
+                    "destinationTable": {
+                        "projectId": self.config.project_id,
+                        "datasetId": self.config.dataset,
+                        "tableId": format!("{}${}", table.name, hour.format("%Y%m%d%H")),
+                    },
+                    "sourceFormat": "NEWLINE_DELIMITED_JSON",
+                    "schema": table_schema(table),
+                    "timePartitioning": { "type": "HOUR", "field": table.time_column },
+                    "createDisposition": "CREATE_IF_NEEDED",
+                    "writeDisposition": if replace { "WRITE_TRUNCATE" } else { "WRITE_APPEND" },
+                    "schemaUpdateOptions": ["ALLOW_FIELD_ADDITION"],
+                }
+            }
+        });
+
+        // Multipart upload of the job and its data, see
+        // https://cloud.google.com/bigquery/docs/reference/api-uploads
+        let boundary = format!("laminar-{}", Uuid::new_v4().simple());
+        let mut body = format!(
+            "--{boundary}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{job}\r\n\
+            --{boundary}\r\nContent-Type: application/octet-stream\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend(to_ndjson(table, rows)?);
+        body.extend(format!("\r\n--{boundary}--\r\n").into_bytes());
+
+        let response = self
+            .http
+            .post(format!(
+                "{UPLOAD_URL}/projects/{}/jobs?uploadType=multipart",
+                self.config.project_id
+            ))
+            .bearer_auth(self.access_token().await?)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                format!("multipart/related; boundary={boundary}"),
+            )
+            .body(body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(response_error(response).await);
+        }
+        let job = response.json::<Value>().await?;
+
+        self.wait_for_job(&job).await
+    }
+
+    async fn wait_for_job(&self, job: &Value) -> Result<()> {
+        let job_id = job["jobReference"]["jobId"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("BigQuery load job has no id"))?;
+        let location = job["jobReference"]["location"].as_str();
+
+        let mut job = job.clone();
+        for _ in 0..MAX_JOB_POLLS {
+            if job["status"]["state"].as_str() == Some("DONE") {
+                return match job["status"]["errorResult"]["message"].as_str() {
+                    Some(message) => Err(anyhow::anyhow!("BigQuery load job failed: {}", message)),
+                    None => Ok(()),
+                };
+            }
+            tokio::time::sleep(JOB_POLL_INTERVAL).await;
+
+            let mut request = self
+                .http
+                .get(format!(
+                    "{API_URL}/projects/{}/jobs/{job_id}",
+                    self.config.project_id
+                ))
+                .bearer_auth(self.access_token().await?);
+            if let Some(location) = location {
+                request = request.query(&[("location", location)]);
+            }
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(response_error(response).await);
+            }
+            job = response.json::<Value>().await?;
+        }
+
+        Err(anyhow::anyhow!(
+            "BigQuery load job {} didn't finish in time",
+            job_id
+        ))
+    }
+}
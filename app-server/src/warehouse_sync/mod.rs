@@ -0,0 +1,584 @@
+//! Hourly sync of a project's observability data to a cloud warehouse, BigQuery or Snowflake,
+//! for organizations that query everything from their warehouse.
+//!
+//! A sync loads either aggregated metrics, per hour and span name or score name, or the raw
+//! spans and scores. Like the lake export, see `lake_export`, an hour is loaded once it ended
+//! more than `SYNC_DELAY_SECONDS` ago, and the sync records the end of the last loaded hour.
+//! Loads are incremental and idempotent: each load replaces the rows of its hour, so that an
+//! hour that failed halfway is loaded again from scratch.
+//!
+//! The tables are created on the first load, and columns added here are added to existing
+//! tables. All columns are nullable, so that added columns don't break the existing rows.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        self,
+        field_visibility::FieldVisibility,
+        labels::ExportedLabel,
+        spans::Span,
+        warehouse_syncs::{WarehouseDestination, WarehouseSync, WarehouseSyncMode},
+        DB,
+    },
+    evaluations::export::to_json_string,
+    provider_api_keys::{self, ValueAndNonceHex},
+    traces::span_attributes::GEN_AI_TOTAL_COST,
+};
+
+pub mod bigquery;
+pub mod snowflake;
+
+use bigquery::BigQueryClient;
+use snowflake::SnowflakeClient;
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Time after the end of an hour until it's loaded, for spans that are still being ingested
+const SYNC_DELAY_SECONDS: i64 = 15 * 60;
+/// Time after which a sync that didn't finish is claimed again, e.g. after a restart
+const SYNC_LEASE_SECONDS: i64 = 30 * 60;
+/// Hours loaded per run, so that a long backlog doesn't hold the worker
+const MAX_HOURS_PER_RUN: i64 = 6;
+/// Rows per load, each load is built in memory before it's sent
+const MAX_ROWS_PER_LOAD: usize = 50_000;
+const SPANS_BATCH_SIZE: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnType {
+    String,
+    Integer,
+    Float,
+    Timestamp,
+}
+
+pub struct Column {
+    pub name: &'static str,
+    pub column_type: ColumnType,
+}
+
+const fn column(name: &'static str, column_type: ColumnType) -> Column {
+    Column { name, column_type }
+}
+
+pub struct WarehouseTable {
+    pub name: &'static str,
+    pub columns: &'static [Column],
+    /// Column that the table is partitioned by, and that tells the hour of a row
+    pub time_column: &'static str,
+}
+
+/// Values of a row, in the order of the table's columns. Timestamps are RFC 3339 strings.
+pub type Row = Vec<Value>;
+
+const SPANS_TABLE: WarehouseTable = WarehouseTable {
+    name: "laminar_spans",
+    columns: &[
+        column("span_id", ColumnType::String),
+        column("trace_id", ColumnType::String),
+        column("parent_span_id", ColumnType::String),
+        column("name", ColumnType::String),
+        column("span_type", ColumnType::String),
+        column("start_time", ColumnType::Timestamp),
+        column("end_time", ColumnType::Timestamp),
+        column("input", ColumnType::String),
+        column("output", ColumnType::String),
+        column("attributes", ColumnType::String),
+    ],
+    time_column: "start_time",
+};
+
+const SCORES_TABLE: WarehouseTable = WarehouseTable {
+    name: "laminar_scores",
+    columns: &[
+        column("id", ColumnType::String),
+        column("span_id", ColumnType::String),
+        column("name", ColumnType::String),
+        column("value", ColumnType::Float),
+        column("source", ColumnType::String),
+        column("user_id", ColumnType::String),
+        column("reasoning", ColumnType::String),
+        column("updated_at", ColumnType::Timestamp),
+    ],
+    time_column: "updated_at",
+};
+
+const SPAN_METRICS_TABLE: WarehouseTable = WarehouseTable {
+    name: "laminar_span_metrics",
+    columns: &[
+        column("hour", ColumnType::Timestamp),
+        column("name", ColumnType::String),
+        column("span_type", ColumnType::String),
+        column("span_count", ColumnType::Integer),
+        column("error_count", ColumnType::Integer),
+        column("total_duration_seconds", ColumnType::Float),
+        column("max_duration_seconds", ColumnType::Float),
+        column("input_tokens", ColumnType::Integer),
+        column("output_tokens", ColumnType::Integer),
+        column("total_cost", ColumnType::Float),
+    ],
+    time_column: "hour",
+};
+
+const SCORE_METRICS_TABLE: WarehouseTable = WarehouseTable {
+    name: "laminar_score_metrics",
+    columns: &[
+        column("hour", ColumnType::Timestamp),
+        column("name", ColumnType::String),
+        column("source", ColumnType::String),
+        column("score_count", ColumnType::Integer),
+        column("average_value", ColumnType::Float),
+        column("min_value", ColumnType::Float),
+        column("max_value", ColumnType::Float),
+    ],
+    time_column: "hour",
+};
+
+/// The credentials are encrypted with the project and destination as associated data, so that
+/// they can't be moved to another sync
+fn credentials_name(project_id: &Uuid, destination: WarehouseDestination) -> String {
+    format!("warehouse_sync:{}:{:?}", project_id, destination)
+}
+
+pub fn encode_credentials(
+    project_id: &Uuid,
+    destination: WarehouseDestination,
+    credentials: &String,
+) -> ValueAndNonceHex {
+    provider_api_keys::encode_api_key(&credentials_name(project_id, destination), credentials)
+}
+
+fn timestamp_value(timestamp: DateTime<Utc>) -> Value {
+    Value::String(timestamp.to_rfc3339_opts(SecondsFormat::Micros, false))
+}
+
+fn enum_name<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn span_row(span: &Span) -> Row {
+    vec![
+        json!(span.span_id),
+        json!(span.trace_id),
+        json!(span.parent_span_id),
+        json!(span.name),
+        json!(enum_name(&span.span_type)),
+        timestamp_value(span.start_time),
+        timestamp_value(span.end_time),
+        json!(span.input.as_ref().map(to_json_string)),
+        json!(span.output.as_ref().map(to_json_string)),
+        json!(span.attributes.to_string()),
+    ]
+}
+
+fn score_row(label: &ExportedLabel) -> Row {
+    vec![
+        json!(label.id),
+        json!(label.span_id),
+        json!(label.class_name),
+        json!(label.value),
+        json!(enum_name(&label.label_source)),
+        json!(label.user_id),
+        json!(label.reasoning),
+        timestamp_value(label.updated_at),
+    ]
+}
+
+#[derive(Default)]
+struct SpanMetrics {
+    span_count: i64,
+    error_count: i64,
+    total_duration_seconds: f64,
+    max_duration_seconds: f64,
+    input_tokens: i64,
+    output_tokens: i64,
+    total_cost: f64,
+}
+
+impl SpanMetrics {
+    fn add(&mut self, span: &Span) {
+        let mut attributes = span.get_attributes();
+        let duration = (span.end_time - span.start_time)
+            .num_microseconds()
+            .unwrap_or(0) as f64
+            / 1_000_000.0;
+        self.span_count += 1;
+        if attributes.is_error() {
+            self.error_count += 1;
+        }
+        self.total_duration_seconds += duration;
+        self.max_duration_seconds = self.max_duration_seconds.max(duration);
+        self.input_tokens += attributes.input_tokens();
+        self.output_tokens += attributes.completion_tokens();
+        self.total_cost += span
+            .attributes
+            .get(GEN_AI_TOTAL_COST)
+            .and_then(Value::as_f64)
+            .unwrap_or_default();
+    }
+}
+
+fn span_metrics_rows(
+    hour: DateTime<Utc>,
+    metrics: HashMap<(String, String), SpanMetrics>,
+) -> Vec<Row> {
+    metrics
+        .into_iter()
+        .map(|((name, span_type), metrics)| {
+            vec![
+                timestamp_value(hour),
+                json!(name),
+                json!(span_type),
+                json!(metrics.span_count),
+                json!(metrics.error_count),
+                json!(metrics.total_duration_seconds),
+                json!(metrics.max_duration_seconds),
+                json!(metrics.input_tokens),
+                json!(metrics.output_tokens),
+                json!(metrics.total_cost),
+            ]
+        })
+        .collect()
+}
+
+fn score_metrics_rows(hour: DateTime<Utc>, labels: &[ExportedLabel]) -> Vec<Row> {
+    let mut values = HashMap::<(&str, String), Vec<f64>>::new();
+    for label in labels {
+        values
+            .entry((&label.class_name, enum_name(&label.label_source)))
+            .or_default()
+            .push(label.value);
+    }
+
+    values
+        .into_iter()
+        .map(|((name, source), values)| {
+            let count = values.len();
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            vec![
+                timestamp_value(hour),
+                json!(name),
+                json!(source),
+                json!(count),
+                json!(values.iter().sum::<f64>() / count as f64),
+                json!(min),
+                json!(max),
+            ]
+        })
+        .collect()
+}
+
+pub enum WarehouseClient {
+    BigQuery(BigQueryClient),
+    Snowflake(SnowflakeClient),
+}
+
+impl WarehouseClient {
+    /// Fails if the config or the credentials are invalid for the destination
+    pub fn new(
+        destination: WarehouseDestination,
+        config: &Value,
+        credentials: &str,
+    ) -> Result<Self> {
+        match destination {
+            WarehouseDestination::BIGQUERY => Ok(Self::BigQuery(BigQueryClient::new(
+                serde_json::from_value(config.clone())?,
+                credentials,
+            )?)),
+            WarehouseDestination::SNOWFLAKE => Ok(Self::Snowflake(SnowflakeClient::new(
+                serde_json::from_value(config.clone())?,
+                credentials,
+            )?)),
+        }
+    }
+
+    /// Config as it's stored, without unknown fields
+    pub fn config(&self) -> Value {
+        match self {
+            Self::BigQuery(client) => json!(client.config),
+            Self::Snowflake(client) => json!(client.config),
+        }
+    }
+
+    /// Checks that the destination is reachable with the credentials
+    pub async fn check_access(&self) -> Result<()> {
+        match self {
+            Self::BigQuery(client) => client.check_access().await,
+            Self::Snowflake(client) => client.check_access().await,
+        }
+    }
+
+    /// Creates the table if it doesn't exist and adds its missing columns
+    async fn ensure_table(&self, table: &WarehouseTable) -> Result<()> {
+        match self {
+            // Load jobs create tables and add columns themselves
+            Self::BigQuery(_) => Ok(()),
+            Self::Snowflake(client) => client.ensure_table(table).await,
+        }
+    }
+
+    /// Loads rows of the hour. The first load of an hour replaces its rows.
+    async fn load(
+        &self,
+        table: &WarehouseTable,
+        hour: DateTime<Utc>,
+        rows: &[Row],
+        replace: bool,
+    ) -> Result<()> {
+        match self {
+            Self::BigQuery(client) => client.load(table, hour, rows, replace).await,
+            Self::Snowflake(client) => client.load(table, hour, rows, replace).await,
+        }
+    }
+}
+
+/// Loads the rows of a table and hour in loads of up to `MAX_ROWS_PER_LOAD` rows
+struct TableLoader<'a> {
+    client: &'a WarehouseClient,
+    table: &'a WarehouseTable,
+    hour: DateTime<Utc>,
+    rows: Vec<Row>,
+    is_first_load: bool,
+}
+
+impl<'a> TableLoader<'a> {
+    fn new(client: &'a WarehouseClient, table: &'a WarehouseTable, hour: DateTime<Utc>) -> Self {
+        Self {
+            client,
+            table,
+            hour,
+            rows: Vec::new(),
+            is_first_load: true,
+        }
+    }
+
+    async fn extend(&mut self, rows: impl IntoIterator<Item = Row>) -> Result<()> {
+        self.rows.extend(rows);
+        if self.rows.len() >= MAX_ROWS_PER_LOAD {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        // An hour without rows only needs to be cleared if rows were loaded by a failed run
+        if self.rows.is_empty() && !self.is_first_load {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.rows);
+        self.client
+            .load(self.table, self.hour, &rows, self.is_first_load)
+            .await?;
+        self.is_first_load = false;
+        Ok(())
+    }
+}
+
+async fn sync_hour(
+    db: &DB,
+    client: &WarehouseClient,
+    sync: &WarehouseSync,
+    hour: DateTime<Utc>,
+) -> Result<()> {
+    let end = hour + Duration::hours(1);
+    let project_id = sync.project_id;
+    let labels = db::labels::get_labels_updated_in_period(&db.pool, project_id, hour, end).await?;
+    let mut batches =
+        db::spans::stream_spans(&db.pool, project_id, hour, end, FieldVisibility::ALL)
+            .chunks(SPANS_BATCH_SIZE);
+
+    match sync.mode {
+        WarehouseSyncMode::RAW => {
+            let mut spans = TableLoader::new(client, &SPANS_TABLE, hour);
+            while let Some(batch) = batches.next().await {
+                let batch = batch.into_iter().collect::<Result<Vec<_>, _>>()?;
+                spans.extend(batch.iter().map(span_row)).await?;
+            }
+            spans.flush().await?;
+
+            let mut scores = TableLoader::new(client, &SCORES_TABLE, hour);
+            scores.extend(labels.iter().map(score_row)).await?;
+            scores.flush().await?;
+        }
+        WarehouseSyncMode::AGGREGATED => {
+            let mut metrics = HashMap::<(String, String), SpanMetrics>::new();
+            while let Some(batch) = batches.next().await {
+                for span in batch {
+                    let span = span?;
+                    metrics
+                        .entry((span.name.clone(), enum_name(&span.span_type)))
+                        .or_default()
+                        .add(&span);
+                }
+            }
+            let mut span_metrics = TableLoader::new(client, &SPAN_METRICS_TABLE, hour);
+            span_metrics
+                .extend(span_metrics_rows(hour, metrics))
+                .await?;
+            span_metrics.flush().await?;
+
+            let mut score_metrics = TableLoader::new(client, &SCORE_METRICS_TABLE, hour);
+            score_metrics
+                .extend(score_metrics_rows(hour, &labels))
+                .await?;
+            score_metrics.flush().await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn tables(mode: WarehouseSyncMode) -> [&'static WarehouseTable; 2] {
+    match mode {
+        WarehouseSyncMode::RAW => [&SPANS_TABLE, &SCORES_TABLE],
+        WarehouseSyncMode::AGGREGATED => [&SPAN_METRICS_TABLE, &SCORE_METRICS_TABLE],
+    }
+}
+
+/// Loads the hours that are due, oldest first, recording the progress after each hour
+async fn run_warehouse_sync(db: &DB, sync: &WarehouseSync) -> Result<()> {
+    let credentials = provider_api_keys::decode_api_key(
+        &credentials_name(&sync.project_id, sync.destination),
+        &sync.credentials_nonce,
+        &sync.credentials_value,
+    )?;
+    let client = WarehouseClient::new(sync.destination, &sync.config, &credentials)?;
+    let due_until = Utc::now() - Duration::seconds(SYNC_DELAY_SECONDS);
+    if sync.synced_until + Duration::hours(1) > due_until {
+        return Ok(());
+    }
+
+    for table in tables(sync.mode) {
+        client.ensure_table(table).await?;
+    }
+
+    let mut hour = sync.synced_until;
+    for _ in 0..MAX_HOURS_PER_RUN {
+        if hour + Duration::hours(1) > due_until {
+            break;
+        }
+        sync_hour(db, &client, sync, hour).await?;
+        hour = hour + Duration::hours(1);
+        db::warehouse_syncs::update_synced_until(&db.pool, &sync.id, hour).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn run_warehouse_syncs_periodically(db: Arc<DB>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        let syncs = match db::warehouse_syncs::claim_due_warehouse_syncs(
+            &db.pool,
+            SYNC_DELAY_SECONDS,
+            SYNC_LEASE_SECONDS,
+        )
+        .await
+        {
+            Ok(syncs) => syncs,
+            Err(e) => {
+                log::error!("Failed to claim due warehouse syncs: {:?}", e);
+                continue;
+            }
+        };
+
+        for sync in syncs {
+            if let Err(e) = run_warehouse_sync(&db, &sync).await {
+                log::error!(
+                    "Failed to run warehouse sync. project_id [{}], sync_id [{}]: {:?}",
+                    sync.project_id,
+                    sync.id,
+                    e
+                );
+                if let Err(e) = db::warehouse_syncs::record_warehouse_sync_error(
+                    &db.pool,
+                    &sync.id,
+                    &e.to_string(),
+                )
+                .await
+                {
+                    log::error!(
+                        "Failed to record warehouse sync error. sync_id [{}]: {:?}",
+                        sync.id,
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::db::labels::LabelSource;
+
+    use super::*;
+
+    fn label(name: &str, value: f64) -> ExportedLabel {
+        ExportedLabel {
+            id: Uuid::new_v4(),
+            span_id: Uuid::new_v4(),
+            class_name: name.to_string(),
+            value,
+            label_source: LabelSource::MANUAL,
+            user_id: None,
+            reasoning: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_rows_match_columns() {
+        let span = Span {
+            name: "openai.chat".to_string(),
+            attributes: json!({"gen_ai.usage.input_tokens": 12}),
+            ..Default::default()
+        };
+        assert_eq!(span_row(&span).len(), SPANS_TABLE.columns.len());
+        assert_eq!(
+            score_row(&label("correct", 1.0)).len(),
+            SCORES_TABLE.columns.len()
+        );
+
+        let hour = Utc.with_ymd_and_hms(2024, 12, 25, 9, 0, 0).unwrap();
+        let mut metrics = HashMap::<(String, String), SpanMetrics>::new();
+        metrics
+            .entry((span.name.clone(), enum_name(&span.span_type)))
+            .or_default()
+            .add(&span);
+        let rows = span_metrics_rows(hour, metrics);
+        assert_eq!(rows[0].len(), SPAN_METRICS_TABLE.columns.len());
+        assert_eq!(rows[0][0], json!("2024-12-25T09:00:00.000000+00:00"));
+        assert_eq!(rows[0][7], json!(12));
+    }
+
+    #[test]
+    fn test_score_metrics() {
+        let hour = Utc.with_ymd_and_hms(2024, 12, 25, 9, 0, 0).unwrap();
+        let labels = vec![
+            label("correct", 1.0),
+            label("correct", 0.0),
+            label("correct", 1.0),
+        ];
+        let rows = score_metrics_rows(hour, &labels);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].len(), SCORE_METRICS_TABLE.columns.len());
+        assert_eq!(rows[0][2], json!("MANUAL"));
+        assert_eq!(rows[0][3], json!(3));
+        assert_eq!(rows[0][5], json!(0.0));
+        assert_eq!(rows[0][6], json!(1.0));
+    }
+}
@@ -0,0 +1,304 @@
+//! Snowflake destination, through the SQL API with key pair authentication. Tables are created
+//! and extended with `IF NOT EXISTS` statements. The first load of an hour deletes the hour's
+//! rows, and rows are inserted with array bindings of `INSERT_BATCH_SIZE` rows per statement.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::network::egress;
+
+use super::{ColumnType, Row, WarehouseTable};
+
+const INSERT_BATCH_SIZE: usize = 1000;
+const STATEMENT_TIMEOUT_SECONDS: u64 = 600;
+const STATEMENT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+const MAX_STATEMENT_POLLS: usize = 300;
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SnowflakeConfig {
+    /// Account identifier, e.g. `myorg-myaccount` or `xy12345.us-east-1`
+    pub account: String,
+    pub user: String,
+    /// Fingerprint of the user's public key, `RSA_PUBLIC_KEY_FP` of `DESCRIBE USER`
+    pub public_key_fingerprint: String,
+    pub database: String,
+    pub schema: String,
+    pub warehouse: String,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JwtClaims {
+    iss: String,
+    sub: String,
+    iat: i64,
+    exp: i64,
+}
+
+pub struct SnowflakeClient {
+    pub config: SnowflakeConfig,
+    key: EncodingKey,
+    http: reqwest::Client,
+}
+
+fn column_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::String => "VARCHAR",
+        ColumnType::Integer => "NUMBER(38, 0)",
+        ColumnType::Float => "FLOAT",
+        ColumnType::Timestamp => "TIMESTAMP_TZ",
+    }
+}
+
+fn create_table_statement(table: &WarehouseTable) -> String {
+    let columns = table
+        .columns
+        .iter()
+        .map(|column| format!("{} {}", column.name, column_type(column.column_type)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("CREATE TABLE IF NOT EXISTS {} ({})", table.name, columns)
+}
+
+fn insert_statement(table: &WarehouseTable) -> String {
+    let names = table
+        .columns
+        .iter()
+        .map(|column| column.name)
+        .collect::<Vec<_>>();
+    let placeholders = vec!["?"; names.len()];
+    format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table.name,
+        names.join(", "),
+        placeholders.join(", ")
+    )
+}
+
+/// Bindings of one array per column. Values are bound as text and converted to the column's
+/// type by Snowflake.
+fn insert_bindings(table: &WarehouseTable, rows: &[Row]) -> Value {
+    let bindings = (0..table.columns.len())
+        .map(|i| {
+            let values = rows
+                .iter()
+                .map(|row| match &row[i] {
+                    Value::Null => Value::Null,
+                    Value::String(s) => Value::String(s.clone()),
+                    value => Value::String(value.to_string()),
+                })
+                .collect::<Vec<_>>();
+            (
+                (i + 1).to_string(),
+                json!({ "type": "TEXT", "value": values }),
+            )
+        })
+        .collect::<Map<_, _>>();
+    Value::Object(bindings)
+}
+
+fn text_binding(value: String) -> Value {
+    json!({ "type": "TEXT", "value": value })
+}
+
+impl SnowflakeClient {
+    pub fn new(config: SnowflakeConfig, credentials: &str) -> Result<Self> {
+        if config.account.is_empty()
+            || !config
+                .account
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        {
+            return Err(anyhow::anyhow!("Invalid Snowflake account identifier"));
+        }
+        let key = EncodingKey::from_rsa_pem(credentials.as_bytes())
+            .map_err(|e| anyhow::anyhow!("Invalid private key: {}", e))?;
+
+        Ok(Self {
+            config,
+            key,
+            http: egress::http_client(),
+        })
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "https://{}.snowflakecomputing.com/api/v2/statements",
+            self.config.account.to_lowercase()
+        )
+    }
+
+    /// Key pair JWT, see https://docs.snowflake.com/en/developer-guide/sql-api/authenticating
+    fn jwt(&self) -> Result<String> {
+        // The region of a locator is not part of the account in the token
+        let account = self
+            .config
+            .account
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_uppercase();
+        let qualified_user = format!("{}.{}", account, self.config.user.to_uppercase());
+        let fingerprint = self.config.public_key_fingerprint.trim();
+        let fingerprint = if fingerprint.starts_with("SHA256:") {
+            fingerprint.to_string()
+        } else {
+            format!("SHA256:{}", fingerprint)
+        };
+        let now = Utc::now();
+        let claims = JwtClaims {
+            iss: format!("{}.{}", qualified_user, fingerprint),
+            sub: qualified_user,
+            iat: now.timestamp(),
+            exp: (now + Duration::minutes(59)).timestamp(),
+        };
+        Ok(jsonwebtoken::encode(
+            &Header::new(Algorithm::RS256),
+            &claims,
+            &self.key,
+        )?)
+    }
+
+    fn request(&self, request: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder> {
+        Ok(request
+            .bearer_auth(self.jwt()?)
+            .header("X-Snowflake-Authorization-Token-Type", "KEYPAIR_JWT")
+            .header(reqwest::header::ACCEPT, "application/json"))
+    }
+
+    /// Runs the statement and waits until it's done
+    async fn execute(&self, statement: &str, bindings: Option<Value>) -> Result<()> {
+        let mut body = json!({
+            "statement": statement,
+            "timeout": STATEMENT_TIMEOUT_SECONDS,
+            "database": self.config.database,
+            "schema": self.config.schema,
+            "warehouse": self.config.warehouse,
+        });
+        if let Some(role) = &self.config.role {
+            body["role"] = json!(role);
+        }
+        if let Some(bindings) = bindings {
+            body["bindings"] = bindings;
+        }
+
+        let mut response = self
+            .request(self.http.post(self.url()))?
+            .json(&body)
+            .send()
+            .await?;
+        for _ in 0..MAX_STATEMENT_POLLS {
+            let status = response.status();
+            let result = response.json::<Value>().await.unwrap_or_default();
+            if status == reqwest::StatusCode::ACCEPTED {
+                // Still running, the result has the handle to poll
+                let handle = result["statementHandle"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Snowflake statement has no handle"))?;
+                tokio::time::sleep(STATEMENT_POLL_INTERVAL).await;
+                response = self
+                    .request(self.http.get(format!("{}/{}", self.url(), handle)))?
+                    .send()
+                    .await?;
+                continue;
+            }
+            if !status.is_success() {
+                let message = result["message"].as_str().unwrap_or("unknown error");
+                return Err(anyhow::anyhow!(
+                    "Snowflake statement failed with {}: {}",
+                    status,
+                    message
+                ));
+            }
+            return Ok(());
+        }
+
+        Err(anyhow::anyhow!("Snowflake statement didn't finish in time"))
+    }
+
+    /// Checks that the warehouse, database and schema are usable with the credentials
+    pub async fn check_access(&self) -> Result<()> {
+        self.execute("SELECT CURRENT_SCHEMA()", None).await
+    }
+
+    pub async fn ensure_table(&self, table: &WarehouseTable) -> Result<()> {
+        self.execute(&create_table_statement(table), None).await?;
+        for column in table.columns {
+            self.execute(
+                &format!(
+                    "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}",
+                    table.name,
+                    column.name,
+                    column_type(column.column_type)
+                ),
+                None,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn load(
+        &self,
+        table: &WarehouseTable,
+        hour: DateTime<Utc>,
+        rows: &[Row],
+        replace: bool,
+    ) -> Result<()> {
+        if replace {
+            let bindings = json!({
+                "1": text_binding(hour.to_rfc3339()),
+                "2": text_binding((hour + Duration::hours(1)).to_rfc3339()),
+            });
+            self.execute(
+                &format!(
+                    "DELETE FROM {} WHERE {} >= TO_TIMESTAMP_TZ(?) AND {} < TO_TIMESTAMP_TZ(?)",
+                    table.name, table.time_column, table.time_column
+                ),
+                Some(bindings),
+            )
+            .await?;
+        }
+
+        let statement = insert_statement(table);
+        for chunk in rows.chunks(INSERT_BATCH_SIZE) {
+            self.execute(&statement, Some(insert_bindings(table, chunk)))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::warehouse_sync::SCORE_METRICS_TABLE;
+
+    #[test]
+    fn test_insert_statement() {
+        assert_eq!(
+            insert_statement(&SCORE_METRICS_TABLE),
+            "INSERT INTO laminar_score_metrics (hour, name, source, score_count, average_value, \
+            min_value, max_value) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        );
+
+        let rows = vec![vec![
+            json!("2024-12-25T09:00:00.000000+00:00"),
+            json!("correct"),
+            Value::Null,
+            json!(2),
+            json!(0.5),
+            json!(0.0),
+            json!(1.0),
+        ]];
+        let bindings = insert_bindings(&SCORE_METRICS_TABLE, &rows);
+        assert_eq!(bindings["2"]["value"], json!(["correct"]));
+        assert_eq!(bindings["3"]["value"], json!([null]));
+        assert_eq!(bindings["4"]["value"], json!(["2"]));
+    }
+}
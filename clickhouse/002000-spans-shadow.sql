@@ -0,0 +1,2 @@
+-- Spans written by the candidate ingestion logic, see app-server/src/traces/shadow.rs
+CREATE TABLE spans_shadow AS spans;
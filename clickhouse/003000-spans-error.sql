@@ -0,0 +1,3 @@
+-- Spans with the OpenTelemetry error status, see app-server/src/traces/spans.rs
+ALTER TABLE spans ADD COLUMN is_error Bool DEFAULT false;
+ALTER TABLE spans_shadow ADD COLUMN is_error Bool DEFAULT false;
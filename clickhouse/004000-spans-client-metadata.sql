@@ -0,0 +1,9 @@
+-- Client metadata added at ingestion, see app-server/src/traces/client_metadata.rs
+ALTER TABLE spans ADD COLUMN sdk_language String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN sdk_version String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN environment String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN region String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN sdk_language String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN sdk_version String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN environment String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN region String DEFAULT '<null>';
@@ -0,0 +1,16 @@
+-- Promoted span attributes, see app-server/src/traces/promoted_attributes.rs
+ALTER TABLE spans ADD COLUMN attribute_0 String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN attribute_1 String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN attribute_2 String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN attribute_3 String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN attribute_4 String DEFAULT '<null>';
+ALTER TABLE spans ADD INDEX attribute_0_idx attribute_0 TYPE bloom_filter GRANULARITY 4;
+ALTER TABLE spans ADD INDEX attribute_1_idx attribute_1 TYPE bloom_filter GRANULARITY 4;
+ALTER TABLE spans ADD INDEX attribute_2_idx attribute_2 TYPE bloom_filter GRANULARITY 4;
+ALTER TABLE spans ADD INDEX attribute_3_idx attribute_3 TYPE bloom_filter GRANULARITY 4;
+ALTER TABLE spans ADD INDEX attribute_4_idx attribute_4 TYPE bloom_filter GRANULARITY 4;
+ALTER TABLE spans_shadow ADD COLUMN attribute_0 String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN attribute_1 String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN attribute_2 String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN attribute_3 String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN attribute_4 String DEFAULT '<null>';
@@ -0,0 +1,11 @@
+-- Scores of online evaluators and manual feedback, see app-server/src/ch/span_scores.rs
+CREATE TABLE span_scores (
+    project_id UUID,
+    timestamp DateTime64(9, 'UTC'),
+    span_id UUID,
+    trace_id UUID,
+    name String,
+    value Float64
+) ENGINE = MergeTree()
+ORDER BY (project_id, name, timestamp, trace_id, span_id)
+SETTINGS index_granularity = 8192;
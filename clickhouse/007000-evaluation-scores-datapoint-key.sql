@@ -0,0 +1,2 @@
+-- Identity of the evaluated datapoint across evaluations, see datapoint_key in app-server/src/ch/evaluation_scores.rs
+ALTER TABLE evaluation_scores ADD COLUMN datapoint_key String DEFAULT '';
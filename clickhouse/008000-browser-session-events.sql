@@ -0,0 +1,13 @@
+-- Events recorded in the browser of browser agents, see app-server/src/ch/browser_events.rs
+CREATE TABLE browser_session_events (
+    id UUID,
+    project_id UUID,
+    session_id String,
+    trace_id UUID,
+    timestamp DateTime64(9, 'UTC'),
+    event_type LowCardinality(String),
+    data String,
+    screenshot_url String DEFAULT ''
+) ENGINE = MergeTree()
+ORDER BY (project_id, trace_id, timestamp)
+SETTINGS index_granularity = 8192;
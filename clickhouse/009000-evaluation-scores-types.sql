@@ -0,0 +1,3 @@
+-- Boolean scores are stored as 1 or 0 in value, so that their average is the pass rate. Categorical scores have value 0 and are aggregated by label.
+ALTER TABLE evaluation_scores ADD COLUMN score_type Enum8('NUMERIC' = 0, 'BOOLEAN', 'CATEGORICAL') DEFAULT 'NUMERIC';
+ALTER TABLE evaluation_scores ADD COLUMN label String DEFAULT '';
@@ -0,0 +1,16 @@
+-- Screenshots and DOM snapshots taken by browser agents after each step, see app-server/src/ch/browser_snapshots.rs. The contents are in object storage.
+CREATE TABLE browser_snapshots (
+    id UUID,
+    project_id UUID,
+    session_id String,
+    trace_id UUID,
+    step UInt32,
+    timestamp DateTime64(9, 'UTC'),
+    page_url String DEFAULT '',
+    screenshot_url String DEFAULT '',
+    screenshot_hash String DEFAULT '',
+    dom_url String DEFAULT '',
+    dom_hash String DEFAULT ''
+) ENGINE = MergeTree()
+ORDER BY (project_id, session_id, timestamp)
+SETTINGS index_granularity = 8192;
@@ -0,0 +1,7 @@
+-- Actions of agent step spans, see app-server/src/traces/spans.rs
+ALTER TABLE spans ADD COLUMN agent_action String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN agent_action_selector String DEFAULT '<null>';
+ALTER TABLE spans ADD COLUMN agent_action_retries UInt32 DEFAULT 0;
+ALTER TABLE spans_shadow ADD COLUMN agent_action String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN agent_action_selector String DEFAULT '<null>';
+ALTER TABLE spans_shadow ADD COLUMN agent_action_retries UInt32 DEFAULT 0;
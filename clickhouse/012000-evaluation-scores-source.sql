@@ -0,0 +1,2 @@
+-- Whether the score was reported by an evaluator or submitted by a user, human scores are aggregated together with the automatic ones
+ALTER TABLE evaluation_scores ADD COLUMN source Enum8('AUTO' = 0, 'HUMAN') DEFAULT 'AUTO';
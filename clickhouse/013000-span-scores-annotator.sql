@@ -0,0 +1,2 @@
+-- Annotator of manual span scores, for agreement between annotators. Nil for scores of online evaluators.
+ALTER TABLE span_scores ADD COLUMN user_id UUID DEFAULT '00000000-0000-0000-0000-000000000000';
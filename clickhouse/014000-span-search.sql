@@ -0,0 +1,15 @@
+-- Text of span inputs and outputs for full-text search, see app-server/src/ch/span_search.rs. The ngram indexes on the lowercased text let substring searches skip granules that can't match.
+CREATE TABLE span_search (
+    project_id UUID,
+    start_time DateTime64(9, 'UTC'),
+    span_id UUID,
+    trace_id UUID,
+    name String,
+    path String DEFAULT '<null>',
+    input String DEFAULT '',
+    output String DEFAULT '',
+    INDEX input_ngram_idx lower(input) TYPE ngrambf_v1(3, 65536, 3, 0) GRANULARITY 1,
+    INDEX output_ngram_idx lower(output) TYPE ngrambf_v1(3, 65536, 3, 0) GRANULARITY 1
+) ENGINE = MergeTree()
+ORDER BY (project_id, start_time, span_id)
+SETTINGS index_granularity = 1024;
@@ -0,0 +1,5 @@
+-- Tables written with app-server/src/ch/utils.rs insert_rows remember the deduplication tokens of their last inserts, so that an insert retried after ClickHouse already wrote it isn't written twice
+ALTER TABLE spans MODIFY SETTING non_replicated_deduplication_window = 1000;
+ALTER TABLE spans_shadow MODIFY SETTING non_replicated_deduplication_window = 1000;
+ALTER TABLE span_scores MODIFY SETTING non_replicated_deduplication_window = 1000;
+ALTER TABLE evaluation_scores MODIFY SETTING non_replicated_deduplication_window = 1000;
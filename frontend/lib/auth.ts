@@ -1,4 +1,4 @@
-import type { DefaultSession, NextAuthOptions, User } from 'next-auth';
+import type { Account, DefaultSession, NextAuthOptions, User } from 'next-auth';
 import CredentialsProvider from 'next-auth/providers/credentials';
 import GithubProvider from 'next-auth/providers/github';
 import GoogleProvider from 'next-auth/providers/google';
@@ -83,13 +83,24 @@ const getProviders = () => {
   return providers;
 };
 
+// the token the app server can check with the identity provider, none for the email provider
+const identityToken = (account: Account | null) => {
+  if (account?.provider === 'github' && account.access_token) {
+    return { provider: 'github', token: account.access_token };
+  }
+  if (account?.provider === 'google' && account.id_token) {
+    return { provider: 'google', token: account.id_token };
+  }
+  return null;
+};
+
 export const authOptions: NextAuthOptions = {
   providers: getProviders(),
   session: {
     strategy: 'jwt'
   },
   callbacks: {
-    async jwt({ token, profile, trigger }) {
+    async jwt({ token, account, profile, trigger }) {
       if (trigger === 'signIn') {
         // token always contains name, email and picture keys
         // name and email should always be provided, picture is optional
@@ -108,7 +119,9 @@ export const authOptions: NextAuthOptions = {
           body: JSON.stringify({
             name,
             email,
-            picture
+            picture,
+            // organizations that require SSO verify the identity with the provider
+            identityToken: identityToken(account)
           })
         });
 
@@ -0,0 +1,52 @@
+DO $$ BEGIN
+ CREATE TYPE "public"."organization_role" AS ENUM('member', 'admin', 'owner');
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "organizations" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"name" text NOT NULL,
+	"tier_id" bigint DEFAULT '1' NOT NULL,
+	"billing_email" text,
+	"sso_required" boolean DEFAULT false NOT NULL,
+	"min_retention_days" bigint
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "members_of_organizations" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"organization_id" uuid NOT NULL,
+	"user_id" uuid NOT NULL,
+	"member_role" "organization_role" DEFAULT 'member' NOT NULL,
+	CONSTRAINT "members_of_organizations_user_organization_unique" UNIQUE("organization_id","user_id")
+);
+--> statement-breakpoint
+ALTER TABLE "workspaces" ADD COLUMN "organization_id" uuid;--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "organizations" ADD CONSTRAINT "organizations_tier_id_fkey" FOREIGN KEY ("tier_id") REFERENCES "public"."subscription_tiers"("id") ON DELETE no action ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "members_of_organizations" ADD CONSTRAINT "members_of_organizations_organization_id_fkey" FOREIGN KEY ("organization_id") REFERENCES "public"."organizations"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "members_of_organizations" ADD CONSTRAINT "members_of_organizations_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "workspaces" ADD CONSTRAINT "workspaces_organization_id_fkey" FOREIGN KEY ("organization_id") REFERENCES "public"."organizations"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "members_of_organizations_user_id_idx" ON "members_of_organizations" USING btree ("user_id");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "workspaces_organization_id_idx" ON "workspaces" USING btree ("organization_id");
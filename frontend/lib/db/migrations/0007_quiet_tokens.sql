@@ -0,0 +1,19 @@
+CREATE TYPE "public"."token_scope" AS ENUM('read', 'write');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "personal_access_tokens" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"user_id" uuid NOT NULL,
+	"name" text NOT NULL,
+	"hash" text NOT NULL,
+	"shorthand" text NOT NULL,
+	"scope" "token_scope" DEFAULT 'read' NOT NULL,
+	"expires_at" timestamp with time zone,
+	"last_used_at" timestamp with time zone,
+	CONSTRAINT "personal_access_tokens_hash_unique" UNIQUE("hash")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "personal_access_tokens" ADD CONSTRAINT "personal_access_tokens_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
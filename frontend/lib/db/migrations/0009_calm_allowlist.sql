@@ -0,0 +1 @@
+ALTER TABLE "workspaces" ADD COLUMN "ip_allowlist" text[] DEFAULT '{}' NOT NULL;
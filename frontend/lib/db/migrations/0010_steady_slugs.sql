@@ -0,0 +1,4 @@
+ALTER TABLE "workspaces" ADD COLUMN "slug" text;--> statement-breakpoint
+ALTER TABLE "projects" ADD COLUMN "slug" text;--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "workspaces_slug_idx" ON "workspaces" USING btree ("slug") WHERE "slug" IS NOT NULL;--> statement-breakpoint
+CREATE UNIQUE INDEX IF NOT EXISTS "projects_workspace_id_slug_idx" ON "projects" USING btree ("workspace_id","slug") WHERE "slug" IS NOT NULL;
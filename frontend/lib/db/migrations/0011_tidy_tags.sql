@@ -0,0 +1,17 @@
+CREATE TYPE "public"."tagged_resource_type" AS ENUM('DATASET', 'EVALUATION', 'PIPELINE');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "resource_tags" (
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"resource_type" "tagged_resource_type" NOT NULL,
+	"resource_id" uuid NOT NULL,
+	"tag" text NOT NULL,
+	CONSTRAINT "resource_tags_pkey" PRIMARY KEY("resource_type","resource_id","tag")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "resource_tags" ADD CONSTRAINT "resource_tags_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "resource_tags_project_id_tag_idx" ON "resource_tags" USING btree ("project_id","tag");
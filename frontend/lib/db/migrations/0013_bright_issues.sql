@@ -0,0 +1,40 @@
+CREATE TYPE "public"."issue_tracker_provider" AS ENUM('JIRA', 'LINEAR');--> statement-breakpoint
+CREATE TYPE "public"."linked_issue_resource_type" AS ENUM('TRACE', 'EVALUATION_RESULT');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "issue_tracker_integrations" (
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"provider" "issue_tracker_provider" NOT NULL,
+	"config" jsonb DEFAULT '{}'::jsonb NOT NULL,
+	"token_nonce" text NOT NULL,
+	"token_value" text NOT NULL,
+	CONSTRAINT "issue_tracker_integrations_pkey" PRIMARY KEY("project_id","provider")
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "linked_issues" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"resource_type" "linked_issue_resource_type" NOT NULL,
+	"resource_id" uuid NOT NULL,
+	"provider" "issue_tracker_provider" NOT NULL,
+	"external_id" text NOT NULL,
+	"key" text NOT NULL,
+	"url" text NOT NULL,
+	"title" text NOT NULL,
+	"status" text,
+	"status_updated_at" timestamp with time zone
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "issue_tracker_integrations" ADD CONSTRAINT "issue_tracker_integrations_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "linked_issues" ADD CONSTRAINT "linked_issues_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "linked_issues_project_id_resource_idx" ON "linked_issues" USING btree ("project_id","resource_type","resource_id");
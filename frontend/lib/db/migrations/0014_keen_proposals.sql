@@ -0,0 +1,31 @@
+CREATE TYPE "public"."eval_proposal_status" AS ENUM('GENERATING', 'READY', 'FAILED', 'ADOPTED');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "eval_proposals" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"span_path" text NOT NULL,
+	"status" "eval_proposal_status" DEFAULT 'GENERATING' NOT NULL,
+	"datapoints" jsonb DEFAULT '[]'::jsonb NOT NULL,
+	"rubric" text,
+	"error" text,
+	"dataset_id" uuid,
+	"label_class_id" uuid
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "eval_proposals" ADD CONSTRAINT "eval_proposals_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "eval_proposals" ADD CONSTRAINT "eval_proposals_dataset_id_fkey" FOREIGN KEY ("dataset_id") REFERENCES "public"."datasets"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "eval_proposals" ADD CONSTRAINT "eval_proposals_label_class_id_fkey" FOREIGN KEY ("label_class_id") REFERENCES "public"."label_classes"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
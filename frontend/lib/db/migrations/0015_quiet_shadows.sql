@@ -0,0 +1,38 @@
+CREATE TYPE "public"."shadow_verdict" AS ENUM('CANDIDATE', 'PRODUCTION', 'TIE');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "shadow_deployments" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"name" text NOT NULL,
+	"span_path" text NOT NULL,
+	"candidate_model" text NOT NULL,
+	"candidate_system_prompt" text,
+	"sample_percentage" double precision DEFAULT '1' NOT NULL,
+	"active" boolean DEFAULT true NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "shadow_comparisons" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"deployment_id" uuid NOT NULL,
+	"span_id" uuid NOT NULL,
+	"trace_id" uuid NOT NULL,
+	"candidate_output" text NOT NULL,
+	"verdict" "shadow_verdict" NOT NULL,
+	"reasoning" text NOT NULL
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "shadow_deployments" ADD CONSTRAINT "shadow_deployments_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "shadow_comparisons" ADD CONSTRAINT "shadow_comparisons_deployment_id_fkey" FOREIGN KEY ("deployment_id") REFERENCES "public"."shadow_deployments"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "shadow_deployments_project_id_span_path_idx" ON "shadow_deployments" USING btree ("project_id","span_path");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "shadow_comparisons_deployment_id_created_at_idx" ON "shadow_comparisons" USING btree ("deployment_id","created_at");
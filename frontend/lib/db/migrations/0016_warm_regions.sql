@@ -0,0 +1,5 @@
+ALTER TABLE "traces" ADD COLUMN "sdk_language" text;--> statement-breakpoint
+ALTER TABLE "traces" ADD COLUMN "sdk_version" text;--> statement-breakpoint
+ALTER TABLE "traces" ADD COLUMN "environment" text;--> statement-breakpoint
+ALTER TABLE "traces" ADD COLUMN "region" text;--> statement-breakpoint
+ALTER TABLE "projects" ADD COLUMN "collect_client_region" boolean DEFAULT false NOT NULL;
@@ -0,0 +1,18 @@
+CREATE TYPE "public"."backfill_status" AS ENUM('PENDING', 'RUNNING', 'DONE', 'FAILED');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "promoted_attributes" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"attribute_key" text NOT NULL,
+	"slot" integer NOT NULL,
+	"backfill_status" "backfill_status" DEFAULT 'PENDING' NOT NULL,
+	"backfilled_spans" bigint DEFAULT '0' NOT NULL,
+	CONSTRAINT "promoted_attributes_project_id_slot_key" UNIQUE("project_id","slot"),
+	CONSTRAINT "promoted_attributes_project_id_attribute_key_key" UNIQUE("project_id","attribute_key")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "promoted_attributes" ADD CONSTRAINT "promoted_attributes_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
@@ -0,0 +1,17 @@
+ALTER TYPE "public"."workspace_role" ADD VALUE 'viewer';--> statement-breakpoint
+CREATE TYPE "public"."sensitive_field" AS ENUM('SPAN_INPUT', 'SPAN_OUTPUT', 'USER_ID');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "field_visibility_rules" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"role" "workspace_role" NOT NULL,
+	"field" "sensitive_field" NOT NULL,
+	"visible" boolean NOT NULL,
+	CONSTRAINT "field_visibility_rules_project_id_role_field_key" UNIQUE("project_id","role","field")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "field_visibility_rules" ADD CONSTRAINT "field_visibility_rules_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
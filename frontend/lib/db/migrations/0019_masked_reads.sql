@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS "masking_profiles" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"role" "workspace_role" NOT NULL,
+	"hash_user_ids" boolean DEFAULT false NOT NULL,
+	"redact_emails" boolean DEFAULT false NOT NULL,
+	CONSTRAINT "masking_profiles_project_id_role_key" UNIQUE("project_id","role")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "masking_profiles" ADD CONSTRAINT "masking_profiles_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
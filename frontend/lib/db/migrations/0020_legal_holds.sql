@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS "legal_holds" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"reason" text NOT NULL,
+	"placed_by" uuid NOT NULL,
+	"released_at" timestamp with time zone,
+	"released_by" uuid
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "legal_hold_audit_log" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"user_id" uuid,
+	"action" text NOT NULL,
+	"details" jsonb DEFAULT '{}'::jsonb NOT NULL
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "legal_holds" ADD CONSTRAINT "legal_holds_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE UNIQUE INDEX IF NOT EXISTS "legal_holds_active_project_id_idx" ON "legal_holds" USING btree ("project_id") WHERE "released_at" IS NULL;--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "legal_hold_audit_log_project_id_created_at_idx" ON "legal_hold_audit_log" USING btree ("project_id","created_at");
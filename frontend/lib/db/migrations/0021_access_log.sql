@@ -0,0 +1,11 @@
+CREATE TYPE "public"."accessed_resource_type" AS ENUM('TRACE', 'SPAN', 'DATAPOINT');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "data_access_log" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"user_id" uuid NOT NULL,
+	"resource_type" "accessed_resource_type" NOT NULL,
+	"resource_id" uuid NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "data_access_log_project_id_created_at_idx" ON "data_access_log" USING btree ("project_id","created_at");
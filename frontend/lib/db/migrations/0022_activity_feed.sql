@@ -0,0 +1,19 @@
+CREATE TYPE "public"."activity_type" AS ENUM('EVALUATION_RUN', 'DATASET_MODIFIED', 'PROMPT_DEPLOYED', 'ALERT_FIRED');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "project_activity" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"activity_type" "activity_type" NOT NULL,
+	"actor_id" uuid,
+	"resource_id" uuid,
+	"summary" text NOT NULL,
+	"details" jsonb DEFAULT '{}'::jsonb NOT NULL
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "project_activity" ADD CONSTRAINT "project_activity_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "project_activity_project_id_created_at_idx" ON "project_activity" USING btree ("project_id","created_at");
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS "custom_metrics" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"name" text NOT NULL,
+	"expression" text NOT NULL,
+	"description" text,
+	CONSTRAINT "custom_metrics_project_id_name_key" UNIQUE("project_id","name")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "custom_metrics" ADD CONSTRAINT "custom_metrics_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
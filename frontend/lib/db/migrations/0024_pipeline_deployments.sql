@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS "pipeline_deployments" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"updated_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"pipeline_id" uuid NOT NULL,
+	"target" text NOT NULL,
+	"pipeline_version_id" uuid NOT NULL,
+	"previous_pipeline_version_id" uuid,
+	"deployed_by" uuid,
+	CONSTRAINT "pipeline_deployments_pipeline_id_target_key" UNIQUE("pipeline_id","target")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_deployments" ADD CONSTRAINT "pipeline_deployments_pipeline_id_fkey" FOREIGN KEY ("pipeline_id") REFERENCES "public"."pipelines"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_deployments" ADD CONSTRAINT "pipeline_deployments_pipeline_version_id_fkey" FOREIGN KEY ("pipeline_version_id") REFERENCES "public"."pipeline_versions"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_deployments" ADD CONSTRAINT "pipeline_deployments_previous_pipeline_version_id_fkey" FOREIGN KEY ("previous_pipeline_version_id") REFERENCES "public"."pipeline_versions"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
@@ -0,0 +1,30 @@
+ALTER TABLE "pipelines" ADD COLUMN "monthly_execution_quota" bigint;--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "pipeline_node_usage" (
+	"pipeline_id" uuid NOT NULL,
+	"month_start" timestamp with time zone NOT NULL,
+	"node_name" text NOT NULL,
+	"node_type" text NOT NULL,
+	"execution_count" bigint DEFAULT '0' NOT NULL,
+	"total_token_count" bigint DEFAULT '0' NOT NULL,
+	"approximate_cost" double precision DEFAULT '0' NOT NULL,
+	CONSTRAINT "pipeline_node_usage_pkey" PRIMARY KEY("pipeline_id","month_start","node_name")
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "pipeline_execution_usage" (
+	"pipeline_id" uuid NOT NULL,
+	"month_start" timestamp with time zone NOT NULL,
+	"execution_count" bigint DEFAULT '0' NOT NULL,
+	CONSTRAINT "pipeline_execution_usage_pkey" PRIMARY KEY("pipeline_id","month_start")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_node_usage" ADD CONSTRAINT "pipeline_node_usage_pipeline_id_fkey" FOREIGN KEY ("pipeline_id") REFERENCES "public"."pipelines"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_execution_usage" ADD CONSTRAINT "pipeline_execution_usage_pipeline_id_fkey" FOREIGN KEY ("pipeline_id") REFERENCES "public"."pipelines"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
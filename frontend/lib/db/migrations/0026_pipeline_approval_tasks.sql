@@ -0,0 +1,22 @@
+CREATE TYPE "public"."approval_task_status" AS ENUM('PENDING', 'APPROVED', 'REJECTED', 'EXPIRED');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "pipeline_approval_tasks" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"node_name" text NOT NULL,
+	"instructions" text DEFAULT '' NOT NULL,
+	"payload" jsonb NOT NULL,
+	"status" "approval_task_status" DEFAULT 'PENDING' NOT NULL,
+	"resolved_payload" jsonb,
+	"comment" text,
+	"resolved_by" uuid,
+	"resolved_at" timestamp with time zone
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_approval_tasks" ADD CONSTRAINT "pipeline_approval_tasks_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "pipeline_approval_tasks_project_id_status_idx" ON "pipeline_approval_tasks" USING btree ("project_id","status");
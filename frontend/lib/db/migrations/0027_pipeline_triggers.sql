@@ -0,0 +1,59 @@
+CREATE TYPE "public"."pipeline_trigger_type" AS ENUM('SCHEDULE', 'DATASET_UPDATED', 'WEBHOOK');--> statement-breakpoint
+CREATE TYPE "public"."pipeline_trigger_run_status" AS ENUM('QUEUED', 'RUNNING', 'SUCCEEDED', 'FAILED');--> statement-breakpoint
+ALTER TYPE "public"."activity_type" ADD VALUE 'PIPELINE_TRIGGER_FAILED';--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "pipeline_triggers" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"pipeline_id" uuid NOT NULL,
+	"target" text DEFAULT 'production' NOT NULL,
+	"trigger_type" "pipeline_trigger_type" NOT NULL,
+	"cron_schedule" text,
+	"dataset_id" uuid,
+	"webhook_token" text,
+	"inputs" jsonb DEFAULT '{}'::jsonb NOT NULL,
+	"enabled" boolean DEFAULT true NOT NULL,
+	"next_run_at" timestamp with time zone,
+	"created_by" uuid,
+	CONSTRAINT "pipeline_triggers_webhook_token_key" UNIQUE("webhook_token")
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "pipeline_trigger_runs" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"trigger_id" uuid NOT NULL,
+	"status" "pipeline_trigger_run_status" DEFAULT 'QUEUED' NOT NULL,
+	"event" jsonb DEFAULT '{}'::jsonb NOT NULL,
+	"outputs" jsonb,
+	"error" text,
+	"started_at" timestamp with time zone,
+	"finished_at" timestamp with time zone
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_triggers" ADD CONSTRAINT "pipeline_triggers_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_triggers" ADD CONSTRAINT "pipeline_triggers_pipeline_id_fkey" FOREIGN KEY ("pipeline_id") REFERENCES "public"."pipelines"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_triggers" ADD CONSTRAINT "pipeline_triggers_dataset_id_fkey" FOREIGN KEY ("dataset_id") REFERENCES "public"."datasets"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "pipeline_trigger_runs" ADD CONSTRAINT "pipeline_trigger_runs_trigger_id_fkey" FOREIGN KEY ("trigger_id") REFERENCES "public"."pipeline_triggers"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "pipeline_triggers_next_run_at_idx" ON "pipeline_triggers" USING btree ("next_run_at");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "pipeline_trigger_runs_trigger_id_created_at_idx" ON "pipeline_trigger_runs" USING btree ("trigger_id","created_at");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "pipeline_trigger_runs_status_created_at_idx" ON "pipeline_trigger_runs" USING btree ("status","created_at");
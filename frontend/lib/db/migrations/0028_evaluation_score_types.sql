@@ -0,0 +1,4 @@
+CREATE TYPE "public"."evaluation_score_type" AS ENUM('NUMERIC', 'BOOLEAN', 'CATEGORICAL');--> statement-breakpoint
+ALTER TABLE "evaluation_scores" ALTER COLUMN "score" DROP NOT NULL;--> statement-breakpoint
+ALTER TABLE "evaluation_scores" ADD COLUMN "score_type" "evaluation_score_type" DEFAULT 'NUMERIC' NOT NULL;--> statement-breakpoint
+ALTER TABLE "evaluation_scores" ADD COLUMN "label" text;
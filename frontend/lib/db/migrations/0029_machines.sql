@@ -0,0 +1,23 @@
+CREATE TYPE "public"."machine_status" AS ENUM('QUEUED', 'STARTING', 'RUNNING', 'TERMINATED', 'FAILED');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "machines" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"status" "machine_status" DEFAULT 'QUEUED' NOT NULL,
+	"provider_machine_id" text,
+	"url" text,
+	"error" text,
+	"started_at" timestamp with time zone,
+	"last_health_check_at" timestamp with time zone,
+	"terminated_at" timestamp with time zone
+);
+--> statement-breakpoint
+ALTER TABLE "projects" ADD COLUMN "machine_limit" integer;--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "machines" ADD CONSTRAINT "machines_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "machines_status_created_at_idx" ON "machines" USING btree ("status","created_at");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "machines_project_id_created_at_idx" ON "machines" USING btree ("project_id","created_at");
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS "evaluation_group_baselines" (
+	"project_id" uuid NOT NULL,
+	"group_id" text NOT NULL,
+	"evaluation_id" uuid NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	CONSTRAINT "evaluation_group_baselines_pkey" PRIMARY KEY("project_id","group_id")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_group_baselines" ADD CONSTRAINT "evaluation_group_baselines_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_group_baselines" ADD CONSTRAINT "evaluation_group_baselines_evaluation_id_fkey" FOREIGN KEY ("evaluation_id") REFERENCES "public"."evaluations"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
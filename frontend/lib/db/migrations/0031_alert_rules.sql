@@ -0,0 +1,46 @@
+CREATE TYPE "public"."alert_score_source" AS ENUM('EVALUATION', 'ONLINE');--> statement-breakpoint
+CREATE TYPE "public"."alert_comparison" AS ENUM('BELOW', 'ABOVE');--> statement-breakpoint
+CREATE TYPE "public"."alert_state" AS ENUM('RESOLVED', 'FIRING');--> statement-breakpoint
+CREATE TYPE "public"."alert_channel_type" AS ENUM('WEBHOOK', 'SLACK', 'EMAIL');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "alert_rules" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"name" text NOT NULL,
+	"source" "alert_score_source" NOT NULL,
+	"score_name" text NOT NULL,
+	"group_id" text,
+	"comparison" "alert_comparison" NOT NULL,
+	"threshold" double precision NOT NULL,
+	"window_minutes" integer DEFAULT 60 NOT NULL,
+	"state" "alert_state" DEFAULT 'RESOLVED' NOT NULL,
+	"last_value" double precision,
+	"last_evaluated_at" timestamp with time zone,
+	"state_changed_at" timestamp with time zone
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "alert_channels" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"rule_id" uuid NOT NULL,
+	"channel_type" "alert_channel_type" NOT NULL,
+	"target_preview" text NOT NULL,
+	"target_nonce" text NOT NULL,
+	"target_value" text NOT NULL
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "alert_rules" ADD CONSTRAINT "alert_rules_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "alert_channels" ADD CONSTRAINT "alert_channels_rule_id_fkey" FOREIGN KEY ("rule_id") REFERENCES "public"."alert_rules"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "alert_rules_project_id_source_idx" ON "alert_rules" USING btree ("project_id","source");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "alert_rules_source_last_evaluated_at_idx" ON "alert_rules" USING btree ("source","last_evaluated_at");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "alert_channels_rule_id_idx" ON "alert_channels" USING btree ("rule_id");
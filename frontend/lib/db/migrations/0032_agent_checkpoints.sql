@@ -0,0 +1,22 @@
+ALTER TABLE "projects" ADD COLUMN "agent_checkpoint_interval" integer DEFAULT 5 NOT NULL;--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "agent_checkpoints" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"trace_id" uuid NOT NULL,
+	"span_id" uuid NOT NULL,
+	"span_path" text,
+	"step" integer NOT NULL,
+	"state" jsonb NOT NULL,
+	"browser_session_id" text,
+	"resume_count" integer DEFAULT 0 NOT NULL,
+	"resumed_at" timestamp with time zone
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "agent_checkpoints" ADD CONSTRAINT "agent_checkpoints_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "agent_checkpoints_project_id_trace_id_step_idx" ON "agent_checkpoints" USING btree ("project_id","trace_id","step");
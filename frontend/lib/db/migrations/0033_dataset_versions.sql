@@ -0,0 +1,46 @@
+ALTER TABLE "datasets" ADD COLUMN "version" integer DEFAULT 0 NOT NULL;--> statement-breakpoint
+ALTER TABLE "evaluations" ADD COLUMN "dataset_id" uuid;--> statement-breakpoint
+ALTER TABLE "evaluations" ADD COLUMN "dataset_version" integer;--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "dataset_versions" (
+	"dataset_id" uuid NOT NULL,
+	"version" integer NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"action" text NOT NULL,
+	CONSTRAINT "dataset_versions_pkey" PRIMARY KEY("dataset_id","version")
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "dataset_datapoint_revisions" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"dataset_id" uuid NOT NULL,
+	"datapoint_id" uuid NOT NULL,
+	"datapoint_created_at" timestamp with time zone NOT NULL,
+	"index_in_batch" bigint,
+	"data" jsonb NOT NULL,
+	"target" jsonb,
+	"metadata" jsonb,
+	"added_in_version" integer NOT NULL,
+	"removed_in_version" integer
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluations" ADD CONSTRAINT "evaluations_dataset_id_fkey" FOREIGN KEY ("dataset_id") REFERENCES "public"."datasets"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "dataset_versions" ADD CONSTRAINT "dataset_versions_dataset_id_fkey" FOREIGN KEY ("dataset_id") REFERENCES "public"."datasets"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "dataset_datapoint_revisions" ADD CONSTRAINT "dataset_datapoint_revisions_dataset_id_fkey" FOREIGN KEY ("dataset_id") REFERENCES "public"."datasets"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "dataset_datapoint_revisions_dataset_id_added_in_version_idx" ON "dataset_datapoint_revisions" USING btree ("dataset_id","added_in_version");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "dataset_datapoint_revisions_datapoint_id_idx" ON "dataset_datapoint_revisions" USING btree ("datapoint_id");--> statement-breakpoint
+INSERT INTO "dataset_versions" ("dataset_id", "version", "action") SELECT "id", 0, 'initial' FROM "datasets";--> statement-breakpoint
+INSERT INTO "dataset_datapoint_revisions" ("dataset_id", "datapoint_id", "datapoint_created_at", "index_in_batch", "data", "target", "metadata", "added_in_version") SELECT "dataset_id", "id", "created_at", "index_in_batch", "data", "target", "metadata", 0 FROM "dataset_datapoints";
@@ -0,0 +1,32 @@
+CREATE TYPE "public"."dataset_import_status" AS ENUM('PENDING', 'RUNNING', 'DONE', 'FAILED');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "dataset_imports" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"dataset_id" uuid NOT NULL,
+	"source" text NOT NULL,
+	"repo_id" text NOT NULL,
+	"config" text NOT NULL,
+	"split" text NOT NULL,
+	"mapping" jsonb NOT NULL,
+	"status" "dataset_import_status" DEFAULT 'PENDING' NOT NULL,
+	"imported_rows" bigint DEFAULT '0' NOT NULL,
+	"failed_rows" bigint DEFAULT '0' NOT NULL,
+	"total_rows" bigint,
+	"error" text,
+	"updated_at" timestamp with time zone DEFAULT now() NOT NULL
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "dataset_imports" ADD CONSTRAINT "dataset_imports_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "dataset_imports" ADD CONSTRAINT "dataset_imports_dataset_id_fkey" FOREIGN KEY ("dataset_id") REFERENCES "public"."datasets"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "dataset_imports_dataset_id_idx" ON "dataset_imports" USING btree ("dataset_id");
@@ -0,0 +1,17 @@
+ALTER TABLE "labeling_queue_items" ADD COLUMN "assigned_to" uuid;--> statement-breakpoint
+ALTER TABLE "labeling_queue_items" ADD COLUMN "locked_until" timestamp with time zone;--> statement-breakpoint
+ALTER TABLE "labeling_queue_items" ADD COLUMN "completed_at" timestamp with time zone;--> statement-breakpoint
+ALTER TABLE "labeling_queue_items" ADD COLUMN "completed_by" uuid;--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "labeling_queue_items" ADD CONSTRAINT "labeling_queue_items_assigned_to_fkey" FOREIGN KEY ("assigned_to") REFERENCES "public"."users"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "labeling_queue_items" ADD CONSTRAINT "labeling_queue_items_completed_by_fkey" FOREIGN KEY ("completed_by") REFERENCES "public"."users"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "labeling_queue_items_queue_id_created_at_idx" ON "labeling_queue_items" USING btree ("queue_id","created_at");
@@ -0,0 +1,17 @@
+CREATE TYPE "public"."sampling_exemption_kind" AS ENUM('USER_ID', 'SPAN_PATH', 'DEBUG_FLAG');--> statement-breakpoint
+ALTER TABLE "projects" ADD COLUMN "span_sample_rate" double precision DEFAULT '1' NOT NULL;--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "sampling_exemptions" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"kind" "sampling_exemption_kind" NOT NULL,
+	"value" text NOT NULL
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "sampling_exemptions" ADD CONSTRAINT "sampling_exemptions_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE UNIQUE INDEX IF NOT EXISTS "sampling_exemptions_project_id_kind_value_idx" ON "sampling_exemptions" USING btree ("project_id","kind","value");
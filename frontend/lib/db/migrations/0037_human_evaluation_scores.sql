@@ -0,0 +1,8 @@
+CREATE TYPE "public"."evaluation_score_source" AS ENUM('AUTO', 'HUMAN');--> statement-breakpoint
+ALTER TABLE "evaluation_scores" ADD COLUMN "source" "evaluation_score_source" DEFAULT 'AUTO' NOT NULL;--> statement-breakpoint
+ALTER TABLE "evaluation_scores" ADD COLUMN "user_id" uuid;--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_scores" ADD CONSTRAINT "evaluation_scores_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
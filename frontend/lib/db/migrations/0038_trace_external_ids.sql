@@ -0,0 +1,2 @@
+ALTER TABLE "traces" ADD COLUMN "external_id" text;--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "traces_project_id_external_id_idx" ON "traces" USING btree ("project_id","external_id") WHERE (external_id IS NOT NULL);
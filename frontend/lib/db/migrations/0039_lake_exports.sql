@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS "lake_exports" (
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid PRIMARY KEY NOT NULL,
+	"bucket" text NOT NULL,
+	"prefix" text DEFAULT '' NOT NULL,
+	"region" text NOT NULL,
+	"access_key_id" text NOT NULL,
+	"secret_nonce" text NOT NULL,
+	"secret_value" text NOT NULL,
+	"enabled" boolean DEFAULT true NOT NULL,
+	"exported_until" timestamp with time zone NOT NULL,
+	"last_run_at" timestamp with time zone,
+	"last_error" text
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "lake_exports" ADD CONSTRAINT "lake_exports_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
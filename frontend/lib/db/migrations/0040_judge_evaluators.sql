@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS "judge_evaluators" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"name" text NOT NULL,
+	"model" text NOT NULL,
+	"prompt" text NOT NULL,
+	"score_type" "evaluation_score_type" DEFAULT 'NUMERIC' NOT NULL,
+	"labels" text[] DEFAULT '{}' NOT NULL,
+	CONSTRAINT "judge_evaluators_project_id_name_key" UNIQUE("project_id","name")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "judge_evaluators" ADD CONSTRAINT "judge_evaluators_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
@@ -0,0 +1,23 @@
+CREATE TYPE "public"."warehouse_destination" AS ENUM('BIGQUERY', 'SNOWFLAKE');--> statement-breakpoint
+CREATE TYPE "public"."warehouse_sync_mode" AS ENUM('AGGREGATED', 'RAW');--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "warehouse_syncs" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"destination" "warehouse_destination" NOT NULL,
+	"mode" "warehouse_sync_mode" DEFAULT 'AGGREGATED' NOT NULL,
+	"config" jsonb NOT NULL,
+	"credentials_nonce" text NOT NULL,
+	"credentials_value" text NOT NULL,
+	"enabled" boolean DEFAULT true NOT NULL,
+	"synced_until" timestamp with time zone NOT NULL,
+	"last_run_at" timestamp with time zone,
+	"last_error" text,
+	CONSTRAINT "warehouse_syncs_project_id_destination_key" UNIQUE("project_id","destination")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "warehouse_syncs" ADD CONSTRAINT "warehouse_syncs_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
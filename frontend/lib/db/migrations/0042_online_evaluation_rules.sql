@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS "online_evaluation_rules" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"name" text NOT NULL,
+	"span_path" text,
+	"span_name" text,
+	"attribute_filters" jsonb DEFAULT '{}'::jsonb NOT NULL,
+	"sample_percentage" double precision DEFAULT '100' NOT NULL,
+	"evaluator" jsonb NOT NULL,
+	"enabled" boolean DEFAULT true NOT NULL,
+	CONSTRAINT "online_evaluation_rules_project_id_name_key" UNIQUE("project_id","name")
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "online_evaluation_rules" ADD CONSTRAINT "online_evaluation_rules_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
@@ -0,0 +1,71 @@
+CREATE TYPE "public"."evaluation_schedule_run_status" AS ENUM('QUEUED', 'RUNNING', 'SUCCEEDED', 'FAILED', 'SKIPPED');--> statement-breakpoint
+ALTER TYPE "public"."activity_type" ADD VALUE 'EVALUATION_SCHEDULE_FAILED';--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "evaluation_schedules" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"project_id" uuid NOT NULL,
+	"name" text NOT NULL,
+	"cron_schedule" text NOT NULL,
+	"dataset_id" uuid NOT NULL,
+	"pipeline_id" uuid NOT NULL,
+	"target" text DEFAULT 'production' NOT NULL,
+	"pipeline_version_id" uuid,
+	"judges" text[] DEFAULT '{}' NOT NULL,
+	"group_id" text NOT NULL,
+	"enabled" boolean DEFAULT true NOT NULL,
+	"next_run_at" timestamp with time zone,
+	"created_by" uuid,
+	CONSTRAINT "evaluation_schedules_project_id_name_key" UNIQUE("project_id","name")
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS "evaluation_schedule_runs" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"schedule_id" uuid NOT NULL,
+	"status" "evaluation_schedule_run_status" DEFAULT 'QUEUED' NOT NULL,
+	"scheduled_at" timestamp with time zone,
+	"evaluation_id" uuid,
+	"error" text,
+	"started_at" timestamp with time zone,
+	"finished_at" timestamp with time zone
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_schedules" ADD CONSTRAINT "evaluation_schedules_project_id_fkey" FOREIGN KEY ("project_id") REFERENCES "public"."projects"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_schedules" ADD CONSTRAINT "evaluation_schedules_dataset_id_fkey" FOREIGN KEY ("dataset_id") REFERENCES "public"."datasets"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_schedules" ADD CONSTRAINT "evaluation_schedules_pipeline_id_fkey" FOREIGN KEY ("pipeline_id") REFERENCES "public"."pipelines"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_schedules" ADD CONSTRAINT "evaluation_schedules_pipeline_version_id_fkey" FOREIGN KEY ("pipeline_version_id") REFERENCES "public"."pipeline_versions"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_schedule_runs" ADD CONSTRAINT "evaluation_schedule_runs_schedule_id_fkey" FOREIGN KEY ("schedule_id") REFERENCES "public"."evaluation_schedules"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "evaluation_schedule_runs" ADD CONSTRAINT "evaluation_schedule_runs_evaluation_id_fkey" FOREIGN KEY ("evaluation_id") REFERENCES "public"."evaluations"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "evaluation_schedules_next_run_at_idx" ON "evaluation_schedules" USING btree ("next_run_at");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "evaluation_schedule_runs_schedule_id_created_at_idx" ON "evaluation_schedule_runs" USING btree ("schedule_id","created_at");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "evaluation_schedule_runs_status_created_at_idx" ON "evaluation_schedule_runs" USING btree ("status","created_at");
@@ -0,0 +1,5 @@
+ALTER TABLE "members_of_organizations" ADD COLUMN "scim_external_id" text;--> statement-breakpoint
+ALTER TABLE "members_of_organizations" ADD COLUMN "active" boolean DEFAULT true NOT NULL;--> statement-breakpoint
+UPDATE "members_of_organizations" SET "scim_external_id" = "users"."scim_external_id", "active" = "users"."active" FROM "users" WHERE "users"."id" = "members_of_organizations"."user_id";--> statement-breakpoint
+ALTER TABLE "users" DROP COLUMN IF EXISTS "scim_external_id";--> statement-breakpoint
+ALTER TABLE "users" DROP COLUMN IF EXISTS "active";
@@ -0,0 +1 @@
+ALTER TABLE "organizations" DROP COLUMN IF EXISTS "min_retention_days";
\ No newline at end of file
@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS "alert_incidents" (
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"created_at" timestamp with time zone DEFAULT now() NOT NULL,
+	"rule_id" uuid NOT NULL,
+	"value" double precision NOT NULL,
+	"evaluation_id" uuid,
+	"window_start" timestamp with time zone,
+	"window_end" timestamp with time zone,
+	"summary" jsonb,
+	"summary_claimed_at" timestamp with time zone,
+	"summarized_at" timestamp with time zone
+);
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "alert_incidents" ADD CONSTRAINT "alert_incidents_rule_id_fkey" FOREIGN KEY ("rule_id") REFERENCES "public"."alert_rules"("id") ON DELETE cascade ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+DO $$ BEGIN
+ ALTER TABLE "alert_incidents" ADD CONSTRAINT "alert_incidents_evaluation_id_fkey" FOREIGN KEY ("evaluation_id") REFERENCES "public"."evaluations"("id") ON DELETE set null ON UPDATE cascade;
+EXCEPTION
+ WHEN duplicate_object THEN null;
+END $$;
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "alert_incidents_rule_id_created_at_idx" ON "alert_incidents" USING btree ("rule_id","created_at");--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS "alert_incidents_summarized_at_idx" ON "alert_incidents" USING btree ("summarized_at");
\ No newline at end of file
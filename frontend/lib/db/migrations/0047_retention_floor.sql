@@ -0,0 +1,3 @@
+ALTER TABLE "organizations" ADD COLUMN "min_retention_days" bigint;--> statement-breakpoint
+ALTER TABLE "workspaces" ADD COLUMN "retention_days" bigint;--> statement-breakpoint
+ALTER TABLE "projects" ADD COLUMN "retention_days" bigint;
\ No newline at end of file
@@ -0,0 +1,8 @@
+ALTER TABLE "members_of_workspaces" ADD COLUMN "scim_provisioned" boolean DEFAULT false NOT NULL;--> statement-breakpoint
+UPDATE "members_of_workspaces" SET "scim_provisioned" = true
+FROM "scim_group_members", "scim_groups"
+WHERE "scim_group_members"."group_id" = "scim_groups"."id"
+	AND "scim_groups"."workspace_id" = "members_of_workspaces"."workspace_id"
+	AND "scim_group_members"."user_id" = "members_of_workspaces"."user_id"
+	AND "scim_groups"."member_role" = "members_of_workspaces"."member_role"
+	AND "members_of_workspaces"."member_role" <> 'owner';
\ No newline at end of file
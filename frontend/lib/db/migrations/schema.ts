@@ -1,4 +1,4 @@
-import { pgTable, foreignKey, unique, uuid, timestamp, index, text, jsonb, bigint, boolean, doublePrecision, integer, primaryKey, pgEnum } from "drizzle-orm/pg-core";
+import { pgTable, foreignKey, unique, uuid, timestamp, index, uniqueIndex, text, jsonb, bigint, boolean, doublePrecision, integer, primaryKey, pgEnum } from "drizzle-orm/pg-core";
 import { sql } from "drizzle-orm";
 
 export const eventSource = pgEnum("event_source", ['AUTO', 'MANUAL', 'CODE']);
@@ -8,7 +8,34 @@ export const labelSource = pgEnum("label_source", ['MANUAL', 'AUTO']);
 export const labelType = pgEnum("label_type", ['BOOLEAN', 'CATEGORICAL']);
 export const spanType = pgEnum("span_type", ['DEFAULT', 'LLM', 'PIPELINE', 'EXECUTOR', 'EVALUATOR', 'EVALUATION']);
 export const traceType = pgEnum("trace_type", ['DEFAULT', 'EVENT', 'EVALUATION']);
-export const workspaceRole = pgEnum("workspace_role", ['member', 'owner']);
+export const workspaceRole = pgEnum("workspace_role", ['member', 'owner', 'viewer']);
+export const organizationRole = pgEnum("organization_role", ['member', 'admin', 'owner']);
+export const tokenScope = pgEnum("token_scope", ['read', 'write']);
+export const taggedResourceType = pgEnum("tagged_resource_type", ['DATASET', 'EVALUATION', 'PIPELINE']);
+export const commentTargetType = pgEnum("comment_target_type", ['TRACE', 'SPAN', 'EVALUATION_RESULT', 'DATAPOINT']);
+export const issueTrackerProvider = pgEnum("issue_tracker_provider", ['JIRA', 'LINEAR']);
+export const linkedIssueResourceType = pgEnum("linked_issue_resource_type", ['TRACE', 'EVALUATION_RESULT']);
+export const evalProposalStatus = pgEnum("eval_proposal_status", ['GENERATING', 'READY', 'FAILED', 'ADOPTED']);
+export const shadowVerdict = pgEnum("shadow_verdict", ['CANDIDATE', 'PRODUCTION', 'TIE']);
+export const backfillStatus = pgEnum("backfill_status", ['PENDING', 'RUNNING', 'DONE', 'FAILED']);
+export const sensitiveField = pgEnum("sensitive_field", ['SPAN_INPUT', 'SPAN_OUTPUT', 'USER_ID']);
+export const accessedResourceType = pgEnum("accessed_resource_type", ['TRACE', 'SPAN', 'DATAPOINT']);
+export const datasetImportStatus = pgEnum("dataset_import_status", ['PENDING', 'RUNNING', 'DONE', 'FAILED']);
+export const samplingExemptionKind = pgEnum("sampling_exemption_kind", ['USER_ID', 'SPAN_PATH', 'DEBUG_FLAG']);
+export const activityType = pgEnum("activity_type", ['EVALUATION_RUN', 'DATASET_MODIFIED', 'PROMPT_DEPLOYED', 'ALERT_FIRED', 'PIPELINE_TRIGGER_FAILED', 'EVALUATION_SCHEDULE_FAILED']);
+export const approvalTaskStatus = pgEnum("approval_task_status", ['PENDING', 'APPROVED', 'REJECTED', 'EXPIRED']);
+export const pipelineTriggerType = pgEnum("pipeline_trigger_type", ['SCHEDULE', 'DATASET_UPDATED', 'WEBHOOK']);
+export const pipelineTriggerRunStatus = pgEnum("pipeline_trigger_run_status", ['QUEUED', 'RUNNING', 'SUCCEEDED', 'FAILED']);
+export const evaluationScoreType = pgEnum("evaluation_score_type", ['NUMERIC', 'BOOLEAN', 'CATEGORICAL']);
+export const evaluationScoreSource = pgEnum("evaluation_score_source", ['AUTO', 'HUMAN']);
+export const machineStatus = pgEnum("machine_status", ['QUEUED', 'STARTING', 'RUNNING', 'TERMINATED', 'FAILED']);
+export const alertScoreSource = pgEnum("alert_score_source", ['EVALUATION', 'ONLINE']);
+export const alertComparison = pgEnum("alert_comparison", ['BELOW', 'ABOVE']);
+export const alertState = pgEnum("alert_state", ['RESOLVED', 'FIRING']);
+export const alertChannelType = pgEnum("alert_channel_type", ['WEBHOOK', 'SLACK', 'EMAIL']);
+export const warehouseDestination = pgEnum("warehouse_destination", ['BIGQUERY', 'SNOWFLAKE']);
+export const warehouseSyncMode = pgEnum("warehouse_sync_mode", ['AGGREGATED', 'RAW']);
+export const evaluationScheduleRunStatus = pgEnum("evaluation_schedule_run_status", ['QUEUED', 'RUNNING', 'SUCCEEDED', 'FAILED', 'SKIPPED']);
 
 
 
@@ -54,11 +81,17 @@ export const traces = pgTable("traces", {
   outputTokenCount: bigint("output_token_count", { mode: "number" }).default(sql`'0'`).notNull(),
   inputCost: doublePrecision("input_cost").default(sql`'0'`).notNull(),
   outputCost: doublePrecision("output_cost").default(sql`'0'`).notNull(),
+  sdkLanguage: text("sdk_language"),
+  sdkVersion: text("sdk_version"),
+  environment: text(),
+  region: text(),
+  externalId: text("external_id"),
 },
 (table) => ({
   idProjectIdStartTimeTimesNotNullIdx: index("traces_id_project_id_start_time_times_not_null_idx").using("btree", table.id.asc().nullsLast(), table.projectId.asc().nullsLast(), table.startTime.desc().nullsFirst()).where(sql`((start_time IS NOT NULL) AND (end_time IS NOT NULL))`),
   projectIdIdx: index("traces_project_id_idx").using("btree", table.projectId.asc().nullsLast()),
   sessionIdIdx: index("traces_session_id_idx").using("btree", table.sessionId.asc().nullsLast()),
+  projectIdExternalIdIdx: index("traces_project_id_external_id_idx").using("btree", table.projectId.asc().nullsLast(), table.externalId.asc().nullsLast()).where(sql`(external_id IS NOT NULL)`),
   startTimeEndTimeIdx: index("traces_start_time_end_time_idx").using("btree", table.startTime.asc().nullsLast(), table.endTime.asc().nullsLast()),
   newTracesProjectIdFkey: foreignKey({
     columns: [table.projectId],
@@ -171,6 +204,8 @@ export const evaluations = pgTable("evaluations", {
   name: text().notNull(),
   metadata: jsonb(),
   groupId: text("group_id").default('default').notNull(),
+  datasetId: uuid("dataset_id"),
+  datasetVersion: integer("dataset_version"),
 },
 (table) => ({
   evaluationsProjectIdFkey1: foreignKey({
@@ -178,6 +213,11 @@ export const evaluations = pgTable("evaluations", {
     foreignColumns: [projects.id],
     name: "evaluations_project_id_fkey1"
   }).onUpdate("cascade").onDelete("cascade"),
+  evaluationsDatasetIdFkey: foreignKey({
+    columns: [table.datasetId],
+    foreignColumns: [datasets.id],
+    name: "evaluations_dataset_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
 }));
 
 export const subscriptionTiers = pgTable("subscription_tiers", {
@@ -235,7 +275,11 @@ export const evaluationScores = pgTable("evaluation_scores", {
   createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
   resultId: uuid("result_id").defaultRandom().notNull(),
   name: text().default('').notNull(),
-  score: doublePrecision().notNull(),
+  score: doublePrecision(),
+  scoreType: evaluationScoreType("score_type").default('NUMERIC').notNull(),
+  label: text(),
+  source: evaluationScoreSource().default('AUTO').notNull(),
+  userId: uuid("user_id"),
 },
 (table) => ({
   resultIdIdx: index("evaluation_scores_result_id_idx").using("hash", table.resultId.asc().nullsLast()),
@@ -244,6 +288,11 @@ export const evaluationScores = pgTable("evaluation_scores", {
     foreignColumns: [evaluationResults.id],
     name: "evaluation_scores_result_id_fkey"
   }).onUpdate("cascade").onDelete("cascade"),
+  evaluationScoresUserIdFkey: foreignKey({
+    columns: [table.userId],
+    foreignColumns: [users.id],
+    name: "evaluation_scores_user_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
   evaluationResultsNamesUnique: unique("evaluation_results_names_unique").on(table.resultId, table.name),
 }));
 
@@ -302,6 +351,7 @@ export const datasets = pgTable("datasets", {
   name: text().notNull(),
   projectId: uuid("project_id").defaultRandom().notNull(),
   indexedOn: text("indexed_on"),
+  version: integer().default(0).notNull(),
 },
 (table) => ({
   publicDatasetsProjectIdFkey: foreignKey({
@@ -317,13 +367,28 @@ export const labelingQueueItems = pgTable("labeling_queue_items", {
   queueId: uuid("queue_id").defaultRandom().notNull(),
   action: jsonb().notNull(),
   spanId: uuid("span_id").notNull(),
+  assignedTo: uuid("assigned_to"),
+  lockedUntil: timestamp("locked_until", { withTimezone: true, mode: 'string' }),
+  completedAt: timestamp("completed_at", { withTimezone: true, mode: 'string' }),
+  completedBy: uuid("completed_by"),
 },
 (table) => ({
+  queueIdCreatedAtIdx: index("labeling_queue_items_queue_id_created_at_idx").using("btree", table.queueId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
   labellingQueueItemsQueueIdFkey: foreignKey({
     columns: [table.queueId],
     foreignColumns: [labelingQueues.id],
     name: "labelling_queue_items_queue_id_fkey"
   }).onUpdate("cascade").onDelete("cascade"),
+  labelingQueueItemsAssignedToFkey: foreignKey({
+    columns: [table.assignedTo],
+    foreignColumns: [users.id],
+    name: "labeling_queue_items_assigned_to_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
+  labelingQueueItemsCompletedByFkey: foreignKey({
+    columns: [table.completedBy],
+    foreignColumns: [users.id],
+    name: "labeling_queue_items_completed_by_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
 }));
 
 export const membersOfWorkspaces = pgTable("members_of_workspaces", {
@@ -332,6 +397,7 @@ export const membersOfWorkspaces = pgTable("members_of_workspaces", {
   createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
   id: uuid().defaultRandom().primaryKey().notNull(),
   memberRole: workspaceRole("member_role").default('owner').notNull(),
+  scimProvisioned: boolean("scim_provisioned").default(false).notNull(),
 },
 (table) => ({
   userIdIdx: index("members_of_workspaces_user_id_idx").using("btree", table.userId.asc().nullsLast()),
@@ -355,6 +421,7 @@ export const pipelines = pgTable("pipelines", {
   name: text().notNull(),
   visibility: text().default('PRIVATE').notNull(),
   pythonRequirements: text("python_requirements").default('').notNull(),
+  monthlyExecutionQuota: bigint("monthly_execution_quota", { mode: "number" }),
 },
 (table) => ({
   nameProjectIdIdx: index("pipelines_name_project_id_idx").using("btree", table.name.asc().nullsLast(), table.projectId.asc().nullsLast()),
@@ -382,9 +449,17 @@ export const projects = pgTable("projects", {
   createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
   name: text().notNull(),
   workspaceId: uuid("workspace_id").notNull(),
+  slug: text(),
+  collectClientRegion: boolean("collect_client_region").default(false).notNull(),
+  machineLimit: integer("machine_limit"),
+  agentCheckpointInterval: integer("agent_checkpoint_interval").default(5).notNull(),
+  spanSampleRate: doublePrecision("span_sample_rate").default(sql`'1'`).notNull(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  retentionDays: bigint("retention_days", { mode: "number" }),
 },
 (table) => ({
   workspaceIdIdx: index("projects_workspace_id_idx").using("btree", table.workspaceId.asc().nullsLast()),
+  workspaceIdSlugIdx: uniqueIndex("projects_workspace_id_slug_idx").using("btree", table.workspaceId.asc().nullsLast(), table.slug.asc().nullsLast()).where(sql`(slug IS NOT NULL)`),
   projectsWorkspaceIdFkey: foreignKey({
     columns: [table.workspaceId],
     foreignColumns: [workspaces.id],
@@ -418,13 +493,68 @@ export const workspaces = pgTable("workspaces", {
   subscriptionId: text("subscription_id").default('').notNull(),
   // You can use { mode: "bigint" } if numbers are exceeding js number limitations
   additionalSeats: bigint("additional_seats", { mode: "number" }).default(sql`'0'`).notNull(),
+  organizationId: uuid("organization_id"),
+  ipAllowlist: text("ip_allowlist").array().default(sql`'{}'`).notNull(),
+  slug: text(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  retentionDays: bigint("retention_days", { mode: "number" }),
 },
 (table) => ({
+  organizationIdIdx: index("workspaces_organization_id_idx").using("btree", table.organizationId.asc().nullsLast()),
+  slugIdx: index("workspaces_slug_idx").using("btree", table.slug.asc().nullsLast()).where(sql`(slug IS NOT NULL)`),
   workspacesTierIdFkey: foreignKey({
     columns: [table.tierId],
     foreignColumns: [subscriptionTiers.id],
     name: "workspaces_tier_id_fkey"
   }).onUpdate("cascade"),
+  workspacesOrganizationIdFkey: foreignKey({
+    columns: [table.organizationId],
+    foreignColumns: [organizations.id],
+    name: "workspaces_organization_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
+}));
+
+export const organizations = pgTable("organizations", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  name: text().notNull(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  tierId: bigint("tier_id", { mode: "number" }).default(sql`'1'`).notNull(),
+  billingEmail: text("billing_email"),
+  ssoRequired: boolean("sso_required").default(false).notNull(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  minRetentionDays: bigint("min_retention_days", { mode: "number" }),
+},
+(table) => ({
+  organizationsTierIdFkey: foreignKey({
+    columns: [table.tierId],
+    foreignColumns: [subscriptionTiers.id],
+    name: "organizations_tier_id_fkey"
+  }).onUpdate("cascade"),
+}));
+
+export const membersOfOrganizations = pgTable("members_of_organizations", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  organizationId: uuid("organization_id").notNull(),
+  userId: uuid("user_id").notNull(),
+  memberRole: organizationRole("member_role").default('member').notNull(),
+  scimExternalId: text("scim_external_id"),
+  active: boolean().default(true).notNull(),
+},
+(table) => ({
+  userIdIdx: index("members_of_organizations_user_id_idx").using("btree", table.userId.asc().nullsLast()),
+  membersOfOrganizationsOrganizationIdFkey: foreignKey({
+    columns: [table.organizationId],
+    foreignColumns: [organizations.id],
+    name: "members_of_organizations_organization_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  membersOfOrganizationsUserIdFkey: foreignKey({
+    columns: [table.userId],
+    foreignColumns: [users.id],
+    name: "members_of_organizations_user_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  membersOfOrganizationsUserOrganizationUnique: unique("members_of_organizations_user_organization_unique").on(table.organizationId, table.userId),
 }));
 
 export const users = pgTable("users", {
@@ -563,3 +693,920 @@ export const spans = pgTable("spans", {
   spansPkey: primaryKey({ columns: [table.spanId, table.projectId], name: "spans_pkey"}),
   uniqueSpanIdProjectId: unique("unique_span_id_project_id").on(table.spanId, table.projectId),
 }));
+
+export const scimTokens = pgTable("scim_tokens", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  organizationId: uuid("organization_id").notNull(),
+  hash: text().notNull(),
+  shorthand: text().notNull(),
+},
+(table) => ({
+  scimTokensOrganizationIdFkey: foreignKey({
+    columns: [table.organizationId],
+    foreignColumns: [organizations.id],
+    name: "scim_tokens_organization_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  scimTokensHashUnique: unique("scim_tokens_hash_unique").on(table.hash),
+}));
+
+export const scimGroups = pgTable("scim_groups", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  organizationId: uuid("organization_id").notNull(),
+  displayName: text("display_name").notNull(),
+  externalId: text("external_id"),
+  workspaceId: uuid("workspace_id"),
+  memberRole: workspaceRole("member_role"),
+},
+(table) => ({
+  scimGroupsOrganizationIdFkey: foreignKey({
+    columns: [table.organizationId],
+    foreignColumns: [organizations.id],
+    name: "scim_groups_organization_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  scimGroupsWorkspaceIdFkey: foreignKey({
+    columns: [table.workspaceId],
+    foreignColumns: [workspaces.id],
+    name: "scim_groups_workspace_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
+  scimGroupsOrganizationIdDisplayNameUnique: unique("scim_groups_organization_id_display_name_unique").on(table.organizationId, table.displayName),
+}));
+
+export const scimGroupMembers = pgTable("scim_group_members", {
+  groupId: uuid("group_id").notNull(),
+  userId: uuid("user_id").notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+},
+(table) => ({
+  scimGroupMembersGroupIdFkey: foreignKey({
+    columns: [table.groupId],
+    foreignColumns: [scimGroups.id],
+    name: "scim_group_members_group_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  scimGroupMembersUserIdFkey: foreignKey({
+    columns: [table.userId],
+    foreignColumns: [users.id],
+    name: "scim_group_members_user_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  scimGroupMembersPkey: primaryKey({ columns: [table.groupId, table.userId], name: "scim_group_members_pkey"}),
+}));
+
+export const personalAccessTokens = pgTable("personal_access_tokens", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  userId: uuid("user_id").notNull(),
+  name: text().notNull(),
+  hash: text().notNull(),
+  shorthand: text().notNull(),
+  scope: tokenScope().default('read').notNull(),
+  expiresAt: timestamp("expires_at", { withTimezone: true, mode: 'string' }),
+  lastUsedAt: timestamp("last_used_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  personalAccessTokensUserIdFkey: foreignKey({
+    columns: [table.userId],
+    foreignColumns: [users.id],
+    name: "personal_access_tokens_user_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  personalAccessTokensHashUnique: unique("personal_access_tokens_hash_unique").on(table.hash),
+}));
+
+export const uiSessions = pgTable("ui_sessions", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  userId: uuid("user_id").notNull(),
+  expiresAt: timestamp("expires_at", { withTimezone: true, mode: 'string' }).notNull(),
+  revokedAt: timestamp("revoked_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  uiSessionsUserIdFkey: foreignKey({
+    columns: [table.userId],
+    foreignColumns: [users.id],
+    name: "ui_sessions_user_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const resourceTags = pgTable("resource_tags", {
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  resourceType: taggedResourceType("resource_type").notNull(),
+  resourceId: uuid("resource_id").notNull(),
+  tag: text().notNull(),
+},
+(table) => ({
+  projectIdTagIdx: index("resource_tags_project_id_tag_idx").using("btree", table.projectId.asc().nullsLast(), table.tag.asc().nullsLast()),
+  resourceTagsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "resource_tags_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  resourceTagsPkey: primaryKey({ columns: [table.resourceType, table.resourceId, table.tag], name: "resource_tags_pkey"}),
+}));
+
+export const comments = pgTable("comments", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  updatedAt: timestamp("updated_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  userId: uuid("user_id").notNull(),
+  targetType: commentTargetType("target_type").notNull(),
+  targetId: uuid("target_id").notNull(),
+  parentId: uuid("parent_id"),
+  content: text().notNull(),
+},
+(table) => ({
+  projectIdTargetIdx: index("comments_project_id_target_idx").using("btree", table.projectId.asc().nullsLast(), table.targetType.asc().nullsLast(), table.targetId.asc().nullsLast()),
+  commentsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "comments_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  commentsUserIdFkey: foreignKey({
+    columns: [table.userId],
+    foreignColumns: [users.id],
+    name: "comments_user_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  commentsParentIdFkey: foreignKey({
+    columns: [table.parentId],
+    foreignColumns: [table.id],
+    name: "comments_parent_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const commentMentions = pgTable("comment_mentions", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  commentId: uuid("comment_id").notNull(),
+  userId: uuid("user_id").notNull(),
+  readAt: timestamp("read_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  userIdIdx: index("comment_mentions_user_id_idx").using("btree", table.userId.asc().nullsLast()).where(sql`(read_at IS NULL)`),
+  commentMentionsCommentIdFkey: foreignKey({
+    columns: [table.commentId],
+    foreignColumns: [comments.id],
+    name: "comment_mentions_comment_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  commentMentionsUserIdFkey: foreignKey({
+    columns: [table.userId],
+    foreignColumns: [users.id],
+    name: "comment_mentions_user_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  commentMentionsCommentIdUserIdKey: unique("comment_mentions_comment_id_user_id_key").on(table.commentId, table.userId),
+}));
+
+export const issueTrackerIntegrations = pgTable("issue_tracker_integrations", {
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  provider: issueTrackerProvider().notNull(),
+  config: jsonb().default({}).notNull(),
+  tokenNonce: text("token_nonce").notNull(),
+  tokenValue: text("token_value").notNull(),
+},
+(table) => ({
+  issueTrackerIntegrationsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "issue_tracker_integrations_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  issueTrackerIntegrationsPkey: primaryKey({ columns: [table.projectId, table.provider], name: "issue_tracker_integrations_pkey"}),
+}));
+
+export const linkedIssues = pgTable("linked_issues", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  resourceType: linkedIssueResourceType("resource_type").notNull(),
+  resourceId: uuid("resource_id").notNull(),
+  provider: issueTrackerProvider().notNull(),
+  externalId: text("external_id").notNull(),
+  key: text().notNull(),
+  url: text().notNull(),
+  title: text().notNull(),
+  status: text(),
+  statusUpdatedAt: timestamp("status_updated_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  projectIdResourceIdx: index("linked_issues_project_id_resource_idx").using("btree", table.projectId.asc().nullsLast(), table.resourceType.asc().nullsLast(), table.resourceId.asc().nullsLast()),
+  linkedIssuesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "linked_issues_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const evalProposals = pgTable("eval_proposals", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  spanPath: text("span_path").notNull(),
+  status: evalProposalStatus().default('GENERATING').notNull(),
+  datapoints: jsonb().default([]).notNull(),
+  rubric: text(),
+  error: text(),
+  datasetId: uuid("dataset_id"),
+  labelClassId: uuid("label_class_id"),
+},
+(table) => ({
+  evalProposalsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "eval_proposals_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  evalProposalsDatasetIdFkey: foreignKey({
+    columns: [table.datasetId],
+    foreignColumns: [datasets.id],
+    name: "eval_proposals_dataset_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
+  evalProposalsLabelClassIdFkey: foreignKey({
+    columns: [table.labelClassId],
+    foreignColumns: [labelClasses.id],
+    name: "eval_proposals_label_class_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
+}));
+
+export const shadowDeployments = pgTable("shadow_deployments", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  name: text().notNull(),
+  spanPath: text("span_path").notNull(),
+  candidateModel: text("candidate_model").notNull(),
+  candidateSystemPrompt: text("candidate_system_prompt"),
+  samplePercentage: doublePrecision("sample_percentage").default(sql`'1'`).notNull(),
+  active: boolean().default(true).notNull(),
+},
+(table) => ({
+  projectIdSpanPathIdx: index("shadow_deployments_project_id_span_path_idx").using("btree", table.projectId.asc().nullsLast(), table.spanPath.asc().nullsLast()),
+  shadowDeploymentsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "shadow_deployments_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const shadowComparisons = pgTable("shadow_comparisons", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  deploymentId: uuid("deployment_id").notNull(),
+  spanId: uuid("span_id").notNull(),
+  traceId: uuid("trace_id").notNull(),
+  candidateOutput: text("candidate_output").notNull(),
+  verdict: shadowVerdict().notNull(),
+  reasoning: text().notNull(),
+},
+(table) => ({
+  deploymentIdCreatedAtIdx: index("shadow_comparisons_deployment_id_created_at_idx").using("btree", table.deploymentId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  shadowComparisonsDeploymentIdFkey: foreignKey({
+    columns: [table.deploymentId],
+    foreignColumns: [shadowDeployments.id],
+    name: "shadow_comparisons_deployment_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const promotedAttributes = pgTable("promoted_attributes", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  attributeKey: text("attribute_key").notNull(),
+  slot: integer().notNull(),
+  backfillStatus: backfillStatus("backfill_status").default('PENDING').notNull(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  backfilledSpans: bigint("backfilled_spans", { mode: "number" }).default(sql`'0'`).notNull(),
+},
+(table) => ({
+  promotedAttributesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "promoted_attributes_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  promotedAttributesProjectIdSlotKey: unique("promoted_attributes_project_id_slot_key").on(table.projectId, table.slot),
+  promotedAttributesProjectIdAttributeKeyKey: unique("promoted_attributes_project_id_attribute_key_key").on(table.projectId, table.attributeKey),
+}));
+
+export const fieldVisibilityRules = pgTable("field_visibility_rules", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  role: workspaceRole().notNull(),
+  field: sensitiveField().notNull(),
+  visible: boolean().notNull(),
+},
+(table) => ({
+  fieldVisibilityRulesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "field_visibility_rules_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  fieldVisibilityRulesProjectIdRoleFieldKey: unique("field_visibility_rules_project_id_role_field_key").on(table.projectId, table.role, table.field),
+}));
+
+export const maskingProfiles = pgTable("masking_profiles", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  role: workspaceRole().notNull(),
+  hashUserIds: boolean("hash_user_ids").default(false).notNull(),
+  redactEmails: boolean("redact_emails").default(false).notNull(),
+},
+(table) => ({
+  maskingProfilesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "masking_profiles_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  maskingProfilesProjectIdRoleKey: unique("masking_profiles_project_id_role_key").on(table.projectId, table.role),
+}));
+
+export const legalHolds = pgTable("legal_holds", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  reason: text().notNull(),
+  placedBy: uuid("placed_by").notNull(),
+  releasedAt: timestamp("released_at", { withTimezone: true, mode: 'string' }),
+  releasedBy: uuid("released_by"),
+},
+(table) => ({
+  activeProjectIdIdx: uniqueIndex("legal_holds_active_project_id_idx").using("btree", table.projectId.asc().nullsLast()).where(sql`(released_at IS NULL)`),
+  legalHoldsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "legal_holds_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const legalHoldAuditLog = pgTable("legal_hold_audit_log", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  userId: uuid("user_id"),
+  action: text().notNull(),
+  details: jsonb().default({}).notNull(),
+},
+(table) => ({
+  projectIdCreatedAtIdx: index("legal_hold_audit_log_project_id_created_at_idx").using("btree", table.projectId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+}));
+
+export const dataAccessLog = pgTable("data_access_log", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  userId: uuid("user_id").notNull(),
+  resourceType: accessedResourceType("resource_type").notNull(),
+  resourceId: uuid("resource_id").notNull(),
+},
+(table) => ({
+  projectIdCreatedAtIdx: index("data_access_log_project_id_created_at_idx").using("btree", table.projectId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+}));
+
+export const projectActivity = pgTable("project_activity", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  activityType: activityType("activity_type").notNull(),
+  actorId: uuid("actor_id"),
+  resourceId: uuid("resource_id"),
+  summary: text().notNull(),
+  details: jsonb().default({}).notNull(),
+},
+(table) => ({
+  projectIdCreatedAtIdx: index("project_activity_project_id_created_at_idx").using("btree", table.projectId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  projectActivityProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "project_activity_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const customMetrics = pgTable("custom_metrics", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  name: text().notNull(),
+  expression: text().notNull(),
+  description: text(),
+},
+(table) => ({
+  customMetricsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "custom_metrics_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  customMetricsProjectIdNameKey: unique("custom_metrics_project_id_name_key").on(table.projectId, table.name),
+}));
+
+export const pipelineDeployments = pgTable("pipeline_deployments", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  updatedAt: timestamp("updated_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  pipelineId: uuid("pipeline_id").notNull(),
+  target: text().notNull(),
+  pipelineVersionId: uuid("pipeline_version_id").notNull(),
+  previousPipelineVersionId: uuid("previous_pipeline_version_id"),
+  deployedBy: uuid("deployed_by"),
+},
+(table) => ({
+  pipelineDeploymentsPipelineIdFkey: foreignKey({
+    columns: [table.pipelineId],
+    foreignColumns: [pipelines.id],
+    name: "pipeline_deployments_pipeline_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  pipelineDeploymentsPipelineVersionIdFkey: foreignKey({
+    columns: [table.pipelineVersionId],
+    foreignColumns: [pipelineVersions.id],
+    name: "pipeline_deployments_pipeline_version_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  pipelineDeploymentsPreviousPipelineVersionIdFkey: foreignKey({
+    columns: [table.previousPipelineVersionId],
+    foreignColumns: [pipelineVersions.id],
+    name: "pipeline_deployments_previous_pipeline_version_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
+  pipelineDeploymentsPipelineIdTargetKey: unique("pipeline_deployments_pipeline_id_target_key").on(table.pipelineId, table.target),
+}));
+
+export const pipelineNodeUsage = pgTable("pipeline_node_usage", {
+  pipelineId: uuid("pipeline_id").notNull(),
+  monthStart: timestamp("month_start", { withTimezone: true, mode: 'string' }).notNull(),
+  nodeName: text("node_name").notNull(),
+  nodeType: text("node_type").notNull(),
+  executionCount: bigint("execution_count", { mode: "number" }).default(sql`'0'`).notNull(),
+  totalTokenCount: bigint("total_token_count", { mode: "number" }).default(sql`'0'`).notNull(),
+  approximateCost: doublePrecision("approximate_cost").default(sql`'0'`).notNull(),
+},
+(table) => ({
+  pipelineNodeUsagePipelineIdFkey: foreignKey({
+    columns: [table.pipelineId],
+    foreignColumns: [pipelines.id],
+    name: "pipeline_node_usage_pipeline_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  pipelineNodeUsagePkey: primaryKey({ columns: [table.pipelineId, table.monthStart, table.nodeName], name: "pipeline_node_usage_pkey"}),
+}));
+
+export const pipelineExecutionUsage = pgTable("pipeline_execution_usage", {
+  pipelineId: uuid("pipeline_id").notNull(),
+  monthStart: timestamp("month_start", { withTimezone: true, mode: 'string' }).notNull(),
+  executionCount: bigint("execution_count", { mode: "number" }).default(sql`'0'`).notNull(),
+},
+(table) => ({
+  pipelineExecutionUsagePipelineIdFkey: foreignKey({
+    columns: [table.pipelineId],
+    foreignColumns: [pipelines.id],
+    name: "pipeline_execution_usage_pipeline_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  pipelineExecutionUsagePkey: primaryKey({ columns: [table.pipelineId, table.monthStart], name: "pipeline_execution_usage_pkey"}),
+}));
+
+export const pipelineApprovalTasks = pgTable("pipeline_approval_tasks", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  nodeName: text("node_name").notNull(),
+  instructions: text().default('').notNull(),
+  payload: jsonb().notNull(),
+  status: approvalTaskStatus().default('PENDING').notNull(),
+  resolvedPayload: jsonb("resolved_payload"),
+  comment: text(),
+  resolvedBy: uuid("resolved_by"),
+  resolvedAt: timestamp("resolved_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  projectIdStatusIdx: index("pipeline_approval_tasks_project_id_status_idx").using("btree", table.projectId.asc().nullsLast(), table.status.asc().nullsLast()),
+  pipelineApprovalTasksProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "pipeline_approval_tasks_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const pipelineTriggers = pgTable("pipeline_triggers", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  pipelineId: uuid("pipeline_id").notNull(),
+  target: text().default('production').notNull(),
+  triggerType: pipelineTriggerType("trigger_type").notNull(),
+  cronSchedule: text("cron_schedule"),
+  datasetId: uuid("dataset_id"),
+  webhookToken: text("webhook_token"),
+  inputs: jsonb().default({}).notNull(),
+  enabled: boolean().default(true).notNull(),
+  nextRunAt: timestamp("next_run_at", { withTimezone: true, mode: 'string' }),
+  createdBy: uuid("created_by"),
+},
+(table) => ({
+  nextRunAtIdx: index("pipeline_triggers_next_run_at_idx").using("btree", table.nextRunAt.asc().nullsLast()),
+  pipelineTriggersProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "pipeline_triggers_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  pipelineTriggersPipelineIdFkey: foreignKey({
+    columns: [table.pipelineId],
+    foreignColumns: [pipelines.id],
+    name: "pipeline_triggers_pipeline_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  pipelineTriggersDatasetIdFkey: foreignKey({
+    columns: [table.datasetId],
+    foreignColumns: [datasets.id],
+    name: "pipeline_triggers_dataset_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  pipelineTriggersWebhookTokenKey: unique("pipeline_triggers_webhook_token_key").on(table.webhookToken),
+}));
+
+export const pipelineTriggerRuns = pgTable("pipeline_trigger_runs", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  triggerId: uuid("trigger_id").notNull(),
+  status: pipelineTriggerRunStatus().default('QUEUED').notNull(),
+  event: jsonb().default({}).notNull(),
+  outputs: jsonb(),
+  error: text(),
+  startedAt: timestamp("started_at", { withTimezone: true, mode: 'string' }),
+  finishedAt: timestamp("finished_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  triggerIdCreatedAtIdx: index("pipeline_trigger_runs_trigger_id_created_at_idx").using("btree", table.triggerId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  statusCreatedAtIdx: index("pipeline_trigger_runs_status_created_at_idx").using("btree", table.status.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  pipelineTriggerRunsTriggerIdFkey: foreignKey({
+    columns: [table.triggerId],
+    foreignColumns: [pipelineTriggers.id],
+    name: "pipeline_trigger_runs_trigger_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const machines = pgTable("machines", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  status: machineStatus().default('QUEUED').notNull(),
+  providerMachineId: text("provider_machine_id"),
+  url: text(),
+  error: text(),
+  startedAt: timestamp("started_at", { withTimezone: true, mode: 'string' }),
+  lastHealthCheckAt: timestamp("last_health_check_at", { withTimezone: true, mode: 'string' }),
+  terminatedAt: timestamp("terminated_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  statusCreatedAtIdx: index("machines_status_created_at_idx").using("btree", table.status.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  projectIdCreatedAtIdx: index("machines_project_id_created_at_idx").using("btree", table.projectId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  machinesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "machines_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const evaluationGroupBaselines = pgTable("evaluation_group_baselines", {
+  projectId: uuid("project_id").notNull(),
+  groupId: text("group_id").notNull(),
+  evaluationId: uuid("evaluation_id").notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+},
+(table) => ({
+  evaluationGroupBaselinesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "evaluation_group_baselines_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  evaluationGroupBaselinesEvaluationIdFkey: foreignKey({
+    columns: [table.evaluationId],
+    foreignColumns: [evaluations.id],
+    name: "evaluation_group_baselines_evaluation_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  evaluationGroupBaselinesPkey: primaryKey({ columns: [table.projectId, table.groupId], name: "evaluation_group_baselines_pkey"}),
+}));
+
+export const alertRules = pgTable("alert_rules", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  name: text().notNull(),
+  source: alertScoreSource().notNull(),
+  scoreName: text("score_name").notNull(),
+  groupId: text("group_id"),
+  comparison: alertComparison().notNull(),
+  threshold: doublePrecision().notNull(),
+  windowMinutes: integer("window_minutes").default(60).notNull(),
+  state: alertState().default('RESOLVED').notNull(),
+  lastValue: doublePrecision("last_value"),
+  lastEvaluatedAt: timestamp("last_evaluated_at", { withTimezone: true, mode: 'string' }),
+  stateChangedAt: timestamp("state_changed_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  projectIdSourceIdx: index("alert_rules_project_id_source_idx").using("btree", table.projectId.asc().nullsLast(), table.source.asc().nullsLast()),
+  sourceLastEvaluatedAtIdx: index("alert_rules_source_last_evaluated_at_idx").using("btree", table.source.asc().nullsLast(), table.lastEvaluatedAt.asc().nullsLast()),
+  alertRulesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "alert_rules_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const alertChannels = pgTable("alert_channels", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  ruleId: uuid("rule_id").notNull(),
+  channelType: alertChannelType("channel_type").notNull(),
+  targetPreview: text("target_preview").notNull(),
+  targetNonce: text("target_nonce").notNull(),
+  targetValue: text("target_value").notNull(),
+},
+(table) => ({
+  ruleIdIdx: index("alert_channels_rule_id_idx").using("btree", table.ruleId.asc().nullsLast()),
+  alertChannelsRuleIdFkey: foreignKey({
+    columns: [table.ruleId],
+    foreignColumns: [alertRules.id],
+    name: "alert_channels_rule_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const alertIncidents = pgTable("alert_incidents", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  ruleId: uuid("rule_id").notNull(),
+  value: doublePrecision().notNull(),
+  evaluationId: uuid("evaluation_id"),
+  windowStart: timestamp("window_start", { withTimezone: true, mode: 'string' }),
+  windowEnd: timestamp("window_end", { withTimezone: true, mode: 'string' }),
+  summary: jsonb(),
+  summaryClaimedAt: timestamp("summary_claimed_at", { withTimezone: true, mode: 'string' }),
+  summarizedAt: timestamp("summarized_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  ruleIdCreatedAtIdx: index("alert_incidents_rule_id_created_at_idx").using("btree", table.ruleId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  summarizedAtIdx: index("alert_incidents_summarized_at_idx").using("btree", table.summarizedAt.asc().nullsLast()),
+  alertIncidentsRuleIdFkey: foreignKey({
+    columns: [table.ruleId],
+    foreignColumns: [alertRules.id],
+    name: "alert_incidents_rule_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  alertIncidentsEvaluationIdFkey: foreignKey({
+    columns: [table.evaluationId],
+    foreignColumns: [evaluations.id],
+    name: "alert_incidents_evaluation_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
+}));
+
+export const agentCheckpoints = pgTable("agent_checkpoints", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  traceId: uuid("trace_id").notNull(),
+  spanId: uuid("span_id").notNull(),
+  spanPath: text("span_path"),
+  step: integer().notNull(),
+  state: jsonb().notNull(),
+  browserSessionId: text("browser_session_id"),
+  resumeCount: integer("resume_count").default(0).notNull(),
+  resumedAt: timestamp("resumed_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  projectIdTraceIdStepIdx: index("agent_checkpoints_project_id_trace_id_step_idx").using("btree", table.projectId.asc().nullsLast(), table.traceId.asc().nullsLast(), table.step.asc().nullsLast()),
+  agentCheckpointsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "agent_checkpoints_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const datasetVersions = pgTable("dataset_versions", {
+  datasetId: uuid("dataset_id").notNull(),
+  version: integer().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  action: text().notNull(),
+},
+(table) => ({
+  datasetVersionsDatasetIdFkey: foreignKey({
+    columns: [table.datasetId],
+    foreignColumns: [datasets.id],
+    name: "dataset_versions_dataset_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  datasetVersionsPkey: primaryKey({ columns: [table.datasetId, table.version], name: "dataset_versions_pkey"}),
+}));
+
+export const datasetDatapointRevisions = pgTable("dataset_datapoint_revisions", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  datasetId: uuid("dataset_id").notNull(),
+  datapointId: uuid("datapoint_id").notNull(),
+  datapointCreatedAt: timestamp("datapoint_created_at", { withTimezone: true, mode: 'string' }).notNull(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  indexInBatch: bigint("index_in_batch", { mode: "number" }),
+  data: jsonb().notNull(),
+  target: jsonb(),
+  metadata: jsonb(),
+  addedInVersion: integer("added_in_version").notNull(),
+  removedInVersion: integer("removed_in_version"),
+},
+(table) => ({
+  datasetIdAddedInVersionIdx: index("dataset_datapoint_revisions_dataset_id_added_in_version_idx").using("btree", table.datasetId.asc().nullsLast(), table.addedInVersion.asc().nullsLast()),
+  datapointIdIdx: index("dataset_datapoint_revisions_datapoint_id_idx").using("btree", table.datapointId.asc().nullsLast()),
+  datasetDatapointRevisionsDatasetIdFkey: foreignKey({
+    columns: [table.datasetId],
+    foreignColumns: [datasets.id],
+    name: "dataset_datapoint_revisions_dataset_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const datasetImports = pgTable("dataset_imports", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  datasetId: uuid("dataset_id").notNull(),
+  source: text().notNull(),
+  repoId: text("repo_id").notNull(),
+  config: text().notNull(),
+  split: text().notNull(),
+  mapping: jsonb().notNull(),
+  status: datasetImportStatus().default('PENDING').notNull(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  importedRows: bigint("imported_rows", { mode: "number" }).default(sql`'0'`).notNull(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  failedRows: bigint("failed_rows", { mode: "number" }).default(sql`'0'`).notNull(),
+  // You can use { mode: "bigint" } if numbers are exceeding js number limitations
+  totalRows: bigint("total_rows", { mode: "number" }),
+  error: text(),
+  updatedAt: timestamp("updated_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+},
+(table) => ({
+  datasetIdIdx: index("dataset_imports_dataset_id_idx").using("btree", table.datasetId.asc().nullsLast()),
+  datasetImportsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "dataset_imports_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  datasetImportsDatasetIdFkey: foreignKey({
+    columns: [table.datasetId],
+    foreignColumns: [datasets.id],
+    name: "dataset_imports_dataset_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const samplingExemptions = pgTable("sampling_exemptions", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  kind: samplingExemptionKind().notNull(),
+  value: text().notNull(),
+},
+(table) => ({
+  projectIdKindValueIdx: uniqueIndex("sampling_exemptions_project_id_kind_value_idx").using("btree", table.projectId.asc().nullsLast(), table.kind.asc().nullsLast(), table.value.asc().nullsLast()),
+  samplingExemptionsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "sampling_exemptions_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const lakeExports = pgTable("lake_exports", {
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").primaryKey().notNull(),
+  bucket: text().notNull(),
+  prefix: text().default('').notNull(),
+  region: text().notNull(),
+  accessKeyId: text("access_key_id").notNull(),
+  secretNonce: text("secret_nonce").notNull(),
+  secretValue: text("secret_value").notNull(),
+  enabled: boolean().default(true).notNull(),
+  exportedUntil: timestamp("exported_until", { withTimezone: true, mode: 'string' }).notNull(),
+  lastRunAt: timestamp("last_run_at", { withTimezone: true, mode: 'string' }),
+  lastError: text("last_error"),
+},
+(table) => ({
+  lakeExportsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "lake_exports_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+}));
+
+export const judgeEvaluators = pgTable("judge_evaluators", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  name: text().notNull(),
+  model: text().notNull(),
+  prompt: text().notNull(),
+  scoreType: evaluationScoreType("score_type").default('NUMERIC').notNull(),
+  labels: text().array().default(sql`'{}'`).notNull(),
+},
+(table) => ({
+  judgeEvaluatorsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "judge_evaluators_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  judgeEvaluatorsProjectIdNameKey: unique("judge_evaluators_project_id_name_key").on(table.projectId, table.name),
+}));
+
+export const warehouseSyncs = pgTable("warehouse_syncs", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  destination: warehouseDestination().notNull(),
+  mode: warehouseSyncMode().default('AGGREGATED').notNull(),
+  config: jsonb().notNull(),
+  credentialsNonce: text("credentials_nonce").notNull(),
+  credentialsValue: text("credentials_value").notNull(),
+  enabled: boolean().default(true).notNull(),
+  syncedUntil: timestamp("synced_until", { withTimezone: true, mode: 'string' }).notNull(),
+  lastRunAt: timestamp("last_run_at", { withTimezone: true, mode: 'string' }),
+  lastError: text("last_error"),
+},
+(table) => ({
+  warehouseSyncsProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "warehouse_syncs_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  warehouseSyncsProjectIdDestinationKey: unique("warehouse_syncs_project_id_destination_key").on(table.projectId, table.destination),
+}));
+
+export const onlineEvaluationRules = pgTable("online_evaluation_rules", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  name: text().notNull(),
+  spanPath: text("span_path"),
+  spanName: text("span_name"),
+  attributeFilters: jsonb("attribute_filters").default({}).notNull(),
+  samplePercentage: doublePrecision("sample_percentage").default(sql`'100'`).notNull(),
+  evaluator: jsonb().notNull(),
+  enabled: boolean().default(true).notNull(),
+},
+(table) => ({
+  onlineEvaluationRulesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "online_evaluation_rules_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  onlineEvaluationRulesProjectIdNameKey: unique("online_evaluation_rules_project_id_name_key").on(table.projectId, table.name),
+}));
+
+export const evaluationSchedules = pgTable("evaluation_schedules", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  projectId: uuid("project_id").notNull(),
+  name: text().notNull(),
+  cronSchedule: text("cron_schedule").notNull(),
+  datasetId: uuid("dataset_id").notNull(),
+  pipelineId: uuid("pipeline_id").notNull(),
+  target: text().default('production').notNull(),
+  pipelineVersionId: uuid("pipeline_version_id"),
+  judges: text().array().default(sql`'{}'`).notNull(),
+  groupId: text("group_id").notNull(),
+  enabled: boolean().default(true).notNull(),
+  nextRunAt: timestamp("next_run_at", { withTimezone: true, mode: 'string' }),
+  createdBy: uuid("created_by"),
+},
+(table) => ({
+  nextRunAtIdx: index("evaluation_schedules_next_run_at_idx").using("btree", table.nextRunAt.asc().nullsLast()),
+  evaluationSchedulesProjectIdFkey: foreignKey({
+    columns: [table.projectId],
+    foreignColumns: [projects.id],
+    name: "evaluation_schedules_project_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  evaluationSchedulesDatasetIdFkey: foreignKey({
+    columns: [table.datasetId],
+    foreignColumns: [datasets.id],
+    name: "evaluation_schedules_dataset_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  evaluationSchedulesPipelineIdFkey: foreignKey({
+    columns: [table.pipelineId],
+    foreignColumns: [pipelines.id],
+    name: "evaluation_schedules_pipeline_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  evaluationSchedulesPipelineVersionIdFkey: foreignKey({
+    columns: [table.pipelineVersionId],
+    foreignColumns: [pipelineVersions.id],
+    name: "evaluation_schedules_pipeline_version_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  evaluationSchedulesProjectIdNameKey: unique("evaluation_schedules_project_id_name_key").on(table.projectId, table.name),
+}));
+
+export const evaluationScheduleRuns = pgTable("evaluation_schedule_runs", {
+  id: uuid().defaultRandom().primaryKey().notNull(),
+  createdAt: timestamp("created_at", { withTimezone: true, mode: 'string' }).defaultNow().notNull(),
+  scheduleId: uuid("schedule_id").notNull(),
+  status: evaluationScheduleRunStatus().default('QUEUED').notNull(),
+  scheduledAt: timestamp("scheduled_at", { withTimezone: true, mode: 'string' }),
+  evaluationId: uuid("evaluation_id"),
+  error: text(),
+  startedAt: timestamp("started_at", { withTimezone: true, mode: 'string' }),
+  finishedAt: timestamp("finished_at", { withTimezone: true, mode: 'string' }),
+},
+(table) => ({
+  scheduleIdCreatedAtIdx: index("evaluation_schedule_runs_schedule_id_created_at_idx").using("btree", table.scheduleId.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  statusCreatedAtIdx: index("evaluation_schedule_runs_status_created_at_idx").using("btree", table.status.asc().nullsLast(), table.createdAt.asc().nullsLast()),
+  evaluationScheduleRunsScheduleIdFkey: foreignKey({
+    columns: [table.scheduleId],
+    foreignColumns: [evaluationSchedules.id],
+    name: "evaluation_schedule_runs_schedule_id_fkey"
+  }).onUpdate("cascade").onDelete("cascade"),
+  evaluationScheduleRunsEvaluationIdFkey: foreignKey({
+    columns: [table.evaluationId],
+    foreignColumns: [evaluations.id],
+    name: "evaluation_schedule_runs_evaluation_id_fkey"
+  }).onUpdate("cascade").onDelete("set null"),
+}));